@@ -28,7 +28,7 @@ use jni::sys::jint;
 use jni::sys::jlong;
 use jni::{objects::JObject, JNIEnv};
 use lance::dataset::transaction::Operation;
-use lance::dataset::{Dataset, WriteParams};
+use lance::dataset::{Dataset, WriteMode, WriteParams};
 use lance::table::format::Fragment;
 use std::iter::empty;
 use std::sync::Arc;
@@ -55,6 +55,15 @@ impl BlockingDataset {
         Ok(Self { inner })
     }
 
+    pub fn append(
+        &mut self,
+        reader: impl RecordBatchReader + Send + 'static,
+        params: Option<WriteParams>,
+    ) -> Result<()> {
+        RT.block_on(self.inner.append(reader, params))?;
+        Ok(())
+    }
+
     pub fn commit(uri: &str, operation: Operation, read_version: Option<u64>) -> Result<Self> {
         let inner = RT.block_on(Dataset::commit(uri, operation, read_version, None, None))?;
         Ok(Self { inner })
@@ -225,6 +234,51 @@ fn create_java_dataset_object<'a>(env: &mut JNIEnv<'a>) -> Result<JObject<'a>> {
     Ok(objet)
 }
 
+#[no_mangle]
+pub extern "system" fn Java_com_lancedb_lance_Dataset_nativeAppend(
+    mut env: JNIEnv,
+    jdataset: JObject,
+    arrow_array_stream_addr: jlong,
+    max_rows_per_file: JObject,  // Optional<Integer>
+    max_rows_per_group: JObject, // Optional<Integer>
+    max_bytes_per_file: JObject, // Optional<Long>
+) {
+    ok_or_throw_without_return!(
+        env,
+        inner_append(
+            &mut env,
+            jdataset,
+            arrow_array_stream_addr,
+            max_rows_per_file,
+            max_rows_per_group,
+            max_bytes_per_file,
+        )
+    )
+}
+
+fn inner_append(
+    env: &mut JNIEnv,
+    jdataset: JObject,
+    arrow_array_stream_addr: jlong,
+    max_rows_per_file: JObject,
+    max_rows_per_group: JObject,
+    max_bytes_per_file: JObject,
+) -> Result<()> {
+    let stream_ptr = arrow_array_stream_addr as *mut FFI_ArrowArrayStream;
+    let reader = unsafe { ArrowArrayStreamReader::from_raw(stream_ptr) }?;
+    let mut write_params = extract_write_params(
+        env,
+        &max_rows_per_file,
+        &max_rows_per_group,
+        &max_bytes_per_file,
+        &JObject::null(),
+    )?;
+    write_params.mode = WriteMode::Append;
+    let mut dataset =
+        unsafe { env.get_rust_field::<_, _, BlockingDataset>(jdataset, NATIVE_DATASET) }?;
+    dataset.append(reader, Some(write_params))
+}
+
 #[no_mangle]
 pub extern "system" fn Java_com_lancedb_lance_Dataset_commitAppend<'local>(
     mut env: JNIEnv<'local>,
@@ -20,7 +20,7 @@ use arrow::{ffi::FFI_ArrowSchema, ffi_stream::FFI_ArrowArrayStream};
 use arrow_schema::SchemaRef;
 use jni::{objects::JObject, sys::jlong, JNIEnv};
 use lance::dataset::scanner::{DatasetRecordBatchStream, Scanner};
-use lance_io::ffi::to_ffi_arrow_array_stream;
+use lance_io::ffi::{BlockingRecordBatchReader, DEFAULT_READ_AHEAD};
 
 use crate::{
     blocking_dataset::{BlockingDataset, NATIVE_DATASET},
@@ -192,7 +192,13 @@ fn inner_open_stream(env: &mut JNIEnv, j_scanner: JObject, stream_addr: jlong) -
             unsafe { env.get_rust_field::<_, _, BlockingScanner>(j_scanner, NATIVE_SCANNER) }?;
         scanner_guard.open_stream()?
     };
-    let ffi_stream = to_ffi_arrow_array_stream(record_batch_stream, RT.handle().clone())?;
+    // Read ahead a few batches while Java processes the current one.
+    let reader = BlockingRecordBatchReader::with_runtime(
+        record_batch_stream,
+        RT.handle().clone(),
+        DEFAULT_READ_AHEAD,
+    );
+    let ffi_stream = FFI_ArrowArrayStream::new(Box::new(reader));
     unsafe { std::ptr::write_unaligned(stream_addr as *mut FFI_ArrowArrayStream, ffi_stream) }
     Ok(())
 }
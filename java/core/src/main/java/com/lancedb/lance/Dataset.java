@@ -157,6 +157,27 @@ public class Dataset implements Closeable {
   public static native Dataset commitAppend(String path, Optional<Long> readVersion,
       List<String> fragmentsMetadata);
 
+  /**
+   * Append the batches of a stream to this dataset. On success, this dataset
+   * is checked out at the new version.
+   *
+   * @param stream arrow stream, consumed by this call
+   * @param params write parameters; the write mode is ignored
+   */
+  public void append(ArrowArrayStream stream, WriteParams params) {
+    Preconditions.checkNotNull(stream);
+    Preconditions.checkNotNull(params);
+    try (LockManager.WriteLock writeLock = lockManager.acquireWriteLock()) {
+      Preconditions.checkArgument(nativeDatasetHandle != 0, "Dataset is closed");
+      nativeAppend(stream.memoryAddress(), params.getMaxRowsPerFile(),
+          params.getMaxRowsPerGroup(), params.getMaxBytesPerFile());
+    }
+  }
+
+  private native void nativeAppend(long arrowStreamMemoryAddress,
+      Optional<Integer> maxRowsPerFile, Optional<Integer> maxRowsPerGroup,
+      Optional<Long> maxBytesPerFile);
+
   /**
    * Create a new Dataset Scanner.
    *
@@ -51,6 +51,22 @@ public class DatasetTest {
     }
   }
 
+  @Test
+  void testAppendStream() throws IOException, URISyntaxException {
+    String datasetPath = tempDir.resolve("append_stream").toString();
+    try (BufferAllocator allocator = new RootAllocator()) {
+      TestUtils.RandomAccessDataset testDataset = new TestUtils.RandomAccessDataset(allocator, datasetPath);
+      testDataset.createDatasetAndValidate();
+      try (Dataset dataset = Dataset.open(datasetPath, allocator)) {
+        testDataset.appendTo(dataset);
+        assertEquals(2, dataset.version());
+        assertEquals(2, dataset.latestVersion());
+        assertEquals(18, dataset.countRows());
+        assertEquals(2, dataset.getFragments().size());
+      }
+    }
+  }
+
   @Test
   void testCreateEmptyDataset() {
     String datasetPath = tempDir.resolve("new_empty_dataset").toString();
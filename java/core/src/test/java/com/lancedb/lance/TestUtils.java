@@ -157,6 +157,20 @@ public class TestUtils {
       }
     }
 
+    public void appendTo(Dataset dataset) throws IOException, URISyntaxException {
+      Path path = Paths.get(DatasetTest.class.getResource(DATA_FILE).toURI());
+      try (BufferAllocator allocator = new RootAllocator();
+           ArrowFileReader reader =
+               new ArrowFileReader(
+                   new SeekableReadChannel(
+                       new ByteArrayReadableSeekableByteChannel(Files.readAllBytes(path))),
+                   allocator);
+           ArrowArrayStream arrowStream = ArrowArrayStream.allocateNew(allocator)) {
+        Data.exportArrayStream(allocator, reader, arrowStream);
+        dataset.append(arrowStream, new WriteParams.Builder().build());
+      }
+    }
+
     public void openDatasetAndValidate() throws IOException {
       try (Dataset dataset = Dataset.open(datasetPath, allocator)) {
         assertEquals(1, dataset.version());
@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+import { test } from "node:test";
+import assert from "node:assert/strict";
+import { mkdtempSync } from "node:fs";
+import { tmpdir } from "node:os";
+import { join } from "node:path";
+
+import {
+  FixedSizeList,
+  Field,
+  Float32,
+  makeData,
+  makeVector,
+  tableFromArrays,
+  tableFromIPC,
+  tableToIPC,
+} from "apache-arrow";
+
+import lance from "../index.js";
+const { Dataset } = lance;
+
+function testData(start, count) {
+  const ids = Int32Array.from({ length: count }, (_, i) => start + i);
+  return tableFromArrays({ id: ids });
+}
+
+test("write, append and scan", async () => {
+  const uri = join(mkdtempSync(join(tmpdir(), "lance-")), "test.lance");
+  const data = tableToIPC(testData(0, 10), "stream");
+  const dataset = await Dataset.write(uri, data);
+  assert.equal(dataset.version, 1);
+
+  await dataset.append(tableToIPC(testData(10, 10), "stream"));
+  assert.equal(dataset.version, 2);
+  assert.equal(await dataset.countRows(), 20);
+  assert.equal(await dataset.countRows("id >= 15"), 5);
+
+  const reopened = await Dataset.open(uri);
+  const table = tableFromIPC(
+    await reopened.scan({ columns: ["id"], filter: "id < 3" }),
+  );
+  assert.deepEqual(Array.from(table.getChild("id")), [0, 1, 2]);
+  assert.equal(tableFromIPC(reopened.schema()).schema.fields[0].name, "id");
+});
+
+test("vector search", async () => {
+  const uri = join(mkdtempSync(join(tmpdir(), "lance-")), "vectors.lance");
+  const type = new FixedSizeList(2, new Field("item", new Float32(), true));
+  const values = makeData({
+    type: new Float32(),
+    data: Float32Array.from([0, 0, 1, 1, 2, 2, 3, 3]),
+  });
+  const vectors = makeVector(
+    makeData({ type, length: 4, nullCount: 0, child: values }),
+  );
+  const table = tableFromArrays({ id: Int32Array.from([0, 1, 2, 3]) });
+  const data = tableToIPC(table.setChild("vector", vectors), "stream");
+  const dataset = await Dataset.write(uri, data);
+
+  const results = tableFromIPC(
+    await dataset.vectorSearch({ column: "vector", vector: [2.1, 2.1], k: 2 }),
+  );
+  assert.deepEqual(Array.from(results.getChild("id")), [2, 3]);
+  assert.ok(results.getChild("_distance"));
+});
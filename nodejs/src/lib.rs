@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Node.js bindings for Lance.
+//!
+//! Data crosses the boundary in the Arrow IPC stream format, which Arrow JS
+//! reads with `tableFromIPC` and writes with `tableToIPC(table, "stream")`.
+
+use std::io::{BufReader, Cursor};
+use std::sync::Mutex;
+
+use arrow_array::{Float32Array, RecordBatch};
+use arrow_ipc::reader::StreamReader;
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::SchemaRef;
+use futures::TryStreamExt;
+use lance::dataset::scanner::Scanner;
+use lance::dataset::{WriteMode, WriteParams};
+use lance::io::RecordBatchStream;
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+
+trait IntoNapiResult<T> {
+    fn napi(self) -> napi::Result<T>;
+}
+
+impl<T, E: std::fmt::Display> IntoNapiResult<T> for std::result::Result<T, E> {
+    fn napi(self) -> napi::Result<T> {
+        self.map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+}
+
+fn to_ipc(schema: SchemaRef, batches: &[RecordBatch]) -> napi::Result<Buffer> {
+    let mut writer = StreamWriter::try_new(Vec::new(), &schema).napi()?;
+    for batch in batches {
+        writer.write(batch).napi()?;
+    }
+    Ok(writer.into_inner().napi()?.into())
+}
+
+fn from_ipc(data: Buffer) -> napi::Result<StreamReader<BufReader<Cursor<Vec<u8>>>>> {
+    StreamReader::try_new(Cursor::new(data.to_vec()), None).napi()
+}
+
+fn write_params(mode: Option<String>) -> napi::Result<WriteParams> {
+    let mut params = WriteParams::default();
+    if let Some(mode) = mode {
+        params.mode = WriteMode::try_from(mode.as_str()).napi()?;
+    }
+    Ok(params)
+}
+
+async fn collect_ipc(scanner: &Scanner) -> napi::Result<Buffer> {
+    let stream = scanner.try_into_stream().await.napi()?;
+    let schema = stream.schema();
+    let batches: Vec<RecordBatch> = stream.try_collect().await.napi()?;
+    to_ipc(schema, &batches)
+}
+
+/// Options of [`Dataset::scan`].
+#[napi(object)]
+pub struct ScanOptions {
+    /// Columns to read. All columns by default.
+    pub columns: Option<Vec<String>>,
+    /// SQL filter expression.
+    pub filter: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// A nearest neighbor query for [`Dataset::vector_search`].
+#[napi(object)]
+pub struct VectorQuery {
+    /// Name of the vector column.
+    pub column: String,
+    pub vector: Vec<f64>,
+    /// Number of results.
+    pub k: u32,
+    /// Number of IVF partitions to search, when the column is indexed.
+    pub nprobes: Option<u32>,
+    /// Re-rank `k * refine_factor` candidates with exact distances.
+    pub refine_factor: Option<u32>,
+    /// SQL filter expression applied to the results.
+    pub filter: Option<String>,
+    /// Columns to return, in addition to `_distance`. All columns by default.
+    pub columns: Option<Vec<String>>,
+}
+
+/// A Lance dataset.
+#[napi]
+pub struct Dataset {
+    inner: Mutex<lance::Dataset>,
+}
+
+impl Dataset {
+    fn dataset(&self) -> lance::Dataset {
+        self.inner.lock().unwrap().clone()
+    }
+}
+
+#[napi]
+impl Dataset {
+    /// Open the latest version of the dataset at `uri`.
+    #[napi(factory)]
+    pub async fn open(uri: String) -> napi::Result<Self> {
+        let dataset = lance::Dataset::open(&uri).await.napi()?;
+        Ok(Self {
+            inner: Mutex::new(dataset),
+        })
+    }
+
+    /// Write Arrow IPC stream `data` to `uri`.
+    ///
+    /// `mode` is `"create"` (the default), `"append"` or `"overwrite"`.
+    #[napi(factory)]
+    pub async fn write(uri: String, data: Buffer, mode: Option<String>) -> napi::Result<Self> {
+        let reader = from_ipc(data)?;
+        let params = write_params(mode)?;
+        let dataset = lance::Dataset::write(reader, &uri, Some(params))
+            .await
+            .napi()?;
+        Ok(Self {
+            inner: Mutex::new(dataset),
+        })
+    }
+
+    /// The checked out version.
+    #[napi(getter)]
+    pub fn version(&self) -> i64 {
+        self.dataset().version().version as i64
+    }
+
+    /// The schema of the dataset, as an empty Arrow IPC stream.
+    #[napi]
+    pub fn schema(&self) -> napi::Result<Buffer> {
+        let schema = arrow_schema::Schema::from(self.dataset().schema());
+        to_ipc(schema.into(), &[])
+    }
+
+    /// Count the rows matching `filter`, or all rows.
+    #[napi]
+    pub async fn count_rows(&self, filter: Option<String>) -> napi::Result<i64> {
+        let count = self.dataset().count_rows(filter).await.napi()?;
+        Ok(count as i64)
+    }
+
+    /// Read the dataset into an Arrow IPC stream.
+    #[napi]
+    pub async fn scan(&self, options: Option<ScanOptions>) -> napi::Result<Buffer> {
+        let dataset = self.dataset();
+        let mut scanner = dataset.scan();
+        if let Some(options) = options {
+            if let Some(columns) = &options.columns {
+                scanner.project(columns).napi()?;
+            }
+            if let Some(filter) = &options.filter {
+                scanner.filter(filter).napi()?;
+            }
+            if options.limit.is_some() || options.offset.is_some() {
+                scanner.limit(options.limit, options.offset).napi()?;
+            }
+        }
+        collect_ipc(&scanner).await
+    }
+
+    /// Find the nearest neighbors of a vector, as an Arrow IPC stream with a
+    /// `_distance` column.
+    #[napi]
+    pub async fn vector_search(&self, query: VectorQuery) -> napi::Result<Buffer> {
+        let dataset = self.dataset();
+        let mut scanner = dataset.scan();
+        if let Some(columns) = &query.columns {
+            scanner.project(columns).napi()?;
+        }
+        if let Some(filter) = &query.filter {
+            scanner.filter(filter).napi()?;
+        }
+        let vector = Float32Array::from_iter_values(query.vector.iter().map(|v| *v as f32));
+        scanner
+            .nearest(&query.column, &vector, query.k as usize)
+            .napi()?;
+        if let Some(nprobes) = query.nprobes {
+            scanner.nprobs(nprobes as usize);
+        }
+        if let Some(refine_factor) = query.refine_factor {
+            scanner.refine(refine_factor);
+        }
+        collect_ipc(&scanner).await
+    }
+
+    /// Append Arrow IPC stream `data` and check out the new version.
+    #[napi]
+    pub async fn append(&self, data: Buffer) -> napi::Result<()> {
+        let reader = from_ipc(data)?;
+        let mut dataset = self.dataset();
+        dataset.append(reader, None).await.napi()?;
+        *self.inner.lock().unwrap() = dataset;
+        Ok(())
+    }
+}
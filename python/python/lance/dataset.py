@@ -260,8 +260,12 @@ class LanceDataset(pa.dataset.Dataset):
                     "q": <query vector as pa.Float32Array>,
                     "k": 10,
                     "nprobes": 1,
-                    "refine_factor": 1
+                    "refine_factor": 1,
+                    "ef": 64
                 }
+
+            Set ``"target_recall"`` (e.g. 0.95) instead of ``"nprobes"`` and
+            ``"refine_factor"`` to let Lance tune them on the first search.
         batch_size: int, default None
             The max size of batches returned.
         batch_readahead: int, optional
@@ -383,9 +387,13 @@ class LanceDataset(pa.dataset.Dataset):
                     "k": 10,
                     "metric": "cosine",
                     "nprobes": 1,
-                    "refine_factor": 1
+                    "refine_factor": 1,
+                    "ef": 64
                 }
 
+            Set ``"target_recall"`` (e.g. 0.95) instead of ``"nprobes"`` and
+            ``"refine_factor"`` to let Lance tune them on the first search.
+
         batch_size: int, optional
             The number of rows to read at a time.
         batch_readahead: int, optional
@@ -640,7 +648,7 @@ class LanceDataset(pa.dataset.Dataset):
         raise NotImplementedError("Versioning not yet supported in Rust")
 
     def alter_columns(self, *alterations: Iterable[Dict[str, Any]]):
-        """Alter column name, data type, and nullability.
+        """Alter column name, data type, nullability, and metadata.
 
         Columns that are renamed can keep any indices that are on them. If a
         column has an IVF_PQ index, it can be kept if the column is casted to
@@ -673,6 +681,14 @@ class LanceDataset(pa.dataset.Dataset):
             - "data_type": pyarrow.DataType, optional
                 The new data type to cast the column to. If not specified, the column
                 data type is not changed.
+            - "metadata": Dict[str, str], optional
+                Metadata entries to set on the column. For example, the
+                ``"lance:null_vectors"`` entry of a vector column sets how vector
+                indices and searches treat null vectors: ``"skip"`` leaves them
+                out, ``"error"`` fails to build an index over them, and
+                ``"null_partition"`` returns them last, with a null distance. It
+                defaults to the ``lance.index.null_vectors`` setting of the
+                dataset config, or ``"skip"``.
 
         Examples
         --------
@@ -902,6 +918,35 @@ class LanceDataset(pa.dataset.Dataset):
             predicate = str(predicate)
         self._ds.delete(predicate)
 
+    def delete_rows(self, row_ids: Iterable[int]):
+        """
+        Delete rows by their row ids.
+
+        Unlike :meth:`delete`, no predicate is evaluated, so this is much
+        cheaper when the rows to delete are already known, for example from a
+        deduplication job. Ids in fragments that are not in the dataset are
+        skipped, and an id past the end of its fragment raises a ``ValueError``.
+
+        Parameters
+        ----------
+        row_ids : Iterable[int]
+            The ``_rowid`` values of the rows to delete.
+
+        Examples
+        --------
+        >>> import lance
+        >>> import pyarrow as pa
+        >>> table = pa.table({"a": [1, 2, 3]})
+        >>> dataset = lance.write_dataset(table, "example", mode="overwrite")
+        >>> dataset.delete_rows([0, 2])
+        >>> dataset.to_table()
+        pyarrow.Table
+        a: int64
+        ----
+        a: [[2]]
+        """
+        self._ds.delete_rows(list(row_ids))
+
     def merge_insert(
         self,
         on: Union[str, Iterable[str]],
@@ -1067,7 +1112,8 @@ class LanceDataset(pa.dataset.Dataset):
 
         older_than: timedelta, optional
             Only versions older than this will be removed.  If not specified, this
-            will default to two weeks.
+            will default to the ``lance.cleanup.retention_seconds`` setting of the
+            dataset config, which is two weeks unless it is set.
 
         delete_unverified: bool, default False
             Files leftover from a failed transaction may appear to be part of an
@@ -1079,11 +1125,9 @@ class LanceDataset(pa.dataset.Dataset):
             is currently working on this dataset.  Otherwise the dataset could be put
             into a corrupted state.
         """
-        if older_than is None:
-            older_than = timedelta(days=14)
-        return self._ds.cleanup_old_versions(
-            td_to_micros(older_than), delete_unverified
-        )
+        if older_than is not None:
+            older_than = td_to_micros(older_than)
+        return self._ds.cleanup_old_versions(older_than, delete_unverified)
 
     def create_scalar_index(
         self,
@@ -2087,6 +2131,8 @@ class ScannerBuilder:
         nprobes: Optional[int] = None,
         refine_factor: Optional[int] = None,
         use_index: bool = True,
+        ef: Optional[int] = None,
+        target_recall: Optional[float] = None,
     ) -> ScannerBuilder:
         q = _coerce_query_vector(q)
 
@@ -2113,6 +2159,10 @@ class ScannerBuilder:
             raise ValueError(f"Nprobes must be > 0 but got {nprobes}")
         if refine_factor is not None and int(refine_factor) < 1:
             raise ValueError(f"Refine factor must be 1 or more got {refine_factor}")
+        if ef is not None and int(ef) <= 0:
+            raise ValueError(f"Ef must be > 0 but got {ef}")
+        if target_recall is not None and not 0 < float(target_recall) <= 1:
+            raise ValueError(f"Target recall must be in (0, 1] but got {target_recall}")
         self._nearest = {
             "column": column,
             "q": q,
@@ -2121,6 +2171,10 @@ class ScannerBuilder:
             "nprobes": nprobes,
             "refine_factor": refine_factor,
             "use_index": use_index,
+            "ef": ef,
+            "target_recall": (
+                float(target_recall) if target_recall is not None else None
+            ),
         }
         return self
 
@@ -2256,6 +2310,20 @@ class LanceScanner(pa.dataset.Scanner):
 
         return self._scanner.explain_plan(verbose=verbose)
 
+    def explain_search(self) -> str:
+        """Run the vector search of this scanner and describe what it did.
+
+        The output lists the IVF partitions probed in each index segment, the
+        number of candidates examined and re-ranked, and how many partitions
+        were served from the index cache. The search results are discarded.
+
+        Returns
+        -------
+        explain : str
+        """
+
+        return self._scanner.explain_search()
+
 
 class DatasetOptimizer:
     def __init__(self, dataset: LanceDataset):
@@ -2264,10 +2332,10 @@ class DatasetOptimizer:
     def compact_files(
         self,
         *,
-        target_rows_per_fragment: int = 1024 * 1024,
-        max_rows_per_group: int = 1024,
+        target_rows_per_fragment: Optional[int] = None,
+        max_rows_per_group: Optional[int] = None,
         materialize_deletions: bool = True,
-        materialize_deletions_threshold: float = 0.1,
+        materialize_deletions_threshold: Optional[float] = None,
         num_threads: Optional[int] = None,
     ) -> CompactionMetrics:
         """Compacts small files in the dataset, reducing total number of files.
@@ -2284,20 +2352,26 @@ class DatasetOptimizer:
         not be compacted because the fragments it is adjacent to do not need
         compaction.
 
+        The options that are not specified default to the ``lance.compaction.*``
+        settings of the dataset config.
+
         Parameters
         ----------
-        target_rows_per_fragment: int, default 1024*1024
+        target_rows_per_fragment: int, optional
             The target number of rows per fragment. This is the number of rows
-            that will be in each fragment after compaction.
-        max_rows_per_group: int, default 1024
+            that will be in each fragment after compaction. Defaults to
+            1024*1024 unless the dataset config sets it.
+        max_rows_per_group: int, optional
             Max number of rows per group. This does not affect which fragments
             need compaction, but does affect how they are re-written if selected.
+            Defaults to 1024 unless the dataset config sets it.
         materialize_deletions: bool, default True
             Whether to compact fragments with soft deleted rows so they are no
             longer present in the file.
-        materialize_deletions_threshold: float, default 0.1
+        materialize_deletions_threshold: float, optional
             The fraction of original rows that are soft deleted in a fragment
-            before the fragment is a candidate for compaction.
+            before the fragment is a candidate for compaction. Defaults to 0.1
+            unless the dataset config sets it.
         num_threads: int, optional
             The number of threads to use when performing compaction. If not
             specified, defaults to the number of cores on the machine.
@@ -2386,6 +2460,7 @@ def write_dataset(
     progress: Optional[FragmentWriteProgress] = None,
     storage_options: Optional[Dict[str, str]] = None,
     use_legacy_format: bool = True,
+    conform_vectors: Optional[str] = None,
 ) -> LanceDataset:
     """Write a given data_obj to the given uri
 
@@ -2428,6 +2503,14 @@ def write_dataset(
     use_legacy_format : optional, bool, default True
         Use the Lance v1 writer to write Lance v1 files.  The default is currently
         True but will change as we roll out the v2 format.
+    conform_vectors : optional, str
+        If set, the vector (fixed size list) columns of each batch are checked
+        before they are written, and lists of another value type or dimension
+        are cast to the vector type of the column. Vectors of another dimension
+        are handled according to the policy: **error** raises an error naming
+        the batch, the column and the row, **pad** pads shorter vectors with
+        zeros, **truncate** truncates longer vectors, and **pad_or_truncate**
+        does both. When appending, vectors are conformed to the dataset schema.
     """
     if _check_for_hugging_face(data_obj):
         # Huggingface datasets
@@ -2450,6 +2533,7 @@ def write_dataset(
         "progress": progress,
         "storage_options": storage_options,
         "use_legacy_format": use_legacy_format,
+        "conform_vectors": conform_vectors,
     }
 
     if commit_lock:
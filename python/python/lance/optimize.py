@@ -14,7 +14,11 @@ from .lance import RewriteResult as RewriteResult
 
 
 class CompactionOptions(TypedDict):
-    """Options for compaction."""
+    """Options for compaction.
+
+    The options that are not given, or are None, default to the
+    ``lance.compaction.*`` settings of the dataset config.
+    """
 
     target_rows_per_fragment: Optional[int]
     """
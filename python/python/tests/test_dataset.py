@@ -116,6 +116,25 @@ def test_dataset_append(tmp_path: Path):
         lance.write_dataset(table2, base_dir, mode="append")
 
 
+def test_dataset_append_conform_vectors(tmp_path: Path):
+    vectors = pa.FixedSizeListArray.from_arrays(pa.array(range(8), pa.float32()), 4)
+    dataset = lance.write_dataset(pa.table({"vec": vectors}), tmp_path)
+
+    lists = pa.table({"vec": pa.array([[1.0, 2.0, 3.0], [1.0, 2.0, 3.0, 4.0, 5.0]])})
+    with pytest.raises(OSError, match="vector 0 of column vec has dimension 3"):
+        lance.write_dataset(lists, tmp_path, mode="append", conform_vectors="error")
+    with pytest.raises(ValueError, match="Invalid vector dimension policy"):
+        lance.write_dataset(lists, tmp_path, mode="append", conform_vectors="clip")
+
+    dataset = lance.write_dataset(
+        lists, tmp_path, mode="append", conform_vectors="pad_or_truncate"
+    )
+    assert dataset.schema == pa.schema({"vec": pa.list_(pa.float32(), 4)})
+    assert dataset.to_table()["vec"].to_pylist()[2:] == [
+        [1.0, 2.0, 3.0, 0.0],
+        [1.0, 2.0, 3.0, 4.0],
+    ]
+
 def test_dataset_from_record_batch_iterable(tmp_path: Path):
     base_dir = tmp_path / "test"
 
@@ -875,6 +894,20 @@ def test_merge_from_dataset(tmp_path: Path):
     )
 
 
+def test_delete_rows(tmp_path: Path):
+    tab = pa.table({"a": range(100)})
+    dataset = lance.write_dataset(tab, tmp_path / "dataset", max_rows_per_file=50)
+
+    row_ids = dataset.to_table(columns=[], with_row_id=True, filter="a % 10 = 0")
+    dataset.delete_rows(row_ids["_rowid"].to_pylist())
+    assert dataset.version == 2
+    assert dataset.to_table()["a"].to_pylist() == [a for a in range(100) if a % 10]
+
+    # Row 50 of the first fragment, which only has 50 rows
+    with pytest.raises(ValueError):
+        dataset.delete_rows([50])
+
+
 def test_delete_data(tmp_path: Path):
     # We pass schema explicitly since we want b to be non-nullable.
     schema = pa.schema(
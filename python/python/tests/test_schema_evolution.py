@@ -213,6 +213,9 @@ def test_alter_columns(tmp_path: Path):
     with pytest.raises(Exception, match="Can't cast value 1024 to type Int8"):
         dataset.alter_columns({"path": "x", "data_type": pa.int8()})
 
+    dataset.alter_columns({"path": "x", "metadata": {"key": "value"}})
+    assert dataset.schema.field("x").metadata == {b"key": b"value"}
+
     with pytest.raises(Exception, match='Cannot cast column "x" from Int32 to Utf8'):
         dataset.alter_columns({"path": "x", "data_type": pa.string()})
 
@@ -224,6 +227,6 @@ def test_alter_columns(tmp_path: Path):
 
     with pytest.raises(
         ValueError,
-        match="At least one of name, nullable, or data_type must be specified",
+        match="At least one of name, nullable, data_type or metadata must be specified",
     ):
         dataset.alter_columns({"path": "x"})
@@ -33,8 +33,8 @@ use lance::dataset::{
     fragment::FileFragment as LanceFileFragment, progress::WriteFragmentProgress,
     scanner::Scanner as LanceScanner, transaction::Operation as LanceOperation,
     Dataset as LanceDataset, MergeInsertBuilder as LanceMergeInsertBuilder, ReadParams,
-    UpdateBuilder, Version, WhenMatched, WhenNotMatched, WhenNotMatchedBySource, WriteMode,
-    WriteParams,
+    UpdateBuilder, VectorDimensionPolicy, Version, WhenMatched, WhenNotMatched,
+    WhenNotMatchedBySource, WriteMode, WriteParams,
 };
 use lance::dataset::{BatchInfo, BatchUDF, NewColumnTransform, UDFCheckpointStore};
 use lance::index::{scalar::ScalarIndexParams, vector::VectorIndexParams};
@@ -58,11 +58,12 @@ use pyo3::types::{PyList, PySet, PyString};
 use pyo3::{
     exceptions::{PyIOError, PyKeyError, PyValueError},
     pyclass,
-    types::{IntoPyDict, PyBool, PyDict, PyInt, PyLong},
+    types::{IntoPyDict, PyBool, PyDict, PyFloat, PyInt, PyLong},
     PyObject, PyResult,
 };
 use snafu::{location, Location};
 
+use crate::error::PythonErrorExt;
 use crate::fragment::{FileFragment, FragmentMetadata};
 use crate::schema::LanceSchema;
 use crate::session::Session;
@@ -556,6 +557,29 @@ impl Dataset {
                 None
             };
 
+            // `ef` is the number of candidates kept when searching a graph index.
+            let ef: Option<usize> = if let Some(ef) = nearest.get_item("ef")? {
+                if ef.is_none() {
+                    None
+                } else {
+                    PyAny::downcast::<PyLong>(ef)?.extract()?
+                }
+            } else {
+                None
+            };
+
+            // With a target recall, `nprobes` and `refine_factor` are tuned by the engine.
+            let target_recall: Option<f32> =
+                if let Some(recall) = nearest.get_item("target_recall")? {
+                    if recall.is_none() {
+                        None
+                    } else {
+                        PyAny::downcast::<PyFloat>(recall)?.extract()?
+                    }
+                } else {
+                    None
+                };
+
             let use_index: bool = if let Some(idx) = nearest.get_item("use_index")? {
                 PyAny::downcast::<PyBool>(idx)?.extract()?
             } else {
@@ -569,6 +593,9 @@ impl Dataset {
                     if let Some(factor) = refine_factor {
                         s = s.refine(factor);
                     }
+                    if let Some(ef) = ef {
+                        s = s.ef(ef);
+                    }
                     if let Some(m) = metric_type {
                         s = s.distance_metric(m);
                     }
@@ -576,6 +603,11 @@ impl Dataset {
                     s
                 })
                 .map_err(|err| PyValueError::new_err(err.to_string()))?;
+            if let Some(recall) = target_recall {
+                scanner
+                    .target_recall(recall)
+                    .map_err(|err| PyValueError::new_err(err.to_string()))?;
+            }
         }
 
         let scan = Arc::new(scanner);
@@ -693,20 +725,28 @@ impl Dataset {
                     .get_item("data_type")?
                     .map(|n| n.extract())
                     .transpose()?;
+                let metadata: Option<HashMap<String, String>> =
+                    obj.get_item("metadata")?.map(|n| n.extract()).transpose()?;
 
                 for key in obj.keys().iter().map(|k| k.extract::<String>()) {
                     let k = key?;
-                    if k != "path" && k != "name" && k != "nullable" && k != "data_type" {
+                    if k != "path"
+                        && k != "name"
+                        && k != "nullable"
+                        && k != "data_type"
+                        && k != "metadata"
+                    {
                         return Err(PyValueError::new_err(format!(
-                            "Unknown key: {}. Valid keys are name, nullable, and data_type.",
+                            "Unknown key: {}. Valid keys are name, nullable, data_type and metadata.",
                             k
                         )));
                     }
                 }
 
-                if name.is_none() && nullable.is_none() && data_type.is_none() {
+                if name.is_none() && nullable.is_none() && data_type.is_none() && metadata.is_none()
+                {
                     return Err(PyValueError::new_err(
-                        "At least one of name, nullable, or data_type must be specified",
+                        "At least one of name, nullable, data_type or metadata must be specified",
                     ));
                 }
 
@@ -720,6 +760,9 @@ impl Dataset {
                 if let Some(data_type) = data_type {
                     alteration = alteration.cast_to(data_type.0);
                 }
+                for (key, value) in metadata.unwrap_or_default() {
+                    alteration = alteration.set_metadata(key, value);
+                }
                 Ok(alteration)
             })
             .collect::<PyResult<Vec<_>>>()?;
@@ -761,6 +804,14 @@ impl Dataset {
         Ok(())
     }
 
+    fn delete_rows(&mut self, row_ids: Vec<u64>) -> PyResult<()> {
+        let mut new_self = self.ds.as_ref().clone();
+        RT.block_on(None, new_self.delete_rows(row_ids))?
+            .infer_error()?;
+        self.ds = Arc::new(new_self);
+        Ok(())
+    }
+
     fn update(&mut self, updates: &PyDict, predicate: Option<&str>) -> PyResult<()> {
         let mut builder = UpdateBuilder::new(self.ds.clone());
         if let Some(predicate) = predicate {
@@ -857,10 +908,13 @@ impl Dataset {
     /// Cleanup old versions from the dataset
     fn cleanup_old_versions(
         &self,
-        older_than_micros: i64,
+        older_than_micros: Option<i64>,
         delete_unverified: Option<bool>,
     ) -> PyResult<CleanupStats> {
-        let older_than = Duration::microseconds(older_than_micros);
+        let older_than = match older_than_micros {
+            Some(micros) => Duration::microseconds(micros),
+            None => self.ds.config().retention().infer_error()?,
+        };
         let cleanup_stats = RT
             .block_on(
                 None,
@@ -1174,6 +1228,12 @@ pub fn get_write_params(options: &PyDict) -> PyResult<Option<WriteParams>> {
         if let Some(use_legacy_format) = get_dict_opt::<bool>(options, "use_legacy_format")? {
             p.use_legacy_format = use_legacy_format;
         }
+        if let Some(policy) = get_dict_opt::<String>(options, "conform_vectors")? {
+            p.conform_vectors = Some(
+                VectorDimensionPolicy::try_from(policy.as_str())
+                    .map_err(|err| PyValueError::new_err(err.to_string()))?,
+            );
+        }
         if let Some(progress) = get_dict_opt::<PyObject>(options, "progress")? {
             p.progress = Arc::new(PyWriteProgress::new(progress.to_object(options.py())));
         }
@@ -1255,6 +1315,10 @@ fn prepare_vector_index_params(
             ivf_params.precomputed_partitons_file = Some(f.to_string());
         };
 
+        if let Some(memory_budget) = kwargs.get_item("memory_budget")? {
+            ivf_params.memory_budget = Some(PyAny::downcast::<PyInt>(memory_budget)?.extract()?);
+        };
+
         match (
                 kwargs.get_item("precomputed_shuffle_buffers")?,
                 kwargs.get_item("precomputed_shuffle_buffers_path")?
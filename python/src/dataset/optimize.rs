@@ -23,11 +23,16 @@ use pyo3::{exceptions::PyNotImplementedError, pyclass::CompareOp, types::PyTuple
 
 use super::*;
 
-fn parse_compaction_options(options: &PyDict) -> PyResult<CompactionOptions> {
-    let mut opts = CompactionOptions::default();
+/// Parse the compaction options, the ones that are missing or `None` default
+/// to the ones configured for the dataset.
+fn parse_compaction_options(dataset: &Dataset, options: &PyDict) -> PyResult<CompactionOptions> {
+    let mut opts = dataset.ds.config().compaction_options().infer_error()?;
 
     for (key, value) in options.into_iter() {
         let key: String = key.extract()?;
+        if value.is_none() && key != "num_threads" {
+            continue;
+        }
 
         match key.as_str() {
             "target_rows_per_fragment" => {
@@ -469,7 +474,7 @@ impl PyCompaction {
         // aren't holding the GIL while blocking the thread on the operation.
         let opts = Python::with_gil(|py| {
             let options = options.downcast::<PyDict>(py)?;
-            parse_compaction_options(options)
+            parse_compaction_options(&dataset, options)
         })?;
         let mut new_ds = dataset.ds.as_ref().clone();
         let fut = compact_files(&mut new_ds, opts, None);
@@ -506,7 +511,7 @@ impl PyCompaction {
         // aren't holding the GIL while blocking the thread on the operation.
         let opts = Python::with_gil(|py| {
             let options = options.downcast::<PyDict>(py)?;
-            parse_compaction_options(options)
+            parse_compaction_options(&dataset, options)
         })?;
         let plan = RT
             .block_on(None, async move {
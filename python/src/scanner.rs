@@ -68,6 +68,18 @@ impl Scanner {
         Ok(res)
     }
 
+    fn explain_search(self_: PyRef<'_, Self>) -> PyResult<String> {
+        let scanner = self_.scanner.clone();
+        let res = RT
+            .spawn(
+                Some(self_.py()),
+                async move { scanner.explain_search().await },
+            )?
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+
+        Ok(res.to_string())
+    }
+
     fn count_rows(self_: PyRef<'_, Self>) -> PyResult<u64> {
         let scanner = self_.scanner.clone();
         RT.spawn(Some(self_.py()), async move { scanner.count_rows().await })?
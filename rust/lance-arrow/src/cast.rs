@@ -3,7 +3,14 @@
 
 use std::sync::Arc;
 
-use arrow_array::{Array, ArrayRef, FixedSizeListArray};
+use arrow_array::{
+    types::{
+        BinaryType, BinaryViewType, ByteArrayType, ByteViewType, LargeBinaryType, LargeUtf8Type,
+        StringViewType, Utf8Type,
+    },
+    Array, ArrayRef, FixedSizeListArray, GenericByteArray, GenericByteViewArray,
+};
+use arrow_buffer::ArrowNativeType;
 use arrow_cast::CastOptions;
 use arrow_schema::{ArrowError, DataType};
 
@@ -16,6 +23,11 @@ pub fn can_cast_types(from_type: &DataType, to_type: &DataType) -> bool {
         (FixedSizeList(from_field, size_from), FixedSizeList(to_field, size_to)) => {
             size_from == size_to && can_cast_types(from_field.data_type(), to_field.data_type())
         }
+        // TODO: remove this once Arrow supports view types in arrow-cast.
+        (Utf8View, Utf8 | LargeUtf8 | Utf8View)
+        | (Utf8 | LargeUtf8, Utf8View)
+        | (BinaryView, Binary | LargeBinary | BinaryView)
+        | (Binary | LargeBinary, BinaryView) => true,
         // TODO: support bfloat16 cast?
         _ => arrow_cast::can_cast_types(from_type, to_type),
     }
@@ -39,6 +51,106 @@ pub fn cast_with_options(
                 array.nulls().cloned(),
             )?))
         }
+        (Utf8View | BinaryView, _) if array.data_type() == to_type => {
+            Ok(array.slice(0, array.len()))
+        }
+        (Utf8View, Utf8) => view_to_bytes::<StringViewType, Utf8Type>(array),
+        (Utf8View, LargeUtf8) => view_to_bytes::<StringViewType, LargeUtf8Type>(array),
+        (BinaryView, Binary) => view_to_bytes::<BinaryViewType, BinaryType>(array),
+        (BinaryView, LargeBinary) => view_to_bytes::<BinaryViewType, LargeBinaryType>(array),
+        (Utf8, Utf8View) => bytes_to_view::<Utf8Type, StringViewType>(array),
+        (LargeUtf8, Utf8View) => bytes_to_view::<LargeUtf8Type, StringViewType>(array),
+        (Binary, BinaryView) => bytes_to_view::<BinaryType, BinaryViewType>(array),
+        (LargeBinary, BinaryView) => bytes_to_view::<LargeBinaryType, BinaryViewType>(array),
         _ => arrow_cast::cast_with_options(array, to_type, cast_options),
     }
 }
+
+fn view_to_bytes<V: ByteViewType, T: ByteArrayType<Native = V::Native>>(
+    array: &dyn Array,
+) -> Result<ArrayRef, ArrowError>
+where
+    V::Native: AsRef<[u8]>,
+{
+    let array = array
+        .as_any()
+        .downcast_ref::<GenericByteViewArray<V>>()
+        .unwrap();
+    // Views have no offsets, so the data may not fit in 32-bit offsets.
+    let num_bytes = array
+        .iter()
+        .flatten()
+        .map(|value| value.as_ref().len())
+        .sum::<usize>();
+    if T::Offset::from_usize(num_bytes).is_none() {
+        return Err(ArrowError::CastError(format!(
+            "Cannot cast {} to {}: {} bytes of data exceed the offset range",
+            V::DATA_TYPE,
+            T::DATA_TYPE,
+            num_bytes
+        )));
+    }
+    Ok(Arc::new(array.iter().collect::<GenericByteArray<T>>()))
+}
+
+fn bytes_to_view<T: ByteArrayType, V: ByteViewType<Native = T::Native>>(
+    array: &dyn Array,
+) -> Result<ArrayRef, ArrowError> {
+    let array = array
+        .as_any()
+        .downcast_ref::<GenericByteArray<T>>()
+        .unwrap();
+    Ok(Arc::new(array.iter().collect::<GenericByteViewArray<V>>()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{BinaryViewArray, LargeStringArray, StringArray, StringViewArray};
+
+    #[test]
+    fn test_cast_view_types() {
+        let views = StringViewArray::from_iter(vec![
+            Some("a"),
+            None,
+            Some("a string longer than twelve bytes"),
+        ]);
+        assert!(can_cast_types(views.data_type(), &DataType::Utf8));
+        let strings = cast_with_options(&views, &DataType::Utf8, &CastOptions::default()).unwrap();
+        assert_eq!(
+            strings.as_any().downcast_ref::<StringArray>().unwrap(),
+            &StringArray::from(vec![
+                Some("a"),
+                None,
+                Some("a string longer than twelve bytes")
+            ])
+        );
+        let large =
+            cast_with_options(&views, &DataType::LargeUtf8, &CastOptions::default()).unwrap();
+        assert_eq!(
+            large.as_any().downcast_ref::<LargeStringArray>().unwrap(),
+            &LargeStringArray::from(vec![
+                Some("a"),
+                None,
+                Some("a string longer than twelve bytes")
+            ])
+        );
+        let round_trip =
+            cast_with_options(&large, &DataType::Utf8View, &CastOptions::default()).unwrap();
+        let round_trip = round_trip
+            .as_any()
+            .downcast_ref::<StringViewArray>()
+            .unwrap();
+        assert_eq!(
+            round_trip.iter().collect::<Vec<_>>(),
+            views.iter().collect::<Vec<_>>()
+        );
+
+        let binary = BinaryViewArray::from_iter_values([b"abc" as &[u8], b"de"]);
+        let cast =
+            cast_with_options(&binary, &DataType::LargeBinary, &CastOptions::default()).unwrap();
+        assert_eq!(cast.data_type(), &DataType::LargeBinary);
+        assert_eq!(cast.len(), 2);
+    }
+}
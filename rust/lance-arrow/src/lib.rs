@@ -24,6 +24,7 @@ pub mod bfloat16;
 pub mod floats;
 pub use floats::*;
 pub mod cast;
+pub mod string;
 
 type Result<T> = std::result::Result<T, ArrowError>;
 
@@ -93,6 +94,7 @@ impl DataTypeExt for DataType {
                 | Date64
                 | Time32(_)
                 | Time64(_)
+                | Interval(_)
         )
     }
 
@@ -0,0 +1,269 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Kernels on string arrays
+
+use std::sync::Arc;
+
+use arrow_array::{
+    cast::AsArray, Array, ArrayRef, BooleanArray, GenericStringArray, OffsetSizeTrait,
+};
+use arrow_buffer::Buffer;
+use arrow_schema::{ArrowError, DataType};
+
+/// Lowercase the strings of a `Utf8` or `LargeUtf8` array
+///
+/// The strings are lowercased as by [`str::to_lowercase`], and nulls are kept.
+/// Unicode normalization is not supported, so an 'é' written as one character
+/// and one written as an 'e' followed by an accent still differ once lowercased.
+/// If the array is all ASCII, which is the common case, its values are
+/// lowercased in a single pass over their buffer, without decoding them or
+/// changing the offsets.
+pub fn lowercase(array: &dyn Array) -> Result<ArrayRef, ArrowError> {
+    match array.data_type() {
+        DataType::Utf8 => Ok(Arc::new(lowercase_impl(array.as_string::<i32>()))),
+        DataType::LargeUtf8 => Ok(Arc::new(lowercase_impl(array.as_string::<i64>()))),
+        data_type => Err(ArrowError::InvalidArgumentError(format!(
+            "Cannot lowercase an array of type {}",
+            data_type
+        ))),
+    }
+}
+
+fn lowercase_impl<O: OffsetSizeTrait>(array: &GenericStringArray<O>) -> GenericStringArray<O> {
+    let values = array.values();
+    if values.is_ascii() {
+        let lowercased = Buffer::from_vec(values.to_ascii_lowercase());
+        // SAFETY: ASCII bytes stay ASCII, so the values are still valid UTF-8
+        // at the same offsets
+        return unsafe {
+            GenericStringArray::new_unchecked(
+                array.offsets().clone(),
+                lowercased,
+                array.nulls().cloned(),
+            )
+        };
+    }
+    array
+        .iter()
+        .map(|value| value.map(str::to_lowercase))
+        .collect()
+}
+
+/// Whether each string of a `Utf8` or `LargeUtf8` array is within
+/// `max_edits` edits of `term`
+///
+/// The edits are the insertions, deletions and substitutions of characters
+/// counted by the Levenshtein distance.  Nulls stay null.
+pub fn within_edit_distance(
+    array: &dyn Array,
+    term: &str,
+    max_edits: usize,
+) -> Result<BooleanArray, ArrowError> {
+    let distance = EditDistance::new(term);
+    let within = |value: Option<&str>| value.map(|value| distance.within(value, max_edits));
+    match array.data_type() {
+        DataType::Utf8 => Ok(array.as_string::<i32>().iter().map(within).collect()),
+        DataType::LargeUtf8 => Ok(array.as_string::<i64>().iter().map(within).collect()),
+        data_type => Err(ArrowError::InvalidArgumentError(format!(
+            "Cannot compute the edit distance of an array of type {}",
+            data_type
+        ))),
+    }
+}
+
+/// The Levenshtein distance between `left` and `right`, in characters
+pub fn edit_distance(left: &str, right: &str) -> usize {
+    EditDistance::new(left).distance(right)
+}
+
+/// Computes the edit distances to a term
+///
+/// Terms of up to 64 characters use the bit-parallel algorithm of Myers, as
+/// formulated by Hyyrö, which computes a column of the distance matrix in a
+/// few word operations, so the distance to a string costs a pass over its
+/// characters.  Longer terms use the dynamic programming algorithm.
+struct EditDistance {
+    term: Vec<char>,
+    /// The positions of each ASCII character in the term, as a bitmask
+    ascii_masks: [u64; 128],
+    /// The positions of each other character in the term, as a bitmask
+    other_masks: Vec<(char, u64)>,
+}
+
+impl EditDistance {
+    fn new(term: &str) -> Self {
+        let term = term.chars().collect::<Vec<_>>();
+        let mut ascii_masks = [0; 128];
+        let mut other_masks: Vec<(char, u64)> = Vec::new();
+        for (i, c) in term.iter().take(64).enumerate() {
+            let bit = 1 << i;
+            if c.is_ascii() {
+                ascii_masks[*c as usize] |= bit;
+            } else if let Some((_, mask)) = other_masks.iter_mut().find(|(other, _)| other == c) {
+                *mask |= bit;
+            } else {
+                other_masks.push((*c, bit));
+            }
+        }
+        Self {
+            term,
+            ascii_masks,
+            other_masks,
+        }
+    }
+
+    fn mask(&self, c: char) -> u64 {
+        if c.is_ascii() {
+            self.ascii_masks[c as usize]
+        } else {
+            self.other_masks
+                .iter()
+                .find(|(other, _)| *other == c)
+                .map(|(_, mask)| *mask)
+                .unwrap_or(0)
+        }
+    }
+
+    fn within(&self, value: &str, max_edits: usize) -> bool {
+        // Strings whose length differs from the term by more than the
+        // edits can't be within them.  Bytes bound characters from above.
+        if value.len() + max_edits < self.term.len() {
+            return false;
+        }
+        let len = value.chars().count();
+        if len.abs_diff(self.term.len()) > max_edits {
+            return false;
+        }
+        self.distance(value) <= max_edits
+    }
+
+    fn distance(&self, value: &str) -> usize {
+        match self.term.len() {
+            0 => value.chars().count(),
+            1..=64 => self.bit_parallel_distance(value),
+            _ => self.dynamic_distance(value),
+        }
+    }
+
+    fn bit_parallel_distance(&self, value: &str) -> usize {
+        let len = self.term.len();
+        let last = 1 << (len - 1);
+        // The vertical deltas of the current column, +1 and -1
+        let mut pv = if len == 64 { u64::MAX } else { (1 << len) - 1 };
+        let mut mv = 0_u64;
+        let mut distance = len;
+        for c in value.chars() {
+            let eq = self.mask(c);
+            let xv = eq | mv;
+            let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+            // The horizontal deltas, +1 and -1
+            let mut ph = mv | !(xh | pv);
+            let mut mh = pv & xh;
+            if ph & last != 0 {
+                distance += 1;
+            } else if mh & last != 0 {
+                distance -= 1;
+            }
+            // The first row of the matrix grows by one in each column
+            ph = (ph << 1) | 1;
+            mh <<= 1;
+            pv = mh | !(xv | ph);
+            mv = ph & xv;
+        }
+        distance
+    }
+
+    fn dynamic_distance(&self, value: &str) -> usize {
+        let mut row = (0..=self.term.len()).collect::<Vec<_>>();
+        for (i, c) in value.chars().enumerate() {
+            let mut diagonal = row[0];
+            row[0] = i + 1;
+            for (j, t) in self.term.iter().enumerate() {
+                let substitution = diagonal + usize::from(*t != c);
+                diagonal = row[j + 1];
+                row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+            }
+        }
+        row[self.term.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{LargeStringArray, StringArray};
+
+    use super::*;
+
+    #[test]
+    fn test_lowercase() {
+        let array = StringArray::from(vec![Some("Hello"), None, Some("WORLD"), Some("")]);
+        let lowercased = lowercase(&array).unwrap();
+        assert_eq!(
+            lowercased.as_string::<i32>(),
+            &StringArray::from(vec![Some("hello"), None, Some("world"), Some("")])
+        );
+
+        // Slices only keep their own values
+        let lowercased = lowercase(&array.slice(2, 2)).unwrap();
+        assert_eq!(
+            lowercased.as_string::<i32>(),
+            &StringArray::from(vec![Some("world"), Some("")])
+        );
+
+        let array = LargeStringArray::from(vec!["ÀÉÎ", "Straße", "ABC"]);
+        let lowercased = lowercase(&array).unwrap();
+        assert_eq!(
+            lowercased.as_string::<i64>(),
+            &LargeStringArray::from(vec!["àéî", "straße", "abc"])
+        );
+
+        assert!(lowercase(&arrow_array::Int32Array::from(vec![1])).is_err());
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("flaw", "lawn"), 2);
+        assert_eq!(edit_distance("café", "cafe"), 1);
+        assert_eq!(edit_distance("naïve", "naïve"), 0);
+
+        // The bit-parallel and dynamic algorithms agree, for terms of up to
+        // and over 64 characters
+        let words = ["", "a", "ab", "ba", "abc", "acb", "xabcx", "ééa", "bbbbbb"];
+        let long = "abcdefghij".repeat(7);
+        for left in words.iter().copied().chain([long.as_str(), &long[..64]]) {
+            let distance = EditDistance::new(left);
+            for right in words.iter().copied().chain([long.as_str(), &long[1..]]) {
+                assert_eq!(
+                    distance.distance(right),
+                    distance.dynamic_distance(right),
+                    "{} {}",
+                    left,
+                    right
+                );
+            }
+        }
+        assert_eq!(edit_distance(&long, &long[1..]), 1);
+    }
+
+    #[test]
+    fn test_within_edit_distance() {
+        let array = StringArray::from(vec![Some("apple"), Some("appel"), None, Some("apricot")]);
+        assert_eq!(
+            within_edit_distance(&array, "apple", 2).unwrap(),
+            BooleanArray::from(vec![Some(true), Some(true), None, Some(false)])
+        );
+        assert_eq!(
+            within_edit_distance(&array, "apple", 0).unwrap(),
+            BooleanArray::from(vec![Some(true), Some(false), None, Some(false)])
+        );
+        let array = LargeStringArray::from(vec!["Straße", "strasse"]);
+        assert_eq!(
+            within_edit_distance(&array, "strasse", 3).unwrap(),
+            BooleanArray::from(vec![true, true])
+        );
+    }
+}
@@ -7,7 +7,7 @@ use std::fmt::{self, Debug, Formatter};
 use std::sync::Arc;
 
 use arrow_array::ArrayRef;
-use arrow_schema::{DataType, Field as ArrowField, TimeUnit};
+use arrow_schema::{DataType, Field as ArrowField, IntervalUnit, TimeUnit};
 use deepsize::DeepSizeOf;
 use lance_arrow::bfloat16::{
     is_bfloat16_field, ARROW_EXT_META_KEY, ARROW_EXT_NAME_KEY, BFLOAT16_EXT_NAME,
@@ -112,6 +112,11 @@ impl TryFrom<&DataType> for LogicalType {
                     .unwrap_or("-".to_string())
             ),
             DataType::Duration(tu) => format!("duration:{}", timeunit_to_str(tu)),
+            DataType::Interval(unit) => match unit {
+                IntervalUnit::YearMonth => "interval:year_month".to_string(),
+                IntervalUnit::DayTime => "interval:day_time".to_string(),
+                IntervalUnit::MonthDayNano => "interval:month_day_nano".to_string(),
+            },
             DataType::Struct(_) => "struct".to_string(),
             DataType::Dictionary(key_type, value_type) => {
                 format!(
@@ -189,6 +194,9 @@ impl TryFrom<&LogicalType> for DataType {
             "duration:ms" => Some(Duration(TimeUnit::Millisecond)),
             "duration:us" => Some(Duration(TimeUnit::Microsecond)),
             "duration:ns" => Some(Duration(TimeUnit::Nanosecond)),
+            "interval:year_month" => Some(Interval(IntervalUnit::YearMonth)),
+            "interval:day_time" => Some(Interval(IntervalUnit::DayTime)),
+            "interval:month_day_nano" => Some(Interval(IntervalUnit::MonthDayNano)),
             _ => None,
         } {
             Ok(t)
@@ -411,6 +411,39 @@ impl Field {
         }
     }
 
+    /// Project by field ids, where the fields of structs must be selected too.
+    ///
+    /// Unlike [`Self::project_by_ids`], selecting a struct doesn't select its
+    /// fields, and a struct without any selected field is left out. This is
+    /// how the fields stored in a data file are resolved, since the fields of
+    /// a struct can be stored in different data files.
+    pub(crate) fn project_by_ids_strict(&self, ids: &[i32]) -> Option<Self> {
+        let children = self
+            .children
+            .iter()
+            .filter_map(|c| c.project_by_ids_strict(ids))
+            .collect::<Vec<_>>();
+        if self.logical_type.is_struct() {
+            if !children.is_empty() || (self.children.is_empty() && ids.contains(&self.id)) {
+                Some(Self {
+                    children,
+                    ..self.clone()
+                })
+            } else {
+                None
+            }
+        } else if ids.contains(&self.id) {
+            Some(self.clone())
+        } else if !children.is_empty() {
+            Some(Self {
+                children,
+                ..self.clone()
+            })
+        } else {
+            None
+        }
+    }
+
     /// Project by a field.
     ///
     pub fn project_by_field(&self, other: &Self) -> Result<Self> {
@@ -753,7 +786,7 @@ mod tests {
     use super::*;
 
     use arrow_array::{DictionaryArray, StringArray, UInt32Array};
-    use arrow_schema::{Fields, TimeUnit};
+    use arrow_schema::{Fields, IntervalUnit, TimeUnit};
 
     #[test]
     fn arrow_field_to_field() {
@@ -797,6 +830,18 @@ mod tests {
             ("duration:ms", DataType::Duration(TimeUnit::Millisecond)),
             ("duration:us", DataType::Duration(TimeUnit::Microsecond)),
             ("duration:ns", DataType::Duration(TimeUnit::Nanosecond)),
+            (
+                "interval:year_month",
+                DataType::Interval(IntervalUnit::YearMonth),
+            ),
+            (
+                "interval:day_time",
+                DataType::Interval(IntervalUnit::DayTime),
+            ),
+            (
+                "interval:month_day_nano",
+                DataType::Interval(IntervalUnit::MonthDayNano),
+            ),
             ("fixed_size_binary:100", DataType::FixedSizeBinary(100)),
             (
                 "fixed_size_list:int32:10",
@@ -277,6 +277,22 @@ impl Schema {
         }
     }
 
+    /// Returns a new schema that only contains the fields in `column_ids`,
+    /// where the fields of structs must be in `column_ids` too.
+    ///
+    /// See [`Field::project_by_ids_strict`].
+    pub fn project_by_ids_strict(&self, column_ids: &[i32]) -> Self {
+        let filtered_fields = self
+            .fields
+            .iter()
+            .filter_map(|f| f.project_by_ids_strict(column_ids))
+            .collect();
+        Self {
+            fields: filtered_fields,
+            metadata: self.metadata.clone(),
+        }
+    }
+
     /// Project the schema by another schema, and preserves field metadata, i.e., Field IDs.
     ///
     /// Parameters
@@ -661,6 +677,45 @@ mod tests {
         assert_eq!(ArrowSchema::from(&projected), expected_arrow_schema);
     }
 
+    #[test]
+    fn test_schema_project_by_ids_strict() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new(
+                "b",
+                DataType::Struct(ArrowFields::from(vec![
+                    ArrowField::new("f1", DataType::Utf8, true),
+                    ArrowField::new("f2", DataType::Boolean, false),
+                ])),
+                true,
+            ),
+            ArrowField::new("c", DataType::new_list(DataType::Int32, true), true),
+        ]);
+        let mut schema = Schema::try_from(&arrow_schema).unwrap();
+        schema.set_field_id(None);
+
+        // Selecting a struct doesn't select its fields
+        let projected = schema.project_by_ids_strict(&[1, 3]);
+        let expected_arrow_schema = ArrowSchema::new(vec![ArrowField::new(
+            "b",
+            DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                "f2",
+                DataType::Boolean,
+                false,
+            )])),
+            true,
+        )]);
+        assert_eq!(ArrowSchema::from(&projected), expected_arrow_schema);
+
+        // Nor does it select the struct on its own, but lists are selected whole
+        let projected = schema.project_by_ids_strict(&[0, 1, 4]);
+        let expected_arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("c", DataType::new_list(DataType::Int32, true), true),
+        ]);
+        assert_eq!(ArrowSchema::from(&projected), expected_arrow_schema);
+    }
+
     #[test]
     fn test_schema_project_by_schema() {
         let arrow_schema = ArrowSchema::new(vec![
@@ -275,7 +275,8 @@ impl From<datafusion_sql::sqlparser::tokenizer::TokenizerError> for Error {
 impl From<Error> for datafusion_common::DataFusionError {
     #[track_caller]
     fn from(e: Error) -> Self {
-        Self::Execution(e.to_string())
+        // Keep the error, so that its cause can still be inspected
+        Self::External(Box::new(e))
     }
 }
 
@@ -6,6 +6,7 @@ use arrow_schema::{DataType, Field as ArrowField};
 pub mod cache;
 pub mod datatypes;
 pub mod error;
+pub mod sink;
 pub mod utils;
 
 pub use error::{Error, Result};
@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! A common interface for destinations of record batches.
+//!
+//! The file writers, the fragment writer and the dataset writer all implement
+//! [`AsyncBatchSink`], so a pipeline can write to any of them, or to a custom
+//! sink, in the same way.  [`TeeSink`] writes the same batches to two sinks.
+
+use arrow_array::RecordBatch;
+use async_trait::async_trait;
+use futures::{Stream, TryStreamExt};
+
+use crate::Result;
+
+/// A destination that record batches are written to
+///
+/// Batches are written in order with [`write_batch`](Self::write_batch).  A sink
+/// may buffer them in memory, [`flush`](Self::flush) writes out whatever is
+/// buffered.  Data is only complete once [`finish`](Self::finish) returns,
+/// e.g. a file has its footer written or a dataset has a new version committed.
+/// A sink must not be written to or finished again after it is finished.
+#[async_trait]
+pub trait AsyncBatchSink: Send {
+    /// The result of finishing the sink, e.g. the number of rows written
+    type Output: Send;
+
+    /// Write a batch to the sink
+    async fn write_batch(&mut self, batch: &RecordBatch) -> Result<()>;
+
+    /// Write out any data the sink is buffering in memory
+    ///
+    /// Sinks that don't buffer data do nothing.  Flushed data may still not be
+    /// visible to readers until the sink is finished.
+    async fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Finish writing, making the written data complete
+    async fn finish(&mut self) -> Result<Self::Output>;
+
+    /// Write every batch of `stream` to the sink and then finish it
+    async fn write_stream<S>(&mut self, stream: S) -> Result<Self::Output>
+    where
+        S: Stream<Item = Result<RecordBatch>> + Send,
+        Self: Sized,
+    {
+        let mut stream = std::pin::pin!(stream);
+        while let Some(batch) = stream.try_next().await? {
+            self.write_batch(&batch).await?;
+        }
+        self.finish().await
+    }
+}
+
+#[async_trait]
+impl<S: AsyncBatchSink + ?Sized> AsyncBatchSink for Box<S> {
+    type Output = S::Output;
+
+    async fn write_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        self.as_mut().write_batch(batch).await
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.as_mut().flush().await
+    }
+
+    async fn finish(&mut self) -> Result<Self::Output> {
+        self.as_mut().finish().await
+    }
+}
+
+/// A sink that writes every batch to two sinks
+///
+/// The two sinks are written, flushed and finished concurrently.  If either of
+/// them fails, the operation fails.
+pub struct TeeSink<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: AsyncBatchSink, B: AsyncBatchSink> TeeSink<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    /// Return the two sinks
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+#[async_trait]
+impl<A: AsyncBatchSink, B: AsyncBatchSink> AsyncBatchSink for TeeSink<A, B> {
+    type Output = (A::Output, B::Output);
+
+    async fn write_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        futures::try_join!(
+            self.first.write_batch(batch),
+            self.second.write_batch(batch)
+        )?;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        futures::try_join!(self.first.flush(), self.second.flush())?;
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<Self::Output> {
+        futures::try_join!(self.first.finish(), self.second.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::Int32Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use futures::StreamExt;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CollectSink {
+        batches: Vec<RecordBatch>,
+        flushed: usize,
+    }
+
+    #[async_trait]
+    impl AsyncBatchSink for CollectSink {
+        type Output = Vec<RecordBatch>;
+
+        async fn write_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+            self.batches.push(batch.clone());
+            Ok(())
+        }
+
+        async fn flush(&mut self) -> Result<()> {
+            self.flushed = self.batches.len();
+            Ok(())
+        }
+
+        async fn finish(&mut self) -> Result<Self::Output> {
+            Ok(std::mem::take(&mut self.batches))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tee_sink() {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let batches = (0..3)
+            .map(|i| {
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(vec![i]))])
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let mut sink = TeeSink::new(CollectSink::default(), CollectSink::default());
+        sink.write_batch(&batches[0]).await.unwrap();
+        sink.flush().await.unwrap();
+        let (first, second) = sink
+            .write_stream(futures::stream::iter(batches[1..].to_vec()).map(Ok))
+            .await
+            .unwrap();
+        assert_eq!(first, batches);
+        assert_eq!(second, batches);
+
+        let (first, second) = sink.into_inner();
+        assert_eq!(first.flushed, 1);
+        assert_eq!(second.flushed, 1);
+    }
+}
@@ -5,7 +5,9 @@ pub mod address;
 pub mod cpu;
 pub mod deletion;
 pub mod futures;
+pub mod hash;
 pub mod mask;
+pub mod temp;
 pub mod testing;
 pub mod tokio;
 pub mod tracing;
@@ -0,0 +1,32 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Hashes that are stable across versions and platforms, so that they can be
+//! persisted
+
+/// The state of a [`fnv1a`] hash before any bytes are hashed
+pub const FNV1A_OFFSET: u64 = 0xcbf29ce484222325;
+
+/// 64-bit FNV-1a
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    fnv1a_extend(FNV1A_OFFSET, bytes)
+}
+
+/// Continue the FNV-1a `hash` with `bytes`, to hash a sequence of values
+pub fn fnv1a_extend(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), FNV1A_OFFSET);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a_extend(fnv1a(b"a"), b"b"), fnv1a(b"ab"));
+    }
+}
@@ -0,0 +1,262 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Temporary files for spills and index builds
+//!
+//! All the temporary files and directories of the process are created in a
+//! directory of their own, named after the host and the process id, under the
+//! system temporary directory or `LANCE_TEMP_DIR` if set.  Files are deleted
+//! when they are dropped.  A process that crashes leaves its directory behind,
+//! which is deleted by the next process that uses temporary files.
+//!
+//! Each directory holds a lock file that its process keeps locked while it
+//! runs, which tells the directories of running processes apart, even those
+//! of other hosts or containers sharing `LANCE_TEMP_DIR`.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use fs2::FileExt;
+use tempfile::{Builder, NamedTempFile, TempDir};
+
+use crate::Result;
+
+const DIR_PREFIX: &str = "lance-tmp-";
+const LOCK_FILE: &str = ".lock";
+
+lazy_static::lazy_static! {
+    static ref TEMP_FILES: TempFileManager = {
+        let root = std::env::var_os("LANCE_TEMP_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        let manager = TempFileManager::new(root);
+        if let Err(err) = manager.cleanup_orphans() {
+            tracing::warn!("Failed to delete orphaned temporary files: {}", err);
+        }
+        manager
+    };
+}
+
+/// The temporary files of the process
+pub fn temp_files() -> &'static TempFileManager {
+    &TEMP_FILES
+}
+
+/// Disk usage of the temporary files of the process
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TempUsage {
+    /// Number of files, including the files in temporary directories
+    pub num_files: usize,
+    pub num_bytes: u64,
+}
+
+/// Creates and accounts for the temporary files of a process, see the
+/// [module documentation](self).
+#[derive(Debug)]
+pub struct TempFileManager {
+    root: PathBuf,
+    dir: PathBuf,
+    /// The locked lock file of `dir`, once it is created
+    lock: Mutex<Option<File>>,
+}
+
+impl TempFileManager {
+    /// Create a manager of temporary files under `root`
+    pub fn new(root: PathBuf) -> Self {
+        let host = hostname::get()
+            .ok()
+            .and_then(|host| host.into_string().ok())
+            .unwrap_or_default()
+            .replace(|c: char| !c.is_ascii_alphanumeric() && c != '.', "_");
+        // Managers of the same process, such as those of tests, are kept apart
+        // by a random suffix
+        let dir = root.join(format!(
+            "{}{}-{}-{:08x}",
+            DIR_PREFIX,
+            host,
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        Self {
+            root,
+            dir,
+            lock: Mutex::new(None),
+        }
+    }
+
+    /// The directory of the temporary files of the process
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Create the directory of the temporary files of the process, if it
+    /// doesn't exist yet, and return it
+    ///
+    /// Files created in it by other means are deleted with it once the
+    /// process is gone, but not when the process ends normally.
+    pub fn create_dir(&self) -> Result<&Path> {
+        let mut lock = self.lock.lock().unwrap();
+        if lock.is_none() {
+            // The directory is only given its name once its lock file is
+            // locked, so that other processes never see it unlocked
+            let staging = self.root.join(format!(
+                ".staging-{}",
+                self.dir.file_name().unwrap().to_string_lossy()
+            ));
+            std::fs::create_dir_all(&staging)?;
+            let locked = File::create(staging.join(LOCK_FILE))
+                .and_then(|file| file.try_lock_exclusive().map(|()| file))
+                .and_then(|file| std::fs::rename(&staging, &self.dir).map(|()| file));
+            match locked {
+                Ok(file) => *lock = Some(file),
+                Err(err) => {
+                    let _ = std::fs::remove_dir_all(&staging);
+                    return Err(err.into());
+                }
+            }
+        }
+        Ok(&self.dir)
+    }
+
+    /// Create a temporary file, deleted when it is dropped
+    pub fn new_file(&self) -> Result<NamedTempFile> {
+        Ok(Builder::new()
+            .prefix("file-")
+            .tempfile_in(self.create_dir()?)?)
+    }
+
+    /// Create a temporary directory, deleted with its content when it is dropped
+    pub fn new_dir(&self) -> Result<TempDir> {
+        Ok(Builder::new()
+            .prefix("dir-")
+            .tempdir_in(self.create_dir()?)?)
+    }
+
+    /// The disk usage of the temporary files that currently exist
+    pub fn usage(&self) -> Result<TempUsage> {
+        let mut usage = TempUsage::default();
+        if self.dir.exists() {
+            add_usage(&self.dir, &mut usage)?;
+        }
+        // The lock file isn't a temporary file
+        if self.lock.lock().unwrap().is_some() {
+            usage.num_files -= 1;
+        }
+        Ok(usage)
+    }
+
+    /// Delete the temporary directories of processes that are no longer
+    /// running, and return how many were deleted
+    ///
+    /// A directory is deleted once its lock file can be locked, which the
+    /// process that created it prevents as long as it is running. Directories
+    /// without a lock file are kept.
+    pub fn cleanup_orphans(&self) -> Result<usize> {
+        let entries = match std::fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err.into()),
+        };
+        let mut num_deleted = 0;
+        for entry in entries {
+            let entry = entry?;
+            let is_temp_dir = entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with(DIR_PREFIX));
+            if !is_temp_dir || entry.path() == self.dir {
+                continue;
+            }
+            let lock = match File::open(entry.path().join(LOCK_FILE)) {
+                Ok(lock) => lock,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            if lock.try_lock_exclusive().is_err() {
+                continue;
+            }
+            match std::fs::remove_dir_all(entry.path()) {
+                Ok(()) => num_deleted += 1,
+                // Another process cleaned it up first
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(num_deleted)
+    }
+}
+
+fn add_usage(dir: &Path, usage: &mut TempUsage) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        // Files may be deleted while they are counted
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            add_usage(&entry.path(), usage)?;
+        } else {
+            usage.num_files += 1;
+            usage.num_bytes += metadata.len();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_temp_files() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = TempFileManager::new(root.path().to_path_buf());
+        assert_eq!(manager.usage().unwrap(), TempUsage::default());
+
+        let mut file = manager.new_file().unwrap();
+        file.write_all(&[0; 100]).unwrap();
+        file.flush().unwrap();
+        let dir = manager.new_dir().unwrap();
+        std::fs::write(dir.path().join("spill"), [0; 20]).unwrap();
+        assert_eq!(
+            manager.usage().unwrap(),
+            TempUsage {
+                num_files: 2,
+                num_bytes: 120
+            }
+        );
+
+        drop(file);
+        drop(dir);
+        assert_eq!(manager.usage().unwrap(), TempUsage::default());
+
+        // The directories of running processes are kept, a leftover of a
+        // process that is gone is deleted
+        let other = TempFileManager::new(root.path().to_path_buf());
+        other.create_dir().unwrap();
+        let orphan = root
+            .path()
+            .join(format!("{}host-{}-0", DIR_PREFIX, u32::MAX));
+        std::fs::create_dir(&orphan).unwrap();
+        File::create(orphan.join(LOCK_FILE)).unwrap();
+        std::fs::write(orphan.join("spill"), [0; 20]).unwrap();
+        let legacy = root.path().join(format!("{}{}", DIR_PREFIX, u32::MAX));
+        std::fs::create_dir(&legacy).unwrap();
+        let num_deleted = manager.cleanup_orphans().unwrap();
+        assert_eq!(num_deleted, 1);
+        assert!(!orphan.exists());
+        assert!(manager.dir().exists());
+        assert!(other.dir().exists());
+        assert!(legacy.exists());
+
+        // Once its process is gone, the directory of another one is deleted
+        let other_dir = other.dir().to_path_buf();
+        drop(other);
+        assert_eq!(manager.cleanup_orphans().unwrap(), 1);
+        assert!(!other_dir.exists());
+        assert!(manager.dir().exists());
+    }
+}
@@ -1,10 +1,14 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
+use std::sync::Arc;
+
 use crate::Result;
 
+use deepsize::{Context, DeepSizeOf};
 use futures::{Future, FutureExt};
 use tokio::runtime::{Builder, Runtime};
+use tokio::sync::{Semaphore, SemaphorePermit};
 use tracing::Span;
 
 lazy_static::lazy_static! {
@@ -13,6 +17,70 @@ lazy_static::lazy_static! {
         .max_blocking_threads(num_cpus::get())
         .build()
         .unwrap();
+    static ref DECODE_PERMITS: Semaphore = Semaphore::new(get_num_decode_threads());
+}
+
+/// Get the number of threads that may decode data at the same time
+///
+/// This is shared by all readers in the process.  It defaults to the number of
+/// CPUs and can be lowered with the `LANCE_DECODE_THREADS` environment variable
+/// to keep cores free for other work.
+pub fn get_num_decode_threads() -> usize {
+    std::env::var("LANCE_DECODE_THREADS")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or_else(num_cpus::get)
+}
+
+/// Bounds how many batches are decoded at the same time
+///
+/// Decoding always waits for one of the process-wide decode threads (see
+/// [`get_num_decode_threads`]).  A limit created with [`DecodeLimit::new`]
+/// further bounds the decoding of the readers it is given to, which allows a
+/// single scan to use fewer threads than the process allows.
+///
+/// The limit only covers decoding.  I/O is not bounded by it.
+#[derive(Debug, Clone, Default)]
+pub struct DecodeLimit {
+    permits: Option<Arc<Semaphore>>,
+}
+
+impl DecodeLimit {
+    /// Create a limit of `num_threads` threads, on top of the process-wide limit
+    pub fn new(num_threads: usize) -> Self {
+        Self {
+            permits: Some(Arc::new(Semaphore::new(num_threads.max(1)))),
+        }
+    }
+
+    /// Wait until a thread is available under this limit, without taking one
+    /// of the process-wide decode threads.
+    ///
+    /// This is for readers that decode while they read, which would hold a
+    /// process-wide decode thread while waiting on I/O.
+    pub async fn reserve(&self) -> Option<SemaphorePermit<'_>> {
+        match &self.permits {
+            Some(permits) => Some(permits.acquire().await.unwrap()),
+            None => None,
+        }
+    }
+
+    /// Run `func` once a decode thread is available under this limit
+    pub async fn run<R>(&self, func: impl FnOnce() -> R) -> R {
+        // The local permit is taken first so that a task holding a
+        // process-wide permit never waits on a local one.
+        let _local_permit = self.reserve().await;
+        let _permit = DECODE_PERMITS.acquire().await.unwrap();
+        func()
+    }
+}
+
+impl DeepSizeOf for DecodeLimit {
+    fn deep_size_of_children(&self, _context: &mut Context) -> usize {
+        // The semaphore is shared with the scan that created the limit
+        0
+    }
 }
 
 /// Spawn a CPU intensive task
@@ -0,0 +1,581 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Decoupling the producer of a stream from its consumer
+//!
+//! A [`BufferedRecordBatchStream`] polls its input on a background task and
+//! keeps the batches until they are consumed, so a slow consumer, such as a
+//! writer, doesn't stall a fast producer, such as a decoder, and the other way
+//! round.  The buffer is bounded: the input is not polled while the buffer is
+//! full, which applies backpressure to the producer.  Batches that don't fit
+//! in memory can be spilled to a temporary file instead.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use datafusion::physical_plan::{RecordBatchStream, SendableRecordBatchStream};
+use datafusion_common::{DataFusionError, Result};
+use futures::stream::BoxStream;
+use futures::{stream, Stream, StreamExt};
+use lance_core::utils::temp::temp_files;
+use tempfile::NamedTempFile;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+/// How much a [`BufferedRecordBatchStream`] buffers
+#[derive(Debug, Clone)]
+pub struct BufferOptions {
+    /// Number of rows kept in memory
+    pub max_rows: usize,
+    /// Bytes kept in memory, estimated from the in-memory size of the batches
+    pub max_bytes: usize,
+    /// Bytes of batches spilled to a temporary file once the memory is full.
+    /// If not set, batches are not spilled.
+    pub max_spill_bytes: Option<usize>,
+}
+
+impl Default for BufferOptions {
+    fn default() -> Self {
+        Self {
+            max_rows: 64 * 1024,
+            max_bytes: 64 * 1024 * 1024,
+            max_spill_bytes: None,
+        }
+    }
+}
+
+enum Entry {
+    Memory(RecordBatch),
+    /// A batch in Arrow IPC format in the spill file
+    Spilled {
+        offset: u64,
+        len: usize,
+    },
+    Error(DataFusionError),
+}
+
+/// What the buffer does with a batch
+enum Push {
+    /// The batch is kept in memory
+    Buffered,
+    /// The batch has to be spilled
+    Spill(RecordBatch),
+    /// The buffer is full
+    Full(RecordBatch),
+}
+
+/// The next entry of a buffer
+enum Pop {
+    Batch(Result<RecordBatch>),
+    /// A batch to read from the spill file
+    Spilled {
+        file: Arc<NamedTempFile>,
+        offset: u64,
+        len: usize,
+    },
+}
+
+/// The bookkeeping of a buffer.  The spill file is only read and written
+/// outside of the lock of the state, on blocking threads.
+struct BufferState {
+    options: BufferOptions,
+    entries: VecDeque<Entry>,
+    num_rows: usize,
+    num_bytes: usize,
+    /// The spill file, once a batch has been spilled
+    spill_file: Option<Arc<NamedTempFile>>,
+    /// Where the next batch is spilled
+    spill_end: u64,
+    /// Bytes and number of the batches of the spill file that are being
+    /// written, or haven't been read
+    spilled_bytes: usize,
+    num_spilled: usize,
+    finished: bool,
+}
+
+impl BufferState {
+    /// Add `batch` to the buffer if there is memory for it
+    fn try_push(&mut self, batch: RecordBatch) -> Push {
+        let num_rows = batch.num_rows();
+        let num_bytes = batch.get_array_memory_size();
+        // A batch is always accepted by an empty buffer, however large
+        if self.entries.is_empty()
+            || (self.num_rows + num_rows <= self.options.max_rows
+                && self.num_bytes + num_bytes <= self.options.max_bytes)
+        {
+            self.num_rows += num_rows;
+            self.num_bytes += num_bytes;
+            self.entries.push_back(Entry::Memory(batch));
+            return Push::Buffered;
+        }
+        match self.options.max_spill_bytes {
+            Some(_) => Push::Spill(batch),
+            None => Push::Full(batch),
+        }
+    }
+
+    /// Reserve `len` bytes at the end of the spill file, and return where
+    /// they start, if they fit the spill budget
+    fn reserve_spill(&mut self, len: usize) -> Option<u64> {
+        let max_spill_bytes = self.options.max_spill_bytes?;
+        if self.spilled_bytes > 0 && self.spilled_bytes + len > max_spill_bytes {
+            return None;
+        }
+        let offset = self.spill_end;
+        self.spill_end += len as u64;
+        self.spilled_bytes += len;
+        self.num_spilled += 1;
+        Some(offset)
+    }
+
+    /// Release the bytes of a spilled batch that has been read
+    fn release_spill(&mut self, len: usize) {
+        self.spilled_bytes -= len;
+        self.num_spilled -= 1;
+        // Start over at the beginning of the file once it is drained, so it
+        // doesn't grow beyond the bytes spilled at once
+        if self.num_spilled == 0 {
+            self.spill_end = 0;
+        }
+    }
+
+    /// The next entry, if there is one
+    fn pop(&mut self) -> Option<Pop> {
+        let entry = self.entries.pop_front()?;
+        Some(match entry {
+            Entry::Memory(batch) => {
+                self.num_rows -= batch.num_rows();
+                self.num_bytes -= batch.get_array_memory_size();
+                Pop::Batch(Ok(batch))
+            }
+            Entry::Spilled { offset, len } => match &self.spill_file {
+                Some(file) => Pop::Spilled {
+                    file: file.clone(),
+                    offset,
+                    len,
+                },
+                None => Pop::Batch(Err(DataFusionError::Internal(
+                    "the spill file of a buffer is missing".to_string(),
+                ))),
+            },
+            Entry::Error(err) => Pop::Batch(Err(err)),
+        })
+    }
+}
+
+fn join_error(err: tokio::task::JoinError) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
+
+/// The state shared by the producer and the consumer of a buffer
+struct Shared {
+    state: Mutex<BufferState>,
+    /// Notified when a batch is added, or the input is finished
+    pushed: Notify,
+    /// Notified when a batch is consumed
+    popped: Notify,
+}
+
+/// Marks the buffer as finished when the producer stops, even if it panics
+/// or is aborted
+struct FinishGuard(Arc<Shared>);
+
+impl Drop for FinishGuard {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        if !state.finished {
+            state
+                .entries
+                .push_back(Entry::Error(DataFusionError::Execution(
+                    "the input of a buffered stream stopped unexpectedly".to_string(),
+                )));
+            state.finished = true;
+        }
+        drop(state);
+        self.0.pushed.notify_one();
+    }
+}
+
+/// A stream that buffers the batches of its input, see the
+/// [module documentation](self)
+///
+/// The input is polled on a background task, which is aborted when this
+/// stream is dropped.
+pub struct BufferedRecordBatchStream {
+    schema: SchemaRef,
+    batches: BoxStream<'static, Result<RecordBatch>>,
+    producer: JoinHandle<()>,
+}
+
+impl BufferedRecordBatchStream {
+    pub fn new(input: SendableRecordBatchStream, options: BufferOptions) -> Self {
+        let schema = input.schema();
+        let shared = Arc::new(Shared {
+            state: Mutex::new(BufferState {
+                options,
+                entries: VecDeque::new(),
+                num_rows: 0,
+                num_bytes: 0,
+                spill_file: None,
+                spill_end: 0,
+                spilled_bytes: 0,
+                num_spilled: 0,
+                finished: false,
+            }),
+            pushed: Notify::new(),
+            popped: Notify::new(),
+        });
+        let producer = tokio::spawn(produce(input, FinishGuard(shared.clone())));
+        let batches = stream::unfold((shared, None), |(shared, reader)| async move {
+            let (batch, reader) = consume(&shared, reader).await?;
+            Some((batch, (shared, reader)))
+        })
+        .boxed();
+        Self {
+            schema,
+            batches,
+            producer,
+        }
+    }
+}
+
+/// The writer of the spill file of a buffer, once it is created
+struct SpillWriter {
+    file: Arc<NamedTempFile>,
+    handle: File,
+}
+
+async fn produce(mut input: SendableRecordBatchStream, guard: FinishGuard) {
+    let shared = &guard.0;
+    let mut writer = None;
+    while let Some(batch) = input.next().await {
+        let pushed = match batch {
+            Ok(batch) => push(shared, batch, &mut writer).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = pushed {
+            let mut state = shared.state.lock().unwrap();
+            state.entries.push_back(Entry::Error(err));
+            state.finished = true;
+            drop(state);
+            shared.pushed.notify_one();
+            return;
+        }
+        shared.pushed.notify_one();
+    }
+    shared.state.lock().unwrap().finished = true;
+}
+
+/// Add `batch` to the buffer, once there is room for it
+async fn push(
+    shared: &Shared,
+    mut batch: RecordBatch,
+    writer: &mut Option<SpillWriter>,
+) -> Result<()> {
+    // The batch in Arrow IPC format, once it has been encoded to be spilled
+    let mut encoded = None;
+    loop {
+        let pushed = shared.state.lock().unwrap().try_push(batch);
+        let rejected = match pushed {
+            Push::Buffered => return Ok(()),
+            Push::Full(rejected) => rejected,
+            Push::Spill(rejected) => {
+                let bytes = match encoded.take() {
+                    Some(bytes) => bytes,
+                    None => {
+                        let batch = rejected.clone();
+                        tokio::task::spawn_blocking(move || {
+                            let mut writer =
+                                StreamWriter::try_new(Vec::new(), batch.schema().as_ref())?;
+                            writer.write(&batch)?;
+                            Ok::<_, DataFusionError>(writer.into_inner()?)
+                        })
+                        .await
+                        .map_err(join_error)??
+                    }
+                };
+                let offset = shared.state.lock().unwrap().reserve_spill(bytes.len());
+                match offset {
+                    Some(offset) => return spill(shared, bytes, offset, writer).await,
+                    None => {
+                        encoded = Some(bytes);
+                        rejected
+                    }
+                }
+            }
+        };
+        batch = rejected;
+        shared.popped.notified().await;
+    }
+}
+
+/// Write an encoded batch at `offset` of the spill file, and add it to the
+/// buffer
+async fn spill(
+    shared: &Shared,
+    bytes: Vec<u8>,
+    offset: u64,
+    writer: &mut Option<SpillWriter>,
+) -> Result<()> {
+    let current = writer.take();
+    let len = bytes.len();
+    let written = tokio::task::spawn_blocking(move || {
+        let mut writer = match current {
+            Some(writer) => writer,
+            None => {
+                let file = Arc::new(temp_files().new_file()?);
+                let handle = file.reopen()?;
+                SpillWriter { file, handle }
+            }
+        };
+        writer.handle.seek(SeekFrom::Start(offset))?;
+        writer.handle.write_all(&bytes)?;
+        Ok::<_, DataFusionError>(writer)
+    })
+    .await
+    .map_err(join_error)??;
+
+    let mut state = shared.state.lock().unwrap();
+    state.spill_file = Some(written.file.clone());
+    state.entries.push_back(Entry::Spilled { offset, len });
+    *writer = Some(written);
+    Ok(())
+}
+
+/// The next batch of the buffer, and the reader of its spill file
+async fn consume(
+    shared: &Shared,
+    reader: Option<File>,
+) -> Option<(Result<RecordBatch>, Option<File>)> {
+    loop {
+        let (popped, finished) = {
+            let mut state = shared.state.lock().unwrap();
+            (state.pop(), state.finished)
+        };
+        let Some(popped) = popped else {
+            if finished {
+                return None;
+            }
+            shared.pushed.notified().await;
+            continue;
+        };
+        return Some(match popped {
+            Pop::Batch(batch) => {
+                shared.popped.notify_one();
+                (batch, reader)
+            }
+            Pop::Spilled { file, offset, len } => {
+                let read = read_spilled(file, reader, offset, len).await;
+                // The bytes can only be reused once they have been read
+                shared.state.lock().unwrap().release_spill(len);
+                shared.popped.notify_one();
+                match read {
+                    Ok((batch, reader)) => (Ok(batch), Some(reader)),
+                    Err(err) => (Err(err), None),
+                }
+            }
+        });
+    }
+}
+
+async fn read_spilled(
+    file: Arc<NamedTempFile>,
+    reader: Option<File>,
+    offset: u64,
+    len: usize,
+) -> Result<(RecordBatch, File)> {
+    tokio::task::spawn_blocking(move || {
+        let mut reader = match reader {
+            Some(reader) => reader,
+            None => file.reopen()?,
+        };
+        let mut encoded = vec![0; len];
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut encoded)?;
+        let batch = StreamReader::try_new(Cursor::new(encoded), None)?
+            .next()
+            .transpose()?
+            .ok_or_else(|| {
+                DataFusionError::Internal("a spilled batch of a buffer is empty".to_string())
+            })?;
+        Ok((batch, reader))
+    })
+    .await
+    .map_err(join_error)?
+}
+
+impl Stream for BufferedRecordBatchStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.batches.poll_next_unpin(cx)
+    }
+}
+
+impl RecordBatchStream for BufferedRecordBatchStream {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+impl Drop for BufferedRecordBatchStream {
+    fn drop(&mut self) {
+        self.producer.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use arrow_array::{cast::AsArray, types::Int32Type, Int32Array};
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use futures::TryStreamExt;
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    /// A stream of `num_batches` batches of 100 rows, which sends the index
+    /// of each batch to `polled` when it is polled
+    fn counted_stream(
+        num_batches: i32,
+        polled: mpsc::UnboundedSender<i32>,
+    ) -> SendableRecordBatchStream {
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "x",
+            DataType::Int32,
+            false,
+        )]));
+        let batch_schema = schema.clone();
+        let batches = stream::iter(0..num_batches).map(move |i| {
+            polled.send(i).unwrap();
+            let values = Int32Array::from_iter_values(i * 100..(i + 1) * 100);
+            Ok(RecordBatch::try_new(batch_schema.clone(), vec![Arc::new(values)]).unwrap())
+        });
+        Box::pin(RecordBatchStreamAdapter::new(schema, batches))
+    }
+
+    /// Wait for the next `count` batches to be polled
+    async fn wait_polled(polled: &mut mpsc::UnboundedReceiver<i32>, count: usize) {
+        for _ in 0..count {
+            tokio::time::timeout(Duration::from_secs(10), polled.recv())
+                .await
+                .expect("the input was not polled")
+                .unwrap();
+        }
+    }
+
+    fn values(batches: &[RecordBatch]) -> Vec<i32> {
+        batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_buffered_stream_backpressure() {
+        let (sender, mut polled) = mpsc::unbounded_channel();
+        let mut stream = BufferedRecordBatchStream::new(
+            counted_stream(10, sender),
+            BufferOptions {
+                max_rows: 300,
+                ..Default::default()
+            },
+        );
+
+        // The producer fills the buffer, and waits for it to be consumed
+        // with the fourth batch.  The test runtime has a single thread, so the
+        // producer is waiting by the time the fourth batch is received.
+        wait_polled(&mut polled, 4).await;
+        tokio::task::yield_now().await;
+        assert!(polled.try_recv().is_err());
+
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.num_rows(), 100);
+        let rest = stream.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(values(&rest), (100..1000).collect::<Vec<_>>());
+        wait_polled(&mut polled, 6).await;
+    }
+
+    #[tokio::test]
+    async fn test_buffered_stream_spill() {
+        let (sender, mut polled) = mpsc::unbounded_channel();
+        let mut stream = BufferedRecordBatchStream::new(
+            counted_stream(10, sender),
+            BufferOptions {
+                max_rows: 200,
+                max_spill_bytes: Some(1024 * 1024),
+                ..Default::default()
+            },
+        );
+
+        // Batches beyond the memory limit are spilled, so the input is
+        // drained before anything is consumed
+        wait_polled(&mut polled, 10).await;
+
+        let mut batches = vec![stream.next().await.unwrap().unwrap()];
+        batches.extend(stream.try_collect::<Vec<_>>().await.unwrap());
+        assert_eq!(values(&batches), (0..1000).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_buffered_stream_spill_budget() {
+        let (sender, mut polled) = mpsc::unbounded_channel();
+        let stream = BufferedRecordBatchStream::new(
+            counted_stream(10, sender),
+            BufferOptions {
+                max_rows: 100,
+                // Room for a single spilled batch
+                max_spill_bytes: Some(1),
+                ..Default::default()
+            },
+        );
+
+        // One batch in memory, one spilled, and one waiting for room
+        wait_polled(&mut polled, 3).await;
+        let batches = stream.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(values(&batches), (0..1000).collect::<Vec<_>>());
+        wait_polled(&mut polled, 7).await;
+    }
+
+    #[tokio::test]
+    async fn test_buffered_stream_error() {
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "x",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10))],
+        )
+        .unwrap();
+        let input = stream::iter(vec![
+            Ok(batch),
+            Err(DataFusionError::Execution("input failed".to_string())),
+        ]);
+        let stream = BufferedRecordBatchStream::new(
+            Box::pin(RecordBatchStreamAdapter::new(schema, input)),
+            BufferOptions::default(),
+        );
+        let results = stream.collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+    }
+}
@@ -21,16 +21,25 @@ struct BatchReaderChunker {
     buffered: VecDeque<RecordBatch>,
     /// The number of rows to yield in each chunk
     output_size: usize,
+    /// The most bytes to yield in each chunk, estimated from the in-memory
+    /// size of the batches
+    max_bytes: usize,
     /// The position within the first batch in the buffer to start yielding from
     i: usize,
 }
 
+/// The average in-memory size of the rows of a batch
+fn row_size(batch: &RecordBatch) -> usize {
+    batch.get_array_memory_size() / batch.num_rows().max(1)
+}
+
 impl BatchReaderChunker {
-    fn new(inner: SendableRecordBatchStream, output_size: usize) -> Self {
+    fn new(inner: SendableRecordBatchStream, output_size: usize, max_bytes: usize) -> Self {
         Self {
             inner,
             buffered: VecDeque::new(),
             output_size,
+            max_bytes,
             i: 0,
         }
     }
@@ -40,8 +49,18 @@ impl BatchReaderChunker {
         buffer_total - self.i
     }
 
+    fn buffered_bytes(&self) -> usize {
+        let buffer_total: usize = self
+            .buffered
+            .iter()
+            .map(|batch| batch.num_rows() * row_size(batch))
+            .sum();
+        let skipped = self.buffered.front().map(|batch| self.i * row_size(batch));
+        buffer_total - skipped.unwrap_or(0)
+    }
+
     async fn fill_buffer(&mut self) -> Result<()> {
-        while self.buffered_len() < self.output_size {
+        while self.buffered_len() < self.output_size && self.buffered_bytes() < self.max_bytes {
             match self.inner.next().await {
                 Some(Ok(batch)) => self.buffered.push_back(batch),
                 Some(Err(e)) => return Err(e.into()),
@@ -60,17 +79,27 @@ impl BatchReaderChunker {
         let mut batches = Vec::new();
 
         let mut rows_collected = 0;
+        let mut bytes_collected = 0;
 
-        while rows_collected < self.output_size {
+        while rows_collected < self.output_size && bytes_collected < self.max_bytes {
             if let Some(batch) = self.buffered.pop_front() {
                 // Skip empty batch
                 if batch.num_rows() == 0 {
                     continue;
                 }
 
+                let row_size = row_size(&batch);
+                let rows_fitting = (self.max_bytes - bytes_collected) / row_size.max(1);
+                if rows_fitting == 0 && rows_collected > 0 {
+                    self.buffered.push_front(batch);
+                    break;
+                }
+
                 let rows_remaining_in_batch = batch.num_rows() - self.i;
-                let rows_to_take =
-                    std::cmp::min(rows_remaining_in_batch, self.output_size - rows_collected);
+                // Take at least one row, even if it is larger than max_bytes
+                let rows_to_take = rows_remaining_in_batch
+                    .min(self.output_size - rows_collected)
+                    .min(rows_fitting.max(1));
 
                 if rows_to_take == rows_remaining_in_batch {
                     // We're taking the whole batch, so we can just move it
@@ -91,6 +120,7 @@ impl BatchReaderChunker {
                 }
 
                 rows_collected += rows_to_take;
+                bytes_collected += rows_to_take * row_size;
             } else {
                 break;
             }
@@ -108,7 +138,19 @@ pub fn chunk_stream(
     stream: SendableRecordBatchStream,
     chunk_size: usize,
 ) -> Pin<Box<dyn Stream<Item = Result<Vec<RecordBatch>>> + Send>> {
-    let chunker = BatchReaderChunker::new(stream, chunk_size);
+    chunk_stream_with_max_bytes(stream, chunk_size, usize::MAX)
+}
+
+/// Like [`chunk_stream`], but chunks also hold at most about `max_bytes`,
+/// estimated from the in-memory size of the batches.
+///
+/// A chunk holds at least one row, even if the row is larger than `max_bytes`.
+pub fn chunk_stream_with_max_bytes(
+    stream: SendableRecordBatchStream,
+    chunk_size: usize,
+    max_bytes: usize,
+) -> Pin<Box<dyn Stream<Item = Result<Vec<RecordBatch>>> + Send>> {
+    let chunker = BatchReaderChunker::new(stream, chunk_size, max_bytes);
     futures::stream::unfold(chunker, |mut chunker| async move {
         match chunker.next().await {
             Some(Ok(batches)) => Some((Ok(batches), chunker)),
@@ -138,3 +180,109 @@ pub fn chunk_concat_stream(
         .boxed();
     Box::pin(RecordBatchStreamAdapter::new(schema_copy, chunk_concat))
 }
+
+/// Normalizes `stream` into batches of `batch_size` rows, or of about
+/// `max_bytes` if that is fewer rows, except for the last batch.
+///
+/// Large batches are sliced without copying, and only batches made of
+/// several smaller batches are copied.
+pub fn rechunk_stream(
+    stream: SendableRecordBatchStream,
+    batch_size: usize,
+    max_bytes: usize,
+) -> SendableRecordBatchStream {
+    let schema = stream.schema();
+    let concat_schema = schema.clone();
+    let rechunked = chunk_stream_with_max_bytes(stream, batch_size, max_bytes)
+        .and_then(move |mut batches| {
+            std::future::ready(if batches.len() == 1 {
+                Ok(batches.pop().unwrap())
+            } else {
+                kernels::concat::concat_batches(&concat_schema, batches.iter())
+                    .map_err(|e| e.into())
+            })
+        })
+        .map_err(DataFusionError::from)
+        .boxed();
+    Box::pin(RecordBatchStreamAdapter::new(schema, rechunked))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+
+    use super::*;
+
+    fn stream_of(batches: Vec<RecordBatch>) -> SendableRecordBatchStream {
+        let schema = batches[0].schema();
+        Box::pin(RecordBatchStreamAdapter::new(
+            schema,
+            futures::stream::iter(batches.into_iter().map(Ok)),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_rechunk_stream() {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let batch = |values: std::ops::Range<i32>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from_iter_values(values))],
+            )
+            .unwrap()
+        };
+
+        // Tiny batches are coalesced
+        let tiny = (0..25).map(|i| batch(i * 2..(i + 1) * 2)).collect();
+        let batches = rechunk_stream(stream_of(tiny), 20, usize::MAX)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let sizes = batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![20, 20, 10]);
+        assert_eq!(batches[1], batch(20..40));
+
+        // Huge batches are sliced
+        let huge = batch(0..50);
+        let batches = rechunk_stream(stream_of(vec![huge.clone()]), 20, usize::MAX)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(
+            batches,
+            vec![huge.slice(0, 20), huge.slice(20, 20), huge.slice(40, 10)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chunk_max_bytes() {
+        let schema = Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, false)]));
+        let values = (0..100).map(|_| "x".repeat(1000)).collect::<Vec<_>>();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringArray::from_iter_values(values))],
+        )
+        .unwrap();
+        let max_bytes = row_size(&batch) * 30;
+
+        let chunks = chunk_stream_with_max_bytes(stream_of(vec![batch.clone()]), 1000, max_bytes)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let sizes = chunks
+            .iter()
+            .map(|chunk| chunk.iter().map(|b| b.num_rows()).sum::<usize>())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![30, 30, 30, 10]);
+
+        // Rows larger than max_bytes are yielded one at a time
+        let chunks = chunk_stream_with_max_bytes(stream_of(vec![batch]), 1000, 1)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 100);
+    }
+}
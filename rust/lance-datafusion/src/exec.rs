@@ -3,40 +3,65 @@
 
 //! Utilities for working with datafusion execution plans
 
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
 
-use arrow_schema::Schema as ArrowSchema;
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow_array::{RecordBatch, RecordBatchReader};
+use arrow_schema::{Schema as ArrowSchema, SchemaRef};
 use datafusion::{
     dataframe::DataFrame,
     datasource::streaming::StreamingTable,
     execution::{
         context::{SessionConfig, SessionContext, SessionState},
         disk_manager::DiskManagerConfig,
-        memory_pool::FairSpillPool,
+        memory_pool::{FairSpillPool, MemoryConsumer, MemoryPool, MemoryReservation},
         runtime_env::{RuntimeConfig, RuntimeEnv},
         TaskContext,
     },
     physical_plan::{
+        coalesce_partitions::CoalescePartitionsExec, stream::RecordBatchStreamAdapter,
         streaming::PartitionStream, DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties,
-        SendableRecordBatchStream,
+        RecordBatchStream, SendableRecordBatchStream,
     },
 };
 use datafusion_common::{DataFusionError, Statistics};
 use datafusion_physical_expr::{EquivalenceProperties, Partitioning};
+use futures::future::{self, BoxFuture, Either};
+use futures::{stream, FutureExt, StreamExt};
 
 use lance_arrow::SchemaExt;
-use lance_core::Result;
+use lance_core::utils::temp::temp_files;
+use lance_core::{Error, Result};
 use log::{info, warn};
+use snafu::{location, Location};
+use tempfile::NamedTempFile;
+use tokio::runtime::Runtime;
+use tokio_util::sync::CancellationToken;
+
+use crate::record::{record_plan, PlanRecorder};
+use crate::trace::trace_plan;
+use crate::utils::reader_to_stream;
 
 /// An source execution node created from an existing stream
 ///
 /// It can only be used once, and will return the stream.  After that the node
-/// is exhuasted.
+/// is exhuasted, unless it was created with [`OneShotExec::new_replayable`].
 ///
 /// Note: the stream should be finite, otherwise we will report datafusion properties
 /// incorrectly.
 pub struct OneShotExec {
     stream: Mutex<Option<SendableRecordBatchStream>>,
+    /// The stream and the batches it returned so far, if the node is
+    /// replayable
+    replay: Option<Arc<tokio::sync::Mutex<ReplayBuffer>>>,
     // We save off a copy of the schema to speed up formatting and so ExecutionPlan::schema & display_as
     // can still function after exhuasted
     schema: Arc<ArrowSchema>,
@@ -47,8 +72,42 @@ impl OneShotExec {
     /// Create a new instance from a given stream
     pub fn new(stream: SendableRecordBatchStream) -> Self {
         let schema = stream.schema().clone();
+        Self::from_parts(Some(stream), None, schema)
+    }
+
+    /// Create a new instance from a synchronous reader
+    ///
+    /// The reader is called on the blocking thread pool, so it may do blocking
+    /// I/O, such as reading an Arrow IPC or CSV file.
+    pub fn from_reader(reader: Box<dyn RecordBatchReader + Send>) -> Self {
+        Self::new(reader_to_stream(reader))
+    }
+
+    /// Create an instance that can be executed more than once
+    ///
+    /// The batches read from `stream` are kept, in memory up to
+    /// `spill_threshold` bytes and in a temporary file after that.  Each
+    /// execution replays the batches kept so far, and then reads on from
+    /// `stream`, so executions can overlap, and an execution dropped early
+    /// leaves the rest of the stream to the next one.
+    pub fn new_replayable(stream: SendableRecordBatchStream, spill_threshold: usize) -> Self {
+        let schema = stream.schema().clone();
+        let replay = ReplayBuffer::new(stream, spill_threshold);
+        Self::from_parts(
+            None,
+            Some(Arc::new(tokio::sync::Mutex::new(replay))),
+            schema,
+        )
+    }
+
+    fn from_parts(
+        stream: Option<SendableRecordBatchStream>,
+        replay: Option<Arc<tokio::sync::Mutex<ReplayBuffer>>>,
+        schema: SchemaRef,
+    ) -> Self {
         Self {
-            stream: Mutex::new(Some(stream)),
+            stream: Mutex::new(stream),
+            replay,
             schema: schema.clone(),
             properties: PlanProperties::new(
                 EquivalenceProperties::new(schema),
@@ -57,13 +116,162 @@ impl OneShotExec {
             ),
         }
     }
+
+    fn is_exhausted(&self) -> bool {
+        self.stream.lock().unwrap().is_none() && self.replay.is_none()
+    }
+}
+
+fn join_error(err: tokio::task::JoinError) -> DataFusionError {
+    DataFusionError::External(Box::new(err))
+}
+
+/// The stream of a replayable [`OneShotExec`], and the batches it returned
+struct ReplayBuffer {
+    /// The rest of the stream, until it has been read to the end
+    source: Option<SendableRecordBatchStream>,
+    spill_threshold: usize,
+    batches: Vec<RecordBatch>,
+    num_bytes: usize,
+    /// Batches after the first `spill_threshold` bytes, in Arrow IPC format
+    spill_file: Option<Arc<NamedTempFile>>,
+    spill_writer: Option<StreamWriter<BufWriter<File>>>,
+    num_spilled: usize,
+}
+
+impl ReplayBuffer {
+    fn new(source: SendableRecordBatchStream, spill_threshold: usize) -> Self {
+        Self {
+            source: Some(source),
+            spill_threshold,
+            batches: Vec::new(),
+            num_bytes: 0,
+            spill_file: None,
+            spill_writer: None,
+            num_spilled: 0,
+        }
+    }
+
+    async fn push(&mut self, batch: &RecordBatch) -> datafusion_common::Result<()> {
+        let batch_bytes = batch.get_array_memory_size();
+        // Once a batch is spilled, all later batches are too, to keep their order
+        if self.spill_file.is_none() && self.num_bytes + batch_bytes <= self.spill_threshold {
+            self.num_bytes += batch_bytes;
+            self.batches.push(batch.clone());
+            return Ok(());
+        }
+
+        let spill_file = self.spill_file.clone();
+        let writer = self.spill_writer.take();
+        let batch = batch.clone();
+        let (spill_file, writer) = tokio::task::spawn_blocking(move || {
+            let (spill_file, mut writer) = match (spill_file, writer) {
+                (Some(spill_file), Some(writer)) => (spill_file, writer),
+                (None, _) => {
+                    let spill_file = Arc::new(temp_files().new_file()?);
+                    let writer = BufWriter::new(spill_file.reopen()?);
+                    let writer = StreamWriter::try_new(writer, batch.schema().as_ref())?;
+                    (spill_file, writer)
+                }
+                (Some(_), None) => {
+                    return Err(DataFusionError::Execution(
+                        "OneShotExec cannot keep batches after failing to spill one".to_string(),
+                    ))
+                }
+            };
+            writer.write(&batch)?;
+            // Replays read the batch while the file is still written
+            writer.get_mut().flush()?;
+            Ok::<_, DataFusionError>((spill_file, writer))
+        })
+        .await
+        .map_err(join_error)??;
+        self.spill_file = Some(spill_file);
+        self.spill_writer = Some(writer);
+        self.num_spilled += 1;
+        Ok(())
+    }
+
+    /// Read the next batch of the stream, and keep it
+    async fn read_next(&mut self) -> datafusion_common::Result<Option<RecordBatch>> {
+        let Some(source) = self.source.as_mut() else {
+            return Ok(None);
+        };
+        match source.next().await.transpose()? {
+            Some(batch) => {
+                self.push(&batch).await?;
+                Ok(Some(batch))
+            }
+            None => {
+                self.source = None;
+                self.spill_writer = None;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// An execution of a replayable [`OneShotExec`]: the position it read up to
+/// in the batches of its [`ReplayBuffer`]
+struct ReplayCursor {
+    buffer: Arc<tokio::sync::Mutex<ReplayBuffer>>,
+    position: usize,
+    spill_reader: Option<StreamReader<BufReader<File>>>,
+}
+
+impl ReplayCursor {
+    async fn next(&mut self) -> datafusion_common::Result<Option<RecordBatch>> {
+        let buffer = self.buffer.clone().lock_owned().await;
+        if let Some(batch) = buffer.batches.get(self.position) {
+            self.position += 1;
+            return Ok(Some(batch.clone()));
+        }
+
+        if self.position < buffer.batches.len() + buffer.num_spilled {
+            // The spilled batches are read in order, from a reader of this
+            // execution
+            let spill_file = buffer.spill_file.clone();
+            drop(buffer);
+            let reader = self.spill_reader.take();
+            let (batch, reader) = tokio::task::spawn_blocking(move || {
+                let mut reader = match (reader, spill_file) {
+                    (Some(reader), _) => reader,
+                    (None, Some(spill_file)) => StreamReader::try_new(spill_file.reopen()?, None)?,
+                    (None, None) => unreachable!("batches are spilled to a file"),
+                };
+                let batch = reader.next().transpose()?;
+                Ok::<_, DataFusionError>((batch, reader))
+            })
+            .await
+            .map_err(join_error)??;
+            self.spill_reader = Some(reader);
+            self.position += 1;
+            return batch.map(Some).ok_or_else(|| {
+                DataFusionError::Internal("A spilled batch is missing".to_string())
+            });
+        }
+
+        // Read on from the stream, keeping the lock so that the batches are
+        // kept in order. The read runs in a task of its own, so that the batch
+        // is kept even if this execution is dropped meanwhile.
+        let batch = tokio::spawn(async move {
+            let mut buffer = buffer;
+            buffer.read_next().await
+        })
+        .await
+        .map_err(join_error)??;
+        if batch.is_some() {
+            self.position += 1;
+        }
+        Ok(batch)
+    }
 }
 
 impl std::fmt::Debug for OneShotExec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let stream = self.stream.lock().unwrap();
         f.debug_struct("OneShotExec")
-            .field("exhausted", &stream.is_none())
+            .field("exhausted", &self.is_exhausted())
+            .field("replayable", &self.replay.is_some())
             .field("schema", self.schema.as_ref())
             .finish()
     }
@@ -75,10 +283,13 @@ impl DisplayAs for OneShotExec {
         t: datafusion::physical_plan::DisplayFormatType,
         f: &mut std::fmt::Formatter,
     ) -> std::fmt::Result {
-        let stream = self.stream.lock().unwrap();
         match t {
             DisplayFormatType::Default | DisplayFormatType::Verbose => {
-                let exhausted = if stream.is_some() { "" } else { "EXHUASTED " };
+                let exhausted = if self.is_exhausted() {
+                    "EXHUASTED "
+                } else {
+                    ""
+                };
                 let columns = self
                     .schema
                     .field_names()
@@ -111,9 +322,15 @@ impl ExecutionPlan for OneShotExec {
 
     fn with_new_children(
         self: Arc<Self>,
-        _children: Vec<Arc<dyn ExecutionPlan>>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> datafusion_common::Result<Arc<dyn ExecutionPlan>> {
-        todo!()
+        if children.is_empty() {
+            Ok(self)
+        } else {
+            Err(DataFusionError::Internal(
+                "OneShotExec cannot be assigned children".to_string(),
+            ))
+        }
     }
 
     fn execute(
@@ -126,12 +343,25 @@ impl ExecutionPlan for OneShotExec {
             .lock()
             .map_err(|err| DataFusionError::Execution(err.to_string()))?
             .take();
-        if let Some(stream) = stream {
-            Ok(stream)
-        } else {
-            Err(DataFusionError::Execution(
+        match (stream, &self.replay) {
+            (Some(stream), _) => Ok(stream),
+            (None, Some(replay)) => {
+                let cursor = ReplayCursor {
+                    buffer: replay.clone(),
+                    position: 0,
+                    spill_reader: None,
+                };
+                let batches = stream::try_unfold(cursor, |mut cursor| async move {
+                    Ok(cursor.next().await?.map(|batch| (batch, cursor)))
+                });
+                Ok(Box::pin(RecordBatchStreamAdapter::new(
+                    self.schema.clone(),
+                    batches,
+                )))
+            }
+            (None, None) => Err(DataFusionError::Execution(
                 "OneShotExec has already been executed".to_string(),
-            ))
+            )),
         }
     }
 
@@ -147,7 +377,93 @@ impl ExecutionPlan for OneShotExec {
 #[derive(Debug, Default, Clone)]
 pub struct LanceExecutionOptions {
     pub use_spilling: bool,
+    /// The size of the memory pool, if not set `LANCE_MEM_POOL_SIZE` is used
     pub mem_pool_size: Option<u64>,
+    /// Whether to disable spilling, if not set spilling is disabled when
+    /// `LANCE_BYPASS_SPILLING` is set
+    pub bypass_spilling: Option<bool>,
+    /// Whether to execute plans with more than one partition
+    ///
+    /// If true, all partitions are executed concurrently and their batches
+    /// are interleaved in the order they are produced.  Otherwise, executing
+    /// a plan with more than one partition fails.
+    pub execute_all_partitions: bool,
+    /// Cancel the execution when this token is cancelled
+    ///
+    /// The stream then returns an error and drops the streams of the plan,
+    /// which releases the resources they hold, such as spill files.
+    pub cancellation_token: Option<CancellationToken>,
+    /// Cancel the execution if it hasn't finished this long after
+    /// `execute_plan` was called
+    pub timeout: Option<Duration>,
+    /// The memory pool to account the memory of the execution in, instead
+    /// of a pool of `mem_pool_size` private to the execution
+    ///
+    /// Use this to share a pool with other DataFusion plans.
+    pub memory_pool: Option<Arc<dyn MemoryPool>>,
+    /// The runtime to execute the plan in
+    ///
+    /// The runtime's memory pool and disk manager are used as they are,
+    /// regardless of the other options.
+    pub runtime_env: Option<Arc<RuntimeEnv>>,
+    /// The directories to spill to, if not set the directory of the temporary
+    /// files of the process is used, see [`temp_files`]
+    pub spill_dirs: Option<Vec<PathBuf>>,
+    /// Fail the execution once its plan spilled more than this many bytes
+    ///
+    /// This is checked each time the plan produces a batch, so nodes that
+    /// spill all their input before producing their first batch, such as
+    /// sorts, can exceed it by the size of their input.
+    pub max_spill_bytes: Option<usize>,
+    /// Whether to skip the tracing spans around the execution of each node
+    ///
+    /// Set this for plans executed in hot paths, where the spans cost more
+    /// than they tell.
+    pub bypass_tracing: bool,
+    /// Execute the plan on a multi-threaded runtime of its own, instead of
+    /// the runtime that polls the stream
+    ///
+    /// Batches are sent back to the caller over a channel.  Use this for
+    /// heavy scans that would otherwise starve the caller's runtime.
+    pub dedicated_runtime: Option<DedicatedRuntimeOptions>,
+    /// Record the memory the execution reserved and spilled into these
+    /// stats, to read once the stream is done
+    ///
+    /// Only the reservations of this execution are counted, even if its
+    /// memory pool is shared with other executions.
+    pub memory_stats: Option<Arc<ExecutionMemoryStats>>,
+    /// Record the batches flowing through each node of the plan with this
+    /// recorder, to replay them when debugging the execution
+    ///
+    /// See [`crate::record`].
+    pub recorder: Option<Arc<PlanRecorder>>,
+}
+
+/// Options of the runtime dedicated to the execution of a plan, see
+/// [`LanceExecutionOptions::dedicated_runtime`]
+#[derive(Debug, Default, Clone)]
+pub struct DedicatedRuntimeOptions {
+    /// The number of worker threads, if not set the number of CPUs
+    pub num_threads: Option<usize>,
+    /// The prefix of the names of the worker threads, if not set `lance-exec`
+    pub thread_name_prefix: Option<String>,
+}
+
+impl DedicatedRuntimeOptions {
+    fn build(&self) -> Result<Runtime> {
+        let prefix = self
+            .thread_name_prefix
+            .clone()
+            .unwrap_or_else(|| "lance-exec".to_string());
+        let thread_id = AtomicUsize::new(0);
+        Ok(tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(self.num_threads.unwrap_or_else(num_cpus::get).max(1))
+            .thread_name_fn(move || {
+                format!("{}-{}", prefix, thread_id.fetch_add(1, Ordering::Relaxed))
+            })
+            .enable_all()
+            .build()?)
+    }
 }
 
 const DEFAULT_LANCE_MEM_POOL_SIZE: u64 = 100 * 1024 * 1024;
@@ -171,6 +487,9 @@ impl LanceExecutionOptions {
         if !self.use_spilling {
             return false;
         }
+        if let Some(bypass_spilling) = self.bypass_spilling {
+            return !bypass_spilling;
+        }
         std::env::var("LANCE_BYPASS_SPILLING")
             .map(|_| {
                 info!("Bypassing spilling because LANCE_BYPASS_SPILLING is set");
@@ -182,25 +501,396 @@ impl LanceExecutionOptions {
 
 /// Executes a plan using default session & runtime configuration
 ///
-/// Only executes a single partition.  Panics if the plan has more than one partition.
+/// Plans with more than one partition are only executed if
+/// [`LanceExecutionOptions::execute_all_partitions`] is set.
+///
+/// Unless [`LanceExecutionOptions::bypass_tracing`] is set, each node is
+/// executed in a tracing span, see [`crate::trace::TracedExec`].
 pub fn execute_plan(
     plan: Arc<dyn ExecutionPlan>,
     options: LanceExecutionOptions,
 ) -> Result<SendableRecordBatchStream> {
     let session_config = SessionConfig::new();
+    let runtime_env = match &options.runtime_env {
+        Some(runtime_env) => runtime_env.clone(),
+        None => build_runtime_env(&options)?,
+    };
+    let runtime_env = match &options.memory_stats {
+        Some(memory_stats) => track_memory(&runtime_env, memory_stats.clone()),
+        None => runtime_env,
+    };
+    let session_state = SessionState::new_with_config_rt(session_config, runtime_env);
+    let num_partitions = plan.properties().partitioning.partition_count();
+    let plan = if num_partitions == 1 {
+        plan
+    } else if options.execute_all_partitions {
+        Arc::new(CoalescePartitionsExec::new(plan))
+    } else {
+        // Executing only the first partition would silently drop data
+        return Err(Error::invalid_input(
+            format!(
+                "Cannot execute a plan with {} partitions unless execute_all_partitions is set",
+                num_partitions
+            ),
+            location!(),
+        ));
+    };
+    let plan = match &options.recorder {
+        Some(recorder) => record_plan(plan, recorder.clone())?,
+        None => plan,
+    };
+    let plan = if options.bypass_tracing {
+        plan
+    } else {
+        trace_plan(plan)?
+    };
+    let context = session_state.task_ctx();
+    let limits = SpillLimits {
+        max_spill_bytes: options.max_spill_bytes,
+        memory_stats: options.memory_stats.clone(),
+    };
+    let stream = match &options.dedicated_runtime {
+        Some(runtime_options) => {
+            execute_on_runtime(plan, context, limits, runtime_options.build()?)
+        }
+        None => execute_stream(plan, context, limits)?,
+    };
+    if options.cancellation_token.is_none() && options.timeout.is_none() {
+        return Ok(stream);
+    }
+    Ok(Box::pin(CancellableStream::new(
+        stream,
+        options.cancellation_token,
+        options.timeout,
+    )))
+}
+
+/// The runtime of the memory and spilling options of `options`
+pub(crate) fn build_runtime_env(options: &LanceExecutionOptions) -> Result<Arc<RuntimeEnv>> {
     let mut runtime_config = RuntimeConfig::new();
     if options.use_spilling() {
-        runtime_config.disk_manager = DiskManagerConfig::NewOs;
+        runtime_config.disk_manager = match &options.spill_dirs {
+            Some(spill_dirs) => DiskManagerConfig::NewSpecified(spill_dirs.clone()),
+            None => DiskManagerConfig::NewSpecified(vec![temp_files().create_dir()?.to_path_buf()]),
+        };
         runtime_config.memory_pool = Some(Arc::new(FairSpillPool::new(
             options.mem_pool_size() as usize
         )));
     }
-    let runtime_env = Arc::new(RuntimeEnv::new(runtime_config)?);
-    let session_state = SessionState::new_with_config_rt(session_config, runtime_env);
-    // NOTE: we are only executing the first partition here. Therefore, if
-    // the plan has more than one partition, we will be missing data.
-    assert_eq!(plan.properties().partitioning.partition_count(), 1);
-    Ok(plan.execute(0, session_state.task_ctx())?)
+    if let Some(memory_pool) = &options.memory_pool {
+        runtime_config.memory_pool = Some(memory_pool.clone());
+    }
+    Ok(Arc::new(RuntimeEnv::new(runtime_config)?))
+}
+
+/// `runtime_env`, with the reservations made in its memory pool recorded
+/// into `memory_stats`
+pub(crate) fn track_memory(
+    runtime_env: &RuntimeEnv,
+    memory_stats: Arc<ExecutionMemoryStats>,
+) -> Arc<RuntimeEnv> {
+    Arc::new(RuntimeEnv {
+        memory_pool: Arc::new(TrackedMemoryPool {
+            inner: runtime_env.memory_pool.clone(),
+            stats: memory_stats,
+        }),
+        disk_manager: runtime_env.disk_manager.clone(),
+        cache_manager: runtime_env.cache_manager.clone(),
+        object_store_registry: runtime_env.object_store_registry.clone(),
+    })
+}
+
+/// What to check and record of the spills of a plan, each time it produces
+/// a batch
+#[derive(Clone)]
+struct SpillLimits {
+    max_spill_bytes: Option<usize>,
+    memory_stats: Option<Arc<ExecutionMemoryStats>>,
+}
+
+fn execute_stream(
+    plan: Arc<dyn ExecutionPlan>,
+    context: Arc<TaskContext>,
+    limits: SpillLimits,
+) -> datafusion_common::Result<SendableRecordBatchStream> {
+    let stream = plan.execute(0, context)?;
+    if limits.max_spill_bytes.is_none() && limits.memory_stats.is_none() {
+        return Ok(stream);
+    }
+    let schema = stream.schema();
+    let limited = stream.map(move |batch| {
+        let spill_stats = SpillStats::from_plan(plan.as_ref());
+        if let Some(memory_stats) = &limits.memory_stats {
+            memory_stats.record_spills(spill_stats);
+        }
+        match limits.max_spill_bytes {
+            Some(max_spill_bytes) if spill_stats.spilled_bytes > max_spill_bytes => {
+                Err(DataFusionError::ResourcesExhausted(format!(
+                    "Plan execution spilled {} bytes, more than the limit of {} bytes",
+                    spill_stats.spilled_bytes, max_spill_bytes
+                )))
+            }
+            _ => batch,
+        }
+    });
+    Ok(Box::pin(RecordBatchStreamAdapter::new(schema, limited)))
+}
+
+/// Execute `plan` in a task of `runtime` and stream its batches back
+///
+/// The runtime is shut down once the returned stream is dropped, which
+/// also drops the stream of the plan if it hasn't finished.
+fn execute_on_runtime(
+    plan: Arc<dyn ExecutionPlan>,
+    context: Arc<TaskContext>,
+    limits: SpillLimits,
+    runtime: Runtime,
+) -> SendableRecordBatchStream {
+    let schema = plan.schema();
+    let (sender, receiver) = tokio::sync::mpsc::channel(2);
+    runtime.spawn(async move {
+        // Nodes may spawn tasks when they are executed, so the plan is
+        // executed in the runtime as well as polled in it
+        let mut stream = match execute_stream(plan, context, limits) {
+            Ok(stream) => stream,
+            Err(err) => {
+                let _ = sender.send(Err(err)).await;
+                return;
+            }
+        };
+        while let Some(batch) = stream.next().await {
+            if sender.send(batch).await.is_err() {
+                // The caller dropped the stream
+                return;
+            }
+        }
+    });
+    let runtime = DedicatedRuntime(Some(runtime));
+    let batches = stream::unfold((receiver, runtime), |(mut receiver, runtime)| async move {
+        let batch = receiver.recv().await?;
+        Some((batch, (receiver, runtime)))
+    });
+    Box::pin(RecordBatchStreamAdapter::new(schema, batches))
+}
+
+/// A runtime that is shut down without waiting for its tasks when dropped
+///
+/// Dropping a runtime normally blocks, which isn't allowed in the async
+/// context the stream of a plan is usually dropped in.
+struct DedicatedRuntime(Option<Runtime>);
+
+impl Drop for DedicatedRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// How much the nodes of an executed plan spilled to disk
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SpillStats {
+    /// Number of times nodes spilled
+    pub spill_count: usize,
+    /// Number of bytes spilled
+    pub spilled_bytes: usize,
+}
+
+impl SpillStats {
+    /// Sum what the nodes of `plan` spilled, so far if it is still executing
+    pub fn from_plan(plan: &dyn ExecutionPlan) -> Self {
+        let mut stats = plan
+            .children()
+            .iter()
+            .map(|child| Self::from_plan(child.as_ref()))
+            .fold(Self::default(), |stats, child| Self {
+                spill_count: stats.spill_count + child.spill_count,
+                spilled_bytes: stats.spilled_bytes + child.spilled_bytes,
+            });
+        if let Some(metrics) = plan.metrics() {
+            stats.spill_count += metrics.spill_count().unwrap_or(0);
+            stats.spilled_bytes += metrics.spilled_bytes().unwrap_or(0);
+        }
+        stats
+    }
+}
+
+/// The memory an execution reserved and spilled, see
+/// [`LanceExecutionOptions::memory_stats`]
+///
+/// Use this to size `mem_pool_size` from the peak memory of typical
+/// executions.
+#[derive(Debug, Default)]
+pub struct ExecutionMemoryStats {
+    reserved_bytes: AtomicUsize,
+    peak_bytes: AtomicUsize,
+    reservation_failures: AtomicUsize,
+    spill_count: AtomicUsize,
+    spilled_bytes: AtomicUsize,
+}
+
+impl ExecutionMemoryStats {
+    /// Bytes currently reserved by the execution
+    pub fn reserved_bytes(&self) -> usize {
+        self.reserved_bytes.load(Ordering::Relaxed)
+    }
+
+    /// The most bytes the execution reserved at once
+    pub fn peak_bytes(&self) -> usize {
+        self.peak_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Number of times the pool refused a reservation of the execution
+    ///
+    /// Nodes that can spill do so when a reservation is refused, the others
+    /// fail the execution.
+    pub fn reservation_failures(&self) -> usize {
+        self.reservation_failures.load(Ordering::Relaxed)
+    }
+
+    /// Number of times the nodes of the plan spilled, as of its last batch
+    pub fn spill_count(&self) -> usize {
+        self.spill_count.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes the nodes of the plan spilled, as of its last batch
+    pub fn spilled_bytes(&self) -> usize {
+        self.spilled_bytes.load(Ordering::Relaxed)
+    }
+
+    fn grow(&self, additional: usize) {
+        let reserved = self.reserved_bytes.fetch_add(additional, Ordering::Relaxed) + additional;
+        self.peak_bytes.fetch_max(reserved, Ordering::Relaxed);
+    }
+
+    fn shrink(&self, shrink: usize) {
+        self.reserved_bytes.fetch_sub(shrink, Ordering::Relaxed);
+    }
+
+    fn record_spills(&self, spill_stats: SpillStats) {
+        self.spill_count
+            .store(spill_stats.spill_count, Ordering::Relaxed);
+        self.spilled_bytes
+            .store(spill_stats.spilled_bytes, Ordering::Relaxed);
+    }
+}
+
+/// A memory pool recording the reservations made through it into
+/// [`ExecutionMemoryStats`]
+#[derive(Debug)]
+struct TrackedMemoryPool {
+    inner: Arc<dyn MemoryPool>,
+    stats: Arc<ExecutionMemoryStats>,
+}
+
+impl MemoryPool for TrackedMemoryPool {
+    fn register(&self, consumer: &MemoryConsumer) {
+        self.inner.register(consumer)
+    }
+
+    fn unregister(&self, consumer: &MemoryConsumer) {
+        self.inner.unregister(consumer)
+    }
+
+    fn grow(&self, reservation: &MemoryReservation, additional: usize) {
+        self.inner.grow(reservation, additional);
+        self.stats.grow(additional);
+    }
+
+    fn shrink(&self, reservation: &MemoryReservation, shrink: usize) {
+        self.inner.shrink(reservation, shrink);
+        self.stats.shrink(shrink);
+    }
+
+    fn try_grow(
+        &self,
+        reservation: &MemoryReservation,
+        additional: usize,
+    ) -> datafusion_common::Result<()> {
+        match self.inner.try_grow(reservation, additional) {
+            Ok(()) => {
+                self.stats.grow(additional);
+                Ok(())
+            }
+            Err(err) => {
+                self.stats
+                    .reservation_failures
+                    .fetch_add(1, Ordering::Relaxed);
+                Err(err)
+            }
+        }
+    }
+
+    fn reserved(&self) -> usize {
+        self.inner.reserved()
+    }
+}
+
+/// A stream that ends with an error once its execution is cancelled or
+/// times out, dropping the stream it wraps.
+struct CancellableStream {
+    schema: SchemaRef,
+    inner: Option<SendableRecordBatchStream>,
+    cancelled: BoxFuture<'static, DataFusionError>,
+}
+
+impl CancellableStream {
+    fn new(
+        inner: SendableRecordBatchStream,
+        cancellation_token: Option<CancellationToken>,
+        timeout: Option<Duration>,
+    ) -> Self {
+        let deadline = timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+        let cancelled = async move {
+            let cancelled = async {
+                match &cancellation_token {
+                    Some(token) => token.cancelled().await,
+                    None => future::pending().await,
+                }
+            };
+            let timed_out = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => future::pending().await,
+                }
+            };
+            match future::select(Box::pin(cancelled), Box::pin(timed_out)).await {
+                Either::Left(_) => {
+                    DataFusionError::Execution("Plan execution was cancelled".to_string())
+                }
+                Either::Right(_) => DataFusionError::Execution(format!(
+                    "Plan execution timed out after {:?}",
+                    timeout.unwrap_or_default()
+                )),
+            }
+        };
+        Self {
+            schema: inner.schema(),
+            inner: Some(inner),
+            cancelled: cancelled.boxed(),
+        }
+    }
+}
+
+impl stream::Stream for CancellableStream {
+    type Item = datafusion_common::Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.inner.is_none() {
+            return Poll::Ready(None);
+        }
+        if let Poll::Ready(err) = self.cancelled.as_mut().poll(cx) {
+            self.inner = None;
+            return Poll::Ready(Some(Err(err)));
+        }
+        self.inner.as_mut().unwrap().poll_next_unpin(cx)
+    }
+}
+
+impl RecordBatchStream for CancellableStream {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
 }
 
 pub trait SessionContextExt {
@@ -252,3 +942,375 @@ impl SessionContextExt for SessionContext {
         self.read_table(Arc::new(provider))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use arrow_array::{Int32Array, RecordBatchIterator};
+    use arrow_schema::{DataType, Field, SortOptions};
+    use datafusion::execution::memory_pool::{MemoryReservation, UnboundedMemoryPool};
+    use datafusion::physical_plan::{expressions, memory::MemoryExec, sorts::sort::SortExec};
+    use datafusion_physical_expr::PhysicalSortExpr;
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    fn batches(num_batches: i32) -> (SchemaRef, Vec<RecordBatch>) {
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "a",
+            DataType::Int32,
+            false,
+        )]));
+        let batches = (0..num_batches)
+            .map(|i| {
+                let values = Int32Array::from_iter_values(i * 10..(i + 1) * 10);
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(values)]).unwrap()
+            })
+            .collect();
+        (schema, batches)
+    }
+
+    #[tokio::test]
+    async fn test_cancel_execution() {
+        let (schema, batches) = batches(1);
+        // Never ends after its first batch
+        let make_plan = || {
+            let stream = stream::iter(batches.clone().into_iter().map(Ok)).chain(stream::pending());
+            Arc::new(OneShotExec::new(Box::pin(RecordBatchStreamAdapter::new(
+                schema.clone(),
+                stream,
+            ))))
+        };
+
+        let token = CancellationToken::new();
+        let options = LanceExecutionOptions {
+            cancellation_token: Some(token.clone()),
+            ..Default::default()
+        };
+        let mut stream = execute_plan(make_plan(), options).unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), batches[0]);
+        token.cancel();
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(err.to_string().contains("cancelled"), "{}", err);
+        assert!(stream.next().await.is_none());
+
+        let options = LanceExecutionOptions {
+            timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let mut stream = execute_plan(make_plan(), options).unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), batches[0]);
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dedicated_runtime() {
+        let (schema, expected) = batches(5);
+        let thread_names = Arc::new(Mutex::new(Vec::new()));
+        let names = thread_names.clone();
+        let source = stream::iter(expected.clone()).map(move |batch| {
+            let name = std::thread::current()
+                .name()
+                .unwrap_or_default()
+                .to_string();
+            names.lock().unwrap().push(name);
+            Ok(batch)
+        });
+        let plan = Arc::new(OneShotExec::new(Box::pin(RecordBatchStreamAdapter::new(
+            schema, source,
+        ))));
+
+        let options = LanceExecutionOptions {
+            dedicated_runtime: Some(DedicatedRuntimeOptions {
+                num_threads: Some(2),
+                thread_name_prefix: Some("test-exec".to_string()),
+            }),
+            ..Default::default()
+        };
+        let actual = execute_plan(plan, options)
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(actual, expected);
+        let thread_names = thread_names.lock().unwrap();
+        assert_eq!(thread_names.len(), 5);
+        assert!(
+            thread_names
+                .iter()
+                .all(|name| name.starts_with("test-exec-")),
+            "{:?}",
+            thread_names
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replayable_one_shot() {
+        let (schema, expected) = batches(5);
+        // Spill after the first two batches
+        let spill_threshold = expected[0].get_array_memory_size() * 2;
+        for (spill_threshold, replayable) in [
+            (usize::MAX, false),
+            (usize::MAX, true),
+            (spill_threshold, true),
+        ] {
+            let source = Box::pin(RecordBatchStreamAdapter::new(
+                schema.clone(),
+                stream::iter(expected.clone().into_iter().map(Ok)),
+            ));
+            let plan: Arc<dyn ExecutionPlan> = if replayable {
+                Arc::new(OneShotExec::new_replayable(source, spill_threshold))
+            } else {
+                Arc::new(OneShotExec::new(source))
+            };
+            // Optimizers rebuild the plan tree
+            let plan = plan.clone().with_new_children(vec![]).unwrap();
+            assert!(plan.clone().with_new_children(vec![plan.clone()]).is_err());
+
+            let ctx = Arc::new(TaskContext::default());
+            if !replayable {
+                let first = plan.execute(0, ctx.clone()).unwrap();
+                let actual = first.try_collect::<Vec<_>>().await.unwrap();
+                assert_eq!(actual, expected);
+                assert!(plan.execute(0, ctx.clone()).is_err());
+                continue;
+            }
+
+            // An execution dropped early leaves the rest of the stream to the
+            // next one
+            let mut first = plan.execute(0, ctx.clone()).unwrap();
+            for batch in &expected[..3] {
+                assert_eq!(&first.next().await.unwrap().unwrap(), batch);
+            }
+            drop(first);
+
+            // Executions overlap, replaying the batches kept so far
+            let mut second = plan.execute(0, ctx.clone()).unwrap();
+            let mut third = plan.execute(0, ctx.clone()).unwrap();
+            for batch in &expected {
+                assert_eq!(&second.next().await.unwrap().unwrap(), batch);
+                assert_eq!(&third.next().await.unwrap().unwrap(), batch);
+            }
+            assert!(second.next().await.is_none());
+            assert!(third.next().await.is_none());
+
+            let replayed = plan
+                .execute(0, ctx.clone())
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            assert_eq!(replayed, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_one_shot_from_reader() {
+        let (schema, expected) = batches(3);
+        let reader = RecordBatchIterator::new(expected.clone().into_iter().map(Ok), schema.clone());
+        let plan = OneShotExec::from_reader(Box::new(reader));
+        assert_eq!(plan.schema(), schema);
+
+        let ctx = Arc::new(TaskContext::default());
+        let actual = plan
+            .execute(0, ctx.clone())
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(actual, expected);
+        assert!(plan.execute(0, ctx).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_execute_all_partitions() {
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "a",
+            DataType::Int32,
+            false,
+        )]));
+        let partitions = (0..4)
+            .map(|i| {
+                let values = Int32Array::from_iter_values(i * 10..(i + 1) * 10);
+                vec![RecordBatch::try_new(schema.clone(), vec![Arc::new(values)]).unwrap()]
+            })
+            .collect::<Vec<_>>();
+        let plan = Arc::new(MemoryExec::try_new(&partitions, schema.clone(), None).unwrap());
+
+        let err = execute_plan(plan.clone(), LanceExecutionOptions::default())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("4 partitions"), "{err}");
+
+        let batches = execute_plan(
+            plan,
+            LanceExecutionOptions {
+                execute_all_partitions: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+        let mut values = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, (0..40).collect::<Vec<_>>());
+    }
+
+    /// A pool recording the most memory reserved at once
+    #[derive(Debug, Default)]
+    struct PeakPool {
+        inner: UnboundedMemoryPool,
+        peak: AtomicUsize,
+    }
+
+    impl MemoryPool for PeakPool {
+        fn grow(&self, reservation: &MemoryReservation, additional: usize) {
+            self.inner.grow(reservation, additional);
+            self.peak
+                .fetch_max(self.inner.reserved(), Ordering::Relaxed);
+        }
+
+        fn shrink(&self, reservation: &MemoryReservation, shrink: usize) {
+            self.inner.shrink(reservation, shrink);
+        }
+
+        fn try_grow(
+            &self,
+            reservation: &MemoryReservation,
+            additional: usize,
+        ) -> datafusion_common::Result<()> {
+            self.inner.try_grow(reservation, additional)?;
+            self.peak
+                .fetch_max(self.inner.reserved(), Ordering::Relaxed);
+            Ok(())
+        }
+
+        fn reserved(&self) -> usize {
+            self.inner.reserved()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shared_memory_pool() {
+        let (schema, batches) = batches(5);
+        let sort = || {
+            let input = MemoryExec::try_new(&[batches.clone()], schema.clone(), None).unwrap();
+            let sort_expr = PhysicalSortExpr {
+                expr: expressions::col("a", &schema).unwrap(),
+                options: SortOptions {
+                    descending: true,
+                    nulls_first: false,
+                },
+            };
+            Arc::new(SortExec::new(vec![sort_expr], Arc::new(input)))
+        };
+
+        let pool = Arc::new(PeakPool::default());
+        let options = LanceExecutionOptions {
+            memory_pool: Some(pool.clone()),
+            ..Default::default()
+        };
+        let sorted = execute_plan(sort(), options)
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(sorted.iter().map(|b| b.num_rows()).sum::<usize>(), 50);
+        assert!(pool.peak.load(Ordering::Relaxed) > 0);
+        assert_eq!(pool.reserved(), 0);
+
+        let pool = Arc::new(PeakPool::default());
+        let runtime_env =
+            RuntimeEnv::new(RuntimeConfig::new().with_memory_pool(pool.clone())).unwrap();
+        let options = LanceExecutionOptions {
+            runtime_env: Some(Arc::new(runtime_env)),
+            ..Default::default()
+        };
+        execute_plan(sort(), options)
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert!(pool.peak.load(Ordering::Relaxed) > 0);
+    }
+
+    #[tokio::test]
+    async fn test_spill_options() {
+        // 8MiB of data, sorted with a pool that can't hold it: 10MiB of the pool
+        // are reserved for merging the sorted runs, leaving 4MiB for the data
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "a",
+            DataType::Int32,
+            false,
+        )]));
+        let batches = (0..32)
+            .map(|i| {
+                let values = Int32Array::from_iter_values((0..65536).map(|j| j * 32 + i));
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(values)]).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let sort = || {
+            let input = MemoryExec::try_new(&[batches.clone()], schema.clone(), None).unwrap();
+            let sort_expr = PhysicalSortExpr {
+                expr: expressions::col("a", &schema).unwrap(),
+                options: SortOptions::default(),
+            };
+            Arc::new(SortExec::new(vec![sort_expr], Arc::new(input)))
+        };
+        let spill_dir = tempfile::tempdir().unwrap();
+        // The spills are read from the memory stats, which sum those of the
+        // executed plan, as the plan given is not the one executed
+        let memory_stats = Arc::new(ExecutionMemoryStats::default());
+        let options = LanceExecutionOptions {
+            use_spilling: true,
+            bypass_spilling: Some(false),
+            mem_pool_size: Some(14 * 1024 * 1024),
+            spill_dirs: Some(vec![spill_dir.path().to_path_buf()]),
+            memory_stats: Some(memory_stats.clone()),
+            ..Default::default()
+        };
+
+        let sorted = execute_plan(sort(), options.clone())
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(
+            sorted.iter().map(|b| b.num_rows()).sum::<usize>(),
+            32 * 65536
+        );
+        assert!(memory_stats.peak_bytes() > 0);
+        assert_eq!(memory_stats.reserved_bytes(), 0);
+        assert!(memory_stats.reservation_failures() > 0);
+        assert!(memory_stats.spill_count() > 0);
+        assert!(memory_stats.spilled_bytes() > 0);
+
+        let options = LanceExecutionOptions {
+            max_spill_bytes: Some(1024),
+            memory_stats: None,
+            ..options
+        };
+        let err = execute_plan(sort(), options)
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("spilled"), "{}", err);
+    }
+}
@@ -6,8 +6,12 @@
 use std::sync::Arc;
 
 use arrow::compute::cast;
+use arrow::datatypes::i256;
+use arrow_array::types::{
+    Decimal128Type, Decimal256Type, DecimalType, IntervalDayTimeType, IntervalMonthDayNanoType,
+};
 use arrow_array::{cast::AsArray, ArrayRef};
-use arrow_schema::{DataType, TimeUnit};
+use arrow_schema::{DataType, IntervalUnit, TimeUnit};
 use datafusion_common::ScalarValue;
 
 #[cfg(feature = "substrait")]
@@ -32,6 +36,129 @@ use {
 };
 
 const MS_PER_DAY: i64 = 86400000;
+const NS_PER_DAY: i128 = 86400000000000;
+
+/// The number of nanoseconds in one tick of `unit`.
+fn nanos_per_tick(unit: &TimeUnit) -> i128 {
+    match unit {
+        TimeUnit::Second => 1000000000,
+        TimeUnit::Millisecond => 1000000,
+        TimeUnit::Microsecond => 1000,
+        TimeUnit::Nanosecond => 1,
+    }
+}
+
+/// Convert a duration, given in nanoseconds, to a duration of type `ty`.
+///
+/// Returns None if the value would lose precision or overflow.
+fn coerce_duration(nanos: i128, ty: &DataType) -> Option<ScalarValue> {
+    let DataType::Duration(unit) = ty else {
+        return None;
+    };
+    let per_tick = nanos_per_tick(unit);
+    if nanos % per_tick != 0 {
+        return None;
+    }
+    let ticks = Some(i64::try_from(nanos / per_tick).ok()?);
+    Some(match unit {
+        TimeUnit::Second => ScalarValue::DurationSecond(ticks),
+        TimeUnit::Millisecond => ScalarValue::DurationMillisecond(ticks),
+        TimeUnit::Microsecond => ScalarValue::DurationMicrosecond(ticks),
+        TimeUnit::Nanosecond => ScalarValue::DurationNanosecond(ticks),
+    })
+}
+
+/// Convert an interval, given as its months, days and nanoseconds, to an
+/// interval or a duration of type `ty`.
+///
+/// Returns None if the value can't be represented exactly.
+fn coerce_interval(months: i32, days: i32, nanos: i64, ty: &DataType) -> Option<ScalarValue> {
+    match ty {
+        DataType::Interval(IntervalUnit::YearMonth) => {
+            (days == 0 && nanos == 0).then_some(ScalarValue::IntervalYearMonth(Some(months)))
+        }
+        DataType::Interval(IntervalUnit::DayTime) => {
+            if months != 0 || nanos % 1000000 != 0 {
+                return None;
+            }
+            let millis = i32::try_from(nanos / 1000000).ok()?;
+            Some(ScalarValue::IntervalDayTime(Some(
+                IntervalDayTimeType::make_value(days, millis),
+            )))
+        }
+        DataType::Interval(IntervalUnit::MonthDayNano) => Some(ScalarValue::IntervalMonthDayNano(
+            Some(IntervalMonthDayNanoType::make_value(months, days, nanos)),
+        )),
+        // Months don't have a fixed length, days are taken as 24 hours.
+        DataType::Duration(_) if months == 0 => {
+            coerce_duration(i128::from(days) * NS_PER_DAY + i128::from(nanos), ty)
+        }
+        _ => None,
+    }
+}
+
+/// Rescale a decimal value, given as its unscaled value and its scale, to a
+/// decimal of type `ty`.
+///
+/// Returns None if the value would lose digits or doesn't fit the precision.
+fn coerce_decimal(unscaled: i256, scale: i8, ty: &DataType) -> Option<ScalarValue> {
+    let (precision, target_scale) = match ty {
+        DataType::Decimal128(precision, scale) | DataType::Decimal256(precision, scale) => {
+            (*precision, *scale)
+        }
+        _ => return None,
+    };
+    let ten = i256::from_i128(10);
+    let value = if target_scale >= scale {
+        let factor = ten.checked_pow((target_scale as i32 - scale as i32) as u32)?;
+        unscaled.checked_mul(factor)?
+    } else {
+        let divisor = ten.checked_pow((scale as i32 - target_scale as i32) as u32)?;
+        if unscaled.checked_rem(divisor)? != i256::ZERO {
+            return None;
+        }
+        unscaled.checked_div(divisor)?
+    };
+    match ty {
+        DataType::Decimal128(..) => {
+            let value = value.to_i128()?;
+            Decimal128Type::validate_decimal_precision(value, precision).ok()?;
+            Some(ScalarValue::Decimal128(
+                Some(value),
+                precision,
+                target_scale,
+            ))
+        }
+        _ => {
+            Decimal256Type::validate_decimal_precision(value, precision).ok()?;
+            Some(ScalarValue::Decimal256(
+                Some(value),
+                precision,
+                target_scale,
+            ))
+        }
+    }
+}
+
+/// Coerce a float to a decimal of type `ty`.
+///
+/// The float is read as its shortest decimal representation, so that a
+/// literal such as `0.1` is the decimal `0.1`, not the closest binary value.
+fn coerce_float_to_decimal(value: impl ToString, ty: &DataType) -> Option<ScalarValue> {
+    // Rust never formats floats in scientific notation, and infinities and
+    // NaN fail to parse below.
+    let repr = value.to_string();
+    let (integer, fraction) = repr.split_once('.').unwrap_or((&repr, ""));
+    let fraction = fraction.trim_end_matches('0');
+    let unscaled = i256::from_string(&format!("{integer}{fraction}"))?;
+    coerce_decimal(unscaled, i8::try_from(fraction.len()).ok()?, ty)
+}
+
+/// Convert a decimal, given as its unscaled value and its scale, to a float.
+fn decimal_to_f64(unscaled: i256, scale: i8) -> Option<f64> {
+    let value = unscaled.to_string().parse::<f64>().ok()?;
+    Some(value / 10_f64.powi(scale as i32))
+}
 
 // This is slightly tedious but when we convert expressions from SQL strings to logical
 // datafusion expressions there is no type coercion that happens.  In other words "x = 7"
@@ -58,6 +185,9 @@ pub fn safe_coerce_scalar(value: &ScalarValue, ty: &DataType) -> Option<ScalarVa
             }
             DataType::Float32 => val.map(|v| ScalarValue::Float32(Some(f32::from(v)))),
             DataType::Float64 => val.map(|v| ScalarValue::Float64(Some(f64::from(v)))),
+            DataType::Decimal128(..) | DataType::Decimal256(..) => {
+                val.and_then(|v| coerce_decimal(i256::from_i128(i128::from(v)), 0, ty))
+            }
             _ => None,
         },
         ScalarValue::Int16(val) => match ty {
@@ -81,6 +211,9 @@ pub fn safe_coerce_scalar(value: &ScalarValue, ty: &DataType) -> Option<ScalarVa
             }
             DataType::Float32 => val.map(|v| ScalarValue::Float32(Some(f32::from(v)))),
             DataType::Float64 => val.map(|v| ScalarValue::Float64(Some(f64::from(v)))),
+            DataType::Decimal128(..) | DataType::Decimal256(..) => {
+                val.and_then(|v| coerce_decimal(i256::from_i128(i128::from(v)), 0, ty))
+            }
             _ => None,
         },
         ScalarValue::Int32(val) => match ty {
@@ -109,6 +242,9 @@ pub fn safe_coerce_scalar(value: &ScalarValue, ty: &DataType) -> Option<ScalarVa
             // clear users would want that anyways
             DataType::Float32 => val.map(|v| ScalarValue::Float32(Some(v as f32))),
             DataType::Float64 => val.map(|v| ScalarValue::Float64(Some(v as f64))),
+            DataType::Decimal128(..) | DataType::Decimal256(..) => {
+                val.and_then(|v| coerce_decimal(i256::from_i128(i128::from(v)), 0, ty))
+            }
             _ => None,
         },
         ScalarValue::Int64(val) => match ty {
@@ -137,6 +273,9 @@ pub fn safe_coerce_scalar(value: &ScalarValue, ty: &DataType) -> Option<ScalarVa
             // See above warning about lossy float conversion
             DataType::Float32 => val.map(|v| ScalarValue::Float32(Some(v as f32))),
             DataType::Float64 => val.map(|v| ScalarValue::Float64(Some(v as f64))),
+            DataType::Decimal128(..) | DataType::Decimal256(..) => {
+                val.and_then(|v| coerce_decimal(i256::from_i128(i128::from(v)), 0, ty))
+            }
             _ => None,
         },
         ScalarValue::UInt8(val) => match ty {
@@ -152,6 +291,9 @@ pub fn safe_coerce_scalar(value: &ScalarValue, ty: &DataType) -> Option<ScalarVa
             DataType::UInt64 => val.map(|v| ScalarValue::UInt64(Some(u64::from(v)))),
             DataType::Float32 => val.map(|v| ScalarValue::Float32(Some(f32::from(v)))),
             DataType::Float64 => val.map(|v| ScalarValue::Float64(Some(f64::from(v)))),
+            DataType::Decimal128(..) | DataType::Decimal256(..) => {
+                val.and_then(|v| coerce_decimal(i256::from_i128(i128::from(v)), 0, ty))
+            }
             _ => None,
         },
         ScalarValue::UInt16(val) => match ty {
@@ -171,6 +313,9 @@ pub fn safe_coerce_scalar(value: &ScalarValue, ty: &DataType) -> Option<ScalarVa
             DataType::UInt64 => val.map(|v| ScalarValue::UInt64(Some(u64::from(v)))),
             DataType::Float32 => val.map(|v| ScalarValue::Float32(Some(f32::from(v)))),
             DataType::Float64 => val.map(|v| ScalarValue::Float64(Some(f64::from(v)))),
+            DataType::Decimal128(..) | DataType::Decimal256(..) => {
+                val.and_then(|v| coerce_decimal(i256::from_i128(i128::from(v)), 0, ty))
+            }
             _ => None,
         },
         ScalarValue::UInt32(val) => match ty {
@@ -195,6 +340,9 @@ pub fn safe_coerce_scalar(value: &ScalarValue, ty: &DataType) -> Option<ScalarVa
             // See above warning about lossy float conversion
             DataType::Float32 => val.map(|v| ScalarValue::Float32(Some(v as f32))),
             DataType::Float64 => val.map(|v| ScalarValue::Float64(Some(v as f64))),
+            DataType::Decimal128(..) | DataType::Decimal256(..) => {
+                val.and_then(|v| coerce_decimal(i256::from_i128(i128::from(v)), 0, ty))
+            }
             _ => None,
         },
         ScalarValue::UInt64(val) => match ty {
@@ -223,16 +371,49 @@ pub fn safe_coerce_scalar(value: &ScalarValue, ty: &DataType) -> Option<ScalarVa
             // See above warning about lossy float conversion
             DataType::Float32 => val.map(|v| ScalarValue::Float32(Some(v as f32))),
             DataType::Float64 => val.map(|v| ScalarValue::Float64(Some(v as f64))),
+            DataType::Decimal128(..) | DataType::Decimal256(..) => {
+                val.and_then(|v| coerce_decimal(i256::from_i128(i128::from(v)), 0, ty))
+            }
             _ => None,
         },
         ScalarValue::Float32(val) => match ty {
             DataType::Float32 => Some(value.clone()),
             DataType::Float64 => val.map(|v| ScalarValue::Float64(Some(f64::from(v)))),
+            DataType::Decimal128(..) | DataType::Decimal256(..) => {
+                val.and_then(|v| coerce_float_to_decimal(v, ty))
+            }
             _ => None,
         },
         ScalarValue::Float64(val) => match ty {
             DataType::Float32 => val.map(|v| ScalarValue::Float32(Some(v as f32))),
             DataType::Float64 => Some(value.clone()),
+            DataType::Decimal128(..) | DataType::Decimal256(..) => {
+                val.and_then(|v| coerce_float_to_decimal(v, ty))
+            }
+            _ => None,
+        },
+        ScalarValue::Decimal128(val, _, scale) => match ty {
+            DataType::Decimal128(..) | DataType::Decimal256(..) => {
+                val.and_then(|v| coerce_decimal(i256::from_i128(v), *scale, ty))
+            }
+            DataType::Float32 => val
+                .and_then(|v| decimal_to_f64(i256::from_i128(v), *scale))
+                .map(|v| ScalarValue::Float32(Some(v as f32))),
+            DataType::Float64 => val
+                .and_then(|v| decimal_to_f64(i256::from_i128(v), *scale))
+                .map(|v| ScalarValue::Float64(Some(v))),
+            _ => None,
+        },
+        ScalarValue::Decimal256(val, _, scale) => match ty {
+            DataType::Decimal128(..) | DataType::Decimal256(..) => {
+                val.and_then(|v| coerce_decimal(v, *scale, ty))
+            }
+            DataType::Float32 => val
+                .and_then(|v| decimal_to_f64(v, *scale))
+                .map(|v| ScalarValue::Float32(Some(v as f32))),
+            DataType::Float64 => val
+                .and_then(|v| decimal_to_f64(v, *scale))
+                .map(|v| ScalarValue::Float64(Some(v))),
             _ => None,
         },
         ScalarValue::Utf8(val) => match ty {
@@ -377,6 +558,37 @@ pub fn safe_coerce_scalar(value: &ScalarValue, ty: &DataType) -> Option<ScalarVa
             DataType::Time64(TimeUnit::Nanosecond) => Some(value.clone()),
             _ => None,
         },
+        ScalarValue::DurationSecond(None)
+        | ScalarValue::DurationMillisecond(None)
+        | ScalarValue::DurationMicrosecond(None)
+        | ScalarValue::DurationNanosecond(None)
+        | ScalarValue::IntervalYearMonth(None)
+        | ScalarValue::IntervalDayTime(None)
+        | ScalarValue::IntervalMonthDayNano(None) => match ty {
+            DataType::Duration(_) | DataType::Interval(_) => ScalarValue::try_from(ty).ok(),
+            _ => None,
+        },
+        ScalarValue::DurationSecond(Some(seconds)) => {
+            coerce_duration(i128::from(*seconds) * nanos_per_tick(&TimeUnit::Second), ty)
+        }
+        ScalarValue::DurationMillisecond(Some(millis)) => coerce_duration(
+            i128::from(*millis) * nanos_per_tick(&TimeUnit::Millisecond),
+            ty,
+        ),
+        ScalarValue::DurationMicrosecond(Some(micros)) => coerce_duration(
+            i128::from(*micros) * nanos_per_tick(&TimeUnit::Microsecond),
+            ty,
+        ),
+        ScalarValue::DurationNanosecond(Some(nanos)) => coerce_duration(i128::from(*nanos), ty),
+        ScalarValue::IntervalYearMonth(Some(months)) => coerce_interval(*months, 0, 0, ty),
+        ScalarValue::IntervalDayTime(Some(value)) => {
+            let (days, millis) = IntervalDayTimeType::to_parts(*value);
+            coerce_interval(0, days, i64::from(millis) * 1000000, ty)
+        }
+        ScalarValue::IntervalMonthDayNano(Some(value)) => {
+            let (months, days, nanos) = IntervalMonthDayNanoType::to_parts(*value);
+            coerce_interval(months, days, nanos, ty)
+        }
         ScalarValue::LargeList(values) => {
             let values = values.clone() as ArrayRef;
             let new_values = cast(&values, ty).ok()?;
@@ -547,6 +759,119 @@ mod tests {
         },
     };
 
+    #[test]
+    fn test_decimal_coerce() {
+        let decimal128 = DataType::Decimal128(9, 2);
+        let decimal256 = DataType::Decimal256(50, 3);
+        // Integers and floats are rescaled to the scale of the decimal
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::Int64(Some(-7)), &decimal128),
+            Some(ScalarValue::Decimal128(Some(-700), 9, 2))
+        );
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::UInt8(Some(7)), &decimal256),
+            Some(ScalarValue::Decimal256(Some(i256::from_i128(7000)), 50, 3))
+        );
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::Float64(Some(10.1)), &decimal128),
+            Some(ScalarValue::Decimal128(Some(1010), 9, 2))
+        );
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::Float32(Some(0.25)), &decimal256),
+            Some(ScalarValue::Decimal256(Some(i256::from_i128(250)), 50, 3))
+        );
+        // Decimals are rescaled between types
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::Decimal128(Some(1230), 9, 3), &decimal128),
+            Some(ScalarValue::Decimal128(Some(123), 9, 2))
+        );
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::Decimal128(Some(123), 9, 2), &decimal256),
+            Some(ScalarValue::Decimal256(Some(i256::from_i128(1230)), 50, 3))
+        );
+        assert_eq!(
+            safe_coerce_scalar(
+                &ScalarValue::Decimal256(Some(i256::from_i128(1230)), 50, 3),
+                &DataType::Float64
+            ),
+            Some(ScalarValue::Float64(Some(1.23)))
+        );
+        // Values that would lose digits or overflow the precision can't be coerced
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::Float64(Some(1.005)), &decimal128),
+            None
+        );
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::Decimal128(Some(1235), 9, 3), &decimal128),
+            None
+        );
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::Int64(Some(10_000_000)), &decimal128),
+            None
+        );
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::Float64(Some(f64::NAN)), &decimal128),
+            None
+        );
+    }
+
+    #[test]
+    fn test_duration_interval_coerce() {
+        let seconds = DataType::Duration(TimeUnit::Second);
+        let millis = DataType::Duration(TimeUnit::Millisecond);
+        let month_day_nano = ScalarValue::IntervalMonthDayNano(Some(
+            IntervalMonthDayNanoType::make_value(0, 1, 500000000),
+        ));
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::DurationSecond(Some(5)), &millis),
+            Some(ScalarValue::DurationMillisecond(Some(5000)))
+        );
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::DurationMillisecond(Some(5000)), &seconds),
+            Some(ScalarValue::DurationSecond(Some(5)))
+        );
+        assert_eq!(
+            safe_coerce_scalar(&month_day_nano, &millis),
+            Some(ScalarValue::DurationMillisecond(Some(86400500)))
+        );
+        assert_eq!(
+            safe_coerce_scalar(&month_day_nano, &DataType::Interval(IntervalUnit::DayTime)),
+            Some(ScalarValue::IntervalDayTime(Some(
+                IntervalDayTimeType::make_value(1, 500)
+            )))
+        );
+        assert_eq!(
+            safe_coerce_scalar(
+                &ScalarValue::IntervalYearMonth(Some(14)),
+                &DataType::Interval(IntervalUnit::MonthDayNano)
+            ),
+            Some(ScalarValue::IntervalMonthDayNano(Some(
+                IntervalMonthDayNanoType::make_value(14, 0, 0)
+            )))
+        );
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::DurationSecond(None), &millis),
+            Some(ScalarValue::DurationMillisecond(None))
+        );
+        // Values that would lose precision can't be coerced, and months have no fixed length
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::DurationMillisecond(Some(5001)), &seconds),
+            None
+        );
+        assert_eq!(safe_coerce_scalar(&month_day_nano, &seconds), None);
+        assert_eq!(
+            safe_coerce_scalar(&ScalarValue::IntervalYearMonth(Some(1)), &seconds),
+            None
+        );
+        assert_eq!(
+            safe_coerce_scalar(
+                &month_day_nano,
+                &DataType::Interval(IntervalUnit::YearMonth)
+            ),
+            None
+        );
+    }
+
     #[test]
     fn test_temporal_coerce() {
         // Conversion from timestamps in one resolution to timestamps in another resolution is allowed
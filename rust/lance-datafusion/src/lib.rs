@@ -1,8 +1,20 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
+pub mod buffer;
 pub mod chunker;
 pub mod dataframe;
 pub mod exec;
 pub mod expr;
+pub mod map;
+pub mod merge;
+pub mod pushdown;
+pub mod record;
+pub mod retry;
+pub mod scheduler;
+#[cfg(feature = "substrait")]
+pub mod substrait;
+pub mod take;
+pub mod trace;
+pub mod unify;
 pub mod utils;
@@ -0,0 +1,301 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Mapping the batches of a plan with a user function
+
+use std::future::Future;
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use datafusion::execution::TaskContext;
+use datafusion::physical_plan::{
+    metrics::{ExecutionPlanMetricsSet, MetricBuilder, MetricsSet},
+    stream::RecordBatchStreamAdapter,
+    DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, PlanProperties,
+    SendableRecordBatchStream,
+};
+use datafusion_common::{DataFusionError, Result, Statistics};
+use datafusion_physical_expr::EquivalenceProperties;
+use futures::{future::BoxFuture, FutureExt, StreamExt};
+
+/// The function of a [`BatchMapExec`]
+pub type BatchMapFn =
+    Arc<dyn Fn(RecordBatch) -> BoxFuture<'static, lance_core::Result<RecordBatch>> + Send + Sync>;
+
+/// A node that maps each batch of its input with an async function
+///
+/// The function can compute anything from the batch, such as embeddings or
+/// tokens, as long as it returns batches of the declared schema, which are
+/// checked.  Several batches can be mapped at once, see
+/// [`BatchMapExec::with_concurrency`], and the output keeps the order of the
+/// input either way.
+///
+/// The time spent in the function is reported in the `map_time` metric.
+pub struct BatchMapExec {
+    input: Arc<dyn ExecutionPlan>,
+    name: String,
+    func: BatchMapFn,
+    schema: SchemaRef,
+    concurrency: usize,
+    properties: PlanProperties,
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl std::fmt::Debug for BatchMapExec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BatchMapExec")
+            .field("input", &self.input)
+            .field("name", &self.name)
+            .field("schema", &self.schema)
+            .field("concurrency", &self.concurrency)
+            .finish()
+    }
+}
+
+impl BatchMapExec {
+    /// Map the batches of `input` with `func`, which returns batches of
+    /// `schema`
+    ///
+    /// `name` identifies the function in the plan and in errors.
+    pub fn new<F, Fut>(
+        input: Arc<dyn ExecutionPlan>,
+        name: impl Into<String>,
+        schema: SchemaRef,
+        func: F,
+    ) -> Self
+    where
+        F: Fn(RecordBatch) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = lance_core::Result<RecordBatch>> + Send + 'static,
+    {
+        let func: BatchMapFn = Arc::new(move |batch| func(batch).boxed());
+        Self::new_boxed(input, name.into(), schema, func, 1)
+    }
+
+    fn new_boxed(
+        input: Arc<dyn ExecutionPlan>,
+        name: String,
+        schema: SchemaRef,
+        func: BatchMapFn,
+        concurrency: usize,
+    ) -> Self {
+        // The function can compute any column, so no ordering or partitioning
+        // of the input carries over
+        let properties = PlanProperties::new(
+            EquivalenceProperties::new(schema.clone()),
+            Partitioning::UnknownPartitioning(input.properties().partitioning.partition_count()),
+            input.properties().execution_mode,
+        );
+        Self {
+            input,
+            name,
+            func,
+            schema,
+            concurrency,
+            properties,
+            metrics: ExecutionPlanMetricsSet::new(),
+        }
+    }
+
+    /// Map up to `concurrency` batches of each partition at once, one by
+    /// default
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl DisplayAs for BatchMapExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                let columns = self
+                    .schema
+                    .fields()
+                    .iter()
+                    .map(|field| field.name().as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(
+                    f,
+                    "BatchMap: name={}, columns=[{}], concurrency={}",
+                    self.name, columns, self.concurrency
+                )
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for BatchMapExec {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if children.len() != 1 {
+            return Err(DataFusionError::Internal(
+                "BatchMapExec wrong number of children".to_string(),
+            ));
+        }
+        Ok(Arc::new(Self::new_boxed(
+            children[0].clone(),
+            self.name.clone(),
+            self.schema.clone(),
+            self.func.clone(),
+            self.concurrency,
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.input.execute(partition, context)?;
+        let map_time = MetricBuilder::new(&self.metrics).subset_time("map_time", partition);
+        let output_rows = MetricBuilder::new(&self.metrics).output_rows(partition);
+        let func = self.func.clone();
+        let schema = self.schema.clone();
+        let name = self.name.clone();
+        let batches = input
+            .map(move |batch| {
+                let func = func.clone();
+                let schema = schema.clone();
+                let name = name.clone();
+                let map_time = map_time.clone();
+                let output_rows = output_rows.clone();
+                async move {
+                    let timer = map_time.timer();
+                    let mapped = func(batch?).await?;
+                    timer.done();
+                    if mapped.schema().fields() != schema.fields() {
+                        return Err(DataFusionError::Execution(format!(
+                            "The function {} of BatchMapExec returned a batch of schema {:?}, \
+                            not the declared {:?}",
+                            name,
+                            mapped.schema(),
+                            schema
+                        )));
+                    }
+                    output_rows.add(mapped.num_rows());
+                    // The declared schema may have metadata the batch lacks
+                    Ok(RecordBatch::try_new(schema, mapped.columns().to_vec())?)
+                }
+            })
+            .buffered(self.concurrency);
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema.clone(),
+            batches,
+        )))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(self.schema.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use arrow_array::{cast::AsArray, types::Int32Type, Int32Array, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use datafusion::physical_plan::{collect, memory::MemoryExec};
+
+    use super::*;
+
+    fn input() -> Arc<dyn ExecutionPlan> {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let batches = (0..4)
+            .map(|b| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int32Array::from_iter_values(b * 10..(b + 1) * 10))],
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        Arc::new(MemoryExec::try_new(&[batches], schema, None).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_batch_map_exec() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new("token", DataType::Utf8, false),
+        ]));
+        let output_schema = schema.clone();
+        let map = BatchMapExec::new(input(), "tokenize", schema.clone(), move |batch| {
+            let schema = output_schema.clone();
+            async move {
+                let values = batch.column(0).as_primitive::<Int32Type>();
+                // Later batches finish first
+                let delay = 40 - values.value(0) as u64;
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                let tokens = StringArray::from_iter_values(
+                    values.values().iter().map(|i| format!("t{}", i)),
+                );
+                Ok(RecordBatch::try_new(
+                    schema,
+                    vec![batch.column(0).clone(), Arc::new(tokens)],
+                )?)
+            }
+        })
+        .with_concurrency(4);
+        let map = Arc::new(map);
+        let batches = collect(map.clone(), Arc::new(TaskContext::default()))
+            .await
+            .unwrap();
+        // The output keeps the order of the input
+        let values = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, (0..40).collect::<Vec<_>>());
+        assert_eq!(batches[3].column(1).as_string::<i32>().value(0), "t30");
+        let metrics = map.metrics().unwrap();
+        assert_eq!(metrics.output_rows(), Some(40));
+        assert!(metrics.sum_by_name("map_time").unwrap().as_usize() > 0);
+
+        // Batches of another schema than the declared one fail the execution
+        let map = BatchMapExec::new(
+            input(),
+            "identity",
+            schema,
+            |batch| async move { Ok(batch) },
+        );
+        let err = collect(Arc::new(map), Arc::new(TaskContext::default()))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("identity"), "{}", err);
+    }
+}
@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Merging streams that are each sorted into one sorted stream
+//!
+//! This is how an ordered scan is built over a dataset whose fragments are
+//! sorted individually: each fragment is scanned in order, and the scans are
+//! merged.
+
+use std::sync::Arc;
+
+use datafusion::physical_plan::{
+    sorts::sort_preserving_merge::SortPreservingMergeExec, union::UnionExec, ExecutionPlan,
+    SendableRecordBatchStream,
+};
+use datafusion_physical_expr::PhysicalSortExpr;
+use lance_core::{Error, Result};
+use snafu::{location, Location};
+
+use crate::buffer::{BufferOptions, BufferedRecordBatchStream};
+use crate::exec::{execute_plan, LanceExecutionOptions, OneShotExec};
+
+/// How [`merge_sorted_streams`] merges its inputs
+#[derive(Debug, Clone, Default)]
+pub struct MergeOptions {
+    /// Read each input ahead of the merge into a buffer of these options,
+    /// which can spill to disk
+    ///
+    /// The merge needs a batch of every input to produce a batch, so it
+    /// waits on its slowest input.  Buffering the inputs keeps the others
+    /// reading meanwhile.  If not set, the inputs are read as they are
+    /// merged.
+    pub input_buffer: Option<BufferOptions>,
+    /// The options of the execution of the merge
+    pub execution: LanceExecutionOptions,
+}
+
+/// Merge `inputs`, each sorted by `sort_key`, into one stream sorted by
+/// `sort_key`
+///
+/// The merge is a k-way merge: it keeps the current batch of each input, so
+/// its memory grows with the number of inputs rather than with their size.
+/// Rows that compare equal are returned in the order of their inputs.  The
+/// inputs must all have the schema of the first one, which `sort_key` is
+/// bound to.
+pub fn merge_sorted_streams(
+    inputs: Vec<SendableRecordBatchStream>,
+    sort_key: Vec<PhysicalSortExpr>,
+    options: MergeOptions,
+) -> Result<SendableRecordBatchStream> {
+    let Some(first) = inputs.first() else {
+        return Err(Error::invalid_input(
+            "Cannot merge an empty list of streams",
+            location!(),
+        ));
+    };
+    let schema = first.schema();
+    if let Some(input) = inputs
+        .iter()
+        .find(|input| input.schema().fields() != schema.fields())
+    {
+        return Err(Error::invalid_input(
+            format!(
+                "Cannot merge streams of different schemas: {:?} and {:?}",
+                schema,
+                input.schema()
+            ),
+            location!(),
+        ));
+    }
+    if sort_key.is_empty() {
+        return Err(Error::invalid_input(
+            "Cannot merge streams without a sort key",
+            location!(),
+        ));
+    }
+
+    let children = inputs
+        .into_iter()
+        .map(|input| {
+            let input: SendableRecordBatchStream = match &options.input_buffer {
+                Some(buffer) => Box::pin(BufferedRecordBatchStream::new(input, buffer.clone())),
+                None => input,
+            };
+            Arc::new(OneShotExec::new(input)) as Arc<dyn ExecutionPlan>
+        })
+        .collect::<Vec<_>>();
+    let merged = Arc::new(SortPreservingMergeExec::new(
+        sort_key,
+        Arc::new(UnionExec::new(children)),
+    ));
+    execute_plan(merged, options.execution)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{cast::AsArray, types::Int32Type, Int32Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema, SortOptions};
+    use datafusion::physical_plan::{expressions, stream::RecordBatchStreamAdapter};
+    use datafusion_common::DataFusionError;
+    use futures::{stream, TryStreamExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_merge_sorted_streams() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("key", DataType::Int32, false),
+            Field::new("input", DataType::Int32, false),
+        ]));
+        // Input i holds the keys i, i + 3, i + 6... in batches of 10 rows
+        let inputs = || {
+            (0..3)
+                .map(|i| {
+                    let batches = (0..5)
+                        .map(|b| {
+                            let keys = (0..10).map(|j| (b * 10 + j) * 3 + i);
+                            RecordBatch::try_new(
+                                schema.clone(),
+                                vec![
+                                    Arc::new(Int32Array::from_iter_values(keys)),
+                                    Arc::new(Int32Array::from(vec![i; 10])),
+                                ],
+                            )
+                        })
+                        .map(|batch| batch.map_err(DataFusionError::from))
+                        .collect::<Vec<_>>();
+                    Box::pin(RecordBatchStreamAdapter::new(
+                        schema.clone(),
+                        stream::iter(batches),
+                    )) as SendableRecordBatchStream
+                })
+                .collect::<Vec<_>>()
+        };
+        let sort_key = vec![PhysicalSortExpr {
+            expr: expressions::col("key", &schema).unwrap(),
+            options: SortOptions::default(),
+        }];
+
+        for input_buffer in [None, Some(BufferOptions::default())] {
+            let options = MergeOptions {
+                input_buffer,
+                ..Default::default()
+            };
+            let batches = merge_sorted_streams(inputs(), sort_key.clone(), options)
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            let keys = batches
+                .iter()
+                .flat_map(|batch| {
+                    batch
+                        .column(0)
+                        .as_primitive::<Int32Type>()
+                        .values()
+                        .to_vec()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(keys, (0..150).collect::<Vec<_>>());
+        }
+
+        assert!(merge_sorted_streams(vec![], sort_key, MergeOptions::default()).is_err());
+    }
+}
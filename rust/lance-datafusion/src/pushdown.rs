@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Filtering and projecting streams batch by batch
+//!
+//! [`SessionContextExt::read_one_shot`](crate::exec::SessionContextExt::read_one_shot)
+//! turns a stream into a DataFrame, but querying it plans and optimizes a full
+//! DataFusion plan, with the filters applied by a `FilterExec` above the
+//! stream.  Small pipelines, which only filter and project a stream, can
+//! instead attach a [`StreamPushdown`] to the stream, which evaluates the
+//! physical expressions on each batch as it is read.
+
+use std::sync::Arc;
+
+use arrow::compute::filter_record_batch;
+use arrow_array::{cast::AsArray, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use datafusion::logical_expr::Expr;
+use datafusion::optimizer::simplify_expressions::{ExprSimplifier, SimplifyContext};
+use datafusion::physical_expr::execution_props::ExecutionProps;
+use datafusion::physical_plan::{
+    stream::RecordBatchStreamAdapter, PhysicalExpr, SendableRecordBatchStream,
+};
+use datafusion_common::{DFSchema, DataFusionError};
+use futures::StreamExt;
+use lance_core::{Error, Result};
+use snafu::{location, Location};
+
+/// A filter and a projection to evaluate on each batch of a stream
+///
+/// The filter is evaluated first, and the projection is evaluated on the rows
+/// it keeps, so both refer to the columns of the stream.
+#[derive(Debug, Clone, Default)]
+pub struct StreamPushdown {
+    filter: Option<Expr>,
+    projection: Option<Vec<Expr>>,
+}
+
+impl StreamPushdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep the rows for which `filter` is true
+    ///
+    /// Filters added more than once are combined with `AND`.
+    pub fn with_filter(mut self, filter: Expr) -> Self {
+        self.filter = Some(match self.filter.take() {
+            Some(existing) => existing.and(filter),
+            None => filter,
+        });
+        self
+    }
+
+    /// Return the values of `projection`, one column per expression, instead
+    /// of the columns of the stream
+    ///
+    /// The columns are named after the expressions, so an expression can be
+    /// aliased to name its column.
+    pub fn with_projection(mut self, projection: Vec<Expr>) -> Self {
+        self.projection = Some(projection);
+        self
+    }
+
+    /// Evaluate the filter and projection on the batches of `input`, as they
+    /// are read
+    ///
+    /// The expressions are planned against the schema of `input`, so invalid
+    /// expressions fail here rather than when the stream is read.
+    pub fn apply(&self, input: SendableRecordBatchStream) -> Result<SendableRecordBatchStream> {
+        let input_schema = input.schema();
+        let df_schema = Arc::new(DFSchema::try_from(input_schema.as_ref().clone())?);
+
+        let filter = self
+            .filter
+            .as_ref()
+            .map(|filter| {
+                let filter = plan_expr(filter, &df_schema)?;
+                let data_type = filter.data_type(&input_schema)?;
+                if data_type != DataType::Boolean {
+                    return Err(Error::invalid_input(
+                        format!("A filter must be a boolean expression, not {}", data_type),
+                        location!(),
+                    ));
+                }
+                Ok(filter)
+            })
+            .transpose()?;
+        let (projection, schema) = match &self.projection {
+            Some(projection) => {
+                let mut fields = Vec::with_capacity(projection.len());
+                let mut exprs = Vec::with_capacity(projection.len());
+                for expr in projection {
+                    let physical = plan_expr(expr, &df_schema)?;
+                    fields.push(Field::new(
+                        expr.display_name()?,
+                        physical.data_type(&input_schema)?,
+                        physical.nullable(&input_schema)?,
+                    ));
+                    exprs.push(physical);
+                }
+                (Some(exprs), Arc::new(Schema::new(fields)))
+            }
+            None => (None, input_schema),
+        };
+
+        let output_schema = schema.clone();
+        let stream = input.map(move |batch| -> datafusion_common::Result<RecordBatch> {
+            let batch = batch?;
+            let batch = match &filter {
+                Some(filter) => {
+                    let mask = filter.evaluate(&batch)?.into_array(batch.num_rows())?;
+                    filter_record_batch(&batch, mask.as_boolean())?
+                }
+                None => batch,
+            };
+            match &projection {
+                Some(projection) => project_batch(&batch, projection, &output_schema),
+                None => Ok(batch),
+            }
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+    }
+}
+
+/// Simplify and coerce `expr`, which DataFusion needs before it can plan
+/// it, and plan it
+fn plan_expr(expr: &Expr, df_schema: &Arc<DFSchema>) -> Result<Arc<dyn PhysicalExpr>> {
+    let props = ExecutionProps::default();
+    let simplifier =
+        ExprSimplifier::new(SimplifyContext::new(&props).with_schema(df_schema.clone()));
+    let expr = simplifier.coerce(simplifier.simplify(expr.clone())?, df_schema.clone())?;
+    Ok(datafusion::physical_expr::create_physical_expr(
+        &expr,
+        df_schema.as_ref(),
+        &props,
+    )?)
+}
+
+fn project_batch(
+    batch: &RecordBatch,
+    projection: &[Arc<dyn PhysicalExpr>],
+    schema: &SchemaRef,
+) -> datafusion_common::Result<RecordBatch> {
+    let columns = projection
+        .iter()
+        .map(|expr| expr.evaluate(batch)?.into_array(batch.num_rows()))
+        .collect::<datafusion_common::Result<Vec<_>>>()?;
+    // The batch of an empty projection still has its rows
+    let options = arrow_array::RecordBatchOptions::new().with_row_count(Some(batch.num_rows()));
+    RecordBatch::try_new_with_options(schema.clone(), columns, &options)
+        .map_err(DataFusionError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{types::Int32Type, Int32Array, StringArray};
+    use datafusion::logical_expr::{col, lit};
+    use futures::{stream, TryStreamExt};
+
+    use super::*;
+
+    fn input() -> SendableRecordBatchStream {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new("s", DataType::Utf8, true),
+        ]));
+        let batches = (0..3)
+            .map(|b| {
+                let ids = (b * 10)..((b + 1) * 10);
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from_iter_values(ids.clone())),
+                        Arc::new(StringArray::from_iter_values(
+                            ids.map(|i| format!("s-{}", i)),
+                        )),
+                    ],
+                )
+                .map_err(DataFusionError::from)
+            })
+            .collect::<Vec<_>>();
+        Box::pin(RecordBatchStreamAdapter::new(schema, stream::iter(batches)))
+    }
+
+    #[tokio::test]
+    async fn test_stream_pushdown() {
+        // Literals are coerced to the type of the column
+        let pushdown = StreamPushdown::new()
+            .with_filter(col("i").gt_eq(lit(5_i64)))
+            .with_filter(col("i").lt(lit(25_i64)))
+            .with_projection(vec![col("s"), (col("i") * lit(2)).alias("double")]);
+        let stream = pushdown.apply(input()).unwrap();
+        let schema = stream.schema();
+        assert_eq!(schema.field(0).name(), "s");
+        assert_eq!(schema.field(1).name(), "double");
+        let batches = stream.try_collect::<Vec<_>>().await.unwrap();
+        let doubles = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(1)
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(doubles, (5..25).map(|i| i * 2).collect::<Vec<_>>());
+        assert_eq!(batches[0].column(0).as_string::<i32>().value(0), "s-5");
+
+        // Without a projection, the columns of the stream are kept
+        let stream = StreamPushdown::new()
+            .with_filter(col("s").eq(lit("s-12")))
+            .apply(input())
+            .unwrap();
+        let batches = stream.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+        assert_eq!(batches[0].num_columns(), 2);
+
+        // Invalid expressions fail before the stream is read
+        assert!(StreamPushdown::new()
+            .with_filter(col("i") + lit(1))
+            .apply(input())
+            .is_err());
+        assert!(StreamPushdown::new()
+            .with_projection(vec![col("missing")])
+            .apply(input())
+            .is_err());
+    }
+}
@@ -0,0 +1,789 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Recording the batches flowing through a plan, to replay them later
+//!
+//! A query that fails in production often can't be reproduced locally,
+//! because the data it read is out of reach.  When a [`PlanRecorder`] is set
+//! in [`LanceExecutionOptions::recorder`](crate::exec::LanceExecutionOptions::recorder),
+//! each node of the executed plan records the schema and row counts of the
+//! batches it produced and, if data capture is enabled, the batches
+//! themselves, in an Arrow IPC file per node and partition.  The plans a
+//! recorder is attached to and their nodes are numbered, and [`replay`]
+//! reads back the batches a node produced, to feed them to the node above it
+//! and execute it again.
+//!
+//! The files are written by a thread of the recorder, so that the streams
+//! being recorded don't wait for them.
+//!
+//! Data capture stops, for a stream or for the whole recording, once it
+//! reaches the limits of the [`RecordingOptions`]: the rest of the batches
+//! are still counted, but not written.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll};
+
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use datafusion::execution::TaskContext;
+use datafusion::physical_plan::display::DisplayableExecutionPlan;
+use datafusion::physical_plan::{
+    metrics::MetricsSet, DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties,
+    RecordBatchStream, SendableRecordBatchStream,
+};
+use datafusion_common::{DataFusionError, Statistics};
+use futures::{Stream, StreamExt};
+use lance_core::{Error, Result};
+use log::warn;
+use snafu::{location, Location};
+
+use crate::utils::reader_to_stream;
+
+const DEFAULT_MAX_BYTES_PER_STREAM: usize = 64 * 1024 * 1024;
+const DEFAULT_MAX_TOTAL_BYTES: usize = 1024 * 1024 * 1024;
+
+/// What a [`PlanRecorder`] records, and where
+#[derive(Debug, Clone)]
+pub struct RecordingOptions {
+    /// The directory to write the recording to, which must exist
+    pub dir: PathBuf,
+    /// Whether to write the batches themselves, not only their schema and
+    /// row counts
+    pub capture_data: bool,
+    /// The most bytes of batches to write for each stream, 64MiB by default
+    pub max_bytes_per_stream: usize,
+    /// The most bytes of batches to write for the whole recording, 1GiB by
+    /// default
+    pub max_total_bytes: usize,
+}
+
+impl RecordingOptions {
+    /// Record the schema and row counts of the batches in `dir`, without
+    /// their data
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            capture_data: false,
+            max_bytes_per_stream: DEFAULT_MAX_BYTES_PER_STREAM,
+            max_total_bytes: DEFAULT_MAX_TOTAL_BYTES,
+        }
+    }
+
+    /// Also write the batches, within the default size limits
+    pub fn with_data(mut self) -> Self {
+        self.capture_data = true;
+        self
+    }
+}
+
+/// What was recorded of the execution of a partition of a node
+#[derive(Debug, Clone)]
+pub struct RecordedStream {
+    /// The number of the plan, see [`PlanRecording::plans`]
+    pub plan: usize,
+    /// The number of the node in the plan
+    pub node: usize,
+    /// The one line description of the node
+    pub name: String,
+    pub partition: usize,
+    pub schema: SchemaRef,
+    /// The number of rows of each batch the stream produced
+    pub batch_rows: Vec<usize>,
+    /// The batches written to `path`, the first ones of the stream
+    pub captured_batches: usize,
+    /// Whether some batches were not written because of the size limits,
+    /// or because writing them failed
+    pub truncated: bool,
+    /// The error the stream failed with, if any
+    pub error: Option<String>,
+    /// The Arrow IPC file the batches were written to, which holds the
+    /// schema even when no batch was captured
+    pub path: PathBuf,
+}
+
+/// The recording of executions, see [`PlanRecorder::finish`]
+#[derive(Debug, Clone)]
+pub struct PlanRecording {
+    /// The plans, in the order they were recorded, with the number of each
+    /// node
+    pub plans: Vec<String>,
+    /// The streams executed, in the order they were dropped
+    pub streams: Vec<RecordedStream>,
+}
+
+impl PlanRecording {
+    /// The streams of node `node` of plan `plan`
+    pub fn node(&self, plan: usize, node: usize) -> impl Iterator<Item = &RecordedStream> {
+        self.streams
+            .iter()
+            .filter(move |stream| stream.plan == plan && stream.node == node)
+    }
+}
+
+/// What the streams of a recording ask its writer thread to do
+enum WriterMessage {
+    /// Create the file of a stream
+    Open {
+        id: usize,
+        path: PathBuf,
+        schema: SchemaRef,
+    },
+    Write {
+        id: usize,
+        batch: RecordBatch,
+    },
+    /// Finish the file of a dropped stream, and add the stream to the
+    /// recording
+    Close {
+        id: usize,
+        stream: RecordedStream,
+    },
+    /// Acknowledge that the messages sent before have been handled
+    Flush(Sender<()>),
+}
+
+/// The file of a stream being written
+struct StreamFile {
+    writer: Option<StreamWriter<BufWriter<File>>>,
+    /// The number of batches written
+    written: usize,
+    failed: bool,
+}
+
+/// Writes the files of the streams of a recording, see [`WriterMessage`]
+fn write_streams(
+    messages: std::sync::mpsc::Receiver<WriterMessage>,
+    streams: Arc<Mutex<Vec<RecordedStream>>>,
+) {
+    let mut files = HashMap::new();
+    for message in messages {
+        match message {
+            WriterMessage::Open { id, path, schema } => {
+                let writer = File::create(&path)
+                    .map_err(DataFusionError::from)
+                    .and_then(|file| Ok(StreamWriter::try_new(BufWriter::new(file), &schema)?));
+                let writer = match writer {
+                    Ok(writer) => Some(writer),
+                    Err(err) => {
+                        warn!("Failed to create {}: {}", path.display(), err);
+                        None
+                    }
+                };
+                let failed = writer.is_none();
+                files.insert(
+                    id,
+                    StreamFile {
+                        writer,
+                        written: 0,
+                        failed,
+                    },
+                );
+            }
+            WriterMessage::Write { id, batch } => {
+                let Some(file) = files.get_mut(&id) else {
+                    continue;
+                };
+                let Some(writer) = file.writer.as_mut() else {
+                    continue;
+                };
+                if file.failed {
+                    continue;
+                }
+                match writer.write(&batch) {
+                    Ok(()) => file.written += 1,
+                    Err(err) => {
+                        warn!("Failed to record a batch: {}", err);
+                        file.failed = true;
+                    }
+                }
+            }
+            WriterMessage::Close { id, mut stream } => {
+                if let Some(mut file) = files.remove(&id) {
+                    if let Some(mut writer) = file.writer.take() {
+                        let finished =
+                            writer.finish().and_then(|_| Ok(writer.get_mut().flush()?));
+                        if let Err(err) = finished {
+                            warn!("Failed to record the batches of {}: {}", stream.name, err);
+                            file.failed = true;
+                        }
+                    }
+                    // Only the batches written before a failure can be read back
+                    if file.failed {
+                        stream.captured_batches = stream.captured_batches.min(file.written);
+                        stream.truncated = true;
+                    }
+                }
+                streams.lock().unwrap().push(stream);
+            }
+            WriterMessage::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+/// Records the batches flowing through the plans it is attached to, see the
+/// [module docs](self)
+pub struct PlanRecorder {
+    options: RecordingOptions,
+    captured_bytes: AtomicUsize,
+    plans: Mutex<Vec<String>>,
+    streams: Arc<Mutex<Vec<RecordedStream>>>,
+    /// The number of the next stream
+    next_stream: AtomicUsize,
+    /// The messages to the writer thread, once it is started
+    writer: OnceLock<Sender<WriterMessage>>,
+}
+
+impl std::fmt::Debug for PlanRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PlanRecorder")
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl PlanRecorder {
+    pub fn new(options: RecordingOptions) -> Self {
+        Self {
+            options,
+            captured_bytes: AtomicUsize::new(0),
+            plans: Mutex::new(Vec::new()),
+            streams: Arc::new(Mutex::new(Vec::new())),
+            next_stream: AtomicUsize::new(0),
+            writer: OnceLock::new(),
+        }
+    }
+
+    pub fn options(&self) -> &RecordingOptions {
+        &self.options
+    }
+
+    /// The bytes of batches written so far
+    pub fn captured_bytes(&self) -> usize {
+        self.captured_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Send `message` to the writer thread, which is started by the first
+    /// message.  Returns false if the thread is gone.
+    fn send(&self, message: WriterMessage) -> bool {
+        let writer = self.writer.get_or_init(|| {
+            let (sender, receiver) = channel();
+            let streams = self.streams.clone();
+            let spawned = std::thread::Builder::new()
+                .name("lance-recorder".to_string())
+                .spawn(move || write_streams(receiver, streams));
+            if let Err(err) = spawned {
+                warn!("Failed to start the writer of a recording: {}", err);
+            }
+            sender
+        });
+        writer.send(message).is_ok()
+    }
+
+    /// Write the numbered plans and a summary of the streams recorded so far
+    /// to the directory of the recording, as `plan-<plan>.txt` and
+    /// `streams.txt`, and return them
+    ///
+    /// This waits for the files of the streams that were dropped to be
+    /// written.  Call it once the streams of the plans are done or dropped,
+    /// streams still running are not part of the recording.
+    pub fn finish(&self) -> Result<PlanRecording> {
+        let (done, flushed) = channel();
+        if self.send(WriterMessage::Flush(done)) && flushed.recv().is_err() {
+            return Err(Error::io(
+                "the writer of a recording stopped".to_string(),
+                location!(),
+            ));
+        }
+        let recording = PlanRecording {
+            plans: self.plans.lock().unwrap().clone(),
+            streams: self.streams.lock().unwrap().clone(),
+        };
+        for (plan, description) in recording.plans.iter().enumerate() {
+            std::fs::write(
+                self.options.dir.join(format!("plan-{}.txt", plan)),
+                description,
+            )?;
+        }
+        let mut summary = BufWriter::new(File::create(self.options.dir.join("streams.txt"))?);
+        for stream in &recording.streams {
+            writeln!(
+                summary,
+                "plan {} node {} partition {}: {} batches, {} rows, {} captured{}{} in {}",
+                stream.plan,
+                stream.node,
+                stream.partition,
+                stream.batch_rows.len(),
+                stream.batch_rows.iter().sum::<usize>(),
+                stream.captured_batches,
+                if stream.truncated { " (truncated)" } else { "" },
+                stream
+                    .error
+                    .as_ref()
+                    .map(|err| format!(", failed with {}", err))
+                    .unwrap_or_default(),
+                stream.path.display()
+            )?;
+        }
+        summary.flush()?;
+        Ok(recording)
+    }
+
+    /// Reserve `bytes` of the size budget of the recording
+    fn reserve(&self, bytes: usize) -> bool {
+        self.captured_bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |captured| {
+                (captured + bytes <= self.options.max_total_bytes).then_some(captured + bytes)
+            })
+            .is_ok()
+    }
+}
+
+/// Wrap each node of `plan` in a node recording its batches into `recorder`
+///
+/// The plan is given the next number of the recorder, and its nodes are
+/// numbered in pre-order, so the root is node 0.
+pub fn record_plan(
+    plan: Arc<dyn ExecutionPlan>,
+    recorder: Arc<PlanRecorder>,
+) -> datafusion_common::Result<Arc<dyn ExecutionPlan>> {
+    // Hold the plans while the plan is numbered, so numbers are not reused
+    let mut plans = recorder.plans.lock().unwrap();
+    let plan_number = plans.len();
+    let mut description = String::new();
+    let plan = record_node(
+        plan,
+        &recorder,
+        plan_number,
+        0,
+        &mut HashMap::new(),
+        &mut description,
+    )?;
+    plans.push(description);
+    Ok(plan)
+}
+
+fn record_node(
+    plan: Arc<dyn ExecutionPlan>,
+    recorder: &Arc<PlanRecorder>,
+    plan_number: usize,
+    depth: usize,
+    recorded: &mut HashMap<*const (), (usize, Arc<dyn ExecutionPlan>)>,
+    description: &mut String,
+) -> datafusion_common::Result<Arc<dyn ExecutionPlan>> {
+    let key = Arc::as_ptr(&plan) as *const ();
+    let name = DisplayableExecutionPlan::new(plan.as_ref())
+        .one_line()
+        .to_string()
+        .trim_end()
+        .to_string();
+    // Nodes used as the input of several nodes stay shared
+    if let Some((node, recorded)) = recorded.get(&key) {
+        description.push_str(&format!(
+            "{}[{}] (shared) {}\n",
+            "  ".repeat(depth),
+            node,
+            name
+        ));
+        return Ok(recorded.clone());
+    }
+    let node = recorded.len();
+    description.push_str(&format!("{}[{}] {}\n", "  ".repeat(depth), node, name));
+    // Reserve the number of the node before numbering its children
+    recorded.insert(key, (node, plan.clone()));
+    let children = plan.children();
+    // Leaves are not rebuilt, some of them can't be
+    let input = if children.is_empty() {
+        plan.clone()
+    } else {
+        let children = children
+            .into_iter()
+            .map(|child| {
+                record_node(
+                    child,
+                    recorder,
+                    plan_number,
+                    depth + 1,
+                    recorded,
+                    description,
+                )
+            })
+            .collect::<datafusion_common::Result<Vec<_>>>()?;
+        plan.clone().with_new_children(children)?
+    };
+    let recorded_node: Arc<dyn ExecutionPlan> = Arc::new(RecordedExec {
+        input,
+        plan: plan_number,
+        node,
+        name,
+        recorder: recorder.clone(),
+    });
+    recorded.insert(key, (node, recorded_node.clone()));
+    Ok(recorded_node)
+}
+
+/// A node recording the batches of its input, see [`record_plan`]
+///
+/// Like [`crate::trace::TracedExec`], the node is transparent: it displays,
+/// downcasts and reports metrics as its input.
+#[derive(Debug)]
+struct RecordedExec {
+    input: Arc<dyn ExecutionPlan>,
+    plan: usize,
+    node: usize,
+    name: String,
+    recorder: Arc<PlanRecorder>,
+}
+
+impl DisplayAs for RecordedExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.input.fmt_as(t, f)
+    }
+}
+
+impl ExecutionPlan for RecordedExec {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self.input.as_any()
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        self.input.children()
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> datafusion_common::Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self {
+            input: self.input.clone().with_new_children(children)?,
+            plan: self.plan,
+            node: self.node,
+            name: self.name.clone(),
+            recorder: self.recorder.clone(),
+        }))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> datafusion_common::Result<SendableRecordBatchStream> {
+        let inner = self.input.execute(partition, context)?;
+        let schema = inner.schema();
+        let path = stream_path(&self.recorder.options.dir, self.plan, self.node, partition);
+        let id = self.recorder.next_stream.fetch_add(1, Ordering::Relaxed);
+        let open = WriterMessage::Open {
+            id,
+            path: path.clone(),
+            schema: schema.clone(),
+        };
+        let writing = self.recorder.send(open);
+        if !writing {
+            warn!("Failed to record the batches of {}", self.name);
+        }
+        Ok(Box::pin(RecordingStream {
+            inner,
+            id,
+            writing,
+            captured_bytes: 0,
+            recorder: self.recorder.clone(),
+            stream: RecordedStream {
+                plan: self.plan,
+                node: self.node,
+                name: self.name.clone(),
+                partition,
+                schema,
+                batch_rows: Vec::new(),
+                captured_batches: 0,
+                truncated: false,
+                error: None,
+                path,
+            },
+        }))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        self.input.metrics()
+    }
+
+    fn statistics(&self) -> datafusion_common::Result<Statistics> {
+        self.input.statistics()
+    }
+}
+
+/// The file the batches of `partition` of node `node` of plan `plan` are
+/// recorded in
+pub fn stream_path(dir: &Path, plan: usize, node: usize, partition: usize) -> PathBuf {
+    dir.join(format!(
+        "plan-{}-node-{}-partition-{}.arrow",
+        plan, node, partition
+    ))
+}
+
+struct RecordingStream {
+    inner: SendableRecordBatchStream,
+    /// The number of the stream in the messages to the writer thread
+    id: usize,
+    /// Whether the writer thread is writing the file of the stream
+    writing: bool,
+    captured_bytes: usize,
+    recorder: Arc<PlanRecorder>,
+    stream: RecordedStream,
+}
+
+impl RecordingStream {
+    fn record(&mut self, batch: &RecordBatch) {
+        self.stream.batch_rows.push(batch.num_rows());
+        if !self.recorder.options.capture_data || self.stream.truncated || !self.writing {
+            return;
+        }
+        let bytes = batch.get_array_memory_size();
+        if self.captured_bytes + bytes > self.recorder.options.max_bytes_per_stream
+            || !self.recorder.reserve(bytes)
+        {
+            // Later batches are not captured either, so the captured ones
+            // are a prefix of the stream
+            self.stream.truncated = true;
+            return;
+        }
+        let write = WriterMessage::Write {
+            id: self.id,
+            batch: batch.clone(),
+        };
+        if self.recorder.send(write) {
+            self.captured_bytes += bytes;
+            self.stream.captured_batches += 1;
+        } else {
+            self.stream.truncated = true;
+        }
+    }
+}
+
+impl Stream for RecordingStream {
+    type Item = datafusion_common::Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.inner.poll_next_unpin(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(batch))) => self.record(batch),
+            Poll::Ready(Some(Err(err))) => self.stream.error = Some(err.to_string()),
+            _ => {}
+        }
+        poll
+    }
+}
+
+impl RecordBatchStream for RecordingStream {
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+}
+
+impl Drop for RecordingStream {
+    fn drop(&mut self) {
+        let close = WriterMessage::Close {
+            id: self.id,
+            stream: self.stream.clone(),
+        };
+        if !self.recorder.send(close) {
+            self.recorder
+                .streams
+                .lock()
+                .unwrap()
+                .push(self.stream.clone());
+        }
+    }
+}
+
+/// Read back the batches recorded in `path`, see [`RecordedStream::path`]
+pub fn replay(path: impl AsRef<Path>) -> Result<SendableRecordBatchStream> {
+    let reader = StreamReader::try_new(BufReader::new(File::open(path)?), None)?;
+    Ok(reader_to_stream(Box::new(reader)))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{cast::AsArray, types::Int32Type, Int32Array};
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use datafusion::physical_plan::{expressions, filter::FilterExec, memory::MemoryExec};
+    use datafusion::scalar::ScalarValue;
+    use datafusion_physical_expr::expressions::BinaryExpr;
+    use futures::TryStreamExt;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::exec::{execute_plan, LanceExecutionOptions, OneShotExec};
+
+    fn plan() -> Arc<dyn ExecutionPlan> {
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "a",
+            DataType::Int32,
+            false,
+        )]));
+        let batches = (0..4)
+            .map(|b| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int32Array::from_iter_values(
+                        b * 100..(b + 1) * 100,
+                    ))],
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let input = Arc::new(MemoryExec::try_new(&[batches], schema.clone(), None).unwrap());
+        let predicate = Arc::new(BinaryExpr::new(
+            expressions::col("a", &schema).unwrap(),
+            datafusion::logical_expr::Operator::Lt,
+            expressions::lit(ScalarValue::Int32(Some(250))),
+        ));
+        Arc::new(FilterExec::try_new(predicate, input).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_record_plan() {
+        let dir = tempdir().unwrap();
+        let recorder = Arc::new(PlanRecorder::new(
+            RecordingOptions::new(dir.path()).with_data(),
+        ));
+        let options = LanceExecutionOptions {
+            recorder: Some(recorder.clone()),
+            ..Default::default()
+        };
+        let batches = execute_plan(plan(), options)
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 250);
+
+        let recording = recorder.finish().unwrap();
+        assert_eq!(recording.plans.len(), 1);
+        assert!(recording.plans[0].starts_with("[0] FilterExec"));
+        assert!(recording.plans[0].contains("  [1] MemoryExec"));
+        let filter = recording.node(0, 0).next().unwrap();
+        // The filter skips the batch it filters out entirely
+        assert_eq!(filter.batch_rows, vec![100, 100, 50]);
+        assert_eq!(filter.captured_batches, 3);
+        assert!(!filter.truncated);
+        let scan = recording.node(0, 1).next().unwrap();
+        assert_eq!(scan.batch_rows, vec![100; 4]);
+        assert!(dir.path().join("plan-0.txt").exists());
+        assert!(dir.path().join("streams.txt").exists());
+
+        // The input of the filter can be replayed to execute it again
+        let replayed = Arc::new(OneShotExec::new(replay(&scan.path).unwrap()));
+        let filter_plan = plan().with_new_children(vec![replayed]).unwrap();
+        let replayed_batches = execute_plan(filter_plan, LanceExecutionOptions::default())
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(replayed_batches, batches);
+        let values = replayed_batches
+            .iter()
+            .flat_map(|b| b.column(0).as_primitive::<Int32Type>().values().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(values, (0..250).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_record_plan_limits() {
+        // Without data capture, only the schema is written
+        let dir = tempdir().unwrap();
+        let recorder = Arc::new(PlanRecorder::new(RecordingOptions::new(dir.path())));
+        let stream = record_plan(plan(), recorder.clone())
+            .unwrap()
+            .execute(0, Arc::new(TaskContext::default()))
+            .unwrap();
+        stream.try_collect::<Vec<_>>().await.unwrap();
+        let recording = recorder.finish().unwrap();
+        let filter = recording.node(0, 0).next().unwrap();
+        assert_eq!(filter.batch_rows.len(), 3);
+        assert_eq!(filter.captured_batches, 0);
+        let replayed = replay(&filter.path).unwrap();
+        assert_eq!(replayed.schema(), filter.schema);
+        assert!(replayed.try_collect::<Vec<_>>().await.unwrap().is_empty());
+        assert_eq!(recorder.captured_bytes(), 0);
+
+        // Only the batches within the budget are captured
+        let dir = tempdir().unwrap();
+        let batch_bytes = plan().children()[0]
+            .execute(0, Arc::new(TaskContext::default()))
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap()[0]
+            .get_array_memory_size();
+        let recorder = Arc::new(PlanRecorder::new(RecordingOptions {
+            max_bytes_per_stream: batch_bytes * 2,
+            ..RecordingOptions::new(dir.path()).with_data()
+        }));
+        let stream = record_plan(plan(), recorder.clone())
+            .unwrap()
+            .execute(0, Arc::new(TaskContext::default()))
+            .unwrap();
+        stream.try_collect::<Vec<_>>().await.unwrap();
+        let recording = recorder.finish().unwrap();
+        let scan = recording.node(0, 1).next().unwrap();
+        assert_eq!(scan.batch_rows.len(), 4);
+        assert_eq!(scan.captured_batches, 2);
+        assert!(scan.truncated);
+        let replayed = replay(&scan.path)
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(replayed.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_record_several_plans() {
+        let dir = tempdir().unwrap();
+        let recorder = Arc::new(PlanRecorder::new(
+            RecordingOptions::new(dir.path()).with_data(),
+        ));
+        for _ in 0..2 {
+            let stream = record_plan(plan(), recorder.clone())
+                .unwrap()
+                .execute(0, Arc::new(TaskContext::default()))
+                .unwrap();
+            stream.try_collect::<Vec<_>>().await.unwrap();
+        }
+
+        // Each plan has its own files
+        let recording = recorder.finish().unwrap();
+        assert_eq!(recording.plans.len(), 2);
+        assert_eq!(recording.streams.len(), 4);
+        let first = recording.node(0, 1).next().unwrap();
+        let second = recording.node(1, 1).next().unwrap();
+        assert_ne!(first.path, second.path);
+        for scan in [first, second] {
+            let replayed = replay(&scan.path)
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            assert_eq!(replayed.len(), 4);
+        }
+        assert!(dir.path().join("plan-1.txt").exists());
+    }
+}
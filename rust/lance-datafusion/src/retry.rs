@@ -0,0 +1,515 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Retrying the execution of a plan after transient I/O errors
+
+use std::error::Error as StdError;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow_array::RecordBatch;
+use datafusion::execution::TaskContext;
+use datafusion::physical_plan::{
+    metrics::{Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet},
+    stream::RecordBatchStreamAdapter,
+    DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties, SendableRecordBatchStream,
+};
+use datafusion_common::{DataFusionError, Result, Statistics};
+use futures::{stream, StreamExt};
+use log::warn;
+
+/// Decides whether an error is worth retrying
+pub type RetryPredicate = Arc<dyn Fn(&DataFusionError) -> bool + Send + Sync>;
+
+/// How a [`RetryExec`] retries
+#[derive(Clone)]
+pub struct RetryConfig {
+    /// Number of times the input is retried after it last produced a batch
+    pub max_retries: usize,
+    /// Time to wait before the first retry
+    pub initial_backoff: Duration,
+    /// Factor the time to wait grows by with each retry
+    pub backoff_multiplier: f64,
+    /// Longest time to wait before a retry
+    pub max_backoff: Duration,
+    /// Errors to retry, if not set those for which [`is_transient`] is true
+    pub retryable: Option<RetryPredicate>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            backoff_multiplier: 2.0,
+            max_backoff: Duration::from_secs(10),
+            retryable: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for RetryConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryConfig")
+            .field("max_retries", &self.max_retries)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("backoff_multiplier", &self.backoff_multiplier)
+            .field("max_backoff", &self.max_backoff)
+            .field("retryable", &self.retryable.is_some())
+            .finish()
+    }
+}
+
+impl RetryConfig {
+    fn is_retryable(&self, err: &DataFusionError) -> bool {
+        match &self.retryable {
+            Some(retryable) => retryable(err),
+            None => is_transient(err),
+        }
+    }
+
+    /// The time to wait before retry number `attempt`, from 0
+    fn backoff(&self, attempt: usize) -> Duration {
+        let backoff = self.initial_backoff.as_secs_f64()
+            * self
+                .backoff_multiplier
+                .powi(attempt.min(i32::MAX as usize) as i32);
+        Duration::try_from_secs_f64(backoff)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+/// Whether `err` is caused by an I/O error that may not happen again, such as
+/// a dropped connection, a timeout or a server error
+///
+/// The causes of `err` are looked through, including those of the Lance
+/// errors it wraps, for an I/O error of a transient kind or an HTTP request
+/// that failed to connect, timed out or got a 408, 429 or 5xx status.  Other
+/// errors, such as a missing object or a denied permission, are not
+/// transient, whichever object store error carries them.
+pub fn is_transient(err: &DataFusionError) -> bool {
+    let mut source: Option<&(dyn StdError + 'static)> = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<std::io::Error>() {
+            use std::io::ErrorKind::*;
+            if matches!(
+                err.kind(),
+                ConnectionReset
+                    | ConnectionAborted
+                    | ConnectionRefused
+                    | BrokenPipe
+                    | TimedOut
+                    | Interrupted
+                    | UnexpectedEof
+                    | WouldBlock
+            ) {
+                return true;
+            }
+        } else if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+            if let Some(status) = err.status() {
+                return status.is_server_error()
+                    || status == reqwest::StatusCode::REQUEST_TIMEOUT
+                    || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+            }
+            if err.is_timeout() || err.is_connect() || err.is_body() {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// A node that executes its input again when its stream fails with a
+/// transient error
+///
+/// If the input is deterministic, producing the same rows in the same order
+/// each time it is executed, the retried stream resumes after the rows that
+/// were already produced.  Otherwise, the input is only retried until it
+/// produces its first batch, since it can't be resumed.
+///
+/// The number of retries is reported in the `retries` metric.
+#[derive(Debug)]
+pub struct RetryExec {
+    input: Arc<dyn ExecutionPlan>,
+    config: RetryConfig,
+    deterministic: bool,
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl RetryExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, config: RetryConfig, deterministic: bool) -> Self {
+        Self {
+            input,
+            config,
+            deterministic,
+            metrics: ExecutionPlanMetricsSet::new(),
+        }
+    }
+}
+
+impl DisplayAs for RetryExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => write!(
+                f,
+                "Retry: max_retries={}, deterministic={}",
+                self.config.max_retries, self.deterministic
+            ),
+        }
+    }
+}
+
+impl ExecutionPlan for RetryExec {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if children.len() != 1 {
+            return Err(DataFusionError::Internal(
+                "RetryExec wrong number of children".to_string(),
+            ));
+        }
+        Ok(Arc::new(Self::new(
+            children[0].clone(),
+            self.config.clone(),
+            self.deterministic,
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let state = RetryState {
+            input: self.input.clone(),
+            partition,
+            context,
+            config: self.config.clone(),
+            deterministic: self.deterministic,
+            retries: MetricBuilder::new(&self.metrics).counter("retries", partition),
+            stream: None,
+            rows_produced: 0,
+            rows_to_skip: 0,
+            attempt: 0,
+            done: false,
+        };
+        let batches = stream::unfold(state, |mut state| async move {
+            let batch = state.next_batch().await?;
+            Some((batch, state))
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.input.schema(),
+            batches,
+        )))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        self.input.statistics()
+    }
+}
+
+struct RetryState {
+    input: Arc<dyn ExecutionPlan>,
+    partition: usize,
+    context: Arc<TaskContext>,
+    config: RetryConfig,
+    deterministic: bool,
+    retries: Count,
+    stream: Option<SendableRecordBatchStream>,
+    rows_produced: usize,
+    /// Rows of the retried stream that were produced before it was retried
+    rows_to_skip: usize,
+    /// Retries since the input last produced a batch
+    attempt: usize,
+    done: bool,
+}
+
+impl RetryState {
+    async fn next_batch(&mut self) -> Option<Result<RecordBatch>> {
+        while !self.done {
+            let result = match &mut self.stream {
+                Some(stream) => match stream.next().await {
+                    Some(result) => result,
+                    None => {
+                        self.done = true;
+                        return None;
+                    }
+                },
+                None => match self.input.execute(self.partition, self.context.clone()) {
+                    Ok(stream) => {
+                        self.stream = Some(stream);
+                        continue;
+                    }
+                    Err(err) => Err(err),
+                },
+            };
+            match result {
+                Ok(batch) => {
+                    let batch = self.skip_produced(batch);
+                    if batch.num_rows() == 0 {
+                        continue;
+                    }
+                    self.attempt = 0;
+                    self.rows_produced += batch.num_rows();
+                    return Some(Ok(batch));
+                }
+                Err(err) if self.can_retry(&err) => {
+                    let backoff = self.config.backoff(self.attempt);
+                    warn!(
+                        "Retrying execution after {} rows in {:?}: {}",
+                        self.rows_produced, backoff, err
+                    );
+                    self.stream = None;
+                    self.rows_to_skip = self.rows_produced;
+                    self.attempt += 1;
+                    self.retries.add(1);
+                    tokio::time::sleep(backoff).await;
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        None
+    }
+
+    fn can_retry(&self, err: &DataFusionError) -> bool {
+        self.attempt < self.config.max_retries
+            && (self.deterministic || self.rows_produced == 0)
+            && self.config.is_retryable(err)
+    }
+
+    fn skip_produced(&mut self, batch: RecordBatch) -> RecordBatch {
+        if self.rows_to_skip == 0 {
+            return batch;
+        }
+        let skipped = self.rows_to_skip.min(batch.num_rows());
+        self.rows_to_skip -= skipped;
+        batch.slice(skipped, batch.num_rows() - skipped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use arrow_array::Int32Array;
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema, SchemaRef};
+    use datafusion::physical_plan::ExecutionMode;
+    use datafusion_physical_expr::{EquivalenceProperties, Partitioning};
+    use futures::TryStreamExt;
+    use snafu::{location, Location};
+
+    use super::*;
+
+    /// Produces batches of 10 rows, failing after `fail_after` batches the
+    /// first `failures` times it is executed
+    #[derive(Debug)]
+    struct FlakyExec {
+        schema: SchemaRef,
+        num_batches: i32,
+        fail_after: i32,
+        failures: AtomicUsize,
+        error: fn() -> DataFusionError,
+        properties: PlanProperties,
+    }
+
+    impl FlakyExec {
+        fn new(fail_after: i32, failures: usize, error: fn() -> DataFusionError) -> Self {
+            let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+                "a",
+                DataType::Int32,
+                false,
+            )]));
+            Self {
+                schema: schema.clone(),
+                num_batches: 5,
+                fail_after,
+                failures: AtomicUsize::new(failures),
+                error,
+                properties: PlanProperties::new(
+                    EquivalenceProperties::new(schema),
+                    Partitioning::UnknownPartitioning(1),
+                    ExecutionMode::Bounded,
+                ),
+            }
+        }
+    }
+
+    impl DisplayAs for FlakyExec {
+        fn fmt_as(&self, _t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "FlakyExec")
+        }
+    }
+
+    impl ExecutionPlan for FlakyExec {
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn properties(&self) -> &PlanProperties {
+            &self.properties
+        }
+
+        fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+            vec![]
+        }
+
+        fn with_new_children(
+            self: Arc<Self>,
+            _children: Vec<Arc<dyn ExecutionPlan>>,
+        ) -> Result<Arc<dyn ExecutionPlan>> {
+            Ok(self)
+        }
+
+        fn execute(
+            &self,
+            _partition: usize,
+            _context: Arc<TaskContext>,
+        ) -> Result<SendableRecordBatchStream> {
+            let fails = self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            let num_batches = if fails {
+                self.fail_after
+            } else {
+                self.num_batches
+            };
+            let schema = self.schema.clone();
+            let batches = (0..num_batches).map(move |i| {
+                let values = Int32Array::from_iter_values(i * 10..(i + 1) * 10);
+                Ok(RecordBatch::try_new(schema.clone(), vec![Arc::new(values)]).unwrap())
+            });
+            let error = self.error;
+            let failure = fails.then(|| Err(error()));
+            Ok(Box::pin(RecordBatchStreamAdapter::new(
+                self.schema.clone(),
+                stream::iter(batches.chain(failure)),
+            )))
+        }
+
+        fn statistics(&self) -> Result<Statistics> {
+            Ok(Statistics::new_unknown(&self.schema))
+        }
+    }
+
+    fn connection_reset() -> DataFusionError {
+        DataFusionError::ObjectStore(object_store::Error::Generic {
+            store: "test",
+            source: Box::new(std::io::Error::from(std::io::ErrorKind::ConnectionReset)),
+        })
+    }
+
+    fn lance_connection_reset() -> DataFusionError {
+        let err = lance_core::Error::IO {
+            source: Box::new(object_store::Error::Generic {
+                store: "test",
+                source: Box::new(std::io::Error::from(std::io::ErrorKind::ConnectionReset)),
+            }),
+            location: location!(),
+        };
+        err.into()
+    }
+
+    fn permission_denied() -> DataFusionError {
+        DataFusionError::ObjectStore(object_store::Error::Generic {
+            store: "test",
+            source: Box::new(std::io::Error::from(std::io::ErrorKind::PermissionDenied)),
+        })
+    }
+
+    fn not_found() -> DataFusionError {
+        DataFusionError::ObjectStore(object_store::Error::NotFound {
+            path: "missing".to_string(),
+            source: "missing".into(),
+        })
+    }
+
+    async fn run(input: FlakyExec, deterministic: bool) -> (Result<Vec<i32>>, usize) {
+        let config = RetryConfig {
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let plan = RetryExec::new(Arc::new(input), config, deterministic);
+        let result = plan
+            .execute(0, Arc::new(TaskContext::default()))
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .map(|batches| {
+                batches
+                    .iter()
+                    .flat_map(|batch| {
+                        let values = batch.column(0).as_any().downcast_ref::<Int32Array>();
+                        values.unwrap().values().to_vec()
+                    })
+                    .collect()
+            });
+        let retries = plan.metrics().unwrap().sum_by_name("retries").unwrap();
+        (result, retries.as_usize())
+    }
+
+    #[tokio::test]
+    async fn test_retry_exec() {
+        let expected = (0..50).collect::<Vec<_>>();
+        assert!(is_transient(&connection_reset()));
+        assert!(is_transient(&lance_connection_reset()));
+        assert!(!is_transient(&not_found()));
+        assert!(!is_transient(&permission_denied()));
+        assert!(!is_transient(&DataFusionError::ObjectStore(
+            object_store::Error::Generic {
+                store: "test",
+                source: "Access Denied".into(),
+            }
+        )));
+
+        // A deterministic input resumes after the rows already produced
+        let (values, retries) = run(FlakyExec::new(2, 2, connection_reset), true).await;
+        assert_eq!(values.unwrap(), expected);
+        assert_eq!(retries, 2);
+
+        // Other inputs are only retried before their first batch
+        let (values, retries) = run(FlakyExec::new(0, 1, connection_reset), false).await;
+        assert_eq!(values.unwrap(), expected);
+        assert_eq!(retries, 1);
+        let (values, retries) = run(FlakyExec::new(2, 1, connection_reset), false).await;
+        assert!(values.is_err());
+        assert_eq!(retries, 0);
+
+        // Lance errors are retried by their cause
+        let (values, retries) = run(FlakyExec::new(2, 1, lance_connection_reset), true).await;
+        assert_eq!(values.unwrap(), expected);
+        assert_eq!(retries, 1);
+
+        // Errors that aren't transient are not retried
+        let (values, retries) = run(FlakyExec::new(2, 1, not_found), true).await;
+        assert!(values.is_err());
+        assert_eq!(retries, 0);
+
+        // Nor are inputs that keep failing without progress
+        let (values, retries) = run(FlakyExec::new(0, 10, connection_reset), true).await;
+        assert!(values.is_err());
+        assert_eq!(retries, 3);
+    }
+}
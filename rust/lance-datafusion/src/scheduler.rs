@@ -0,0 +1,377 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Executing many small plans concurrently
+//!
+//! Jobs such as compaction or index builds execute a plan per fragment or
+//! per partition, thousands of them.  A [`PlanScheduler`] executes a bounded
+//! number of them at once, highest priority first, in a runtime shared by all
+//! of them, so their memory is accounted for in a single pool.
+
+use std::collections::BinaryHeap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::physical_plan::{ExecutionPlan, RecordBatchStream, SendableRecordBatchStream};
+use futures::{Stream, TryStreamExt};
+use lance_core::{Error, Result};
+use snafu::{location, Location};
+use tokio::sync::oneshot;
+
+use crate::exec::{
+    build_runtime_env, execute_plan, track_memory, ExecutionMemoryStats, LanceExecutionOptions,
+};
+
+/// The priority of a plan submitted to a [`PlanScheduler`]
+///
+/// Plans of a higher priority are executed first, and plans of the same
+/// priority in the order they are submitted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PlanPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// How a [`PlanScheduler`] executes its plans
+#[derive(Debug, Clone)]
+pub struct PlanSchedulerOptions {
+    /// The most plans executing at once, by default the number of CPUs
+    pub max_concurrent_plans: usize,
+    /// The options of the execution of each plan
+    ///
+    /// Their memory and spilling options configure the runtime shared by the
+    /// plans: `mem_pool_size` is the memory of all the plans executing at
+    /// once, rather than of each of them.
+    pub execution: LanceExecutionOptions,
+}
+
+impl Default for PlanSchedulerOptions {
+    fn default() -> Self {
+        Self {
+            max_concurrent_plans: num_cpus::get(),
+            execution: LanceExecutionOptions::default(),
+        }
+    }
+}
+
+/// Executes plans concurrently, up to a bound, in a shared runtime
+///
+/// A plan holds its slot from the time it is executed until its stream is
+/// done or dropped.  Plans submitted while all the slots are held wait for
+/// one, by priority.  Clones of the scheduler share its slots and runtime.
+#[derive(Debug, Clone)]
+pub struct PlanScheduler {
+    slots: Arc<Slots>,
+    runtime_env: Arc<RuntimeEnv>,
+    memory_stats: Arc<ExecutionMemoryStats>,
+    execution: LanceExecutionOptions,
+}
+
+impl PlanScheduler {
+    pub fn try_new(options: PlanSchedulerOptions) -> Result<Self> {
+        if options.max_concurrent_plans == 0 {
+            return Err(Error::invalid_input(
+                "A plan scheduler must execute at least one plan at once",
+                location!(),
+            ));
+        }
+        let runtime_env = match &options.execution.runtime_env {
+            Some(runtime_env) => runtime_env.clone(),
+            None => build_runtime_env(&options.execution)?,
+        };
+        let memory_stats = Arc::new(ExecutionMemoryStats::default());
+        let runtime_env = track_memory(&runtime_env, memory_stats.clone());
+        Ok(Self {
+            slots: Arc::new(Slots::new(options.max_concurrent_plans)),
+            runtime_env,
+            memory_stats,
+            execution: options.execution,
+        })
+    }
+
+    /// The memory the plans of the scheduler reserved, all together
+    ///
+    /// Spills are not recorded, as they are counted by plan.
+    pub fn memory_stats(&self) -> &ExecutionMemoryStats {
+        &self.memory_stats
+    }
+
+    /// The number of plans executing
+    pub fn num_running(&self) -> usize {
+        self.slots.state.lock().unwrap().running
+    }
+
+    /// The number of plans waiting for a slot
+    pub fn num_queued(&self) -> usize {
+        self.slots.state.lock().unwrap().waiting.len()
+    }
+
+    /// Execute `plan` once a slot is free
+    ///
+    /// The slot is released once the stream is done or dropped.  Dropping the
+    /// future before it resolves withdraws the plan.
+    pub async fn execute(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        priority: PlanPriority,
+    ) -> Result<SendableRecordBatchStream> {
+        let slot = self.slots.clone().acquire(priority).await;
+        let mut options = self.execution.clone();
+        options.runtime_env = Some(self.runtime_env.clone());
+        let stream = execute_plan(plan, options)?;
+        Ok(Box::pin(ScheduledStream {
+            schema: stream.schema(),
+            inner: stream,
+            slot: Some(slot),
+        }))
+    }
+
+    /// Execute `plan` once a slot is free, and collect its batches
+    pub async fn collect(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        priority: PlanPriority,
+    ) -> Result<Vec<RecordBatch>> {
+        Ok(self.execute(plan, priority).await?.try_collect().await?)
+    }
+}
+
+#[derive(Debug)]
+struct Waiter {
+    priority: PlanPriority,
+    /// The order the plan was submitted in
+    sequence: u64,
+    /// Hands the slot of a finished plan over to the waiting plan
+    sender: oneshot::Sender<()>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority && self.sequence == other.sequence
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    // The heap pops the greatest waiter: the highest priority, submitted first
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+#[derive(Debug, Default)]
+struct SlotsState {
+    running: usize,
+    waiting: BinaryHeap<Waiter>,
+    next_sequence: u64,
+}
+
+#[derive(Debug)]
+struct Slots {
+    max_running: usize,
+    state: Mutex<SlotsState>,
+}
+
+impl Slots {
+    fn new(max_running: usize) -> Self {
+        Self {
+            max_running,
+            state: Mutex::new(SlotsState::default()),
+        }
+    }
+
+    async fn acquire(self: Arc<Self>, priority: PlanPriority) -> Slot {
+        let receiver = {
+            let mut state = self.state.lock().unwrap();
+            if state.running < self.max_running && state.waiting.is_empty() {
+                state.running += 1;
+                return Slot(self.clone());
+            }
+            let (sender, receiver) = oneshot::channel();
+            let sequence = state.next_sequence;
+            state.next_sequence += 1;
+            state.waiting.push(Waiter {
+                priority,
+                sequence,
+                sender,
+            });
+            receiver
+        };
+        let mut waiting = WaitingSlot {
+            slots: self.clone(),
+            receiver,
+        };
+        (&mut waiting.receiver)
+            .await
+            .expect("Waiters are only dropped when handed a slot");
+        Slot(self)
+    }
+
+    /// Hand the slot of a finished plan over to the next waiting plan, if any
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        while let Some(waiter) = state.waiting.pop() {
+            // Plans withdrawn while waiting dropped their receiver
+            if waiter.sender.send(()).is_ok() {
+                return;
+            }
+        }
+        state.running -= 1;
+    }
+}
+
+/// The slot held by an executing plan, released when dropped
+#[derive(Debug)]
+struct Slot(Arc<Slots>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+/// A plan waiting for a slot
+///
+/// If the plan is withdrawn after it was handed a slot, but before it took
+/// it, the slot is released.
+struct WaitingSlot {
+    slots: Arc<Slots>,
+    receiver: oneshot::Receiver<()>,
+}
+
+impl Drop for WaitingSlot {
+    fn drop(&mut self) {
+        if self.receiver.try_recv().is_ok() {
+            self.slots.release();
+        }
+    }
+}
+
+/// The stream of a scheduled plan, which releases its slot once done
+struct ScheduledStream {
+    schema: SchemaRef,
+    inner: SendableRecordBatchStream,
+    slot: Option<Slot>,
+}
+
+impl Stream for ScheduledStream {
+    type Item = datafusion::error::Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.inner.as_mut().poll_next(cx);
+        if let Poll::Ready(None) = poll {
+            self.slot.take();
+        }
+        poll
+    }
+}
+
+impl RecordBatchStream for ScheduledStream {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use arrow_array::Int32Array;
+    use arrow_schema::{DataType, Field, Schema, SortOptions};
+    use datafusion::physical_plan::{expressions, memory::MemoryExec, sorts::sort::SortExec};
+    use datafusion_physical_expr::PhysicalSortExpr;
+
+    use super::*;
+
+    fn plan() -> Arc<dyn ExecutionPlan> {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values((0..1000).rev()))],
+        )
+        .unwrap();
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch]], schema.clone(), None).unwrap());
+        let sort_expr = PhysicalSortExpr {
+            expr: expressions::col("i", &schema).unwrap(),
+            options: SortOptions::default(),
+        };
+        Arc::new(SortExec::new(vec![sort_expr], input))
+    }
+
+    #[tokio::test]
+    async fn test_plan_scheduler() {
+        let scheduler = PlanScheduler::try_new(PlanSchedulerOptions {
+            max_concurrent_plans: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        let running = scheduler
+            .execute(plan(), PlanPriority::Normal)
+            .await
+            .unwrap();
+        assert_eq!(scheduler.num_running(), 1);
+
+        // Once the slot is released, the waiting plans run by priority
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let tasks = [PlanPriority::Low, PlanPriority::High, PlanPriority::Normal]
+            .into_iter()
+            .map(|priority| {
+                let scheduler = scheduler.clone();
+                let order = order.clone();
+                tokio::spawn(async move {
+                    let batches = scheduler.collect(plan(), priority).await.unwrap();
+                    order.lock().unwrap().push(priority);
+                    batches
+                })
+            })
+            .collect::<Vec<_>>();
+        while scheduler.num_queued() < 3 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        // Withdrawn plans don't hold a slot
+        let withdrawn = scheduler.execute(plan(), PlanPriority::High);
+        assert!(tokio::time::timeout(Duration::from_millis(10), withdrawn)
+            .await
+            .is_err());
+
+        let batches = running.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(
+            batches[0].column(0).as_ref(),
+            &Int32Array::from_iter_values(0..1000)
+        );
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec![PlanPriority::High, PlanPriority::Normal, PlanPriority::Low]
+        );
+        assert_eq!(scheduler.num_running(), 0);
+        assert_eq!(scheduler.num_queued(), 0);
+
+        // The memory of the plans is accounted for in the shared pool
+        assert!(scheduler.memory_stats().peak_bytes() > 0);
+        assert_eq!(scheduler.memory_stats().reserved_bytes(), 0);
+
+        assert!(PlanScheduler::try_new(PlanSchedulerOptions {
+            max_concurrent_plans: 0,
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
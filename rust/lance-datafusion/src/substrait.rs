@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Execution of Substrait plans produced by external planners
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use datafusion::{
+    datasource::TableProvider, execution::context::SessionContext,
+    physical_plan::SendableRecordBatchStream,
+};
+use datafusion_common::TableReference;
+use datafusion_substrait::{logical_plan::consumer::from_substrait_plan, substrait::proto::Plan};
+use lance_core::Result;
+use prost::Message;
+
+use crate::exec::{execute_plan, LanceExecutionOptions};
+
+/// Execute a serialized Substrait plan
+///
+/// The named tables the plan reads are looked up in `tables` by name, and
+/// must not be qualified by a schema or catalog.  The plan is planned by
+/// DataFusion, which pushes its projections and filters down to the tables,
+/// and executed with [`execute_plan`].
+pub async fn execute_substrait(
+    plan: &[u8],
+    tables: HashMap<String, Arc<dyn TableProvider>>,
+    options: LanceExecutionOptions,
+) -> Result<SendableRecordBatchStream> {
+    let plan = Plan::decode(plan)?;
+    let ctx = SessionContext::new();
+    for (name, table) in tables {
+        ctx.register_table(TableReference::bare(name), table)?;
+    }
+    let logical_plan = from_substrait_plan(&ctx, &plan).await?;
+    let physical_plan = ctx.state().create_physical_plan(&logical_plan).await?;
+    execute_plan(physical_plan, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Int32Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use datafusion::datasource::MemTable;
+    use datafusion_substrait::logical_plan::producer::to_substrait_plan;
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_execute_substrait() {
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "x",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10))],
+        )
+        .unwrap();
+        let table: Arc<dyn TableProvider> =
+            Arc::new(MemTable::try_new(schema, vec![vec![batch]]).unwrap());
+
+        // Produce the plan as an external planner would
+        let ctx = SessionContext::new();
+        ctx.register_table("t", table.clone()).unwrap();
+        let logical_plan = ctx
+            .sql("SELECT x FROM t WHERE x >= 7")
+            .await
+            .unwrap()
+            .into_optimized_plan()
+            .unwrap();
+        let plan = to_substrait_plan(&logical_plan, &ctx)
+            .unwrap()
+            .encode_to_vec();
+
+        let tables = HashMap::from([("t".to_string(), table)]);
+        let batches = execute_substrait(&plan, tables, LanceExecutionOptions::default())
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let values = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![7, 8, 9]);
+
+        // Tables the plan reads must be provided
+        let result =
+            execute_substrait(&plan, HashMap::new(), LanceExecutionOptions::default()).await;
+        assert!(result.is_err());
+    }
+}
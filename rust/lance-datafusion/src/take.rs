@@ -0,0 +1,454 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Point lookups of the rows of a stream of row addresses
+
+use std::sync::Arc;
+
+use arrow::compute::{filter_record_batch, take};
+use arrow_array::{
+    cast::AsArray, types::UInt64Type, Array, BooleanArray, RecordBatch, UInt32Array,
+};
+use arrow_schema::{DataType, Schema as ArrowSchema, SchemaRef};
+use async_trait::async_trait;
+use datafusion::execution::TaskContext;
+use datafusion::physical_plan::{
+    metrics::{Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet},
+    stream::RecordBatchStreamAdapter,
+    DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties, SendableRecordBatchStream,
+};
+use datafusion_common::{DataFusionError, Result, Statistics};
+use datafusion_physical_expr::EquivalenceProperties;
+use futures::StreamExt;
+use lance_core::ROW_ID;
+
+use crate::chunker::rechunk_stream;
+
+/// Reads rows by their row addresses
+#[async_trait]
+pub trait RowAddressReader: std::fmt::Debug + Send + Sync {
+    /// The schema of the rows read
+    fn schema(&self) -> SchemaRef;
+
+    /// Read the rows at `addresses`, which are sorted and unique, in the
+    /// same order
+    ///
+    /// Addresses without a row, such as those of deleted rows, are skipped.
+    /// The addresses of the rows read are returned along with them.
+    async fn take(&self, addresses: &[u64]) -> lance_core::Result<(Vec<u64>, RecordBatch)>;
+}
+
+/// How a [`TakeRowsExec`] reads rows
+#[derive(Debug, Clone)]
+pub struct TakeOptions {
+    /// Name of the column of the input holding the row addresses
+    pub address_column: String,
+    /// Number of addresses read together.  Smaller input batches are
+    /// coalesced, and larger ones are split.
+    pub batch_size: usize,
+    /// Number of batches read concurrently
+    pub readahead: usize,
+}
+
+impl Default for TakeOptions {
+    fn default() -> Self {
+        Self {
+            address_column: ROW_ID.to_string(),
+            batch_size: 1024,
+            readahead: 4,
+        }
+    }
+}
+
+/// A node that adds the rows read from a [`RowAddressReader`] to the rows of
+/// its input, by the row addresses of the input
+///
+/// The addresses of a batch are sorted and deduplicated before they are read,
+/// so that reads of nearby rows can be coalesced, and the rows read are put
+/// back in the order of the input.  Input rows whose address has no row, such
+/// as a deleted row, are left out.  The output schema is the input schema
+/// followed by the schema of the reader.
+///
+/// The number of reads is reported in the `takes` metric.
+#[derive(Debug)]
+pub struct TakeRowsExec {
+    input: Arc<dyn ExecutionPlan>,
+    reader: Arc<dyn RowAddressReader>,
+    options: TakeOptions,
+    address_index: usize,
+    schema: SchemaRef,
+    properties: PlanProperties,
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl TakeRowsExec {
+    pub fn try_new(
+        input: Arc<dyn ExecutionPlan>,
+        reader: Arc<dyn RowAddressReader>,
+        options: TakeOptions,
+    ) -> Result<Self> {
+        let input_schema = input.schema();
+        let (address_index, address_field) = input_schema
+            .column_with_name(&options.address_column)
+            .ok_or_else(|| {
+                DataFusionError::Plan(format!(
+                    "TakeRowsExec requires the input plan to have a column named '{}'",
+                    options.address_column
+                ))
+            })?;
+        if address_field.data_type() != &DataType::UInt64 {
+            return Err(DataFusionError::Plan(format!(
+                "The row address column '{}' must be UInt64, not {}",
+                options.address_column,
+                address_field.data_type()
+            )));
+        }
+        if options.batch_size == 0 || options.readahead == 0 {
+            return Err(DataFusionError::Plan(
+                "TakeRowsExec requires a positive batch_size and readahead".to_string(),
+            ));
+        }
+
+        let reader_schema = reader.schema();
+        if let Some(field) = reader_schema
+            .fields()
+            .iter()
+            .find(|field| input_schema.column_with_name(field.name()).is_some())
+        {
+            return Err(DataFusionError::Plan(format!(
+                "TakeRowsExec reads column '{}', which is already in its input",
+                field.name()
+            )));
+        }
+        let schema = Arc::new(ArrowSchema::new(
+            input_schema
+                .fields()
+                .iter()
+                .chain(reader_schema.fields().iter())
+                .cloned()
+                .collect::<Vec<_>>(),
+        ));
+        let properties = input
+            .properties()
+            .clone()
+            .with_eq_properties(EquivalenceProperties::new(schema.clone()));
+
+        Ok(Self {
+            input,
+            reader,
+            options,
+            address_index,
+            schema,
+            properties,
+            metrics: ExecutionPlanMetricsSet::new(),
+        })
+    }
+
+    pub fn reader(&self) -> &Arc<dyn RowAddressReader> {
+        &self.reader
+    }
+
+    pub fn options(&self) -> &TakeOptions {
+        &self.options
+    }
+}
+
+impl DisplayAs for TakeRowsExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                let columns = self
+                    .reader
+                    .schema()
+                    .fields()
+                    .iter()
+                    .map(|field| field.name().as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(
+                    f,
+                    "TakeRows: columns=[{}], address_column={}, batch_size={}, readahead={}",
+                    columns,
+                    self.options.address_column,
+                    self.options.batch_size,
+                    self.options.readahead
+                )
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for TakeRowsExec {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if children.len() != 1 {
+            return Err(DataFusionError::Internal(
+                "TakeRowsExec wrong number of children".to_string(),
+            ));
+        }
+        Ok(Arc::new(Self::try_new(
+            children[0].clone(),
+            self.reader.clone(),
+            self.options.clone(),
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.input.execute(partition, context)?;
+        let takes = MetricBuilder::new(&self.metrics).counter("takes", partition);
+        let reader = self.reader.clone();
+        let schema = self.schema.clone();
+        let address_index = self.address_index;
+        let batches =
+            rechunk_stream(input, self.options.batch_size, usize::MAX)
+                .map(move |batch| {
+                    let reader = reader.clone();
+                    let schema = schema.clone();
+                    let takes = takes.clone();
+                    async move {
+                        take_batch(batch?, address_index, reader.as_ref(), schema, &takes).await
+                    }
+                })
+                .buffered(self.options.readahead);
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema.clone(),
+            batches,
+        )))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics {
+            num_rows: self.input.statistics()?.num_rows,
+            ..Statistics::new_unknown(self.schema.as_ref())
+        })
+    }
+}
+
+/// Add the rows at the addresses of `batch` to `batch`
+async fn take_batch(
+    batch: RecordBatch,
+    address_index: usize,
+    reader: &dyn RowAddressReader,
+    schema: SchemaRef,
+    takes: &Count,
+) -> Result<RecordBatch> {
+    if batch.num_rows() == 0 {
+        return Ok(RecordBatch::new_empty(schema));
+    }
+    let addresses = batch.column(address_index).as_primitive::<UInt64Type>();
+    if addresses.null_count() > 0 {
+        return Err(DataFusionError::Execution(
+            "TakeRowsExec cannot take rows at null row addresses".to_string(),
+        ));
+    }
+
+    let mut unique = addresses.values().to_vec();
+    unique.sort_unstable();
+    unique.dedup();
+    let (found, rows) = reader.take(&unique).await?;
+    takes.add(1);
+    if rows.num_rows() != found.len() || found.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(DataFusionError::Internal(format!(
+            "TakeRowsExec read {} rows for {} sorted row addresses",
+            rows.num_rows(),
+            found.len()
+        )));
+    }
+
+    // Keep the input rows whose rows were found
+    let mut indices = Vec::with_capacity(addresses.len());
+    let keep = BooleanArray::from_iter(addresses.values().iter().map(|address| {
+        let position = found.binary_search(address).ok();
+        indices.extend(position.map(|index| index as u32));
+        Some(position.is_some())
+    }));
+    let batch = if indices.len() == batch.num_rows() {
+        batch
+    } else {
+        filter_record_batch(&batch, &keep)?
+    };
+    let indices = UInt32Array::from(indices);
+    let mut columns = batch.columns().to_vec();
+    for column in rows.columns() {
+        columns.push(take(column, &indices, None)?);
+    }
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use arrow_array::{Int32Array, UInt64Array};
+    use arrow_schema::Field;
+    use datafusion::physical_plan::memory::MemoryExec;
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    /// Reads the rows of a batch by their index, recording each read.
+    /// Addresses past the end of the batch have no row.
+    #[derive(Debug)]
+    struct BatchReader {
+        batch: RecordBatch,
+        reads: Mutex<Vec<Vec<u64>>>,
+    }
+
+    #[async_trait]
+    impl RowAddressReader for BatchReader {
+        fn schema(&self) -> SchemaRef {
+            self.batch.schema()
+        }
+
+        async fn take(&self, addresses: &[u64]) -> lance_core::Result<(Vec<u64>, RecordBatch)> {
+            self.reads.lock().unwrap().push(addresses.to_vec());
+            let found = addresses
+                .iter()
+                .copied()
+                .filter(|address| *address < self.batch.num_rows() as u64)
+                .collect::<Vec<_>>();
+            let indices = UInt64Array::from(found.clone());
+            let columns = self
+                .batch
+                .columns()
+                .iter()
+                .map(|column| take(column, &indices, None))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            Ok((found, RecordBatch::try_new(self.batch.schema(), columns)?))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_take_rows_exec() {
+        let values_schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "value",
+            DataType::Int32,
+            false,
+        )]));
+        let values = RecordBatch::try_new(
+            values_schema,
+            vec![Arc::new(Int32Array::from_iter_values(
+                (0..100).map(|v| v * 10),
+            ))],
+        )
+        .unwrap();
+        let reader = Arc::new(BatchReader {
+            batch: values,
+            reads: Mutex::new(vec![]),
+        });
+
+        let input_schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            ROW_ID,
+            DataType::UInt64,
+            false,
+        )]));
+        let input_batches = [vec![42_u64, 7, 42], vec![3], vec![99, 150, 0, 7]]
+            .into_iter()
+            .map(|addresses| {
+                RecordBatch::try_new(
+                    input_schema.clone(),
+                    vec![Arc::new(UInt64Array::from(addresses))],
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let input =
+            Arc::new(MemoryExec::try_new(&[input_batches], input_schema.clone(), None).unwrap());
+
+        let take_exec = TakeRowsExec::try_new(
+            input.clone(),
+            reader.clone(),
+            TakeOptions {
+                batch_size: 4,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            take_exec
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| field.name().as_str())
+                .collect::<Vec<_>>(),
+            vec![ROW_ID, "value"]
+        );
+        let batches = take_exec
+            .execute(0, Arc::new(TaskContext::default()))
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+
+        let values = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(1)
+                    .as_primitive::<arrow_array::types::Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![420, 70, 420, 30, 990, 0, 70]);
+        // The row at an address without a row is left out
+        let addresses = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<UInt64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(addresses, vec![42, 7, 42, 3, 99, 0, 7]);
+        // Input batches are coalesced, and addresses sorted and deduplicated
+        assert_eq!(
+            *reader.reads.lock().unwrap(),
+            vec![vec![3, 7, 42], vec![0, 7, 99, 150]]
+        );
+
+        // Columns cannot be read twice
+        let err =
+            TakeRowsExec::try_new(Arc::new(take_exec), reader.clone(), TakeOptions::default());
+        assert!(err.is_err());
+
+        // The address column must exist
+        let err = TakeRowsExec::try_new(
+            input,
+            reader,
+            TakeOptions {
+                address_column: "missing".to_string(),
+                ..Default::default()
+            },
+        );
+        assert!(err.is_err());
+    }
+}
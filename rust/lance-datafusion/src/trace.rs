@@ -0,0 +1,241 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Tracing spans around the execution of each node of a plan
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use datafusion::execution::TaskContext;
+use datafusion::physical_plan::display::DisplayableExecutionPlan;
+use datafusion::physical_plan::{
+    metrics::MetricsSet, DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties,
+    RecordBatchStream, SendableRecordBatchStream,
+};
+use datafusion_common::{Result, Statistics};
+use futures::{Stream, StreamExt};
+use tracing::{field::Empty, info_span, Span};
+
+/// A node executing its input in a tracing span
+///
+/// The span of an execution of the node covers opening its stream and each
+/// poll of the stream, so the spans of its input are nested in it.  Once
+/// the stream is dropped, the span records the number of polls, and the
+/// number of rows and in-memory size of the batches the node produced.
+///
+/// The node is transparent: it displays, downcasts and reports metrics as
+/// its input.
+#[derive(Debug)]
+pub struct TracedExec {
+    input: Arc<dyn ExecutionPlan>,
+    name: String,
+}
+
+impl TracedExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>) -> Self {
+        let description = DisplayableExecutionPlan::new(input.as_ref())
+            .one_line()
+            .to_string();
+        let name = description
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        Self { input, name }
+    }
+}
+
+impl DisplayAs for TracedExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.input.fmt_as(t, f)
+    }
+}
+
+impl ExecutionPlan for TracedExec {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self.input.as_any()
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        self.input.children()
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self::new(
+            self.input.clone().with_new_children(children)?,
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let span = info_span!(
+            "execute_node",
+            node = self.name.as_str(),
+            partition,
+            polls = Empty,
+            rows = Empty,
+            bytes = Empty,
+        );
+        let inner = span.in_scope(|| self.input.execute(partition, context))?;
+        Ok(Box::pin(TracedStream {
+            inner,
+            span,
+            polls: 0,
+            rows: 0,
+            bytes: 0,
+        }))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        self.input.metrics()
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        self.input.statistics()
+    }
+}
+
+struct TracedStream {
+    inner: SendableRecordBatchStream,
+    span: Span,
+    polls: usize,
+    rows: usize,
+    bytes: usize,
+}
+
+impl Stream for TracedStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let _entered = this.span.enter();
+        this.polls += 1;
+        let poll = this.inner.poll_next_unpin(cx);
+        if let Poll::Ready(Some(Ok(batch))) = &poll {
+            this.rows += batch.num_rows();
+            this.bytes += batch.get_array_memory_size();
+        }
+        poll
+    }
+}
+
+impl RecordBatchStream for TracedStream {
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+}
+
+impl Drop for TracedStream {
+    fn drop(&mut self) {
+        self.span.record("polls", self.polls);
+        self.span.record("rows", self.rows);
+        self.span.record("bytes", self.bytes);
+    }
+}
+
+/// Wrap each node of `plan` in a [`TracedExec`]
+///
+/// Nodes used as the input of several nodes, such as replays, stay shared in
+/// the traced plan.
+pub fn trace_plan(plan: Arc<dyn ExecutionPlan>) -> Result<Arc<dyn ExecutionPlan>> {
+    trace_node(plan, &mut HashMap::new())
+}
+
+fn trace_node(
+    plan: Arc<dyn ExecutionPlan>,
+    traced: &mut HashMap<*const (), Arc<dyn ExecutionPlan>>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let key = Arc::as_ptr(&plan) as *const ();
+    if let Some(node) = traced.get(&key) {
+        return Ok(node.clone());
+    }
+    let children = plan.children();
+    // Leaves are not rebuilt, some of them can't be
+    let node = if children.is_empty() {
+        plan.clone()
+    } else {
+        let children = children
+            .into_iter()
+            .map(|child| trace_node(child, traced))
+            .collect::<Result<Vec<_>>>()?;
+        plan.clone().with_new_children(children)?
+    };
+    let node: Arc<dyn ExecutionPlan> = Arc::new(TracedExec::new(node));
+    traced.insert(key, node.clone());
+    Ok(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::Int32Array;
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use datafusion::physical_plan::{
+        displayable, expressions, filter::FilterExec, memory::MemoryExec,
+    };
+    use datafusion::scalar::ScalarValue;
+    use datafusion_physical_expr::expressions::BinaryExpr;
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_trace_plan() {
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "a",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10))],
+        )
+        .unwrap();
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch]], schema.clone(), None).unwrap());
+        let predicate = Arc::new(BinaryExpr::new(
+            expressions::col("a", &schema).unwrap(),
+            datafusion::logical_expr::Operator::Lt,
+            expressions::lit(ScalarValue::Int32(Some(5))),
+        ));
+        let plan: Arc<dyn ExecutionPlan> = Arc::new(FilterExec::try_new(predicate, input).unwrap());
+
+        let traced = trace_plan(plan.clone()).unwrap();
+        assert_eq!(
+            displayable(traced.as_ref()).indent(true).to_string(),
+            displayable(plan.as_ref()).indent(true).to_string()
+        );
+        assert!(traced.as_any().downcast_ref::<FilterExec>().is_some());
+        assert!(traced.children()[0]
+            .as_any()
+            .downcast_ref::<MemoryExec>()
+            .is_some());
+
+        let batches = traced
+            .execute(0, Arc::new(TaskContext::default()))
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 5);
+        // The metrics are those of the executed filter
+        assert_eq!(traced.metrics().unwrap().output_rows(), Some(5));
+    }
+}
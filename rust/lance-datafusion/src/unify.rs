@@ -0,0 +1,313 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Concatenation of streams with different schemas into a stream of one schema
+
+use arrow::compute::cast;
+use arrow_array::{new_null_array, RecordBatch, RecordBatchOptions};
+use arrow_schema::{DataType, Schema as ArrowSchema, SchemaRef};
+use datafusion::physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream};
+use datafusion_common::DataFusionError;
+use futures::{stream, StreamExt};
+use lance_core::{Error, Result};
+use snafu::{location, Location};
+
+/// What [`unify_streams`] does with the columns of a stream that are not in
+/// the unified schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExtraColumns {
+    /// Fail before reading any stream.
+    #[default]
+    Error,
+    /// Drop the columns.
+    Drop,
+}
+
+/// Whether every value of `from` has the same value in `to`, whose values
+/// are at least as wide.
+fn is_widening_cast(from: &DataType, to: &DataType) -> bool {
+    use DataType::*;
+    // The bits of an integer, and whether it is signed
+    fn int_bits(data_type: &DataType) -> Option<(u32, bool)> {
+        Some(match data_type {
+            Int8 => (8, true),
+            Int16 => (16, true),
+            Int32 => (32, true),
+            Int64 => (64, true),
+            UInt8 => (8, false),
+            UInt16 => (16, false),
+            UInt32 => (32, false),
+            UInt64 => (64, false),
+            _ => return None,
+        })
+    }
+    // The bits of the mantissa of a float
+    fn mantissa_bits(data_type: &DataType) -> Option<u32> {
+        Some(match data_type {
+            Float16 => 11,
+            Float32 => 24,
+            Float64 => 53,
+            _ => return None,
+        })
+    }
+    match (from, to) {
+        _ if from == to => true,
+        (Utf8, LargeUtf8) | (Binary, LargeBinary) | (Date32, Date64) => true,
+        (Float16, Float32 | Float64) | (Float32, Float64) => true,
+        _ => match (int_bits(from), int_bits(to), mantissa_bits(to)) {
+            (Some((from_bits, from_signed)), Some((to_bits, to_signed)), _) => {
+                if from_signed == to_signed {
+                    from_bits <= to_bits
+                } else {
+                    // Only unsigned integers fit wider signed integers
+                    !from_signed && from_bits < to_bits
+                }
+            }
+            (Some((from_bits, from_signed)), None, Some(mantissa_bits)) => {
+                from_bits - from_signed as u32 <= mantissa_bits
+            }
+            _ => false,
+        },
+    }
+}
+
+/// For each field of `schema`, the index of the column of `input` it is read
+/// from, if any.
+fn plan_columns(
+    input: &ArrowSchema,
+    schema: &ArrowSchema,
+    extra_columns: ExtraColumns,
+) -> Result<Vec<Option<usize>>> {
+    let schema_error = |message: String| Error::Schema {
+        message,
+        location: location!(),
+    };
+    if extra_columns == ExtraColumns::Error {
+        if let Some(extra) = input
+            .fields()
+            .iter()
+            .find(|field| schema.field_with_name(field.name()).is_err())
+        {
+            return Err(schema_error(format!(
+                "Unexpected column {} in stream",
+                extra.name()
+            )));
+        }
+    }
+    schema
+        .fields()
+        .iter()
+        .map(|field| match input.index_of(field.name()) {
+            Ok(i) => {
+                let input_type = input.field(i).data_type();
+                if !is_widening_cast(input_type, field.data_type()) {
+                    return Err(schema_error(format!(
+                        "Column {} of type {} can't be widened to {}",
+                        field.name(),
+                        input_type,
+                        field.data_type()
+                    )));
+                }
+                Ok(Some(i))
+            }
+            Err(_) if field.is_nullable() => Ok(None),
+            Err(_) => Err(schema_error(format!(
+                "Non-nullable column {} is missing from stream",
+                field.name()
+            ))),
+        })
+        .collect()
+}
+
+fn unify_batch(
+    batch: &RecordBatch,
+    schema: &SchemaRef,
+    columns: &[Option<usize>],
+) -> std::result::Result<RecordBatch, DataFusionError> {
+    let arrays = schema
+        .fields()
+        .iter()
+        .zip(columns)
+        .map(|(field, column)| match column {
+            Some(i) if batch.column(*i).data_type() == field.data_type() => {
+                Ok(batch.column(*i).clone())
+            }
+            Some(i) => cast(batch.column(*i), field.data_type()),
+            None => Ok(new_null_array(field.data_type(), batch.num_rows())),
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let options = RecordBatchOptions::new().with_row_count(Some(batch.num_rows()));
+    Ok(RecordBatch::try_new_with_options(
+        schema.clone(),
+        arrays,
+        &options,
+    )?)
+}
+
+/// Concatenate `streams`, one after the other, into a stream of `schema`.
+///
+/// Columns are matched by name. Columns missing from a stream are filled
+/// with nulls, and columns of another type are cast to the type of `schema`,
+/// which must be at least as wide, such as `Int64` for an `Int32` column, so
+/// that no value changes. Streams must be compatible with `schema`: they
+/// can't miss non-nullable columns or have columns that can't be widened,
+/// and, depending on `extra_columns`, can't have columns that are not in
+/// `schema`.
+pub fn unify_streams(
+    streams: Vec<SendableRecordBatchStream>,
+    schema: SchemaRef,
+    extra_columns: ExtraColumns,
+) -> Result<SendableRecordBatchStream> {
+    let streams = streams
+        .into_iter()
+        .map(|stream| {
+            let columns = plan_columns(stream.schema().as_ref(), schema.as_ref(), extra_columns)?;
+            let schema = schema.clone();
+            Ok(stream.map(move |batch| unify_batch(&batch?, &schema, &columns)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Box::pin(RecordBatchStreamAdapter::new(
+        schema,
+        stream::iter(streams).flatten(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{
+        Array, ArrayRef, Float64Array, Int32Array, Int64Array, StringArray, UInt64Array,
+    };
+    use arrow_schema::Field;
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    fn stream_of(batch: RecordBatch) -> SendableRecordBatchStream {
+        Box::pin(RecordBatchStreamAdapter::new(
+            batch.schema(),
+            stream::iter(vec![Ok(batch)]),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_unify_streams() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let first = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        )
+        .unwrap();
+        // Narrower ids, no names, and an extra column
+        let second = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                Field::new("extra", DataType::Utf8, false),
+                Field::new("id", DataType::Int32, false),
+            ])),
+            vec![
+                Arc::new(StringArray::from(vec!["x"])),
+                Arc::new(Int32Array::from(vec![3])),
+            ],
+        )
+        .unwrap();
+
+        let streams = vec![stream_of(first.clone()), stream_of(second.clone())];
+        assert!(unify_streams(streams, schema.clone(), ExtraColumns::Error).is_err());
+
+        let streams = vec![stream_of(first.clone()), stream_of(second.clone())];
+        let batches = unify_streams(streams, schema.clone(), ExtraColumns::Drop)
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0], first);
+        assert_eq!(batches[1].schema(), schema);
+        assert_eq!(
+            batches[1].column(0).as_ref(),
+            &Int64Array::from(vec![3]) as &dyn Array
+        );
+        assert_eq!(batches[1].column(1).null_count(), 1);
+
+        // A non-nullable column can't be missing
+        let no_id = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![Field::new(
+                "name",
+                DataType::Utf8,
+                true,
+            )])),
+            vec![Arc::new(StringArray::from(vec!["c"]))],
+        )
+        .unwrap();
+        let err = unify_streams(vec![stream_of(no_id)], schema.clone(), ExtraColumns::Drop)
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("Non-nullable column id"),
+            "{}",
+            err
+        );
+
+        // Columns can only be widened
+        for (data_type, values) in [
+            (
+                DataType::Utf8,
+                Arc::new(StringArray::from(vec!["4"])) as ArrayRef,
+            ),
+            (
+                DataType::UInt64,
+                Arc::new(UInt64Array::from(vec![4])) as ArrayRef,
+            ),
+            (
+                DataType::Float64,
+                Arc::new(Float64Array::from(vec![4.0])) as ArrayRef,
+            ),
+        ] {
+            let batch = RecordBatch::try_new(
+                Arc::new(ArrowSchema::new(vec![Field::new("id", data_type, false)])),
+                vec![values],
+            )
+            .unwrap();
+            let err = unify_streams(vec![stream_of(batch)], schema.clone(), ExtraColumns::Drop)
+                .err()
+                .unwrap();
+            assert!(err.to_string().contains("can't be widened"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_is_widening_cast() {
+        use DataType::*;
+        for (from, to) in [
+            (Int32, Int32),
+            (Int8, Int64),
+            (UInt32, UInt64),
+            (UInt32, Int64),
+            (Int32, Float64),
+            (UInt16, Float32),
+            (Float32, Float64),
+            (Utf8, LargeUtf8),
+        ] {
+            assert!(is_widening_cast(&from, &to), "{from} to {to}");
+        }
+        for (from, to) in [
+            (Int64, Int32),
+            (Int32, UInt64),
+            (UInt32, Int32),
+            (Int64, Float64),
+            (Int32, Float32),
+            (Float64, Float32),
+            (LargeUtf8, Utf8),
+            (Utf8, Int64),
+        ] {
+            assert!(!is_widening_cast(&from, &to), "{from} to {to}");
+        }
+    }
+}
@@ -2,9 +2,11 @@
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
 use arrow_array::{RecordBatchIterator, RecordBatchReader};
+use arrow_schema::{DataType, Schema as ArrowSchema};
 use datafusion::physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream};
 use datafusion_common::DataFusionError;
 use futures::{stream, Stream, StreamExt, TryFutureExt, TryStreamExt};
+use lance_arrow::DataTypeExt;
 use lance_core::datatypes::Schema;
 use lance_core::{Error, Result};
 use tokio::task::spawn_blocking;
@@ -64,3 +66,92 @@ pub fn reader_to_stream(batches: Box<dyn RecordBatchReader + Send>) -> SendableR
     );
     Box::pin(stream)
 }
+
+/// Estimated size of a value of a variable-width type, such as a string
+const ESTIMATED_VARIABLE_WIDTH: usize = 64;
+/// Estimated number of items of a list
+const ESTIMATED_LIST_LENGTH: usize = 8;
+
+/// Estimated in-memory size of a value of `data_type`, in bytes
+fn estimated_value_width(data_type: &DataType) -> usize {
+    match data_type {
+        DataType::Null => 0,
+        // Rounded up from a bit
+        DataType::Boolean => 1,
+        DataType::Utf8 | DataType::Binary => 4 + ESTIMATED_VARIABLE_WIDTH,
+        DataType::LargeUtf8 | DataType::LargeBinary => 8 + ESTIMATED_VARIABLE_WIDTH,
+        DataType::List(field) => {
+            4 + ESTIMATED_LIST_LENGTH * estimated_value_width(field.data_type())
+        }
+        DataType::LargeList(field) => {
+            8 + ESTIMATED_LIST_LENGTH * estimated_value_width(field.data_type())
+        }
+        DataType::FixedSizeList(field, size) => {
+            *size as usize * estimated_value_width(field.data_type())
+        }
+        DataType::Struct(fields) => fields
+            .iter()
+            .map(|field| estimated_value_width(field.data_type()))
+            .sum(),
+        DataType::Dictionary(key_type, _) => key_type.byte_width(),
+        data_type if data_type.is_fixed_stride() => data_type.byte_width(),
+        _ => ESTIMATED_VARIABLE_WIDTH,
+    }
+}
+
+/// Estimated in-memory size of a row of `schema`, in bytes
+///
+/// Fixed-width columns are counted exactly.  Strings and binaries are
+/// assumed to be [`ESTIMATED_VARIABLE_WIDTH`] bytes long, and lists to have
+/// [`ESTIMATED_LIST_LENGTH`] items.
+pub fn estimated_row_width(schema: &ArrowSchema) -> usize {
+    schema
+        .fields()
+        .iter()
+        .map(|field| estimated_value_width(field.data_type()))
+        .sum()
+}
+
+/// The number of rows of `schema` in a batch of about `target_bytes`
+///
+/// This is at least one row, and `max_rows` at most.
+pub fn batch_size_for_bytes(schema: &ArrowSchema, target_bytes: usize, max_rows: usize) -> usize {
+    let row_width = estimated_row_width(schema).max(1);
+    (target_bytes / row_width).clamp(1, max_rows.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_schema::Field;
+
+    use super::*;
+
+    #[test]
+    fn test_batch_size_for_bytes() {
+        let narrow = ArrowSchema::new(vec![Field::new("i", DataType::Int32, false)]);
+        let wide = ArrowSchema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(
+                    Arc::new(Field::new("item", DataType::Float32, true)),
+                    1024,
+                ),
+                false,
+            ),
+        ]);
+        assert_eq!(estimated_row_width(&narrow), 4);
+        assert_eq!(estimated_row_width(&wide), 4 + 4096);
+
+        let target_bytes = 16 * 1024 * 1024;
+        assert_eq!(batch_size_for_bytes(&narrow, target_bytes, 8192), 8192);
+        assert_eq!(batch_size_for_bytes(&wide, target_bytes, 8192), 4092);
+        // A row larger than the target is still read
+        assert_eq!(batch_size_for_bytes(&wide, 1024, 8192), 1);
+
+        let strings = ArrowSchema::new(vec![Field::new("s", DataType::Utf8, true)]);
+        assert_eq!(estimated_row_width(&strings), 4 + ESTIMATED_VARIABLE_WIDTH);
+    }
+}
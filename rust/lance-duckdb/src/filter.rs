@@ -0,0 +1,129 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+/// A constant of a filter pushed down by DuckDB.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Boolean(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Varchar(String),
+}
+
+impl Value {
+    fn to_sql(&self) -> Option<String> {
+        match self {
+            Self::Boolean(value) => Some(value.to_string()),
+            Self::Int(value) => Some(value.to_string()),
+            Self::UInt(value) => Some(value.to_string()),
+            // NaN and infinities have no SQL literal
+            Self::Float(value) if value.is_finite() => Some(format!("{:?}", value)),
+            Self::Float(_) => None,
+            Self::Varchar(value) => Some(format!("'{}'", value.replace('\'', "''"))),
+        }
+    }
+}
+
+/// The comparison of a [`TableFilter::Compare`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+}
+
+impl CompareOp {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Self::Equal => "=",
+            Self::NotEqual => "!=",
+            Self::LessThan => "<",
+            Self::LessThanOrEqual => "<=",
+            Self::GreaterThan => ">",
+            Self::GreaterThanOrEqual => ">=",
+        }
+    }
+}
+
+/// A filter DuckDB pushes down to a column, mirroring DuckDB's
+/// `TableFilter`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableFilter {
+    /// The column compared with a constant.
+    Compare(CompareOp, Value),
+    IsNull,
+    IsNotNull,
+    /// All the filters hold.
+    And(Vec<Self>),
+    /// Any of the filters holds.
+    Or(Vec<Self>),
+}
+
+impl TableFilter {
+    /// The filter on `column` as a Lance SQL filter, or `None` if it can't be
+    /// expressed.
+    ///
+    /// A filter that can't be expressed must be applied by the extension.
+    /// Parts of an `And` are never dropped, as that would return more rows
+    /// than DuckDB expects.
+    pub fn to_sql(&self, column: &str) -> Option<String> {
+        let column = format!("`{}`", column.replace('`', "``"));
+        self.column_to_sql(&column)
+    }
+
+    fn column_to_sql(&self, column: &str) -> Option<String> {
+        match self {
+            Self::Compare(op, value) => {
+                Some(format!("{} {} {}", column, op.as_sql(), value.to_sql()?))
+            }
+            Self::IsNull => Some(format!("{} IS NULL", column)),
+            Self::IsNotNull => Some(format!("{} IS NOT NULL", column)),
+            Self::And(filters) => Self::join(filters, column, " AND "),
+            Self::Or(filters) => Self::join(filters, column, " OR "),
+        }
+    }
+
+    fn join(filters: &[Self], column: &str, separator: &str) -> Option<String> {
+        let filters = filters
+            .iter()
+            .map(|filter| filter.column_to_sql(column).map(|sql| format!("({})", sql)))
+            .collect::<Option<Vec<_>>>()?;
+        (!filters.is_empty()).then(|| filters.join(separator))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_to_sql() {
+        let filter = TableFilter::And(vec![
+            TableFilter::IsNotNull,
+            TableFilter::Compare(CompareOp::GreaterThanOrEqual, Value::Int(-3)),
+            TableFilter::Or(vec![
+                TableFilter::Compare(CompareOp::LessThan, Value::Float(2.5)),
+                TableFilter::Compare(CompareOp::Equal, Value::Float(10.0)),
+            ]),
+        ]);
+        assert_eq!(
+            filter.to_sql("x").unwrap(),
+            "(`x` IS NOT NULL) AND (`x` >= -3) AND ((`x` < 2.5) OR (`x` = 10.0))"
+        );
+
+        let filter = TableFilter::Compare(CompareOp::NotEqual, Value::Varchar("it's".into()));
+        assert_eq!(filter.to_sql("a`b").unwrap(), "`a``b` != 'it''s'");
+
+        // Filters are pushed down whole or not at all
+        let filter = TableFilter::And(vec![
+            TableFilter::IsNotNull,
+            TableFilter::Compare(CompareOp::LessThan, Value::Float(f64::NAN)),
+        ]);
+        assert_eq!(filter.to_sql("x"), None);
+        assert_eq!(TableFilter::Or(vec![]).to_sql("x"), None);
+    }
+}
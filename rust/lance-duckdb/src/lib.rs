@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Glue for a DuckDB table function that scans Lance datasets.
+//!
+//! DuckDB table functions go through three phases, and this crate provides
+//! the Lance side of each of them, so an extension only has to convert
+//! between DuckDB's API and these types:
+//!
+//! 1. *bind*: [`LanceBindData::try_new`] opens the dataset and returns the
+//!    names and DuckDB types of its columns, see [`duckdb_type`].
+//! 2. *init*: [`LanceBindData::init`] takes the ids of the projected columns
+//!    and the filters DuckDB pushed down, see [`TableFilter`], and plans a
+//!    scan, shared by all threads of the query.
+//! 3. *scan*: each thread creates a [`LanceLocalState`] and calls
+//!    [`LanceLocalState::next_batch`] until it returns `None`. Threads scan
+//!    different fragments, so a query can use up to
+//!    [`LanceGlobalState::max_threads`] threads.
+//!
+//! Batches are Arrow record batches, to be exported to DuckDB through the
+//! Arrow C data interface. All functions block the calling thread.
+
+mod filter;
+mod scan;
+mod types;
+
+pub use filter::{CompareOp, TableFilter, Value};
+pub use scan::{LanceBindData, LanceGlobalState, LanceLocalState, ROW_ID_COLUMN_INDEX};
+pub use types::duckdb_type;
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref RT: tokio::runtime::Runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to create tokio runtime");
+}
@@ -0,0 +1,313 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use lance::dataset::ROW_ID;
+use lance::{Dataset, Error, Result};
+use lance_io::ffi::{BlockingRecordBatchReader, DEFAULT_READ_AHEAD};
+use lance_table::format::Fragment;
+use snafu::{location, Location};
+
+use crate::filter::TableFilter;
+use crate::types::duckdb_type;
+use crate::RT;
+
+/// The column id DuckDB uses for row ids, `COLUMN_IDENTIFIER_ROW_ID`.
+pub const ROW_ID_COLUMN_INDEX: u64 = u64::MAX;
+
+/// The result of binding the table function to a dataset.
+#[derive(Debug)]
+pub struct LanceBindData {
+    dataset: Arc<Dataset>,
+    names: Vec<String>,
+    types: Vec<String>,
+}
+
+impl LanceBindData {
+    /// Open `version` of the dataset at `uri`, or its latest version.
+    ///
+    /// The columns of the table are the top-level columns of the dataset,
+    /// except those of a type DuckDB can't read, see [`duckdb_type`]. The id
+    /// of a column is its position in [`Self::column_names`].
+    pub fn try_new(uri: &str, version: Option<u64>) -> Result<Self> {
+        let dataset = RT.block_on(async {
+            let dataset = Dataset::open(uri).await?;
+            match version {
+                Some(version) => dataset.checkout_version(version).await,
+                None => Ok(dataset),
+            }
+        })?;
+        let (names, types) = dataset
+            .schema()
+            .fields
+            .iter()
+            .filter_map(|field| {
+                let duckdb_type = duckdb_type(&field.data_type()).ok()?;
+                Some((field.name.clone(), duckdb_type))
+            })
+            .unzip();
+        Ok(Self {
+            dataset: Arc::new(dataset),
+            names,
+            types,
+        })
+    }
+
+    /// The names of the columns.
+    pub fn column_names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// The DuckDB types of the columns.
+    pub fn column_types(&self) -> &[String] {
+        &self.types
+    }
+
+    /// The number of rows of the dataset, for DuckDB's cardinality estimate.
+    pub fn cardinality(&self) -> Result<usize> {
+        RT.block_on(self.dataset.count_rows(None))
+    }
+
+    /// Plan a scan of the columns with ids `column_ids`, in this order, of
+    /// the rows matching `filters`, pairs of a column id and a filter.
+    ///
+    /// Filters that can't be pushed down to Lance are listed by
+    /// [`LanceGlobalState::unapplied_filters`].
+    pub fn init(
+        &self,
+        column_ids: &[u64],
+        filters: &[(u64, TableFilter)],
+    ) -> Result<LanceGlobalState> {
+        let column_name = |id: u64| {
+            if id == ROW_ID_COLUMN_INDEX {
+                return Ok(ROW_ID);
+            }
+            self.names
+                .get(id as usize)
+                .map(String::as_str)
+                .ok_or_else(|| {
+                    Error::invalid_input(format!("Column {} does not exist", id), location!())
+                })
+        };
+        let columns = column_ids
+            .iter()
+            .map(|id| column_name(*id).map(String::from))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut pushed_down = Vec::new();
+        let mut unapplied_filters = Vec::new();
+        for (i, (id, filter)) in filters.iter().enumerate() {
+            match filter.to_sql(column_name(*id)?) {
+                Some(sql) => pushed_down.push(format!("({})", sql)),
+                None => unapplied_filters.push(i),
+            }
+        }
+        let filter = (!pushed_down.is_empty()).then(|| pushed_down.join(" AND "));
+
+        Ok(LanceGlobalState {
+            dataset: self.dataset.clone(),
+            columns,
+            filter,
+            unapplied_filters,
+            fragments: self.dataset.manifest().fragments.as_ref().clone(),
+            next_fragment: AtomicUsize::new(0),
+        })
+    }
+}
+
+/// The state of a scan shared by all threads.
+#[derive(Debug)]
+pub struct LanceGlobalState {
+    dataset: Arc<Dataset>,
+    /// Names of the output columns, including `_rowid`
+    columns: Vec<String>,
+    filter: Option<String>,
+    unapplied_filters: Vec<usize>,
+    fragments: Vec<Fragment>,
+    next_fragment: AtomicUsize,
+}
+
+impl LanceGlobalState {
+    /// The most threads that can scan at the same time.
+    pub fn max_threads(&self) -> usize {
+        self.fragments.len().max(1)
+    }
+
+    /// The positions of the filters given to [`LanceBindData::init`] that
+    /// were not pushed down and must be applied by the extension.
+    pub fn unapplied_filters(&self) -> &[usize] {
+        &self.unapplied_filters
+    }
+
+    /// A reader of the next fragment no thread scans yet.
+    fn next_reader(&self) -> Result<Option<BlockingRecordBatchReader>> {
+        let i = self.next_fragment.fetch_add(1, Ordering::Relaxed);
+        let Some(fragment) = self.fragments.get(i) else {
+            return Ok(None);
+        };
+        let mut scanner = self.dataset.scan();
+        scanner.with_fragments(vec![fragment.clone()]);
+        let projection = self
+            .columns
+            .iter()
+            .filter(|column| *column != ROW_ID)
+            .collect::<Vec<_>>();
+        scanner.project(&projection)?;
+        // Scans of no column, such as for `count(*)`, still need the number
+        // of rows
+        if projection.len() < self.columns.len() || projection.is_empty() {
+            scanner.with_row_id();
+        }
+        if let Some(filter) = &self.filter {
+            scanner.filter(filter)?;
+        }
+        let stream = RT.block_on(scanner.try_into_stream())?;
+        Ok(Some(BlockingRecordBatchReader::with_runtime(
+            stream,
+            RT.handle().clone(),
+            DEFAULT_READ_AHEAD,
+        )))
+    }
+
+    /// The columns of `batch` in the order of the scan.
+    fn reorder(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        let schema = batch.schema();
+        let indices = self
+            .columns
+            .iter()
+            .map(|column| schema.index_of(column))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(batch.project(&indices)?)
+    }
+}
+
+/// The state of a scan of a single thread.
+#[derive(Default)]
+pub struct LanceLocalState {
+    reader: Option<BlockingRecordBatchReader>,
+}
+
+impl LanceLocalState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The next batch of the scan, or `None` once no fragment is left.
+    pub fn next_batch(&mut self, global: &LanceGlobalState) -> Result<Option<RecordBatch>> {
+        loop {
+            if let Some(reader) = self.reader.as_mut() {
+                match reader.next() {
+                    Some(batch) => return Ok(Some(global.reorder(batch?)?)),
+                    None => self.reader = None,
+                }
+            }
+            match global.next_reader()? {
+                Some(reader) => self.reader = Some(reader),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Int32Array, RecordBatchIterator, StringArray};
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use lance::dataset::WriteParams;
+
+    use super::*;
+    use crate::filter::{CompareOp, Value};
+
+    #[test]
+    fn test_scan() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new("s", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..30)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..30).map(|i| format!("s-{}", i)),
+                )),
+            ],
+        )
+        .unwrap();
+        let params = WriteParams {
+            max_rows_per_file: 10,
+            ..Default::default()
+        };
+        RT.block_on(Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema),
+            uri,
+            Some(params),
+        ))
+        .unwrap();
+
+        let bind = LanceBindData::try_new(uri, None).unwrap();
+        assert_eq!(bind.column_names(), &["i", "s"]);
+        assert_eq!(bind.column_types(), &["INTEGER", "VARCHAR"]);
+        assert_eq!(bind.cardinality().unwrap(), 30);
+
+        let filters = [
+            (
+                0,
+                TableFilter::Compare(CompareOp::GreaterThanOrEqual, Value::Int(5)),
+            ),
+            (
+                0,
+                TableFilter::Compare(CompareOp::LessThan, Value::Float(f64::NAN)),
+            ),
+        ];
+        let global = bind.init(&[1, ROW_ID_COLUMN_INDEX, 0], &filters).unwrap();
+        assert_eq!(global.max_threads(), 3);
+        assert_eq!(global.unapplied_filters(), &[1]);
+
+        // Two threads share the fragments
+        let mut threads = [LanceLocalState::new(), LanceLocalState::new()];
+        let mut values = vec![];
+        let mut done = [false, false];
+        while !done.iter().all(|done| *done) {
+            for (thread, done) in threads.iter_mut().zip(done.iter_mut()) {
+                match thread.next_batch(&global).unwrap() {
+                    Some(batch) => {
+                        let names = batch
+                            .schema()
+                            .fields()
+                            .iter()
+                            .map(|field| field.name().clone())
+                            .collect::<Vec<_>>();
+                        assert_eq!(names, vec!["s", ROW_ID, "i"]);
+                        let column = batch
+                            .column(2)
+                            .as_any()
+                            .downcast_ref::<Int32Array>()
+                            .unwrap();
+                        values.extend(column.values().iter().copied());
+                    }
+                    None => *done = true,
+                }
+            }
+        }
+        values.sort();
+        assert_eq!(values, (5..30).collect::<Vec<_>>());
+
+        // No columns, as for count(*)
+        let global = bind.init(&[], &[]).unwrap();
+        let mut thread = LanceLocalState::new();
+        let mut num_rows = 0;
+        while let Some(batch) = thread.next_batch(&global).unwrap() {
+            assert_eq!(batch.num_columns(), 0);
+            num_rows += batch.num_rows();
+        }
+        assert_eq!(num_rows, 30);
+
+        assert!(bind.init(&[2], &[]).is_err());
+    }
+}
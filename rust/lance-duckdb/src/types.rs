@@ -0,0 +1,106 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use arrow_schema::{DataType, TimeUnit};
+use lance::{Error, Result};
+use snafu::{location, Location};
+
+/// The DuckDB type of Arrow columns of type `data_type`, in DuckDB's SQL
+/// syntax.
+///
+/// Dictionary columns have the type of their values. Types DuckDB has no
+/// equivalent of are an error, and those columns can't be scanned.
+pub fn duckdb_type(data_type: &DataType) -> Result<String> {
+    let duckdb_type = match data_type {
+        DataType::Boolean => "BOOLEAN".to_string(),
+        DataType::Int8 => "TINYINT".to_string(),
+        DataType::Int16 => "SMALLINT".to_string(),
+        DataType::Int32 => "INTEGER".to_string(),
+        DataType::Int64 => "BIGINT".to_string(),
+        DataType::UInt8 => "UTINYINT".to_string(),
+        DataType::UInt16 => "USMALLINT".to_string(),
+        DataType::UInt32 => "UINTEGER".to_string(),
+        DataType::UInt64 => "UBIGINT".to_string(),
+        DataType::Float16 | DataType::Float32 => "FLOAT".to_string(),
+        DataType::Float64 => "DOUBLE".to_string(),
+        DataType::Decimal128(precision, scale) => format!("DECIMAL({}, {})", precision, scale),
+        DataType::Utf8 | DataType::LargeUtf8 => "VARCHAR".to_string(),
+        DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_) => {
+            "BLOB".to_string()
+        }
+        DataType::Date32 | DataType::Date64 => "DATE".to_string(),
+        DataType::Time32(_) | DataType::Time64(_) => "TIME".to_string(),
+        DataType::Timestamp(_, Some(_)) => "TIMESTAMP WITH TIME ZONE".to_string(),
+        DataType::Timestamp(TimeUnit::Second, None) => "TIMESTAMP_S".to_string(),
+        DataType::Timestamp(TimeUnit::Millisecond, None) => "TIMESTAMP_MS".to_string(),
+        DataType::Timestamp(TimeUnit::Microsecond, None) => "TIMESTAMP".to_string(),
+        DataType::Timestamp(TimeUnit::Nanosecond, None) => "TIMESTAMP_NS".to_string(),
+        DataType::Interval(_) | DataType::Duration(_) => "INTERVAL".to_string(),
+        DataType::List(item) | DataType::LargeList(item) => {
+            format!("{}[]", duckdb_type(item.data_type())?)
+        }
+        DataType::FixedSizeList(item, size) => {
+            format!("{}[{}]", duckdb_type(item.data_type())?, size)
+        }
+        DataType::Struct(fields) => {
+            let fields = fields
+                .iter()
+                .map(|field| {
+                    Ok(format!(
+                        "\"{}\" {}",
+                        field.name().replace('"', "\"\""),
+                        duckdb_type(field.data_type())?
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            format!("STRUCT({})", fields.join(", "))
+        }
+        DataType::Dictionary(_, value_type) => duckdb_type(value_type)?,
+        _ => {
+            return Err(Error::invalid_input(
+                format!("Columns of type {} can't be read by DuckDB", data_type),
+                location!(),
+            ))
+        }
+    };
+    Ok(duckdb_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_schema::{Field, Fields};
+
+    use super::*;
+
+    #[test]
+    fn test_duckdb_type() {
+        assert_eq!(duckdb_type(&DataType::Int32).unwrap(), "INTEGER");
+        assert_eq!(
+            duckdb_type(&DataType::Decimal128(10, 2)).unwrap(),
+            "DECIMAL(10, 2)"
+        );
+        assert_eq!(
+            duckdb_type(&DataType::Timestamp(
+                TimeUnit::Microsecond,
+                Some("UTC".into())
+            ))
+            .unwrap(),
+            "TIMESTAMP WITH TIME ZONE"
+        );
+        let vector =
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 128);
+        assert_eq!(duckdb_type(&vector).unwrap(), "FLOAT[128]");
+        let tags = DataType::List(Arc::new(Field::new("item", DataType::Utf8, true)));
+        let nested = DataType::Struct(Fields::from(vec![
+            Field::new("id", DataType::UInt64, false),
+            Field::new("tags", tags, true),
+        ]));
+        assert_eq!(
+            duckdb_type(&nested).unwrap(),
+            "STRUCT(\"id\" UBIGINT, \"tags\" VARCHAR[])"
+        );
+        assert!(duckdb_type(&DataType::Null).is_err());
+    }
+}
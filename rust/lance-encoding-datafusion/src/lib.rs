@@ -3,7 +3,7 @@
 
 use arrow_schema::DataType;
 use lance_encoding::encoder::{
-    ColumnIndexSequence, CoreFieldEncodingStrategy, FieldEncodingStrategy,
+    ColumnIndexSequence, CoreFieldEncodingStrategy, EncodingOptions, FieldEncodingStrategy,
 };
 use zone::ZoneMapsFieldEncoder;
 
@@ -24,12 +24,13 @@ impl FieldEncodingStrategy for LanceDfFieldEncodingStrategy {
         encoding_strategy_root: &dyn FieldEncodingStrategy,
         field: &lance_core::datatypes::Field,
         column_index: &mut ColumnIndexSequence,
-        cache_bytes_per_column: u64,
-        keep_original_array: bool,
+        options: &EncodingOptions,
         config: &std::collections::HashMap<String, String>,
     ) -> lance_core::Result<Box<dyn lance_encoding::encoder::FieldEncoder>> {
         let data_type = field.data_type();
-        if data_type.is_primitive()
+        // DataFusion's min/max accumulators don't support durations and intervals
+        let has_min_max = !matches!(data_type, DataType::Duration(_) | DataType::Interval(_));
+        if (data_type.is_primitive() && has_min_max)
             || matches!(
                 data_type,
                 DataType::Boolean | DataType::Utf8 | DataType::LargeUtf8
@@ -40,8 +41,7 @@ impl FieldEncodingStrategy for LanceDfFieldEncodingStrategy {
                 &self.core,
                 field,
                 column_index,
-                cache_bytes_per_column,
-                keep_original_array,
+                options,
                 config,
             )?;
             Ok(Box::new(ZoneMapsFieldEncoder::try_new(
@@ -54,8 +54,7 @@ impl FieldEncodingStrategy for LanceDfFieldEncodingStrategy {
                 encoding_strategy_root,
                 field,
                 column_index,
-                cache_bytes_per_column,
-                keep_original_array,
+                options,
                 config,
             )
         }
@@ -168,12 +168,17 @@ impl FieldEncoder for ZoneMapsFieldEncoder {
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
+    use std::sync::Arc;
 
     use arrow_array::types::Int32Type;
+    use arrow_array::{Array, ArrayRef, Decimal128Array, Decimal256Array};
+    use arrow_buffer::i256;
     use arrow_schema::DataType;
+    use datafusion_common::ScalarValue;
     use lance_datagen::{BatchCount, RowCount};
     use lance_encoding::encoder::{
-        ColumnIndexSequence, CoreFieldEncodingStrategy, FieldEncoder, FieldEncodingStrategy,
+        ColumnIndexSequence, CoreFieldEncodingStrategy, EncodingOptions, FieldEncoder,
+        FieldEncodingStrategy,
     };
 
     #[tokio::test]
@@ -191,8 +196,11 @@ mod tests {
                 &encoding_strategy,
                 &mock_field,
                 &mut col_idx_seq,
-                4096,
-                true,
+                &EncodingOptions {
+                    cache_bytes_per_column: 4096,
+                    keep_original_array: true,
+                    ..Default::default()
+                },
                 &HashMap::new(),
             )
             .unwrap();
@@ -218,4 +226,65 @@ mod tests {
         // TODO: Test out the different types
         assert!(!zone_maps_buffer.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_decimal_stats() {
+        let encoding_strategy = CoreFieldEncodingStrategy::default();
+        let decimal128: ArrayRef = Arc::new(
+            Decimal128Array::from_iter_values((0..150).map(|v| v * 7 - 500))
+                .with_precision_and_scale(10, 2)
+                .unwrap(),
+        );
+        let decimal256: ArrayRef = Arc::new(
+            Decimal256Array::from_iter_values((0..150).map(|v| i256::from_i128(v * 7 - 500)))
+                .with_precision_and_scale(50, 2)
+                .unwrap(),
+        );
+        for array in [decimal128, decimal256] {
+            let data_type = array.data_type().clone();
+            let field = lance_core::datatypes::Field::try_from(arrow_schema::Field::new(
+                "price",
+                data_type.clone(),
+                false,
+            ))
+            .unwrap();
+            let inner = encoding_strategy
+                .create_field_encoder(
+                    &encoding_strategy,
+                    &field,
+                    &mut ColumnIndexSequence::default(),
+                    &EncodingOptions {
+                        cache_bytes_per_column: 4096,
+                        keep_original_array: true,
+                        ..Default::default()
+                    },
+                    &HashMap::new(),
+                )
+                .unwrap();
+            let mut encoder =
+                super::ZoneMapsFieldEncoder::try_new(inner, data_type.clone(), 100).unwrap();
+            encoder.maybe_encode(array.clone()).unwrap();
+            encoder.flush().unwrap();
+
+            // The zones keep the precision and scale of the column
+            let bounds = encoder
+                .maps
+                .iter()
+                .map(|map| (map.min.clone(), map.max.clone()))
+                .collect::<Vec<_>>();
+            let expected = [(0, 99), (100, 149)]
+                .iter()
+                .map(|(min, max)| {
+                    (
+                        ScalarValue::try_from_array(&array, *min).unwrap(),
+                        ScalarValue::try_from_array(&array, *max).unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(bounds, expected, "{}", data_type);
+
+            let zone_maps_buffer = encoder.finish().await.unwrap();
+            assert_eq!(zone_maps_buffer.len(), 1);
+        }
+    }
 }
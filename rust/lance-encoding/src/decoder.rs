@@ -226,6 +226,7 @@ use log::trace;
 use snafu::{location, Location};
 use tokio::sync::mpsc::{self, unbounded_channel};
 
+use lance_core::utils::tokio::DecodeLimit;
 use lance_core::{Error, Result};
 use tracing::instrument;
 
@@ -647,6 +648,7 @@ pub struct BatchDecodeStream {
     rows_per_batch: u32,
     rows_scheduled: u64,
     rows_drained: u64,
+    decode_limit: DecodeLimit,
 }
 
 impl BatchDecodeStream {
@@ -673,9 +675,18 @@ impl BatchDecodeStream {
             rows_per_batch,
             rows_scheduled: 0,
             rows_drained: 0,
+            decode_limit: DecodeLimit::default(),
         }
     }
 
+    /// Bound how many batches of this stream are decoded at the same time
+    ///
+    /// By default only the process-wide decode limit applies.
+    pub fn with_decode_limit(mut self, decode_limit: DecodeLimit) -> Self {
+        self.decode_limit = decode_limit;
+        self
+    }
+
     fn accept_decoder(&mut self, decoder: DecoderReady) -> Result<()> {
         if decoder.path.is_empty() {
             // The root decoder we can ignore
@@ -767,11 +778,14 @@ impl BatchDecodeStream {
     pub fn into_stream(self) -> BoxStream<'static, ReadBatchTask> {
         let stream = futures::stream::unfold(self, |mut slf| async move {
             let next_task = slf.next_batch_task().await;
+            let decode_limit = slf.decode_limit.clone();
             let next_task = next_task.transpose().map(|next_task| {
                 let num_rows = next_task.as_ref().map(|t| t.num_rows).unwrap_or(0);
                 let task = tokio::spawn(async move {
                     let next_task = next_task?;
-                    Self::task_to_batch(next_task)
+                    decode_limit
+                        .run(move || Self::task_to_batch(next_task))
+                        .await
                 });
                 (task, num_rows)
             });
@@ -234,6 +234,32 @@ impl ColumnIndexSequence {
     }
 }
 
+/// Options that control how the data of each column is split into pages
+#[derive(Debug, Clone)]
+pub struct EncodingOptions {
+    /// How many bytes of data to buffer for a column before writing a page
+    ///
+    /// This is the target size of a page, before it is encoded.
+    pub cache_bytes_per_column: u64,
+    /// How many values to buffer for a column before writing a page
+    ///
+    /// A page is written once either this or `cache_bytes_per_column` is
+    /// reached.  Incoming arrays are split so that no page holds more values.
+    pub max_page_rows: u64,
+    /// Whether to keep the buffered arrays as they are, instead of copying them
+    pub keep_original_array: bool,
+}
+
+impl Default for EncodingOptions {
+    fn default() -> Self {
+        Self {
+            cache_bytes_per_column: 8 * 1024 * 1024,
+            max_page_rows: u64::MAX,
+            keep_original_array: false,
+        }
+    }
+}
+
 /// A trait to pick which kind of field encoding to use for a field
 ///
 /// Unlike the ArrayEncodingStrategy, the field encoding strategy is
@@ -256,8 +282,7 @@ pub trait FieldEncodingStrategy: Send + Sync + std::fmt::Debug {
         encoding_strategy_root: &dyn FieldEncodingStrategy,
         field: &Field,
         column_index: &mut ColumnIndexSequence,
-        cache_bytes_per_column: u64,
-        keep_original_array: bool,
+        options: &EncodingOptions,
         config: &HashMap<String, String>,
     ) -> Result<Box<dyn FieldEncoder>>;
 }
@@ -283,8 +308,7 @@ impl FieldEncodingStrategy for CoreFieldEncodingStrategy {
         encoding_strategy_root: &dyn FieldEncodingStrategy,
         field: &Field,
         column_index: &mut ColumnIndexSequence,
-        cache_bytes_per_column: u64,
-        keep_original_array: bool,
+        options: &EncodingOptions,
         _config: &HashMap<String, String>,
     ) -> Result<Box<dyn FieldEncoder>> {
         match field.data_type() {
@@ -313,8 +337,7 @@ impl FieldEncodingStrategy for CoreFieldEncodingStrategy {
             | DataType::UInt8
             | DataType::FixedSizeBinary(_)
             | DataType::FixedSizeList(_, _) => Ok(Box::new(PrimitiveFieldEncoder::try_new(
-                cache_bytes_per_column,
-                keep_original_array,
+                options,
                 self.array_encoding_strategy.clone(),
                 column_index.next_column_index(field.id),
             )?)),
@@ -324,14 +347,12 @@ impl FieldEncodingStrategy for CoreFieldEncodingStrategy {
                     encoding_strategy_root,
                     &field.children[0],
                     column_index,
-                    cache_bytes_per_column,
-                    keep_original_array,
+                    options,
                     child.metadata(),
                 )?;
                 Ok(Box::new(ListFieldEncoder::new(
                     inner_encoding,
-                    cache_bytes_per_column,
-                    keep_original_array,
+                    options,
                     list_idx,
                 )))
             }
@@ -345,8 +366,7 @@ impl FieldEncodingStrategy for CoreFieldEncodingStrategy {
                             encoding_strategy_root,
                             field,
                             column_index,
-                            cache_bytes_per_column,
-                            keep_original_array,
+                            options,
                             &field.metadata,
                         )
                     })
@@ -359,11 +379,7 @@ impl FieldEncodingStrategy for CoreFieldEncodingStrategy {
             DataType::Utf8 | DataType::Binary | DataType::LargeUtf8 | DataType::LargeBinary => {
                 let list_idx = column_index.next_column_index(field.id);
                 column_index.skip();
-                Ok(Box::new(BinaryFieldEncoder::new(
-                    cache_bytes_per_column,
-                    keep_original_array,
-                    list_idx,
-                )))
+                Ok(Box::new(BinaryFieldEncoder::new(options, list_idx)))
             }
             _ => todo!("Implement encoding for field {}", field),
         }
@@ -381,8 +397,7 @@ impl BatchEncoder {
     pub fn try_new(
         schema: &Schema,
         strategy: &dyn FieldEncodingStrategy,
-        cache_bytes_per_column: u64,
-        keep_original_array: bool,
+        options: &EncodingOptions,
     ) -> Result<Self> {
         let mut col_idx = 0;
         let mut col_idx_sequence = ColumnIndexSequence::default();
@@ -394,8 +409,7 @@ impl BatchEncoder {
                     strategy,
                     field,
                     &mut col_idx_sequence,
-                    cache_bytes_per_column,
-                    keep_original_array,
+                    options,
                     &field.metadata,
                 )?;
                 col_idx += encoder.as_ref().num_columns();
@@ -437,12 +451,12 @@ pub async fn encode_batch(
 ) -> Result<EncodedBatch> {
     let mut data_buffer = BytesMut::new();
     let lance_schema = Schema::try_from(batch.schema().as_ref())?;
-    let batch_encoder = BatchEncoder::try_new(
-        &lance_schema,
-        encoding_strategy,
+    let options = EncodingOptions {
         cache_bytes_per_column,
-        true,
-    )?;
+        keep_original_array: true,
+        ..Default::default()
+    };
+    let batch_encoder = BatchEncoder::try_new(&lance_schema, encoding_strategy, &options)?;
     let mut page_table = Vec::new();
     for (arr, mut encoder) in batch.columns().iter().zip(batch_encoder.field_encoders) {
         let mut tasks = encoder.maybe_encode(arr.clone())?;
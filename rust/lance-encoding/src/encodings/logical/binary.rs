@@ -20,7 +20,7 @@ use crate::{
         DecodeArrayTask, DecoderReady, FieldScheduler, LogicalPageDecoder, NextDecodeTask,
         ScheduledScanLine, SchedulerContext, SchedulingJob,
     },
-    encoder::{CoreArrayEncodingStrategy, EncodeTask, FieldEncoder},
+    encoder::{CoreArrayEncodingStrategy, EncodeTask, EncodingOptions, FieldEncoder},
 };
 
 use super::{list::ListFieldEncoder, primitive::PrimitiveFieldEncoder};
@@ -173,23 +173,17 @@ pub struct BinaryFieldEncoder {
 }
 
 impl BinaryFieldEncoder {
-    pub fn new(cache_bytes_per_column: u64, keep_original_array: bool, column_index: u32) -> Self {
+    pub fn new(options: &EncodingOptions, column_index: u32) -> Self {
         let items_encoder = Box::new(
             PrimitiveFieldEncoder::try_new(
-                cache_bytes_per_column,
-                keep_original_array,
+                options,
                 Arc::new(CoreArrayEncodingStrategy),
                 column_index + 1,
             )
             .unwrap(),
         );
         Self {
-            varbin_encoder: Box::new(ListFieldEncoder::new(
-                items_encoder,
-                cache_bytes_per_column,
-                keep_original_array,
-                column_index,
-            )),
+            varbin_encoder: Box::new(ListFieldEncoder::new(items_encoder, options, column_index)),
         }
     }
 
@@ -23,7 +23,7 @@ use crate::{
         DecodeArrayTask, DecodeBatchScheduler, FieldScheduler, LogicalPageDecoder, NextDecodeTask,
         ScheduledScanLine, SchedulerContext, SchedulingJob,
     },
-    encoder::{ArrayEncoder, EncodeTask, EncodedArray, EncodedPage, FieldEncoder},
+    encoder::{ArrayEncoder, EncodeTask, EncodedArray, EncodedPage, EncodingOptions, FieldEncoder},
     encodings::{
         logical::r#struct::SimpleStructScheduler,
         physical::{
@@ -804,13 +804,9 @@ struct ListOffsetsEncoder {
 }
 
 impl ListOffsetsEncoder {
-    fn new(cache_bytes: u64, keep_original_array: bool, column_index: u32) -> Self {
+    fn new(options: &EncodingOptions, column_index: u32) -> Self {
         Self {
-            accumulation_queue: AccumulationQueue::new(
-                cache_bytes,
-                column_index,
-                keep_original_array,
-            ),
+            accumulation_queue: AccumulationQueue::new(options, column_index),
             inner_encoder: Arc::new(BasicEncoder::new(Box::new(
                 ValueEncoder::try_new(&DataType::Int64, CompressionScheme::None).unwrap(),
             ))),
@@ -879,11 +875,14 @@ impl ListOffsetsEncoder {
         let offsets = Self::extract_offsets(list_arr);
         let validity = Self::extract_validity(list_arr);
         // Either inserting the offsets OR inserting the validity could cause the
-        // accumulation queue to fill up
-        if let Some(mut arrays) = self.accumulation_queue.insert(offsets) {
+        // accumulation queue to fill up.  The rows are counted with the offsets.
+        if let Some(mut arrays) = self
+            .accumulation_queue
+            .insert(offsets, list_arr.len() as u64)
+        {
             arrays.push(validity);
             Some(self.make_encode_task(arrays))
-        } else if let Some(arrays) = self.accumulation_queue.insert(validity) {
+        } else if let Some(arrays) = self.accumulation_queue.insert(validity, 0) {
             Some(self.make_encode_task(arrays))
         } else {
             None
@@ -1064,16 +1063,11 @@ pub struct ListFieldEncoder {
 impl ListFieldEncoder {
     pub fn new(
         items_encoder: Box<dyn FieldEncoder>,
-        cache_bytes_per_columns: u64,
-        keep_original_array: bool,
+        options: &EncodingOptions,
         column_index: u32,
     ) -> Self {
         Self {
-            offsets_encoder: ListOffsetsEncoder::new(
-                cache_bytes_per_columns,
-                keep_original_array,
-                column_index,
-            ),
+            offsets_encoder: ListOffsetsEncoder::new(options, column_index),
             items_encoder,
         }
     }
@@ -1087,10 +1081,8 @@ impl ListFieldEncoder {
         all_tasks.extend(item_tasks);
         Ok(all_tasks)
     }
-}
 
-impl FieldEncoder for ListFieldEncoder {
-    fn maybe_encode(&mut self, array: ArrayRef) -> Result<Vec<EncodeTask>> {
+    fn encode_lists(&mut self, array: ArrayRef) -> Result<Vec<EncodeTask>> {
         // The list may have an offset / shorter length which means the underlying
         // values array could be longer than what we need to encode and so we need
         // to slice down to the region of interest.
@@ -1131,6 +1123,23 @@ impl FieldEncoder for ListFieldEncoder {
         }
         Self::combine_tasks(offsets_tasks, item_tasks)
     }
+}
+
+impl FieldEncoder for ListFieldEncoder {
+    fn maybe_encode(&mut self, mut array: ArrayRef) -> Result<Vec<EncodeTask>> {
+        let mut tasks = Vec::new();
+        // Split the lists at the row limit so that no offsets page exceeds it
+        loop {
+            let remaining = self.offsets_encoder.accumulation_queue.remaining_rows();
+            if array.len() as u64 <= remaining {
+                tasks.extend(self.encode_lists(array)?);
+                return Ok(tasks);
+            }
+            let split = remaining as usize;
+            tasks.extend(self.encode_lists(array.slice(0, split))?);
+            array = array.slice(split, array.len() - split);
+        }
+    }
 
     fn flush(&mut self) -> Result<Vec<EncodeTask>> {
         let offsets_tasks = self
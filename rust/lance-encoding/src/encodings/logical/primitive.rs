@@ -31,7 +31,7 @@ use crate::{
         DecodeArrayTask, FieldScheduler, LogicalPageDecoder, NextDecodeTask, PageInfo,
         PageScheduler, PhysicalPageDecoder, ScheduledScanLine, SchedulerContext, SchedulingJob,
     },
-    encoder::{ArrayEncodingStrategy, EncodeTask, EncodedPage, FieldEncoder},
+    encoder::{ArrayEncodingStrategy, EncodeTask, EncodedPage, EncodingOptions, FieldEncoder},
     encodings::physical::{decoder_from_array_encoding, ColumnBuffers, PageBuffers},
 };
 
@@ -537,36 +537,49 @@ impl LogicalPageDecoder for PrimitiveFieldDecoder {
 #[derive(Debug)]
 pub struct AccumulationQueue {
     cache_bytes: u64,
+    max_rows: u64,
     keep_original_array: bool,
     buffered_arrays: Vec<ArrayRef>,
     current_bytes: u64,
+    current_rows: u64,
     // This is only for logging / debugging purposes
     column_index: u32,
 }
 
 impl AccumulationQueue {
-    pub fn new(cache_bytes: u64, column_index: u32, keep_original_array: bool) -> Self {
+    pub fn new(options: &EncodingOptions, column_index: u32) -> Self {
         Self {
-            cache_bytes,
+            cache_bytes: options.cache_bytes_per_column,
+            max_rows: options.max_page_rows,
             buffered_arrays: Vec::new(),
             current_bytes: 0,
+            current_rows: 0,
             column_index,
-            keep_original_array,
+            keep_original_array: options.keep_original_array,
         }
     }
 
-    /// Adds an array to the queue, if there is enough data then the queue is flushed
-    /// and returned
-    pub fn insert(&mut self, array: ArrayRef) -> Option<Vec<ArrayRef>> {
+    /// The number of rows that can be inserted before the queue is flushed
+    ///
+    /// Callers should slice larger arrays so that no page exceeds the row limit.
+    pub fn remaining_rows(&self) -> u64 {
+        self.max_rows - self.current_rows
+    }
+
+    /// Adds an array of `num_rows` rows to the queue, if there is enough data then
+    /// the queue is flushed and returned
+    pub fn insert(&mut self, array: ArrayRef, num_rows: u64) -> Option<Vec<ArrayRef>> {
         self.current_bytes += array.get_array_memory_size() as u64;
-        if self.current_bytes > self.cache_bytes {
+        self.current_rows += num_rows;
+        if self.current_bytes > self.cache_bytes || self.current_rows >= self.max_rows {
             debug!(
-                "Flushing column {} page of size {} bytes (unencoded)",
-                self.column_index, self.current_bytes
+                "Flushing column {} page of size {} bytes and {} rows (unencoded)",
+                self.column_index, self.current_bytes, self.current_rows
             );
             // Push into buffered_arrays without copy since we are about to flush anyways
             self.buffered_arrays.push(array);
             self.current_bytes = 0;
+            self.current_rows = 0;
             Some(std::mem::take(&mut self.buffered_arrays))
         } else {
             trace!(
@@ -597,6 +610,7 @@ impl AccumulationQueue {
                 self.current_bytes
             );
             self.current_bytes = 0;
+            self.current_rows = 0;
             Some(std::mem::take(&mut self.buffered_arrays))
         }
     }
@@ -610,17 +624,12 @@ pub struct PrimitiveFieldEncoder {
 
 impl PrimitiveFieldEncoder {
     pub fn try_new(
-        cache_bytes: u64,
-        keep_original_array: bool,
+        options: &EncodingOptions,
         array_encoding_strategy: Arc<dyn ArrayEncodingStrategy>,
         column_index: u32,
     ) -> Result<Self> {
         Ok(Self {
-            accumulation_queue: AccumulationQueue::new(
-                cache_bytes,
-                column_index,
-                keep_original_array,
-            ),
+            accumulation_queue: AccumulationQueue::new(options, column_index),
             column_index,
             array_encoding_strategy,
         })
@@ -648,12 +657,22 @@ impl PrimitiveFieldEncoder {
 
 impl FieldEncoder for PrimitiveFieldEncoder {
     // Buffers data, if there is enough to write a page then we create an encode task
-    fn maybe_encode(&mut self, array: ArrayRef) -> Result<Vec<EncodeTask>> {
-        if let Some(arrays) = self.accumulation_queue.insert(array) {
-            Ok(vec![self.do_flush(arrays)?])
-        } else {
-            Ok(vec![])
+    fn maybe_encode(&mut self, mut array: ArrayRef) -> Result<Vec<EncodeTask>> {
+        let mut tasks = Vec::new();
+        // Split the array at the row limit so that no page exceeds it
+        while array.len() as u64 > self.accumulation_queue.remaining_rows() {
+            let split = self.accumulation_queue.remaining_rows() as usize;
+            let head = array.slice(0, split);
+            array = array.slice(split, array.len() - split);
+            if let Some(arrays) = self.accumulation_queue.insert(head, split as u64) {
+                tasks.push(self.do_flush(arrays)?);
+            }
+        }
+        let num_rows = array.len() as u64;
+        if let Some(arrays) = self.accumulation_queue.insert(array, num_rows) {
+            tasks.push(self.do_flush(arrays)?);
         }
+        Ok(tasks)
     }
 
     // If there is any data left in the buffer then create an encode task from it
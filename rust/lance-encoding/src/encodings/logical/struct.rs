@@ -11,6 +11,7 @@ use arrow_array::{cast::AsArray, ArrayRef, StructArray};
 use arrow_schema::{DataType, Fields};
 use futures::{future::BoxFuture, FutureExt};
 use log::trace;
+use rayon::prelude::*;
 use snafu::{location, Location};
 
 use crate::{
@@ -231,20 +232,49 @@ struct ChildState {
     field_index: u32,
 }
 
+/// Fixed-size lists with at least this many bytes per row have their pages
+/// decoded in parallel
+const PARALLEL_DECODE_MIN_ROW_BYTES: usize = 512;
+
+/// Whether the pages of a field of `data_type` are worth decoding in parallel
+///
+/// This is the case for wide fixed-size lists of fixed-width values, such as
+/// vectors, whose pages hold few rows but take a while to decode.
+fn decode_pages_in_parallel(data_type: &DataType) -> bool {
+    match data_type {
+        DataType::FixedSizeList(items, dimension) => items
+            .data_type()
+            .primitive_width()
+            .map(|width| width * *dimension as usize >= PARALLEL_DECODE_MIN_ROW_BYTES)
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
 struct CompositeDecodeTask {
-    // One per child
+    // One per page
     tasks: Vec<Box<dyn DecodeArrayTask>>,
     num_rows: u32,
     has_more: bool,
+    // Whether the pages are decoded in parallel
+    parallel: bool,
 }
 
 impl CompositeDecodeTask {
     fn decode(self) -> Result<ArrayRef> {
-        let arrays = self
-            .tasks
-            .into_iter()
-            .map(|task| task.decode())
-            .collect::<Result<Vec<_>>>()?;
+        let arrays = if self.parallel && self.tasks.len() > 1 {
+            // The pages are decoded on the rayon thread pool, an indexed
+            // collect keeps them in order
+            self.tasks
+                .into_par_iter()
+                .map(|task| task.decode())
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            self.tasks
+                .into_iter()
+                .map(|task| task.decode())
+                .collect::<Result<Vec<_>>>()?
+        };
         let array_refs = arrays.iter().map(|arr| arr.as_ref()).collect::<Vec<_>>();
         // TODO: If this is a primitive column we should be able to avoid this
         // allocation + copy with "page bridging" which could save us a few CPU
@@ -313,7 +343,7 @@ impl ChildState {
         }
     }
 
-    fn drain(&mut self, num_rows: u64) -> Result<CompositeDecodeTask> {
+    fn drain(&mut self, num_rows: u64, parallel: bool) -> Result<CompositeDecodeTask> {
         trace!("Struct draining {} rows", num_rows);
         debug_assert!(self.rows_available >= num_rows);
 
@@ -323,6 +353,7 @@ impl ChildState {
             tasks: Vec::new(),
             num_rows: 0,
             has_more: true,
+            parallel,
         };
         while remaining > 0 {
             let next = self.scheduled.front_mut().unwrap();
@@ -393,7 +424,10 @@ impl SimpleStructDecoder {
         let child_tasks = self
             .children
             .iter_mut()
-            .map(|child| child.drain(num_rows))
+            .zip(self.child_fields.iter())
+            .map(|(child, field)| {
+                child.drain(num_rows, decode_pages_in_parallel(field.data_type()))
+            })
             .collect::<Result<Vec<_>>>()?;
         let num_rows = child_tasks[0].num_rows;
         let has_more = child_tasks[0].has_more;
@@ -441,7 +475,10 @@ impl LogicalPageDecoder for SimpleStructDecoder {
         let child_tasks = self
             .children
             .iter_mut()
-            .map(|child| child.drain(num_rows as u64))
+            .zip(self.child_fields.iter())
+            .map(|(child, field)| {
+                child.drain(num_rows as u64, decode_pages_in_parallel(field.data_type()))
+            })
             .collect::<Result<Vec<_>>>()?;
         let num_rows = child_tasks[0].num_rows;
         let has_more = child_tasks[0].has_more;
@@ -574,9 +611,12 @@ mod tests {
         Array, ArrayRef, Int32Array, StructArray,
     };
     use arrow_schema::{DataType, Field, Fields};
+    use lance_core::Result;
 
-    use crate::testing::{
-        check_round_trip_encoding_of_data, check_round_trip_encoding_random, TestCases,
+    use super::{decode_pages_in_parallel, CompositeDecodeTask};
+    use crate::{
+        decoder::DecodeArrayTask,
+        testing::{check_round_trip_encoding_of_data, check_round_trip_encoding_random, TestCases},
     };
 
     #[test_log::test(tokio::test)]
@@ -653,4 +693,54 @@ mod tests {
             .collect::<Vec<_>>();
         check_round_trip_encoding_of_data(struct_arrays, &TestCases::default()).await;
     }
+
+    struct RangeDecodeTask(std::ops::Range<i32>);
+
+    impl DecodeArrayTask for RangeDecodeTask {
+        fn decode(self: Box<Self>) -> Result<ArrayRef> {
+            Ok(Arc::new(Int32Array::from_iter_values(self.0)))
+        }
+    }
+
+    #[test]
+    fn test_parallel_page_decode() {
+        let vector = |dimension| {
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                dimension,
+            )
+        };
+        assert!(decode_pages_in_parallel(&vector(128)));
+        assert!(!decode_pages_in_parallel(&vector(8)));
+        assert!(!decode_pages_in_parallel(&DataType::Int32));
+
+        // Pages are reassembled in order, however long each takes to decode
+        let task = CompositeDecodeTask {
+            tasks: (0..100)
+                .map(|page| {
+                    Box::new(RangeDecodeTask(page * 10..(page + 1) * 10))
+                        as Box<dyn DecodeArrayTask>
+                })
+                .collect(),
+            num_rows: 1000,
+            has_more: false,
+            parallel: true,
+        };
+        let array = task.decode().unwrap();
+        assert_eq!(array.as_ref(), &Int32Array::from_iter_values(0..1000));
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_struct_wide_vectors() {
+        let data_type = DataType::Struct(Fields::from(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(
+                "vector",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 256),
+                true,
+            ),
+        ]));
+        let field = Field::new("row", data_type, false);
+        check_round_trip_encoding_random(field).await;
+    }
 }
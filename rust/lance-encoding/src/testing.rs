@@ -17,7 +17,7 @@ use lance_datagen::{array, gen, RowCount, Seed};
 use crate::{
     decoder::{BatchDecodeStream, ColumnInfo, DecodeBatchScheduler, DecoderMessage, PageInfo},
     encoder::{
-        ColumnIndexSequence, CoreFieldEncodingStrategy, EncodedPage, FieldEncoder,
+        ColumnIndexSequence, CoreFieldEncodingStrategy, EncodedPage, EncodingOptions, FieldEncoder,
         FieldEncodingStrategy,
     },
     encodings::logical::r#struct::SimpleStructDecoder,
@@ -97,11 +97,27 @@ async fn test_decode(
     }
 }
 
+/// Small and large pages, by size and by number of rows
+fn test_encoding_options() -> Vec<EncodingOptions> {
+    [
+        (4096, u64::MAX),
+        (1024 * 1024, u64::MAX),
+        (1024 * 1024, 100),
+    ]
+    .into_iter()
+    .map(|(cache_bytes_per_column, max_page_rows)| EncodingOptions {
+        cache_bytes_per_column,
+        max_page_rows,
+        keep_original_array: true,
+    })
+    .collect()
+}
+
 /// Given a field this will test the round trip encoding and decoding of random data
 pub async fn check_round_trip_encoding_random(field: Field) {
     let lance_field = lance_core::datatypes::Field::try_from(&field).unwrap();
-    for page_size in [4096, 1024 * 1024] {
-        debug!("Testing random data with a page size of {}", page_size);
+    for options in test_encoding_options() {
+        debug!("Testing random data with {:?}", options);
         let encoding_strategy = CoreFieldEncodingStrategy::default();
         let encoding_config = HashMap::new();
         let encoder_factory = || {
@@ -111,8 +127,7 @@ pub async fn check_round_trip_encoding_random(field: Field) {
                     &encoding_strategy,
                     &lance_field,
                     &mut column_index_seq,
-                    page_size,
-                    true,
+                    &options,
                     &encoding_config,
                 )
                 .unwrap()
@@ -179,7 +194,7 @@ pub async fn check_round_trip_encoding_of_data(data: Vec<Arc<dyn Array>>, test_c
     let example_data = data.first().expect("Data must have at least one array");
     let field = Field::new("", example_data.data_type().clone(), true);
     let lance_field = lance_core::datatypes::Field::try_from(&field).unwrap();
-    for page_size in [4096, 1024 * 1024] {
+    for options in test_encoding_options() {
         let encoding_strategy = CoreFieldEncodingStrategy::default();
         let encoding_config = HashMap::new();
         let mut column_index_seq = ColumnIndexSequence::default();
@@ -188,8 +203,7 @@ pub async fn check_round_trip_encoding_of_data(data: Vec<Arc<dyn Array>>, test_c
                 &encoding_strategy,
                 &lance_field,
                 &mut column_index_seq,
-                page_size,
-                true,
+                &options,
                 &encoding_config,
             )
             .unwrap();
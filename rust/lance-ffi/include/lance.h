@@ -0,0 +1,181 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+// C API for Lance datasets.
+//
+// Every function returns a LanceStatus. On failure, lance_last_error()
+// describes the error. Functions block the calling thread.
+//
+// Data is exchanged through the Arrow C data interface:
+// https://arrow.apache.org/docs/format/CDataInterface.html
+
+#ifndef LANCE_H
+#define LANCE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#ifndef ARROW_C_DATA_INTERFACE
+#define ARROW_C_DATA_INTERFACE
+
+#define ARROW_FLAG_DICTIONARY_ORDERED 1
+#define ARROW_FLAG_NULLABLE 2
+#define ARROW_FLAG_MAP_KEYS_SORTED 4
+
+struct ArrowSchema {
+  const char* format;
+  const char* name;
+  const char* metadata;
+  int64_t flags;
+  int64_t n_children;
+  struct ArrowSchema** children;
+  struct ArrowSchema* dictionary;
+  void (*release)(struct ArrowSchema*);
+  void* private_data;
+};
+
+struct ArrowArray {
+  int64_t length;
+  int64_t null_count;
+  int64_t offset;
+  int64_t n_buffers;
+  int64_t n_children;
+  const void** buffers;
+  struct ArrowArray** children;
+  struct ArrowArray* dictionary;
+  void (*release)(struct ArrowArray*);
+  void* private_data;
+};
+
+#endif  // ARROW_C_DATA_INTERFACE
+
+#ifndef ARROW_C_STREAM_INTERFACE
+#define ARROW_C_STREAM_INTERFACE
+
+struct ArrowArrayStream {
+  int (*get_schema)(struct ArrowArrayStream*, struct ArrowSchema* out);
+  int (*get_next)(struct ArrowArrayStream*, struct ArrowArray* out);
+  const char* (*get_last_error)(struct ArrowArrayStream*);
+  void (*release)(struct ArrowArrayStream*);
+  void* private_data;
+};
+
+#endif  // ARROW_C_STREAM_INTERFACE
+
+typedef enum LanceStatus {
+  LANCE_OK = 0,
+  // An argument was null, not valid UTF-8 or otherwise invalid.
+  LANCE_INVALID_INPUT = 1,
+  // The dataset or version does not exist.
+  LANCE_NOT_FOUND = 2,
+  // The dataset already exists.
+  LANCE_ALREADY_EXISTS = 3,
+  // A concurrent commit conflicts with this one.
+  LANCE_COMMIT_CONFLICT = 4,
+  // Reading from or writing to storage failed.
+  LANCE_IO = 5,
+  // Any other error.
+  LANCE_INTERNAL = 6,
+} LanceStatus;
+
+typedef enum LanceWriteMode {
+  // Create a new dataset, failing if it already exists.
+  LANCE_WRITE_CREATE = 0,
+  // Append to an existing dataset.
+  LANCE_WRITE_APPEND = 1,
+  // Overwrite the dataset as a new version, creating it if needed.
+  LANCE_WRITE_OVERWRITE = 2,
+} LanceWriteMode;
+
+// An opened version of a dataset. Free with lance_dataset_free().
+typedef struct LanceDataset LanceDataset;
+
+// The message of the last error on the calling thread, or NULL. Valid until
+// the next failing call on the same thread.
+const char* lance_last_error(void);
+
+// Open the latest version of the dataset at `uri`.
+LanceStatus lance_dataset_open(const char* uri, LanceDataset** out);
+
+// Open another version of an opened dataset.
+LanceStatus lance_dataset_checkout_version(const LanceDataset* dataset,
+                                           uint64_t version, LanceDataset** out);
+
+// Free a dataset handle. NULL is ignored.
+void lance_dataset_free(LanceDataset* dataset);
+
+// The version number of the dataset handle.
+LanceStatus lance_dataset_version(const LanceDataset* dataset, uint64_t* out);
+
+// Export the schema of the dataset. The caller must release `out`.
+LanceStatus lance_dataset_schema(const LanceDataset* dataset,
+                                 struct ArrowSchema* out);
+
+// Count the rows matching the SQL `filter`, or all rows if `filter` is NULL.
+LanceStatus lance_dataset_count_rows(const LanceDataset* dataset,
+                                     const char* filter, uint64_t* out);
+
+// Scan `num_columns` `columns` (all columns if NULL) of the rows matching the
+// SQL `filter` (all rows if NULL). The caller must release `out`.
+LanceStatus lance_dataset_scan(const LanceDataset* dataset,
+                               const char* const* columns, size_t num_columns,
+                               const char* filter,
+                               struct ArrowArrayStream* out);
+
+// Options of lance_dataset_scan_with_options(). Create with
+// lance_scan_options_default() and set the fields to change.
+typedef struct LanceScanOptions {
+  // `num_columns` column names to read, or NULL to read all columns.
+  const char* const* columns;
+  size_t num_columns;
+  // SQL expression the rows must match, or NULL to read all rows.
+  const char* filter;
+  // Maximum rows per batch, or 0 for the default.
+  size_t batch_size;
+  // Batches read ahead of the consumer in the background, or 0 for the
+  // default.
+  size_t read_ahead;
+  // Whether to add the row ids, in a `_rowid` column.
+  bool with_row_id;
+} LanceScanOptions;
+
+// All columns and rows, default batch size and read ahead, no row ids.
+LanceScanOptions lance_scan_options_default(void);
+
+// Scan the dataset with `options`. The caller must release `out`.
+//
+// Batches are exported without copying: each array shares the buffers read
+// from storage and keeps them alive until it is released. The stream keeps
+// its own reference to the dataset, which may be freed before the stream.
+LanceStatus lance_dataset_scan_with_options(const LanceDataset* dataset,
+                                            const LanceScanOptions* options,
+                                            struct ArrowArrayStream* out);
+
+// Write `stream` to the dataset at `uri`. The stream is consumed.
+LanceStatus lance_dataset_write(const char* uri, struct ArrowArrayStream* stream,
+                                LanceWriteMode mode, LanceDataset** out);
+
+// Write `stream` as uncommitted fragments of the dataset at `uri`. `out` is a
+// JSON array of fragments; free it with lance_string_free(). The stream is
+// consumed.
+LanceStatus lance_write_fragments(const char* uri,
+                                  struct ArrowArrayStream* stream, char** out);
+
+// Commit a JSON array of fragments from lance_write_fragments() as an append
+// on top of `read_version`.
+LanceStatus lance_commit_append(const char* uri, const char* fragments_json,
+                                uint64_t read_version, LanceDataset** out);
+
+// Free a string returned by this library. NULL is ignored.
+void lance_string_free(char* s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif  // LANCE_H
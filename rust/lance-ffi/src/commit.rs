@@ -0,0 +1,179 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Two-phase writes, for writers that produce data on several workers and
+//! commit it once.
+
+use std::ffi::{c_char, CString};
+
+use arrow::ffi_stream::FFI_ArrowArrayStream;
+use lance::dataset::transaction::Operation;
+use lance::dataset::{write_fragments, WriteMode, WriteParams};
+use lance::Dataset;
+use lance_table::format::Fragment;
+
+use crate::dataset::{import_stream, LanceDataset};
+use crate::error::{c_str, check_out, ffi_call, Error, LanceStatus};
+use crate::RT;
+
+/// Write the batches of `stream` as new data files of the dataset at `uri`
+/// without committing them.
+///
+/// On success `out` is set to a JSON array describing the written fragments,
+/// to be passed to [`lance_commit_append`] and freed with
+/// [`lance_string_free`]. The stream is consumed, even on failure.
+///
+/// # Safety
+///
+/// `uri` must be a NUL-terminated string, `stream` must point to a valid
+/// Arrow C stream and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lance_write_fragments(
+    uri: *const c_char,
+    stream: *mut FFI_ArrowArrayStream,
+    out: *mut *mut c_char,
+) -> LanceStatus {
+    ffi_call(|| {
+        let reader = import_stream(stream)?;
+        let uri = c_str(uri, "uri")?;
+        check_out(out, "out")?;
+        let params = WriteParams {
+            mode: WriteMode::Append,
+            ..Default::default()
+        };
+        let fragments = RT.block_on(write_fragments(uri, reader, params))?;
+        let json = serde_json::to_string(&fragments)?;
+        // JSON escapes control characters, so there are no interior NUL bytes.
+        *out = CString::new(json).unwrap().into_raw();
+        Ok(())
+    })
+}
+
+/// Commit fragments written by [`lance_write_fragments`] as an append to the
+/// dataset at `uri`, and return a handle to the new version.
+///
+/// `fragments_json` is a JSON array of fragments: the output of one call to
+/// [`lance_write_fragments`], or the outputs of several merged into one
+/// array. `read_version` is the version the writers
+/// started from. Appends only conflict with operations that rewrite the
+/// dataset, so the commit is retried on top of concurrent appends.
+///
+/// # Safety
+///
+/// `uri` and `fragments_json` must be NUL-terminated strings and `out` must
+/// be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lance_commit_append(
+    uri: *const c_char,
+    fragments_json: *const c_char,
+    read_version: u64,
+    out: *mut *mut LanceDataset,
+) -> LanceStatus {
+    ffi_call(|| {
+        let uri = c_str(uri, "uri")?;
+        let fragments_json = c_str(fragments_json, "fragments_json")?;
+        check_out(out, "out")?;
+        let fragments: Vec<Fragment> = serde_json::from_str(fragments_json)?;
+        if fragments.is_empty() {
+            return Err(Error::invalid_input("no fragments to commit"));
+        }
+        let operation = Operation::Append { fragments };
+        let dataset = RT.block_on(Dataset::commit(
+            uri,
+            operation,
+            Some(read_version),
+            None,
+            None,
+        ))?;
+        *out = Box::into_raw(Box::new(LanceDataset { inner: dataset }));
+        Ok(())
+    })
+}
+
+/// Free a string returned by this library. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn lance_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+
+    use super::*;
+    use crate::dataset::tests::test_stream;
+    use crate::dataset::{lance_dataset_count_rows, lance_dataset_free, lance_dataset_write};
+    use crate::{lance_dataset_version, LanceWriteMode};
+
+    #[test]
+    fn test_write_and_commit() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let uri = CString::new(test_dir.path().to_str().unwrap()).unwrap();
+
+        unsafe {
+            let mut stream = test_stream(0);
+            let mut dataset = std::ptr::null_mut();
+            let status = lance_dataset_write(
+                uri.as_ptr(),
+                &mut stream,
+                LanceWriteMode::Create,
+                &mut dataset,
+            );
+            assert_eq!(status, LanceStatus::Ok);
+            lance_dataset_free(dataset);
+
+            // Two workers write fragments independently.
+            let mut fragments = Vec::new();
+            for start in [10, 20] {
+                let mut stream = test_stream(start);
+                let mut json = std::ptr::null_mut();
+                assert_eq!(
+                    lance_write_fragments(uri.as_ptr(), &mut stream, &mut json),
+                    LanceStatus::Ok
+                );
+                let written: Vec<Fragment> =
+                    serde_json::from_str(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+                fragments.extend(written);
+                lance_string_free(json);
+            }
+
+            // Nothing is visible until the commit.
+            let mut dataset = std::ptr::null_mut();
+            assert_eq!(
+                crate::lance_dataset_open(uri.as_ptr(), &mut dataset),
+                LanceStatus::Ok
+            );
+            let mut version = 0;
+            lance_dataset_version(dataset, &mut version);
+            assert_eq!(version, 1);
+            lance_dataset_free(dataset);
+
+            let json = CString::new(serde_json::to_string(&fragments).unwrap()).unwrap();
+            let mut dataset = std::ptr::null_mut();
+            assert_eq!(
+                lance_commit_append(uri.as_ptr(), json.as_ptr(), 1, &mut dataset),
+                LanceStatus::Ok
+            );
+            let mut count = 0;
+            lance_dataset_count_rows(dataset, std::ptr::null(), &mut count);
+            assert_eq!(count, 30);
+            lance_dataset_version(dataset, &mut version);
+            assert_eq!(version, 2);
+            lance_dataset_free(dataset);
+
+            let empty = CString::new("[]").unwrap();
+            let mut dataset = std::ptr::null_mut();
+            assert_eq!(
+                lance_commit_append(uri.as_ptr(), empty.as_ptr(), 2, &mut dataset),
+                LanceStatus::InvalidInput
+            );
+        }
+    }
+}
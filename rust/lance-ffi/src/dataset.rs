@@ -0,0 +1,543 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use std::ffi::c_char;
+
+use arrow::ffi::FFI_ArrowSchema;
+use arrow::ffi_stream::{ArrowArrayStreamReader, FFI_ArrowArrayStream};
+use arrow_schema::Schema as ArrowSchema;
+use lance::dataset::{WriteMode, WriteParams};
+use lance::Dataset;
+use lance_io::ffi::{BlockingRecordBatchReader, DEFAULT_READ_AHEAD};
+
+use crate::error::{c_str, check_out, ffi_call, opt_c_str, Error, LanceStatus, Result};
+use crate::RT;
+
+/// An opened version of a dataset.
+///
+/// Handles are immutable: writes return a new handle for the new version.
+/// Free with [`lance_dataset_free`].
+pub struct LanceDataset {
+    pub inner: Dataset,
+}
+
+/// How [`lance_dataset_write`] treats an existing dataset.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanceWriteMode {
+    /// Create a new dataset, failing if it already exists.
+    Create = 0,
+    /// Append to an existing dataset.
+    Append = 1,
+    /// Overwrite the dataset as a new version, creating it if needed.
+    Overwrite = 2,
+}
+
+impl From<LanceWriteMode> for WriteMode {
+    fn from(mode: LanceWriteMode) -> Self {
+        match mode {
+            LanceWriteMode::Create => Self::Create,
+            LanceWriteMode::Append => Self::Append,
+            LanceWriteMode::Overwrite => Self::Overwrite,
+        }
+    }
+}
+
+fn dataset_ref<'a>(dataset: *const LanceDataset) -> Result<&'a Dataset> {
+    if dataset.is_null() {
+        return Err(Error::invalid_input("dataset must not be null"));
+    }
+    // Safety: non-null handles are created by this library and only freed
+    // by `lance_dataset_free`.
+    Ok(unsafe { &(*dataset).inner })
+}
+
+fn into_handle(dataset: Dataset) -> *mut LanceDataset {
+    Box::into_raw(Box::new(LanceDataset { inner: dataset }))
+}
+
+/// Open the latest version of the dataset at `uri`.
+///
+/// # Safety
+///
+/// `uri` must be a NUL-terminated string and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lance_dataset_open(
+    uri: *const c_char,
+    out: *mut *mut LanceDataset,
+) -> LanceStatus {
+    ffi_call(|| {
+        let uri = c_str(uri, "uri")?;
+        check_out(out, "out")?;
+        let dataset = RT.block_on(Dataset::open(uri))?;
+        *out = into_handle(dataset);
+        Ok(())
+    })
+}
+
+/// Open another version of an opened dataset.
+///
+/// # Safety
+///
+/// `dataset` must be a handle returned by this library and `out` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lance_dataset_checkout_version(
+    dataset: *const LanceDataset,
+    version: u64,
+    out: *mut *mut LanceDataset,
+) -> LanceStatus {
+    ffi_call(|| {
+        let dataset = dataset_ref(dataset)?;
+        check_out(out, "out")?;
+        let checked_out = RT.block_on(dataset.checkout_version(version))?;
+        *out = into_handle(checked_out);
+        Ok(())
+    })
+}
+
+/// Free a dataset handle. Null is ignored.
+///
+/// # Safety
+///
+/// `dataset` must be null or a handle returned by this library that has not
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn lance_dataset_free(dataset: *mut LanceDataset) {
+    if !dataset.is_null() {
+        drop(Box::from_raw(dataset));
+    }
+}
+
+/// The version number of the dataset handle.
+///
+/// # Safety
+///
+/// `dataset` must be a handle returned by this library and `out` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lance_dataset_version(
+    dataset: *const LanceDataset,
+    out: *mut u64,
+) -> LanceStatus {
+    ffi_call(|| {
+        let dataset = dataset_ref(dataset)?;
+        check_out(out, "out")?;
+        *out = dataset.version().version;
+        Ok(())
+    })
+}
+
+/// Export the schema of the dataset.
+///
+/// The caller owns the schema and must release it.
+///
+/// # Safety
+///
+/// `dataset` must be a handle returned by this library and `out` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lance_dataset_schema(
+    dataset: *const LanceDataset,
+    out: *mut FFI_ArrowSchema,
+) -> LanceStatus {
+    ffi_call(|| {
+        let dataset = dataset_ref(dataset)?;
+        check_out(out, "out")?;
+        let schema = ArrowSchema::from(dataset.schema());
+        std::ptr::write_unaligned(out, FFI_ArrowSchema::try_from(&schema)?);
+        Ok(())
+    })
+}
+
+/// Count the rows of the dataset matching `filter`, a SQL expression, or all
+/// rows if `filter` is null.
+///
+/// # Safety
+///
+/// `dataset` must be a handle returned by this library, `filter` must be
+/// null or a NUL-terminated string and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lance_dataset_count_rows(
+    dataset: *const LanceDataset,
+    filter: *const c_char,
+    out: *mut u64,
+) -> LanceStatus {
+    ffi_call(|| {
+        let dataset = dataset_ref(dataset)?;
+        let filter = opt_c_str(filter, "filter")?;
+        check_out(out, "out")?;
+        *out = RT.block_on(dataset.count_rows(filter.map(String::from)))? as u64;
+        Ok(())
+    })
+}
+
+/// Options of [`lance_dataset_scan_with_options`].
+///
+/// Create with [`lance_scan_options_default`] and set the fields to change.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LanceScanOptions {
+    /// Array of `num_columns` column names to read, or null to read all
+    /// columns.
+    pub columns: *const *const c_char,
+    pub num_columns: usize,
+    /// SQL expression the rows must match, or null to read all rows.
+    pub filter: *const c_char,
+    /// Maximum rows per batch, or 0 for the default.
+    pub batch_size: usize,
+    /// Batches read ahead of the consumer in the background, or 0 for the
+    /// default.
+    pub read_ahead: usize,
+    /// Whether to add the row ids, in a `_rowid` column.
+    pub with_row_id: bool,
+}
+
+/// The default scan options: all columns and rows, default batch size and
+/// read ahead, without row ids.
+#[no_mangle]
+pub extern "C" fn lance_scan_options_default() -> LanceScanOptions {
+    LanceScanOptions {
+        columns: std::ptr::null(),
+        num_columns: 0,
+        filter: std::ptr::null(),
+        batch_size: 0,
+        read_ahead: 0,
+        with_row_id: false,
+    }
+}
+
+/// Scan the dataset into an Arrow C stream.
+///
+/// `columns` is an array of `num_columns` column names to read, or null to
+/// read all columns. `filter` is a SQL expression, or null to read all rows.
+/// The stream reads ahead a few batches in the background and must be
+/// released by the caller.
+///
+/// # Safety
+///
+/// `dataset` must be a handle returned by this library, `columns` must be
+/// null or point to `num_columns` NUL-terminated strings, `filter` must be
+/// null or a NUL-terminated string and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lance_dataset_scan(
+    dataset: *const LanceDataset,
+    columns: *const *const c_char,
+    num_columns: usize,
+    filter: *const c_char,
+    out: *mut FFI_ArrowArrayStream,
+) -> LanceStatus {
+    let options = LanceScanOptions {
+        columns,
+        num_columns,
+        filter,
+        ..lance_scan_options_default()
+    };
+    lance_dataset_scan_with_options(dataset, &options, out)
+}
+
+/// Scan the dataset into an Arrow C stream, with `options`.
+///
+/// Batches are exported without copying: each exported array shares the
+/// buffers read from storage and keeps them alive until the consumer
+/// releases it. The stream keeps its own reference to the dataset, so the
+/// handle may be freed before the stream is released.
+///
+/// # Safety
+///
+/// `dataset` must be a handle returned by this library, `options` must
+/// point to valid options whose `columns` and `filter` are null or valid
+/// as described in [`LanceScanOptions`], and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lance_dataset_scan_with_options(
+    dataset: *const LanceDataset,
+    options: *const LanceScanOptions,
+    out: *mut FFI_ArrowArrayStream,
+) -> LanceStatus {
+    ffi_call(|| {
+        let dataset = dataset_ref(dataset)?;
+        if options.is_null() {
+            return Err(Error::invalid_input("options must not be null"));
+        }
+        let options = &*options;
+        check_out(out, "out")?;
+        let mut scanner = dataset.scan();
+        if !options.columns.is_null() {
+            let columns = std::slice::from_raw_parts(options.columns, options.num_columns)
+                .iter()
+                .map(|column| c_str(*column, "column"))
+                .collect::<Result<Vec<_>>>()?;
+            scanner.project(&columns)?;
+        }
+        if let Some(filter) = opt_c_str(options.filter, "filter")? {
+            scanner.filter(filter)?;
+        }
+        if options.batch_size > 0 {
+            scanner.batch_size(options.batch_size);
+        }
+        if options.with_row_id {
+            scanner.with_row_id();
+        }
+        let read_ahead = match options.read_ahead {
+            0 => DEFAULT_READ_AHEAD,
+            read_ahead => read_ahead,
+        };
+        let stream = RT.block_on(scanner.try_into_stream())?;
+        let reader =
+            BlockingRecordBatchReader::with_runtime(stream, RT.handle().clone(), read_ahead);
+        std::ptr::write_unaligned(out, FFI_ArrowArrayStream::new(Box::new(reader)));
+        Ok(())
+    })
+}
+
+/// Write the batches of `stream` to the dataset at `uri` and return a handle
+/// to the written version.
+///
+/// The stream is consumed, even on failure.
+///
+/// # Safety
+///
+/// `uri` must be a NUL-terminated string, `stream` must point to a valid
+/// Arrow C stream and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn lance_dataset_write(
+    uri: *const c_char,
+    stream: *mut FFI_ArrowArrayStream,
+    mode: LanceWriteMode,
+    out: *mut *mut LanceDataset,
+) -> LanceStatus {
+    ffi_call(|| {
+        let reader = import_stream(stream)?;
+        let uri = c_str(uri, "uri")?;
+        check_out(out, "out")?;
+        let params = WriteParams {
+            mode: mode.into(),
+            ..Default::default()
+        };
+        let dataset = RT.block_on(Dataset::write(reader, uri, Some(params)))?;
+        *out = into_handle(dataset);
+        Ok(())
+    })
+}
+
+/// Take ownership of an Arrow C stream.
+///
+/// # Safety
+///
+/// `stream` must be null or point to a valid Arrow C stream.
+pub unsafe fn import_stream(stream: *mut FFI_ArrowArrayStream) -> Result<ArrowArrayStreamReader> {
+    if stream.is_null() {
+        return Err(Error::invalid_input("stream must not be null"));
+    }
+    Ok(ArrowArrayStreamReader::from_raw(stream)?)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::ffi::{CStr, CString};
+    use std::sync::Arc;
+
+    use arrow::record_batch::RecordBatchReader;
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator, StringArray};
+    use arrow_schema::{DataType, Field};
+
+    use super::*;
+
+    pub fn test_stream(start: i32) -> FFI_ArrowArrayStream {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new("s", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(start..start + 10)),
+                Arc::new(StringArray::from_iter_values(
+                    (start..start + 10).map(|i| format!("s-{}", i)),
+                )),
+            ],
+        )
+        .unwrap();
+        FFI_ArrowArrayStream::new(Box::new(RecordBatchIterator::new(vec![Ok(batch)], schema)))
+    }
+
+    #[test]
+    fn test_write_open_scan() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let uri = CString::new(test_dir.path().to_str().unwrap()).unwrap();
+
+        unsafe {
+            let mut stream = test_stream(0);
+            let mut written = std::ptr::null_mut();
+            let status = lance_dataset_write(
+                uri.as_ptr(),
+                &mut stream,
+                LanceWriteMode::Create,
+                &mut written,
+            );
+            assert_eq!(status, LanceStatus::Ok);
+            lance_dataset_free(written);
+
+            let mut stream = test_stream(10);
+            let mut written = std::ptr::null_mut();
+            let status = lance_dataset_write(
+                uri.as_ptr(),
+                &mut stream,
+                LanceWriteMode::Append,
+                &mut written,
+            );
+            assert_eq!(status, LanceStatus::Ok);
+            lance_dataset_free(written);
+
+            let mut dataset = std::ptr::null_mut();
+            assert_eq!(
+                lance_dataset_open(uri.as_ptr(), &mut dataset),
+                LanceStatus::Ok
+            );
+
+            let mut version = 0;
+            assert_eq!(
+                lance_dataset_version(dataset, &mut version),
+                LanceStatus::Ok
+            );
+            assert_eq!(version, 2);
+
+            let filter = CString::new("i >= 5").unwrap();
+            let mut count = 0;
+            assert_eq!(
+                lance_dataset_count_rows(dataset, filter.as_ptr(), &mut count),
+                LanceStatus::Ok
+            );
+            assert_eq!(count, 15);
+
+            let mut schema = FFI_ArrowSchema::empty();
+            assert_eq!(lance_dataset_schema(dataset, &mut schema), LanceStatus::Ok);
+            let schema = ArrowSchema::try_from(&schema).unwrap();
+            assert_eq!(schema.fields().len(), 2);
+
+            let columns = [CString::new("i").unwrap()];
+            let column_ptrs = columns.iter().map(|c| c.as_ptr()).collect::<Vec<_>>();
+            let mut stream = FFI_ArrowArrayStream::empty();
+            let status = lance_dataset_scan(
+                dataset,
+                column_ptrs.as_ptr(),
+                column_ptrs.len(),
+                filter.as_ptr(),
+                &mut stream,
+            );
+            assert_eq!(status, LanceStatus::Ok);
+            let reader = ArrowArrayStreamReader::try_new(stream).unwrap();
+            assert_eq!(reader.schema().fields().len(), 1);
+            let num_rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+            assert_eq!(num_rows, 15);
+
+            let mut first = std::ptr::null_mut();
+            assert_eq!(
+                lance_dataset_checkout_version(dataset, 1, &mut first),
+                LanceStatus::Ok
+            );
+            let mut count = 0;
+            assert_eq!(
+                lance_dataset_count_rows(first, std::ptr::null(), &mut count),
+                LanceStatus::Ok
+            );
+            assert_eq!(count, 10);
+
+            lance_dataset_free(first);
+            lance_dataset_free(dataset);
+        }
+    }
+
+    #[test]
+    fn test_scan_with_options() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let uri = CString::new(test_dir.path().to_str().unwrap()).unwrap();
+
+        unsafe {
+            let mut stream = test_stream(0);
+            let mut dataset = std::ptr::null_mut();
+            let status = lance_dataset_write(
+                uri.as_ptr(),
+                &mut stream,
+                LanceWriteMode::Create,
+                &mut dataset,
+            );
+            assert_eq!(status, LanceStatus::Ok);
+
+            let columns = [CString::new("s").unwrap()];
+            let column_ptrs = columns.iter().map(|c| c.as_ptr()).collect::<Vec<_>>();
+            let options = LanceScanOptions {
+                columns: column_ptrs.as_ptr(),
+                num_columns: column_ptrs.len(),
+                batch_size: 4,
+                read_ahead: 1,
+                with_row_id: true,
+                ..lance_scan_options_default()
+            };
+            let mut stream = FFI_ArrowArrayStream::empty();
+            assert_eq!(
+                lance_dataset_scan_with_options(dataset, std::ptr::null(), &mut stream),
+                LanceStatus::InvalidInput
+            );
+            let status = lance_dataset_scan_with_options(dataset, &options, &mut stream);
+            assert_eq!(status, LanceStatus::Ok);
+            // The stream outlives the dataset handle
+            lance_dataset_free(dataset);
+
+            let reader = ArrowArrayStreamReader::try_new(stream).unwrap();
+            let schema = reader.schema();
+            let names = schema
+                .fields()
+                .iter()
+                .map(|field| field.name().as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(names, vec!["s", "_rowid"]);
+            let batches = reader.map(|batch| batch.unwrap()).collect::<Vec<_>>();
+            assert!(batches.iter().all(|batch| batch.num_rows() <= 4));
+            let num_rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+            assert_eq!(num_rows, 10);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let uri = CString::new(test_dir.path().to_str().unwrap()).unwrap();
+
+        unsafe {
+            let mut dataset = std::ptr::null_mut();
+            assert_eq!(
+                lance_dataset_open(uri.as_ptr(), &mut dataset),
+                LanceStatus::NotFound
+            );
+            assert!(!crate::lance_last_error().is_null());
+
+            assert_eq!(
+                lance_dataset_open(std::ptr::null(), &mut dataset),
+                LanceStatus::InvalidInput
+            );
+            let message = CStr::from_ptr(crate::lance_last_error());
+            assert_eq!(message.to_str().unwrap(), "uri must not be null");
+
+            let mut stream = test_stream(0);
+            let mut written = std::ptr::null_mut();
+            lance_dataset_write(
+                uri.as_ptr(),
+                &mut stream,
+                LanceWriteMode::Create,
+                &mut written,
+            );
+            lance_dataset_free(written);
+            let mut stream = test_stream(0);
+            assert_eq!(
+                lance_dataset_write(
+                    uri.as_ptr(),
+                    &mut stream,
+                    LanceWriteMode::Create,
+                    &mut written
+                ),
+                LanceStatus::AlreadyExists
+            );
+        }
+    }
+}
@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use arrow_schema::ArrowError;
+use lance::Error as LanceError;
+
+/// Result of a call through the C API.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanceStatus {
+    Ok = 0,
+    /// An argument was null, not valid UTF-8 or otherwise invalid.
+    InvalidInput = 1,
+    /// The dataset or version does not exist.
+    NotFound = 2,
+    /// The dataset already exists.
+    AlreadyExists = 3,
+    /// A concurrent commit conflicts with this one.
+    CommitConflict = 4,
+    /// Reading from or writing to storage failed.
+    Io = 5,
+    /// Any other error, including panics.
+    Internal = 6,
+}
+
+/// An error to report through the C API.
+#[derive(Debug)]
+pub struct Error {
+    status: LanceStatus,
+    message: String,
+}
+
+impl Error {
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self {
+            status: LanceStatus::InvalidInput,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<LanceError> for Error {
+    fn from(err: LanceError) -> Self {
+        let status = match &err {
+            LanceError::InvalidInput { .. }
+            | LanceError::SchemaMismatch { .. }
+            | LanceError::Schema { .. } => LanceStatus::InvalidInput,
+            LanceError::DatasetNotFound { .. } | LanceError::NotFound { .. } => {
+                LanceStatus::NotFound
+            }
+            LanceError::DatasetAlreadyExists { .. } => LanceStatus::AlreadyExists,
+            LanceError::CommitConflict { .. } => LanceStatus::CommitConflict,
+            LanceError::IO { .. } => LanceStatus::Io,
+            _ => LanceStatus::Internal,
+        };
+        Self {
+            status,
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<ArrowError> for Error {
+    fn from(err: ArrowError) -> Self {
+        Self {
+            status: LanceStatus::Internal,
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::invalid_input(err.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: &str) {
+    // Interior NUL bytes cannot be represented in a C string.
+    let message = CString::new(message.replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Run the body of a C API function, converting errors and panics to a
+/// [`LanceStatus`] and recording the message for [`lance_last_error`].
+///
+/// Unwinding across the C ABI is undefined behavior, so panics are caught
+/// here. Datasets are immutable, so a handle remains usable after a panic.
+pub fn ffi_call(f: impl FnOnce() -> Result<()>) -> LanceStatus {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => LanceStatus::Ok,
+        Ok(Err(err)) => {
+            set_last_error(&err.message);
+            err.status
+        }
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_last_error(&format!("Lance panicked: {}", message));
+            LanceStatus::Internal
+        }
+    }
+}
+
+/// Read a required C string argument.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL-terminated string that outlives the
+/// returned reference.
+pub unsafe fn c_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(Error::invalid_input(format!("{} must not be null", name)));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| Error::invalid_input(format!("{} is not valid UTF-8", name)))
+}
+
+/// Read an optional C string argument, where null means absent.
+///
+/// # Safety
+///
+/// Same as [`c_str`].
+pub unsafe fn opt_c_str<'a>(ptr: *const c_char, name: &str) -> Result<Option<&'a str>> {
+    if ptr.is_null() {
+        Ok(None)
+    } else {
+        c_str(ptr, name).map(Some)
+    }
+}
+
+/// Check that an output pointer is not null.
+pub fn check_out<T>(ptr: *mut T, name: &str) -> Result<()> {
+    if ptr.is_null() {
+        Err(Error::invalid_input(format!("{} must not be null", name)))
+    } else {
+        Ok(())
+    }
+}
+
+/// The message of the last error on the calling thread, or null if no call
+/// on this thread has failed.
+///
+/// The string is owned by the library and stays valid until the next failing
+/// call on the same thread.
+#[no_mangle]
+pub extern "C" fn lance_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi_call_errors() {
+        let status = ffi_call(|| Err(Error::invalid_input("bad argument")));
+        assert_eq!(status, LanceStatus::InvalidInput);
+        let message = unsafe { CStr::from_ptr(lance_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "bad argument");
+
+        let status = ffi_call(|| panic!("boom"));
+        assert_eq!(status, LanceStatus::Internal);
+        let message = unsafe { CStr::from_ptr(lance_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "Lance panicked: boom");
+    }
+}
@@ -0,0 +1,41 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! C bindings for Lance.
+//!
+//! This crate exposes the core dataset operations through a C ABI so that
+//! languages other than Python and Java can embed Lance. The declarations are
+//! in `include/lance.h`.
+//!
+//! Data is exchanged through the [Arrow C data interface]: schemas are
+//! exported as `ArrowSchema`, scans return an `ArrowArrayStream`, and writes
+//! consume an `ArrowArrayStream`.
+//!
+//! Every function returns a [`LanceStatus`]. On failure, [`lance_last_error`]
+//! returns a description of the error for the calling thread. Functions
+//! block the calling thread until they complete and must not be called from
+//! a thread driving an async runtime.
+//!
+//! [Arrow C data interface]: https://arrow.apache.org/docs/format/CDataInterface.html
+
+mod commit;
+mod dataset;
+mod error;
+
+pub use commit::{lance_commit_append, lance_string_free, lance_write_fragments};
+pub use dataset::{
+    lance_dataset_checkout_version, lance_dataset_count_rows, lance_dataset_free,
+    lance_dataset_open, lance_dataset_scan, lance_dataset_scan_with_options, lance_dataset_schema,
+    lance_dataset_version, lance_dataset_write, lance_scan_options_default, LanceDataset,
+    LanceScanOptions, LanceWriteMode,
+};
+pub use error::{lance_last_error, LanceStatus};
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref RT: tokio::runtime::Runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to create tokio runtime");
+}
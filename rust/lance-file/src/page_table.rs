@@ -167,6 +167,16 @@ impl PageTable {
             .get(&field_id)
             .and_then(|c_map| c_map.get(&batch))
     }
+
+    /// Iterate over all pages as `(field_id, batch_id, page_info)`, ordered by
+    /// field id and then batch id.
+    pub fn iter(&self) -> impl Iterator<Item = (i32, i32, &PageInfo)> {
+        self.pages.iter().flat_map(|(field_id, batches)| {
+            batches
+                .iter()
+                .map(move |(batch_id, page_info)| (*field_id, *batch_id, page_info))
+        })
+    }
 }
 
 #[cfg(test)]
@@ -4,6 +4,7 @@
 //! Lance Data File Reader
 
 // Standard
+use std::collections::BTreeMap;
 use std::ops::{Range, RangeTo};
 use std::sync::Arc;
 
@@ -27,6 +28,7 @@ use lance_arrow::*;
 use lance_core::cache::FileMetadataCache;
 use lance_core::datatypes::{Field, Schema};
 use lance_core::utils::deletion::DeletionVector;
+use lance_core::utils::tokio::DecodeLimit;
 use lance_core::{Error, Result, ROW_ID, ROW_ID_FIELD};
 use lance_io::encodings::dictionary::DictionaryDecoder;
 use lance_io::encodings::AsyncIndex;
@@ -74,6 +76,9 @@ pub struct FileReader {
 
     /// Page table for statistics
     stats_page_table: Arc<Option<PageTable>>,
+
+    /// Bounds how many batches of this file are read at the same time
+    decode_limit: DecodeLimit,
 }
 
 impl std::fmt::Debug for FileReader {
@@ -171,6 +176,7 @@ impl FileReader {
             with_row_id: false,
             make_deletions_null: false,
             stats_page_table,
+            decode_limit: DecodeLimit::default(),
         })
     }
 
@@ -275,6 +281,19 @@ impl FileReader {
         self
     }
 
+    /// Bound how many batches of this file are read at the same time.
+    ///
+    /// This file format decodes while it reads, so the limit bounds its
+    /// I/O as well.
+    pub fn with_decode_limit(&mut self, decode_limit: DecodeLimit) -> &mut Self {
+        self.decode_limit = decode_limit;
+        self
+    }
+
+    pub fn decode_limit(&self) -> &DecodeLimit {
+        &self.decode_limit
+    }
+
     /// Requested projection of the data in this file, excluding the row id column.
     pub fn schema(&self) -> &Schema {
         &self.schema
@@ -446,6 +465,57 @@ impl FileReader {
             Ok(None)
         }
     }
+
+    /// Get the number of bytes each field occupies on disk, keyed by field id.
+    ///
+    /// Only fields that own data pages are reported (leaf fields, list offsets and
+    /// dictionary keys). Pages are written back to back, so each page is charged
+    /// with the bytes between the end of the previous page and its own end. This
+    /// also charges the values of binary pages, which precede their offsets.
+    pub fn field_sizes_on_disk(&self) -> BTreeMap<i32, u64> {
+        let mut page_ends = self
+            .page_table
+            .iter()
+            .filter_map(|(field_id, _, page_info)| {
+                let field = self.schema.field_by_id(field_id)?;
+                let page_len = page_size_on_disk(&field.data_type(), page_info.length)?;
+                Some((page_info.position + page_len, field_id))
+            })
+            .collect::<Vec<_>>();
+        page_ends.sort_unstable();
+
+        let mut sizes = BTreeMap::new();
+        let mut last_end = 0;
+        for (end, field_id) in page_ends {
+            *sizes.entry(field_id).or_default() += end.saturating_sub(last_end) as u64;
+            last_end = last_end.max(end);
+        }
+        sizes
+    }
+}
+
+/// Number of bytes, from the page position onwards, of a page with `length` items.
+///
+/// Returns `None` for pages that do not hold any data.
+fn page_size_on_disk(data_type: &DataType, length: usize) -> Option<usize> {
+    if length == 0 {
+        return None;
+    }
+    match data_type {
+        DataType::Null | DataType::Struct(_) => None,
+        DataType::Boolean => Some(length.div_ceil(8)),
+        DataType::FixedSizeList(child, dim) if child.data_type() == &DataType::Boolean => {
+            Some((length * *dim as usize).div_ceil(8))
+        }
+        // Values are written before the page position, followed by i64 offsets.
+        dt if dt.is_binary_like() => Some((length + 1) * std::mem::size_of::<i64>()),
+        // The page length of a list already accounts for the extra offset.
+        DataType::List(_) => Some(length * std::mem::size_of::<i32>()),
+        DataType::LargeList(_) => Some(length * std::mem::size_of::<i64>()),
+        DataType::Dictionary(key_type, _) => Some(length * key_type.byte_width()),
+        dt if dt.is_fixed_stride() => Some(length * dt.byte_width()),
+        _ => None,
+    }
 }
 
 /// Stream desired full batches from the file.
@@ -1712,6 +1782,43 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_field_sizes_on_disk() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int64, false),
+            ArrowField::new("s", DataType::Utf8, false),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let store = ObjectStore::memory();
+        let path = Path::from("/field_sizes");
+        let mut file_writer = FileWriter::<NotSelfDescribing>::try_new(
+            &store,
+            &path,
+            schema.clone(),
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+        for _ in 0..2 {
+            let columns: Vec<ArrayRef> = vec![
+                Arc::new(Int64Array::from_iter_values(0..100)),
+                Arc::new(StringArray::from_iter_values((0..100).map(|_| "abcd"))),
+            ];
+            let batch = RecordBatch::try_new(Arc::new(arrow_schema.clone()), columns).unwrap();
+            file_writer.write(&[batch]).await.unwrap();
+        }
+        file_writer.finish().await.unwrap();
+
+        let reader = FileReader::try_new(&store, &path, schema).await.unwrap();
+        let sizes = reader.field_sizes_on_disk();
+        assert_eq!(sizes.len(), 2);
+        // 100 i64 values per batch
+        assert_eq!(sizes[&0], 2 * 100 * 8);
+        // 400 bytes of string data and 101 i64 offsets per batch
+        assert_eq!(sizes[&1], 2 * (400 + 101 * 8));
+    }
+
     #[tokio::test]
     async fn test_batches_stream() {
         let store = ObjectStore::memory();
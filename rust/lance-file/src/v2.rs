@@ -2,5 +2,6 @@
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
 pub(crate) mod io;
+pub mod layout;
 pub mod reader;
 pub mod writer;
@@ -0,0 +1,376 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Introspection of the physical page layout of a Lance v2 file
+//!
+//! This is meant for debugging why files are large or why scans are slow.  The
+//! layout is returned as a [`RecordBatch`] so it can be filtered, sorted, and
+//! aggregated with the usual Arrow / DataFusion tooling.
+//!
+//! Lance v2 files do not store statistics of their pages, such as min / max
+//! values or null counts.  The layout only reports whether a page has nulls,
+//! which is known from its encoding.
+
+use std::sync::{Arc, OnceLock};
+
+use arrow_array::builder::{Float64Builder, StringBuilder, UInt32Builder, UInt64Builder};
+use arrow_array::RecordBatch;
+use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema, SchemaRef};
+use bytes::Bytes;
+use lance_arrow::DataTypeExt;
+use lance_core::datatypes::{Field, Schema};
+use lance_core::Result;
+use lance_encoding::format::pb as pbenc;
+use prost::Message;
+
+use crate::format::pbfile;
+
+use super::reader::{CachedFileMetadata, FileReader};
+
+/// The schema of the batch returned by [`page_layout`]
+///
+/// Each row describes one page of one column:
+///
+/// * `column_index` - the index of the physical column in the file
+/// * `column_name` - the dotted path of the field that owns the column
+/// * `page_index` - the index of the page within the column
+/// * `num_rows` - the number of values in the page
+/// * `num_bytes` - the number of bytes the page occupies on disk
+/// * `uncompressed_bytes` - the estimated number of bytes the page would occupy
+///   without any compression (null if it cannot be estimated)
+/// * `compression_ratio` - `uncompressed_bytes / num_bytes` (null if unknown)
+/// * `encoding` - a compact description of the encoding tree of the page
+/// * `nulls` - one of `no_nulls`, `some_nulls`, `all_nulls`, or null if the page
+///   does not carry validity information.  This comes from the encoding of the
+///   page, the number of nulls is not recorded.
+pub fn page_layout_schema() -> SchemaRef {
+    static SCHEMA: OnceLock<SchemaRef> = OnceLock::new();
+    SCHEMA
+        .get_or_init(|| {
+            Arc::new(ArrowSchema::new(vec![
+                ArrowField::new("column_index", DataType::UInt32, false),
+                ArrowField::new("column_name", DataType::Utf8, true),
+                ArrowField::new("page_index", DataType::UInt32, false),
+                ArrowField::new("num_rows", DataType::UInt64, false),
+                ArrowField::new("num_bytes", DataType::UInt64, false),
+                ArrowField::new("uncompressed_bytes", DataType::UInt64, true),
+                ArrowField::new("compression_ratio", DataType::Float64, true),
+                ArrowField::new("encoding", DataType::Utf8, false),
+                ArrowField::new("nulls", DataType::Utf8, true),
+            ]))
+        })
+        .clone()
+}
+
+/// Summary of a (possibly nested) page encoding
+#[derive(Debug, Default)]
+struct EncodingSummary {
+    description: String,
+    uncompressed_bits: Option<u64>,
+    nulls: Option<&'static str>,
+}
+
+fn summarize_encoding(encoding: &pbenc::ArrayEncoding, num_values: u64) -> EncodingSummary {
+    use pbenc::array_encoding::ArrayEncoding as Enc;
+    use pbenc::nullable::Nullability;
+    match &encoding.array_encoding {
+        Some(Enc::Flat(flat)) => {
+            let description = match flat.compression.as_ref() {
+                Some(compression) if !compression.scheme.is_empty() => format!(
+                    "flat(bits={},compression={})",
+                    flat.bits_per_value, compression.scheme
+                ),
+                _ => format!("flat(bits={})", flat.bits_per_value),
+            };
+            EncodingSummary {
+                description,
+                uncompressed_bits: Some(flat.bits_per_value * num_values),
+                nulls: None,
+            }
+        }
+        Some(Enc::Nullable(nullable)) => match &nullable.nullability {
+            Some(Nullability::NoNulls(no_nulls)) => {
+                let values = summarize_child(no_nulls.values.as_deref(), num_values);
+                EncodingSummary {
+                    description: format!("nullable({})", values.description),
+                    uncompressed_bits: values.uncompressed_bits,
+                    nulls: Some("no_nulls"),
+                }
+            }
+            Some(Nullability::SomeNulls(some_nulls)) => {
+                let validity = summarize_child(some_nulls.validity.as_deref(), num_values);
+                let values = summarize_child(some_nulls.values.as_deref(), num_values);
+                EncodingSummary {
+                    description: format!(
+                        "nullable(validity={},values={})",
+                        validity.description, values.description
+                    ),
+                    uncompressed_bits: validity
+                        .uncompressed_bits
+                        .zip(values.uncompressed_bits)
+                        .map(|(validity, values)| validity + values),
+                    nulls: Some("some_nulls"),
+                }
+            }
+            Some(Nullability::AllNulls(_)) => EncodingSummary {
+                description: "nullable(all_nulls)".to_string(),
+                uncompressed_bits: Some(0),
+                nulls: Some("all_nulls"),
+            },
+            None => EncodingSummary {
+                description: "nullable(?)".to_string(),
+                ..Default::default()
+            },
+        },
+        Some(Enc::FixedSizeList(fsl)) => {
+            let items = summarize_child(fsl.items.as_deref(), num_values * fsl.dimension as u64);
+            EncodingSummary {
+                description: format!(
+                    "fixed_size_list(dim={},{})",
+                    fsl.dimension, items.description
+                ),
+                uncompressed_bits: items.uncompressed_bits,
+                nulls: items.nulls,
+            }
+        }
+        Some(Enc::List(list)) => {
+            let offsets = summarize_child(list.offsets.as_deref(), num_values);
+            EncodingSummary {
+                description: format!("list(offsets={})", offsets.description),
+                uncompressed_bits: offsets.uncompressed_bits,
+                nulls: offsets.nulls,
+            }
+        }
+        Some(Enc::Struct(_)) => EncodingSummary {
+            description: "struct".to_string(),
+            uncompressed_bits: Some(0),
+            nulls: None,
+        },
+        None => EncodingSummary {
+            description: "unknown".to_string(),
+            ..Default::default()
+        },
+    }
+}
+
+fn summarize_child(encoding: Option<&pbenc::ArrayEncoding>, num_values: u64) -> EncodingSummary {
+    match encoding {
+        Some(encoding) => summarize_encoding(encoding, num_values),
+        None => EncodingSummary {
+            description: "missing".to_string(),
+            ..Default::default()
+        },
+    }
+}
+
+fn summarize_page(page: &pbfile::column_metadata::Page) -> EncodingSummary {
+    let encoding = page.encoding.as_ref().and_then(|encoding| {
+        if let Some(pbfile::encoding::Location::Direct(direct)) = &encoding.location {
+            prost_types::Any::decode(Bytes::from(direct.encoding.clone()))
+                .ok()
+                .and_then(|encoding_any| encoding_any.to_msg::<pbenc::ArrayEncoding>().ok())
+        } else {
+            None
+        }
+    });
+    match encoding {
+        Some(encoding) => summarize_encoding(&encoding, page.length as u64),
+        // Indirect / missing encodings are described but cannot be sized
+        None => EncodingSummary {
+            description: super::reader::describe_encoding(page),
+            ..Default::default()
+        },
+    }
+}
+
+// Mirrors the column assignment performed by the core field encoding strategy
+fn collect_column_names(field: &Field, prefix: &str, names: &mut Vec<String>) {
+    let name = if prefix.is_empty() {
+        field.name.clone()
+    } else {
+        format!("{}.{}", prefix, field.name)
+    };
+    if field.data_type().is_binary_like() {
+        // Binary fields occupy an offsets column and a bytes column
+        names.push(name.clone());
+        names.push(name);
+    } else {
+        names.push(name.clone());
+        for child in &field.children {
+            collect_column_names(child, &name, names);
+        }
+    }
+}
+
+fn column_names(schema: &Schema) -> Vec<String> {
+    let mut names = Vec::new();
+    for field in &schema.fields {
+        collect_column_names(field, "", &mut names);
+    }
+    names
+}
+
+/// Describe the layout of every page in a file given its metadata
+///
+/// See [`page_layout_schema`] for a description of the output.
+pub fn page_layout(metadata: &CachedFileMetadata) -> Result<RecordBatch> {
+    let names = column_names(&metadata.file_schema);
+    let num_pages = metadata
+        .column_metadatas
+        .iter()
+        .map(|col| col.pages.len())
+        .sum::<usize>();
+
+    let mut column_index = UInt32Builder::with_capacity(num_pages);
+    let mut column_name = StringBuilder::with_capacity(num_pages, num_pages * 8);
+    let mut page_index = UInt32Builder::with_capacity(num_pages);
+    let mut num_rows = UInt64Builder::with_capacity(num_pages);
+    let mut num_bytes = UInt64Builder::with_capacity(num_pages);
+    let mut uncompressed_bytes = UInt64Builder::with_capacity(num_pages);
+    let mut compression_ratio = Float64Builder::with_capacity(num_pages);
+    let mut encoding = StringBuilder::with_capacity(num_pages, num_pages * 16);
+    let mut nulls = StringBuilder::with_capacity(num_pages, num_pages * 8);
+
+    for (col_idx, col) in metadata.column_metadatas.iter().enumerate() {
+        for (page_idx, page) in col.pages.iter().enumerate() {
+            let summary = summarize_page(page);
+            let page_bytes = page.buffer_sizes.iter().sum::<u64>();
+            let raw_bytes = summary.uncompressed_bits.map(|bits| bits.div_ceil(8));
+
+            column_index.append_value(col_idx as u32);
+            column_name.append_option(names.get(col_idx));
+            page_index.append_value(page_idx as u32);
+            num_rows.append_value(page.length as u64);
+            num_bytes.append_value(page_bytes);
+            uncompressed_bytes.append_option(raw_bytes);
+            compression_ratio.append_option(
+                raw_bytes
+                    .filter(|_| page_bytes > 0)
+                    .map(|raw| raw as f64 / page_bytes as f64),
+            );
+            encoding.append_value(summary.description);
+            nulls.append_option(summary.nulls);
+        }
+    }
+
+    Ok(RecordBatch::try_new(
+        page_layout_schema(),
+        vec![
+            Arc::new(column_index.finish()),
+            Arc::new(column_name.finish()),
+            Arc::new(page_index.finish()),
+            Arc::new(num_rows.finish()),
+            Arc::new(num_bytes.finish()),
+            Arc::new(uncompressed_bytes.finish()),
+            Arc::new(compression_ratio.finish()),
+            Arc::new(encoding.finish()),
+            Arc::new(nulls.finish()),
+        ],
+    )?)
+}
+
+impl FileReader {
+    /// Describe the layout of every page in the file
+    ///
+    /// This only uses the metadata that was loaded when the file was opened and does
+    /// not perform any I/O.  See [`page_layout_schema`] for a description of the output.
+    pub fn page_layout(&self) -> Result<RecordBatch> {
+        page_layout(self.metadata())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{
+        cast::AsArray,
+        types::{Float64Type, Int32Type, UInt32Type, UInt64Type},
+        RecordBatchReader,
+    };
+    use arrow_schema::ArrowError;
+    use lance_datagen::{array, gen, BatchCount, RowCount};
+    use lance_io::{object_store::ObjectStore, scheduler::ScanScheduler};
+    use object_store::path::Path;
+
+    use crate::v2::{
+        reader::FileReader,
+        writer::{FileWriter, FileWriterOptions},
+    };
+
+    #[tokio::test]
+    async fn test_page_layout() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let tmp_path = Path::parse(tmp_dir.path().to_str().unwrap())
+            .unwrap()
+            .child("layout.lance");
+        let object_store = Arc::new(ObjectStore::local());
+
+        let reader = gen()
+            .col("ints", array::step::<Int32Type>())
+            .col("floats", array::rand::<Float64Type>())
+            .col("strings", array::rand_utf8(16.into(), false))
+            .into_reader_rows(RowCount::from(100), BatchCount::from(4));
+        let lance_schema =
+            lance_core::datatypes::Schema::try_from(reader.schema().as_ref()).unwrap();
+        let data = reader
+            .collect::<std::result::Result<Vec<_>, ArrowError>>()
+            .unwrap();
+
+        let writer = object_store.create(&tmp_path).await.unwrap();
+        let mut file_writer = FileWriter::try_new(
+            writer,
+            tmp_path.to_string(),
+            lance_schema,
+            FileWriterOptions::default(),
+        )
+        .unwrap();
+        for batch in &data {
+            file_writer.write_batch(batch).await.unwrap();
+        }
+        file_writer.finish().await.unwrap();
+
+        let scheduler = ScanScheduler::new(object_store, 8);
+        let file_scheduler = scheduler.open_file(&tmp_path).await.unwrap();
+        let file_reader = FileReader::try_open(file_scheduler, None).await.unwrap();
+
+        let layout = file_reader.page_layout().unwrap();
+        assert!(layout.num_rows() > 0);
+
+        let column_index = layout
+            .column_by_name("column_index")
+            .unwrap()
+            .as_primitive::<UInt32Type>();
+        let num_rows = layout
+            .column_by_name("num_rows")
+            .unwrap()
+            .as_primitive::<UInt64Type>();
+        let names = layout
+            .column_by_name("column_name")
+            .unwrap()
+            .as_string::<i32>();
+        let uncompressed = layout
+            .column_by_name("uncompressed_bytes")
+            .unwrap()
+            .as_primitive::<UInt64Type>();
+
+        // Every value of the first column (ints) shows up in exactly one page
+        let int_rows = (0..layout.num_rows())
+            .filter(|idx| column_index.value(*idx) == 0)
+            .map(|idx| {
+                assert_eq!(names.value(idx), "ints");
+                // Uncompressed size of an int32 page is 4 bytes per value
+                assert_eq!(uncompressed.value(idx), num_rows.value(idx) * 4);
+                num_rows.value(idx)
+            })
+            .sum::<u64>();
+        assert_eq!(int_rows, 400);
+
+        // The string column occupies two physical columns
+        let string_columns = (0..layout.num_rows())
+            .filter(|idx| names.value(*idx) == "strings")
+            .map(|idx| column_index.value(idx))
+            .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(string_columns.len(), 2);
+    }
+}
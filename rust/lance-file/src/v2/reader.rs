@@ -18,6 +18,7 @@ use snafu::{location, Location};
 
 use lance_core::{
     datatypes::{Field, Schema},
+    utils::tokio::DecodeLimit,
     Error, Result,
 };
 use lance_encoding::format::pb as pbenc;
@@ -93,13 +94,14 @@ pub struct ReaderProjection {
     pub column_indices: Vec<u32>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FileReader {
     scheduler: Arc<LanceEncodingsIo>,
     // The default projection to be applied to all reads
     base_projection: ReaderProjection,
     num_rows: u64,
     metadata: Arc<CachedFileMetadata>,
+    decode_limit: DecodeLimit,
 }
 
 struct Footer {
@@ -546,9 +548,19 @@ impl FileReader {
                 .unwrap_or(Self::default_projection(file_metadata.file_schema.as_ref())),
             num_rows,
             metadata: file_metadata,
+            decode_limit: DecodeLimit::default(),
         })
     }
 
+    /// Bound how many batches read from this file are decoded at the same time
+    ///
+    /// This is in addition to the process-wide decode limit.  It has no effect on
+    /// the I/O parallelism of the reads.
+    pub fn with_decode_limit(mut self, decode_limit: DecodeLimit) -> Self {
+        self.decode_limit = decode_limit;
+        self
+    }
+
     fn collect_columns(
         &self,
         field: &Field,
@@ -629,7 +641,11 @@ impl FileReader {
         let scheduler = self.scheduler.clone() as Arc<dyn EncodingsIo>;
         tokio::task::spawn(async move { decode_scheduler.schedule_range(range, tx, scheduler) });
 
-        Ok(BatchDecodeStream::new(rx, batch_size, num_rows_to_read, root_decoder).into_stream())
+        Ok(
+            BatchDecodeStream::new(rx, batch_size, num_rows_to_read, root_decoder)
+                .with_decode_limit(self.decode_limit.clone())
+                .into_stream(),
+        )
     }
 
     fn take_rows(
@@ -664,7 +680,11 @@ impl FileReader {
         let scheduler = self.scheduler.clone() as Arc<dyn EncodingsIo>;
         tokio::task::spawn(async move { decode_scheduler.schedule_take(&indices, tx, scheduler) });
 
-        Ok(BatchDecodeStream::new(rx, batch_size, num_rows_to_read, root_decoder).into_stream())
+        Ok(
+            BatchDecodeStream::new(rx, batch_size, num_rows_to_read, root_decoder)
+                .with_decode_limit(self.decode_limit.clone())
+                .into_stream(),
+        )
     }
 
     /// Creates a stream of "read tasks" to read the data from the file
@@ -847,7 +867,7 @@ mod tests {
 
     use crate::v2::{
         reader::{FileReader, ReaderProjection},
-        writer::{FileWriter, FileWriterOptions},
+        writer::{FileWriter, FileWriterOptions, PAGE_BYTES_META_KEY, PAGE_ROWS_META_KEY},
     };
 
     struct FsFixture {
@@ -988,6 +1008,63 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_max_page_rows() {
+        let fs = FsFixture::default();
+
+        let reader = gen()
+            .col("score", array::rand::<Float64Type>())
+            .into_reader_rows(RowCount::from(70), BatchCount::from(10));
+        // Batches don't line up with pages, so they have to be split
+        let lance_schema = Schema::try_from(reader.schema().as_ref()).unwrap();
+        let data = reader
+            .collect::<std::result::Result<Vec<_>, ArrowError>>()
+            .unwrap();
+
+        let writer = fs.object_store.create(&fs.tmp_path).await.unwrap();
+        let mut file_writer = FileWriter::try_new(
+            writer,
+            fs.tmp_path.to_string(),
+            lance_schema,
+            FileWriterOptions {
+                max_page_rows: Some(100),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        for batch in &data {
+            file_writer.write_batch(batch).await.unwrap();
+        }
+        file_writer.finish().await.unwrap();
+
+        let file_scheduler = fs.scheduler.open_file(&fs.tmp_path).await.unwrap();
+        let file_reader = FileReader::try_open(file_scheduler, None).await.unwrap();
+        let metadata = file_reader.metadata();
+        assert_eq!(
+            metadata
+                .file_schema
+                .metadata
+                .get(PAGE_ROWS_META_KEY)
+                .unwrap(),
+            "100"
+        );
+        assert!(!metadata
+            .file_schema
+            .metadata
+            .contains_key(PAGE_BYTES_META_KEY));
+        let page_rows = metadata.column_infos[0]
+            .page_infos
+            .iter()
+            .map(|page| page.num_rows)
+            .collect::<Vec<_>>();
+        assert_eq!(page_rows, vec![100; 7]);
+
+        let batch_stream = file_reader
+            .read_stream(lance_io::ReadBatchParams::RangeFull, 1024, 16)
+            .unwrap();
+        verify_expected(&data, batch_stream, 1024, None).await;
+    }
+
     #[test_log::test(tokio::test)]
     async fn test_projection() {
         let fs = FsFixture::default();
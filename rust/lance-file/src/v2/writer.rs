@@ -4,15 +4,17 @@
 use std::sync::Arc;
 
 use arrow_array::RecordBatch;
+use async_trait::async_trait;
 
 use bytes::{BufMut, Bytes, BytesMut};
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use lance_core::datatypes::Schema as LanceSchema;
+use lance_core::sink::AsyncBatchSink;
 use lance_core::{Error, Result};
 use lance_encoding::encoder::{
-    BatchEncoder, CoreFieldEncodingStrategy, EncodeTask, EncodedBatch, EncodedPage, FieldEncoder,
-    FieldEncodingStrategy,
+    BatchEncoder, CoreFieldEncodingStrategy, EncodeTask, EncodedBatch, EncodedPage,
+    EncodingOptions, FieldEncoder, FieldEncodingStrategy,
 };
 use lance_io::object_writer::ObjectWriter;
 use lance_io::traits::Writer;
@@ -30,6 +32,13 @@ use crate::format::MAGIC;
 use crate::format::MAJOR_VERSION;
 use crate::format::MINOR_VERSION_NEXT;
 
+/// Schema metadata key recording the target page size, in bytes, the file was written
+/// with, if it was set
+pub const PAGE_BYTES_META_KEY: &str = "lance:file:page_bytes";
+/// Schema metadata key recording the maximum page size, in rows, the file was written
+/// with, if it was set
+pub const PAGE_ROWS_META_KEY: &str = "lance:file:page_rows";
+
 #[derive(Debug, Clone, Default)]
 pub struct FileWriterOptions {
     /// How many bytes to use for buffering column data
@@ -66,6 +75,19 @@ pub struct FileWriterOptions {
     /// of that batch's data has been written to disk)
     pub keep_original_array: Option<bool>,
     pub encoding_strategy: Option<Arc<dyn FieldEncodingStrategy>>,
+    /// The target size of a page, in bytes, before encoding
+    ///
+    /// A column writes a page once it has buffered this much data.  If set, this
+    /// is used for every column instead of an even share of `data_cache_bytes`.
+    /// Larger pages mean fewer, larger reads for scans, smaller pages mean less
+    /// data is read for point lookups.
+    pub max_page_bytes: Option<u64>,
+    /// The number of values after which a page is written, even if it is smaller
+    /// than the target page size
+    ///
+    /// This bounds how many rows a point lookup has to decode.  By default pages
+    /// are only bounded by size.
+    pub max_page_rows: Option<u64>,
 }
 
 pub struct FileWriter {
@@ -98,25 +120,46 @@ impl FileWriter {
         schema: LanceSchema,
         options: FileWriterOptions,
     ) -> Result<Self> {
-        let cache_bytes_per_column = if let Some(data_cache_bytes) = options.data_cache_bytes {
+        let cache_bytes_per_column = if let Some(max_page_bytes) = options.max_page_bytes {
+            max_page_bytes
+        } else if let Some(data_cache_bytes) = options.data_cache_bytes {
             data_cache_bytes / schema.fields.len() as u64
         } else {
             8 * 1024 * 1024
         };
+        if cache_bytes_per_column == 0 || options.max_page_rows == Some(0) {
+            return Err(Error::invalid_input(
+                "the page size of a Lance file must be greater than 0",
+                location!(),
+            ));
+        }
 
         schema.validate()?;
 
-        let keep_original_array = options.keep_original_array.unwrap_or(false);
+        // Record the page layout that was asked for in the file so it can be inspected later
+        let mut schema = schema;
+        if let Some(max_page_bytes) = options.max_page_bytes {
+            schema
+                .metadata
+                .insert(PAGE_BYTES_META_KEY.to_string(), max_page_bytes.to_string());
+        }
+        if let Some(max_page_rows) = options.max_page_rows {
+            schema
+                .metadata
+                .insert(PAGE_ROWS_META_KEY.to_string(), max_page_rows.to_string());
+        }
+
+        let encoding_options = EncodingOptions {
+            cache_bytes_per_column,
+            max_page_rows: options.max_page_rows.unwrap_or(u64::MAX),
+            keep_original_array: options.keep_original_array.unwrap_or(false),
+        };
         let encoding_strategy = options
             .encoding_strategy
             .unwrap_or_else(|| Arc::new(CoreFieldEncodingStrategy::default()));
 
-        let encoder = BatchEncoder::try_new(
-            &schema,
-            encoding_strategy.as_ref(),
-            cache_bytes_per_column,
-            keep_original_array,
-        )?;
+        let encoder =
+            BatchEncoder::try_new(&schema, encoding_strategy.as_ref(), &encoding_options)?;
         let num_columns = encoder.num_columns();
 
         let column_writers = encoder.field_encoders;
@@ -398,6 +441,20 @@ impl FileWriter {
     }
 }
 
+#[async_trait]
+impl AsyncBatchSink for FileWriter {
+    /// The number of rows written
+    type Output = u64;
+
+    async fn write_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        Self::write_batch(self, batch).await
+    }
+
+    async fn finish(&mut self) -> Result<u64> {
+        Self::finish(self).await
+    }
+}
+
 /// Utility trait for converting EncodedBatch to Bytes using the
 /// lance file format
 pub trait EncodedBatchWriteExt {
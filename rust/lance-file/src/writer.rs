@@ -16,6 +16,7 @@ use async_recursion::async_recursion;
 use async_trait::async_trait;
 use lance_arrow::*;
 use lance_core::datatypes::{Encoding, Field, Schema, SchemaCompareOptions};
+use lance_core::sink::AsyncBatchSink;
 use lance_core::{Error, Result};
 use lance_io::encodings::{
     binary::BinaryEncoder, dictionary::DictionaryEncoder, plain::PlainEncoder, Encoder,
@@ -680,6 +681,20 @@ impl<M: ManifestProvider + Send + Sync> FileWriter<M> {
     }
 }
 
+#[async_trait]
+impl<M: ManifestProvider + Send + Sync> AsyncBatchSink for FileWriter<M> {
+    /// The number of rows written
+    type Output = usize;
+
+    async fn write_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        self.write(std::slice::from_ref(batch)).await
+    }
+
+    async fn finish(&mut self) -> Result<usize> {
+        Self::finish(self).await
+    }
+}
+
 /// Walk through the schema and return arrays with their Lance field.
 ///
 /// This skips over nested arrays and fields within list arrays. It does walk
@@ -12,17 +12,21 @@ use arrow_array::{
     builder::{GenericBinaryBuilder, GenericStringBuilder},
     cast::{as_generic_binary_array, as_primitive_array, AsArray},
     types::{
-        ArrowDictionaryKeyType, Date32Type, Date64Type, Decimal128Type, DurationMicrosecondType,
-        DurationMillisecondType, DurationNanosecondType, DurationSecondType, Float32Type,
-        Float64Type, Int16Type, Int32Type, Int64Type, Int8Type, Time32MillisecondType,
-        Time32SecondType, Time64MicrosecondType, Time64NanosecondType, TimestampMicrosecondType,
-        TimestampMillisecondType, TimestampNanosecondType, TimestampSecondType, UInt16Type,
-        UInt32Type, UInt64Type, UInt8Type,
+        ArrowDictionaryKeyType, Date32Type, Date64Type, Decimal128Type, Decimal256Type,
+        DurationMicrosecondType, DurationMillisecondType, DurationNanosecondType,
+        DurationSecondType, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type,
+        IntervalYearMonthType, Time32MillisecondType, Time32SecondType, Time64MicrosecondType,
+        Time64NanosecondType, TimestampMicrosecondType, TimestampMillisecondType,
+        TimestampNanosecondType, TimestampSecondType, UInt16Type, UInt32Type, UInt64Type,
+        UInt8Type,
     },
     Array, ArrayRef, ArrowNumericType, ArrowPrimitiveType, OffsetSizeTrait, PrimitiveArray,
     RecordBatch, StructArray,
 };
-use arrow_schema::{ArrowError, DataType, Field as ArrowField, Schema as ArrowSchema, TimeUnit};
+use arrow_buffer::i256;
+use arrow_schema::{
+    ArrowError, DataType, Field as ArrowField, IntervalUnit, Schema as ArrowSchema, TimeUnit,
+};
 use datafusion_common::ScalarValue;
 use lance_arrow::{as_fixed_size_binary_array, DataTypeExt};
 use lance_core::datatypes::{Field, Schema};
@@ -173,6 +177,33 @@ fn get_decimal_statistics(arrays: &[&ArrayRef]) -> StatisticsRow {
     }
 }
 
+// i256 doesn't implement `Bounded`, so this can't use `compute_primitive_statistics`.
+fn get_decimal256_statistics(arrays: &[&ArrayRef]) -> StatisticsRow {
+    let mut min_value = None;
+    let mut max_value = None;
+    let mut null_count: i64 = 0;
+    for array in arrays
+        .iter()
+        .map(|x| as_primitive_array::<Decimal256Type>(x))
+    {
+        null_count += array.null_count() as i64;
+        for value in array.iter().flatten() {
+            min_value = Some(min_value.map_or(value, |min: i256| min.min(value)));
+            max_value = Some(max_value.map_or(value, |max: i256| max.max(value)));
+        }
+    }
+    let array = as_primitive_array::<Decimal256Type>(arrays[0]);
+    let precision = array.precision();
+    let scale = array.scale();
+
+    // Like the other types, all null arrays get the full range.
+    StatisticsRow {
+        null_count,
+        min_value: ScalarValue::Decimal256(Some(min_value.unwrap_or(i256::MIN)), precision, scale),
+        max_value: ScalarValue::Decimal256(Some(max_value.unwrap_or(i256::MAX)), precision, scale),
+    }
+}
+
 /// Truncate a UTF8 slice to the longest prefix that is still a valid UTF8 string, while being less than `length` bytes.
 fn truncate_utf8(data: &str, length: usize) -> Option<&str> {
     // We return values like that at an earlier stage in the process.
@@ -544,6 +575,9 @@ fn get_temporal_statistics(arrays: &[&ArrayRef]) -> StatisticsRow {
         DataType::Duration(TimeUnit::Nanosecond) => {
             get_statistics::<DurationNanosecondType>(arrays)
         }
+        DataType::Interval(IntervalUnit::YearMonth) => {
+            get_statistics::<IntervalYearMonthType>(arrays)
+        }
         _ => {
             unreachable!()
         }
@@ -571,9 +605,10 @@ pub fn collect_statistics(arrays: &[&ArrayRef]) -> StatisticsRow {
         | DataType::Date64
         | DataType::Time64(_)
         | DataType::Timestamp(_, _)
-        | DataType::Duration(_) => get_temporal_statistics(arrays),
+        | DataType::Duration(_)
+        | DataType::Interval(IntervalUnit::YearMonth) => get_temporal_statistics(arrays),
         DataType::Decimal128(_, _) => get_decimal_statistics(arrays),
-        // TODO: Decimal256
+        DataType::Decimal256(_, _) => get_decimal256_statistics(arrays),
         DataType::Binary => get_binary_statistics::<i32>(arrays),
         DataType::LargeBinary => get_binary_statistics::<i64>(arrays),
         DataType::FixedSizeBinary(_) => get_fixed_size_binary_statistics(arrays),
@@ -602,12 +637,19 @@ pub fn supports_stats_collection(datatype: &DataType) -> bool {
             | DataType::Float64
             | DataType::Date32
             | DataType::Date64
+            | DataType::Time32(_)
+            | DataType::Time64(_)
+            | DataType::Timestamp(_, _)
+            | DataType::Duration(_)
+            // Only year-month intervals have a total order
+            | DataType::Interval(IntervalUnit::YearMonth)
             | DataType::Utf8
             | DataType::Binary
             | DataType::LargeUtf8
             | DataType::LargeBinary
             // | DataType::FixedSizeBinary(_)
             | DataType::Decimal128(_, _)
+            | DataType::Decimal256(_, _)
     )
 }
 
@@ -916,8 +958,11 @@ impl StatisticsBuilder {
             DataType::Duration(TimeUnit::Nanosecond) => {
                 self.statistics_appender::<DurationNanosecondType>(row)
             }
+            DataType::Interval(IntervalUnit::YearMonth) => {
+                self.statistics_appender::<IntervalYearMonthType>(row)
+            }
             DataType::Decimal128(_, _) => self.statistics_appender::<Decimal128Type>(row),
-            // TODO: Decimal256
+            DataType::Decimal256(_, _) => self.statistics_appender::<Decimal256Type>(row),
             DataType::Binary => self.binary_statistics_appender::<i32>(row),
             DataType::LargeBinary => self.binary_statistics_appender::<i64>(row),
             DataType::Utf8 => self.string_statistics_appender::<i32>(row),
@@ -935,13 +980,14 @@ impl StatisticsBuilder {
 mod tests {
     use arrow_array::{
         builder::StringDictionaryBuilder, make_array, new_empty_array, new_null_array, BinaryArray,
-        BooleanArray, Date32Array, Date64Array, Datum, Decimal128Array, DictionaryArray,
-        DurationMicrosecondArray, DurationMillisecondArray, DurationNanosecondArray,
-        DurationSecondArray, FixedSizeBinaryArray, Float32Array, Float64Array, Int16Array,
-        Int32Array, Int64Array, Int8Array, LargeBinaryArray, LargeStringArray, StringArray,
-        Time32MillisecondArray, Time32SecondArray, Time64MicrosecondArray, Time64NanosecondArray,
-        TimestampMicrosecondArray, TimestampMillisecondArray, TimestampNanosecondArray,
-        TimestampSecondArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array,
+        BooleanArray, Date32Array, Date64Array, Datum, Decimal128Array, Decimal256Array,
+        DictionaryArray, DurationMicrosecondArray, DurationMillisecondArray,
+        DurationNanosecondArray, DurationSecondArray, FixedSizeBinaryArray, Float32Array,
+        Float64Array, Int16Array, Int32Array, Int64Array, Int8Array, IntervalYearMonthArray,
+        LargeBinaryArray, LargeStringArray, StringArray, Time32MillisecondArray, Time32SecondArray,
+        Time64MicrosecondArray, Time64NanosecondArray, TimestampMicrosecondArray,
+        TimestampMillisecondArray, TimestampNanosecondArray, TimestampSecondArray, UInt16Array,
+        UInt32Array, UInt64Array, UInt8Array,
     };
     use arrow_select::interleave::interleave;
     use num_traits::One;
@@ -1040,7 +1086,7 @@ mod tests {
             expected_null_count: i64,
         }
 
-        let cases: [TestCase; 24] = [
+        let cases: [TestCase; 26] = [
             // Int8
             TestCase {
                 source_arrays: vec![
@@ -1282,6 +1328,20 @@ mod tests {
                 expected_max: ScalarValue::DurationNanosecond(Some(68)),
                 expected_null_count: 0,
             },
+            // Interval
+            TestCase {
+                source_arrays: vec![
+                    Arc::new(IntervalYearMonthArray::from(vec![
+                        Some(53),
+                        None,
+                        Some(-42),
+                    ])),
+                    Arc::new(IntervalYearMonthArray::from(vec![68, 32])),
+                ],
+                expected_min: ScalarValue::IntervalYearMonth(Some(-42)),
+                expected_max: ScalarValue::IntervalYearMonth(Some(68)),
+                expected_null_count: 1,
+            },
             // Decimal
             TestCase {
                 source_arrays: vec![
@@ -1292,6 +1352,22 @@ mod tests {
                 expected_max: ScalarValue::try_new_decimal128(68, 38, 10).unwrap(),
                 expected_null_count: 0,
             },
+            TestCase {
+                source_arrays: vec![
+                    Arc::new(Decimal256Array::from(vec![
+                        Some(i256::from_i128(53)),
+                        None,
+                        Some(i256::from_i128(-42)),
+                    ])),
+                    Arc::new(Decimal256Array::from(vec![
+                        i256::from_i128(68),
+                        i256::from_i128(32),
+                    ])),
+                ],
+                expected_min: ScalarValue::Decimal256(Some(i256::from_i128(-42)), 76, 10),
+                expected_max: ScalarValue::Decimal256(Some(i256::from_i128(68)), 76, 10),
+                expected_null_count: 1,
+            },
         ];
 
         for case in cases {
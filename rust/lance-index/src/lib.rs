@@ -16,6 +16,7 @@ use lance_core::Result;
 use roaring::RoaringBitmap;
 use serde::{Deserialize, Serialize};
 
+pub mod minhash;
 pub mod optimize;
 pub mod scalar;
 pub mod traits;
@@ -63,6 +64,8 @@ pub trait Index: Send + Sync + DeepSizeOf {
 pub enum IndexType {
     // Preserve 0-100 for simple indices.
     Scalar = 0,
+    /// MinHash LSH index for Jaccard similarity.
+    MinHash = 1,
     // 100+ and up for vector index.
     /// Flat vector index.
     Vector = 100,
@@ -72,6 +75,7 @@ impl std::fmt::Display for IndexType {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Self::Scalar => write!(f, "Scalar"),
+            Self::MinHash => write!(f, "MinHash"),
             Self::Vector => write!(f, "Vector"),
         }
     }
@@ -0,0 +1,688 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! MinHash LSH index for Jaccard similarity search
+//!
+//! Every row is reduced to a set of tokens: the shingles of consecutive words
+//! of a text column, or the elements of a list column.  The MinHash signature
+//! of the set estimates the Jaccard similarity with any other set, and the
+//! signatures are split into bands so that rows sharing a whole band land in
+//! the same bucket.  Two rows with Jaccard similarity `s` share a bucket with
+//! probability `1 - (1 - s^r)^b` for `b` bands of `r` rows, which makes
+//! finding near duplicates sub-linear in the size of the corpus.
+
+use std::collections::{HashMap, HashSet};
+use std::{any::Any, sync::Arc};
+
+use arrow_array::{
+    cast::AsArray, types::UInt32Type, types::UInt64Type, Array, FixedSizeListArray,
+    GenericListArray, GenericStringArray, OffsetSizeTrait, RecordBatch, StringArray, UInt32Array,
+    UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema};
+use async_trait::async_trait;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use deepsize::DeepSizeOf;
+use futures::TryStreamExt;
+use lance_arrow::FixedSizeListArrayExt;
+use lance_core::utils::address::RowAddress;
+use lance_core::utils::hash::{fnv1a, fnv1a_extend, FNV1A_OFFSET};
+use lance_core::{Error, Result, ROW_ID};
+use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
+use snafu::{location, Location};
+
+use crate::scalar::IndexStore;
+use crate::{Index, IndexParams, IndexType};
+
+pub const LANCE_MINHASH_INDEX: &str = "__lance_minhash_index";
+
+const MINHASH_PARAMS_NAME: &str = "minhash_params.lance";
+const MINHASH_SIGNATURES_NAME: &str = "minhash_signatures.lance";
+const WRITE_BATCH_SIZE: usize = 4096;
+
+/// Parameters of a MinHash LSH index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, DeepSizeOf)]
+pub struct MinHashParams {
+    /// Number of bands the signatures are split into
+    pub num_bands: u32,
+    /// Number of min-hashes in each band
+    pub rows_per_band: u32,
+    /// Number of consecutive words hashed together, for text columns
+    pub shingle_size: u32,
+}
+
+impl Default for MinHashParams {
+    fn default() -> Self {
+        Self {
+            num_bands: 16,
+            rows_per_band: 8,
+            shingle_size: 3,
+        }
+    }
+}
+
+impl IndexParams for MinHashParams {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn index_type(&self) -> IndexType {
+        IndexType::MinHash
+    }
+
+    fn index_name(&self) -> &str {
+        LANCE_MINHASH_INDEX
+    }
+}
+
+impl MinHashParams {
+    /// Length of the signatures
+    pub fn num_hashes(&self) -> usize {
+        (self.num_bands * self.rows_per_band) as usize
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.num_bands == 0 || self.rows_per_band == 0 || self.shingle_size == 0 {
+            return Err(Error::invalid_input(
+                "num_bands, rows_per_band and shingle_size of a MinHash index must be positive",
+                location!(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The Jaccard similarity above which rows are likely to share a bucket.
+    ///
+    /// Pairs far below this similarity are rarely found by the index.
+    pub fn threshold(&self) -> f32 {
+        (1.0 / self.num_bands as f32).powf(1.0 / self.rows_per_band as f32)
+    }
+
+    /// Whether a column of `data_type` can be indexed
+    pub fn supports(data_type: &DataType) -> bool {
+        match data_type {
+            DataType::Utf8 | DataType::LargeUtf8 => true,
+            DataType::List(field) | DataType::LargeList(field) => {
+                matches!(field.data_type(), DataType::Utf8 | DataType::LargeUtf8)
+            }
+            _ => false,
+        }
+    }
+
+    /// The hashes of the shingles of `text`
+    ///
+    /// Text is split into lowercase words at any character that isn't
+    /// alphanumeric.  Text with fewer words than the shingle size is a single
+    /// shingle.
+    pub fn hash_text(&self, text: &str) -> Vec<u64> {
+        let words = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect::<Vec<_>>();
+        let shingle_size = (self.shingle_size as usize).min(words.len()).max(1);
+        let mut hashes = words
+            .windows(shingle_size)
+            .map(|shingle| {
+                shingle.iter().fold(FNV1A_OFFSET, |hash, word| {
+                    // Separate the words so that "ab c" and "a bc" differ
+                    fnv1a_extend(fnv1a_extend(hash, word.as_bytes()), &[0])
+                })
+            })
+            .collect::<Vec<_>>();
+        hashes.sort_unstable();
+        hashes.dedup();
+        hashes
+    }
+
+    /// The hashes of a set of tokens
+    pub fn hash_tokens<'a>(&self, tokens: impl IntoIterator<Item = &'a str>) -> Vec<u64> {
+        let mut hashes = tokens
+            .into_iter()
+            .map(|token| fnv1a(token.as_bytes()))
+            .collect::<Vec<_>>();
+        hashes.sort_unstable();
+        hashes.dedup();
+        hashes
+    }
+
+    /// The MinHash signature of a set of token hashes, or `None` for the
+    /// empty set, which is not similar to anything.
+    pub fn signature(&self, hashes: &[u64]) -> Option<Vec<u32>> {
+        if hashes.is_empty() {
+            return None;
+        }
+        let signature = (0..self.num_hashes() as u64)
+            .map(|i| {
+                // Each min-hash uses a different random affine permutation of
+                // the hashes.  The coefficients are derived from the position,
+                // so they are the same for every index.
+                let a = splitmix64(2 * i) | 1;
+                let b = splitmix64(2 * i + 1);
+                hashes
+                    .iter()
+                    .map(|hash| (hash.wrapping_mul(a).wrapping_add(b) >> 32) as u32)
+                    .min()
+                    .unwrap()
+            })
+            .collect();
+        Some(signature)
+    }
+
+    /// The signatures of the values of a column
+    pub fn signatures(&self, values: &dyn Array) -> Result<Vec<Option<Vec<u32>>>> {
+        match values.data_type() {
+            DataType::Utf8 => Ok(self.text_signatures(values.as_string::<i32>())),
+            DataType::LargeUtf8 => Ok(self.text_signatures(values.as_string::<i64>())),
+            DataType::List(_) => self.token_set_signatures(values.as_list::<i32>()),
+            DataType::LargeList(_) => self.token_set_signatures(values.as_list::<i64>()),
+            data_type => Err(Error::invalid_input(
+                format!("MinHash index does not support type {data_type}"),
+                location!(),
+            )),
+        }
+    }
+
+    fn text_signatures<O: OffsetSizeTrait>(
+        &self,
+        values: &GenericStringArray<O>,
+    ) -> Vec<Option<Vec<u32>>> {
+        values
+            .iter()
+            .map(|text| text.and_then(|text| self.signature(&self.hash_text(text))))
+            .collect()
+    }
+
+    fn token_set_signatures<O: OffsetSizeTrait>(
+        &self,
+        values: &GenericListArray<O>,
+    ) -> Result<Vec<Option<Vec<u32>>>> {
+        values
+            .iter()
+            .map(|tokens| {
+                let Some(tokens) = tokens else {
+                    return Ok(None);
+                };
+                let hashes = match tokens.data_type() {
+                    DataType::Utf8 => self.hash_tokens(tokens.as_string::<i32>().iter().flatten()),
+                    DataType::LargeUtf8 => {
+                        self.hash_tokens(tokens.as_string::<i64>().iter().flatten())
+                    }
+                    data_type => {
+                        return Err(Error::invalid_input(
+                            format!("MinHash index does not support lists of {data_type}"),
+                            location!(),
+                        ))
+                    }
+                };
+                Ok(self.signature(&hashes))
+            })
+            .collect()
+    }
+
+    /// The bucket of each band of a signature
+    fn bucket_keys<'a>(&self, signature: &'a [u32]) -> impl Iterator<Item = u64> + 'a {
+        signature
+            .chunks(self.rows_per_band as usize)
+            .enumerate()
+            .map(|(band, rows)| {
+                rows.iter()
+                    .fold(fnv1a(&(band as u32).to_le_bytes()), |hash, row| {
+                        fnv1a_extend(hash, &row.to_le_bytes())
+                    })
+            })
+    }
+}
+
+/// The fraction of equal min-hashes of two signatures, which estimates the
+/// Jaccard similarity of their sets
+pub fn estimate_jaccard(left: &[u32], right: &[u32]) -> f32 {
+    let equal = left.iter().zip(right).filter(|(l, r)| l == r).count();
+    equal as f32 / left.len() as f32
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// A MinHash LSH index
+///
+/// The signatures of all rows are kept in memory.  Rows with a null or empty
+/// value have no signature and are not indexed.
+#[derive(DeepSizeOf)]
+pub struct MinHashIndex {
+    params: MinHashParams,
+    row_ids: Vec<u64>,
+    /// The signatures of the rows, `num_hashes` values per row
+    signatures: Vec<u32>,
+    /// The positions of the rows in each bucket
+    buckets: HashMap<u64, Vec<u32>>,
+}
+
+impl std::fmt::Debug for MinHashIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MinHashIndex")
+            .field("params", &self.params)
+            .field("num_rows", &self.row_ids.len())
+            .field("num_buckets", &self.buckets.len())
+            .finish()
+    }
+}
+
+impl MinHashIndex {
+    /// Create an index of the rows with the given signatures
+    pub fn new(params: MinHashParams, row_ids: Vec<u64>, signatures: Vec<u32>) -> Self {
+        debug_assert_eq!(row_ids.len() * params.num_hashes(), signatures.len());
+        let mut buckets: HashMap<u64, Vec<u32>> = HashMap::new();
+        for (position, signature) in signatures.chunks(params.num_hashes()).enumerate() {
+            for key in params.bucket_keys(signature) {
+                buckets.entry(key).or_default().push(position as u32);
+            }
+        }
+        Self {
+            params,
+            row_ids,
+            signatures,
+            buckets,
+        }
+    }
+
+    /// Create an index of a stream of batches with the values in the first
+    /// column and the row ids in the [`ROW_ID`] column
+    pub async fn try_from_stream(
+        params: MinHashParams,
+        data: SendableRecordBatchStream,
+    ) -> Result<Self> {
+        let mut row_ids = Vec::new();
+        let mut signatures = Vec::new();
+        Self::collect_signatures(&params, data, &mut row_ids, &mut signatures).await?;
+        Ok(Self::new(params, row_ids, signatures))
+    }
+
+    async fn collect_signatures(
+        params: &MinHashParams,
+        mut data: SendableRecordBatchStream,
+        row_ids: &mut Vec<u64>,
+        signatures: &mut Vec<u32>,
+    ) -> Result<()> {
+        while let Some(batch) = data.try_next().await? {
+            let batch_row_ids = batch
+                .column_by_name(ROW_ID)
+                .ok_or_else(|| Error::Internal {
+                    message: "MinHash index data must contain row ids".to_string(),
+                    location: location!(),
+                })?
+                .as_primitive::<UInt64Type>();
+            let batch_signatures = params.signatures(batch.column(0).as_ref())?;
+            for (row_id, signature) in batch_row_ids.values().iter().zip(batch_signatures) {
+                if let Some(signature) = signature {
+                    row_ids.push(*row_id);
+                    signatures.extend(signature);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn params(&self) -> &MinHashParams {
+        &self.params
+    }
+
+    /// The ids of the indexed rows
+    pub fn row_ids(&self) -> &[u64] {
+        &self.row_ids
+    }
+
+    /// The signature of the row at `position` in [`Self::row_ids`]
+    pub fn signature(&self, position: usize) -> &[u32] {
+        let num_hashes = self.params.num_hashes();
+        &self.signatures[position * num_hashes..(position + 1) * num_hashes]
+    }
+
+    /// Find the rows whose estimated Jaccard similarity with `signature` is at
+    /// least `threshold`.
+    ///
+    /// Returns the row ids and similarities, most similar first.
+    pub fn search(&self, signature: &[u32], threshold: f32) -> Vec<(u64, f32)> {
+        let candidates = self
+            .params
+            .bucket_keys(signature)
+            .filter_map(|key| self.buckets.get(&key))
+            .flatten()
+            .copied()
+            .collect::<HashSet<_>>();
+        let mut results = candidates
+            .into_iter()
+            .map(|position| {
+                let position = position as usize;
+                let similarity = estimate_jaccard(signature, self.signature(position));
+                (self.row_ids[position], similarity)
+            })
+            .filter(|(_, similarity)| *similarity >= threshold)
+            .collect::<Vec<_>>();
+        results.sort_by(|(left_id, left), (right_id, right)| {
+            right.total_cmp(left).then(left_id.cmp(right_id))
+        });
+        results
+    }
+
+    /// Find the pairs of rows whose estimated Jaccard similarity is at least
+    /// `threshold`.
+    ///
+    /// Every pair of rows sharing a bucket is compared, so this is quadratic in
+    /// the size of the largest bucket.  Returns `(row_id, row_id, similarity)`
+    /// with the smaller row id first, most similar pairs first.
+    pub fn similar_pairs(&self, threshold: f32) -> Vec<(u64, u64, f32)> {
+        let mut candidates = HashSet::new();
+        for positions in self
+            .buckets
+            .values()
+            .filter(|positions| positions.len() > 1)
+        {
+            for (i, left) in positions.iter().enumerate() {
+                for right in &positions[i + 1..] {
+                    candidates.insert((*left, *right));
+                }
+            }
+        }
+        let mut results = candidates
+            .into_iter()
+            .filter_map(|(left, right)| {
+                let similarity = estimate_jaccard(
+                    self.signature(left as usize),
+                    self.signature(right as usize),
+                );
+                let (left, right) = (self.row_ids[left as usize], self.row_ids[right as usize]);
+                (similarity >= threshold).then_some((left.min(right), left.max(right), similarity))
+            })
+            .collect::<Vec<_>>();
+        results.sort_by(|left, right| {
+            right
+                .2
+                .total_cmp(&left.2)
+                .then((left.0, left.1).cmp(&(right.0, right.1)))
+        });
+        results
+    }
+
+    /// Write the index to `store`
+    pub async fn write(&self, store: &dyn IndexStore) -> Result<()> {
+        let params = StringArray::from(vec![serde_json::to_string(&self.params)?]);
+        let params = RecordBatch::try_from_iter(vec![("params", Arc::new(params) as _)])?;
+        let mut params_file = store
+            .new_index_file(MINHASH_PARAMS_NAME, params.schema())
+            .await?;
+        params_file.write_record_batch(params).await?;
+        params_file.finish().await?;
+
+        let num_hashes = self.params.num_hashes();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("row_ids", DataType::UInt64, false),
+            Field::new(
+                "signatures",
+                DataType::FixedSizeList(
+                    Arc::new(Field::new("item", DataType::UInt32, true)),
+                    num_hashes as i32,
+                ),
+                false,
+            ),
+        ]));
+        let mut signatures_file = store
+            .new_index_file(MINHASH_SIGNATURES_NAME, schema.clone())
+            .await?;
+        for (row_ids, signatures) in self
+            .row_ids
+            .chunks(WRITE_BATCH_SIZE)
+            .zip(self.signatures.chunks(WRITE_BATCH_SIZE * num_hashes))
+        {
+            let signatures = FixedSizeListArray::try_new_from_values(
+                UInt32Array::from(signatures.to_vec()),
+                num_hashes as i32,
+            )?;
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(UInt64Array::from(row_ids.to_vec())),
+                    Arc::new(signatures),
+                ],
+            )?;
+            signatures_file.write_record_batch(batch).await?;
+        }
+        signatures_file.finish().await?;
+        Ok(())
+    }
+
+    /// Load an index written by [`Self::write`]
+    pub async fn load(store: Arc<dyn IndexStore>) -> Result<Arc<Self>> {
+        let params_file = store.open_index_file(MINHASH_PARAMS_NAME).await?;
+        let params = params_file.read_record_batch(0).await?;
+        let params: MinHashParams =
+            serde_json::from_str(params.column(0).as_string::<i32>().value(0))?;
+
+        let signatures_file = store.open_index_file(MINHASH_SIGNATURES_NAME).await?;
+        let mut row_ids = Vec::new();
+        let mut signatures = Vec::new();
+        for batch_idx in 0..signatures_file.num_batches().await {
+            let batch = signatures_file.read_record_batch(batch_idx).await?;
+            row_ids.extend(batch.column(0).as_primitive::<UInt64Type>().values());
+            signatures.extend(
+                batch
+                    .column(1)
+                    .as_fixed_size_list()
+                    .values()
+                    .as_primitive::<UInt32Type>()
+                    .values(),
+            );
+        }
+        Ok(Arc::new(Self::new(params, row_ids, signatures)))
+    }
+
+    /// Remap the row ids, writing the remapped index to `dest_store`
+    pub async fn remap(
+        &self,
+        mapping: &HashMap<u64, Option<u64>>,
+        dest_store: &dyn IndexStore,
+    ) -> Result<()> {
+        let mut row_ids = Vec::with_capacity(self.row_ids.len());
+        let mut signatures = Vec::with_capacity(self.signatures.len());
+        for (position, row_id) in self.row_ids.iter().enumerate() {
+            let new_id = match mapping.get(row_id) {
+                Some(Some(new_id)) => *new_id,
+                Some(None) => continue,
+                None => *row_id,
+            };
+            row_ids.push(new_id);
+            signatures.extend_from_slice(self.signature(position));
+        }
+        Self::new(self.params.clone(), row_ids, signatures)
+            .write(dest_store)
+            .await
+    }
+
+    /// Add the rows of `new_data` to the index, writing the updated index to
+    /// `dest_store`
+    pub async fn update(
+        &self,
+        new_data: SendableRecordBatchStream,
+        dest_store: &dyn IndexStore,
+    ) -> Result<()> {
+        let mut row_ids = self.row_ids.clone();
+        let mut signatures = self.signatures.clone();
+        Self::collect_signatures(&self.params, new_data, &mut row_ids, &mut signatures).await?;
+        Self::new(self.params.clone(), row_ids, signatures)
+            .write(dest_store)
+            .await
+    }
+}
+
+#[async_trait]
+impl Index for MinHashIndex {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_index(self: Arc<Self>) -> Arc<dyn Index> {
+        self
+    }
+
+    fn index_type(&self) -> IndexType {
+        IndexType::MinHash
+    }
+
+    fn statistics(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::json!({
+            "index_type": "MinHash",
+            "num_rows": self.row_ids.len(),
+            "num_bands": self.params.num_bands,
+            "rows_per_band": self.params.rows_per_band,
+            "shingle_size": self.params.shingle_size,
+            "num_buckets": self.buckets.len(),
+        }))
+    }
+
+    async fn calculate_included_frags(&self) -> Result<RoaringBitmap> {
+        Ok(self
+            .row_ids
+            .iter()
+            .map(|row_id| RowAddress::new_from_id(*row_id).fragment_id())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::builder::{ListBuilder, StringBuilder};
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use futures::stream;
+    use lance_io::object_store::ObjectStore;
+    use tempfile::tempdir;
+
+    use crate::scalar::lance_format::LanceIndexStore;
+
+    fn text_stream(texts: Vec<Option<&str>>) -> SendableRecordBatchStream {
+        let row_ids = UInt64Array::from_iter_values(0..texts.len() as u64);
+        let batch = RecordBatch::try_from_iter(vec![
+            ("text", Arc::new(StringArray::from(texts)) as _),
+            (ROW_ID, Arc::new(row_ids) as _),
+        ])
+        .unwrap();
+        Box::pin(RecordBatchStreamAdapter::new(
+            batch.schema(),
+            stream::iter(vec![Ok(batch)]),
+        ))
+    }
+
+    #[test]
+    fn test_signatures() {
+        let params = MinHashParams::default();
+        assert!(params.validate().is_ok());
+        assert!(MinHashParams {
+            num_bands: 0,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+
+        // Case and punctuation don't matter
+        assert_eq!(
+            params.hash_text("The quick brown fox"),
+            params.hash_text("the quick, brown FOX!")
+        );
+        assert_eq!(params.hash_text("a b c d").len(), 2);
+        assert_eq!(params.hash_text("a b").len(), 1);
+        assert!(params.hash_text(" ,. ").is_empty());
+        assert_ne!(params.hash_text("ab c"), params.hash_text("a bc"));
+        assert_eq!(
+            params.hash_tokens(["a", "b", "a"]),
+            params.hash_tokens(["b", "a"])
+        );
+
+        assert_eq!(params.signature(&[]), None);
+        let numbers = (0..150).map(|i| i.to_string()).collect::<Vec<_>>();
+        let left = params
+            .signature(&params.hash_tokens(numbers[..100].iter().map(String::as_str)))
+            .unwrap();
+        let right = params
+            .signature(&params.hash_tokens(numbers[50..].iter().map(String::as_str)))
+            .unwrap();
+        assert_eq!(left.len(), params.num_hashes());
+        // The true Jaccard similarity is 1/3
+        let estimate = estimate_jaccard(&left, &right);
+        assert!((estimate - 1.0 / 3.0).abs() < 0.15, "{estimate}");
+        assert_eq!(estimate_jaccard(&left, &left), 1.0);
+
+        let mut builder = ListBuilder::new(StringBuilder::new());
+        builder.values().append_value("a");
+        builder.values().append_value("b");
+        builder.append(true);
+        builder.append(false);
+        let signatures = params.signatures(&builder.finish()).unwrap();
+        assert_eq!(
+            signatures[0],
+            params.signature(&params.hash_tokens(["b", "a"]))
+        );
+        assert_eq!(signatures[1], None);
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let texts = vec![
+            Some("the quick brown fox jumps over the lazy dog near the river bank"),
+            Some("the quick brown fox jumps over the lazy dog near the river bed"),
+            Some("lorem ipsum dolor sit amet consectetur adipiscing elit sed do"),
+            None,
+            Some("the quick brown fox jumps over the lazy dog near the river bank"),
+        ];
+        let params = MinHashParams::default();
+        let index = MinHashIndex::try_from_stream(params.clone(), text_stream(texts))
+            .await
+            .unwrap();
+        // Null rows are not indexed
+        assert_eq!(index.row_ids(), &[0, 1, 2, 4]);
+
+        let query = params
+            .signature(
+                &params
+                    .hash_text("The quick brown fox jumps over the lazy dog near the river bank."),
+            )
+            .unwrap();
+        let results = index.search(&query, 0.5);
+        assert_eq!(results[0], (0, 1.0));
+        assert_eq!(results[1], (4, 1.0));
+        assert!(results.iter().all(|(row_id, _)| *row_id != 2));
+
+        let pairs = index.similar_pairs(0.99);
+        assert_eq!(pairs, vec![(0, 4, 1.0)]);
+
+        let tempdir = tempdir().unwrap();
+        let (object_store, path) =
+            ObjectStore::from_path(tempdir.path().to_str().unwrap()).unwrap();
+        let store = Arc::new(LanceIndexStore::new(object_store, path, None));
+        index.write(store.as_ref()).await.unwrap();
+        let loaded = MinHashIndex::load(store.clone()).await.unwrap();
+        assert_eq!(loaded.params(), &params);
+        assert_eq!(loaded.row_ids(), index.row_ids());
+        assert_eq!(loaded.search(&query, 0.5), results);
+
+        let mapping = HashMap::from([(0, None), (4, Some(40))]);
+        let remapped_dir = tempdir.path().join("remapped");
+        let (object_store, path) = ObjectStore::from_path(remapped_dir.to_str().unwrap()).unwrap();
+        let remapped_store = Arc::new(LanceIndexStore::new(object_store, path, None));
+        loaded
+            .remap(&mapping, remapped_store.as_ref())
+            .await
+            .unwrap();
+        let remapped = MinHashIndex::load(remapped_store).await.unwrap();
+        assert_eq!(remapped.row_ids(), &[1, 2, 40]);
+        assert_eq!(
+            remapped.calculate_included_frags().await.unwrap(),
+            RoaringBitmap::from_iter([0])
+        );
+    }
+}
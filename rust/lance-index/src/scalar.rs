@@ -18,11 +18,42 @@ use lance_core::Result;
 
 use crate::Index;
 
+use self::composite::{parse_composite_index_key, CompositeQuery};
+
 pub mod btree;
+pub mod composite;
 pub mod expression;
 pub mod flat;
 pub mod lance_format;
 
+/// The kind of the scalar indices of the lowercase values of a column
+///
+/// They answer case-insensitive queries, such as `lower(name) = 'bob'` or
+/// `name ILIKE 'Bob%'`.  Queries refer to them by
+/// [`lowercase_index_key`], the expression they index.
+pub const LOWERCASE_INDEX_KIND: &str = "lowercase";
+
+/// The name of the lowercase index of `column` in index queries
+pub fn lowercase_index_key(column: &str) -> String {
+    format!("lower({})", column)
+}
+
+/// The column of the lowercase index named `key` in index queries, if `key`
+/// names one
+pub fn parse_lowercase_index_key(key: &str) -> Option<&str> {
+    key.strip_prefix("lower(")?.strip_suffix(')')
+}
+
+/// The expression indexed by the index named `key` in index queries
+fn index_key_expr(key: String) -> Expr {
+    match parse_lowercase_index_key(&key) {
+        Some(column) => datafusion::functions::string::expr_fn::lower(Expr::Column(
+            Column::new_unqualified(column),
+        )),
+        None => Expr::Column(Column::new_unqualified(key)),
+    }
+}
+
 /// Trait for storing an index (or parts of an index) into storage
 #[async_trait]
 pub trait IndexWriter: Send {
@@ -84,11 +115,18 @@ pub enum ScalarQuery {
     Equals(ScalarValue),
     /// Retrieve all row ids where the value is null
     IsNull(),
+    /// Retrieve all row ids matching a query of a composite index, whose
+    /// key lists its columns
+    Composite(CompositeQuery),
 }
 
 impl ScalarQuery {
     pub fn to_expr(&self, col: String) -> Expr {
-        let col_expr = Expr::Column(Column::new_unqualified(col));
+        if let Self::Composite(query) = self {
+            let columns = parse_composite_index_key(&col).unwrap_or_default();
+            return query.to_expr(&columns);
+        }
+        let col_expr = index_key_expr(col);
         match self {
             Self::Range(lower, upper) => match (lower, upper) {
                 (Bound::Unbounded, Bound::Unbounded) => {
@@ -127,6 +165,7 @@ impl ScalarQuery {
             ),
             Self::IsNull() => col_expr.is_null(),
             Self::Equals(value) => col_expr.eq(Expr::Literal(value.clone())),
+            Self::Composite(_) => unreachable!(),
         }
     }
 
@@ -168,6 +207,9 @@ impl ScalarQuery {
             Self::Equals(val) => {
                 format!("{} = {}", col, val)
             }
+            Self::Composite(query) => {
+                query.fmt_with_columns(&parse_composite_index_key(col).unwrap_or_default())
+            }
         }
     }
 }
@@ -10,8 +10,15 @@ use std::{
     sync::Arc,
 };
 
-use arrow_array::{Array, RecordBatch, UInt32Array, UInt64Array};
-use arrow_schema::{DataType, Field, Schema, SortOptions};
+use arrow_array::{
+    cast::AsArray,
+    types::{
+        DurationMicrosecondType, DurationMillisecondType, DurationNanosecondType,
+        DurationSecondType, IntervalDayTimeType, IntervalMonthDayNanoType, IntervalYearMonthType,
+    },
+    Array, ArrayRef, ArrowNativeTypeOp, ArrowPrimitiveType, RecordBatch, UInt32Array, UInt64Array,
+};
+use arrow_schema::{DataType, Field, IntervalUnit, Schema, SortOptions, TimeUnit};
 use async_trait::async_trait;
 use datafusion::physical_plan::{
     sorts::sort_preserving_merge::SortPreservingMergeExec, stream::RecordBatchStreamAdapter,
@@ -829,6 +836,10 @@ impl Index for BTreeIndex {
 #[async_trait]
 impl ScalarIndex for BTreeIndex {
     async fn search(&self, query: &ScalarQuery) -> Result<UInt64Array> {
+        if let ScalarQuery::Composite(query) = query {
+            // The keys of composite indices sort like their rows
+            return self.search(&query.key_range()?).await;
+        }
         let pages = match query {
             ScalarQuery::Equals(val) => self
                 .page_lookup
@@ -840,6 +851,7 @@ impl ScalarIndex for BTreeIndex {
                 .page_lookup
                 .pages_in(values.iter().map(|val| OrderableScalarValue(val.clone()))),
             ScalarQuery::IsNull() => self.page_lookup.pages_null(),
+            ScalarQuery::Composite(_) => unreachable!(),
         };
         let sub_index_reader = self.store.open_index_file(BTREE_PAGES_NAME).await?;
         let page_tasks = pages
@@ -948,10 +960,47 @@ fn max_val(array: &Arc<dyn Array>) -> Result<ScalarValue> {
     check_for_nan(acc.evaluate()?)
 }
 
+// The datafusion accumulators don't support durations and intervals, these are
+// ordered by their native values, as the btree orders them.
+fn primitive_min_max<T: ArrowPrimitiveType>(array: &dyn Array) -> Result<(ScalarValue, ScalarValue)>
+where
+    T::Native: ArrowNativeTypeOp,
+{
+    let values = array.as_primitive::<T>();
+    Ok((
+        ScalarValue::new_primitive::<T>(arrow::compute::min(values), array.data_type())?,
+        ScalarValue::new_primitive::<T>(arrow::compute::max(values), array.data_type())?,
+    ))
+}
+
+fn min_max(array: &Arc<dyn Array>) -> Result<(ScalarValue, ScalarValue)> {
+    match array.data_type() {
+        DataType::Duration(TimeUnit::Second) => primitive_min_max::<DurationSecondType>(array),
+        DataType::Duration(TimeUnit::Millisecond) => {
+            primitive_min_max::<DurationMillisecondType>(array)
+        }
+        DataType::Duration(TimeUnit::Microsecond) => {
+            primitive_min_max::<DurationMicrosecondType>(array)
+        }
+        DataType::Duration(TimeUnit::Nanosecond) => {
+            primitive_min_max::<DurationNanosecondType>(array)
+        }
+        DataType::Interval(IntervalUnit::YearMonth) => {
+            primitive_min_max::<IntervalYearMonthType>(array)
+        }
+        DataType::Interval(IntervalUnit::DayTime) => {
+            primitive_min_max::<IntervalDayTimeType>(array)
+        }
+        DataType::Interval(IntervalUnit::MonthDayNano) => {
+            primitive_min_max::<IntervalMonthDayNanoType>(array)
+        }
+        _ => Ok((min_val(array)?, max_val(array)?)),
+    }
+}
+
 fn analyze_batch(batch: &RecordBatch) -> Result<BatchStats> {
     let values = batch.column(0);
-    let min = min_val(values)?;
-    let max = max_val(values)?;
+    let (min, max) = min_max(values)?;
     Ok(BatchStats {
         min,
         max,
@@ -1007,9 +1056,32 @@ async fn train_btree_page(
     })
 }
 
+// ScalarValue::iter_to_array doesn't support durations
+fn scalars_to_array(values: impl Iterator<Item = ScalarValue>) -> Result<ArrayRef> {
+    let mut values = values.peekable();
+    match values.peek() {
+        Some(
+            ScalarValue::DurationSecond(_)
+            | ScalarValue::DurationMillisecond(_)
+            | ScalarValue::DurationMicrosecond(_)
+            | ScalarValue::DurationNanosecond(_),
+        ) => {
+            let arrays = values
+                .map(|value| value.to_array())
+                .collect::<datafusion_common::Result<Vec<_>>>()?;
+            let arrays = arrays
+                .iter()
+                .map(|array| array.as_ref())
+                .collect::<Vec<_>>();
+            Ok(arrow_select::concat::concat(&arrays)?)
+        }
+        _ => Ok(ScalarValue::iter_to_array(values)?),
+    }
+}
+
 fn btree_stats_as_batch(stats: Vec<EncodedBatch>) -> Result<RecordBatch> {
-    let mins = ScalarValue::iter_to_array(stats.iter().map(|stat| stat.stats.min.clone()))?;
-    let maxs = ScalarValue::iter_to_array(stats.iter().map(|stat| stat.stats.max.clone()))?;
+    let mins = scalars_to_array(stats.iter().map(|stat| stat.stats.min.clone()))?;
+    let maxs = scalars_to_array(stats.iter().map(|stat| stat.stats.max.clone()))?;
     let null_counts = UInt32Array::from_iter_values(stats.iter().map(|stat| stat.stats.null_count));
     let page_numbers = UInt32Array::from_iter_values(stats.iter().map(|stat| stat.page_number));
 
@@ -0,0 +1,491 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Composite scalar indices, over several columns
+//!
+//! A composite index on `(tenant_id, created_at)` is a btree index of keys
+//! that concatenate the values of the columns of each row, in an encoding
+//! whose byte order is the order of the tuples of values.  Rows sharing
+//! values of the leading columns are then adjacent in the index, so a filter
+//! fixing a prefix of the columns, such as `tenant_id = 5`, and optionally
+//! bounding the next one, such as `created_at > '2024-01-01'`, is a single
+//! range of keys: the filter doesn't need to intersect the results of an
+//! index per column.
+//!
+//! Queries refer to composite indices by [`composite_index_key`].
+
+use std::ops::Bound;
+
+use arrow::compute::cast;
+use arrow_array::{
+    cast::AsArray,
+    types::{Float64Type, Int64Type, UInt64Type},
+    Array, ArrayRef, BinaryArray,
+};
+use arrow_schema::DataType;
+use datafusion_common::ScalarValue;
+use datafusion_expr::Expr;
+use lance_core::{Error, Result};
+use snafu::{location, Location};
+
+use super::ScalarQuery;
+
+/// The kind of composite scalar indices
+pub const COMPOSITE_INDEX_KIND: &str = "composite";
+
+/// The name of the composite index of `columns` in index queries
+pub fn composite_index_key(columns: &[&str]) -> String {
+    format!("composite({})", columns.join(","))
+}
+
+/// The columns of the composite index named `key` in index queries, if
+/// `key` names one
+pub fn parse_composite_index_key(key: &str) -> Option<Vec<&str>> {
+    let columns = key.strip_prefix("composite(")?.strip_suffix(')')?;
+    Some(columns.split(',').collect())
+}
+
+/// Whether composite indices can include columns of `data_type`
+pub fn supports(data_type: &DataType) -> bool {
+    data_type.is_integer()
+        || data_type.is_floating()
+        || matches!(
+            data_type,
+            DataType::Boolean
+                | DataType::Utf8
+                | DataType::LargeUtf8
+                | DataType::Binary
+                | DataType::LargeBinary
+                | DataType::Date32
+                | DataType::Date64
+                | DataType::Timestamp(_, _)
+                | DataType::Time32(_)
+                | DataType::Time64(_)
+                | DataType::Duration(_)
+        )
+}
+
+const NULL_TAG: u8 = 0;
+const VALUE_TAG: u8 = 1;
+
+/// Encode the rows of `columns` into keys that sort like the rows
+///
+/// The rows are compared column by column, with nulls first.  Each value is
+/// a tag for nulls, then for other values:
+/// - integers and temporal values, as 64-bit big-endian integers, with the
+///   sign bit flipped for signed ones
+/// - floats, as 64-bit floats whose bits are flipped to sort in the total
+///   order of floats
+/// - strings and binaries, with their zero bytes escaped and a terminator,
+///   so no value is the prefix of another
+pub fn encode_composite_keys(columns: &[ArrayRef]) -> Result<BinaryArray> {
+    let num_rows = columns.first().map(|column| column.len()).unwrap_or(0);
+    let mut keys = vec![Vec::new(); num_rows];
+    for column in columns {
+        encode_column(column.as_ref(), &mut keys)?;
+    }
+    Ok(BinaryArray::from_iter_values(keys))
+}
+
+fn encode_column(column: &dyn Array, keys: &mut [Vec<u8>]) -> Result<()> {
+    let data_type = column.data_type();
+    if !supports(data_type) {
+        return Err(Error::invalid_input(
+            format!(
+                "A composite index can't include a column of type {}",
+                data_type
+            ),
+            location!(),
+        ));
+    }
+    let is_signed = data_type.is_signed_integer()
+        || matches!(
+            data_type,
+            DataType::Date32
+                | DataType::Date64
+                | DataType::Timestamp(_, _)
+                | DataType::Time32(_)
+                | DataType::Time64(_)
+                | DataType::Duration(_)
+        );
+    if is_signed {
+        // Temporal values can't be cast to integers directly
+        let column = cast(&cast_to_physical(column)?, &DataType::Int64)?;
+        let values = column.as_primitive::<Int64Type>();
+        encode_values(values.iter(), keys, |value, key| {
+            key.extend(((value as u64) ^ (1 << 63)).to_be_bytes())
+        });
+    } else if data_type.is_unsigned_integer() {
+        let column = cast(column, &DataType::UInt64)?;
+        let values = column.as_primitive::<UInt64Type>();
+        encode_values(values.iter(), keys, |value, key| {
+            key.extend(value.to_be_bytes())
+        });
+    } else if data_type.is_floating() {
+        let column = cast(column, &DataType::Float64)?;
+        let values = column.as_primitive::<Float64Type>();
+        encode_values(values.iter(), keys, |value, key| {
+            // Zeros are equal, whatever their sign
+            let bits = if value == 0.0 { 0 } else { value.to_bits() };
+            let bits = if bits >> 63 == 1 {
+                !bits
+            } else {
+                bits ^ (1 << 63)
+            };
+            key.extend(bits.to_be_bytes())
+        });
+    } else {
+        match data_type {
+            DataType::Boolean => encode_values(column.as_boolean().iter(), keys, |value, key| {
+                key.push(value as u8)
+            }),
+            DataType::Utf8 => {
+                encode_values(column.as_string::<i32>().iter(), keys, |value, key| {
+                    encode_bytes(value.as_bytes(), key)
+                })
+            }
+            DataType::LargeUtf8 => {
+                encode_values(column.as_string::<i64>().iter(), keys, |value, key| {
+                    encode_bytes(value.as_bytes(), key)
+                })
+            }
+            DataType::Binary => encode_values(column.as_binary::<i32>().iter(), keys, encode_bytes),
+            DataType::LargeBinary => {
+                encode_values(column.as_binary::<i64>().iter(), keys, encode_bytes)
+            }
+            _ => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+/// Cast temporal arrays to the integers they store
+fn cast_to_physical(column: &dyn Array) -> Result<ArrayRef> {
+    let physical_type = match column.data_type() {
+        DataType::Date32 | DataType::Time32(_) => DataType::Int32,
+        DataType::Date64
+        | DataType::Time64(_)
+        | DataType::Timestamp(_, _)
+        | DataType::Duration(_) => DataType::Int64,
+        _ => return Ok(arrow_array::make_array(column.to_data())),
+    };
+    let data = column.to_data().into_builder().data_type(physical_type);
+    // SAFETY: temporal arrays have the layout of the integers they store
+    Ok(arrow_array::make_array(unsafe { data.build_unchecked() }))
+}
+
+fn encode_values<T>(
+    values: impl Iterator<Item = Option<T>>,
+    keys: &mut [Vec<u8>],
+    encode: impl Fn(T, &mut Vec<u8>),
+) {
+    for (value, key) in values.zip(keys.iter_mut()) {
+        match value {
+            Some(value) => {
+                key.push(VALUE_TAG);
+                encode(value, key);
+            }
+            None => key.push(NULL_TAG),
+        }
+    }
+}
+
+fn encode_bytes(value: &[u8], key: &mut Vec<u8>) {
+    for byte in value {
+        key.push(*byte);
+        if *byte == 0 {
+            key.push(0xFF);
+        }
+    }
+    key.extend([0, 0]);
+}
+
+fn encode_scalars<'a>(values: impl IntoIterator<Item = &'a ScalarValue>) -> Result<Vec<u8>> {
+    let columns = values
+        .into_iter()
+        .map(|value| Ok(value.to_array()?))
+        .collect::<Result<Vec<_>>>()?;
+    if columns.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(encode_composite_keys(&columns)?.value(0).to_vec())
+}
+
+/// The smallest key greater than all the keys starting with `prefix`, if
+/// any
+fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let last = prefix.iter().rposition(|byte| *byte != 0xFF)?;
+    let mut successor = prefix[..=last].to_vec();
+    successor[last] += 1;
+    Some(successor)
+}
+
+/// A query of a composite index: the rows whose leading columns equal
+/// `prefix`, and whose next column is between `lower` and `upper`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompositeQuery {
+    /// The values of the leading columns
+    pub prefix: Vec<ScalarValue>,
+    /// The lower bound of the column after the prefix
+    pub lower: Bound<ScalarValue>,
+    /// The upper bound of the column after the prefix
+    pub upper: Bound<ScalarValue>,
+}
+
+impl CompositeQuery {
+    fn is_range(&self) -> bool {
+        !matches!(
+            (&self.lower, &self.upper),
+            (Bound::Unbounded, Bound::Unbounded)
+        )
+    }
+
+    /// The range of the keys of the rows matching the query
+    pub fn key_range(&self) -> Result<ScalarQuery> {
+        let prefix = encode_scalars(&self.prefix)?;
+        let with_next = |value: &ScalarValue| -> Result<Vec<u8>> {
+            let mut key = prefix.clone();
+            key.extend(encode_scalars([value])?);
+            Ok(key)
+        };
+        let lower = match &self.lower {
+            // Bounds exclude the nulls of the next column, which come first
+            Bound::Unbounded if self.is_range() => {
+                let mut key = prefix.clone();
+                key.push(VALUE_TAG);
+                Bound::Included(key)
+            }
+            // Without a prefix, every key matches
+            Bound::Unbounded if prefix.is_empty() => Bound::Unbounded,
+            Bound::Unbounded => Bound::Included(prefix.clone()),
+            Bound::Included(value) => Bound::Included(with_next(value)?),
+            // Values are never empty, so they have a successor
+            Bound::Excluded(value) => Bound::Included(
+                prefix_successor(&with_next(value)?).expect("keys of values have a successor"),
+            ),
+        };
+        let upper = match &self.upper {
+            Bound::Unbounded => prefix_successor(&prefix).map_or(Bound::Unbounded, Bound::Excluded),
+            Bound::Included(value) => {
+                prefix_successor(&with_next(value)?).map_or(Bound::Unbounded, Bound::Excluded)
+            }
+            Bound::Excluded(value) => Bound::Excluded(with_next(value)?),
+        };
+        let binary = |bound: Bound<Vec<u8>>| match bound {
+            Bound::Included(key) => Bound::Included(ScalarValue::Binary(Some(key))),
+            Bound::Excluded(key) => Bound::Excluded(ScalarValue::Binary(Some(key))),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        Ok(ScalarQuery::Range(binary(lower), binary(upper)))
+    }
+
+    /// The filter on `columns` matching the rows of the query
+    pub fn to_expr(&self, columns: &[&str]) -> Expr {
+        let mut expr = self
+            .prefix
+            .iter()
+            .zip(columns)
+            .map(|(value, column)| ScalarQuery::Equals(value.clone()).to_expr(column.to_string()))
+            .reduce(Expr::and);
+        if self.is_range() {
+            let range = ScalarQuery::Range(self.lower.clone(), self.upper.clone())
+                .to_expr(columns[self.prefix.len()].to_string());
+            expr = Some(match expr {
+                Some(expr) => expr.and(range),
+                None => range,
+            });
+        }
+        expr.unwrap_or(Expr::Literal(ScalarValue::Boolean(Some(true))))
+    }
+
+    pub fn fmt_with_columns(&self, columns: &[&str]) -> String {
+        let mut conditions = self
+            .prefix
+            .iter()
+            .zip(columns)
+            .map(|(value, column)| ScalarQuery::Equals(value.clone()).fmt_with_col(column))
+            .collect::<Vec<_>>();
+        if self.is_range() {
+            conditions.push(
+                ScalarQuery::Range(self.lower.clone(), self.upper.clone())
+                    .fmt_with_col(columns[self.prefix.len()]),
+            );
+        }
+        conditions.join(" && ")
+    }
+}
+
+/// The columns of a composite index, see
+/// [`IndexInformationProvider::composite_indices`](super::expression::IndexInformationProvider::composite_indices)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompositeIndexColumns {
+    /// The name of the index in index queries, see [`composite_index_key`]
+    pub key: String,
+    /// The names and types of the columns, in the order of the index
+    pub columns: Vec<(String, DataType)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Float32Array, Int32Array, StringArray, TimestampMillisecondArray};
+
+    use super::*;
+
+    #[test]
+    fn test_encode_composite_keys() {
+        let ints: ArrayRef = Arc::new(Int32Array::from(vec![
+            Some(1),
+            Some(-5),
+            None,
+            Some(1),
+            Some(i32::MAX),
+            Some(1),
+        ]));
+        let strings: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("b"),
+            Some("z"),
+            Some("a"),
+            Some("a\0"),
+            None,
+            Some("a"),
+        ]));
+        let keys = encode_composite_keys(&[ints, strings]).unwrap();
+        let mut order = (0..keys.len()).collect::<Vec<_>>();
+        order.sort_by_key(|i| keys.value(*i));
+        // (null, a), (-5, z), (1, a), (1, a\0), (1, b), (MAX, null)
+        assert_eq!(order, vec![2, 1, 5, 3, 0, 4]);
+
+        let floats: ArrayRef = Arc::new(Float32Array::from(vec![
+            1.5,
+            -0.0,
+            -2.0,
+            0.0,
+            f32::NEG_INFINITY,
+        ]));
+        let keys = encode_composite_keys(&[floats]).unwrap();
+        let mut order = (0..keys.len()).collect::<Vec<_>>();
+        order.sort_by_key(|i| (keys.value(*i), *i));
+        assert_eq!(order, vec![4, 2, 1, 3, 0]);
+        assert_eq!(keys.value(1), keys.value(3));
+
+        let timestamps: ArrayRef = Arc::new(TimestampMillisecondArray::from(vec![10, -10]));
+        let keys = encode_composite_keys(&[timestamps]).unwrap();
+        assert!(keys.value(1) < keys.value(0));
+
+        let lists: ArrayRef = Arc::new(arrow_array::ListArray::new_null(
+            Arc::new(arrow_schema::Field::new("item", DataType::Int32, true)),
+            1,
+        ));
+        assert!(encode_composite_keys(&[lists]).is_err());
+    }
+
+    #[test]
+    fn test_key_range() {
+        let ints: ArrayRef = Arc::new(Int32Array::from(vec![
+            Some(1),
+            Some(1),
+            Some(1),
+            Some(1),
+            Some(2),
+            Some(0),
+        ]));
+        let strings: ArrayRef = Arc::new(StringArray::from(vec![
+            Some("a"),
+            Some("b"),
+            Some("c"),
+            None,
+            Some("a"),
+            Some("b"),
+        ]));
+        let keys = encode_composite_keys(&[ints, strings]).unwrap();
+        let matches = |query: CompositeQuery| {
+            let ScalarQuery::Range(lower, upper) = query.key_range().unwrap() else {
+                panic!("Expected a range query");
+            };
+            let key = |bound: &Bound<ScalarValue>| match bound {
+                Bound::Included(ScalarValue::Binary(Some(key)))
+                | Bound::Excluded(ScalarValue::Binary(Some(key))) => key.clone(),
+                _ => Vec::new(),
+            };
+            (0..keys.len())
+                .filter(|i| {
+                    let value = keys.value(*i);
+                    let above = match &lower {
+                        Bound::Included(_) => value >= key(&lower).as_slice(),
+                        Bound::Excluded(_) => value > key(&lower).as_slice(),
+                        Bound::Unbounded => true,
+                    };
+                    let below = match &upper {
+                        Bound::Included(_) => value <= key(&upper).as_slice(),
+                        Bound::Excluded(_) => value < key(&upper).as_slice(),
+                        Bound::Unbounded => true,
+                    };
+                    above && below
+                })
+                .collect::<Vec<_>>()
+        };
+        let int = |value: i32| ScalarValue::Int32(Some(value));
+        let string = |value: &str| ScalarValue::Utf8(Some(value.to_string()));
+
+        let query = |prefix, lower, upper| CompositeQuery {
+            prefix,
+            lower,
+            upper,
+        };
+        // The prefix alone includes the nulls of the next column
+        assert_eq!(
+            matches(query(vec![int(1)], Bound::Unbounded, Bound::Unbounded)),
+            vec![0, 1, 2, 3]
+        );
+        assert_eq!(
+            matches(query(
+                vec![int(1), string("b")],
+                Bound::Unbounded,
+                Bound::Unbounded
+            )),
+            vec![1]
+        );
+        assert_eq!(
+            matches(query(
+                vec![int(1)],
+                Bound::Excluded(string("a")),
+                Bound::Unbounded
+            )),
+            vec![1, 2]
+        );
+        assert_eq!(
+            matches(query(
+                vec![int(1)],
+                Bound::Unbounded,
+                Bound::Included(string("b"))
+            )),
+            vec![0, 1]
+        );
+        assert_eq!(
+            matches(query(
+                vec![int(1)],
+                Bound::Included(string("b")),
+                Bound::Excluded(string("c"))
+            )),
+            vec![1]
+        );
+        // Without a prefix, the range is on the first column
+        assert_eq!(
+            matches(query(vec![], Bound::Included(int(1)), Bound::Unbounded)),
+            vec![0, 1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_composite_index_key() {
+        let key = composite_index_key(&["tenant_id", "created_at"]);
+        assert_eq!(key, "composite(tenant_id,created_at)");
+        assert_eq!(
+            parse_composite_index_key(&key),
+            Some(vec!["tenant_id", "created_at"])
+        );
+        assert_eq!(parse_composite_index_key("tenant_id"), None);
+    }
+}
@@ -7,7 +7,11 @@ use arrow_schema::DataType;
 use async_recursion::async_recursion;
 use async_trait::async_trait;
 use datafusion_common::ScalarValue;
-use datafusion_expr::{expr::InList, Between, BinaryExpr, Expr, Operator};
+use datafusion_expr::{
+    expr::{InList, ScalarFunction},
+    utils::{conjunction, split_conjunction},
+    Between, BinaryExpr, Expr, Like, Operator,
+};
 
 use futures::join;
 use lance_core::{
@@ -17,7 +21,13 @@ use lance_core::{
 use lance_datafusion::expr::safe_coerce_scalar;
 use tracing::instrument;
 
-use super::{ScalarIndex, ScalarQuery};
+use super::{
+    composite::{CompositeIndexColumns, CompositeQuery},
+    lowercase_index_key, ScalarIndex, ScalarQuery,
+};
+
+/// The [`ScalarIndexExpr::estimated_cost`] of queries that match few rows, such as equalities
+const SELECTIVE_COST: u32 = 1;
 
 /// An indexed expression consists of a scalar index query with a post-scan filter
 ///
@@ -129,32 +139,29 @@ impl IndexedExpression {
         // the database and so we short-circuit and return None
         let scalar_query = self.scalar_query?;
         let other_scalar_query = other.scalar_query?;
+
+        // If either side has a refine expression, then the union of the index queries is a
+        // superset of the matching rows.  For example, with "(color == 'blue' AND size < 20) OR
+        // (color == 'green' AND size < 50)", we load the rows matching "color == 'blue' OR
+        // color == 'green'", and refine them with the whole expression, which we rebuild from
+        // the index queries.
+        let refine_expr = if self.refine_expr.is_none() && other.refine_expr.is_none() {
+            None
+        } else {
+            let side_expr =
+                |scalar_query: &ScalarIndexExpr, refine_expr: Option<Expr>| match refine_expr {
+                    Some(refine_expr) => scalar_query.to_expr().and(refine_expr),
+                    None => scalar_query.to_expr(),
+                };
+            Some(
+                side_expr(&scalar_query, self.refine_expr)
+                    .or(side_expr(&other_scalar_query, other.refine_expr)),
+            )
+        };
         let scalar_query = Some(ScalarIndexExpr::Or(
             Box::new(scalar_query),
             Box::new(other_scalar_query),
         ));
-
-        let refine_expr = match (self.refine_expr, other.refine_expr) {
-            // TODO
-            //
-            // To handle these cases we need a way of going back from a scalar expression query to a logical DF expression (perhaps
-            // we can store the expression that led to the creation of the query)
-            //
-            // For example, imagine we have something like "(color == 'blue' AND size < 20) OR (color == 'green' AND size < 50)"
-            //
-            // We can do an indexed load of all rows matching "color == 'blue' OR color == 'green'" but then we need to
-            // refine that load with the full original expression which, at the moment, we no longer have.
-            (Some(_), Some(_)) => {
-                return None;
-            }
-            (Some(_), None) => {
-                return None;
-            }
-            (None, Some(_)) => {
-                return None;
-            }
-            (None, None) => None,
-        };
         Some(Self {
             scalar_query,
             refine_expr,
@@ -222,7 +229,22 @@ impl ScalarIndexExpr {
                 let result = inner.evaluate(index_loader).await?;
                 Ok(!result)
             }
+            // Conjunctions are ordered by cost, see `order_by_cost`.  If the cheapest side
+            // matches few rows, it is searched first, and the other side is skipped if no row
+            // is left.  Otherwise both sides are searched at the same time.
             Self::And(lhs, rhs) => {
+                if lhs.estimated_cost() <= SELECTIVE_COST {
+                    let lhs_result = lhs.evaluate(index_loader).await?;
+                    if lhs_result
+                        .allow_list
+                        .as_ref()
+                        .is_some_and(|allow_list| allow_list.is_empty())
+                    {
+                        return Ok(lhs_result);
+                    }
+                    let rhs_result = rhs.evaluate(index_loader).await?;
+                    return Ok(lhs_result & rhs_result);
+                }
                 let lhs_result = lhs.evaluate(index_loader);
                 let rhs_result = rhs.evaluate(index_loader);
                 let (lhs_result, rhs_result) = join!(lhs_result, rhs_result);
@@ -246,6 +268,87 @@ impl ScalarIndexExpr {
         }
     }
 
+    /// An estimate of the cost of the expression, relative to other expressions
+    ///
+    /// The cost grows with the number of rows the queries are expected to match, and so with
+    /// the number of pages they read: an equality matches few rows, a range or a negation
+    /// many.  This is a ranking of the kinds of queries, not a cost model: no index is read to
+    /// estimate it, and the values queried are not considered.
+    pub fn estimated_cost(&self) -> u32 {
+        match self {
+            Self::Not(inner) => 8 + inner.estimated_cost(),
+            // The intersection is at most as large as its smallest input
+            Self::And(lhs, rhs) => lhs.estimated_cost().min(rhs.estimated_cost()),
+            Self::Or(lhs, rhs) => lhs.estimated_cost() + rhs.estimated_cost(),
+            Self::Query(_, query) => match query {
+                ScalarQuery::Equals(_) => 1,
+                ScalarQuery::IsNull() => 2,
+                ScalarQuery::IsIn(values) => (values.len() as u32).clamp(1, 8),
+                ScalarQuery::Range(Bound::Unbounded, _)
+                | ScalarQuery::Range(_, Bound::Unbounded) => 6,
+                ScalarQuery::Range(_, _) => 4,
+                ScalarQuery::Composite(query) => {
+                    let range = Self::Query(
+                        String::new(),
+                        ScalarQuery::Range(query.lower.clone(), query.upper.clone()),
+                    );
+                    match (query.prefix.len(), &query.lower, &query.upper) {
+                        (0, _, _) => range.estimated_cost(),
+                        (_, Bound::Unbounded, Bound::Unbounded) => 1,
+                        // The prefix already narrows the range
+                        _ => range.estimated_cost().min(2),
+                    }
+                }
+            },
+        }
+    }
+
+    /// Order the operands of conjunctions and disjunctions by estimated cost, cheapest first
+    ///
+    /// Conjunctions are then evaluated cheapest first, and stop once no row is left.
+    pub fn order_by_cost(self) -> Self {
+        match self {
+            Self::Not(inner) => Self::Not(Box::new(inner.order_by_cost())),
+            Self::And(_, _) | Self::Or(_, _) => {
+                let is_and = matches!(self, Self::And(_, _));
+                let mut operands = Vec::new();
+                self.flatten_into(is_and, &mut operands);
+                let mut operands = operands
+                    .into_iter()
+                    .map(Self::order_by_cost)
+                    .collect::<Vec<_>>();
+                // The sort is stable, so operands of the same cost keep their order
+                operands.sort_by_key(Self::estimated_cost);
+                operands
+                    .into_iter()
+                    .reduce(|lhs, rhs| {
+                        if is_and {
+                            Self::And(Box::new(lhs), Box::new(rhs))
+                        } else {
+                            Self::Or(Box::new(lhs), Box::new(rhs))
+                        }
+                    })
+                    .expect("Operators have operands")
+            }
+            Self::Query(_, _) => self,
+        }
+    }
+
+    // Collect the operands of a chain of conjunctions, or of disjunctions
+    fn flatten_into(self, is_and: bool, operands: &mut Vec<Self>) {
+        match self {
+            Self::And(lhs, rhs) if is_and => {
+                lhs.flatten_into(is_and, operands);
+                rhs.flatten_into(is_and, operands);
+            }
+            Self::Or(lhs, rhs) if !is_and => {
+                lhs.flatten_into(is_and, operands);
+                rhs.flatten_into(is_and, operands);
+            }
+            _ => operands.push(self),
+        }
+    }
+
     pub fn to_expr(&self) -> Expr {
         match self {
             Self::Not(inner) => Expr::Not(inner.to_expr().into()),
@@ -272,13 +375,26 @@ fn maybe_column(expr: &Expr) -> Option<&str> {
     }
 }
 
+// Extract the name of the index of the expression, if it is a column or the lowercase of a
+// column, or None
+fn maybe_index_key(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::ScalarFunction(ScalarFunction { func_def, args })
+            if func_def.name() == "lower" && args.len() == 1 =>
+        {
+            maybe_column(&args[0]).map(lowercase_index_key)
+        }
+        _ => maybe_column(expr).map(str::to_string),
+    }
+}
+
 // Extract a column from the expression, if it is a column, and we have an index for that column, or None
-fn maybe_indexed_column<'a, 'b>(
-    expr: &'a Expr,
+fn maybe_indexed_column<'b>(
+    expr: &Expr,
     index_info: &'b dyn IndexInformationProvider,
-) -> Option<(&'a str, &'b DataType)> {
-    let col = maybe_column(expr)?;
-    let data_type = index_info.get_index(col);
+) -> Option<(String, &'b DataType)> {
+    let col = maybe_index_key(expr)?;
+    let data_type = index_info.get_index(&col);
     data_type.map(|ty| (col, ty))
 }
 
@@ -315,7 +431,7 @@ fn visit_between(
     let high = maybe_scalar(&between.high, col_type)?;
 
     let query = ScalarQuery::Range(Bound::Included(low.clone()), Bound::Included(high.clone()));
-    let indexed_expr = IndexedExpression::index_query(column.to_string(), query);
+    let indexed_expr = IndexedExpression::index_query(column, query);
     if between.negated {
         indexed_expr.maybe_not()
     } else {
@@ -331,7 +447,7 @@ fn visit_in_list(
     let values = maybe_scalar_list(&in_list.list, col_type)?;
 
     let query = ScalarQuery::IsIn(values);
-    let indexed_expr = IndexedExpression::index_query(column.to_string(), query);
+    let indexed_expr = IndexedExpression::index_query(column, query);
     if in_list.negated {
         indexed_expr.maybe_not()
     } else {
@@ -349,7 +465,7 @@ fn visit_is_bool(
         None
     } else {
         Some(IndexedExpression::index_query(
-            column.to_string(),
+            column,
             ScalarQuery::Equals(ScalarValue::Boolean(Some(value))),
         ))
     }
@@ -365,7 +481,7 @@ fn visit_column(
         None
     } else {
         Some(IndexedExpression::index_query(
-            column.to_string(),
+            column,
             ScalarQuery::Equals(ScalarValue::Boolean(Some(true))),
         ))
     }
@@ -377,7 +493,7 @@ fn visit_is_null(
     negated: bool,
 ) -> Option<IndexedExpression> {
     let (column, _) = maybe_indexed_column(expr, index_info)?;
-    let indexed_expr = IndexedExpression::index_query(column.to_string(), ScalarQuery::IsNull());
+    let indexed_expr = IndexedExpression::index_query(column, ScalarQuery::IsNull());
     if negated {
         indexed_expr.maybe_not()
     } else {
@@ -385,6 +501,75 @@ fn visit_is_null(
     }
 }
 
+// The smallest string greater than all the strings starting with `prefix`, if any
+fn prefix_upper_bound(prefix: &str) -> Option<String> {
+    let mut chars = prefix.chars().collect::<Vec<_>>();
+    while let Some(last) = chars.pop() {
+        // The next char, skipping the surrogates, which aren't chars
+        if let Some(next) = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32) {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+// The query for the values matching the LIKE pattern `pattern`, if it is a plain string or
+// a prefix followed by '%'
+fn like_query(pattern: &str, col_type: &DataType) -> Option<ScalarQuery> {
+    let scalar = |value: &str| match col_type {
+        DataType::LargeUtf8 => ScalarValue::LargeUtf8(Some(value.to_string())),
+        _ => ScalarValue::Utf8(Some(value.to_string())),
+    };
+    // Backslashes escape the wildcards
+    if pattern.contains('\\') {
+        return None;
+    }
+    match pattern.find(['%', '_']) {
+        None => Some(ScalarQuery::Equals(scalar(pattern))),
+        Some(pos) if pos > 0 && &pattern[pos..] == "%" => {
+            let prefix = &pattern[..pos];
+            let upper = match prefix_upper_bound(prefix) {
+                Some(upper) => Bound::Excluded(scalar(&upper)),
+                None => Bound::Unbounded,
+            };
+            Some(ScalarQuery::Range(Bound::Included(scalar(prefix)), upper))
+        }
+        _ => None,
+    }
+}
+
+// Case-insensitive patterns can only use the lowercase index of the column, which they are
+// lowercased to match.
+fn visit_like(like: &Like, index_info: &dyn IndexInformationProvider) -> Option<IndexedExpression> {
+    if like.escape_char.is_some() {
+        return None;
+    }
+    let (column, col_type) = if like.case_insensitive {
+        let key = lowercase_index_key(maybe_column(&like.expr)?);
+        let col_type = index_info.get_index(&key)?;
+        (key, col_type)
+    } else {
+        maybe_indexed_column(&like.expr, index_info)?
+    };
+    let (ScalarValue::Utf8(Some(pattern)) | ScalarValue::LargeUtf8(Some(pattern))) =
+        maybe_scalar(&like.pattern, col_type)?
+    else {
+        return None;
+    };
+    let pattern = if like.case_insensitive {
+        pattern.to_lowercase()
+    } else {
+        pattern
+    };
+    let indexed_expr = IndexedExpression::index_query(column, like_query(&pattern, col_type)?);
+    if like.negated {
+        indexed_expr.maybe_not()
+    } else {
+        Some(indexed_expr)
+    }
+}
+
 fn visit_not(expr: &Expr, index_info: &dyn IndexInformationProvider) -> Option<IndexedExpression> {
     let node = visit_node(expr, index_info)?;
     node.maybe_not()
@@ -411,14 +596,14 @@ fn visit_comparison(
     if let Some((column, col_type)) = left_col {
         let scalar = maybe_scalar(&expr.right, col_type)?;
         Some(IndexedExpression::index_query(
-            column.to_string(),
+            column,
             visit_comparison_normalized(scalar, &expr.op),
         ))
     } else {
         let (column, col_type) = maybe_indexed_column(&expr.right, index_info)?;
         let scalar = maybe_scalar(&expr.left, col_type)?;
         Some(IndexedExpression::index_query(
-            column.to_string(),
+            column,
             visit_comparison_normalized(scalar, &expr.op),
         ))
     }
@@ -481,6 +666,7 @@ fn visit_node(expr: &Expr, index_info: &dyn IndexInformationProvider) -> Option<
         Expr::IsFalse(expr) => visit_is_bool(expr.as_ref(), index_info, false),
         Expr::IsTrue(expr) => visit_is_bool(expr.as_ref(), index_info, true),
         Expr::IsNull(expr) => visit_is_null(expr.as_ref(), index_info, false),
+        Expr::Like(like) => visit_like(like, index_info),
         Expr::IsNotNull(expr) => visit_is_null(expr.as_ref(), index_info, true),
         Expr::Not(expr) => visit_not(expr.as_ref(), index_info),
         Expr::BinaryExpr(binary_expr) => visit_binary_expr(binary_expr, index_info),
@@ -488,19 +674,179 @@ fn visit_node(expr: &Expr, index_info: &dyn IndexInformationProvider) -> Option<
     }
 }
 
+// A condition on a column of a composite index
+enum CompositeCondition {
+    Equals(ScalarValue),
+    Range(Bound<ScalarValue>, Bound<ScalarValue>),
+}
+
+// Extract the condition of the expression on `column`, if it compares `column` to a value, or None
+fn composite_condition(
+    expr: &Expr,
+    column: &str,
+    col_type: &DataType,
+) -> Option<CompositeCondition> {
+    // Comparisons to null match no row, while the index has keys for nulls
+    let non_null = |expr: &Expr| maybe_scalar(expr, col_type).filter(|value| !value.is_null());
+    match expr {
+        Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+            let (op, value) = if maybe_column(left) == Some(column) {
+                (*op, non_null(right)?)
+            } else if maybe_column(right) == Some(column) {
+                (op.swap()?, non_null(left)?)
+            } else {
+                return None;
+            };
+            match op {
+                Operator::Lt | Operator::LtEq | Operator::Gt | Operator::GtEq | Operator::Eq => {
+                    match visit_comparison_normalized(value, &op) {
+                        ScalarQuery::Equals(value) => Some(CompositeCondition::Equals(value)),
+                        ScalarQuery::Range(lower, upper) => {
+                            Some(CompositeCondition::Range(lower, upper))
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+                _ => None,
+            }
+        }
+        Expr::Between(between)
+            if !between.negated && maybe_column(&between.expr) == Some(column) =>
+        {
+            Some(CompositeCondition::Range(
+                Bound::Included(non_null(&between.low)?),
+                Bound::Included(non_null(&between.high)?),
+            ))
+        }
+        _ => None,
+    }
+}
+
+// Build the query of the composite index answering the most conjuncts: equalities on a prefix of
+// its columns, and bounds on the next column.  Returns the query and the positions of the
+// conjuncts it answers, or None if it answers none.
+fn match_composite_index(
+    index: &CompositeIndexColumns,
+    conjuncts: &[&Expr],
+) -> Option<(CompositeQuery, Vec<usize>)> {
+    let mut used = Vec::new();
+    let mut prefix = Vec::new();
+    let (mut lower, mut upper) = (Bound::Unbounded, Bound::Unbounded);
+    for (column, col_type) in &index.columns {
+        let conditions = conjuncts
+            .iter()
+            .enumerate()
+            .filter(|(pos, _)| !used.contains(pos))
+            .filter_map(|(pos, conjunct)| {
+                Some((pos, composite_condition(conjunct, column, col_type)?))
+            })
+            .collect::<Vec<_>>();
+        let equality = conditions
+            .iter()
+            .find_map(|(pos, condition)| match condition {
+                CompositeCondition::Equals(value) => Some((*pos, value.clone())),
+                _ => None,
+            });
+        if let Some((pos, value)) = equality {
+            used.push(pos);
+            prefix.push(value);
+            continue;
+        }
+        // The rows of the prefix are sorted by this column, so it can still be bounded, but the
+        // columns after it can't
+        for (pos, condition) in conditions {
+            if let CompositeCondition::Range(range_lower, range_upper) = condition {
+                let fits = |bound: &Bound<ScalarValue>, current: &Bound<ScalarValue>| {
+                    matches!(bound, Bound::Unbounded) || matches!(current, Bound::Unbounded)
+                };
+                if fits(&range_lower, &lower) && fits(&range_upper, &upper) {
+                    if !matches!(range_lower, Bound::Unbounded) {
+                        lower = range_lower;
+                    }
+                    if !matches!(range_upper, Bound::Unbounded) {
+                        upper = range_upper;
+                    }
+                    used.push(pos);
+                }
+            }
+        }
+        break;
+    }
+    if used.is_empty() {
+        return None;
+    }
+    Some((
+        CompositeQuery {
+            prefix,
+            lower,
+            upper,
+        },
+        used,
+    ))
+}
+
+// Answer the conjuncts of the expression with a composite index, if one answers several of them,
+// or answers conditions on a column without an index of its own.  The other conjuncts are
+// applied to the other indices.
+fn visit_composite(
+    expr: &Expr,
+    index_info: &dyn IndexInformationProvider,
+) -> Option<IndexedExpression> {
+    let conjuncts = split_conjunction(expr);
+    let (index, query, used) = index_info
+        .composite_indices()
+        .iter()
+        .filter_map(|index| {
+            let (query, used) = match_composite_index(index, &conjuncts)?;
+            Some((index, query, used))
+        })
+        .filter(|(index, _, used)| {
+            used.len() > 1 || index_info.get_index(&index.columns[0].0).is_none()
+        })
+        .max_by_key(|(_, _, used)| used.len())?;
+    let indexed_expr =
+        IndexedExpression::index_query(index.key.clone(), ScalarQuery::Composite(query));
+    let rest = conjunction(
+        conjuncts
+            .iter()
+            .enumerate()
+            .filter(|(pos, _)| !used.contains(pos))
+            .map(|(_, conjunct)| (*conjunct).clone()),
+    );
+    Some(match rest {
+        Some(rest) => indexed_expr.and(apply_scalar_indices(rest, index_info)),
+        None => indexed_expr,
+    })
+}
+
 /// A trait to be used in `apply_scalar_indices` to inform the function which columns are indexeds
 pub trait IndexInformationProvider {
     /// Check if an index exists for `col` and, if so, return the data type of col
     fn get_index(&self, col: &str) -> Option<&DataType>;
+
+    /// The composite indices, which index several columns together
+    fn composite_indices(&self) -> &[CompositeIndexColumns] {
+        &[]
+    }
 }
 
 /// Attempt to split a filter expression into a search of scalar indexes and an
 ///   optional post-search refinement query
+///
+/// Conjunctions answered by a composite index are searched in that index rather than in the
+/// index of each column.  Other conjunctions and disjunctions of indexed predicates intersect and
+/// unite the results of their indices, searched in the order of [`ScalarIndexExpr::order_by_cost`].
 pub fn apply_scalar_indices(
     expr: Expr,
     index_info: &dyn IndexInformationProvider,
 ) -> IndexedExpression {
-    visit_node(&expr, index_info).unwrap_or(IndexedExpression::refine_only(expr))
+    let mut indexed_expr = visit_composite(&expr, index_info)
+        .or_else(|| visit_node(&expr, index_info))
+        .unwrap_or(IndexedExpression::refine_only(expr));
+    indexed_expr.scalar_query = indexed_expr
+        .scalar_query
+        .map(ScalarIndexExpr::order_by_cost);
+    indexed_expr
 }
 
 #[cfg(test)]
@@ -515,10 +861,12 @@ mod tests {
     use datafusion_sql::planner::{ContextProvider, PlannerContext, SqlToRel};
     use datafusion_sql::sqlparser::{dialect::PostgreSqlDialect, parser::Parser};
 
+    use super::super::composite::composite_index_key;
     use super::*;
 
     struct MockIndexInfoProvider {
         indexed_columns: HashMap<String, DataType>,
+        composite_indices: Vec<CompositeIndexColumns>,
     }
 
     impl MockIndexInfoProvider {
@@ -529,14 +877,31 @@ mod tests {
                         .into_iter()
                         .map(|(s, ty)| (s.to_string(), ty)),
                 ),
+                composite_indices: Vec::new(),
             }
         }
+
+        fn with_composite(mut self, columns: Vec<(&str, DataType)>) -> Self {
+            let names = columns.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+            self.composite_indices.push(CompositeIndexColumns {
+                key: composite_index_key(&names),
+                columns: columns
+                    .into_iter()
+                    .map(|(name, ty)| (name.to_string(), ty))
+                    .collect(),
+            });
+            self
+        }
     }
 
     impl IndexInformationProvider for MockIndexInfoProvider {
         fn get_index(&self, col: &str) -> Option<&DataType> {
             self.indexed_columns.get(col)
         }
+
+        fn composite_indices(&self) -> &[CompositeIndexColumns] {
+            &self.composite_indices
+        }
     }
 
     struct MockContextProvider {}
@@ -547,8 +912,11 @@ mod tests {
             todo!()
         }
 
-        fn get_function_meta(&self, _: &str) -> Option<std::sync::Arc<ScalarUDF>> {
-            todo!()
+        fn get_function_meta(&self, name: &str) -> Option<std::sync::Arc<ScalarUDF>> {
+            match name {
+                "lower" => Some(datafusion::functions::string::lower()),
+                _ => todo!(),
+            }
         }
 
         fn get_aggregate_meta(&self, _: &str) -> Option<std::sync::Arc<AggregateUDF>> {
@@ -646,6 +1014,69 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_like_expressions() {
+        let index_info = MockIndexInfoProvider::new(vec![
+            ("color", DataType::Utf8),
+            ("lower(color)", DataType::Utf8),
+        ]);
+        let utf8 = |value: &str| ScalarValue::Utf8(Some(value.to_string()));
+
+        check_simple(
+            &index_info,
+            "color LIKE 'blue'",
+            "color",
+            ScalarQuery::Equals(utf8("blue")),
+        );
+        check_simple(
+            &index_info,
+            "color LIKE 'bl%'",
+            "color",
+            ScalarQuery::Range(Bound::Included(utf8("bl")), Bound::Excluded(utf8("bm"))),
+        );
+        check_simple_negated(
+            &index_info,
+            "color NOT LIKE 'blue'",
+            "color",
+            ScalarQuery::Equals(utf8("blue")),
+        );
+        check_simple(
+            &index_info,
+            "lower(color) = 'blue'",
+            "lower(color)",
+            ScalarQuery::Equals(utf8("blue")),
+        );
+        check_simple(
+            &index_info,
+            "lower(color) LIKE 'bl%'",
+            "lower(color)",
+            ScalarQuery::Range(Bound::Included(utf8("bl")), Bound::Excluded(utf8("bm"))),
+        );
+        check_no_index(&index_info, "color LIKE '%ue'");
+        check_no_index(&index_info, "color LIKE 'b_ue'");
+        check_simple(
+            &index_info,
+            "color ILIKE 'BLue'",
+            "lower(color)",
+            ScalarQuery::Equals(utf8("blue")),
+        );
+        check_simple_negated(
+            &index_info,
+            "color NOT ILIKE 'Bl%'",
+            "lower(color)",
+            ScalarQuery::Range(Bound::Included(utf8("bl")), Bound::Excluded(utf8("bm"))),
+        );
+        check_no_index(&index_info, "color ILIKE 'b_ue'");
+        // Without a lowercase index, ILIKE is not answered by an index of the values
+        let values_only = MockIndexInfoProvider::new(vec![("color", DataType::Utf8)]);
+        check_no_index(&values_only, "color ILIKE 'blue'");
+        check_no_index(&index_info, "lower(size) = 'blue'");
+
+        assert_eq!(prefix_upper_bound("a\u{10FFFF}"), Some("b".to_string()));
+        assert_eq!(prefix_upper_bound("\u{D7FF}"), Some("\u{E000}".to_string()));
+        assert_eq!(prefix_upper_bound("\u{10FFFF}"), None);
+    }
+
     #[test]
     fn test_expressions() {
         let index_info = MockIndexInfoProvider::new(vec![
@@ -835,15 +1266,197 @@ mod tests {
                 refine_expr: Some(refine.clone()),
             }),
         );
-        // Examples of things that are not yet supported but should be supportable someday
-
-        // OR'd group of refined index searches (see IndexedExpression::or for details)
-        check_no_index(
+        // OR'd group of refined index searches load the union of the index searches, and
+        // refine it with the whole expression (see IndexedExpression::or for details)
+        let size = || Expr::Column(Column::new_unqualified("size"));
+        check(
             &index_info,
             "(aisle = 10 AND size > 30) OR (color = 'blue' AND size > 20)",
+            Some(IndexedExpression {
+                scalar_query: Some(ScalarIndexExpr::Or(left.clone(), right.clone())),
+                refine_expr: Some(
+                    left.to_expr()
+                        .and(size().gt(datafusion_expr::lit(30_i64)))
+                        .or(right.to_expr().and(size().gt(datafusion_expr::lit(20_i64)))),
+                ),
+            }),
         );
 
+        // Examples of things that are not yet supported but should be supportable someday
+
         // Non-normalized arithmetic (can use expression simplification)
         check_no_index(&index_info, "aisle + 3 < 10")
     }
+
+    #[test]
+    fn test_composite_expressions() {
+        let index_info = MockIndexInfoProvider::new(vec![
+            ("color", DataType::Utf8),
+            ("aisle", DataType::UInt32),
+        ])
+        .with_composite(vec![("color", DataType::Utf8), ("aisle", DataType::UInt32)])
+        .with_composite(vec![
+            ("size", DataType::Float32),
+            ("aisle", DataType::UInt32),
+        ]);
+        let key = "composite(color,aisle)";
+        let blue = || ScalarValue::Utf8(Some("blue".to_string()));
+        let aisle = |value: u32| ScalarValue::UInt32(Some(value));
+        let composite = |prefix, lower, upper| {
+            ScalarQuery::Composite(CompositeQuery {
+                prefix,
+                lower,
+                upper,
+            })
+        };
+
+        check_simple(
+            &index_info,
+            "color = 'blue' AND aisle > 10",
+            key,
+            composite(vec![blue()], Bound::Excluded(aisle(10)), Bound::Unbounded),
+        );
+        // The conjuncts can be in any order, and compare the value to the column
+        check_simple(
+            &index_info,
+            "10 < aisle AND aisle <= 20 AND color = 'blue'",
+            key,
+            composite(
+                vec![blue()],
+                Bound::Excluded(aisle(10)),
+                Bound::Included(aisle(20)),
+            ),
+        );
+        check_simple(
+            &index_info,
+            "color = 'blue' AND aisle BETWEEN 5 AND 10",
+            key,
+            composite(
+                vec![blue()],
+                Bound::Included(aisle(5)),
+                Bound::Included(aisle(10)),
+            ),
+        );
+        check_simple(
+            &index_info,
+            "aisle = 10 AND color = 'blue'",
+            key,
+            composite(vec![blue(), aisle(10)], Bound::Unbounded, Bound::Unbounded),
+        );
+        // The other conjuncts are refined
+        let refine =
+            Expr::Column(Column::new_unqualified("price")).gt(datafusion_expr::lit(30_i64));
+        check(
+            &index_info,
+            "color = 'blue' AND price > 30 AND aisle = 10",
+            Some(IndexedExpression {
+                scalar_query: Some(ScalarIndexExpr::Query(
+                    key.to_string(),
+                    composite(vec![blue(), aisle(10)], Bound::Unbounded, Bound::Unbounded),
+                )),
+                refine_expr: Some(refine),
+            }),
+        );
+        // A single condition on a column with its own index uses that index
+        check_simple(
+            &index_info,
+            "color = 'blue'",
+            "color",
+            ScalarQuery::Equals(blue()),
+        );
+        check_simple(
+            &index_info,
+            "aisle = 10",
+            "aisle",
+            ScalarQuery::Equals(aisle(10)),
+        );
+        // but a column without one uses the composite index it leads
+        check_simple(
+            &index_info,
+            "size > 30",
+            "composite(size,aisle)",
+            composite(
+                vec![],
+                Bound::Excluded(ScalarValue::Float32(Some(30.0))),
+                Bound::Unbounded,
+            ),
+        );
+        check_no_index(&index_info, "color = 'blue' OR price > 30");
+        assert_eq!(
+            composite(vec![blue()], Bound::Excluded(aisle(10)), Bound::Unbounded)
+                .to_expr(key.to_string()),
+            Expr::Column(Column::new_unqualified("color"))
+                .eq(datafusion_expr::lit("blue"))
+                .and(
+                    Expr::Column(Column::new_unqualified("aisle")).gt(datafusion_expr::lit(10_u32))
+                )
+        );
+    }
+
+    #[test]
+    fn test_order_by_cost() {
+        let index_info = MockIndexInfoProvider::new(vec![
+            ("color", DataType::Utf8),
+            ("aisle", DataType::UInt32),
+            ("price", DataType::Float32),
+        ]);
+        let query = |column: &str, query: ScalarQuery| {
+            Box::new(ScalarIndexExpr::Query(column.to_string(), query))
+        };
+        let price = query(
+            "price",
+            ScalarQuery::Range(
+                Bound::Excluded(ScalarValue::Float32(Some(10.0))),
+                Bound::Unbounded,
+            ),
+        );
+        let aisle = query(
+            "aisle",
+            ScalarQuery::Range(
+                Bound::Included(ScalarValue::UInt32(Some(5))),
+                Bound::Included(ScalarValue::UInt32(Some(10))),
+            ),
+        );
+        let color = query(
+            "color",
+            ScalarQuery::Equals(ScalarValue::Utf8(Some("blue".to_string()))),
+        );
+        // The most selective predicates are searched first
+        check(
+            &index_info,
+            "price > 10 AND aisle BETWEEN 5 AND 10 AND color = 'blue'",
+            Some(IndexedExpression {
+                scalar_query: Some(ScalarIndexExpr::And(
+                    Box::new(ScalarIndexExpr::And(color.clone(), aisle.clone())),
+                    price.clone(),
+                )),
+                refine_expr: None,
+            }),
+        );
+        // and negations last
+        check(
+            &index_info,
+            "color <> 'blue' AND price > 10",
+            Some(IndexedExpression {
+                scalar_query: Some(ScalarIndexExpr::And(
+                    price.clone(),
+                    Box::new(ScalarIndexExpr::Not(color.clone())),
+                )),
+                refine_expr: None,
+            }),
+        );
+        check(
+            &index_info,
+            "(price > 10 OR color = 'blue') AND aisle BETWEEN 5 AND 10",
+            Some(IndexedExpression {
+                scalar_query: Some(ScalarIndexExpr::And(
+                    aisle.clone(),
+                    Box::new(ScalarIndexExpr::Or(color.clone(), price.clone())),
+                )),
+                refine_expr: None,
+            }),
+        );
+        assert_eq!(color.estimated_cost(), 1);
+        assert!(aisle.estimated_cost() < price.estimated_cost());
+    }
 }
@@ -183,6 +183,10 @@ impl Index for FlatIndex {
 #[async_trait]
 impl ScalarIndex for FlatIndex {
     async fn search(&self, query: &ScalarQuery) -> Result<UInt64Array> {
+        if let ScalarQuery::Composite(query) = query {
+            // The keys of composite indices sort like their rows
+            return self.search(&query.key_range()?).await;
+        }
         // Since we have all the values in memory we can use basic arrow-rs compute
         // functions to satisfy scalar queries.
         let predicate = match query {
@@ -240,6 +244,7 @@ impl ScalarIndex for FlatIndex {
                     &arrow_ord::cmp::lt(self.values(), &upper.to_scalar()?)?,
                 )?,
             },
+            ScalarQuery::Composite(_) => unreachable!(),
         };
         Ok(arrow_select::filter::filter(self.ids(), &predicate)?
             .as_any()
@@ -174,7 +174,7 @@ mod tests {
         types::{Float32Type, Int32Type, UInt64Type},
         RecordBatchIterator, RecordBatchReader, UInt64Array,
     };
-    use arrow_schema::{DataType, Field, TimeUnit};
+    use arrow_schema::{DataType, Field, IntervalUnit, TimeUnit};
     use arrow_select::take::TakeOptions;
     use datafusion::physical_plan::SendableRecordBatchStream;
     use datafusion_common::ScalarValue;
@@ -535,9 +535,9 @@ mod tests {
             DataType::Date32,
             DataType::Time64(TimeUnit::Nanosecond),
             DataType::Time32(TimeUnit::Second),
-            // Not supported today, error from datafusion:
-            // Min/max accumulator not implemented for Duration(Nanosecond)
-            // DataType::Duration(TimeUnit::Nanosecond),
+            DataType::Duration(TimeUnit::Nanosecond),
+            DataType::Interval(IntervalUnit::YearMonth),
+            DataType::Interval(IntervalUnit::MonthDayNano),
         ] {
             let tempdir = tempdir().unwrap();
             let index_store = test_store(&tempdir);
@@ -33,6 +33,13 @@ pub trait DatasetIndexExt {
         replace: bool,
     ) -> Result<()>;
 
+    /// Drop the index named `name`, with all its deltas.
+    ///
+    /// Upon finish, a new dataset version is generated. The index files are
+    /// not read, so this also works for indices that fail to load. To rebuild
+    /// such an index, call [`Self::create_index`] with `replace` set instead.
+    async fn drop_index(&mut self, name: &str) -> Result<()>;
+
     /// Read all indices of this Dataset version.
     ///
     /// The indices are lazy loaded and cached in memory within the [`Dataset`] instance.
@@ -4,8 +4,14 @@
 //! Vector Index
 //!
 
+use std::fmt::Display;
+use std::str::FromStr;
+
 use arrow_array::ArrayRef;
+use lance_core::datatypes::Field;
+use lance_core::{Error, Result};
 use lance_linalg::distance::DistanceType;
+use snafu::{location, Location};
 
 pub mod bq;
 pub mod flat;
@@ -28,6 +34,9 @@ pub const PART_ID_COLUMN: &str = "__ivf_part_id";
 pub const INDEX_UUID_COLUMN: &str = "__index_uuid";
 pub const DIST_COL: &str = "_distance";
 
+/// The metadata key of a vector column that sets its [`NullVectorHandling`].
+pub const NULL_VECTORS_KEY: &str = "lance:null_vectors";
+
 use super::pb;
 pub use residual::RESIDUAL_COLUMN;
 
@@ -46,6 +55,13 @@ pub struct Query {
     /// The number of probes to load and search.
     pub nprobes: usize,
 
+    /// If presented, keep probing the closest partitions, past `nprobes`,
+    /// until they hold this fraction of the indexed rows.
+    ///
+    /// The number of probes then adapts to the size of the partitions
+    /// around the query vector.
+    pub probe_fraction: Option<f32>,
+
     /// The number of candidates to reserve while searching.
     /// this is an optional parameter for HNSW related index types.
     pub ef: Option<usize>,
@@ -61,6 +77,71 @@ pub struct Query {
     pub use_index: bool,
 }
 
+/// How vector indices and searches treat the rows of a vector column whose
+/// vector is null, or has NaN or infinite values.
+///
+/// It is set per column, with the [`NULL_VECTORS_KEY`] metadata of the field,
+/// which overrides the default of the dataset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NullVectorHandling {
+    /// The rows are not indexed, and are not returned by vector searches.
+    #[default]
+    Skip,
+    /// Building an index fails if there are such rows, and so does searching
+    /// with a query vector that is not finite.
+    Error,
+    /// Rows with a null vector form a partition that is searched last: vector
+    /// searches return them after all other rows, with a null distance. Rows
+    /// with NaN or infinite values are skipped.
+    NullPartition,
+}
+
+impl NullVectorHandling {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Skip => "skip",
+            Self::Error => "error",
+            Self::NullPartition => "null_partition",
+        }
+    }
+
+    /// The handling configured in the metadata of a vector field, or
+    /// `default` if the field doesn't set one.
+    pub fn from_field(field: &Field, default: Self) -> Result<Self> {
+        field
+            .metadata
+            .get(NULL_VECTORS_KEY)
+            .map(|value| value.parse())
+            .transpose()
+            .map(|handling| handling.unwrap_or(default))
+    }
+}
+
+impl Display for NullVectorHandling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for NullVectorHandling {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(Self::Skip),
+            "error" => Ok(Self::Error),
+            "null_partition" => Ok(Self::NullPartition),
+            _ => Err(Error::invalid_input(
+                format!(
+                    "Unknown null vector handling: {}, expected skip, error or null_partition",
+                    s
+                ),
+                location!(),
+            )),
+        }
+    }
+}
+
 impl From<pb::VectorMetricType> for DistanceType {
     fn from(proto: pb::VectorMetricType) -> Self {
         match proto {
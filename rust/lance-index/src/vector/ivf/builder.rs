@@ -38,13 +38,26 @@ pub struct IvfBuildParams {
     /// mutually exclusive with `precomputed_partitons_file`
     /// requires `centroids` to be set
     ///
-    /// The input is expected to be (/dir/to/buffers, [buffer1.lance, buffer2.lance, ...])
+    /// The input is expected to be (/dir/to/buffers, [buffer1.lance, buffer2.lance, ...]),
+    /// with buffers written by [`super::shuffler::ShuffleShardWriter`].
     pub precomputed_shuffle_buffers: Option<(Path, Vec<String>)>,
 
     pub shuffle_partition_batches: usize,
 
     pub shuffle_partition_concurrency: usize,
 
+    /// Bytes of memory the shuffle may use to sort rows by partition.
+    ///
+    /// Rows are sorted in runs that fit the budget, which are written to disk
+    /// and merged while the partitions are written. If set, this overrides
+    /// `shuffle_partition_batches`.
+    ///
+    /// The budget only covers sorting the runs. Merging them reads ahead up
+    /// to 4 batches of each run, so a smaller budget, which makes more runs,
+    /// makes the merge use more memory. Writing the partitions also holds all
+    /// the rows of one partition at a time, whatever the budget.
+    pub memory_budget: Option<usize>,
+
     /// Use residual vectors to build sub-vector.
     pub use_residual: bool,
 }
@@ -60,6 +73,7 @@ impl Default for IvfBuildParams {
             precomputed_shuffle_buffers: None,
             shuffle_partition_batches: 1024 * 10,
             shuffle_partition_concurrency: 2,
+            memory_budget: None,
             use_residual: true,
         }
     }
@@ -10,17 +10,36 @@
 //! Problems for the future:
 //! 1. while groupby column will stay the same, we may want to include extra data columns in the future
 //! 2. shuffling into memory is fast but we should add disk buffer to support bigger datasets
+//!
+//! ## Shuffle shards
+//!
+//! The unsorted input of the shuffle is a set of shards, which external
+//! workers can write to build an index in a distributed way. A shard is a
+//! Lance file (legacy format) on the local file system with the columns of
+//! [`shard_schema`]:
+//!
+//! * `_rowid` (u64): the row id of the vector
+//! * `__ivf_part_id` (u32, no nulls): its IVF partition
+//! * `__pq_code` (fixed size list of `num_sub_vectors` u8): its PQ code
+//!
+//! [`ShuffleShardWriter`] writes shards. All the shards of an index go in one
+//! directory, where [`IvfShuffler::merge_shards`] writes the runs sorted by
+//! partition as `sorted_{n}.lance`, so the shards must not use these names.
+//! To finalize the index, pass the directory and the shard names to
+//! `IvfBuildParams::precomputed_shuffle_buffers`, along with the centroids
+//! and PQ codebook the shards were computed with.
 
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use arrow::compute::sort_to_indices;
+use arrow::compute::{cast, sort_to_indices};
 use arrow_array::{cast::AsArray, types::UInt64Type, Array, RecordBatch, UInt32Array};
-use arrow_schema::Field;
+use arrow_schema::{DataType, Field, Schema as ArrowSchema, SchemaRef};
 use futures::stream::repeat_with;
 use futures::{stream, Stream, StreamExt, TryStreamExt};
 use lance_arrow::RecordBatchExt;
-use lance_core::{datatypes::Schema, Error, Result, ROW_ID};
+use lance_core::utils::temp::temp_files;
+use lance_core::{datatypes::Schema, Error, Result, ROW_ID, ROW_ID_FIELD};
 use lance_file::reader::FileReader;
 use lance_file::writer::FileWriter;
 use lance_io::object_store::ObjectStore;
@@ -35,12 +54,110 @@ use tempfile::TempDir;
 
 use crate::vector::ivf::Ivf;
 use crate::vector::transform::{KeepFiniteVectors, Transformer};
-use crate::vector::PART_ID_COLUMN;
+use crate::vector::{PART_ID_COLUMN, PQ_CODE_COLUMN};
 
 const UNSORTED_BUFFER: &str = "unsorted.lance";
 
-fn get_temp_dir() -> Result<Path> {
-    let dir = TempDir::new()?;
+/// Number of batches of each sorted run read ahead while merging the runs
+const MERGE_READAHEAD: usize = 4;
+
+/// The bytes of a value of `data_type`, assuming 8 for variable width types
+fn fixed_width(data_type: &DataType) -> usize {
+    match data_type {
+        DataType::FixedSizeList(item, len) => *len as usize * fixed_width(item.data_type()),
+        data_type => data_type.primitive_width().unwrap_or(8),
+    }
+}
+
+/// The schema of shuffle shards with PQ codes of `num_sub_vectors` bytes
+pub fn shard_schema(num_sub_vectors: usize) -> SchemaRef {
+    Arc::new(ArrowSchema::new(vec![
+        ROW_ID_FIELD.clone(),
+        Field::new(PART_ID_COLUMN, DataType::UInt32, false),
+        Field::new(
+            PQ_CODE_COLUMN,
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::UInt8, true)),
+                num_sub_vectors as i32,
+            ),
+            false,
+        ),
+    ]))
+}
+
+/// Writes a shuffle shard, see the [module documentation](self).
+pub struct ShuffleShardWriter {
+    writer: FileWriter<ManifestDescribing>,
+    schema: SchemaRef,
+    file_name: String,
+}
+
+impl ShuffleShardWriter {
+    /// Create the shard `file_name` in the local directory `dir`.
+    pub async fn try_new(dir: &Path, file_name: &str, num_sub_vectors: usize) -> Result<Self> {
+        let object_store = ObjectStore::local();
+        let writer = object_store.create(&dir.child(file_name)).await?;
+        let schema = shard_schema(num_sub_vectors);
+        let writer = FileWriter::<ManifestDescribing>::with_object_writer(
+            writer,
+            Schema::try_from(schema.as_ref())?,
+            &Default::default(),
+        )?;
+        Ok(Self {
+            writer,
+            schema,
+            file_name: file_name.to_string(),
+        })
+    }
+
+    /// Write the rows of `batch`, which must have the columns of
+    /// [`shard_schema`]. Other columns are ignored.
+    pub async fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        let columns = self
+            .schema
+            .fields()
+            .iter()
+            .map(|field| {
+                let column = batch.column_by_name(field.name()).ok_or_else(|| {
+                    Error::invalid_input(
+                        format!("Shuffle shards must have a {} column", field.name()),
+                        location!(),
+                    )
+                })?;
+                let valid_type = match (column.data_type(), field.data_type()) {
+                    (DataType::FixedSizeList(item, len), DataType::FixedSizeList(_, expected)) => {
+                        item.data_type() == &DataType::UInt8 && len == expected
+                    }
+                    (data_type, expected) => data_type == expected,
+                };
+                if !valid_type || column.null_count() > 0 {
+                    return Err(Error::invalid_input(
+                        format!(
+                            "The {} column of shuffle shards must be of type {} without nulls, \
+                            but is {} with {} nulls",
+                            field.name(),
+                            field.data_type(),
+                            column.data_type(),
+                            column.null_count()
+                        ),
+                        location!(),
+                    ));
+                }
+                Ok(cast(column, field.data_type())?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        self.writer.write(&[batch]).await
+    }
+
+    /// Finish the shard, and return its file name.
+    pub async fn finish(mut self) -> Result<String> {
+        self.writer.finish().await?;
+        Ok(self.file_name)
+    }
+}
+
+fn temp_dir_path(dir: &TempDir) -> Result<Path> {
     let tmp_dir_path = Path::from_filesystem_path(dir.path()).map_err(|e| Error::IO {
         source: Box::new(e),
         location: location!(),
@@ -58,6 +175,9 @@ fn get_temp_dir() -> Result<Path> {
 ///   *ivf*: IVF model.
 ///   *num_partitions*: number of IVF partitions.
 ///   *num_sub_vectors*: number of PQ sub-vectors.
+///   *memory_budget*: if set, the bytes of memory the sorted runs may use,
+///   instead of sorting `shuffle_partition_batches` batches per run. Merging
+///   the runs reads ahead 4 batches of each, outside of the budget.
 ///
 /// Returns
 /// -------
@@ -76,15 +196,11 @@ pub async fn shuffle_dataset(
     shuffle_partition_batches: usize,
     shuffle_partition_concurrency: usize,
     precomputed_shuffle_buffers: Option<(Path, Vec<String>)>,
+    memory_budget: Option<usize>,
 ) -> Result<Vec<impl Stream<Item = Result<RecordBatch>>>> {
     // step 1: either use precomputed shuffle files or write shuffle data to a file
     let shuffler = if let Some((path, buffers)) = precomputed_shuffle_buffers {
-        let mut shuffler = IvfShuffler::try_new(num_partitions, Some(path))?;
-        unsafe {
-            shuffler.set_unsorted_buffers(&buffers);
-        }
-
-        shuffler
+        IvfShuffler::try_from_shards(num_partitions, path, &buffers).await?
     } else {
         let mut shuffler = IvfShuffler::try_new(num_partitions, None)?;
 
@@ -116,13 +232,6 @@ pub async fn shuffle_dataset(
                                 .iter()
                                 .map(|row_id| partition_map.get(row_id).copied()),
                         );
-                        let part_ids = UInt32Array::from(part_ids);
-                        batch = batch
-                            .try_with_column(
-                                Field::new(PART_ID_COLUMN, part_ids.data_type().clone(), true),
-                                Arc::new(part_ids.clone()),
-                            )
-                            .expect("failed to add part id column");
 
                         if part_ids.null_count() > 0 {
                             info!(
@@ -138,6 +247,13 @@ pub async fn shuffle_dataset(
                             assert_eq!(indices.len(), batch.num_rows() - part_ids.null_count());
                             batch = batch.take(&indices)?;
                         }
+                        let part_ids = UInt32Array::from_iter_values(part_ids.iter().flatten());
+                        batch = batch
+                            .try_with_column(
+                                Field::new(PART_ID_COLUMN, DataType::UInt32, false),
+                                Arc::new(part_ids),
+                            )
+                            .expect("failed to add part id column");
                     }
 
                     // Filter out NaNs/Infs
@@ -161,16 +277,20 @@ pub async fn shuffle_dataset(
         shuffler
     };
 
-    // step 2: stream in the shuffle data in chunks and write sorted chuncks out
+    // step 2: write the shuffle data out in sorted chunks, and load them back.
+    // Consumers are expected to be responsible for merging the streams
     let start = std::time::Instant::now();
-    let partition_files = shuffler
-        .write_partitioned_shuffles(shuffle_partition_batches, shuffle_partition_concurrency)
+    let batches_per_run = match memory_budget {
+        Some(memory_budget) => {
+            shuffler
+                .batches_per_run(memory_budget, shuffle_partition_concurrency)
+                .await?
+        }
+        None => shuffle_partition_batches,
+    };
+    let stream = shuffler
+        .merge_shards(batches_per_run, shuffle_partition_concurrency)
         .await?;
-    info!("counted partition sizes in {:?}", start.elapsed());
-
-    // step 3: load the sorted chuncks, consumers are expect to be responsible for merging the streams
-    let start = std::time::Instant::now();
-    let stream = shuffler.load_partitioned_shuffles(partition_files).await?;
     info!("merged partitioned shuffles in {:?}", start.elapsed());
 
     Ok(stream)
@@ -182,6 +302,10 @@ pub struct IvfShuffler {
     num_partitions: u32,
 
     output_dir: Path,
+
+    /// The temporary directory of `output_dir`, if it is one, which is
+    /// deleted once the shuffler and the streams it returned are dropped
+    temp_dir: Option<Arc<TempDir>>,
 }
 
 /// Represents a range of batches in a file that should be shuffled
@@ -196,14 +320,18 @@ struct ShuffleInput {
 
 impl IvfShuffler {
     pub fn try_new(num_partitions: u32, output_dir: Option<Path>) -> Result<Self> {
-        let output_dir = match output_dir {
-            Some(output_dir) => output_dir,
-            None => get_temp_dir()?,
+        let (output_dir, temp_dir) = match output_dir {
+            Some(output_dir) => (output_dir, None),
+            None => {
+                let temp_dir = temp_files().new_dir()?;
+                (temp_dir_path(&temp_dir)?, Some(Arc::new(temp_dir)))
+            }
         };
 
         Ok(Self {
             num_partitions,
             output_dir,
+            temp_dir,
             unsorted_buffers: vec![],
         })
     }
@@ -217,6 +345,50 @@ impl IvfShuffler {
         self.unsorted_buffers = unsorted_buffers.iter().map(|x| x.to_string()).collect();
     }
 
+    /// Create a shuffler of the shards in the local directory `dir`, written
+    /// by [`ShuffleShardWriter`] or in the same format.
+    ///
+    /// The shards must have the row id, partition id and PQ code columns.
+    pub async fn try_from_shards(
+        num_partitions: u32,
+        dir: Path,
+        shards: &[impl ToString],
+    ) -> Result<Self> {
+        let object_store = ObjectStore::local();
+        for shard in shards {
+            let path = dir.child(shard.to_string());
+            let reader = FileReader::try_new_self_described(&object_store, &path, None).await?;
+            for column in [ROW_ID, PART_ID_COLUMN, PQ_CODE_COLUMN] {
+                if reader.schema().field(column).is_none() {
+                    return Err(Error::corrupt_file(
+                        path,
+                        format!("Shuffle shard has no {column} column"),
+                        location!(),
+                    ));
+                }
+            }
+        }
+        let mut shuffler = Self::try_new(num_partitions, Some(dir))?;
+        shuffler.unsorted_buffers = shards.iter().map(|x| x.to_string()).collect();
+        Ok(shuffler)
+    }
+
+    /// Sort the rows of the shards by partition.
+    ///
+    /// The rows are sorted in runs of `batches_per_run` batches, which are
+    /// written to the shuffle directory. Returns a stream per run, sorted by
+    /// partition, that consumers merge.
+    pub async fn merge_shards(
+        &self,
+        batches_per_run: usize,
+        concurrent_jobs: usize,
+    ) -> Result<Vec<impl Stream<Item = Result<RecordBatch>>>> {
+        let runs = self
+            .write_partitioned_shuffles(batches_per_run, concurrent_jobs)
+            .await?;
+        self.load_partitioned_shuffles(runs).await
+    }
+
     pub async fn write_unsorted_stream(
         &mut self,
         data: impl Stream<Item = Result<RecordBatch>>,
@@ -265,6 +437,38 @@ impl IvfShuffler {
         Ok(())
     }
 
+    /// The number of batches per sorted run, so that `concurrent_jobs` runs
+    /// sorted at once use about `memory_budget` bytes.
+    ///
+    /// Each run is held in memory about twice while it is sorted. Runs have at
+    /// least one batch, whatever the budget. The memory used to merge the
+    /// runs, by [`Self::load_partitioned_shuffles`], is not part of the
+    /// budget.
+    pub async fn batches_per_run(
+        &self,
+        memory_budget: usize,
+        concurrent_jobs: usize,
+    ) -> Result<usize> {
+        let object_store = ObjectStore::local();
+        let mut num_rows = 0;
+        let mut num_batches = 0;
+        let mut row_width = 0;
+        for buffer in &self.unsorted_buffers {
+            let path = self.output_dir.child(buffer.as_str());
+            let reader = FileReader::try_new_self_described(&object_store, &path, None).await?;
+            num_rows += reader.len();
+            num_batches += reader.num_batches();
+            row_width = reader
+                .schema()
+                .fields
+                .iter()
+                .map(|field| fixed_width(&field.data_type()))
+                .sum();
+        }
+        let batch_bytes = (num_rows.div_ceil(num_batches.max(1)) * row_width).max(1);
+        Ok((memory_budget / (2 * concurrent_jobs.max(1) * batch_bytes)).max(1))
+    }
+
     async fn total_batches(&self) -> Result<Vec<usize>> {
         let mut total_batches = vec![];
         for buffer in &self.unsorted_buffers {
@@ -301,9 +505,18 @@ impl IvfShuffler {
             while let Some(batch) = stream.next().await {
                 let batch = batch?;
                 let part_ids: &UInt32Array = batch.column(0).as_primitive();
-                part_ids.values().iter().for_each(|part_id| {
-                    partition_sizes[*part_id as usize] += 1;
-                });
+                for part_id in part_ids.values() {
+                    let size = partition_sizes.get_mut(*part_id as usize).ok_or_else(|| {
+                        Error::invalid_input(
+                            format!(
+                                "Partition {} is out of range, there are {} partitions",
+                                part_id, self.num_partitions
+                            ),
+                            location!(),
+                        )
+                    })?;
+                    *size += 1;
+                }
             }
         }
 
@@ -478,13 +691,13 @@ impl IvfShuffler {
             let reader = Arc::new(reader);
 
             let stream = stream::iter(0..reader.num_batches())
-                .zip(stream::repeat(reader))
-                .map(|(i, reader)| async move {
+                .zip(stream::repeat((reader, self.temp_dir.clone())))
+                .map(|(i, (reader, _))| async move {
                     reader
                         .read_batch(i as i32, ReadBatchParams::RangeFull, reader.schema(), None)
                         .await
                 })
-                .buffered(4);
+                .buffered(MERGE_READAHEAD);
             streams.push(stream);
         }
 
@@ -498,19 +711,15 @@ mod test {
         types::{UInt32Type, UInt8Type},
         FixedSizeListArray, UInt64Array, UInt8Array,
     };
-    use arrow_schema::DataType;
     use lance_arrow::FixedSizeListArrayExt;
-    use lance_core::ROW_ID_FIELD;
     use lance_io::stream::RecordBatchStreamAdapter;
 
-    use crate::vector::PQ_CODE_COLUMN;
-
     use super::*;
 
     fn make_schema() -> Arc<arrow_schema::Schema> {
         Arc::new(arrow_schema::Schema::new(vec![
             ROW_ID_FIELD.clone(),
-            arrow_schema::Field::new(PART_ID_COLUMN, DataType::UInt32, true),
+            arrow_schema::Field::new(PART_ID_COLUMN, DataType::UInt32, false),
             arrow_schema::Field::new(
                 PQ_CODE_COLUMN,
                 DataType::FixedSizeList(
@@ -736,4 +945,122 @@ mod test {
 
         assert_eq!(num_batches, 200);
     }
+
+    fn shard_batch(row_ids: std::ops::Range<u64>, part_id: impl Fn(u64) -> u32) -> RecordBatch {
+        let part_ids = UInt32Array::from_iter_values(row_ids.clone().map(part_id));
+        let codes = UInt8Array::from_iter_values(row_ids.clone().flat_map(|id| [id as u8; 32]));
+        RecordBatch::try_new(
+            shard_schema(32),
+            vec![
+                Arc::new(UInt64Array::from_iter_values(row_ids)),
+                Arc::new(part_ids),
+                Arc::new(FixedSizeListArray::try_new_from_values(codes, 32).unwrap()),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_merge_shards() {
+        let temp_dir = temp_files().new_dir().unwrap();
+        let dir = temp_dir_path(&temp_dir).unwrap();
+        // Two workers write shards of rows of all partitions
+        let mut shards = vec![];
+        for (worker, rows) in [(0, 0..600), (1, 600..1000)] {
+            let mut writer =
+                ShuffleShardWriter::try_new(&dir, &format!("shard_{worker}.lance"), 32)
+                    .await
+                    .unwrap();
+            writer
+                .write(&shard_batch(rows, |id| (id % 4) as u32))
+                .await
+                .unwrap();
+            shards.push(writer.finish().await.unwrap());
+        }
+
+        let shuffler = IvfShuffler::try_from_shards(4, dir.clone(), &shards)
+            .await
+            .unwrap();
+        let runs = shuffler.merge_shards(1, 2).await.unwrap();
+        assert_eq!(runs.len(), 2);
+        let mut num_rows = 0;
+        for run in runs {
+            let batches = run.try_collect::<Vec<_>>().await.unwrap();
+            let mut last_part_id = 0;
+            for batch in batches {
+                let part_id_field = batch.schema().field_with_name(PART_ID_COLUMN).cloned();
+                assert!(!part_id_field.unwrap().is_nullable());
+                let part_ids = batch[PART_ID_COLUMN].as_primitive::<UInt32Type>();
+                let row_ids = batch[ROW_ID].as_primitive::<UInt64Type>();
+                for (part_id, row_id) in part_ids.values().iter().zip(row_ids.values()) {
+                    assert!(*part_id >= last_part_id);
+                    assert_eq!(*part_id as u64, row_id % 4);
+                    last_part_id = *part_id;
+                }
+                num_rows += batch.num_rows();
+            }
+        }
+        assert_eq!(num_rows, 1000);
+
+        // Partitions are checked when the shards are merged
+        let shuffler = IvfShuffler::try_from_shards(2, dir.clone(), &shards)
+            .await
+            .unwrap();
+        assert!(shuffler.merge_shards(1, 2).await.is_err());
+        // Shards must exist and have the shuffle columns
+        assert!(
+            IvfShuffler::try_from_shards(4, dir.clone(), &["missing.lance"])
+                .await
+                .is_err()
+        );
+
+        let mut writer = ShuffleShardWriter::try_new(&dir, "invalid.lance", 32)
+            .await
+            .unwrap();
+        let batch = shard_batch(0..10, |_| 0);
+        let without_codes = batch.project(&[0, 1]).unwrap();
+        assert!(writer.write(&without_codes).await.is_err());
+        let short_codes = RecordBatch::try_new(
+            shard_schema(8),
+            vec![
+                batch.column(0).clone(),
+                batch.column(1).clone(),
+                Arc::new(
+                    FixedSizeListArray::try_new_from_values(
+                        UInt8Array::from_iter_values(std::iter::repeat(0).take(80)),
+                        8,
+                    )
+                    .unwrap(),
+                ),
+            ],
+        )
+        .unwrap();
+        assert!(writer.write(&short_codes).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_batches_per_run() {
+        let (stream, mut shuffler) = make_stream_and_shuffler(false);
+        shuffler.write_unsorted_stream(stream).await.unwrap();
+
+        // Batches have 1024 rows of 8 + 4 + 32 bytes
+        let batch_bytes = 1024 * 44;
+        assert_eq!(
+            shuffler.batches_per_run(batch_bytes * 20, 2).await.unwrap(),
+            5
+        );
+        assert_eq!(shuffler.batches_per_run(0, 2).await.unwrap(), 1);
+
+        // Runs within the budget sort the same rows
+        let batches_per_run = shuffler.batches_per_run(batch_bytes * 40, 1).await.unwrap();
+        let runs = shuffler.merge_shards(batches_per_run, 1).await.unwrap();
+        assert_eq!(runs.len(), 5);
+        let mut num_rows = 0;
+        for run in runs {
+            for batch in run.try_collect::<Vec<_>>().await.unwrap() {
+                num_rows += batch.num_rows();
+            }
+        }
+        assert_eq!(num_rows, 100 * 1024);
+    }
 }
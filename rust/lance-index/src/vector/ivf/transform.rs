@@ -88,7 +88,7 @@ impl Transformer for IvfTransformer {
             })?;
 
         let part_ids = self.compute_partitions(fsl);
-        let field = Field::new(PART_ID_COLUMN, part_ids.data_type().clone(), true);
+        let field = Field::new(PART_ID_COLUMN, part_ids.data_type().clone(), false);
         Ok(batch.try_with_column(field, Arc::new(part_ids))?)
     }
 }
@@ -8,7 +8,9 @@ use std::fmt::Debug;
 use std::sync::Arc;
 
 use arrow_array::types::{Float16Type, Float32Type, Float64Type};
-use arrow_array::{cast::AsArray, Array, ArrowPrimitiveType, RecordBatch, UInt32Array};
+use arrow_array::{
+    cast::AsArray, Array, ArrowPrimitiveType, FixedSizeListArray, RecordBatch, UInt32Array,
+};
 use arrow_schema::{DataType, Field};
 use lance_arrow::RecordBatchExt;
 use num_traits::Float;
@@ -106,6 +108,29 @@ where
         .any(|&v| !v.is_finite())
 }
 
+/// Returns the indices of the vectors that are not null, and only have finite
+/// values.
+pub fn finite_vector_indices(data: &FixedSizeListArray) -> Vec<u32> {
+    data.iter()
+        .enumerate()
+        .filter_map(|(idx, arr)| {
+            arr.and_then(|data| {
+                let is_valid = match data.data_type() {
+                    DataType::Float16 => is_all_finite::<Float16Type>(&data),
+                    DataType::Float32 => is_all_finite::<Float32Type>(&data),
+                    DataType::Float64 => is_all_finite::<Float64Type>(&data),
+                    _ => false,
+                };
+                if is_valid {
+                    Some(idx as u32)
+                } else {
+                    None
+                }
+            })
+        })
+        .collect()
+}
+
 impl Transformer for KeepFiniteVectors {
     fn transform(&self, batch: &RecordBatch) -> Result<RecordBatch> {
         let arr = batch.column_by_name(&self.column).ok_or(Error::Index {
@@ -124,25 +149,7 @@ impl Transformer for KeepFiniteVectors {
             location: location!(),
         })?;
 
-        let valid = data
-            .iter()
-            .enumerate()
-            .filter_map(|(idx, arr)| {
-                arr.and_then(|data| {
-                    let is_valid = match data.data_type() {
-                        DataType::Float16 => is_all_finite::<Float16Type>(&data),
-                        DataType::Float32 => is_all_finite::<Float32Type>(&data),
-                        DataType::Float64 => is_all_finite::<Float64Type>(&data),
-                        _ => false,
-                    };
-                    if is_valid {
-                        Some(idx as u32)
-                    } else {
-                        None
-                    }
-                })
-            })
-            .collect::<Vec<_>>();
+        let valid = finite_vector_indices(data);
         if valid.len() < batch.num_rows() {
             let indices = UInt32Array::from(valid);
             Ok(batch.take(&indices)?)
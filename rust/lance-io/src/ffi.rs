@@ -1,11 +1,16 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
+//! Adaptors for consumers that are not async, such as FFI and C bindings.
+
 use arrow::ffi_stream::FFI_ArrowArrayStream;
 use arrow_array::RecordBatch;
 use arrow_schema::{ArrowError, SchemaRef};
 use futures::StreamExt;
 use lance_core::Result;
+use tokio::runtime::{Builder, Handle, Runtime};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 
 use crate::stream::RecordBatchStream;
 
@@ -58,3 +63,188 @@ pub fn to_ffi_arrow_array_stream(
 
     Ok(reader)
 }
+
+/// Default number of batches a [`BlockingRecordBatchReader`] reads ahead of
+/// its consumer.
+pub const DEFAULT_READ_AHEAD: usize = 2;
+
+lazy_static::lazy_static! {
+    /// Runtime used by [`BlockingRecordBatchReader::new`] to drive streams.
+    static ref BLOCKING_READER_RUNTIME: Runtime = Builder::new_multi_thread()
+        .thread_name("lance-blocking-reader")
+        .enable_all()
+        .build()
+        .unwrap();
+}
+
+/// A blocking [`RecordBatchReader`](arrow::record_batch::RecordBatchReader)
+/// over a [`RecordBatchStream`].
+///
+/// The stream is polled by a task on a tokio runtime, which sends batches to
+/// the reader through a bounded channel. At most `read_ahead` batches are
+/// buffered; once the buffer is full the task waits until the consumer catches
+/// up, so a slow consumer does not cause the whole stream to be loaded into
+/// memory. Dropping the reader cancels the task.
+///
+/// The reader blocks the calling thread and must not be used from within an
+/// async context.
+pub struct BlockingRecordBatchReader {
+    schema: SchemaRef,
+    receiver: mpsc::Receiver<Result<RecordBatch>>,
+    task: Option<JoinHandle<()>>,
+    handle: Handle,
+}
+
+impl BlockingRecordBatchReader {
+    /// Create a reader that drives the stream on a runtime shared by all
+    /// blocking readers, reading up to [`DEFAULT_READ_AHEAD`] batches ahead.
+    pub fn new(stream: impl RecordBatchStream + 'static) -> Self {
+        Self::with_runtime(
+            stream,
+            BLOCKING_READER_RUNTIME.handle().clone(),
+            DEFAULT_READ_AHEAD,
+        )
+    }
+
+    /// Create a reader that drives the stream on the runtime of `handle`,
+    /// reading up to `read_ahead` batches ahead (at least one).
+    ///
+    /// The runtime must make progress while the consumer is blocked, so it
+    /// should be a multi-threaded runtime or be driven by another thread.
+    pub fn with_runtime(
+        stream: impl RecordBatchStream + 'static,
+        handle: Handle,
+        read_ahead: usize,
+    ) -> Self {
+        let schema = stream.schema();
+        let (sender, receiver) = mpsc::channel(read_ahead.max(1));
+        let task = handle.spawn(async move {
+            let mut stream = Box::pin(stream);
+            while let Some(batch) = stream.next().await {
+                let is_err = batch.is_err();
+                if sender.send(batch).await.is_err() || is_err {
+                    break;
+                }
+            }
+        });
+        Self {
+            schema,
+            receiver,
+            task: Some(task),
+            handle,
+        }
+    }
+}
+
+impl arrow::record_batch::RecordBatchReader for BlockingRecordBatchReader {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+impl Iterator for BlockingRecordBatchReader {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(batch) = self.receiver.blocking_recv() {
+            return Some(batch.map_err(|e| ArrowError::ExternalError(Box::new(e))));
+        }
+        // The channel is closed, either because the stream ended or because
+        // the task panicked. Report a panic once, then end the iteration.
+        let task = self.task.take()?;
+        match self.handle.block_on(task) {
+            Err(err) if err.is_panic() => Some(Err(ArrowError::ExternalError(Box::new(err)))),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for BlockingRecordBatchReader {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use arrow_array::Int32Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use lance_core::Error;
+    use snafu::{location, Location};
+
+    use super::*;
+    use crate::stream::RecordBatchStreamAdapter;
+
+    fn counting_stream(
+        num_batches: i32,
+        produced: Arc<AtomicUsize>,
+    ) -> impl RecordBatchStream + 'static {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let stream_schema = schema.clone();
+        let stream = futures::stream::iter(0..num_batches).map(move |i| {
+            produced.fetch_add(1, Ordering::SeqCst);
+            Ok(RecordBatch::try_new(
+                stream_schema.clone(),
+                vec![Arc::new(Int32Array::from(vec![i]))],
+            )
+            .unwrap())
+        });
+        RecordBatchStreamAdapter::new(schema, stream)
+    }
+
+    #[test]
+    fn test_blocking_reader() {
+        let produced = Arc::new(AtomicUsize::new(0));
+        let reader = BlockingRecordBatchReader::new(counting_stream(10, produced.clone()));
+        let values = reader
+            .map(|batch| {
+                let batch = batch.unwrap();
+                batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap()
+                    .value(0)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_blocking_reader_backpressure() {
+        let produced = Arc::new(AtomicUsize::new(0));
+        let mut reader = BlockingRecordBatchReader::with_runtime(
+            counting_stream(100, produced.clone()),
+            BLOCKING_READER_RUNTIME.handle().clone(),
+            3,
+        );
+        reader.next().unwrap().unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        // One batch consumed, three buffered and one waiting to be sent.
+        assert!(produced.load(Ordering::SeqCst) <= 5);
+
+        // Dropping the reader stops the stream.
+        drop(reader);
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(produced.load(Ordering::SeqCst) <= 5);
+    }
+
+    #[test]
+    fn test_blocking_reader_error() {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let stream = futures::stream::iter(vec![
+            Err(Error::io("read failed".to_string(), location!())),
+            Ok(RecordBatch::new_empty(schema.clone())),
+        ]);
+        let mut reader =
+            BlockingRecordBatchReader::new(RecordBatchStreamAdapter::new(schema, stream));
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+}
@@ -112,7 +112,7 @@ impl ReadBatchParams {
     pub fn valid_given_len(&self, len: usize) -> bool {
         match self {
             Self::Indices(indices) => indices.iter().all(|i| i.unwrap_or(0) < len as u32),
-            Self::Range(r) => r.start < len && r.end <= len,
+            Self::Range(r) => r.start < len && r.start <= r.end && r.end <= len,
             Self::RangeFull => true,
             Self::RangeTo(r) => r.end <= len,
             Self::RangeFrom(r) => r.start < len,
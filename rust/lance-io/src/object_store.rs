@@ -4,36 +4,48 @@
 //! Extend [object_store::ObjectStore] functionalities
 
 use std::collections::HashMap;
+use std::ops::Range;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::time::Duration;
 
 use async_trait::async_trait;
-use aws_config::default_provider::credentials::DefaultCredentialsChain;
-use aws_credential_types::provider::ProvideCredentials;
+use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use deepsize::DeepSizeOf;
 use futures::{future, stream::BoxStream, StreamExt, TryStreamExt};
+use http::header::{HeaderMap, HeaderName, HeaderValue};
 use object_store::aws::{
     AmazonS3ConfigKey, AwsCredential as ObjectStoreAwsCredential, AwsCredentialProvider,
 };
 use object_store::gcp::GoogleCloudStorageBuilder;
 use object_store::{
     aws::AmazonS3Builder, azure::AzureConfigKey, gcp::GoogleConfigKey, local::LocalFileSystem,
-    memory::InMemory, CredentialProvider, Error as ObjectStoreError, Result as ObjectStoreResult,
+    memory::InMemory, Error as ObjectStoreError,
+};
+use object_store::{
+    parse_url_opts, ClientConfigKey, ClientOptions, DynObjectStore, StaticCredentialProvider,
 };
-use object_store::{parse_url_opts, ClientOptions, DynObjectStore, StaticCredentialProvider};
 use object_store::{path::Path, ObjectMeta, ObjectStore as OSObjectStore};
 use shellexpand::tilde;
 use snafu::{location, Location};
-use tokio::{io::AsyncWriteExt, sync::RwLock};
+use tokio::io::AsyncWriteExt;
 use url::Url;
 
 use super::local::LocalObjectReader;
+mod credentials;
+#[cfg(any(test, feature = "testing"))]
+pub mod fault;
 mod gcs_wrapper;
+mod load_balance;
+mod registry;
 mod tracing;
+pub use self::credentials::{AwsCredentialAdapter, CredentialChain, CredentialSource};
 use self::gcs_wrapper::PatchedGoogleCloudStorage;
+use self::load_balance::{pin_endpoint_ips, LoadBalancedObjectStore};
+pub use self::registry::ObjectStoreRegistry;
 use self::tracing::ObjectStoreTracingExt;
 use crate::{object_reader::CloudObjectReader, object_writer::ObjectWriter, traits::Reader};
 use lance_core::{Error, Result};
@@ -85,6 +97,8 @@ pub struct ObjectStore {
     pub inner: Arc<dyn OSObjectStore>,
     scheme: String,
     block_size: usize,
+    // Bytes read through the readers opened by this store, shared by its clones
+    bytes_read: Arc<AtomicU64>,
 }
 
 impl DeepSizeOf for ObjectStore {
@@ -103,85 +117,38 @@ impl std::fmt::Display for ObjectStore {
     }
 }
 
-const AWS_CREDS_CACHE_KEY: &str = "aws_credentials";
-
-/// Adapt an AWS SDK cred into object_store credentials
+/// A [Reader] that adds the bytes it reads to its store's counter.
 #[derive(Debug)]
-pub struct AwsCredentialAdapter {
-    pub inner: Arc<dyn ProvideCredentials>,
-
-    // RefCell can't be shared accross threads, so we use HashMap
-    cache: Arc<RwLock<HashMap<String, Arc<aws_credential_types::Credentials>>>>,
-
-    // The amount of time before expiry to refresh credentials
-    credentials_refresh_offset: Duration,
+struct CountingReader {
+    inner: Box<dyn Reader>,
+    bytes_read: Arc<AtomicU64>,
 }
 
-impl AwsCredentialAdapter {
-    pub fn new(
-        provider: Arc<dyn ProvideCredentials>,
-        credentials_refresh_offset: Duration,
-    ) -> Self {
-        Self {
-            inner: provider,
-            cache: Arc::new(RwLock::new(HashMap::new())),
-            credentials_refresh_offset,
-        }
+impl DeepSizeOf for CountingReader {
+    fn deep_size_of_children(&self, context: &mut deepsize::Context) -> usize {
+        self.inner.deep_size_of_children(context)
     }
 }
 
 #[async_trait]
-impl CredentialProvider for AwsCredentialAdapter {
-    type Credential = ObjectStoreAwsCredential;
-
-    async fn get_credential(&self) -> ObjectStoreResult<Arc<Self::Credential>> {
-        let cached_creds = {
-            let cache_value = self.cache.read().await.get(AWS_CREDS_CACHE_KEY).cloned();
-            let expired = cache_value
-                .clone()
-                .map(|cred| {
-                    cred.expiry()
-                        .map(|exp| {
-                            exp.checked_sub(self.credentials_refresh_offset)
-                                .expect("this time should always be valid")
-                                < SystemTime::now()
-                        })
-                        // no expiry is never expire
-                        .unwrap_or(false)
-                })
-                .unwrap_or(true); // no cred is the same as expired;
-            if expired {
-                None
-            } else {
-                cache_value.clone()
-            }
-        };
+impl Reader for CountingReader {
+    fn path(&self) -> &Path {
+        self.inner.path()
+    }
 
-        if let Some(creds) = cached_creds {
-            Ok(Arc::new(Self::Credential {
-                key_id: creds.access_key_id().to_string(),
-                secret_key: creds.secret_access_key().to_string(),
-                token: creds.session_token().map(|s| s.to_string()),
-            }))
-        } else {
-            let refreshed_creds = Arc::new(self.inner.provide_credentials().await.map_err(
-                |e| Error::Internal {
-                    message: format!("Failed to get AWS credentials: {}", e),
-                    location: location!(),
-                },
-            )?);
+    fn block_size(&self) -> usize {
+        self.inner.block_size()
+    }
 
-            self.cache
-                .write()
-                .await
-                .insert(AWS_CREDS_CACHE_KEY.to_string(), refreshed_creds.clone());
+    async fn size(&self) -> object_store::Result<usize> {
+        self.inner.size().await
+    }
 
-            Ok(Arc::new(Self::Credential {
-                key_id: refreshed_creds.access_key_id().to_string(),
-                secret_key: refreshed_creds.secret_access_key().to_string(),
-                token: refreshed_creds.session_token().map(|s| s.to_string()),
-            }))
-        }
+    async fn get_range(&self, range: Range<usize>) -> object_store::Result<Bytes> {
+        let bytes = self.inner.get_range(range).await?;
+        self.bytes_read
+            .fetch_add(bytes.len() as u64, Ordering::Relaxed);
+        Ok(bytes)
     }
 }
 
@@ -229,12 +196,14 @@ async fn resolve_s3_region(
 /// 1. An explicit `credentials` provider
 /// 2. Explicit credentials in storage_options (as in `aws_access_key_id`,
 ///    `aws_secret_access_key`, `aws_session_token`)
-/// 3. The default credential provider chain from AWS SDK.
+/// 3. `credential_chain`, or the default [CredentialChain].
 ///
+/// Providers built from a chain are shared by all stores in the process.
 /// `credentials_refresh_offset` is the amount of time before expiry to refresh credentials.
 pub async fn build_aws_credential(
     credentials_refresh_offset: Duration,
     credentials: Option<AwsCredentialProvider>,
+    credential_chain: Option<&CredentialChain>,
     storage_options: Option<&HashMap<AmazonS3ConfigKey, String>>,
     region: Option<String>,
 ) -> Result<(AwsCredentialProvider, String)> {
@@ -258,15 +227,11 @@ pub async fn build_aws_credential(
     } else if let Some(creds) = storage_options.and_then(extract_static_s3_credentials) {
         Ok((Arc::new(creds), region))
     } else {
-        let credentials_provider = DefaultCredentialsChain::builder().build().await;
-
-        Ok((
-            Arc::new(AwsCredentialAdapter::new(
-                Arc::new(credentials_provider),
-                credentials_refresh_offset,
-            )),
-            region,
-        ))
+        let provider = match credential_chain {
+            Some(chain) => chain.build_shared(&region, credentials_refresh_offset)?,
+            None => CredentialChain::default().build_shared(&region, credentials_refresh_offset)?,
+        };
+        Ok((provider, region))
     }
 }
 
@@ -306,6 +271,9 @@ pub struct ObjectStoreParams {
     pub object_store: Option<(Arc<DynObjectStore>, Url)>,
     pub s3_credentials_refresh_offset: Duration,
     pub aws_credentials: Option<AwsCredentialProvider>,
+    /// Where to look for AWS credentials when `aws_credentials` is not set
+    /// and the storage options have no static keys.
+    pub aws_credential_chain: Option<CredentialChain>,
     pub object_store_wrapper: Option<Arc<dyn WrappingObjectStore>>,
     pub storage_options: Option<HashMap<String, String>>,
 }
@@ -317,6 +285,7 @@ impl Default for ObjectStoreParams {
             block_size: None,
             s3_credentials_refresh_offset: Duration::from_secs(60),
             aws_credentials: None,
+            aws_credential_chain: None,
             object_store_wrapper: None,
             storage_options: None,
         }
@@ -396,6 +365,7 @@ impl ObjectStore {
                 inner: Arc::new(LocalFileSystem::new()).traced(),
                 scheme: String::from(scheme),
                 block_size: 4 * 1024, // 4KB block size
+                bytes_read: Default::default(),
             },
             Path::from_absolute_path(expanded_path.as_path())?,
         ))
@@ -415,6 +385,7 @@ impl ObjectStore {
             inner: Arc::new(LocalFileSystem::new()).traced(),
             scheme: String::from("file"),
             block_size: 4 * 1024, // 4KB block size
+            bytes_read: Default::default(),
         }
     }
 
@@ -424,6 +395,7 @@ impl ObjectStore {
             inner: Arc::new(InMemory::new()).traced(),
             scheme: String::from("memory"),
             block_size: 64 * 1024,
+            bytes_read: Default::default(),
         }
     }
 
@@ -445,15 +417,16 @@ impl ObjectStore {
     /// Parameters
     /// - ``path``: Absolute path to the file.
     pub async fn open(&self, path: &Path) -> Result<Box<dyn Reader>> {
-        match self.scheme.as_str() {
-            "file" => LocalObjectReader::open(path, self.block_size, None).await,
-            _ => Ok(Box::new(CloudObjectReader::new(
+        let reader = match self.scheme.as_str() {
+            "file" => LocalObjectReader::open(path, self.block_size, None).await?,
+            _ => Box::new(CloudObjectReader::new(
                 self.inner.clone(),
                 path.clone(),
                 self.block_size,
                 None,
-            )?)),
-        }
+            )?),
+        };
+        Ok(self.count_reads(reader))
     }
 
     /// Open a reader for a file with known size.
@@ -462,15 +435,32 @@ impl ObjectStore {
     /// cached metadata. By passing in the known size, we can skip a HEAD / metadata
     /// call.
     pub async fn open_with_size(&self, path: &Path, known_size: usize) -> Result<Box<dyn Reader>> {
-        match self.scheme.as_str() {
-            "file" => LocalObjectReader::open(path, self.block_size, Some(known_size)).await,
-            _ => Ok(Box::new(CloudObjectReader::new(
+        let reader = match self.scheme.as_str() {
+            "file" => LocalObjectReader::open(path, self.block_size, Some(known_size)).await?,
+            _ => Box::new(CloudObjectReader::new(
                 self.inner.clone(),
                 path.clone(),
                 self.block_size,
                 Some(known_size),
-            )?)),
-        }
+            )?),
+        };
+        Ok(self.count_reads(reader))
+    }
+
+    fn count_reads(&self, reader: Box<dyn Reader>) -> Box<dyn Reader> {
+        Box::new(CountingReader {
+            inner: reader,
+            bytes_read: self.bytes_read.clone(),
+        })
+    }
+
+    /// Total number of bytes read through the readers opened by this store
+    /// and its clones.
+    ///
+    /// The counter is shared by all users of the store, so the difference
+    /// between two calls includes the reads of anything running concurrently.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
     }
 
     /// Create an [ObjectWriter] from local [std::path::Path]
@@ -646,6 +636,91 @@ impl StorageOptions {
         })
     }
 
+    /// Whether requests are billed to the requester, for requester-pays buckets.
+    ///
+    /// On GCS, the billed project is set with `billing_project`. S3 isn't
+    /// supported: its `x-amz-request-payer` header must be signed, and the S3
+    /// client only signs the headers it sets itself.
+    pub fn requester_pays(&self) -> bool {
+        self.0
+            .get("requester_pays")
+            .map(|value| str_is_truthy(value))
+            .unwrap_or(false)
+    }
+
+    /// Whether S3 requests use path-style addressing (`endpoint/bucket/key`),
+    /// as needed by MinIO and Ceph, instead of virtual-hosted style.
+    pub fn force_path_style(&self) -> Option<bool> {
+        self.0
+            .get("aws_force_path_style")
+            .or_else(|| self.0.get("force_path_style"))
+            .map(|value| str_is_truthy(value))
+    }
+
+    /// Number of S3 clients to spread requests over, from `endpoint_fanout`.
+    ///
+    /// Plain HTTP endpoints get one client per resolved IP, up to this number,
+    /// unless [`Self::endpoint_pin_ips`] is false. These clients always use
+    /// path-style requests, as the bucket can't be part of the host name of an
+    /// IP. Other endpoints get this many clients with their own connection
+    /// pools.
+    pub fn endpoint_fanout(&self) -> Result<Option<usize>> {
+        self.0
+            .get("aws_endpoint_fanout")
+            .or_else(|| self.0.get("endpoint_fanout"))
+            .map(|value| {
+                value
+                    .parse()
+                    .ok()
+                    .filter(|fanout| *fanout > 0)
+                    .ok_or_else(|| {
+                        Error::invalid_input(
+                            format!("endpoint_fanout must be a positive integer, got {}", value),
+                            location!(),
+                        )
+                    })
+            })
+            .transpose()
+    }
+
+    /// Whether the S3 clients of [`Self::endpoint_fanout`] are each pinned to
+    /// a resolved IP of the endpoint, from `endpoint_pin_ips`.
+    ///
+    /// Only plain HTTP endpoints can be pinned, as HTTPS connections to an IP
+    /// fail certificate validation. Defaults to pinning them.
+    pub fn endpoint_pin_ips(&self) -> Option<bool> {
+        self.0
+            .get("aws_endpoint_pin_ips")
+            .or_else(|| self.0.get("endpoint_pin_ips"))
+            .map(|value| str_is_truthy(value))
+    }
+
+    /// Headers to send with every request, from options named `header.<name>`.
+    ///
+    /// The headers are added after requests are signed, so S3 rejects the
+    /// `x-amz-*` ones.
+    pub fn headers(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for (key, value) in &self.0 {
+            if let Some(name) = key.strip_prefix("header.") {
+                let name = HeaderName::from_str(name).map_err(|e| {
+                    Error::invalid_input(
+                        format!("Invalid header name in storage option {}: {}", key, e),
+                        location!(),
+                    )
+                })?;
+                let value = HeaderValue::from_str(value).map_err(|e| {
+                    Error::invalid_input(
+                        format!("Invalid header value in storage option {}: {}", key, e),
+                        location!(),
+                    )
+                })?;
+                headers.insert(name, value);
+            }
+        }
+        Ok(headers)
+    }
+
     /// Subset of options relevant for azure storage
     pub fn as_azure_options(&self) -> HashMap<AzureConfigKey, String> {
         self.0
@@ -704,11 +779,38 @@ async fn configure_store(url: &str, options: ObjectStoreParams) -> Result<Object
             //     });
             // }
 
+            // S3 signs the x-amz-* headers, which the client only does for the
+            // ones it sets itself, not for default headers
+            let headers = storage_options.headers()?;
+            if storage_options.requester_pays() {
+                return Err(Error::NotSupported {
+                    source: "requester_pays is not supported on S3, as the x-amz-request-payer header can't be signed".into(),
+                    location: location!(),
+                });
+            }
+            if let Some(name) = headers
+                .keys()
+                .find(|name| name.as_str().starts_with("x-amz-"))
+            {
+                return Err(Error::NotSupported {
+                    source: format!(
+                        "Header {} is not supported on S3, as it can't be signed",
+                        name
+                    )
+                    .into(),
+                    location: location!(),
+                });
+            }
+            let force_path_style = storage_options.force_path_style();
+            let endpoint_fanout = storage_options.endpoint_fanout()?;
+            let endpoint_pin_ips = storage_options.endpoint_pin_ips();
+
             let storage_options = storage_options.as_s3_options();
             let region = resolve_s3_region(&url, &storage_options).await?;
             let (aws_creds, region) = build_aws_credential(
                 options.s3_credentials_refresh_offset,
                 options.aws_credentials.clone(),
+                options.aws_credential_chain.as_ref(),
                 Some(&storage_options),
                 region,
             )
@@ -724,27 +826,109 @@ async fn configure_store(url: &str, options: ObjectStoreParams) -> Result<Object
 
             // we can't use parse_url_opts here because we need to manually set the credentials provider
             let mut builder = AmazonS3Builder::new();
+            let mut client_keys = Vec::new();
             for (key, value) in storage_options {
+                if let AmazonS3ConfigKey::Client(client_key) = key {
+                    client_keys.push((client_key, value.clone()));
+                }
                 builder = builder.with_config(key, value);
             }
+            if !headers.is_empty() {
+                builder = builder.with_client_options(client_options(client_keys, headers));
+            }
+            if let Some(force_path_style) = force_path_style {
+                builder = builder.with_virtual_hosted_style_request(!force_path_style);
+            }
             builder = builder
                 .with_url(url.as_ref())
                 .with_credentials(aws_creds)
                 .with_region(region);
-            let store = builder.build()?;
+            let endpoint = builder.get_config_value(&AmazonS3ConfigKey::Endpoint);
+            let pinnable = endpoint
+                .as_ref()
+                .is_some_and(|endpoint| endpoint.starts_with("http://"));
+            if endpoint_pin_ips == Some(true) && !pinnable {
+                return Err(Error::invalid_input(
+                    format!(
+                        "endpoint_pin_ips needs a plain http endpoint, got {}",
+                        endpoint.as_deref().unwrap_or("the default https endpoint")
+                    ),
+                    location!(),
+                ));
+            }
+            let store: Arc<DynObjectStore> = match endpoint_fanout {
+                Some(fanout) if fanout > 1 => {
+                    let endpoints = match endpoint {
+                        Some(endpoint) if pinnable && endpoint_pin_ips != Some(false) => {
+                            let endpoint = Url::parse(&endpoint).map_err(|e| {
+                                Error::invalid_input(
+                                    format!("Invalid endpoint {}: {}", endpoint, e),
+                                    location!(),
+                                )
+                            })?;
+                            pin_endpoint_ips(&endpoint, fanout)
+                                .await?
+                                .into_iter()
+                                .map(Some)
+                                .collect()
+                        }
+                        // Each client resolves the host for its own connections.
+                        _ => vec![None; fanout],
+                    };
+                    let stores = endpoints
+                        .into_iter()
+                        .map(|endpoint| {
+                            let builder = match endpoint {
+                                // Virtual-hosted-style requests would replace the pinned IP
+                                // with the bucket's host name
+                                Some(endpoint) => builder
+                                    .clone()
+                                    .with_endpoint(endpoint)
+                                    .with_virtual_hosted_style_request(false),
+                                None => builder.clone(),
+                            };
+                            Ok(Arc::new(builder.build()?) as Arc<DynObjectStore>)
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    Arc::new(LoadBalancedObjectStore::new(stores))
+                }
+                _ => Arc::new(builder.build()?),
+            };
 
             Ok(ObjectStore {
-                inner: Arc::new(store),
+                inner: store,
                 scheme: String::from(url.scheme()),
                 block_size: 64 * 1024,
+                bytes_read: Default::default(),
             })
         }
         "gs" => {
             storage_options.with_env_gcs();
+            let mut headers = storage_options.headers()?;
+            if storage_options.requester_pays() {
+                let project = storage_options.0.get("billing_project").ok_or_else(|| {
+                    Error::invalid_input(
+                        "requester_pays on GCS requires the billing_project storage option",
+                        location!(),
+                    )
+                })?;
+                let project = HeaderValue::from_str(project).map_err(|e| {
+                    Error::invalid_input(format!("Invalid billing_project: {}", e), location!())
+                })?;
+                headers.insert("x-goog-user-project", project);
+            }
+
             let mut builder = GoogleCloudStorageBuilder::new().with_url(url.as_ref());
+            let mut client_keys = Vec::new();
             for (key, value) in storage_options.as_gcs_options() {
+                if let GoogleConfigKey::Client(client_key) = key {
+                    client_keys.push((client_key, value.clone()));
+                }
                 builder = builder.with_config(key, value);
             }
+            if !headers.is_empty() {
+                builder = builder.with_client_options(client_options(client_keys, headers));
+            }
             let store = builder.build()?;
             // Temporary fix for having larger object sizes. Replace when
             // object_store 0.10.0 is available.
@@ -755,6 +939,7 @@ async fn configure_store(url: &str, options: ObjectStoreParams) -> Result<Object
                 inner: store,
                 scheme: String::from("gs"),
                 block_size: 64 * 1024,
+                bytes_read: Default::default(),
             })
         }
         "az" => {
@@ -766,6 +951,7 @@ async fn configure_store(url: &str, options: ObjectStoreParams) -> Result<Object
                 inner: store,
                 scheme: String::from("az"),
                 block_size: 64 * 1024,
+                bytes_read: Default::default(),
             })
         }
         // we have a bypass logic to use `tokio::fs` directly to lower overhead
@@ -780,6 +966,7 @@ async fn configure_store(url: &str, options: ObjectStoreParams) -> Result<Object
             inner: Arc::new(InMemory::new()).traced(),
             scheme: String::from("memory"),
             block_size: 64 * 1024,
+            bytes_read: Default::default(),
         }),
         unknow_scheme => {
             let err = lance_core::Error::from(object_store::Error::NotSupported {
@@ -809,6 +996,7 @@ impl ObjectStore {
             inner: store,
             scheme: scheme.into(),
             block_size,
+            bytes_read: Default::default(),
         }
     }
 }
@@ -823,6 +1011,22 @@ fn infer_block_size(scheme: &str) -> usize {
     }
 }
 
+/// Client options with `headers` sent on every request.
+///
+/// Setting client options on a builder replaces the ones set through its
+/// config keys, so `client_keys` are applied again here.
+fn client_options(
+    client_keys: impl IntoIterator<Item = (ClientConfigKey, String)>,
+    headers: HeaderMap,
+) -> ClientOptions {
+    client_keys
+        .into_iter()
+        .fold(ClientOptions::default(), |options, (key, value)| {
+            options.with_config(key, value)
+        })
+        .with_default_headers(headers)
+}
+
 fn str_is_truthy(val: &str) -> bool {
     val.eq_ignore_ascii_case("1")
         | val.eq_ignore_ascii_case("true")
@@ -912,6 +1116,8 @@ mod tests {
     use std::path::Path as StdPath;
     use std::sync::atomic::{AtomicBool, Ordering};
 
+    use object_store::{CredentialProvider, Result as ObjectStoreResult};
+
     /// Write test content to file.
     fn write_to_file(path_str: &str, contents: &str) -> std::io::Result<()> {
         let expanded = tilde(path_str).to_string();
@@ -1020,6 +1226,23 @@ mod tests {
         assert_eq!(sub_dirs, vec!["bar", "zoo", "test_file"]);
     }
 
+    #[tokio::test]
+    async fn test_bytes_read() {
+        let store = ObjectStore::memory();
+        let path = Path::from("test_file");
+        store.put(&path, b"0123456789").await.unwrap();
+        assert_eq!(store.bytes_read(), 0);
+
+        let reader = store.open(&path).await.unwrap();
+        reader.get_range(2..6).await.unwrap();
+        // Clones share the counter
+        let clone = store.clone();
+        let reader = clone.open_with_size(&path, 10).await.unwrap();
+        reader.get_range(0..10).await.unwrap();
+        assert_eq!(store.bytes_read(), 14);
+        assert_eq!(clone.bytes_read(), 14);
+    }
+
     #[tokio::test]
     async fn test_delete_directory() {
         let tmp_dir = tempfile::tempdir().unwrap();
@@ -1144,6 +1367,225 @@ mod tests {
         assert!(mock_provider.called.load(Ordering::Relaxed));
     }
 
+    #[test]
+    fn test_storage_options_headers() {
+        let options = StorageOptions(HashMap::from([
+            ("header.x-custom".to_string(), "value".to_string()),
+            ("requester_pays".to_string(), "true".to_string()),
+            ("force_path_style".to_string(), "false".to_string()),
+            ("region".to_string(), "us-east-1".to_string()),
+        ]));
+        let headers = options.headers().unwrap();
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers["x-custom"], "value");
+        assert!(options.requester_pays());
+        assert_eq!(options.force_path_style(), Some(false));
+
+        let options = StorageOptions(HashMap::from([(
+            "header.bad header".to_string(),
+            "value".to_string(),
+        )]));
+        assert!(matches!(options.headers(), Err(Error::InvalidInput { .. })));
+        assert!(!options.requester_pays());
+        assert_eq!(options.force_path_style(), None);
+    }
+
+    #[tokio::test]
+    async fn test_s3_headers() {
+        use tokio::io::AsyncReadExt;
+        use tokio::net::TcpListener;
+
+        // A fake S3 endpoint that records the first request and answers 404.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            socket
+                .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap().to_ascii_lowercase()
+        });
+
+        let params = ObjectStoreParams {
+            storage_options: Some(HashMap::from([
+                ("endpoint".to_string(), endpoint),
+                ("region".to_string(), "us-east-1".to_string()),
+                ("allow_http".to_string(), "true".to_string()),
+                ("aws_access_key_id".to_string(), "key".to_string()),
+                ("aws_secret_access_key".to_string(), "secret".to_string()),
+                ("force_path_style".to_string(), "true".to_string()),
+                ("header.x-lance-test".to_string(), "hello".to_string()),
+            ])),
+            ..Default::default()
+        };
+        let (store, path) = ObjectStore::from_uri_and_params("s3://bucket/data", &params)
+            .await
+            .unwrap();
+        assert!(!store.exists(&path.child("missing")).await.unwrap());
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("head /bucket/data/missing "));
+        assert!(request.contains("x-lance-test: hello"));
+
+        // The headers S3 needs signed can't be sent
+        for (key, value) in [
+            ("requester_pays", "true"),
+            ("header.x-amz-request-payer", "requester"),
+        ] {
+            let mut params = params.clone();
+            params
+                .storage_options
+                .as_mut()
+                .unwrap()
+                .insert(key.to_string(), value.to_string());
+            let err = ObjectStore::from_uri_and_params("s3://bucket/data", &params)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, Error::NotSupported { .. }), "{}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_s3_endpoint_fanout() {
+        let s3_params = |extra: &[(&str, &str)]| {
+            let mut options = HashMap::from([
+                ("region".to_string(), "us-east-1".to_string()),
+                ("aws_access_key_id".to_string(), "key".to_string()),
+                ("aws_secret_access_key".to_string(), "secret".to_string()),
+            ]);
+            for (key, value) in extra {
+                options.insert(key.to_string(), value.to_string());
+            }
+            ObjectStoreParams {
+                storage_options: Some(options),
+                ..Default::default()
+            }
+        };
+
+        let params = s3_params(&[("endpoint_fanout", "3")]);
+        let (store, _) = ObjectStore::from_uri_and_params("s3://bucket/data", &params)
+            .await
+            .unwrap();
+        assert!(store
+            .inner
+            .to_string()
+            .starts_with("LoadBalancedObjectStore(3 x"));
+
+        // An IP endpoint resolves to a single address.
+        let params = s3_params(&[
+            ("endpoint_fanout", "3"),
+            ("endpoint", "http://127.0.0.1:9000"),
+            ("allow_http", "true"),
+        ]);
+        let (store, _) = ObjectStore::from_uri_and_params("s3://bucket/data", &params)
+            .await
+            .unwrap();
+        assert!(store
+            .inner
+            .to_string()
+            .starts_with("LoadBalancedObjectStore(1 x"));
+
+        // Unless it is asked not to be pinned.
+        let params = s3_params(&[
+            ("endpoint_fanout", "3"),
+            ("endpoint", "http://127.0.0.1:9000"),
+            ("allow_http", "true"),
+            ("endpoint_pin_ips", "false"),
+        ]);
+        let (store, _) = ObjectStore::from_uri_and_params("s3://bucket/data", &params)
+            .await
+            .unwrap();
+        assert!(store
+            .inner
+            .to_string()
+            .starts_with("LoadBalancedObjectStore(3 x"));
+
+        // HTTPS endpoints can't be pinned.
+        for endpoint in [Some("https://s3.example.com"), None] {
+            let mut options = vec![("endpoint_fanout", "3"), ("endpoint_pin_ips", "true")];
+            options.extend(endpoint.map(|endpoint| ("endpoint", endpoint)));
+            let err = ObjectStore::from_uri_and_params("s3://bucket/data", &s3_params(&options))
+                .await
+                .unwrap_err();
+            assert!(matches!(err, Error::InvalidInput { .. }));
+        }
+
+        for fanout in ["many", "0"] {
+            let params = s3_params(&[("endpoint_fanout", fanout)]);
+            let err = ObjectStore::from_uri_and_params("s3://bucket/data", &params)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, Error::InvalidInput { .. }));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_s3_endpoint_fanout_path_style() {
+        use tokio::io::AsyncReadExt;
+        use tokio::net::TcpListener;
+
+        // A fake S3 endpoint that records the first request and answers 404.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            socket
+                .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap().to_ascii_lowercase()
+        });
+
+        // Pinned IPs ignore a request for virtual-hosted-style addressing
+        let params = ObjectStoreParams {
+            storage_options: Some(HashMap::from([
+                ("endpoint".to_string(), endpoint),
+                ("endpoint_fanout".to_string(), "2".to_string()),
+                ("force_path_style".to_string(), "false".to_string()),
+                ("region".to_string(), "us-east-1".to_string()),
+                ("allow_http".to_string(), "true".to_string()),
+                ("aws_access_key_id".to_string(), "key".to_string()),
+                ("aws_secret_access_key".to_string(), "secret".to_string()),
+            ])),
+            ..Default::default()
+        };
+        let (store, path) = ObjectStore::from_uri_and_params("s3://bucket/data", &params)
+            .await
+            .unwrap();
+        assert!(!store.exists(&path.child("missing")).await.unwrap());
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("head /bucket/data/missing "));
+    }
+
+    #[tokio::test]
+    async fn test_gcs_requester_pays_requires_project() {
+        let params = ObjectStoreParams {
+            storage_options: Some(HashMap::from([(
+                "requester_pays".to_string(),
+                "true".to_string(),
+            )])),
+            ..Default::default()
+        };
+        let err = ObjectStore::from_uri_and_params("gs://bucket/data", &params)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+    }
+
     #[tokio::test]
     async fn test_local_paths() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -0,0 +1,507 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! AWS credential resolution shared by all object stores in a process.
+//!
+//! A [`CredentialChain`] lists the sources to try, in order, when looking for
+//! credentials. [`CredentialChain::build_shared`] returns a caching provider
+//! that is shared by every store built from an equivalent chain, so opening
+//! many datasets does not query the instance metadata service or STS once per
+//! store when credentials expire.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use aws_config::ecs::EcsCredentialsProvider;
+use aws_config::environment::credentials::EnvironmentVariableCredentialsProvider;
+use aws_config::imds::credentials::ImdsCredentialsProvider;
+use aws_config::meta::credentials::CredentialsProviderChain;
+use aws_config::profile::ProfileFileCredentialsProvider;
+use aws_config::provider_config::ProviderConfig;
+use aws_config::web_identity_token::WebIdentityTokenCredentialsProvider;
+use aws_credential_types::credential_fn::provide_credentials_fn;
+use aws_credential_types::provider::{self, ProvideCredentials};
+use aws_credential_types::Credentials;
+use aws_types::region::Region;
+use lazy_static::lazy_static;
+use object_store::aws::AwsCredential as ObjectStoreAwsCredential;
+use object_store::{CredentialProvider, Result as ObjectStoreResult};
+use snafu::{location, Location};
+use tokio::sync::RwLock;
+
+use lance_core::{Error, Result};
+
+/// A source of AWS credentials in a [`CredentialChain`].
+#[derive(Clone, Debug)]
+pub enum CredentialSource {
+    /// `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`.
+    Environment,
+    /// The shared AWS config and credentials files, using the chain's profile.
+    Profile,
+    /// A web identity token, as used by IAM roles for service accounts (IRSA).
+    WebIdentity,
+    /// The ECS container credentials endpoint.
+    EcsContainer,
+    /// The EC2 instance metadata service (IMDS).
+    InstanceMetadata,
+    /// A custom provider.
+    ///
+    /// Chains only share cached credentials if they hold the same provider
+    /// instance, so providers with the same name never see each other's
+    /// credentials.
+    Custom(String, Arc<dyn ProvideCredentials>),
+}
+
+impl CredentialSource {
+    fn name(&self) -> &str {
+        match self {
+            Self::Environment => "Environment",
+            Self::Profile => "Profile",
+            Self::WebIdentity => "WebIdentityToken",
+            Self::EcsContainer => "EcsContainer",
+            Self::InstanceMetadata => "Ec2InstanceMetadata",
+            Self::Custom(name, _) => name,
+        }
+    }
+
+    /// Identifies the source in the process-wide cache.
+    fn cache_key(&self) -> String {
+        match self {
+            // The allocation can't be reused while a cached provider holds it
+            Self::Custom(name, provider) => {
+                format!("{}@{:p}", name, Arc::as_ptr(provider) as *const ())
+            }
+            _ => self.name().to_string(),
+        }
+    }
+}
+
+/// An ordered list of sources to resolve AWS credentials from.
+///
+/// The default chain tries the environment, the profile files, a web identity
+/// token, the ECS container endpoint and the instance metadata service, in
+/// that order, like the default chain of the AWS SDK.
+///
+/// ```
+/// # use lance_io::object_store::CredentialChain;
+/// # use aws_credential_types::Credentials;
+/// let chain = CredentialChain::default().with_callback("vault", || async {
+///     Ok(Credentials::new("key", "secret", None, None, "vault"))
+/// });
+/// ```
+#[derive(Clone, Debug)]
+pub struct CredentialChain {
+    sources: Vec<CredentialSource>,
+    profile: Option<String>,
+}
+
+impl Default for CredentialChain {
+    fn default() -> Self {
+        Self::empty()
+            .with_source(CredentialSource::Environment)
+            .with_source(CredentialSource::Profile)
+            .with_source(CredentialSource::WebIdentity)
+            .with_source(CredentialSource::EcsContainer)
+            .with_source(CredentialSource::InstanceMetadata)
+    }
+}
+
+lazy_static! {
+    static ref SHARED_PROVIDERS: Mutex<HashMap<String, Weak<AwsCredentialAdapter>>> =
+        Mutex::new(HashMap::new());
+}
+
+impl CredentialChain {
+    /// A chain without any sources.
+    pub fn empty() -> Self {
+        Self {
+            sources: Vec::new(),
+            profile: None,
+        }
+    }
+
+    /// Try `source` after the sources already in the chain.
+    pub fn with_source(mut self, source: CredentialSource) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    /// Try the async callback `f` after the sources already in the chain.
+    ///
+    /// `f` should return [`provider::error::CredentialsError::CredentialsNotLoaded`]
+    /// to fall through to the next source.
+    pub fn with_callback<F, Fut>(self, name: impl Into<String>, f: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = provider::Result> + Send + 'static,
+    {
+        self.with_source(CredentialSource::Custom(
+            name.into(),
+            Arc::new(provide_credentials_fn(f)),
+        ))
+    }
+
+    /// Use the named profile instead of `AWS_PROFILE` or `default`.
+    pub fn with_profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    fn build(&self, region: &str) -> Result<CredentialsProviderChain> {
+        let config = ProviderConfig::default().with_region(Some(Region::new(region.to_string())));
+
+        let mut chain: Option<CredentialsProviderChain> = None;
+        for source in &self.sources {
+            let name = source.name().to_string();
+            chain = Some(match source {
+                CredentialSource::Environment => {
+                    add_source(chain, name, EnvironmentVariableCredentialsProvider::new())
+                }
+                CredentialSource::Profile => {
+                    let mut builder = ProfileFileCredentialsProvider::builder().configure(&config);
+                    if let Some(profile) = &self.profile {
+                        builder = builder.profile_name(profile);
+                    }
+                    add_source(chain, name, builder.build())
+                }
+                CredentialSource::WebIdentity => add_source(
+                    chain,
+                    name,
+                    WebIdentityTokenCredentialsProvider::builder()
+                        .configure(&config)
+                        .build(),
+                ),
+                CredentialSource::EcsContainer => add_source(
+                    chain,
+                    name,
+                    EcsCredentialsProvider::builder().configure(&config).build(),
+                ),
+                CredentialSource::InstanceMetadata => add_source(
+                    chain,
+                    name,
+                    ImdsCredentialsProvider::builder()
+                        .configure(&config)
+                        .build(),
+                ),
+                CredentialSource::Custom(_, provider) => add_source(chain, name, provider.clone()),
+            });
+        }
+        chain.ok_or_else(|| Error::invalid_input("credential chain has no sources", location!()))
+    }
+
+    fn cache_key(&self, region: &str, refresh_offset: Duration) -> String {
+        let sources = self
+            .sources
+            .iter()
+            .map(CredentialSource::cache_key)
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{}|{}|{}|{}",
+            sources,
+            self.profile.as_deref().unwrap_or_default(),
+            region,
+            refresh_offset.as_millis()
+        )
+    }
+
+    /// Build a caching provider for this chain, shared by the whole process.
+    ///
+    /// Chains with the same sources, profile, `region` and `refresh_offset`
+    /// return the same provider while any store still uses it.
+    pub fn build_shared(
+        &self,
+        region: &str,
+        refresh_offset: Duration,
+    ) -> Result<Arc<AwsCredentialAdapter>> {
+        let key = self.cache_key(region, refresh_offset);
+        let mut providers = SHARED_PROVIDERS.lock().unwrap();
+        if let Some(provider) = providers.get(&key).and_then(Weak::upgrade) {
+            return Ok(provider);
+        }
+        // Forget the providers no store uses anymore
+        providers.retain(|_, provider| provider.strong_count() > 0);
+        let provider = Arc::new(AwsCredentialAdapter::new(
+            Arc::new(self.build(region)?),
+            refresh_offset,
+        ));
+        providers.insert(key, Arc::downgrade(&provider));
+        Ok(provider)
+    }
+}
+
+fn add_source(
+    chain: Option<CredentialsProviderChain>,
+    name: String,
+    provider: impl ProvideCredentials + 'static,
+) -> CredentialsProviderChain {
+    match chain {
+        Some(chain) => chain.or_else(name, provider),
+        None => CredentialsProviderChain::first_try(name, provider),
+    }
+}
+
+/// Adapt an AWS SDK cred into object_store credentials
+///
+/// Credentials are cached until they expire. Once they are within
+/// `credentials_refresh_offset` of expiring, they are refreshed in the
+/// background while callers keep using the cached ones. Only one refresh runs
+/// at a time; concurrent callers wait for it instead of querying the provider.
+#[derive(Debug)]
+pub struct AwsCredentialAdapter {
+    pub inner: Arc<dyn ProvideCredentials>,
+
+    cache: Arc<RwLock<Option<Arc<Credentials>>>>,
+
+    // Held while fetching credentials from `inner`
+    refresh_lock: Arc<tokio::sync::Mutex<()>>,
+
+    // The amount of time before expiry to refresh credentials
+    credentials_refresh_offset: Duration,
+}
+
+impl AwsCredentialAdapter {
+    pub fn new(
+        provider: Arc<dyn ProvideCredentials>,
+        credentials_refresh_offset: Duration,
+    ) -> Self {
+        Self {
+            inner: provider,
+            cache: Arc::new(RwLock::new(None)),
+            refresh_lock: Arc::new(tokio::sync::Mutex::new(())),
+            credentials_refresh_offset,
+        }
+    }
+
+    fn is_expired(creds: &Credentials, offset: Duration) -> bool {
+        creds
+            .expiry()
+            .map(|exp| {
+                exp.checked_sub(offset)
+                    .expect("this time should always be valid")
+                    < SystemTime::now()
+            })
+            // no expiry is never expire
+            .unwrap_or(false)
+    }
+
+    async fn fetch(
+        inner: &dyn ProvideCredentials,
+        cache: &RwLock<Option<Arc<Credentials>>>,
+    ) -> Result<Arc<Credentials>> {
+        let creds = Arc::new(
+            inner
+                .provide_credentials()
+                .await
+                .map_err(|e| Error::Internal {
+                    message: format!("Failed to get AWS credentials: {}", e),
+                    location: location!(),
+                })?,
+        );
+        *cache.write().await = Some(creds.clone());
+        Ok(creds)
+    }
+
+    fn refresh_in_background(&self) {
+        // Skip if a refresh is already in flight.
+        let Ok(guard) = self.refresh_lock.clone().try_lock_owned() else {
+            return;
+        };
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+        tokio::spawn(async move {
+            let _guard = guard;
+            if let Err(e) = Self::fetch(inner.as_ref(), &cache).await {
+                tracing::warn!("Failed to refresh AWS credentials ahead of expiry: {}", e);
+            }
+        });
+    }
+}
+
+fn to_object_store_credential(creds: &Credentials) -> Arc<ObjectStoreAwsCredential> {
+    Arc::new(ObjectStoreAwsCredential {
+        key_id: creds.access_key_id().to_string(),
+        secret_key: creds.secret_access_key().to_string(),
+        token: creds.session_token().map(|s| s.to_string()),
+    })
+}
+
+#[async_trait]
+impl CredentialProvider for AwsCredentialAdapter {
+    type Credential = ObjectStoreAwsCredential;
+
+    async fn get_credential(&self) -> ObjectStoreResult<Arc<Self::Credential>> {
+        let cached = self.cache.read().await.clone();
+        if let Some(creds) = &cached {
+            if !Self::is_expired(creds, Duration::ZERO) {
+                if Self::is_expired(creds, self.credentials_refresh_offset) {
+                    self.refresh_in_background();
+                }
+                return Ok(to_object_store_credential(creds));
+            }
+        }
+
+        // No usable credentials: fetch them once and let concurrent callers
+        // reuse the result.
+        let _guard = self.refresh_lock.lock().await;
+        if let Some(creds) = self.cache.read().await.as_ref() {
+            if !Self::is_expired(creds, Duration::ZERO) {
+                return Ok(to_object_store_credential(creds));
+            }
+        }
+        let creds = Self::fetch(self.inner.as_ref(), &self.cache).await?;
+        Ok(to_object_store_credential(&creds))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use aws_credential_types::provider::error::CredentialsError;
+    use futures::future::try_join_all;
+
+    fn counting_chain(
+        name: &str,
+        calls: Arc<AtomicUsize>,
+        expires_in: Duration,
+    ) -> CredentialChain {
+        CredentialChain::empty().with_callback(name, move || {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok(Credentials::new(
+                    format!("key-{}", call),
+                    "secret",
+                    None,
+                    Some(SystemTime::now() + expires_in),
+                    "test",
+                ))
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_callers_share_fetch() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let provider = counting_chain("stampede", calls.clone(), Duration::from_secs(3600))
+            .build_shared("us-east-1", Duration::from_secs(60))
+            .unwrap();
+
+        let creds = try_join_all((0..32).map(|_| provider.get_credential()))
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(creds.iter().all(|c| c.key_id == "key-0"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_ahead() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        // Credentials expire in 30s, which is within the 60s refresh offset.
+        let provider = counting_chain("refresh", calls.clone(), Duration::from_secs(30))
+            .build_shared("us-east-1", Duration::from_secs(60))
+            .unwrap();
+
+        assert_eq!(provider.get_credential().await.unwrap().key_id, "key-0");
+        // Still valid, so the cached credentials are returned while a single
+        // refresh runs in the background.
+        assert_eq!(provider.get_credential().await.unwrap().key_id, "key-0");
+        assert_eq!(provider.get_credential().await.unwrap().key_id, "key-0");
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(provider.get_credential().await.unwrap().key_id, "key-1");
+    }
+
+    #[tokio::test]
+    async fn test_shared_across_stores() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let chain = counting_chain("shared", calls.clone(), Duration::from_secs(3600));
+        let a = chain
+            .build_shared("us-east-1", Duration::from_secs(60))
+            .unwrap();
+        let b = chain
+            .clone()
+            .build_shared("us-east-1", Duration::from_secs(60))
+            .unwrap();
+        let other_region = chain
+            .build_shared("us-west-2", Duration::from_secs(60))
+            .unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &other_region));
+
+        a.get_credential().await.unwrap();
+        b.get_credential().await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_custom_sources_not_shared_by_name() {
+        let tenant_a = Arc::new(AtomicUsize::new(0));
+        let tenant_b = Arc::new(AtomicUsize::new(0));
+        let a = counting_chain("tenant", tenant_a.clone(), Duration::from_secs(3600))
+            .build_shared("us-east-1", Duration::from_secs(60))
+            .unwrap();
+        let b = counting_chain("tenant", tenant_b.clone(), Duration::from_secs(3600))
+            .build_shared("us-east-1", Duration::from_secs(60))
+            .unwrap();
+        assert!(!Arc::ptr_eq(&a, &b));
+
+        a.get_credential().await.unwrap();
+        b.get_credential().await.unwrap();
+        assert_eq!(tenant_a.load(Ordering::SeqCst), 1);
+        assert_eq!(tenant_b.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unused_providers_evicted() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let chain = counting_chain("evicted", calls.clone(), Duration::from_secs(3600));
+        let key = chain.cache_key("us-east-1", Duration::from_secs(60));
+        let provider = chain
+            .build_shared("us-east-1", Duration::from_secs(60))
+            .unwrap();
+        drop(provider);
+
+        // Building any provider forgets the ones that are no longer used
+        CredentialChain::default()
+            .build_shared("us-east-1", Duration::from_secs(60))
+            .unwrap();
+        assert!(!SHARED_PROVIDERS.lock().unwrap().contains_key(&key));
+    }
+
+    #[tokio::test]
+    async fn test_chain_falls_through() {
+        let chain = CredentialChain::empty()
+            .with_callback("missing", || async {
+                Err(CredentialsError::not_loaded("no credentials here"))
+            })
+            .with_callback("fallback", || async {
+                Ok(Credentials::new(
+                    "fallback-key",
+                    "secret",
+                    None,
+                    None,
+                    "test",
+                ))
+            });
+        let provider = chain
+            .build_shared("us-east-1", Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(
+            provider.get_credential().await.unwrap().key_id,
+            "fallback-key"
+        );
+
+        let err = CredentialChain::empty()
+            .build_shared("us-east-1", Duration::from_secs(60))
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+    }
+}
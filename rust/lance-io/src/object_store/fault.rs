@@ -0,0 +1,407 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! An object store that injects faults, to test how code copes with them
+//!
+//! A [`FaultInjector`] wraps object stores in a [`FaultInjectingObjectStore`],
+//! which delays operations and makes them fail according to a [`FaultConfig`].
+//! Injected failures are generic object store errors caused by a reset
+//! connection, which retry logic treats as transient.  Faults are drawn from
+//! a seeded random generator, so a test sees the same faults each run as long
+//! as it issues the same operations in the same order.
+//!
+//! The injector is a [`WrappingObjectStore`], so it can be given to a dataset
+//! through [`ObjectStoreParams::object_store_wrapper`](super::ObjectStoreParams).
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{self, BoxStream};
+use futures::StreamExt;
+use object_store::path::Path;
+use object_store::{
+    GetOptions, GetResult, ListResult, MultipartId, ObjectMeta, ObjectStore as OSObjectStore,
+    PutOptions, PutResult, Result as OSResult,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tokio::io::AsyncWrite;
+
+use super::WrappingObjectStore;
+
+/// Kinds of object store operations faults can be injected into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Put,
+    PutMultipart,
+    /// Any read of the content of an object
+    Get,
+    Head,
+    Delete,
+    List,
+    /// Copies, including conditional ones
+    Copy,
+    Rename,
+}
+
+/// Which faults a [`FaultInjector`] injects
+#[derive(Debug, Clone, Default)]
+pub struct FaultConfig {
+    /// Probability that an operation fails without reaching the store
+    pub failure_probability: f64,
+    /// Probability that a put writes only the first half of the object and
+    /// then fails, as a store without atomic writes could
+    pub partial_write_probability: f64,
+    /// Time each operation is delayed by
+    pub latency: Duration,
+    /// Longest random time each operation is further delayed by
+    pub latency_jitter: Duration,
+    /// The operations faults are injected into, all if not set
+    pub operations: Option<Vec<Operation>>,
+    /// Only inject faults into operations on paths containing this
+    pub path_contains: Option<String>,
+}
+
+impl FaultConfig {
+    fn applies_to(&self, operation: Operation, path: &Path) -> bool {
+        let operation_matches = self
+            .operations
+            .as_ref()
+            .map(|operations| operations.contains(&operation))
+            .unwrap_or(true);
+        let path_matches = self
+            .path_contains
+            .as_ref()
+            .map(|part| path.as_ref().contains(part.as_str()))
+            .unwrap_or(true);
+        operation_matches && path_matches
+    }
+}
+
+/// What happens to an operation
+#[derive(Debug, Default)]
+struct Fault {
+    delay: Duration,
+    fail: bool,
+    partial_write: bool,
+}
+
+#[derive(Debug)]
+struct InjectorState {
+    config: FaultConfig,
+    rng: StdRng,
+    /// Operations to fail regardless of the config
+    fail_next: HashMap<Operation, usize>,
+    num_failures: usize,
+    num_partial_writes: usize,
+}
+
+/// Injects faults into the object stores it wraps
+///
+/// Clones share their config and counters, so a test can keep a clone to
+/// change the faults while the wrapped stores are in use.
+#[derive(Debug, Clone)]
+pub struct FaultInjector {
+    state: Arc<Mutex<InjectorState>>,
+}
+
+impl FaultInjector {
+    /// Create an injector of the faults of `config`, drawn from `seed`
+    pub fn new(config: FaultConfig, seed: u64) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(InjectorState {
+                config,
+                rng: StdRng::seed_from_u64(seed),
+                fail_next: HashMap::new(),
+                num_failures: 0,
+                num_partial_writes: 0,
+            })),
+        }
+    }
+
+    /// Replace the faults injected from now on
+    pub fn set_config(&self, config: FaultConfig) {
+        self.state.lock().unwrap().config = config;
+    }
+
+    /// Fail the next `count` operations of kind `operation`, on any path
+    pub fn fail_next(&self, operation: Operation, count: usize) {
+        *self
+            .state
+            .lock()
+            .unwrap()
+            .fail_next
+            .entry(operation)
+            .or_default() += count;
+    }
+
+    /// Number of failures injected so far, including partial writes
+    pub fn num_failures(&self) -> usize {
+        self.state.lock().unwrap().num_failures
+    }
+
+    /// Number of partial writes injected so far
+    pub fn num_partial_writes(&self) -> usize {
+        self.state.lock().unwrap().num_partial_writes
+    }
+
+    /// Wrap `target` in a store injecting the faults of this injector
+    pub fn store(&self, target: Arc<dyn OSObjectStore>) -> Arc<dyn OSObjectStore> {
+        Arc::new(FaultInjectingObjectStore {
+            target,
+            injector: self.clone(),
+        })
+    }
+
+    fn next_fault(&self, operation: Operation, path: &Path) -> Fault {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        if let Some(count) = state.fail_next.get_mut(&operation) {
+            if *count > 0 {
+                *count -= 1;
+                state.num_failures += 1;
+                return Fault {
+                    fail: true,
+                    ..Default::default()
+                };
+            }
+        }
+        if !state.config.applies_to(operation, path) {
+            return Fault::default();
+        }
+        let config = state.config.clone();
+        let mut delay = config.latency;
+        if !config.latency_jitter.is_zero() {
+            delay += config.latency_jitter.mul_f64(state.rng.gen::<f64>());
+        }
+        let fail = state
+            .rng
+            .gen_bool(config.failure_probability.clamp(0.0, 1.0));
+        let partial_write = !fail
+            && operation == Operation::Put
+            && state
+                .rng
+                .gen_bool(config.partial_write_probability.clamp(0.0, 1.0));
+        if fail || partial_write {
+            state.num_failures += 1;
+        }
+        if partial_write {
+            state.num_partial_writes += 1;
+        }
+        Fault {
+            delay,
+            fail,
+            partial_write,
+        }
+    }
+
+    /// Delay an operation and fail it if it is to fail
+    async fn inject(&self, operation: Operation, path: &Path) -> OSResult<Fault> {
+        let fault = self.next_fault(operation, path);
+        if !fault.delay.is_zero() {
+            tokio::time::sleep(fault.delay).await;
+        }
+        if fault.fail {
+            return Err(injected_error(operation, path));
+        }
+        Ok(fault)
+    }
+}
+
+impl WrappingObjectStore for FaultInjector {
+    fn wrap(&self, original: Arc<dyn OSObjectStore>) -> Arc<dyn OSObjectStore> {
+        self.store(original)
+    }
+}
+
+fn injected_error(operation: Operation, path: &Path) -> object_store::Error {
+    object_store::Error::Generic {
+        store: "FaultInjectingObjectStore",
+        source: Box::new(std::io::Error::new(
+            std::io::ErrorKind::ConnectionReset,
+            format!("injected fault in {:?} of {}", operation, path),
+        )),
+    }
+}
+
+/// An object store that injects the faults of a [`FaultInjector`]
+#[derive(Debug)]
+pub struct FaultInjectingObjectStore {
+    target: Arc<dyn OSObjectStore>,
+    injector: FaultInjector,
+}
+
+impl std::fmt::Display for FaultInjectingObjectStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FaultInjectingObjectStore({})", self.target)
+    }
+}
+
+#[async_trait]
+impl OSObjectStore for FaultInjectingObjectStore {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        bytes: Bytes,
+        opts: PutOptions,
+    ) -> OSResult<PutResult> {
+        let fault = self.injector.inject(Operation::Put, location).await?;
+        if fault.partial_write {
+            let partial = bytes.slice(..bytes.len() / 2);
+            self.target.put_opts(location, partial, opts).await?;
+            return Err(injected_error(Operation::Put, location));
+        }
+        self.target.put_opts(location, bytes, opts).await
+    }
+
+    async fn put_multipart(
+        &self,
+        location: &Path,
+    ) -> OSResult<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        self.injector
+            .inject(Operation::PutMultipart, location)
+            .await?;
+        self.target.put_multipart(location).await
+    }
+
+    async fn abort_multipart(&self, location: &Path, multipart_id: &MultipartId) -> OSResult<()> {
+        self.target.abort_multipart(location, multipart_id).await
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> OSResult<GetResult> {
+        self.injector.inject(Operation::Get, location).await?;
+        self.target.get_opts(location, options).await
+    }
+
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> OSResult<Bytes> {
+        self.injector.inject(Operation::Get, location).await?;
+        self.target.get_range(location, range).await
+    }
+
+    async fn get_ranges(&self, location: &Path, ranges: &[Range<usize>]) -> OSResult<Vec<Bytes>> {
+        self.injector.inject(Operation::Get, location).await?;
+        self.target.get_ranges(location, ranges).await
+    }
+
+    async fn head(&self, location: &Path) -> OSResult<ObjectMeta> {
+        self.injector.inject(Operation::Head, location).await?;
+        self.target.head(location).await
+    }
+
+    async fn delete(&self, location: &Path) -> OSResult<()> {
+        self.injector.inject(Operation::Delete, location).await?;
+        self.target.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, OSResult<ObjectMeta>> {
+        let path = prefix.cloned().unwrap_or_default();
+        let objects = self.target.list(prefix);
+        stream::once(async move {
+            match self.injector.inject(Operation::List, &path).await {
+                Ok(_) => objects,
+                Err(err) => stream::once(async move { Err(err) }).boxed(),
+            }
+        })
+        .flatten()
+        .boxed()
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> OSResult<ListResult> {
+        let path = prefix.cloned().unwrap_or_default();
+        self.injector.inject(Operation::List, &path).await?;
+        self.target.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> OSResult<()> {
+        self.injector.inject(Operation::Copy, to).await?;
+        self.target.copy(from, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> OSResult<()> {
+        self.injector.inject(Operation::Rename, to).await?;
+        self.target.rename(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> OSResult<()> {
+        self.injector.inject(Operation::Copy, to).await?;
+        self.target.copy_if_not_exists(from, to).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fault_injection() {
+        let injector = FaultInjector::new(FaultConfig::default(), 42);
+        let store = injector.store(Arc::new(InMemory::new()));
+        let path = Path::from("data/a.lance");
+
+        // Without faults operations go through
+        store.put(&path, Bytes::from_static(b"0123")).await.unwrap();
+        assert_eq!(
+            store.get(&path).await.unwrap().bytes().await.unwrap(),
+            "0123"
+        );
+
+        // Scheduled failures fail the next operations of a kind only
+        injector.fail_next(Operation::Get, 2);
+        assert!(store.get(&path).await.is_err());
+        assert!(store.head(&path).await.is_ok());
+        let err = store.get(&path).await.unwrap_err();
+        assert!(err.to_string().contains("injected fault"), "{}", err);
+        assert!(store.get(&path).await.is_ok());
+        assert_eq!(injector.num_failures(), 2);
+
+        // Partial writes leave half of the object behind
+        injector.set_config(FaultConfig {
+            partial_write_probability: 1.0,
+            ..Default::default()
+        });
+        let err = store
+            .put(&path, Bytes::from_static(b"abcdef"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("injected fault"), "{}", err);
+        assert_eq!(
+            store.get(&path).await.unwrap().bytes().await.unwrap(),
+            "abc"
+        );
+        assert_eq!(injector.num_partial_writes(), 1);
+
+        // Faults can be restricted to operations and paths
+        injector.set_config(FaultConfig {
+            failure_probability: 1.0,
+            operations: Some(vec![Operation::List]),
+            path_contains: Some("data".to_string()),
+            ..Default::default()
+        });
+        assert!(store.head(&path).await.is_ok());
+        let listed = store
+            .list(Some(&Path::from("data")))
+            .try_collect::<Vec<_>>()
+            .await;
+        assert!(listed.is_err());
+        let listed = store
+            .list(Some(&Path::from("other")))
+            .try_collect::<Vec<_>>()
+            .await;
+        assert!(listed.unwrap().is_empty());
+
+        // Latency delays operations
+        injector.set_config(FaultConfig {
+            latency: Duration::from_millis(20),
+            ..Default::default()
+        });
+        let start = std::time::Instant::now();
+        store.head(&path).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
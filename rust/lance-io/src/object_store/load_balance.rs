@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Spread requests over several clients of the same bucket.
+//!
+//! A single S3 client keeps one connection pool, and its connections tend to
+//! land on the few front-end IPs resolved when they were opened. For very high
+//! throughput scans that pool becomes the bottleneck. Fanning out over several
+//! clients, each pinned to a different resolved IP where possible, spreads the
+//! connections over more front-ends.
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use object_store::path::Path;
+use object_store::{
+    GetOptions, GetResult, ListResult, MultipartId, ObjectMeta, PutOptions, PutResult,
+    Result as OSResult,
+};
+use snafu::{location, Location};
+use tokio::io::AsyncWrite;
+use url::Url;
+
+use lance_core::{Error, Result};
+
+/// Round-robins requests over equivalent object stores.
+#[derive(Debug)]
+pub struct LoadBalancedObjectStore {
+    targets: Vec<Arc<dyn object_store::ObjectStore>>,
+    next: AtomicUsize,
+}
+
+impl LoadBalancedObjectStore {
+    /// Create a store that round-robins over `targets`.
+    ///
+    /// All targets must point to the same bucket with the same configuration.
+    pub fn new(targets: Vec<Arc<dyn object_store::ObjectStore>>) -> Self {
+        assert!(
+            !targets.is_empty(),
+            "load balancing needs at least one store"
+        );
+        Self {
+            targets,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    fn target(&self) -> &Arc<dyn object_store::ObjectStore> {
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        &self.targets[next % self.targets.len()]
+    }
+}
+
+impl std::fmt::Display for LoadBalancedObjectStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "LoadBalancedObjectStore({} x {})",
+            self.targets.len(),
+            self.targets[0]
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl object_store::ObjectStore for LoadBalancedObjectStore {
+    async fn put(&self, location: &Path, bytes: Bytes) -> OSResult<PutResult> {
+        self.target().put(location, bytes).await
+    }
+
+    async fn put_opts(
+        &self,
+        location: &Path,
+        bytes: Bytes,
+        opts: PutOptions,
+    ) -> OSResult<PutResult> {
+        self.target().put_opts(location, bytes, opts).await
+    }
+
+    async fn put_multipart(
+        &self,
+        location: &Path,
+    ) -> OSResult<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        self.target().put_multipart(location).await
+    }
+
+    async fn abort_multipart(&self, location: &Path, multipart_id: &MultipartId) -> OSResult<()> {
+        self.target().abort_multipart(location, multipart_id).await
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> OSResult<GetResult> {
+        self.target().get_opts(location, options).await
+    }
+
+    async fn get_range(&self, location: &Path, range: Range<usize>) -> OSResult<Bytes> {
+        self.target().get_range(location, range).await
+    }
+
+    async fn get_ranges(&self, location: &Path, ranges: &[Range<usize>]) -> OSResult<Vec<Bytes>> {
+        self.target().get_ranges(location, ranges).await
+    }
+
+    async fn head(&self, location: &Path) -> OSResult<ObjectMeta> {
+        self.target().head(location).await
+    }
+
+    async fn delete(&self, location: &Path) -> OSResult<()> {
+        self.target().delete(location).await
+    }
+
+    fn delete_stream<'a>(
+        &'a self,
+        locations: BoxStream<'a, OSResult<Path>>,
+    ) -> BoxStream<'a, OSResult<Path>> {
+        self.target().delete_stream(locations)
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, OSResult<ObjectMeta>> {
+        self.target().list(prefix)
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> OSResult<ListResult> {
+        self.target().list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> OSResult<()> {
+        self.target().copy(from, to).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> OSResult<()> {
+        self.target().rename(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> OSResult<()> {
+        self.target().copy_if_not_exists(from, to).await
+    }
+}
+
+/// Resolve the distinct IPs of the host of `endpoint`, in resolver order.
+pub async fn resolve_endpoint_ips(endpoint: &Url) -> Result<Vec<IpAddr>> {
+    let host = endpoint.host_str().ok_or_else(|| {
+        Error::invalid_input(format!("Endpoint has no host: {}", endpoint), location!())
+    })?;
+    let port = endpoint.port_or_known_default().unwrap_or(443);
+    let mut seen = HashSet::new();
+    Ok(tokio::net::lookup_host((host, port))
+        .await?
+        .map(|addr| addr.ip())
+        .filter(|ip| seen.insert(*ip))
+        .collect())
+}
+
+/// Copies of a plain HTTP `endpoint`, each pinned to a different resolved IP
+/// of its host, up to `max` of them.
+///
+/// HTTPS endpoints can't be addressed by IP without failing certificate
+/// validation, so they are not supported here.
+pub async fn pin_endpoint_ips(endpoint: &Url, max: usize) -> Result<Vec<String>> {
+    if endpoint.scheme() != "http" {
+        return Err(Error::invalid_input(
+            format!("Only http endpoints can be pinned to IPs: {}", endpoint),
+            location!(),
+        ));
+    }
+    let mut endpoints = Vec::new();
+    for ip in resolve_endpoint_ips(endpoint).await?.into_iter().take(max) {
+        let mut pinned = endpoint.clone();
+        pinned.set_ip_host(ip).map_err(|()| {
+            Error::invalid_input(format!("Cannot pin {} to {}", endpoint, ip), location!())
+        })?;
+        endpoints.push(pinned.as_str().trim_end_matches('/').to_string());
+    }
+    if endpoints.is_empty() {
+        return Err(Error::invalid_input(
+            format!("Endpoint {} did not resolve to any address", endpoint),
+            location!(),
+        ));
+    }
+    Ok(endpoints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use object_store::memory::InMemory;
+    use object_store::ObjectStore;
+
+    #[tokio::test]
+    async fn test_round_robin() {
+        let a = Arc::new(InMemory::new());
+        let b = Arc::new(InMemory::new());
+        let store = LoadBalancedObjectStore::new(vec![a.clone(), b.clone()]);
+
+        for i in 0..4 {
+            store
+                .put(&Path::from(format!("file-{}", i)), Bytes::from("data"))
+                .await
+                .unwrap();
+        }
+        assert!(a.head(&Path::from("file-0")).await.is_ok());
+        assert!(b.head(&Path::from("file-1")).await.is_ok());
+        assert!(a.head(&Path::from("file-2")).await.is_ok());
+        assert!(b.head(&Path::from("file-3")).await.is_ok());
+        assert!(a.head(&Path::from("file-1")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pin_endpoint_ips() {
+        let endpoint = Url::parse("http://localhost:9000").unwrap();
+        let endpoints = pin_endpoint_ips(&endpoint, 8).await.unwrap();
+        assert!(endpoints.contains(&"http://127.0.0.1:9000".to_string()));
+        assert!(endpoints.iter().all(|e| !e.contains("localhost")));
+
+        let endpoints = pin_endpoint_ips(&endpoint, 1).await.unwrap();
+        assert_eq!(endpoints.len(), 1);
+
+        let endpoint = Url::parse("https://s3.us-east-1.amazonaws.com").unwrap();
+        assert!(pin_endpoint_ips(&endpoint, 3).await.is_err());
+    }
+}
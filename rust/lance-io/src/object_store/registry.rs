@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Object stores shared between datasets
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use deepsize::DeepSizeOf;
+use object_store::path::Path;
+use url::Url;
+
+use super::ObjectStore;
+
+/// Object stores registered for URI prefixes.
+///
+/// Building an object store sets up its own connection pool and credentials.
+/// Datasets resolving their URI through a registry reuse the store registered
+/// for the longest prefix of the URI instead, which is already configured.
+#[derive(Debug, Default)]
+pub struct ObjectStoreRegistry {
+    stores: RwLock<BTreeMap<String, ObjectStore>>,
+}
+
+impl DeepSizeOf for ObjectStoreRegistry {
+    fn deep_size_of_children(&self, context: &mut deepsize::Context) -> usize {
+        self.stores.read().unwrap().deep_size_of_children(context)
+    }
+}
+
+impl ObjectStoreRegistry {
+    /// Use `store` for the URIs starting with `prefix`.
+    ///
+    /// The prefix is a URI, like `s3://bucket` or `s3://bucket/path`, which
+    /// matches the URIs of the same scheme and authority within its
+    /// directory. As stores are bound to a bucket, a bare scheme like `s3://`
+    /// only matches the URIs without an authority, like `file:///path`. A store
+    /// registered for the same prefix before is replaced.
+    pub fn register(&self, prefix: impl Into<String>, store: ObjectStore) {
+        let prefix = normalize_prefix(prefix.into());
+        self.stores.write().unwrap().insert(prefix, store);
+    }
+
+    /// Stop using the store registered for `prefix`, returning it.
+    pub fn unregister(&self, prefix: impl Into<String>) -> Option<ObjectStore> {
+        let prefix = normalize_prefix(prefix.into());
+        self.stores.write().unwrap().remove(&prefix)
+    }
+
+    /// The prefixes with a registered store.
+    pub fn prefixes(&self) -> Vec<String> {
+        self.stores.read().unwrap().keys().cloned().collect()
+    }
+
+    /// The store registered for the longest prefix of `uri`, and the path of
+    /// `uri` within it.
+    ///
+    /// Returns `None` when no prefix matches, or when `uri` is a local path
+    /// rather than a URI.
+    pub fn resolve(&self, uri: &str) -> Option<(ObjectStore, Path)> {
+        let url = match Url::parse(uri) {
+            // On Windows, the drive is parsed as a scheme
+            Ok(url) if url.scheme().len() == 1 && cfg!(windows) => return None,
+            Ok(url) => url,
+            Err(_) => return None,
+        };
+        let key = url_key(&url);
+        let stores = self.stores.read().unwrap();
+        let store = stores
+            .iter()
+            .filter(|(prefix, _)| matches_prefix(&key, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, store)| store.clone())?;
+        Some((store, Path::from(url.path())))
+    }
+}
+
+/// `scheme://authority/path` of `url`, without a trailing slash.
+///
+/// The stores for different authorities, such as buckets, are different, so
+/// the authority is always part of the key, even when it is empty.
+fn url_key(url: &Url) -> String {
+    let authority = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => String::new(),
+    };
+    format!(
+        "{}://{}{}",
+        url.scheme(),
+        authority,
+        url.path().trim_end_matches('/')
+    )
+}
+
+fn normalize_prefix(prefix: String) -> String {
+    match Url::parse(&prefix) {
+        Ok(url) => url_key(&url),
+        Err(_) => prefix.trim_end_matches('/').to_string(),
+    }
+}
+
+/// Whether `key` is `prefix` or within its directory.
+fn matches_prefix(key: &str, prefix: &str) -> bool {
+    match key.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let registry = ObjectStoreRegistry::default();
+        assert!(registry.resolve("memory://bucket/table").is_none());
+
+        registry.register("memory://bucket", ObjectStore::memory());
+        let mut table_store = ObjectStore::memory();
+        table_store.set_block_size(1024);
+        registry.register("memory://bucket/table/", table_store);
+        assert_eq!(
+            registry.prefixes(),
+            vec!["memory://bucket", "memory://bucket/table"]
+        );
+
+        // The longest prefix wins
+        let (store, path) = registry.resolve("memory://bucket/table/data").unwrap();
+        assert_eq!(store.block_size(), 1024);
+        assert_eq!(path, Path::from("table/data"));
+        let (store, _) = registry.resolve("memory://bucket/table").unwrap();
+        assert_eq!(store.block_size(), 1024);
+
+        // Prefixes only match whole path segments
+        let (store, path) = registry.resolve("memory://bucket/table2").unwrap();
+        assert_eq!(store.block_size(), 64 * 1024);
+        assert_eq!(path, Path::from("table2"));
+
+        // Nor do they match other buckets, even with the same path
+        assert!(registry.resolve("memory://bucket2/table").is_none());
+        assert!(registry.resolve("s3://bucket/table").is_none());
+        assert!(registry.resolve("/tmp/table").is_none());
+
+        // A bare scheme only matches URIs without an authority
+        registry.register("memory://", ObjectStore::memory());
+        assert!(registry.resolve("memory://bucket2/table").is_none());
+        assert!(registry.resolve("memory:///table").is_some());
+
+        assert!(registry.unregister("memory://bucket/table").is_some());
+        let (store, _) = registry.resolve("memory://bucket/table").unwrap();
+        assert_eq!(store.block_size(), 64 * 1024);
+    }
+}
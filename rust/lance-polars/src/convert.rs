@@ -0,0 +1,97 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Conversion of Arrow data to Polars through the Arrow C data interface
+
+use arrow::ffi::{to_ffi, FFI_ArrowArray, FFI_ArrowSchema};
+use arrow_array::{Array, RecordBatch};
+use arrow_schema::{Field, Schema as ArrowSchema};
+use polars::prelude::*;
+use polars_arrow::ffi;
+
+fn arrow_error(err: arrow_schema::ArrowError) -> PolarsError {
+    PolarsError::ComputeError(err.to_string().into())
+}
+
+/// Import a field exported to the C data interface.
+fn import_field(schema: FFI_ArrowSchema) -> PolarsResult<polars_arrow::datatypes::Field> {
+    // Both are the C data interface's `ArrowSchema`, and the imported schema
+    // takes care of releasing it
+    let schema = unsafe { std::mem::transmute::<FFI_ArrowSchema, ffi::ArrowSchema>(schema) };
+    unsafe { ffi::import_field_from_c(&schema) }
+}
+
+/// The Polars schema of `schema`.
+pub fn to_polars_schema(schema: &ArrowSchema) -> PolarsResult<Schema> {
+    schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let schema = FFI_ArrowSchema::try_from(field.as_ref()).map_err(arrow_error)?;
+            Ok(polars::prelude::Field::from(&import_field(schema)?))
+        })
+        .collect()
+}
+
+/// The Polars series of `array`, named after `field`.
+fn to_series(field: &Field, array: &dyn Array) -> PolarsResult<Series> {
+    let (array, schema) = to_ffi(&array.to_data()).map_err(arrow_error)?;
+    let data_type = import_field(schema)?.data_type;
+    let array = unsafe { std::mem::transmute::<FFI_ArrowArray, ffi::ArrowArray>(array) };
+    let array = unsafe { ffi::import_array_from_c(array, data_type)? };
+    Series::try_from((field.name().as_str(), array))
+}
+
+/// The Polars data frame of `batch`.
+pub fn to_data_frame(batch: &RecordBatch) -> PolarsResult<DataFrame> {
+    let columns = batch
+        .schema()
+        .fields()
+        .iter()
+        .zip(batch.columns())
+        .map(|(field, array)| to_series(field, array.as_ref()))
+        .collect::<PolarsResult<Vec<_>>>()?;
+    DataFrame::new(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Float64Array, Int32Array, StringArray};
+    use arrow_schema::DataType;
+
+    use super::*;
+
+    #[test]
+    fn test_to_data_frame() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new("s", DataType::Utf8, true),
+            Field::new("f", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec![Some("a"), None, Some("c")])),
+                Arc::new(Float64Array::from(vec![Some(0.5), Some(1.5), None])),
+            ],
+        )
+        .unwrap();
+
+        let polars_schema = to_polars_schema(&schema).unwrap();
+        assert_eq!(polars_schema.len(), 3);
+        assert_eq!(
+            polars_schema.get("i"),
+            Some(&polars::prelude::DataType::Int32)
+        );
+
+        let df = to_data_frame(&batch).unwrap();
+        assert_eq!(df.shape(), (3, 3));
+        assert_eq!(df.get_column_names(), vec!["i", "s", "f"]);
+        assert_eq!(df.column("s").unwrap().null_count(), 1);
+        let i = df.column("i").unwrap().i32().unwrap();
+        assert_eq!(i.into_no_null_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! A Polars scan source for Lance datasets.
+//!
+//! [`scan_lance`] returns a [`LazyFrame`] that reads a dataset when it is
+//! collected. Polars pushes down to Lance:
+//!
+//! * the columns the query reads,
+//! * the parts of its filters that can be expressed as Lance filters, the
+//!   other parts are applied by Polars after the scan,
+//! * the number of rows it needs, when the whole filter is pushed down.
+//!
+//! ```ignore
+//! let dataset = Arc::new(Dataset::open(uri).await?);
+//! let df = scan_lance(dataset)?
+//!     .filter(col("i").gt(lit(5)))
+//!     .select([col("s")])
+//!     .collect()?;
+//! ```
+
+use std::any::Any;
+use std::sync::Arc;
+
+use futures::TryStreamExt;
+use lance::Dataset;
+use lazy_static::lazy_static;
+use polars::prelude::*;
+
+mod convert;
+mod predicate;
+
+lazy_static! {
+    static ref RT: tokio::runtime::Runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to create tokio runtime");
+}
+
+fn lance_error(err: lance::Error) -> PolarsError {
+    PolarsError::ComputeError(err.to_string().into())
+}
+
+/// A lazy frame reading `dataset`.
+pub fn scan_lance(dataset: Arc<Dataset>) -> PolarsResult<LazyFrame> {
+    let scan = LanceScan::try_new(dataset)?;
+    let args = ScanArgsAnonymous {
+        schema: Some(scan.schema.clone()),
+        name: "LANCE SCAN",
+        ..Default::default()
+    };
+    LazyFrame::anonymous_scan(Arc::new(scan), args)
+}
+
+/// The [`AnonymousScan`] of a dataset, see [`scan_lance`].
+pub struct LanceScan {
+    dataset: Arc<Dataset>,
+    schema: SchemaRef,
+}
+
+impl LanceScan {
+    pub fn try_new(dataset: Arc<Dataset>) -> PolarsResult<Self> {
+        let arrow_schema = arrow_schema::Schema::from(dataset.schema());
+        let schema = Arc::new(convert::to_polars_schema(&arrow_schema)?);
+        Ok(Self { dataset, schema })
+    }
+}
+
+impl AnonymousScan for LanceScan {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn scan(&self, scan_opts: AnonymousScanArgs) -> PolarsResult<DataFrame> {
+        let mut scanner = self.dataset.scan();
+        if let Some(columns) = &scan_opts.with_columns {
+            let columns = columns.iter().map(String::as_str).collect::<Vec<_>>();
+            scanner.project(&columns).map_err(lance_error)?;
+        }
+        let (filter, remaining) = match &scan_opts.predicate {
+            Some(predicate) => predicate::split(predicate),
+            None => (None, None),
+        };
+        if let Some(filter) = &filter {
+            scanner.filter(filter).map_err(lance_error)?;
+        }
+        // Rows Polars filters out after the scan can't count towards the limit
+        if let (Some(n_rows), None) = (scan_opts.n_rows, &remaining) {
+            scanner
+                .limit(Some(n_rows as i64), None)
+                .map_err(lance_error)?;
+        }
+
+        let batches = RT
+            .block_on(async {
+                scanner
+                    .try_into_stream()
+                    .await?
+                    .try_collect::<Vec<_>>()
+                    .await
+            })
+            .map_err(lance_error)?;
+        let mut df = match scan_opts.output_schema.as_ref() {
+            Some(schema) => DataFrame::from(schema.as_ref()),
+            None => DataFrame::from(scan_opts.schema.as_ref()),
+        };
+        for (i, batch) in batches.iter().enumerate() {
+            let batch_df = convert::to_data_frame(batch)?;
+            if i == 0 {
+                df = batch_df;
+            } else {
+                df.vstack_mut(&batch_df)?;
+            }
+        }
+        if let Some(columns) = &scan_opts.with_columns {
+            df = df.select(columns.iter().map(String::as_str))?;
+        }
+        if let Some(remaining) = remaining {
+            df = df.lazy().filter(remaining).collect()?;
+        }
+        if let Some(n_rows) = scan_opts.n_rows {
+            df = df.head(Some(n_rows));
+        }
+        Ok(df)
+    }
+
+    fn schema(&self, _infer_schema_length: Option<usize>) -> PolarsResult<SchemaRef> {
+        Ok(self.schema.clone())
+    }
+
+    fn allows_predicate_pushdown(&self) -> bool {
+        true
+    }
+
+    fn allows_projection_pushdown(&self) -> bool {
+        true
+    }
+
+    fn allows_slice_pushdown(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator, StringArray};
+    use arrow_schema::{DataType as ArrowDataType, Field as ArrowField, Schema as ArrowSchema};
+    use lance::dataset::WriteParams;
+
+    use super::*;
+
+    #[test]
+    fn test_scan_lance() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", ArrowDataType::Int32, false),
+            ArrowField::new("s", ArrowDataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..30)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..30).map(|i| format!("s-{}", i)),
+                )),
+            ],
+        )
+        .unwrap();
+        let params = WriteParams {
+            max_rows_per_file: 10,
+            ..Default::default()
+        };
+        let dataset = RT
+            .block_on(Dataset::write(
+                RecordBatchIterator::new(vec![Ok(batch)], schema),
+                uri,
+                Some(params),
+            ))
+            .unwrap();
+        let dataset = Arc::new(dataset);
+
+        let df = scan_lance(dataset.clone()).unwrap().collect().unwrap();
+        assert_eq!(df.shape(), (30, 2));
+
+        // The filter on `i` is pushed down, the arithmetic is applied by
+        // Polars, and the limit after both
+        let df = scan_lance(dataset)
+            .unwrap()
+            .filter(col("i").gt_eq(lit(5)).and((col("i") % lit(2)).eq(lit(0))))
+            .select([col("s")])
+            .limit(3)
+            .collect()
+            .unwrap();
+        assert_eq!(df.get_column_names(), vec!["s"]);
+        let s = df.column("s").unwrap().str().unwrap();
+        assert_eq!(
+            s.into_no_null_iter().collect::<Vec<_>>(),
+            vec!["s-6", "s-8", "s-10"]
+        );
+    }
+}
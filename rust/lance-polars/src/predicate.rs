@@ -0,0 +1,136 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Translation of Polars predicates to Lance filters
+
+use polars::prelude::*;
+
+/// Split `predicate` into a Lance filter of the conjuncts Lance can apply,
+/// and an expression of the others, which must be applied by Polars.
+pub fn split(predicate: &Expr) -> (Option<String>, Option<Expr>) {
+    let mut conjuncts = Vec::new();
+    collect_conjuncts(predicate, &mut conjuncts);
+
+    let mut pushed_down = Vec::new();
+    let mut remaining: Option<Expr> = None;
+    for conjunct in conjuncts {
+        match to_sql(conjunct) {
+            Some(sql) => pushed_down.push(sql),
+            None => {
+                remaining = Some(match remaining {
+                    Some(remaining) => remaining.and(conjunct.clone()),
+                    None => conjunct.clone(),
+                })
+            }
+        }
+    }
+    let filter = (!pushed_down.is_empty()).then(|| pushed_down.join(" AND "));
+    (filter, remaining)
+}
+
+fn collect_conjuncts<'a>(expr: &'a Expr, conjuncts: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::BinaryExpr {
+            left,
+            op: Operator::And | Operator::LogicalAnd,
+            right,
+        } => {
+            collect_conjuncts(left, conjuncts);
+            collect_conjuncts(right, conjuncts);
+        }
+        _ => conjuncts.push(expr),
+    }
+}
+
+/// The expression as Lance SQL, or `None` if it can't be expressed.
+fn to_sql(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Column(name) => Some(format!("`{}`", name.replace('`', "``"))),
+        Expr::Literal(value) => literal_to_sql(value),
+        Expr::Alias(expr, _) => to_sql(expr),
+        Expr::BinaryExpr { left, op, right } => {
+            let op = match op {
+                Operator::Eq => "=",
+                Operator::NotEq => "!=",
+                Operator::Lt => "<",
+                Operator::LtEq => "<=",
+                Operator::Gt => ">",
+                Operator::GtEq => ">=",
+                Operator::And | Operator::LogicalAnd => "AND",
+                Operator::Or | Operator::LogicalOr => "OR",
+                _ => return None,
+            };
+            Some(format!("({} {} {})", to_sql(left)?, op, to_sql(right)?))
+        }
+        Expr::Function {
+            input,
+            function: FunctionExpr::Boolean(function),
+            ..
+        } => {
+            let [input] = input.as_slice() else {
+                return None;
+            };
+            let input = to_sql(input)?;
+            match function {
+                BooleanFunction::IsNull => Some(format!("({} IS NULL)", input)),
+                BooleanFunction::IsNotNull => Some(format!("({} IS NOT NULL)", input)),
+                BooleanFunction::Not => Some(format!("(NOT {})", input)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn literal_to_sql(value: &LiteralValue) -> Option<String> {
+    match value {
+        LiteralValue::Boolean(value) => Some(value.to_string()),
+        LiteralValue::Int8(value) => Some(value.to_string()),
+        LiteralValue::Int16(value) => Some(value.to_string()),
+        LiteralValue::Int32(value) => Some(value.to_string()),
+        LiteralValue::Int64(value) => Some(value.to_string()),
+        LiteralValue::UInt8(value) => Some(value.to_string()),
+        LiteralValue::UInt16(value) => Some(value.to_string()),
+        LiteralValue::UInt32(value) => Some(value.to_string()),
+        LiteralValue::UInt64(value) => Some(value.to_string()),
+        // Literals whose type is inferred from the column they are compared to
+        LiteralValue::Int(value) => Some(value.to_string()),
+        // NaN and infinities have no SQL literal
+        LiteralValue::Float32(value) if value.is_finite() => Some(format!("{:?}", value)),
+        LiteralValue::Float64(value) if value.is_finite() => Some(format!("{:?}", value)),
+        LiteralValue::Float(value) if value.is_finite() => Some(format!("{:?}", value)),
+        LiteralValue::String(value) => Some(format!("'{}'", value.replace('\'', "''"))),
+        // Comparisons with nulls are null in SQL, but not in Polars
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let predicate = col("i")
+            .gt_eq(lit(5))
+            .and(col("s").eq(lit("it's")).or(col("s").is_null()))
+            .and(col("x").not());
+        let (filter, remaining) = split(&predicate);
+        assert_eq!(
+            filter.unwrap(),
+            "(`i` >= 5) AND ((`s` = 'it''s') OR (`s` IS NULL)) AND (NOT `x`)"
+        );
+        assert!(remaining.is_none());
+
+        // Arithmetic is left to Polars
+        let even = (col("i") % lit(2)).eq(lit(0));
+        let predicate = col("i").lt(lit(2.5)).and(even.clone());
+        let (filter, remaining) = split(&predicate);
+        assert_eq!(filter.unwrap(), "(`i` < 2.5)");
+        assert_eq!(remaining.unwrap(), even);
+
+        let (filter, remaining) = split(&even);
+        assert!(filter.is_none());
+        assert_eq!(remaining.unwrap(), even);
+    }
+}
@@ -18,5 +18,15 @@ fn main() -> Result<()> {
         &["./protos"],
     )?;
 
+    // The gRPC server and client are only generated when they are used
+    #[cfg(feature = "coordinator")]
+    tonic_build::configure()
+        .protoc_arg("--experimental_allow_proto3_optional")
+        .compile(&["./protos/commit_coordinator.proto"], &["./protos"])?;
+    #[cfg(not(feature = "coordinator"))]
+    prost_build::Config::new()
+        .protoc_arg("--experimental_allow_proto3_optional")
+        .compile_protos(&["./protos/commit_coordinator.proto"], &["./protos"])?;
+
     Ok(())
 }
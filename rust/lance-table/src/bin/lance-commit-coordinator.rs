@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! A commit coordinator for writers that share tables on an object store
+//! without conditional writes.
+//!
+//! Usage: `lance-commit-coordinator [ADDRESS]`. It listens on `0.0.0.0:50051`
+//! by default. Writers connect to it with a `GrpcCoordinatorClient`.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use lance_table::io::commit::lease::LeaseCoordinator;
+use tonic::transport::Server;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let addr: SocketAddr = std::env::args()
+        .nth(1)
+        .as_deref()
+        .unwrap_or("0.0.0.0:50051")
+        .parse()?;
+    Server::builder()
+        .add_service(Arc::new(LeaseCoordinator::new()).into_server())
+        .serve(addr)
+        .await?;
+    Ok(())
+}
@@ -5,7 +5,7 @@
 
 use snafu::{location, Location};
 
-use crate::format::Manifest;
+use crate::format::{Index, Manifest};
 use lance_core::{Error, Result};
 
 /// Fragments may contain deletion files, which record the tombstones of
@@ -14,9 +14,45 @@ pub const FLAG_DELETION_FILES: u64 = 1;
 /// Row ids are table after moves, but not updates. Fragments contain an index
 /// mapping row ids to row addresses.
 pub const FLAG_MOVE_STABLE_ROW_IDS: u64 = 2;
+/// Indices may have a kind, such as MinHash or composite indices, and must
+/// not be used as the btree indices they would otherwise look like.
+pub const FLAG_INDEX_KINDS: u64 = 4;
+/// The manifest holds a dataset configuration, which writers must carry over.
+pub const FLAG_TABLE_CONFIG: u64 = 8;
+/// The dataset is partitioned, and writers must carry over the partitioning
+/// and the partitions of fragments.
+pub const FLAG_PARTITIONING: u64 = 16;
 
-/// Set the reader and writer feature flags in the manifest based on the contents of the manifest.
-pub fn apply_feature_flags(manifest: &mut Manifest) -> Result<()> {
+/// The reader feature flags this library supports.
+const READER_FEATURE_FLAGS: u64 = FLAG_DELETION_FILES | FLAG_MOVE_STABLE_ROW_IDS | FLAG_INDEX_KINDS;
+/// The writer feature flags this library supports.
+const WRITER_FEATURE_FLAGS: u64 = FLAG_DELETION_FILES
+    | FLAG_MOVE_STABLE_ROW_IDS
+    | FLAG_INDEX_KINDS
+    | FLAG_TABLE_CONFIG
+    | FLAG_PARTITIONING;
+
+/// The names of the features in `flags`, in the order of their bits.
+///
+/// Bits this library doesn't know about are listed as `unknown(<bit value>)`.
+pub fn feature_flag_names(flags: u64) -> Vec<String> {
+    (0..u64::BITS)
+        .map(|bit| 1 << bit)
+        .filter(|flag| flags & flag != 0)
+        .map(|flag| match flag {
+            FLAG_DELETION_FILES => "deletion_files".to_string(),
+            FLAG_MOVE_STABLE_ROW_IDS => "move_stable_row_ids".to_string(),
+            FLAG_INDEX_KINDS => "index_kinds".to_string(),
+            FLAG_TABLE_CONFIG => "table_config".to_string(),
+            FLAG_PARTITIONING => "partitioning".to_string(),
+            _ => format!("unknown({})", flag),
+        })
+        .collect()
+}
+
+/// Set the reader and writer feature flags in the manifest based on the contents of the manifest
+/// and of its indices.
+pub fn apply_feature_flags(manifest: &mut Manifest, indices: &[Index]) -> Result<()> {
     // Reset flags
     manifest.reader_feature_flags = 0;
     manifest.writer_feature_flags = 0;
@@ -51,21 +87,49 @@ pub fn apply_feature_flags(manifest: &mut Manifest) -> Result<()> {
         manifest.writer_feature_flags |= FLAG_MOVE_STABLE_ROW_IDS;
     }
 
+    // Older readers would use these indices as btree indices, and older
+    // writers would drop their kind.
+    let has_index_kinds = indices
+        .iter()
+        .any(|index| index.kind.as_ref().is_some_and(|kind| !kind.is_empty()));
+    if has_index_kinds {
+        manifest.reader_feature_flags |= FLAG_INDEX_KINDS;
+        manifest.writer_feature_flags |= FLAG_INDEX_KINDS;
+    }
+
+    // Older writers would drop the configuration and the partitioning, which
+    // readers can do without.
+    if !manifest.config.is_empty() {
+        manifest.writer_feature_flags |= FLAG_TABLE_CONFIG;
+    }
+    let has_partitioning = manifest.partitioning.is_some()
+        || manifest
+            .fragments
+            .iter()
+            .any(|frag| frag.partition.is_some());
+    if has_partitioning {
+        manifest.writer_feature_flags |= FLAG_PARTITIONING;
+    }
+
     Ok(())
 }
 
 pub fn can_read_dataset(reader_flags: u64) -> bool {
-    reader_flags <= 3
+    reader_flags & !READER_FEATURE_FLAGS == 0
 }
 
 pub fn can_write_dataset(writer_flags: u64) -> bool {
-    writer_flags <= 3
+    writer_flags & !WRITER_FEATURE_FLAGS == 0
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::format::{Fragment, PartitionScheme, Partitioning};
+    use lance_core::datatypes::Schema;
+    use std::sync::Arc;
+
     #[test]
     fn test_read_check() {
         assert!(can_read_dataset(0));
@@ -74,7 +138,9 @@ mod tests {
         assert!(can_read_dataset(
             super::FLAG_DELETION_FILES | super::FLAG_MOVE_STABLE_ROW_IDS
         ));
-        assert!(!can_read_dataset(super::FLAG_MOVE_STABLE_ROW_IDS << 1));
+        assert!(can_read_dataset(super::FLAG_INDEX_KINDS));
+        assert!(!can_read_dataset(super::FLAG_INDEX_KINDS << 1));
+        assert!(!can_read_dataset(super::FLAG_PARTITIONING << 1));
     }
 
     #[test]
@@ -85,6 +151,52 @@ mod tests {
         assert!(can_write_dataset(
             super::FLAG_DELETION_FILES | super::FLAG_MOVE_STABLE_ROW_IDS
         ));
-        assert!(!can_write_dataset(super::FLAG_MOVE_STABLE_ROW_IDS << 1));
+        assert!(can_write_dataset(
+            super::FLAG_INDEX_KINDS | super::FLAG_TABLE_CONFIG | super::FLAG_PARTITIONING
+        ));
+        assert!(!can_write_dataset(super::FLAG_PARTITIONING << 1));
+    }
+
+    #[test]
+    fn test_feature_flag_names() {
+        assert!(feature_flag_names(0).is_empty());
+        assert_eq!(
+            feature_flag_names(FLAG_DELETION_FILES | FLAG_MOVE_STABLE_ROW_IDS | 32),
+            vec!["deletion_files", "move_stable_row_ids", "unknown(32)"]
+        );
+        assert_eq!(
+            feature_flag_names(FLAG_INDEX_KINDS | FLAG_TABLE_CONFIG | FLAG_PARTITIONING),
+            vec!["index_kinds", "table_config", "partitioning"]
+        );
+    }
+
+    #[test]
+    fn test_apply_feature_flags() {
+        let mut manifest = Manifest::new(Schema::default(), Arc::new(vec![Fragment::new(0)]));
+        apply_feature_flags(&mut manifest, &[]).unwrap();
+        assert_eq!(manifest.reader_feature_flags, 0);
+        assert_eq!(manifest.writer_feature_flags, 0);
+
+        let index = Index {
+            uuid: uuid::Uuid::new_v4(),
+            fields: vec![0],
+            name: "idx".to_string(),
+            dataset_version: 1,
+            fragment_bitmap: None,
+            kind: Some("minhash".to_string()),
+        };
+        manifest
+            .config
+            .insert("key".to_string(), "value".to_string());
+        manifest.partitioning = Some(Partitioning {
+            field_id: 0,
+            scheme: PartitionScheme::Hash { num_buckets: 4 },
+        });
+        apply_feature_flags(&mut manifest, &[index]).unwrap();
+        assert_eq!(manifest.reader_feature_flags, FLAG_INDEX_KINDS);
+        assert_eq!(
+            manifest.writer_feature_flags,
+            FLAG_INDEX_KINDS | FLAG_TABLE_CONFIG | FLAG_PARTITIONING
+        );
     }
 }
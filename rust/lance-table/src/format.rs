@@ -8,10 +8,17 @@ use uuid::Uuid;
 mod fragment;
 mod index;
 mod manifest;
+mod partitioning;
+mod sketch;
 
 pub use fragment::*;
 pub use index::Index;
 pub use manifest::{Manifest, SelfDescribingFileReader, WriterVersion};
+pub use partitioning::{PartitionScheme, Partitioning};
+pub use sketch::{
+    hash_values, ColumnSketch, CountMinSketch, DataFileSketches, HyperLogLog, CMS_DEPTH, CMS_WIDTH,
+    HLL_PRECISION, MAX_CANDIDATES,
+};
 
 use lance_core::{Error, Result};
 
@@ -63,7 +63,7 @@ impl DataFile {
     }
 
     pub fn schema(&self, full_schema: &Schema) -> Schema {
-        full_schema.project_by_ids(&self.fields)
+        full_schema.project_by_ids_strict(&self.fields)
     }
 
     pub fn is_legacy_file(&self) -> bool {
@@ -226,6 +226,17 @@ pub struct Fragment {
     /// unknown. This is only optional for legacy reasons. All new tables should
     /// have this set.
     pub physical_rows: Option<usize>,
+
+    /// The partition of the rows of this fragment, if the dataset is
+    /// partitioned. If this is None, the fragment may hold rows of any
+    /// partition.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partition: Option<u32>,
+
+    /// Whether the fragment holds a small append, to be merged into a larger
+    /// fragment, see `WriteParams::delta_log`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub delta: bool,
 }
 
 impl Fragment {
@@ -236,6 +247,8 @@ impl Fragment {
             deletion_file: None,
             row_id_meta: None,
             physical_rows: None,
+            partition: None,
+            delta: false,
         }
     }
 
@@ -273,6 +286,8 @@ impl Fragment {
             deletion_file: None,
             physical_rows,
             row_id_meta: None,
+            partition: None,
+            delta: false,
         }
     }
 
@@ -322,6 +337,8 @@ impl TryFrom<pb::DataFragment> for Fragment {
             deletion_file: p.deletion_file.map(DeletionFile::try_from).transpose()?,
             row_id_meta: p.row_id_sequence.map(RowIdMeta::try_from).transpose()?,
             physical_rows,
+            partition: p.partition,
+            delta: p.delta,
         })
     }
 }
@@ -358,6 +375,8 @@ impl From<&Fragment> for pb::DataFragment {
             deletion_file,
             row_id_sequence,
             physical_rows: f.physical_rows.unwrap_or_default() as u64,
+            partition: f.partition,
+            delta: f.delta,
         }
     }
 }
@@ -30,6 +30,11 @@ pub struct Index {
     ///
     /// If this is None, then this is unknown.
     pub fragment_bitmap: Option<RoaringBitmap>,
+
+    /// The kind of index, for indices that can't be told apart by their files.
+    ///
+    /// None for btree and vector indices.
+    pub kind: Option<String>,
 }
 
 impl DeepSizeOf for Index {
@@ -43,6 +48,7 @@ impl DeepSizeOf for Index {
                 .as_ref()
                 .map(|fragment_bitmap| fragment_bitmap.serialized_size())
                 .unwrap_or(0)
+            + self.kind.deep_size_of_children(context)
     }
 }
 
@@ -69,6 +75,7 @@ impl TryFrom<pb::IndexMetadata> for Index {
             fields: proto.fields,
             dataset_version: proto.dataset_version,
             fragment_bitmap,
+            kind: Some(proto.kind).filter(|kind| !kind.is_empty()),
         })
     }
 }
@@ -91,6 +98,7 @@ impl From<&Index> for pb::IndexMetadata {
             fields: idx.fields.clone(),
             dataset_version: idx.dataset_version,
             fragment_bitmap,
+            kind: idx.kind.clone().unwrap_or_default(),
         }
     }
 }
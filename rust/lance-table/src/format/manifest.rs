@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
+use std::collections::HashMap;
 use std::ops::Range;
 use std::sync::Arc;
 
@@ -12,7 +13,7 @@ use lance_io::traits::{ProtoStruct, Reader};
 use object_store::path::Path;
 use prost_types::Timestamp;
 
-use super::Fragment;
+use super::{Fragment, Partitioning};
 use crate::feature_flags::FLAG_MOVE_STABLE_ROW_IDS;
 use crate::format::pb;
 use lance_core::cache::FileMetadataCache;
@@ -72,6 +73,12 @@ pub struct Manifest {
 
     /// The max row id used so far.
     pub next_row_id: u64,
+
+    /// Dataset-level configuration, carried over to each new version.
+    pub config: HashMap<String, String>,
+
+    /// How rows are bucketed into fragments, if they are.
+    pub partitioning: Option<Partitioning>,
 }
 
 fn compute_fragment_offsets(fragments: &[Fragment]) -> Vec<usize> {
@@ -105,6 +112,8 @@ impl Manifest {
             transaction_file: None,
             fragment_offsets,
             next_row_id: 0,
+            config: HashMap::new(),
+            partitioning: None,
         }
     }
 
@@ -130,6 +139,8 @@ impl Manifest {
             transaction_file: None,
             fragment_offsets,
             next_row_id: previous.next_row_id,
+            config: previous.config.clone(),
+            partitioning: previous.partitioning.clone(),
         }
     }
 
@@ -390,6 +401,8 @@ impl TryFrom<pb::Manifest> for Manifest {
             },
             fragment_offsets,
             next_row_id: p.next_row_id,
+            config: p.config,
+            partitioning: p.partitioning.map(Partitioning::try_from).transpose()?,
         })
     }
 }
@@ -428,6 +441,8 @@ impl From<&Manifest> for pb::Manifest {
             max_fragment_id: m.max_fragment_id,
             transaction_file: m.transaction_file.clone().unwrap_or_default(),
             next_row_id: m.next_row_id,
+            config: m.config.clone(),
+            partitioning: m.partitioning.as_ref().map(pb::Partitioning::from),
         }
     }
 }
@@ -590,6 +605,8 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: None,
+                partition: None,
+                delta: false,
             },
             Fragment {
                 id: 1,
@@ -600,6 +617,8 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: None,
+                partition: None,
+                delta: false,
             },
         ];
 
@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Metadata for partitioned datasets
+
+use deepsize::DeepSizeOf;
+use snafu::{location, Location};
+
+use super::pb;
+use lance_core::utils::hash::fnv1a;
+use lance_core::{Error, Result};
+
+/// How rows are bucketed into partitions by the value of a column
+#[derive(Debug, Clone, PartialEq, Eq, DeepSizeOf)]
+pub enum PartitionScheme {
+    /// Rows are bucketed by the hash of their value
+    Hash { num_buckets: u32 },
+    /// Partition `i` holds the values in `[bounds[i - 1], bounds[i])`
+    Range { bounds: Vec<i64> },
+}
+
+impl PartitionScheme {
+    /// Number of partitions of this scheme
+    pub fn num_partitions(&self) -> u32 {
+        match self {
+            Self::Hash { num_buckets } => *num_buckets,
+            Self::Range { bounds } => bounds.len() as u32 + 1,
+        }
+    }
+
+    /// Check that the scheme has at least one partition and sorted bounds.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::Hash { num_buckets: 0 } => Err(Error::invalid_input(
+                "Hash partitioning needs at least one bucket",
+                location!(),
+            )),
+            Self::Range { bounds } if bounds.windows(2).any(|w| w[0] >= w[1]) => {
+                Err(Error::invalid_input(
+                    "The bounds of range partitioning must be sorted and distinct",
+                    location!(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The partition of an integer value
+    pub fn partition_of_int(&self, value: i64) -> u32 {
+        match self {
+            Self::Hash { num_buckets } => {
+                (fnv1a(&value.to_le_bytes()) % *num_buckets as u64) as u32
+            }
+            Self::Range { bounds } => bounds.partition_point(|bound| *bound <= value) as u32,
+        }
+    }
+
+    /// The partition of a string value, if the scheme supports strings
+    pub fn partition_of_str(&self, value: &str) -> Option<u32> {
+        match self {
+            Self::Hash { num_buckets } => {
+                Some((fnv1a(value.as_bytes()) % *num_buckets as u64) as u32)
+            }
+            Self::Range { .. } => None,
+        }
+    }
+
+    /// The partitions that may hold values in `[lower, upper]`, if the scheme
+    /// orders partitions by value
+    pub fn partitions_in_range(&self, lower: Option<i64>, upper: Option<i64>) -> Option<Vec<u32>> {
+        match self {
+            Self::Hash { .. } => None,
+            Self::Range { .. } => {
+                let first = lower.map(|v| self.partition_of_int(v)).unwrap_or(0);
+                let last = upper
+                    .map(|v| self.partition_of_int(v))
+                    .unwrap_or(self.num_partitions() - 1);
+                Some((first..=last).collect())
+            }
+        }
+    }
+}
+
+/// Partitioning of a dataset
+///
+/// Each fragment written to a partitioned dataset holds the rows of a single
+/// partition, recorded in [`super::Fragment::partition`].
+#[derive(Debug, Clone, PartialEq, Eq, DeepSizeOf)]
+pub struct Partitioning {
+    /// The id of the partitioning column
+    pub field_id: i32,
+    pub scheme: PartitionScheme,
+}
+
+impl TryFrom<pb::Partitioning> for Partitioning {
+    type Error = Error;
+
+    fn try_from(proto: pb::Partitioning) -> Result<Self> {
+        let scheme = match proto.scheme {
+            Some(pb::partitioning::Scheme::Hash(hash)) => PartitionScheme::Hash {
+                num_buckets: hash.num_buckets,
+            },
+            Some(pb::partitioning::Scheme::Range(range)) => PartitionScheme::Range {
+                bounds: range.bounds,
+            },
+            None => {
+                return Err(Error::io(
+                    "partitioning scheme does not exist in the manifest".to_string(),
+                    location!(),
+                ))
+            }
+        };
+        Ok(Self {
+            field_id: proto.field_id,
+            scheme,
+        })
+    }
+}
+
+impl From<&Partitioning> for pb::Partitioning {
+    fn from(partitioning: &Partitioning) -> Self {
+        let scheme = match &partitioning.scheme {
+            PartitionScheme::Hash { num_buckets } => {
+                pb::partitioning::Scheme::Hash(pb::HashPartitioning {
+                    num_buckets: *num_buckets,
+                })
+            }
+            PartitionScheme::Range { bounds } => {
+                pb::partitioning::Scheme::Range(pb::RangePartitioning {
+                    bounds: bounds.clone(),
+                })
+            }
+        };
+        Self {
+            field_id: partitioning.field_id,
+            scheme: Some(scheme),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partition_of() {
+        let range = PartitionScheme::Range {
+            bounds: vec![0, 10, 20],
+        };
+        assert_eq!(range.num_partitions(), 4);
+        assert_eq!(range.partition_of_int(-5), 0);
+        assert_eq!(range.partition_of_int(0), 1);
+        assert_eq!(range.partition_of_int(19), 2);
+        assert_eq!(range.partition_of_int(20), 3);
+        assert_eq!(range.partition_of_str("a"), None);
+        assert_eq!(
+            range.partitions_in_range(Some(5), None),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(
+            range.partitions_in_range(None, Some(10)),
+            Some(vec![0, 1, 2])
+        );
+
+        let hash = PartitionScheme::Hash { num_buckets: 8 };
+        // The hashes are part of the format, so they must not change
+        assert_eq!(
+            hash.partition_of_int(1),
+            (fnv1a(&1_i64.to_le_bytes()) % 8) as u32
+        );
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash.partition_of_str("a"), Some(4));
+        assert_eq!(hash.partitions_in_range(Some(0), Some(1)), None);
+
+        assert!(PartitionScheme::Hash { num_buckets: 0 }.validate().is_err());
+        assert!(PartitionScheme::Range { bounds: vec![1, 1] }
+            .validate()
+            .is_err());
+        assert!(range.validate().is_ok());
+    }
+
+    #[test]
+    fn test_roundtrip_partitioning() {
+        for scheme in [
+            PartitionScheme::Hash { num_buckets: 4 },
+            PartitionScheme::Range {
+                bounds: vec![1, 2, 3],
+            },
+        ] {
+            let partitioning = Partitioning {
+                field_id: 3,
+                scheme,
+            };
+            let proto = pb::Partitioning::from(&partitioning);
+            assert_eq!(Partitioning::try_from(proto).unwrap(), partitioning);
+        }
+    }
+}
@@ -0,0 +1,410 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Count-distinct and heavy-hitters sketches of column values
+//!
+//! A [`ColumnSketch`] summarizes the values of a column with a HyperLogLog
+//! sketch, which estimates the number of distinct values, and a Count-Min
+//! sketch with a list of candidate heavy hitters, which estimates the most
+//! frequent values.  Sketches of disjoint sets of rows can be merged, so
+//! sketches kept per data file answer questions about the whole dataset
+//! without scanning it.
+
+use std::collections::{BTreeMap, HashMap};
+
+use arrow::compute::{concat, take};
+use arrow_array::{cast::AsArray, new_empty_array, Array, ArrayRef, UInt32Array};
+use arrow_schema::DataType;
+use deepsize::DeepSizeOf;
+use snafu::{location, Location};
+
+use lance_core::utils::hash::fnv1a;
+use lance_core::{Error, Result};
+
+/// Number of bits of the hashes that pick a HyperLogLog register
+pub const HLL_PRECISION: u32 = 12;
+/// Number of rows of the Count-Min sketches
+pub const CMS_DEPTH: usize = 4;
+/// Number of counters in each row of the Count-Min sketches
+pub const CMS_WIDTH: usize = 2048;
+/// Number of candidate heavy hitters kept by a [`ColumnSketch`]
+pub const MAX_CANDIDATES: usize = 64;
+
+/// A HyperLogLog sketch, estimating the number of distinct hashes
+///
+/// The standard error of the estimate is about 1.6%.
+#[derive(Debug, Clone, PartialEq, Eq, DeepSizeOf)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; 1 << HLL_PRECISION],
+        }
+    }
+}
+
+impl HyperLogLog {
+    /// A sketch with the given registers, or `None` if there are not
+    /// `2^HLL_PRECISION` of them
+    pub fn from_registers(registers: Vec<u8>) -> Option<Self> {
+        (registers.len() == 1 << HLL_PRECISION).then_some(Self { registers })
+    }
+
+    pub fn registers(&self) -> &[u8] {
+        &self.registers
+    }
+
+    pub fn insert(&mut self, hash: u64) {
+        let register = (hash >> (64 - HLL_PRECISION)) as usize;
+        // The position of the first set bit of the remaining bits
+        let rank = ((hash << HLL_PRECISION).leading_zeros() + 1).min(64 - HLL_PRECISION + 1);
+        self.registers[register] = self.registers[register].max(rank as u8);
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (register, other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(*other);
+        }
+    }
+
+    /// The estimated number of distinct hashes inserted
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum = self
+            .registers
+            .iter()
+            .map(|register| 2f64.powi(-(*register as i32)))
+            .sum::<f64>();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate for small cardinalities
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+/// A Count-Min sketch, estimating how many times each hash was inserted
+///
+/// Estimates never undercount, and overcount by at most `e / CMS_WIDTH` of
+/// the inserted hashes with probability `1 - e^-CMS_DEPTH`.
+#[derive(Debug, Clone, PartialEq, Eq, DeepSizeOf)]
+pub struct CountMinSketch {
+    counters: Vec<u64>,
+}
+
+impl Default for CountMinSketch {
+    fn default() -> Self {
+        Self {
+            counters: vec![0; CMS_DEPTH * CMS_WIDTH],
+        }
+    }
+}
+
+impl CountMinSketch {
+    /// A sketch with the given counters, or `None` if there are not
+    /// `CMS_DEPTH * CMS_WIDTH` of them
+    pub fn from_counters(counters: Vec<u64>) -> Option<Self> {
+        (counters.len() == CMS_DEPTH * CMS_WIDTH).then_some(Self { counters })
+    }
+
+    pub fn counters(&self) -> &[u64] {
+        &self.counters
+    }
+
+    fn positions(hash: u64) -> impl Iterator<Item = usize> {
+        // Derive the hash of each row from two halves of the hash
+        let (low, high) = (hash as u32 as u64, hash >> 32);
+        (0..CMS_DEPTH).map(move |row| {
+            row * CMS_WIDTH
+                + (low.wrapping_add((row as u64).wrapping_mul(high)) as usize % CMS_WIDTH)
+        })
+    }
+
+    pub fn insert(&mut self, hash: u64) {
+        for position in Self::positions(hash) {
+            self.counters[position] += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (counter, other) in self.counters.iter_mut().zip(&other.counters) {
+            *counter += other;
+        }
+    }
+
+    /// The estimated number of times `hash` was inserted
+    pub fn estimate(&self, hash: u64) -> u64 {
+        Self::positions(hash)
+            .map(|position| self.counters[position])
+            .min()
+            .unwrap_or_default()
+    }
+}
+
+/// Count-distinct and heavy-hitters sketch of the values of a column
+///
+/// Null values are not counted.
+#[derive(Debug, Clone)]
+pub struct ColumnSketch {
+    /// Number of non-null values
+    pub num_values: u64,
+    pub distinct: HyperLogLog,
+    pub frequencies: CountMinSketch,
+    /// The values with the highest estimated frequency, in no particular order
+    pub candidates: ArrayRef,
+}
+
+impl DeepSizeOf for ColumnSketch {
+    fn deep_size_of_children(&self, context: &mut deepsize::Context) -> usize {
+        self.distinct.deep_size_of_children(context)
+            + self.frequencies.deep_size_of_children(context)
+            + self.candidates.get_array_memory_size()
+    }
+}
+
+impl ColumnSketch {
+    /// An empty sketch of a column of `data_type`
+    pub fn try_new(data_type: &DataType) -> Result<Self> {
+        if !Self::supports(data_type) {
+            return Err(Error::invalid_input(
+                format!("Sketches are not supported for columns of type {data_type}"),
+                location!(),
+            ));
+        }
+        Ok(Self {
+            num_values: 0,
+            distinct: HyperLogLog::default(),
+            frequencies: CountMinSketch::default(),
+            candidates: new_empty_array(data_type),
+        })
+    }
+
+    /// Whether columns of `data_type` can be sketched
+    pub fn supports(data_type: &DataType) -> bool {
+        data_type.primitive_width().is_some()
+            || matches!(
+                data_type,
+                DataType::Boolean
+                    | DataType::Utf8
+                    | DataType::LargeUtf8
+                    | DataType::Binary
+                    | DataType::LargeBinary
+                    | DataType::FixedSizeBinary(_)
+            )
+    }
+
+    /// Add values to the sketch
+    pub fn update(&mut self, values: &dyn Array) -> Result<()> {
+        let hashes = hash_values(values)?;
+        // The first position of each distinct value in this batch
+        let mut positions = HashMap::new();
+        for (position, hash) in hashes.iter().enumerate() {
+            if let Some(hash) = hash {
+                self.num_values += 1;
+                self.distinct.insert(*hash);
+                self.frequencies.insert(*hash);
+                positions.entry(*hash).or_insert(position as u32);
+            }
+        }
+        let positions = UInt32Array::from_iter_values(positions.into_values());
+        let new_values = take(values, &positions, None)?;
+        self.candidates = concat(&[self.candidates.as_ref(), new_values.as_ref()])?;
+        self.prune_candidates()
+    }
+
+    /// Merge the sketch of other rows of the same column into this one
+    pub fn merge(&mut self, other: &Self) -> Result<()> {
+        self.num_values += other.num_values;
+        self.distinct.merge(&other.distinct);
+        self.frequencies.merge(&other.frequencies);
+        self.candidates = concat(&[self.candidates.as_ref(), other.candidates.as_ref()])?;
+        self.prune_candidates()
+    }
+
+    /// The estimated number of distinct values
+    pub fn count_distinct(&self) -> u64 {
+        self.distinct.estimate().min(self.num_values)
+    }
+
+    /// The `k` values with the highest estimated frequency, most frequent
+    /// first, and their estimated frequencies.
+    ///
+    /// Only the [`MAX_CANDIDATES`] most frequent values are tracked.
+    pub fn top_k(&self, k: usize) -> Result<(ArrayRef, Vec<u64>)> {
+        let mut ranked = self.ranked_candidates()?;
+        ranked.truncate(k);
+        let positions = UInt32Array::from_iter_values(ranked.iter().map(|(pos, _)| *pos));
+        let values = take(self.candidates.as_ref(), &positions, None)?;
+        Ok((values, ranked.into_iter().map(|(_, count)| count).collect()))
+    }
+
+    /// The distinct candidates by decreasing estimated frequency
+    fn ranked_candidates(&self) -> Result<Vec<(u32, u64)>> {
+        let mut seen = HashMap::new();
+        for (position, hash) in hash_values(self.candidates.as_ref())?
+            .into_iter()
+            .enumerate()
+        {
+            if let Some(hash) = hash {
+                seen.entry(hash).or_insert(position as u32);
+            }
+        }
+        let mut ranked = seen
+            .into_iter()
+            .map(|(hash, position)| (position, self.frequencies.estimate(hash)))
+            .collect::<Vec<_>>();
+        ranked.sort_by(|(left_pos, left), (right_pos, right)| {
+            right.cmp(left).then(left_pos.cmp(right_pos))
+        });
+        Ok(ranked)
+    }
+
+    fn prune_candidates(&mut self) -> Result<()> {
+        let mut ranked = self.ranked_candidates()?;
+        if ranked.len() == self.candidates.len() && ranked.len() <= MAX_CANDIDATES {
+            return Ok(());
+        }
+        ranked.truncate(MAX_CANDIDATES);
+        let positions = UInt32Array::from_iter_values(ranked.iter().map(|(pos, _)| *pos));
+        self.candidates = take(self.candidates.as_ref(), &positions, None)?;
+        Ok(())
+    }
+}
+
+/// Stable 64-bit hashes of the values of an array, `None` for nulls
+///
+/// The hashes are persisted in sketches, so they must not change across
+/// platforms or releases.
+pub fn hash_values(values: &dyn Array) -> Result<Vec<Option<u64>>> {
+    let hash_bytes = |bytes: &[u8]| mix(fnv1a(bytes));
+    let hashes = match values.data_type() {
+        DataType::Boolean => values
+            .as_boolean()
+            .iter()
+            .map(|value| value.map(|value| hash_bytes(&[value as u8])))
+            .collect(),
+        DataType::Utf8 => values
+            .as_string::<i32>()
+            .iter()
+            .map(|value| value.map(|value| hash_bytes(value.as_bytes())))
+            .collect(),
+        DataType::LargeUtf8 => values
+            .as_string::<i64>()
+            .iter()
+            .map(|value| value.map(|value| hash_bytes(value.as_bytes())))
+            .collect(),
+        DataType::Binary => values
+            .as_binary::<i32>()
+            .iter()
+            .map(|value| value.map(hash_bytes))
+            .collect(),
+        DataType::LargeBinary => values
+            .as_binary::<i64>()
+            .iter()
+            .map(|value| value.map(hash_bytes))
+            .collect(),
+        DataType::FixedSizeBinary(_) => values
+            .as_fixed_size_binary()
+            .iter()
+            .map(|value| value.map(hash_bytes))
+            .collect(),
+        data_type => {
+            let Some(width) = data_type.primitive_width() else {
+                return Err(Error::invalid_input(
+                    format!("Sketches are not supported for columns of type {data_type}"),
+                    location!(),
+                ));
+            };
+            let data = values.to_data();
+            let buffer = &data.buffers()[0].as_slice()[data.offset() * width..];
+            (0..values.len())
+                .map(|i| {
+                    values
+                        .is_valid(i)
+                        .then(|| hash_bytes(&buffer[i * width..(i + 1) * width]))
+                })
+                .collect()
+        }
+    };
+    Ok(hashes)
+}
+
+/// Spread the bits of a hash, since HyperLogLog relies on its high bits
+fn mix(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^ (hash >> 33)
+}
+
+/// Sketches of some columns of a data file, by field id
+pub type DataFileSketches = BTreeMap<i32, ColumnSketch>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int64Array, StringArray};
+
+    #[test]
+    fn test_hyperloglog() {
+        let mut left = HyperLogLog::default();
+        let mut right = HyperLogLog::default();
+        assert_eq!(left.estimate(), 0);
+        let hashes = hash_values(&Int64Array::from_iter_values(0..100_000)).unwrap();
+        for hash in &hashes[..60_000] {
+            left.insert(hash.unwrap());
+        }
+        for hash in &hashes[40_000..] {
+            right.insert(hash.unwrap());
+        }
+        left.merge(&right);
+        let estimate = left.estimate() as f64;
+        assert!((estimate - 100_000.0).abs() < 5_000.0, "{estimate}");
+
+        let mut small = HyperLogLog::default();
+        for hash in &hashes[..100] {
+            small.insert(hash.unwrap());
+        }
+        assert!((small.estimate() as i64 - 100).abs() <= 3);
+    }
+
+    #[test]
+    fn test_column_sketch() {
+        let mut sketch = ColumnSketch::try_new(&DataType::Utf8).unwrap();
+        // "a" is the most frequent value, then "b", among 1000 distinct values
+        let values = (0..1000)
+            .map(|i| Some(format!("v{i}")))
+            .chain((0..500).map(|_| Some("a".to_string())))
+            .chain((0..200).map(|_| Some("b".to_string())))
+            .chain([None])
+            .collect::<StringArray>();
+        sketch.update(&values.slice(0, 1200)).unwrap();
+        let mut other = ColumnSketch::try_new(&DataType::Utf8).unwrap();
+        other.update(&values.slice(1200, 501)).unwrap();
+        sketch.merge(&other).unwrap();
+
+        assert_eq!(sketch.num_values, 1700);
+        let distinct = sketch.count_distinct() as f64;
+        assert!((distinct - 1002.0).abs() < 50.0, "{distinct}");
+        assert!(sketch.candidates.len() <= MAX_CANDIDATES);
+
+        let (values, counts) = sketch.top_k(2).unwrap();
+        let values = values.as_string::<i32>();
+        assert_eq!(values.value(0), "a");
+        assert_eq!(values.value(1), "b");
+        // Count-Min sketches never undercount
+        assert!(counts[0] >= 500 && counts[0] < 520, "{counts:?}");
+        assert!(counts[1] >= 200 && counts[1] < 220, "{counts:?}");
+
+        assert!(ColumnSketch::try_new(&DataType::Utf8View).is_err());
+    }
+}
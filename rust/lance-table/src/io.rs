@@ -4,3 +4,4 @@
 pub mod commit;
 pub mod deletion;
 pub mod manifest;
+pub mod sketch;
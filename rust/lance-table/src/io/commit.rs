@@ -34,11 +34,13 @@ use futures::{
 };
 use object_store::{path::Path, Error as ObjectStoreError, ObjectStore as OSObjectStore};
 use snafu::{location, Location};
+use tracing::{debug, instrument};
 use url::Url;
 
 #[cfg(feature = "dynamodb")]
 pub mod dynamodb;
 pub mod external_manifest;
+pub mod lease;
 
 use lance_core::{Error, Result};
 use lance_io::object_store::{ObjectStore, ObjectStoreExt, ObjectStoreParams};
@@ -445,6 +447,7 @@ pub async fn commit_handler_from_url(
             let (aws_creds, region) = build_aws_credential(
                 options.s3_credentials_refresh_offset,
                 options.aws_credentials.clone(),
+                options.aws_credential_chain.as_ref(),
                 Some(&storage_options),
                 region,
             )
@@ -518,6 +521,7 @@ pub struct UnsafeCommitHandler;
 
 #[async_trait::async_trait]
 impl CommitHandler for UnsafeCommitHandler {
+    #[instrument(level = "debug", name = "UnsafeCommitHandler::commit", skip_all, fields(version = manifest.version))]
     async fn commit(
         &self,
         manifest: &mut Manifest,
@@ -581,6 +585,7 @@ pub trait CommitLease: Send + Sync {
 
 #[async_trait::async_trait]
 impl<T: CommitLock + Send + Sync> CommitHandler for T {
+    #[instrument(level = "debug", name = "CommitLock::commit", skip_all, fields(version = manifest.version))]
     async fn commit(
         &self,
         manifest: &mut Manifest,
@@ -595,11 +600,13 @@ impl<T: CommitLock + Send + Sync> CommitHandler for T {
         // NOTE: once we have the lease we cannot use ? to return errors, since
         // we must release the lease before returning.
         let lease = self.lock(manifest.version).await?;
+        debug!("acquired commit lock");
 
         // Head the location and make sure it's not already committed
         match object_store.head(&path).await {
             Ok(_) => {
                 // The path already exists, so it's already committed
+                debug!("manifest already exists");
                 // Release the lock
                 lease.release(false).await?;
 
@@ -648,6 +655,7 @@ pub struct RenameCommitHandler;
 
 #[async_trait::async_trait]
 impl CommitHandler for RenameCommitHandler {
+    #[instrument(level = "debug", name = "RenameCommitHandler::commit", skip_all, fields(version = manifest.version))]
     async fn commit(
         &self,
         manifest: &mut Manifest,
@@ -682,6 +690,7 @@ impl CommitHandler for RenameCommitHandler {
             Ok(_) => Ok(()),
             Err(ObjectStoreError::AlreadyExists { .. }) => {
                 // Another transaction has already been committed
+                debug!("manifest already exists");
                 // Attempt to clean up temporary object, but ignore errors if we can't
                 let _ = object_store.delete(&tmp_path).await;
 
@@ -13,6 +13,7 @@ use lance_io::object_store::{ObjectStore, ObjectStoreExt};
 use log::warn;
 use object_store::{path::Path, ObjectStore as OSObjectStore};
 use snafu::{location, Location};
+use tracing::{debug, instrument};
 
 use super::{
     current_manifest_path, make_staging_manifest_path, manifest_path, write_latest_manifest,
@@ -228,6 +229,7 @@ impl CommitHandler for ExternalManifestCommitHandler {
         Ok(manifest_path)
     }
 
+    #[instrument(level = "debug", name = "ExternalManifestCommitHandler::commit", skip_all, fields(version = manifest.version))]
     async fn commit(
         &self,
         manifest: &mut Manifest,
@@ -251,6 +253,7 @@ impl CommitHandler for ExternalManifestCommitHandler {
             .put_if_not_exists(base_path.as_ref(), manifest.version, staging_path.as_ref())
             .await
             .map_err(|_| CommitError::CommitConflict {})?;
+        debug!("committed version to external store");
 
         // step 4: copy the manifest to the final location
         object_store.copy(
@@ -0,0 +1,396 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Lease-based commit coordination.
+//!
+//! Writers that share a table on an object store without conditional writes
+//! can coordinate through a central service, described by the
+//! `CommitCoordinator` gRPC service of `protos/commit_coordinator.proto`. A
+//! writer leases the version it is about to commit and releases the lease
+//! once the manifest is written.
+//!
+//! [`LeaseCoordinator`] keeps the state of the service, and
+//! [`LeaseCommitLock`] is the [`CommitLock`] of the writers. The lock talks to
+//! the coordinator through a [`CoordinatorClient`], which is implemented by
+//! the coordinator itself for writers in the same process. With the
+//! `coordinator` feature, `GrpcCoordinatorClient` calls a remote coordinator,
+//! and the `lance-commit-coordinator` binary serves one.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use lance_core::{Error, Result};
+use snafu::{location, Location};
+use tracing::debug;
+
+use super::{CommitError, CommitLease, CommitLock};
+
+#[cfg(feature = "coordinator")]
+mod grpc;
+#[cfg(feature = "coordinator")]
+pub use grpc::GrpcCoordinatorClient;
+
+/// Protobuf messages of the commit coordinator service.
+pub mod pb {
+    #![allow(clippy::all)]
+    #![allow(clippy::use_self)]
+    include!(concat!(env!("OUT_DIR"), "/lance.table.coordinator.rs"));
+}
+
+use pb::acquire_lease_response::Status;
+
+/// The calls of the commit coordinator service.
+#[async_trait]
+pub trait CoordinatorClient: std::fmt::Debug + Send + Sync {
+    async fn acquire_lease(
+        &self,
+        request: pb::AcquireLeaseRequest,
+    ) -> Result<pb::AcquireLeaseResponse>;
+
+    async fn release_lease(
+        &self,
+        request: pb::ReleaseLeaseRequest,
+    ) -> Result<pb::ReleaseLeaseResponse>;
+}
+
+#[derive(Debug)]
+struct Lease {
+    version: u64,
+    token: String,
+    holder: String,
+    expires_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct TableState {
+    /// The latest version committed under a lease.
+    committed: Option<u64>,
+    lease: Option<Lease>,
+}
+
+/// The longest lease a [`LeaseCoordinator`] grants by default.
+pub const DEFAULT_MAX_LEASE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// The state of a commit coordinator: one lease per table at a time.
+///
+/// Versions committed before the coordinator started are unknown to it, the
+/// [`CommitLock`] checks the object store for them.
+#[derive(Debug)]
+pub struct LeaseCoordinator {
+    tables: Mutex<HashMap<String, TableState>>,
+    max_ttl: Duration,
+}
+
+impl Default for LeaseCoordinator {
+    fn default() -> Self {
+        Self {
+            tables: Default::default(),
+            max_ttl: DEFAULT_MAX_LEASE_TTL,
+        }
+    }
+}
+
+impl LeaseCoordinator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reject leases longer than `max_ttl`. Default: [`DEFAULT_MAX_LEASE_TTL`].
+    pub fn with_max_ttl(mut self, max_ttl: Duration) -> Self {
+        self.max_ttl = max_ttl;
+        self
+    }
+
+    /// Lease a version of a table.
+    ///
+    /// Returns [`Error::InvalidInput`] if the TTL of the lease is zero or
+    /// longer than the maximum TTL.
+    pub fn acquire(&self, request: &pb::AcquireLeaseRequest) -> Result<pb::AcquireLeaseResponse> {
+        let ttl = Duration::from_millis(request.ttl_ms);
+        let now = Instant::now();
+        // Checked before taking the lock, as the TTL comes from the client
+        let expires_at = if ttl.is_zero() || ttl > self.max_ttl {
+            None
+        } else {
+            now.checked_add(ttl)
+        };
+        let Some(expires_at) = expires_at else {
+            return Err(Error::invalid_input(
+                format!(
+                    "The TTL of a lease must be between 1ms and {}ms, got {}ms",
+                    self.max_ttl.as_millis(),
+                    request.ttl_ms
+                ),
+                location!(),
+            ));
+        };
+        let mut tables = self.tables.lock().unwrap();
+        let state = tables.entry(request.table.clone()).or_default();
+        if state.committed.is_some_and(|v| request.version <= v) {
+            return Ok(pb::AcquireLeaseResponse {
+                status: Status::Committed.into(),
+                ..Default::default()
+            });
+        }
+        if let Some(lease) = &state.lease {
+            if lease.expires_at > now {
+                return Ok(pb::AcquireLeaseResponse {
+                    status: Status::Held.into(),
+                    holder: lease.holder.clone(),
+                    ..Default::default()
+                });
+            }
+            debug!(
+                "lease of {} on {} version {} expired",
+                lease.holder, request.table, lease.version
+            );
+        }
+        let token = uuid::Uuid::new_v4().to_string();
+        state.lease = Some(Lease {
+            version: request.version,
+            token: token.clone(),
+            holder: request.holder.clone(),
+            expires_at,
+        });
+        Ok(pb::AcquireLeaseResponse {
+            status: Status::Granted.into(),
+            token,
+            ..Default::default()
+        })
+    }
+
+    pub fn release(&self, request: &pb::ReleaseLeaseRequest) -> pb::ReleaseLeaseResponse {
+        let mut tables = self.tables.lock().unwrap();
+        let Some(state) = tables.get_mut(&request.table) else {
+            return pb::ReleaseLeaseResponse { released: false };
+        };
+        match state.lease.take() {
+            Some(lease) if lease.token == request.token => {
+                if request.committed {
+                    state.committed = Some(state.committed.unwrap_or(0).max(lease.version));
+                }
+                pb::ReleaseLeaseResponse { released: true }
+            }
+            other => {
+                state.lease = other;
+                pb::ReleaseLeaseResponse { released: false }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl CoordinatorClient for LeaseCoordinator {
+    async fn acquire_lease(
+        &self,
+        request: pb::AcquireLeaseRequest,
+    ) -> Result<pb::AcquireLeaseResponse> {
+        self.acquire(&request)
+    }
+
+    async fn release_lease(
+        &self,
+        request: pb::ReleaseLeaseRequest,
+    ) -> Result<pb::ReleaseLeaseResponse> {
+        Ok(self.release(&request))
+    }
+}
+
+/// A [`CommitLock`] that leases versions from a commit coordinator.
+///
+/// Pass it as the commit handler of the writes to the table.
+#[derive(Debug)]
+pub struct LeaseCommitLock {
+    client: Arc<dyn CoordinatorClient>,
+    table: String,
+    holder: String,
+    /// How long a lease lasts if it is not released. Default: 60s.
+    pub ttl: Duration,
+    /// How long to wait for a lease held by another writer. Default: 60s.
+    pub timeout: Duration,
+    /// How long to wait between attempts. Default: 100ms.
+    pub retry_interval: Duration,
+}
+
+impl LeaseCommitLock {
+    /// Lease versions of `table`, usually its URI, on behalf of `holder`.
+    pub fn new(
+        client: Arc<dyn CoordinatorClient>,
+        table: impl Into<String>,
+        holder: impl Into<String>,
+    ) -> Self {
+        Self {
+            client,
+            table: table.into(),
+            holder: holder.into(),
+            ttl: Duration::from_secs(60),
+            timeout: Duration::from_secs(60),
+            retry_interval: Duration::from_millis(100),
+        }
+    }
+}
+
+/// A lease granted by the coordinator.
+pub struct CoordinatorLease {
+    client: Arc<dyn CoordinatorClient>,
+    table: String,
+    token: String,
+}
+
+#[async_trait]
+impl CommitLease for CoordinatorLease {
+    async fn release(&self, success: bool) -> std::result::Result<(), CommitError> {
+        let response = self
+            .client
+            .release_lease(pb::ReleaseLeaseRequest {
+                table: self.table.clone(),
+                token: self.token.clone(),
+                committed: success,
+            })
+            .await?;
+        if !response.released {
+            return Err(CommitError::OtherError(Error::Internal {
+                message: format!(
+                    "The commit lease on {} expired before it was released",
+                    self.table
+                ),
+                location: location!(),
+            }));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CommitLock for LeaseCommitLock {
+    type Lease = CoordinatorLease;
+
+    async fn lock(&self, version: u64) -> std::result::Result<Self::Lease, CommitError> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let response = self
+                .client
+                .acquire_lease(pb::AcquireLeaseRequest {
+                    table: self.table.clone(),
+                    version,
+                    holder: self.holder.clone(),
+                    ttl_ms: self.ttl.as_millis() as u64,
+                })
+                .await?;
+            match response.status() {
+                Status::Granted => {
+                    return Ok(CoordinatorLease {
+                        client: self.client.clone(),
+                        table: self.table.clone(),
+                        token: response.token,
+                    })
+                }
+                Status::Committed => return Err(CommitError::CommitConflict),
+                Status::Held => {
+                    if Instant::now() >= deadline {
+                        return Err(CommitError::OtherError(Error::io(
+                            format!(
+                                "Timed out waiting for the commit lease on {} held by {}",
+                                self.table, response.holder
+                            ),
+                            location!(),
+                        )));
+                    }
+                    tokio::time::sleep(self.retry_interval).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(version: u64, holder: &str, ttl_ms: u64) -> pb::AcquireLeaseRequest {
+        pb::AcquireLeaseRequest {
+            table: "memory://table".to_string(),
+            version,
+            holder: holder.to_string(),
+            ttl_ms,
+        }
+    }
+
+    #[test]
+    fn test_coordinator() {
+        let coordinator = LeaseCoordinator::new();
+        let granted = coordinator.acquire(&request(2, "a", 60_000)).unwrap();
+        assert_eq!(granted.status(), Status::Granted);
+
+        let held = coordinator.acquire(&request(2, "b", 60_000)).unwrap();
+        assert_eq!(held.status(), Status::Held);
+        assert_eq!(held.holder, "a");
+
+        let release = |token: &str, committed| {
+            coordinator
+                .release(&pb::ReleaseLeaseRequest {
+                    table: "memory://table".to_string(),
+                    token: token.to_string(),
+                    committed,
+                })
+                .released
+        };
+        assert!(!release("other", true));
+        assert!(release(&granted.token, true));
+
+        let committed = coordinator.acquire(&request(2, "b", 60_000)).unwrap();
+        assert_eq!(committed.status(), Status::Committed);
+
+        // An expired lease goes to the next writer, and can't be released.
+        let expired = coordinator.acquire(&request(3, "b", 1)).unwrap();
+        assert_eq!(expired.status(), Status::Granted);
+        std::thread::sleep(Duration::from_millis(5));
+        let granted = coordinator.acquire(&request(3, "c", 60_000)).unwrap();
+        assert_eq!(granted.status(), Status::Granted);
+        assert!(!release(&expired.token, true));
+        assert!(release(&granted.token, false));
+        let granted = coordinator.acquire(&request(3, "b", 60_000)).unwrap();
+        assert_eq!(granted.status(), Status::Granted);
+    }
+
+    #[test]
+    fn test_coordinator_ttl() {
+        let coordinator = LeaseCoordinator::new().with_max_ttl(Duration::from_secs(60));
+        for ttl_ms in [0, 60_001, u64::MAX] {
+            let err = coordinator.acquire(&request(1, "a", ttl_ms)).unwrap_err();
+            assert!(matches!(err, Error::InvalidInput { .. }));
+        }
+        // The rejected leases leave the coordinator usable
+        let granted = coordinator.acquire(&request(1, "a", 60_000)).unwrap();
+        assert_eq!(granted.status(), Status::Granted);
+    }
+
+    #[tokio::test]
+    async fn test_lease_commit_lock() {
+        let coordinator = Arc::new(LeaseCoordinator::new());
+        let mut first = LeaseCommitLock::new(coordinator.clone(), "memory://table", "a");
+        first.retry_interval = Duration::from_millis(1);
+        let mut second = LeaseCommitLock::new(coordinator, "memory://table", "b");
+        second.retry_interval = Duration::from_millis(1);
+        second.timeout = Duration::from_millis(20);
+
+        let lease = first.lock(1).await.unwrap();
+        let err = second.lock(1).await.err().unwrap();
+        assert!(matches!(err, CommitError::OtherError(_)));
+
+        // The second writer gets the lease once it is released.
+        let waiting = tokio::spawn(async move {
+            second.timeout = Duration::from_secs(10);
+            second.lock(2).await.map(|_| ())
+        });
+        lease.release(true).await.unwrap();
+        waiting.await.unwrap().unwrap();
+
+        assert!(matches!(
+            first.lock(1).await,
+            Err(CommitError::CommitConflict)
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! gRPC server and client of the commit coordinator
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use lance_core::{Error, Result};
+use snafu::{location, Location};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Response, Status};
+
+use super::pb::commit_coordinator_client::CommitCoordinatorClient;
+use super::pb::commit_coordinator_server::{CommitCoordinator, CommitCoordinatorServer};
+use super::{pb, CoordinatorClient, LeaseCoordinator};
+
+impl LeaseCoordinator {
+    /// A gRPC service to add to a `tonic` server.
+    pub fn into_server(self: Arc<Self>) -> CommitCoordinatorServer<Self> {
+        CommitCoordinatorServer::from_arc(self)
+    }
+}
+
+#[tonic::async_trait]
+impl CommitCoordinator for LeaseCoordinator {
+    async fn acquire_lease(
+        &self,
+        request: Request<pb::AcquireLeaseRequest>,
+    ) -> std::result::Result<Response<pb::AcquireLeaseResponse>, Status> {
+        match self.acquire(request.get_ref()) {
+            Ok(response) => Ok(Response::new(response)),
+            Err(Error::InvalidInput { source, .. }) => {
+                Err(Status::invalid_argument(source.to_string()))
+            }
+            Err(err) => Err(Status::internal(err.to_string())),
+        }
+    }
+
+    async fn release_lease(
+        &self,
+        request: Request<pb::ReleaseLeaseRequest>,
+    ) -> std::result::Result<Response<pb::ReleaseLeaseResponse>, Status> {
+        Ok(Response::new(self.release(request.get_ref())))
+    }
+}
+
+/// A [`CoordinatorClient`] calling a commit coordinator over gRPC.
+#[derive(Debug, Clone)]
+pub struct GrpcCoordinatorClient {
+    client: CommitCoordinatorClient<Channel>,
+}
+
+impl GrpcCoordinatorClient {
+    /// Connect to the coordinator at `uri`, such as `http://coordinator:50051`.
+    pub async fn connect(uri: impl Into<String>) -> Result<Self> {
+        let endpoint = Endpoint::from_shared(uri.into()).map_err(|err| {
+            Error::invalid_input(
+                format!("Invalid commit coordinator URI: {}", err),
+                location!(),
+            )
+        })?;
+        let channel = endpoint.connect().await.map_err(|err| {
+            Error::io(
+                format!("Failed to connect to the commit coordinator: {}", err),
+                location!(),
+            )
+        })?;
+        Ok(Self {
+            client: CommitCoordinatorClient::new(channel),
+        })
+    }
+}
+
+fn to_error(status: Status) -> Error {
+    let message = format!("Commit coordinator call failed: {}", status);
+    match status.code() {
+        tonic::Code::InvalidArgument => Error::invalid_input(message, location!()),
+        _ => Error::io(message, location!()),
+    }
+}
+
+#[async_trait]
+impl CoordinatorClient for GrpcCoordinatorClient {
+    async fn acquire_lease(
+        &self,
+        request: pb::AcquireLeaseRequest,
+    ) -> Result<pb::AcquireLeaseResponse> {
+        // Calls need a mutable client, and cloning one shares its channel
+        let response = self
+            .client
+            .clone()
+            .acquire_lease(request)
+            .await
+            .map_err(to_error)?;
+        Ok(response.into_inner())
+    }
+
+    async fn release_lease(
+        &self,
+        request: pb::ReleaseLeaseRequest,
+    ) -> Result<pb::ReleaseLeaseResponse> {
+        let response = self
+            .client
+            .clone()
+            .release_lease(request)
+            .await
+            .map_err(to_error)?;
+        Ok(response.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+
+    use crate::io::commit::lease::LeaseCommitLock;
+    use crate::io::commit::{CommitError, CommitLease, CommitLock};
+
+    #[tokio::test]
+    async fn test_grpc_coordinator() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(Arc::new(LeaseCoordinator::new()).into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let client = Arc::new(
+            GrpcCoordinatorClient::connect(format!("http://{}", addr))
+                .await
+                .unwrap(),
+        );
+
+        let first = LeaseCommitLock::new(client.clone(), "memory://table", "a");
+        let mut second = LeaseCommitLock::new(client, "memory://table", "b");
+        second.retry_interval = Duration::from_millis(1);
+        second.timeout = Duration::from_millis(20);
+
+        let lease = first.lock(1).await.unwrap();
+        assert!(matches!(
+            second.lock(1).await,
+            Err(CommitError::OtherError(_))
+        ));
+        lease.release(true).await.unwrap();
+        assert!(matches!(
+            second.lock(1).await,
+            Err(CommitError::CommitConflict)
+        ));
+        second.lock(2).await.unwrap().release(true).await.unwrap();
+
+        // A lease too long is rejected, and the coordinator keeps serving
+        second.ttl = Duration::from_millis(u64::MAX);
+        assert!(matches!(
+            second.lock(3).await,
+            Err(CommitError::OtherError(Error::InvalidInput { .. }))
+        ));
+        first.lock(3).await.unwrap().release(true).await.unwrap();
+
+        let err = GrpcCoordinatorClient::connect("not a uri")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+    }
+}
@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Sketch files, holding the column sketches of a data file
+//!
+//! A sketch file is an Arrow IPC file with a single row. Each sketched field
+//! has four columns, suffixed with its field id: `num_values_{id}`,
+//! `hll_{id}` with the HyperLogLog registers, `cms_{id}` with the Count-Min
+//! counters as little-endian u64, and `candidates_{id}`, a list of the
+//! candidate heavy hitters.
+
+use std::sync::Arc;
+
+use arrow_array::{
+    cast::AsArray, types::UInt64Type, Array, ArrayRef, BinaryArray, ListArray, RecordBatch,
+    UInt64Array,
+};
+use arrow_buffer::OffsetBuffer;
+use arrow_ipc::reader::FileReader as ArrowFileReader;
+use arrow_ipc::writer::{FileWriter as ArrowFileWriter, IpcWriteOptions};
+use arrow_ipc::CompressionType;
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use lance_core::error::{box_error, CorruptFileSnafu};
+use lance_core::{Error, Result};
+use lance_io::object_store::ObjectStore;
+use object_store::path::Path;
+use snafu::{location, Location, ResultExt};
+
+use crate::format::{ColumnSketch, CountMinSketch, DataFile, DataFileSketches, HyperLogLog};
+
+pub const SKETCH_DIR: &str = "_sketches";
+
+/// Get the path of the sketch file of a data file.
+pub fn sketch_file_path(base: &Path, data_file: &DataFile) -> Path {
+    let stem = data_file
+        .path
+        .strip_suffix(".lance")
+        .unwrap_or(&data_file.path);
+    base.child(SKETCH_DIR).child(format!("{stem}.arrow"))
+}
+
+/// Write the sketches of a data file.
+pub async fn write_sketch_file(
+    object_store: &ObjectStore,
+    path: &Path,
+    sketches: &DataFileSketches,
+) -> Result<()> {
+    let mut fields = Vec::with_capacity(sketches.len() * 4);
+    let mut columns: Vec<ArrayRef> = Vec::with_capacity(sketches.len() * 4);
+    for (field_id, sketch) in sketches {
+        let counters = sketch
+            .frequencies
+            .counters()
+            .iter()
+            .flat_map(|counter| counter.to_le_bytes())
+            .collect::<Vec<_>>();
+        let candidates_field = Arc::new(Field::new(
+            "item",
+            sketch.candidates.data_type().clone(),
+            true,
+        ));
+        let candidates = ListArray::new(
+            candidates_field.clone(),
+            OffsetBuffer::from_lengths([sketch.candidates.len()]),
+            sketch.candidates.clone(),
+            None,
+        );
+        fields.extend([
+            Field::new(format!("num_values_{field_id}"), DataType::UInt64, false),
+            Field::new(format!("hll_{field_id}"), DataType::Binary, false),
+            Field::new(format!("cms_{field_id}"), DataType::Binary, false),
+            Field::new(
+                format!("candidates_{field_id}"),
+                DataType::List(candidates_field),
+                false,
+            ),
+        ]);
+        columns.extend([
+            Arc::new(UInt64Array::from(vec![sketch.num_values])) as ArrayRef,
+            Arc::new(BinaryArray::from_vec(vec![sketch.distinct.registers()])),
+            Arc::new(BinaryArray::from_vec(vec![counters.as_slice()])),
+            Arc::new(candidates),
+        ]);
+    }
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+
+    let mut out: Vec<u8> = Vec::new();
+    let write_options =
+        IpcWriteOptions::default().try_with_compression(Some(CompressionType::ZSTD))?;
+    {
+        let mut writer =
+            ArrowFileWriter::try_new_with_options(&mut out, schema.as_ref(), write_options)?;
+        writer.write(&batch)?;
+        writer.finish()?;
+    }
+    object_store.inner.put(path, out.into()).await?;
+    Ok(())
+}
+
+/// Read the sketches of a data file, or `None` if it has no sketch file.
+pub async fn read_sketch_file(
+    object_store: &ObjectStore,
+    path: &Path,
+) -> Result<Option<DataFileSketches>> {
+    let data = match object_store.inner.get(path).await {
+        Ok(result) => result.bytes().await?,
+        Err(object_store::Error::NotFound { .. }) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let batches: Vec<RecordBatch> = ArrowFileReader::try_new(std::io::Cursor::new(data), None)?
+        .collect::<std::result::Result<_, ArrowError>>()
+        .map_err(box_error)
+        .context(CorruptFileSnafu { path: path.clone() })?;
+    let corrupt = |message: String| Error::corrupt_file(path.clone(), message, location!());
+    let [batch] = batches.as_slice() else {
+        return Err(corrupt(format!(
+            "Expected a single batch in the sketch file, found {}",
+            batches.len()
+        )));
+    };
+
+    let column = |name: String| {
+        batch
+            .column_by_name(&name)
+            .filter(|column| column.len() == 1)
+            .ok_or_else(|| corrupt(format!("Sketch file has no column {name}")))
+    };
+    let mut sketches = DataFileSketches::new();
+    for field in batch.schema().fields() {
+        let Some(field_id) = field.name().strip_prefix("num_values_") else {
+            continue;
+        };
+        let num_values = column(format!("num_values_{field_id}"))?
+            .as_primitive_opt::<UInt64Type>()
+            .ok_or_else(|| corrupt("num_values must be a u64".to_string()))?
+            .value(0);
+        let registers = column(format!("hll_{field_id}"))?
+            .as_binary_opt::<i32>()
+            .ok_or_else(|| corrupt("hll must be binary".to_string()))?
+            .value(0)
+            .to_vec();
+        let counters = column(format!("cms_{field_id}"))?
+            .as_binary_opt::<i32>()
+            .ok_or_else(|| corrupt("cms must be binary".to_string()))?
+            .value(0)
+            .chunks_exact(8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        let candidates = column(format!("candidates_{field_id}"))?
+            .as_list_opt::<i32>()
+            .ok_or_else(|| corrupt("candidates must be a list".to_string()))?
+            .value(0);
+        let sketch = ColumnSketch {
+            num_values,
+            distinct: HyperLogLog::from_registers(registers)
+                .ok_or_else(|| corrupt("Invalid HyperLogLog sketch".to_string()))?,
+            frequencies: CountMinSketch::from_counters(counters)
+                .ok_or_else(|| corrupt("Invalid Count-Min sketch".to_string()))?,
+            candidates,
+        };
+        let field_id = field_id
+            .parse()
+            .map_err(|_| corrupt(format!("Invalid field id {field_id}")))?;
+        sketches.insert(field_id, sketch);
+    }
+    Ok(Some(sketches))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int32Array, StringArray};
+
+    #[tokio::test]
+    async fn test_roundtrip_sketch_file() {
+        let object_store = ObjectStore::memory();
+        let data_file = DataFile::new_legacy_from_fields("abc.lance", vec![0, 1]);
+        let path = sketch_file_path(&Path::from("base"), &data_file);
+        assert_eq!(path, Path::from("base/_sketches/abc.arrow"));
+        assert!(read_sketch_file(&object_store, &path)
+            .await
+            .unwrap()
+            .is_none());
+
+        let mut ints = ColumnSketch::try_new(&DataType::Int32).unwrap();
+        ints.update(&Int32Array::from(vec![Some(1), Some(2), Some(2), None]))
+            .unwrap();
+        let mut strings = ColumnSketch::try_new(&DataType::Utf8).unwrap();
+        strings
+            .update(&StringArray::from(vec!["a", "b", "a"]))
+            .unwrap();
+        let sketches = DataFileSketches::from([(0, ints), (1, strings)]);
+        write_sketch_file(&object_store, &path, &sketches)
+            .await
+            .unwrap();
+
+        let read = read_sketch_file(&object_store, &path)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.len(), 2);
+        for (field_id, sketch) in &sketches {
+            let read = &read[field_id];
+            assert_eq!(read.num_values, sketch.num_values);
+            assert_eq!(read.distinct, sketch.distinct);
+            assert_eq!(read.frequencies, sketch.frequencies);
+            assert_eq!(read.top_k(1).unwrap(), sketch.top_k(1).unwrap());
+        }
+    }
+}
@@ -0,0 +1,508 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Reproducible benchmarks
+//!
+//! [`BenchDatasetSpec`] describes a synthetic dataset, which is generated
+//! from a seed so that the same spec always produces the same data.  The
+//! standard benchmarks write, scan and search such datasets and report their
+//! timings as [`BenchResult`]s, which serialize to JSON so regressions can be
+//! tracked across versions and hardware can be sized from them.
+//!
+//! ```no_run
+//! # use lance::bench::{run_benchmarks, BenchColumn, BenchColumnKind, BenchConfig, BenchDatasetSpec};
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let spec = BenchDatasetSpec {
+//!     columns: vec![
+//!         BenchColumn::new("id", BenchColumnKind::Id),
+//!         BenchColumn::new("category", BenchColumnKind::Category { cardinality: 100 }),
+//!         BenchColumn::new("vector", BenchColumnKind::Vector { dim: 128 }),
+//!     ],
+//!     ..Default::default()
+//! };
+//! let report = run_benchmarks(&spec, "/tmp/bench.lance", &BenchConfig::default())
+//!     .await
+//!     .unwrap();
+//! println!("{}", report.to_json().unwrap());
+//! # })
+//! ```
+
+use std::time::{Duration, Instant};
+
+use arrow_array::{
+    types::{Float32Type, Int64Type},
+    Float32Array, RecordBatchReader,
+};
+use futures::TryStreamExt;
+use lance_datagen::{array, gen, BatchCount, ByteCount, Dimension, RowCount, Seed};
+use lance_index::{DatasetIndexExt, IndexType};
+use lance_linalg::distance::MetricType;
+use rand::{distributions::Uniform, Rng, SeedableRng};
+use serde::Serialize;
+use snafu::{location, Location};
+
+use crate::dataset::{Dataset, WriteMode, WriteParams};
+use crate::index::vector::VectorIndexParams;
+use crate::{Error, Result};
+
+/// The values of a column of a synthetic dataset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BenchColumnKind {
+    /// 64-bit integers counting up from 0
+    Id,
+    /// 64-bit integers drawn uniformly from `0..cardinality`
+    Category { cardinality: u64 },
+    /// Random strings of `bytes` bytes
+    Text { bytes: u64 },
+    /// Random float32 vectors of `dim` dimensions
+    Vector { dim: u32 },
+}
+
+/// A column of a synthetic dataset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BenchColumn {
+    pub name: String,
+    pub kind: BenchColumnKind,
+}
+
+impl BenchColumn {
+    pub fn new(name: impl Into<String>, kind: BenchColumnKind) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+        }
+    }
+}
+
+/// A synthetic dataset
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchDatasetSpec {
+    pub num_rows: u64,
+    /// Number of rows of the batches the data is generated and written in
+    pub rows_per_batch: u64,
+    /// The columns of the dataset, in order
+    pub columns: Vec<BenchColumn>,
+    /// Seed of the random values
+    pub seed: u64,
+}
+
+impl Default for BenchDatasetSpec {
+    /// An `id` column, a `category` column of 100 values and a `text`
+    /// column of 32 bytes
+    fn default() -> Self {
+        Self {
+            num_rows: 100_000,
+            rows_per_batch: 10_000,
+            columns: vec![
+                BenchColumn::new("id", BenchColumnKind::Id),
+                BenchColumn::new("category", BenchColumnKind::Category { cardinality: 100 }),
+                BenchColumn::new("text", BenchColumnKind::Text { bytes: 32 }),
+            ],
+            seed: 42,
+        }
+    }
+}
+
+impl BenchDatasetSpec {
+    /// Generate the data of the dataset
+    pub fn generate(&self) -> Result<impl RecordBatchReader + Send + 'static> {
+        if self.rows_per_batch == 0 || self.num_rows % self.rows_per_batch != 0 {
+            return Err(Error::invalid_input(
+                format!(
+                    "num_rows ({}) must be a multiple of rows_per_batch ({})",
+                    self.num_rows, self.rows_per_batch
+                ),
+                location!(),
+            ));
+        }
+        if self.columns.is_empty() {
+            return Err(Error::invalid_input(
+                "the dataset must have at least one column",
+                location!(),
+            ));
+        }
+        let num_batches = u32::try_from(self.num_rows / self.rows_per_batch).map_err(|_| {
+            Error::invalid_input("too many batches for rows_per_batch", location!())
+        })?;
+        let mut builder = gen().with_seed(Seed::from(self.seed));
+        for column in &self.columns {
+            builder = match &column.kind {
+                BenchColumnKind::Id => builder.col(&column.name, array::step::<Int64Type>()),
+                BenchColumnKind::Category { cardinality } => {
+                    if *cardinality == 0 {
+                        return Err(Error::invalid_input(
+                            format!("the cardinality of {} must be positive", column.name),
+                            location!(),
+                        ));
+                    }
+                    builder.col(
+                        &column.name,
+                        array::rand_with_distribution::<Int64Type, _>(Uniform::new(
+                            0,
+                            *cardinality as i64,
+                        )),
+                    )
+                }
+                BenchColumnKind::Text { bytes } => builder.col(
+                    &column.name,
+                    array::rand_utf8(ByteCount::from(*bytes), false),
+                ),
+                BenchColumnKind::Vector { dim } => builder.col(
+                    &column.name,
+                    array::rand_vec::<Float32Type>(Dimension::from(*dim)),
+                ),
+            };
+        }
+        Ok(builder.into_reader_rows(
+            RowCount::from(self.rows_per_batch),
+            BatchCount::from(num_batches),
+        ))
+    }
+
+    /// Generate the dataset and write it to `uri`, replacing any dataset there
+    pub async fn write(&self, uri: &str) -> Result<Dataset> {
+        let params = WriteParams {
+            mode: WriteMode::Overwrite,
+            ..Default::default()
+        };
+        Dataset::write(self.generate()?, uri, Some(params)).await
+    }
+
+    /// The first category column, if any
+    pub fn category_column(&self) -> Option<&str> {
+        self.columns
+            .iter()
+            .find(|column| matches!(column.kind, BenchColumnKind::Category { .. }))
+            .map(|column| column.name.as_str())
+    }
+
+    /// The name and dimension of the first vector column, if any
+    pub fn vector_column(&self) -> Option<(&str, u32)> {
+        self.columns.iter().find_map(|column| match column.kind {
+            BenchColumnKind::Vector { dim } => Some((column.name.as_str(), dim)),
+            _ => None,
+        })
+    }
+
+    /// Generate `num_queries` query vectors for the first vector column,
+    /// reproducibly from the seed
+    pub fn query_vectors(&self, num_queries: usize) -> Result<Vec<Float32Array>> {
+        let (_, vector_dim) = self
+            .vector_column()
+            .ok_or_else(|| Error::invalid_input("the dataset has no vector column", location!()))?;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(self.seed.wrapping_add(1));
+        Ok((0..num_queries)
+            .map(|_| Float32Array::from_iter_values((0..vector_dim).map(|_| rng.gen::<f32>())))
+            .collect())
+    }
+}
+
+/// Parameters of the standard benchmarks
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchConfig {
+    /// Number of times each benchmark is run
+    pub iterations: usize,
+    /// Number of query vectors of the ANN benchmark
+    pub num_queries: usize,
+    /// Number of nearest neighbors searched for
+    pub k: usize,
+    /// Number of IVF partitions of the vector index
+    pub num_partitions: usize,
+    /// Number of PQ sub-vectors of the vector index
+    pub num_sub_vectors: usize,
+    /// Number of IVF partitions probed by a search
+    pub nprobes: usize,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 3,
+            num_queries: 100,
+            k: 10,
+            num_partitions: 32,
+            num_sub_vectors: 16,
+            nprobes: 10,
+        }
+    }
+}
+
+/// Timings of a benchmark
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchResult {
+    pub name: String,
+    pub iterations: usize,
+    /// Number of rows processed by an iteration
+    pub rows_per_iteration: u64,
+    pub mean_seconds: f64,
+    pub min_seconds: f64,
+    pub max_seconds: f64,
+    /// Rows processed per second, from the mean time of an iteration
+    pub rows_per_second: f64,
+}
+
+impl BenchResult {
+    fn new(name: &str, rows_per_iteration: u64, timings: &[Duration]) -> Self {
+        let seconds = timings
+            .iter()
+            .map(Duration::as_secs_f64)
+            .collect::<Vec<_>>();
+        let mean_seconds = seconds.iter().sum::<f64>() / seconds.len().max(1) as f64;
+        Self {
+            name: name.to_string(),
+            iterations: seconds.len(),
+            rows_per_iteration,
+            mean_seconds,
+            min_seconds: seconds.iter().copied().fold(f64::INFINITY, f64::min),
+            max_seconds: seconds.iter().copied().fold(0.0, f64::max),
+            rows_per_second: if mean_seconds > 0.0 {
+                rows_per_iteration as f64 / mean_seconds
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+/// The results of [`run_benchmarks`], along with what was benchmarked
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub lance_version: String,
+    pub dataset: BenchDatasetSpec,
+    pub config: BenchConfig,
+    pub results: Vec<BenchResult>,
+}
+
+impl BenchReport {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Benchmark writing the dataset of `spec` to `uri`
+///
+/// The dataset is left at `uri` once this returns.
+pub async fn bench_write(
+    spec: &BenchDatasetSpec,
+    uri: &str,
+    iterations: usize,
+) -> Result<BenchResult> {
+    let mut timings = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        // Generating the data is not part of what is measured
+        let reader = spec.generate()?;
+        let schema = reader.schema();
+        let batches = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+        let reader = arrow_array::RecordBatchIterator::new(batches.into_iter().map(Ok), schema);
+        let params = WriteParams {
+            mode: WriteMode::Overwrite,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        Dataset::write(reader, uri, Some(params)).await?;
+        timings.push(start.elapsed());
+    }
+    Ok(BenchResult::new("write", spec.num_rows, &timings))
+}
+
+/// Benchmark scanning all the columns of `dataset`
+pub async fn bench_scan(dataset: &Dataset, iterations: usize) -> Result<BenchResult> {
+    let num_rows = dataset.count_rows(None).await? as u64;
+    let mut timings = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        dataset
+            .scan()
+            .try_into_stream()
+            .await?
+            .try_for_each(|_| futures::future::ready(Ok(())))
+            .await?;
+        timings.push(start.elapsed());
+    }
+    Ok(BenchResult::new("scan", num_rows, &timings))
+}
+
+/// Benchmark scanning the rows of `dataset` where `column` is 0
+pub async fn bench_filtered_scan(
+    dataset: &Dataset,
+    column: &str,
+    iterations: usize,
+) -> Result<BenchResult> {
+    let filter = format!("{} = 0", column);
+    let mut timings = Vec::with_capacity(iterations);
+    let mut num_rows = 0;
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let mut scanner = dataset.scan();
+        scanner.filter(&filter)?;
+        num_rows = scanner
+            .try_into_stream()
+            .await?
+            .try_fold(0, |num_rows, batch| {
+                futures::future::ready(Ok(num_rows + batch.num_rows() as u64))
+            })
+            .await?;
+        timings.push(start.elapsed());
+    }
+    Ok(BenchResult::new("filtered_scan", num_rows, &timings))
+}
+
+/// Benchmark approximate nearest neighbor searches of `queries` in `column`
+///
+/// Rows per iteration count the queries, so the throughput is in queries
+/// per second.  The column is expected to be indexed.
+pub async fn bench_ann(
+    dataset: &Dataset,
+    column: &str,
+    queries: &[Float32Array],
+    config: &BenchConfig,
+) -> Result<BenchResult> {
+    let mut timings = Vec::with_capacity(config.iterations);
+    for _ in 0..config.iterations {
+        let start = Instant::now();
+        for query in queries {
+            let mut scanner = dataset.scan();
+            scanner
+                .nearest(column, query, config.k)?
+                .nprobs(config.nprobes);
+            scanner.try_into_batch().await?;
+        }
+        timings.push(start.elapsed());
+    }
+    Ok(BenchResult::new("ann", queries.len() as u64, &timings))
+}
+
+/// Run the standard benchmarks over the dataset of `spec`, written to `uri`
+///
+/// The filtered scan benchmark is only run if the dataset has a category
+/// column, and filters on the first one.  The ANN benchmark, and the build of
+/// its index, are only run if the dataset has a vector column, and search the
+/// first one.
+pub async fn run_benchmarks(
+    spec: &BenchDatasetSpec,
+    uri: &str,
+    config: &BenchConfig,
+) -> Result<BenchReport> {
+    let mut results = vec![bench_write(spec, uri, config.iterations).await?];
+    let mut dataset = Dataset::open(uri).await?;
+    results.push(bench_scan(&dataset, config.iterations).await?);
+    if let Some(column) = spec.category_column() {
+        results.push(bench_filtered_scan(&dataset, column, config.iterations).await?);
+    }
+
+    if let Some((column, _)) = spec.vector_column() {
+        let params = VectorIndexParams::ivf_pq(
+            config.num_partitions,
+            8,
+            config.num_sub_vectors,
+            MetricType::L2,
+            50,
+        );
+        let start = Instant::now();
+        dataset
+            .create_index(&[column], IndexType::Vector, None, &params, true)
+            .await?;
+        results.push(BenchResult::new(
+            "build_vector_index",
+            spec.num_rows,
+            &[start.elapsed()],
+        ));
+        let queries = spec.query_vectors(config.num_queries)?;
+        results.push(bench_ann(&dataset, column, &queries, config).await?);
+    }
+
+    Ok(BenchReport {
+        lance_version: env!("CARGO_PKG_VERSION").to_string(),
+        dataset: spec.clone(),
+        config: config.clone(),
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_benchmarks() {
+        let spec = BenchDatasetSpec {
+            num_rows: 1000,
+            rows_per_batch: 250,
+            columns: vec![
+                BenchColumn::new("key", BenchColumnKind::Id),
+                BenchColumn::new("emb", BenchColumnKind::Vector { dim: 16 }),
+                BenchColumn::new("label", BenchColumnKind::Text { bytes: 8 }),
+                BenchColumn::new("bucket", BenchColumnKind::Category { cardinality: 10 }),
+            ],
+            seed: 7,
+        };
+        let reader = spec.generate().unwrap();
+        let names = reader
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["key", "emb", "label", "bucket"]);
+
+        // The same spec generates the same data
+        let first = reader.collect::<Vec<_>>();
+        let second = spec.generate().unwrap().collect::<Vec<_>>();
+        assert_eq!(first.len(), 4);
+        for (first, second) in first.into_iter().zip(second) {
+            assert_eq!(first.unwrap(), second.unwrap());
+        }
+
+        let config = BenchConfig {
+            iterations: 1,
+            num_queries: 2,
+            num_partitions: 2,
+            num_sub_vectors: 2,
+            nprobes: 2,
+            ..Default::default()
+        };
+        let test_dir = tempfile::tempdir().unwrap();
+        let uri = test_dir.path().join("bench.lance");
+        let report = run_benchmarks(&spec, uri.to_str().unwrap(), &config)
+            .await
+            .unwrap();
+        let names = report
+            .results
+            .iter()
+            .map(|result| result.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "write",
+                "scan",
+                "filtered_scan",
+                "build_vector_index",
+                "ann"
+            ]
+        );
+        assert_eq!(report.results[1].rows_per_iteration, 1000);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["dataset"]["num_rows"], 1000);
+        assert_eq!(json["results"][4]["name"], "ann");
+
+        // Without category and vector columns, only the scans are run
+        let spec = BenchDatasetSpec {
+            num_rows: 100,
+            rows_per_batch: 100,
+            columns: vec![BenchColumn::new("key", BenchColumnKind::Id)],
+            seed: 7,
+        };
+        let report = run_benchmarks(&spec, uri.to_str().unwrap(), &config)
+            .await
+            .unwrap();
+        let names = report
+            .results
+            .iter()
+            .map(|result| result.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["write", "scan"]);
+    }
+}
@@ -5,5 +5,13 @@
 //!
 
 pub(crate) mod dataframe;
+pub(crate) mod functions;
 pub(crate) mod logical_expr;
 pub(crate) mod logical_plan;
+
+#[cfg(feature = "substrait")]
+pub use dataframe::execute_substrait;
+pub use dataframe::{
+    new_session_context, register_object_stores, LanceTableProvider, SessionContextExt,
+};
+pub use functions::register_lance_functions;
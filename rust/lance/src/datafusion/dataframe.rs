@@ -17,12 +17,20 @@ use datafusion::{
         TaskContext,
     },
     logical_expr::{Expr, TableProviderFilterPushDown, TableType},
-    physical_plan::{streaming::PartitionStream, ExecutionPlan, SendableRecordBatchStream},
+    physical_plan::{
+        expressions::Column, projection::ProjectionExec, streaming::PartitionStream, ExecutionPlan,
+        PhysicalExpr, SendableRecordBatchStream,
+    },
 };
 use lance_core::ROW_ID;
+use lance_io::object_store::{ObjectStore, ObjectStoreParams};
+use url::Url;
 
 use crate::Dataset;
 
+/// A DataFusion table reading a Lance dataset
+///
+/// Projections, filters and limits are pushed down to the Lance scanner.
 pub struct LanceTableProvider {
     dataset: Arc<Dataset>,
     full_schema: Arc<Schema>,
@@ -30,7 +38,9 @@ pub struct LanceTableProvider {
 }
 
 impl LanceTableProvider {
-    fn new(dataset: Arc<Dataset>, with_row_id: bool) -> Self {
+    /// A table of the columns of `dataset`, and of its row ids as a last
+    /// `_rowid` column if `with_row_id` is set
+    pub fn new(dataset: Arc<Dataset>, with_row_id: bool) -> Self {
         let full_schema = if with_row_id {
             let mut full_schema = dataset.schema().clone();
             full_schema
@@ -83,9 +93,12 @@ impl TableProvider for LanceTableProvider {
                     columns.push(self.full_schema.field(*field_idx).name());
                 }
             }
-            if !columns.is_empty() {
-                scan.project(&columns)?;
+            // Queries that read no column, such as `count(*)`, still need
+            // the number of rows, so they read row ids
+            if columns.is_empty() {
+                scan.with_row_id();
             }
+            scan.project(&columns)?;
         }
         let combined_filter = match filters.len() {
             0 => None,
@@ -103,7 +116,24 @@ impl TableProvider for LanceTableProvider {
         }
         scan.limit(limit.map(|l| l as i64), None)?;
 
-        scan.create_plan().await.map_err(DataFusionError::from)
+        let plan = scan.create_plan().await.map_err(DataFusionError::from)?;
+        let Some(projection) = projection else {
+            return Ok(plan);
+        };
+        // The scan returns row ids last, and row ids it only read to count
+        // rows, while DataFusion expects the columns of the projection, in
+        // its order
+        let plan_schema = plan.schema();
+        let exprs = projection
+            .iter()
+            .map(|field_idx| {
+                let name = self.full_schema.field(*field_idx).name();
+                let column: Arc<dyn PhysicalExpr> =
+                    Arc::new(Column::new(name, plan_schema.index_of(name)?));
+                Ok((column, name.clone()))
+            })
+            .collect::<datafusion::common::Result<Vec<_>>>()?;
+        Ok(Arc::new(ProjectionExec::try_new(exprs, plan)?))
     }
 
     // Since we are using datafusion itself to apply the filters it should
@@ -120,6 +150,64 @@ impl TableProvider for LanceTableProvider {
     }
 }
 
+/// Execute a serialized Substrait plan, reading each named table of the plan
+/// from the dataset of that name in `datasets`
+///
+/// See [`lance_datafusion::substrait::execute_substrait`].
+#[cfg(feature = "substrait")]
+pub async fn execute_substrait(
+    plan: &[u8],
+    datasets: std::collections::HashMap<String, Arc<Dataset>>,
+) -> crate::Result<SendableRecordBatchStream> {
+    let tables = datasets
+        .into_iter()
+        .map(|(name, dataset)| {
+            let table: Arc<dyn TableProvider> = Arc::new(LanceTableProvider::new(dataset, false));
+            (name, table)
+        })
+        .collect();
+    lance_datafusion::substrait::execute_substrait(plan, tables, Default::default()).await
+}
+
+/// Creates a session context whose runtime reads the buckets of `uris` with
+/// the object stores Lance builds for them from `params`
+///
+/// See [`register_object_stores`].
+pub async fn new_session_context(
+    uris: &[&str],
+    params: &ObjectStoreParams,
+) -> crate::Result<SessionContext> {
+    let ctx = SessionContext::new();
+    register_object_stores(&ctx, uris, params).await?;
+    Ok(ctx)
+}
+
+/// Registers the object stores Lance builds from `params` for `uris` in the
+/// runtime of `ctx`, so that SQL referencing paths such as `s3://bucket/...`
+/// reads them with the credentials and storage options of `params`
+///
+/// DataFusion looks stores up by the scheme and host of a URL, so a store is
+/// registered for the bucket of each URI and replaces any store registered
+/// for that bucket before.  Local paths are skipped, DataFusion reads them
+/// without a registered store.
+pub async fn register_object_stores(
+    ctx: &SessionContext,
+    uris: &[&str],
+    params: &ObjectStoreParams,
+) -> crate::Result<()> {
+    for uri in uris {
+        let url = match Url::parse(uri) {
+            // On Windows, the drive is parsed as a scheme
+            Ok(url) if url.scheme().len() == 1 && cfg!(windows) => continue,
+            Ok(url) => url,
+            Err(_) => continue,
+        };
+        let (store, _) = ObjectStore::from_uri_and_params(uri, params).await?;
+        ctx.runtime_env().register_object_store(&url, store.inner);
+    }
+    Ok(())
+}
+
 pub trait SessionContextExt {
     /// Creates a DataFrame for reading a Lance dataset
     fn read_lance(
@@ -127,6 +215,13 @@ pub trait SessionContextExt {
         dataset: Arc<Dataset>,
         with_row_id: bool,
     ) -> datafusion::common::Result<DataFrame>;
+    /// Registers a Lance dataset as a table named `name`, to query it with SQL
+    fn register_lance(
+        &self,
+        name: &str,
+        dataset: Arc<Dataset>,
+        with_row_id: bool,
+    ) -> datafusion::common::Result<()>;
     /// Creates a DataFrame for reading a stream of data
     ///
     /// This dataframe may only be queried once, future queries will fail
@@ -173,6 +268,19 @@ impl SessionContextExt for SessionContext {
         self.read_table(Arc::new(LanceTableProvider::new(dataset, with_row_id)))
     }
 
+    fn register_lance(
+        &self,
+        name: &str,
+        dataset: Arc<Dataset>,
+        with_row_id: bool,
+    ) -> datafusion::common::Result<()> {
+        self.register_table(
+            name,
+            Arc::new(LanceTableProvider::new(dataset, with_row_id)),
+        )?;
+        Ok(())
+    }
+
     fn read_one_shot(
         &self,
         data: SendableRecordBatchStream,
@@ -183,3 +291,101 @@ impl SessionContextExt for SessionContext {
         self.read_table(Arc::new(provider))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{cast::AsArray, types::Int32Type, types::Int64Type, RecordBatch};
+    use lance_datagen::{array, gen, BatchCount, RowCount};
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_register_lance() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let data = gen()
+            .col("i", array::step::<Int32Type>())
+            .col("j", array::step_custom::<Int32Type>(100, 1))
+            .into_reader_rows(RowCount::from(50), BatchCount::from(2));
+        let dataset = Arc::new(Dataset::write(data, test_uri, None).await.unwrap());
+
+        let ctx = SessionContext::new();
+        ctx.register_lance("t", dataset.clone(), true).unwrap();
+
+        let count =
+            |batches: Vec<RecordBatch>| batches[0].column(0).as_primitive::<Int64Type>().value(0);
+        let batches = ctx
+            .sql("SELECT count(*) FROM t WHERE i >= 10")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(count(batches), 90);
+
+        // Columns are returned in the order of the query, row ids included
+        let batches = ctx
+            .sql("SELECT j, _rowid, i FROM t WHERE i < 3 ORDER BY i")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let batch = &batches[0];
+        assert_eq!(batch.schema().field(0).name(), "j");
+        assert_eq!(batch.schema().field(1).name(), ROW_ID);
+        let j = batch.column(0).as_primitive::<Int32Type>();
+        assert_eq!(j.values(), &[100, 101, 102]);
+
+        let df = ctx.read_lance(dataset, false).unwrap();
+        let batches = df.count().await.unwrap();
+        assert_eq!(batches, 100);
+    }
+
+    #[tokio::test]
+    async fn test_new_session_context() {
+        use datafusion::datasource::object_store::ObjectStoreUrl;
+        use datafusion::prelude::CsvReadOptions;
+        use object_store::path::Path;
+
+        use crate::utils::test::IoTrackingStore;
+
+        let (io_stats_wrapper, io_stats) = IoTrackingStore::new_wrapper();
+        let params = ObjectStoreParams {
+            object_store_wrapper: Some(io_stats_wrapper),
+            ..Default::default()
+        };
+        let ctx = new_session_context(&["memory://bucket/table", "/local/path"], &params)
+            .await
+            .unwrap();
+        let store = ctx
+            .runtime_env()
+            .object_store(ObjectStoreUrl::parse("memory://bucket").unwrap())
+            .unwrap();
+        store
+            .put(&Path::from("data.csv"), "i,j\n1,2\n3,4\n".into())
+            .await
+            .unwrap();
+
+        // SQL reads the paths of the bucket through the store built by Lance
+        ctx.register_csv("t", "memory://bucket/data.csv", CsvReadOptions::new())
+            .await
+            .unwrap();
+        let batches = ctx
+            .sql("SELECT count(*) FROM t")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(batches[0].column(0).as_primitive::<Int64Type>().value(0), 2);
+        assert!(io_stats.lock().unwrap().read_iops > 0);
+
+        // Other buckets aren't registered
+        assert!(ctx
+            .runtime_env()
+            .object_store(ObjectStoreUrl::parse("memory://other").unwrap())
+            .is_err());
+    }
+}
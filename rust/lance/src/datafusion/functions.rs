@@ -0,0 +1,317 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Vector distance functions for SQL
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use arrow::compute::cast;
+use arrow_array::{
+    cast::AsArray,
+    types::{Float16Type, Float32Type, Float64Type, UInt8Type},
+    Array, ArrayRef, ArrowPrimitiveType, Float32Array,
+};
+use arrow_schema::{DataType, Field};
+use datafusion::{
+    error::{DataFusionError, Result as DFResult},
+    execution::context::SessionContext,
+    logical_expr::{ColumnarValue, ScalarUDF, ScalarUDFImpl, Signature, Volatility},
+    scalar::ScalarValue,
+};
+use lance_linalg::distance::{cosine_distance, dot, hamming::hamming, l2, Cosine, Dot, L2};
+
+/// Register the Lance functions in `ctx`:
+///
+/// * `l2_distance(a, b)`: the squared L2 distance, as used by vector search
+/// * `cosine_distance(a, b)`: one minus the cosine similarity
+/// * `dot_product(a, b)`: the dot product
+/// * `hamming(a, b)`: the number of bits that differ between vectors of bytes
+///
+/// The vectors are lists or fixed-size lists, of floats for all but `hamming`,
+/// which takes vectors of `uint8`.  Either may be a literal, such as
+/// `[1.0, 0.0]`, whose values are cast to the type of the values of the
+/// other.  The distance is null if either vector is null.
+pub fn register_lance_functions(ctx: &SessionContext) {
+    for kind in [
+        DistanceKind::L2,
+        DistanceKind::Cosine,
+        DistanceKind::Dot,
+        DistanceKind::Hamming,
+    ] {
+        ctx.register_udf(ScalarUDF::new_from_impl(DistanceUdf::new(kind)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DistanceKind {
+    L2,
+    Cosine,
+    Dot,
+    Hamming,
+}
+
+impl DistanceKind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::L2 => "l2_distance",
+            Self::Cosine => "cosine_distance",
+            Self::Dot => "dot_product",
+            Self::Hamming => "hamming",
+        }
+    }
+
+    fn distance<T: L2 + Cosine + Dot>(&self, x: &[T], y: &[T]) -> f32 {
+        match self {
+            Self::L2 => l2(x, y),
+            Self::Cosine => cosine_distance(x, y),
+            Self::Dot => dot(x, y),
+            Self::Hamming => unreachable!("hamming is computed on bytes"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DistanceUdf {
+    kind: DistanceKind,
+    signature: Signature,
+}
+
+impl DistanceUdf {
+    fn new(kind: DistanceKind) -> Self {
+        Self {
+            kind,
+            signature: Signature::any(2, Volatility::Immutable),
+        }
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> DataFusionError {
+        DataFusionError::Execution(format!("{}: {}", self.kind.name(), message))
+    }
+
+    /// The type of the values of the vectors `arg_types` are computed in
+    fn value_type(&self, arg_types: &[DataType]) -> DFResult<DataType> {
+        let [x, y] = arg_types else {
+            return Err(self.error("expects two arguments"));
+        };
+        let values_of = |data_type: &DataType| match data_type {
+            DataType::FixedSizeList(field, _) | DataType::List(field) => {
+                Ok(field.data_type().clone())
+            }
+            _ => Err(self.error(format!("expects lists of values, got {}", data_type))),
+        };
+        let (x_type, y_type) = (values_of(x)?, values_of(y)?);
+        // Literals are lists, prefer the type of the values of a column
+        let value_type = match (x, y) {
+            (DataType::List(_), DataType::FixedSizeList(..)) => y_type,
+            _ => x_type,
+        };
+        let supported = match self.kind {
+            DistanceKind::Hamming => value_type == DataType::UInt8,
+            _ => matches!(
+                value_type,
+                DataType::Float16 | DataType::Float32 | DataType::Float64
+            ),
+        };
+        if !supported {
+            return Err(self.error(format!("unsupported vectors of {}", value_type)));
+        }
+        Ok(value_type)
+    }
+}
+
+/// Cast the values of the vectors of `array` to `value_type`
+fn cast_values(array: &ArrayRef, value_type: &DataType) -> DFResult<ArrayRef> {
+    let data_type = match array.data_type() {
+        DataType::FixedSizeList(field, _) | DataType::List(field)
+            if field.data_type() == value_type =>
+        {
+            return Ok(array.clone());
+        }
+        DataType::FixedSizeList(field, size) => DataType::FixedSizeList(
+            Arc::new(Field::new(field.name(), value_type.clone(), true)),
+            *size,
+        ),
+        DataType::List(field) => {
+            DataType::List(Arc::new(Field::new(field.name(), value_type.clone(), true)))
+        }
+        _ => unreachable!("checked by value_type"),
+    };
+    Ok(cast(array, &data_type)?)
+}
+
+/// The values of the vectors of `array`, and the range of the values of each
+/// vector, or `None` if the vector is null
+fn vectors<T: ArrowPrimitiveType>(array: &ArrayRef) -> (&[T::Native], Vec<Option<Range<usize>>>) {
+    let (values, ranges) = match array.data_type() {
+        DataType::FixedSizeList(_, size) => {
+            let list = array.as_fixed_size_list();
+            let ranges = (0..list.len())
+                .map(|i| {
+                    let start = list.value_offset(i) as usize;
+                    start..start + *size as usize
+                })
+                .collect::<Vec<_>>();
+            (list.values(), ranges)
+        }
+        _ => {
+            let list = array.as_list::<i32>();
+            let ranges = list
+                .value_offsets()
+                .windows(2)
+                .map(|w| w[0] as usize..w[1] as usize)
+                .collect::<Vec<_>>();
+            (list.values(), ranges)
+        }
+    };
+    let ranges = ranges
+        .into_iter()
+        .enumerate()
+        .map(|(i, range)| array.is_valid(i).then_some(range))
+        .collect();
+    (values.as_primitive::<T>().values(), ranges)
+}
+
+impl ScalarUDFImpl for DistanceUdf {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.kind.name()
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> DFResult<DataType> {
+        self.value_type(arg_types)?;
+        Ok(DataType::Float32)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> DFResult<ColumnarValue> {
+        let arg_types = args.iter().map(|arg| arg.data_type()).collect::<Vec<_>>();
+        let value_type = self.value_type(&arg_types)?;
+        let num_rows = args
+            .iter()
+            .find_map(|arg| match arg {
+                ColumnarValue::Array(array) => Some(array.len()),
+                ColumnarValue::Scalar(_) => None,
+            })
+            .unwrap_or(1);
+        let arrays = args
+            .iter()
+            .map(|arg| cast_values(&arg.clone().into_array(num_rows)?, &value_type))
+            .collect::<DFResult<Vec<_>>>()?;
+
+        let distances = match value_type {
+            DataType::Float16 => self.distances::<Float16Type>(&arrays[0], &arrays[1]),
+            DataType::Float32 => self.distances::<Float32Type>(&arrays[0], &arrays[1]),
+            DataType::Float64 => self.distances::<Float64Type>(&arrays[0], &arrays[1]),
+            _ => self.hamming_distances(&arrays[0], &arrays[1]),
+        }?;
+        if args
+            .iter()
+            .all(|arg| matches!(arg, ColumnarValue::Scalar(_)))
+        {
+            Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(
+                &distances, 0,
+            )?))
+        } else {
+            Ok(ColumnarValue::Array(Arc::new(distances)))
+        }
+    }
+}
+
+impl DistanceUdf {
+    fn compute<N>(
+        &self,
+        (x_values, x_ranges): (&[N], Vec<Option<Range<usize>>>),
+        (y_values, y_ranges): (&[N], Vec<Option<Range<usize>>>),
+        distance: impl Fn(&[N], &[N]) -> f32,
+    ) -> DFResult<Float32Array> {
+        x_ranges
+            .into_iter()
+            .zip(y_ranges)
+            .map(|(x, y)| match (x, y) {
+                (Some(x), Some(y)) if x.len() != y.len() => Err(self.error(format!(
+                    "vectors of different lengths {} and {}",
+                    x.len(),
+                    y.len()
+                ))),
+                (Some(x), Some(y)) => Ok(Some(distance(&x_values[x], &y_values[y]))),
+                _ => Ok(None),
+            })
+            .collect()
+    }
+
+    fn distances<T: ArrowPrimitiveType>(&self, x: &ArrayRef, y: &ArrayRef) -> DFResult<Float32Array>
+    where
+        T::Native: L2 + Cosine + Dot,
+    {
+        self.compute(vectors::<T>(x), vectors::<T>(y), |x, y| {
+            self.kind.distance(x, y)
+        })
+    }
+
+    fn hamming_distances(&self, x: &ArrayRef, y: &ArrayRef) -> DFResult<Float32Array> {
+        self.compute(vectors::<UInt8Type>(x), vectors::<UInt8Type>(y), hamming)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{FixedSizeListArray, Float32Array, RecordBatch, UInt8Array};
+    use arrow_schema::Schema as ArrowSchema;
+    use lance_arrow::FixedSizeListArrayExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_distance_functions() {
+        let vectors = FixedSizeListArray::try_new_from_values(
+            Float32Array::from(vec![1.0, 0.0, 0.0, 2.0, 3.0, 4.0]),
+            2,
+        )
+        .unwrap();
+        let bits =
+            FixedSizeListArray::try_new_from_values(UInt8Array::from(vec![0, 1, 3, 255, 0, 0]), 2)
+                .unwrap();
+        let batch = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                Field::new("vec", vectors.data_type().clone(), true),
+                Field::new("bits", bits.data_type().clone(), true),
+            ])),
+            vec![Arc::new(vectors), Arc::new(bits)],
+        )
+        .unwrap();
+        let ctx = SessionContext::new();
+        ctx.register_batch("t", batch).unwrap();
+        register_lance_functions(&ctx);
+
+        let query = |sql: &'static str| {
+            let ctx = ctx.clone();
+            async move {
+                let batches = ctx.sql(sql).await.unwrap().collect().await.unwrap();
+                batches[0].column(0).as_primitive::<Float32Type>().clone()
+            }
+        };
+        assert_eq!(
+            query("SELECT l2_distance(vec, [1.0, 0.0]) FROM t").await,
+            Float32Array::from(vec![0.0, 5.0, 20.0])
+        );
+        assert_eq!(
+            query("SELECT dot_product(vec, vec) FROM t").await,
+            Float32Array::from(vec![1.0, 4.0, 25.0])
+        );
+        let cosine = query("SELECT cosine_distance(vec, [0.0, 1.0]) FROM t").await;
+        assert!((cosine.value(0) - 1.0).abs() < 1e-6);
+        assert!(cosine.value(1).abs() < 1e-6);
+        assert!((cosine.value(2) - 0.2).abs() < 1e-6);
+        assert_eq!(
+            query("SELECT hamming(bits, [0, 0]) FROM t").await,
+            Float32Array::from(vec![1.0, 10.0, 0.0])
+        );
+    }
+}
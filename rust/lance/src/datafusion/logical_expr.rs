@@ -3,12 +3,12 @@
 
 //! Extends logical expression.
 
-use arrow_schema::DataType;
+use arrow_schema::{DataType, DECIMAL128_MAX_PRECISION, DECIMAL256_MAX_PRECISION};
 
 use datafusion::logical_expr::ScalarFunctionDefinition;
 use datafusion::logical_expr::ScalarUDFImpl;
 use datafusion::logical_expr::{
-    expr::ScalarFunction, BinaryExpr, GetFieldAccess, GetIndexedField, Operator,
+    expr::ScalarFunction, BinaryExpr, Cast, GetFieldAccess, GetIndexedField, Operator,
 };
 use datafusion::prelude::*;
 use datafusion::scalar::ScalarValue;
@@ -35,6 +35,47 @@ fn resolve_value(expr: &Expr, data_type: &DataType) -> Result<Expr> {
     }
 }
 
+/// Resolve the literal compared with a column of type `column_type`.
+///
+/// Literals with more fractional digits than a decimal column can't be
+/// coerced to its type. The column is then cast to a decimal with enough
+/// scale for the literal, so that comparisons stay exact.
+fn resolve_comparison(
+    column: &Expr,
+    column_type: &DataType,
+    literal: &Expr,
+) -> Result<(Expr, Expr)> {
+    let err = match resolve_value(literal, column_type) {
+        Ok(value) => return Ok((column.clone(), value)),
+        Err(err) => err,
+    };
+    let (
+        Expr::Literal(value),
+        DataType::Decimal128(precision, scale) | DataType::Decimal256(precision, scale),
+    ) = (literal, column_type)
+    else {
+        return Err(err);
+    };
+    for extra_scale in 1..=(DECIMAL256_MAX_PRECISION - precision) {
+        let precision = precision + extra_scale;
+        let scale = scale + extra_scale as i8;
+        let widened = if matches!(column_type, DataType::Decimal128(..))
+            && precision <= DECIMAL128_MAX_PRECISION
+        {
+            DataType::Decimal128(precision, scale)
+        } else {
+            DataType::Decimal256(precision, scale)
+        };
+        if let Some(value) = safe_coerce_scalar(value, &widened) {
+            return Ok((
+                Expr::Cast(Cast::new(Box::new(column.clone()), widened)),
+                Expr::Literal(value),
+            ));
+        }
+    }
+    Err(err)
+}
+
 /// A simple helper function that interprets an Expr as a string scalar
 /// or returns None if it is not.
 pub fn get_as_string_scalar_opt(expr: &Expr) -> Option<&str> {
@@ -113,11 +154,14 @@ pub fn resolve_expr(expr: &Expr, schema: &Schema) -> Result<Expr> {
                 }))
             } else if let Some(left_type) = resolve_column_type(left.as_ref(), schema) {
                 match right.as_ref() {
-                    Expr::Literal(_) => Ok(Expr::BinaryExpr(BinaryExpr {
-                        left: left.clone(),
-                        op: *op,
-                        right: Box::new(resolve_value(right.as_ref(), &left_type)?),
-                    })),
+                    Expr::Literal(_) => {
+                        let (left, right) = resolve_comparison(left, &left_type, right)?;
+                        Ok(Expr::BinaryExpr(BinaryExpr {
+                            left: Box::new(left),
+                            op: *op,
+                            right: Box::new(right),
+                        }))
+                    }
                     // For cases complex expressions (not just literals) on right hand side like x = 1 + 1 + -2*2
                     Expr::BinaryExpr(r) => Ok(Expr::BinaryExpr(BinaryExpr {
                         left: left.clone(),
@@ -132,11 +176,14 @@ pub fn resolve_expr(expr: &Expr, schema: &Schema) -> Result<Expr> {
                 }
             } else if let Some(right_type) = resolve_column_type(right.as_ref(), schema) {
                 match left.as_ref() {
-                    Expr::Literal(_) => Ok(Expr::BinaryExpr(BinaryExpr {
-                        left: Box::new(resolve_value(left.as_ref(), &right_type)?),
-                        op: *op,
-                        right: right.clone(),
-                    })),
+                    Expr::Literal(_) => {
+                        let (right, left) = resolve_comparison(right, &right_type, left)?;
+                        Ok(Expr::BinaryExpr(BinaryExpr {
+                            left: Box::new(left),
+                            op: *op,
+                            right: Box::new(right),
+                        }))
+                    }
                     _ => Ok(expr.clone()),
                 }
             } else {
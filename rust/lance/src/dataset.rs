@@ -7,71 +7,105 @@
 use arrow_array::{RecordBatch, RecordBatchReader};
 use byteorder::{ByteOrder, LittleEndian};
 use chrono::{prelude::*, Duration};
+use datafusion::physical_plan::{ExecutionPlan, SendableRecordBatchStream};
 use deepsize::DeepSizeOf;
 use futures::future::BoxFuture;
 use futures::stream::{self, StreamExt, TryStreamExt};
 use futures::{FutureExt, Stream};
 use lance_core::datatypes::SchemaCompareOptions;
+use lance_datafusion::exec::LanceExecutionOptions;
 use lance_datafusion::utils::{peek_reader_schema, reader_to_stream};
 use lance_file::datatypes::populate_schema_dictionary;
 use lance_io::object_store::{ObjectStore, ObjectStoreParams};
 use lance_io::object_writer::ObjectWriter;
 use lance_io::traits::WriteExt;
 use lance_io::utils::{read_last_block, read_metadata_offset, read_struct};
-use lance_table::format::{Fragment, Index, Manifest, MAGIC, MAJOR_VERSION, MINOR_VERSION};
+use lance_table::format::{
+    Fragment, Index, Manifest, Partitioning, MAGIC, MAJOR_VERSION, MINOR_VERSION,
+};
 use lance_table::io::commit::{
     commit_handler_from_url, CommitError, CommitHandler, CommitLock, ManifestLocation,
 };
 use lance_table::io::manifest::{read_manifest, write_manifest};
-use log::warn;
 use object_store::path::Path;
 use prost::Message;
+use roaring::RoaringBitmap;
 use snafu::{location, Location};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Range;
 use std::pin::Pin;
 use std::sync::Arc;
 use tracing::instrument;
 
+mod append;
 pub mod builder;
 pub mod cleanup;
+pub mod config;
+mod cost;
+mod delete;
+pub(crate) mod download;
+mod extract;
+mod format_version;
 pub mod fragment;
 mod hash_joiner;
 pub mod index;
 pub mod optimize;
+mod partitioning;
+mod preview;
 pub mod progress;
+pub mod reader;
 mod rowids;
 pub mod scanner;
 mod schema_evolution;
+mod shard;
+mod sketch;
+mod split;
+mod statistics;
 mod take;
+pub mod take_service;
 pub mod transaction;
 pub mod updater;
 mod utils;
 mod write;
 
+pub use self::append::AppendDatasetParams;
 use self::builder::DatasetBuilder;
 use self::cleanup::RemovalStats;
+use self::config::{ConfigChange, DatasetConfig};
+pub use self::download::DownloadParams;
+pub use self::format_version::{FileFormatVersion, FormatReport};
 use self::fragment::FileFragment;
+pub use self::partitioning::PartitionSpec;
 use self::scanner::{DatasetRecordBatchStream, Scanner};
 use self::transaction::{Operation, Transaction};
-use self::write::write_fragments_internal;
+use self::write::{write_fragments_internal, PendingWrite};
 use crate::datatypes::Schema;
 use crate::error::box_error;
+use crate::index::minhash::MinHashQuery;
+use crate::index::vector::VectorIndexParams;
 use crate::io::commit::{commit_new_dataset, commit_transaction};
+use crate::io::exec::{LancePushdownScanExec, LanceScanExec, TakeExec};
 use crate::session::Session;
 use crate::utils::temporal::{timestamp_to_nanos, utc_now, SystemTime};
 use crate::{Error, Result};
 use hash_joiner::HashJoiner;
 pub use lance_core::ROW_ID;
-use lance_table::feature_flags::{apply_feature_flags, can_read_dataset, can_write_dataset};
+use lance_table::feature_flags::{apply_feature_flags, can_read_dataset};
+pub use lance_table::format::ColumnSketch;
 pub use schema_evolution::{
     BatchInfo, BatchUDF, ColumnAlteration, NewColumnTransform, UDFCheckpointStore,
 };
+pub use split::{
+    SplitMethod, SPLIT_METHOD_KEY, SPLIT_PART_KEY, SPLIT_SOURCE_KEY, SPLIT_SOURCE_VERSION_KEY,
+};
+pub use statistics::{DeletionStorageStats, FieldStorageStats, IndexStorageStats, StorageStats};
 pub use write::merge_insert::{
     MergeInsertBuilder, MergeInsertJob, WhenMatched, WhenNotMatched, WhenNotMatchedBySource,
 };
 pub use write::update::{UpdateBuilder, UpdateJob};
-pub use write::{write_fragments, WriteMode, WriteParams};
+pub use write::{
+    write_fragments, DatasetWriter, DeltaLogParams, VectorDimensionPolicy, WriteMode, WriteParams,
+};
 
 const INDICES_DIR: &str = "_indices";
 
@@ -92,6 +126,61 @@ pub struct Dataset {
     pub(crate) base: Path,
     pub(crate) manifest: Arc<Manifest>,
     pub(crate) session: Arc<Session>,
+    /// The end of the manifest file the dataset was loaded from, if it was
+    pub(crate) manifest_tail: Option<Arc<ManifestTail>>,
+}
+
+/// The last block of a manifest file, kept when a dataset is loaded.
+///
+/// The index section is written just before the manifest, so it is usually
+/// in this block, and the index metadata can be decoded from it when it is
+/// first needed, instead of being read again.  Queries only convert the
+/// metadata of the indices on the columns they filter, see
+/// [`DatasetIndexInternalExt::load_indices_on_fields`](crate::index::DatasetIndexInternalExt::load_indices_on_fields).
+///
+/// The schema, with the metadata of its fields, is still decoded whole with
+/// the manifest when the dataset is loaded.
+#[derive(Debug)]
+pub(crate) struct ManifestTail {
+    version: u64,
+    /// Position of the block in the manifest file
+    offset: usize,
+    block: bytes::Bytes,
+}
+
+impl ManifestTail {
+    /// Decode the index metadata of `manifest` from the block, if it is the
+    /// manifest the block was read from and its index section is in the block
+    ///
+    /// If `fields` is given, only the indices on any of these fields are kept.
+    pub(crate) fn read_indices(
+        &self,
+        manifest: &Manifest,
+        fields: Option<&[i32]>,
+    ) -> Option<Result<Vec<Index>>> {
+        if manifest.version != self.version {
+            return None;
+        }
+        let start = manifest.index_section?.checked_sub(self.offset)?;
+        let len = LittleEndian::read_u32(self.block.get(start..start + 4)?) as usize;
+        let message = self.block.get(start + 4..start + 4 + len)?;
+        Some(
+            lance_table::format::pb::IndexSection::decode(message)
+                .map_err(Error::from)
+                .and_then(|section| {
+                    section
+                        .indices
+                        .into_iter()
+                        .filter(|index| {
+                            fields.map_or(true, |fields| {
+                                index.fields.iter().any(|field| fields.contains(field))
+                            })
+                        })
+                        .map(Index::try_from)
+                        .collect::<Result<Vec<_>>>()
+                }),
+        )
+    }
 }
 
 /// Dataset Version
@@ -117,6 +206,37 @@ impl From<&Manifest> for Version {
     }
 }
 
+/// How [`Dataset::count_rows_with_strategy`] counted rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountStrategy {
+    /// From the row counts of the fragments and of their deletion files,
+    /// without reading data.
+    Metadata,
+    /// From the rows scalar indices match, without reading data.
+    Index,
+    /// By reading the columns of the filter.
+    Scan,
+}
+
+impl CountStrategy {
+    /// Whether `plan` reads data files.
+    fn reads_data(plan: &dyn ExecutionPlan) -> bool {
+        let node = plan.as_any();
+        // Takes of no columns, like those of the row ids of index searches,
+        // don't read anything
+        let takes_columns = node
+            .downcast_ref::<TakeExec>()
+            .is_some_and(|take| !take.extra_schema.fields.is_empty());
+        node.is::<LanceScanExec>()
+            || node.is::<LancePushdownScanExec>()
+            || takes_columns
+            || plan
+                .children()
+                .iter()
+                .any(|child| Self::reads_data(child.as_ref()))
+    }
+}
+
 /// Customize read behavior of a dataset.
 #[derive(Clone, Debug)]
 pub struct ReadParams {
@@ -204,10 +324,15 @@ impl Dataset {
         uri: &str,
         commit_handler: &Option<Arc<dyn CommitHandler>>,
         store_options: &Option<ObjectStoreParams>,
+        session: Option<&Session>,
     ) -> Result<(ObjectStore, Path, Arc<dyn CommitHandler>)> {
-        let (mut object_store, base_path) = match store_options.as_ref() {
-            Some(store_options) => ObjectStore::from_uri_and_params(uri, store_options).await?,
-            None => ObjectStore::from_uri(uri).await?,
+        let registered = session.and_then(|session| session.object_store_registry().resolve(uri));
+        let (mut object_store, base_path) = match (registered, store_options.as_ref()) {
+            (Some(registered), _) => registered,
+            (None, Some(store_options)) => {
+                ObjectStore::from_uri_and_params(uri, store_options).await?
+            }
+            (None, None) => ObjectStore::from_uri(uri).await?,
         };
 
         if let Some(block_size) = store_options.as_ref().and_then(|opts| opts.block_size) {
@@ -358,6 +483,11 @@ impl Dataset {
         }
 
         populate_schema_dictionary(&mut manifest.schema, object_reader.as_ref()).await?;
+        let manifest_tail = ManifestTail {
+            version: manifest.version,
+            offset: manifest_size - last_block.len(),
+            block: last_block,
+        };
         Ok(Self {
             object_store,
             base: base_path,
@@ -365,6 +495,7 @@ impl Dataset {
             manifest: Arc::new(manifest),
             commit_handler,
             session,
+            manifest_tail: Some(Arc::new(manifest_tail)),
         })
     }
 
@@ -374,144 +505,36 @@ impl Dataset {
         uri: &str,
         params: Option<WriteParams>,
     ) -> Result<Self> {
-        let mut params = params.unwrap_or_default();
-        let (object_store, base, commit_handler) =
-            Self::params_from_uri(uri, &params.commit_handler, &params.store_params).await?;
-
-        // Read expected manifest path for the dataset
-        let dataset_exists = match commit_handler
-            .resolve_latest_version(&base, &object_store)
-            .await
-        {
-            Ok(_) => true,
-            Err(Error::NotFound { .. }) => false,
-            Err(e) => return Err(e),
-        };
-
-        let (batches, schema) = peek_reader_schema(Box::new(batches)).await?;
+        let pending = PendingWrite::try_new(uri, params).await?;
+
+        let target = pending
+            .dataset
+            .as_ref()
+            .filter(|_| matches!(pending.params.mode, WriteMode::Append))
+            .map(|d| d.schema());
+        let batches = write::conform_views(batches, target)?;
+        let (batches, schema) = peek_reader_schema(batches).await?;
         let stream = reader_to_stream(batches);
 
-        // Running checks for the different write modes
-        // create + dataset already exists = error
-        if dataset_exists && matches!(params.mode, WriteMode::Create) {
-            return Err(Error::DatasetAlreadyExists {
-                uri: uri.to_owned(),
-                location: location!(),
-            });
-        }
-
-        // append + dataset doesn't already exists = warn + switch to create mode
-        if !dataset_exists
-            && (matches!(params.mode, WriteMode::Append)
-                || matches!(params.mode, WriteMode::Overwrite))
-        {
-            warn!("No existing dataset at {uri}, it will be created");
-            params = WriteParams {
-                mode: WriteMode::Create,
-                ..params
-            };
-        }
-        let params = params; // discard mut
-
-        let dataset = if matches!(params.mode, WriteMode::Create) {
-            None
+        let (stream, schema) = if let Some(policy) = pending.params.conform_vectors {
+            write::conform_vectors(stream, &schema, target, policy)?
         } else {
-            // pull the store params from write params because there might be creds in there
-            Some(
-                DatasetBuilder::from_uri(uri)
-                    .with_read_params(ReadParams {
-                        store_options: params.store_params.clone(),
-                        commit_handler: params.commit_handler.clone(),
-                        ..Default::default()
-                    })
-                    .load()
-                    .await?,
-            )
+            (stream, schema)
         };
 
-        // append + input schema different from existing schema = error
-        if matches!(params.mode, WriteMode::Append) {
-            if let Some(d) = dataset.as_ref() {
-                let m = d.manifest.as_ref();
-                schema.check_compatible(
-                    &m.schema,
-                    &SchemaCompareOptions {
-                        compare_dictionary: true,
-                        ..Default::default()
-                    },
-                )?;
-            }
-        }
-
-        if let Some(d) = dataset.as_ref() {
-            if !can_write_dataset(d.manifest.writer_feature_flags) {
-                let message = format!(
-                    "This dataset cannot be written by this version of Lance. \
-                Please upgrade Lance to write to this dataset.\n Flags: {}",
-                    d.manifest.writer_feature_flags
-                );
-                return Err(Error::NotSupported {
-                    source: message.into(),
-                    location: location!(),
-                });
-            }
-        }
+        pending.check_schema(&schema)?;
 
-        let object_store = Arc::new(object_store);
         let fragments = write_fragments_internal(
-            dataset.as_ref(),
-            object_store.clone(),
-            &base,
+            pending.dataset.as_ref(),
+            pending.object_store.clone(),
+            &pending.base,
             &schema,
             stream,
-            params.clone(),
+            pending.params.clone(),
         )
         .await?;
 
-        let operation = match params.mode {
-            WriteMode::Create | WriteMode::Overwrite => Operation::Overwrite { schema, fragments },
-            WriteMode::Append => Operation::Append { fragments },
-        };
-
-        let transaction = Transaction::new(
-            dataset.as_ref().map(|ds| ds.manifest.version).unwrap_or(0),
-            operation,
-            None,
-        );
-
-        let manifest_config = ManifestWriteConfig {
-            use_move_stable_row_ids: params.enable_move_stable_row_ids,
-            ..Default::default()
-        };
-        let manifest = if let Some(dataset) = &dataset {
-            commit_transaction(
-                dataset,
-                &object_store,
-                commit_handler.as_ref(),
-                &transaction,
-                &manifest_config,
-                &Default::default(),
-            )
-            .await?
-        } else {
-            commit_new_dataset(
-                &object_store,
-                commit_handler.as_ref(),
-                &base,
-                &transaction,
-                &manifest_config,
-            )
-            .await?
-        };
-
-        Ok(Self {
-            object_store,
-            base,
-            uri: uri.to_string(),
-            manifest: Arc::new(manifest.clone()),
-            session: Arc::new(Session::default()),
-            commit_handler,
-        })
+        pending.commit(schema, fragments).await
     }
 
     /// Write to or Create a [Dataset] with a stream of [RecordBatch]s.
@@ -530,16 +553,20 @@ impl Dataset {
         Self::write_impl(batches, uri, params).await
     }
 
-    async fn append_impl(
-        &mut self,
+    /// Check `batches` and `params` for an append to the dataset, and conform
+    /// them to its schema
+    async fn prepare_append(
+        &self,
         batches: Box<dyn RecordBatchReader + Send>,
         params: Option<WriteParams>,
-    ) -> Result<()> {
+    ) -> Result<(SendableRecordBatchStream, Schema, WriteParams)> {
         // Force append mode
-        let params = WriteParams {
+        let mut params = WriteParams {
             mode: WriteMode::Append,
             ..params.unwrap_or_default()
         };
+        params.partitioning =
+            partitioning::write_partitioning(Some(self), true, params.partitioning.as_ref())?;
 
         if params.commit_handler.is_some() || params.store_params.is_some() {
             return Err(Error::InvalidInput {
@@ -549,11 +576,23 @@ impl Dataset {
             });
         }
 
-        let (batches, schema) = peek_reader_schema(Box::new(batches)).await?;
+        let batches = write::conform_views(batches, Some(self.schema()))?;
+        let (batches, schema) = peek_reader_schema(batches).await?;
         let stream = reader_to_stream(batches);
+        let policy = match params.conform_vectors {
+            Some(policy) => Some(policy),
+            None => self.config().conform_vectors()?,
+        };
+        let (stream, schema) = if let Some(policy) = policy {
+            write::conform_vectors(stream, &schema, Some(self.schema()), policy)?
+        } else {
+            (stream, schema)
+        };
 
         // Return Error if append and input schema differ
-        self.manifest.schema.check_compatible(
+        let expected =
+            write::append_schema(&schema, &self.manifest.schema, params.allow_missing_columns)?;
+        expected.check_compatible(
             &schema,
             &SchemaCompareOptions {
                 compare_dictionary: true,
@@ -561,16 +600,11 @@ impl Dataset {
             },
         )?;
 
-        let fragments = write_fragments_internal(
-            Some(self),
-            self.object_store.clone(),
-            &self.base,
-            &schema,
-            stream,
-            params.clone(),
-        )
-        .await?;
+        Ok((stream, schema, params))
+    }
 
+    /// Commit the append of `fragments`
+    async fn commit_append(&mut self, fragments: Vec<Fragment>) -> Result<()> {
         let transaction =
             Transaction::new(self.manifest.version, Operation::Append { fragments }, None);
 
@@ -589,6 +623,29 @@ impl Dataset {
         Ok(())
     }
 
+    async fn append_impl(
+        &mut self,
+        batches: Box<dyn RecordBatchReader + Send>,
+        params: Option<WriteParams>,
+    ) -> Result<()> {
+        let (stream, schema, params) = self.prepare_append(batches, params).await?;
+
+        let mut fragments = write_fragments_internal(
+            Some(self),
+            self.object_store.clone(),
+            &self.base,
+            &schema,
+            stream,
+            params.clone(),
+        )
+        .await?;
+        write::mark_delta_fragments(&params, &mut fragments);
+
+        self.commit_append(fragments).await?;
+        write::maybe_merge_delta_fragments(self, &params).await;
+        Ok(())
+    }
+
     /// Append to existing [Dataset] with a stream of [RecordBatch]s
     ///
     /// Returns void result or Returns [Error]
@@ -603,6 +660,30 @@ impl Dataset {
         self.append_impl(batches, params).await
     }
 
+    /// Append to the dataset the rows of each partition of `partition_by`
+    /// concurrently, in a single commit
+    ///
+    /// `partition_by` is a SQL expression over the columns of `batches`, such
+    /// as `region` or `date_trunc('day', ts)`.  The rows of each of its values
+    /// are written to their own fragments, by a writer of their own, so
+    /// writing many logical partitions, such as a day each, takes a commit
+    /// rather than one per partition, which would conflict when made
+    /// concurrently.
+    ///
+    /// A writer is kept open for each partition until the input is done.
+    pub async fn append_partitioned(
+        &mut self,
+        batches: impl RecordBatchReader + Send + 'static,
+        partition_by: &str,
+        params: Option<WriteParams>,
+    ) -> Result<()> {
+        let (stream, schema, params) = self.prepare_append(Box::new(batches), params).await?;
+        let fragments =
+            write::write_fragments_by_partition(self, &schema, stream, partition_by, params)
+                .await?;
+        self.commit_append(fragments).await
+    }
+
     /// Get the fully qualified URI of this dataset.
     pub fn uri(&self) -> &str {
         &self.uri
@@ -638,6 +719,81 @@ impl Dataset {
         Transaction::try_from(transaction).map(Some)
     }
 
+    /// Get the rows of this version that changed since `since_version`, by
+    /// fragment id.
+    ///
+    /// This is meant for updating indices and other derived data
+    /// incrementally. Rows deleted or rewritten in fragments that exist in both
+    /// versions are listed by their offset in the fragment, while fragments
+    /// added or rewritten since then are listed with all of their rows.
+    /// Unchanged fragments are left out. If the history between the versions
+    /// can't be followed, because of an overwrite, a restore or a missing
+    /// transaction file, every fragment is listed with all of its rows.
+    pub async fn modified_rows(&self, since_version: u64) -> Result<HashMap<u64, RoaringBitmap>> {
+        if since_version > self.manifest.version {
+            return Err(Error::invalid_input(
+                format!(
+                    "Version {} is newer than the checked out version {}",
+                    since_version, self.manifest.version
+                ),
+                location!(),
+            ));
+        }
+        let old_fragment_ids = self
+            .checkout_version(since_version)
+            .await?
+            .manifest
+            .fragments
+            .iter()
+            .map(|f| f.id)
+            .collect::<HashSet<_>>();
+
+        let mut modified_rows = HashMap::<u64, RoaringBitmap>::new();
+        let mut rewritten_fragment_ids = HashSet::new();
+        let mut all_rewritten = false;
+        for version in since_version + 1..=self.manifest.version {
+            let transaction = self
+                .checkout_version(version)
+                .await?
+                .read_transaction()
+                .await?;
+            match transaction {
+                None
+                | Some(Transaction {
+                    operation: Operation::Overwrite { .. } | Operation::Restore { .. },
+                    ..
+                }) => {
+                    all_rewritten = true;
+                    break;
+                }
+                Some(transaction) => {
+                    if let Operation::Merge { fragments, .. } = &transaction.operation {
+                        rewritten_fragment_ids.extend(fragments.iter().map(|f| f.id));
+                    }
+                    for (fragment_id, rows) in transaction.modified_rows {
+                        *modified_rows.entry(fragment_id).or_default() |= rows;
+                    }
+                }
+            }
+        }
+
+        let mut changes = HashMap::new();
+        for fragment in self.get_fragments() {
+            let fragment_id = fragment.id() as u64;
+            if all_rewritten
+                || rewritten_fragment_ids.contains(&fragment_id)
+                || !old_fragment_ids.contains(&fragment_id)
+            {
+                let mut rows = RoaringBitmap::new();
+                rows.insert_range(0..fragment.physical_rows().await? as u32);
+                changes.insert(fragment_id, rows);
+            } else if let Some(rows) = modified_rows.remove(&fragment_id) {
+                changes.insert(fragment_id, rows);
+            }
+        }
+        Ok(changes)
+    }
+
     /// Restore the currently checked out version of the dataset as the latest version.
     pub async fn restore(&mut self) -> Result<()> {
         let latest_manifest = self.latest_manifest().await?;
@@ -674,6 +830,8 @@ impl Dataset {
     /// Once a version is removed it can no longer be checked out or restored.  Any data unique
     /// to that version will be lost.
     ///
+    /// The retention configured for the dataset is given by [`DatasetConfig::retention`].
+    ///
     /// # Arguments
     ///
     /// * `older_than` - Versions older than this will be deleted.
@@ -742,7 +900,7 @@ impl Dataset {
         )?;
 
         let (object_store, base, commit_handler) =
-            Self::params_from_uri(base_uri, &commit_handler, &store_params).await?;
+            Self::params_from_uri(base_uri, &commit_handler, &store_params, None).await?;
 
         // Test if the dataset exists
         let dataset_exists = match commit_handler
@@ -806,6 +964,7 @@ impl Dataset {
             manifest: Arc::new(manifest.clone()),
             session: Arc::new(Session::default()),
             commit_handler,
+            manifest_tail: None,
         })
     }
 
@@ -929,25 +1088,44 @@ impl Dataset {
     /// Count the number of rows in the dataset.
     ///
     /// It offers a fast path of counting rows by just computing via metadata.
-    #[instrument(skip_all)]
     pub async fn count_rows(&self, filter: Option<String>) -> Result<usize> {
-        // TODO: consolidate the count_rows into Scanner plan.
-        if let Some(filter) = filter {
-            let mut scanner = self.scan();
-            scanner.filter(&filter)?;
-            Ok(scanner
-                .project::<String>(&[])?
-                .with_row_id() // TODO: fix scan plan to not require row_id for count_rows.
-                .count_rows()
-                .await? as usize)
-        } else {
+        Ok(self.count_rows_with_strategy(filter).await?.0)
+    }
+
+    /// Count the number of rows in the dataset, and report how they were
+    /// counted.
+    ///
+    /// Rows are counted from the metadata of the fragments without a filter,
+    /// from scalar indices if they answer the whole filter, and by scanning
+    /// the filtered columns otherwise. Deleted rows are never counted.
+    #[instrument(skip_all, fields(strategy = tracing::field::Empty))]
+    pub async fn count_rows_with_strategy(
+        &self,
+        filter: Option<String>,
+    ) -> Result<(usize, CountStrategy)> {
+        let Some(filter) = filter else {
             let cnts = stream::iter(self.get_fragments())
                 .map(|f| async move { f.count_rows().await })
                 .buffer_unordered(16)
                 .try_collect::<Vec<_>>()
                 .await?;
-            Ok(cnts.iter().sum())
-        }
+            tracing::Span::current().record("strategy", "metadata");
+            return Ok((cnts.iter().sum(), CountStrategy::Metadata));
+        };
+
+        let mut scanner = self.scan();
+        scanner.filter(&filter)?;
+        // TODO: fix scan plan to not require row_id for count_rows.
+        scanner.project::<String>(&[])?.with_row_id();
+        let plan = scanner.create_plan().await?;
+        let strategy = if CountStrategy::reads_data(plan.as_ref()) {
+            CountStrategy::Scan
+        } else {
+            CountStrategy::Index
+        };
+        tracing::Span::current().record("strategy", format!("{:?}", strategy).as_str());
+        let count = Scanner::count_plan_rows(plan).await? as usize;
+        Ok((count, strategy))
     }
 
     #[instrument(skip_all, fields(num_rows=row_indices.len()))]
@@ -980,8 +1158,30 @@ impl Dataset {
         self.take(&ids, projection).await
     }
 
+    /// A quick preview of up to `n` rows, for example to show a table in a UI.
+    ///
+    /// The first rows of a few fragments spread over the dataset are read
+    /// concurrently, so the preview is representative and does not wait on a
+    /// single slow read. Reading stops after `n` rows or about `max_bytes` of
+    /// data, and when `timeout` expires the rows read so far are returned.
+    pub async fn preview(
+        &self,
+        n: usize,
+        max_bytes: usize,
+        timeout: std::time::Duration,
+    ) -> Result<RecordBatch> {
+        preview::preview(self, n, max_bytes, timeout).await
+    }
+
     /// Delete rows based on a predicate.
+    ///
+    /// When the predicate can use scalar indices, they locate the rows to
+    /// delete, and only the fragments with matching rows are rewritten.
     pub async fn delete(&mut self, predicate: &str) -> Result<()> {
+        if delete::uses_scalar_index(self, predicate).await? {
+            return delete::delete_indexed(self, predicate).await;
+        }
+
         let mut updated_fragments: Vec<Fragment> = Vec::new();
         let mut deleted_fragment_ids: Vec<u64> = Vec::new();
         stream::iter(self.get_fragments())
@@ -1004,29 +1204,43 @@ impl Dataset {
             })
             .await?;
 
-        let transaction = Transaction::new(
-            self.manifest.version,
-            Operation::Delete {
-                updated_fragments,
-                deleted_fragment_ids,
-                predicate: predicate.to_string(),
-            },
-            None,
-        );
-
-        let manifest = commit_transaction(
+        delete::commit_delete(
             self,
-            &self.object_store,
-            self.commit_handler.as_ref(),
-            &transaction,
-            &Default::default(),
-            &Default::default(),
+            updated_fragments,
+            deleted_fragment_ids,
+            predicate.to_string(),
         )
-        .await?;
+        .await
+    }
 
-        self.manifest = Arc::new(manifest);
+    /// Delete rows by their row ids.
+    ///
+    /// The deletion vectors are built directly from the ids, without
+    /// evaluating a predicate, so this is much cheaper than [`Self::delete`]
+    /// when the rows to delete are already known, for example from a
+    /// deduplication job.
+    ///
+    /// The ids are row addresses, as in the `_rowid` column of scans. Ids in
+    /// fragments that are not in the dataset are skipped, and no version is
+    /// committed if no row is deleted. An id past the end of its fragment is
+    /// an [`Error::InvalidInput`].
+    pub async fn delete_rows(&mut self, row_ids: impl IntoIterator<Item = u64>) -> Result<()> {
+        delete::delete_rows(self, row_ids).await
+    }
 
-        Ok(())
+    /// Append all rows of `other` to this dataset.
+    ///
+    /// When the schemas match, the data files and deletion files of `other`
+    /// are copied into this dataset instead of being read and written again,
+    /// which makes merging shards cheap. With
+    /// [`AppendDatasetParams::allow_cast`], columns whose types differ are
+    /// cast to the types of this dataset. Indices of `other` are not copied.
+    pub async fn append_dataset(
+        &mut self,
+        other: &Self,
+        params: Option<AppendDatasetParams>,
+    ) -> Result<()> {
+        append::append_dataset(self, other, params).await
     }
 
     pub async fn count_deleted_rows(&self) -> Result<usize> {
@@ -1037,70 +1251,275 @@ impl Dataset {
             .await
     }
 
-    pub(crate) fn object_store(&self) -> &ObjectStore {
-        &self.object_store
+    /// Report how much storage the dataset uses.
+    ///
+    /// The data files of the current version are broken down per field, along
+    /// with the size of every index and the overhead of deleted rows. It also
+    /// reports the total size of the dataset directory, which includes files
+    /// only referenced by older versions.
+    pub async fn storage_stats(&self) -> Result<StorageStats> {
+        statistics::storage_stats(self).await
     }
 
-    pub(crate) async fn manifest_file(&self, version: u64) -> Result<Path> {
-        self.commit_handler
-            .resolve_version(&self.base, version, &self.object_store.inner)
-            .await
+    /// A count-distinct and heavy-hitters sketch of the values of `column`.
+    ///
+    /// The sketches kept for the data files written with
+    /// [`WriteParams::sketch_columns`] are merged, and only the fragments
+    /// without one, or with deleted rows, are scanned. Use
+    /// [`ColumnSketch::count_distinct`] and [`ColumnSketch::top_k`] to query it.
+    pub async fn column_sketch(&self, column: &str) -> Result<ColumnSketch> {
+        sketch::column_sketch(self, column).await
     }
 
-    pub(crate) fn data_dir(&self) -> Path {
-        self.base.child(DATA_DIR)
+    /// The configuration of the checked out version.
+    ///
+    /// See [`config`] for the settings Lance understands.
+    pub fn config(&self) -> DatasetConfig<'_> {
+        DatasetConfig::new(&self.manifest.config)
     }
 
-    pub(crate) fn indices_dir(&self) -> Path {
-        self.base.child(INDICES_DIR)
+    /// The options to execute queries that can spill to disk with.
+    ///
+    /// The [`LanceConfig`](crate::session::LanceConfig) of the session takes
+    /// precedence over the configuration of the dataset.
+    pub(crate) fn spilling_execution_options(&self) -> Result<LanceExecutionOptions> {
+        let mut options = self.session.config().spilling_execution_options();
+        if options.mem_pool_size.is_none() {
+            options.mem_pool_size = self.config().mem_pool_size()?;
+        }
+        Ok(options)
     }
 
-    pub fn session(&self) -> Arc<Session> {
-        self.session.clone()
+    /// Set the values of configuration keys, committing a new version.
+    ///
+    /// The values of the settings Lance understands are checked before they
+    /// are stored.
+    pub async fn update_config(
+        &mut self,
+        values: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<()> {
+        config::update_config(self, values.into_iter().collect(), vec![]).await
     }
 
-    pub fn version(&self) -> Version {
-        Version::from(self.manifest.as_ref())
+    /// Remove configuration keys, so their default values are used, committing
+    /// a new version.
+    pub async fn delete_config_keys(&mut self, keys: &[&str]) -> Result<()> {
+        let keys = keys.iter().map(|key| key.to_string()).collect();
+        config::update_config(self, HashMap::new(), keys).await
     }
 
-    /// Get the number of entries currently in the index cache.
-    pub fn index_cache_entry_count(&self) -> usize {
-        self.session.index_cache.get_size()
+    /// The changes to the configuration, up to the checked out version.
+    ///
+    /// Versions that didn't change the configuration are left out. Versions
+    /// that have been cleaned up are skipped, so a change is reported against
+    /// the oldest version still available before it.
+    pub async fn config_history(&self) -> Result<Vec<ConfigChange>> {
+        config::config_history(self).await
     }
 
-    /// Get cache hit ratio.
-    pub fn index_cache_hit_rate(&self) -> f32 {
-        self.session.index_cache.hit_rate()
+    /// Report the format versions of the checked out version.
+    ///
+    /// This lists the file format versions of the data files and the features
+    /// required by the manifest, and whether this library can read and write
+    /// the dataset.
+    pub fn format_report(&self) -> FormatReport {
+        format_version::format_report(self)
     }
 
-    pub fn cache_size_bytes(&self) -> u64 {
-        self.session.deep_size_of() as u64
+    /// Rewrite the data files so they all use the `target` file format version.
+    ///
+    /// Fragments are rewritten in place: they keep their ids and the positions
+    /// of their rows, so indices and deletion files are kept. The rewritten
+    /// files are committed as a new version.
+    pub async fn migrate_format(&mut self, target: FileFormatVersion) -> Result<()> {
+        format_version::migrate_format(self, target).await
     }
 
-    /// Get all versions.
-    pub async fn versions(&self) -> Result<Vec<Version>> {
-        let mut versions: Vec<Version> = self
-            .commit_handler
-            .list_manifests(&self.base, &self.object_store.inner)
-            .await?
-            .try_filter_map(|path| async move {
-                match read_manifest(&self.object_store, &path).await {
-                    Ok(manifest) => Ok(Some(Version::from(&manifest))),
-                    Err(e) => Err(e),
-                }
-            })
-            .try_collect()
-            .await?;
-
-        // TODO: this API should support pagination
-        versions.sort_by_key(|v| v.version);
-
-        Ok(versions)
+    /// Copy the files needed to read `version` to `uri`, usually a local
+    /// directory, and open the copy.
+    ///
+    /// The copy is a standalone dataset with a single version, numbered like
+    /// the source version. `params` can narrow it down to some columns and
+    /// fragments. Indices are copied when they only cover the copied columns
+    /// and fragments.
+    pub async fn download(&self, version: u64, uri: &str, params: &DownloadParams) -> Result<Self> {
+        download::download(self, version, uri, params).await
     }
 
-    /// Get the latest version of the dataset
-    /// This is meant to be a fast path for checking if a dataset has changed. This is why
-    /// we don't return the full version struct.
+    /// Create a new dataset at `uri` that holds only `columns` of this one.
+    ///
+    /// The new dataset shares no files with this one. Data files that hold
+    /// only selected columns are copied as they are when they already use the
+    /// file format of `params`. Other fragments are read and written again.
+    /// Indices are not carried over.
+    pub async fn extract_columns(
+        &self,
+        columns: &[&str],
+        uri: &str,
+        params: Option<WriteParams>,
+    ) -> Result<Self> {
+        extract::extract_columns(self, columns, uri, params).await
+    }
+
+    /// Export the index named `name` to a standalone artifact at `uri`.
+    ///
+    /// The artifact can be imported with [`Self::import_index`] into a
+    /// replica of this dataset, to reuse an expensive index build.
+    pub async fn export_index(&self, name: &str, uri: &str) -> Result<()> {
+        crate::index::artifact::export_index(self, name, uri).await
+    }
+
+    /// Import an index exported with [`Self::export_index`].
+    ///
+    /// The fragments covered by the index must be identical to the ones of
+    /// the exported dataset, as checked with checksums stored in the
+    /// artifact. This holds for replicas copied from the same data files, for
+    /// example with [`Self::download`].
+    pub async fn import_index(&mut self, uri: &str) -> Result<()> {
+        crate::index::artifact::import_index(self, uri).await
+    }
+
+    /// Create an IVF_PQ index on `column` from IVF partition assignments and
+    /// PQ codes computed outside of Lance, for example by a distributed job.
+    ///
+    /// `params` must be IVF_PQ parameters with the IVF centroids and PQ
+    /// codebook the assignments were computed with. Each batch of
+    /// `assignments` has the row id (`_rowid`), partition (`__ivf_part_id`)
+    /// and PQ code (`__pq_code`) of some rows. Every row with a vector must be
+    /// assigned exactly once, and the assignments are validated before the
+    /// index is committed.
+    ///
+    /// The assignments must be computed the way Lance computes them. With the
+    /// L2 and cosine metrics, a vector's PQ code encodes its residual, the
+    /// vector minus the centroid of its partition, and with cosine the vector
+    /// is normalized first. With the dot metric, the PQ code encodes the
+    /// vector itself. Codes computed otherwise are not detected, but make the
+    /// index return the wrong rows.
+    pub async fn create_index_from_assignments(
+        &mut self,
+        column: &str,
+        name: Option<String>,
+        params: &VectorIndexParams,
+        assignments: impl RecordBatchReader + Send,
+    ) -> Result<()> {
+        crate::index::vector::ivf::create_index_from_assignments(
+            self,
+            column,
+            name,
+            params,
+            assignments,
+        )
+        .await
+    }
+
+    /// Find the rows whose value of `column` has an estimated Jaccard
+    /// similarity of at least `threshold` with `query`.
+    ///
+    /// Uses the MinHash index on the column, created with
+    /// [`lance_index::IndexType::MinHash`]. Returns the row ids and similarities, most
+    /// similar first.
+    pub async fn jaccard_search(
+        &self,
+        column: &str,
+        query: MinHashQuery<'_>,
+        threshold: f32,
+    ) -> Result<Vec<(u64, f32)>> {
+        crate::index::minhash::jaccard_search(self, column, query, threshold).await
+    }
+
+    /// Find the pairs of rows whose values of `column` have an estimated
+    /// Jaccard similarity of at least `threshold`, to deduplicate a corpus.
+    ///
+    /// Uses the MinHash index on the column. Returns `(row_id, row_id,
+    /// similarity)` with the smaller row id first, most similar pairs first.
+    pub async fn near_duplicates(
+        &self,
+        column: &str,
+        threshold: f32,
+    ) -> Result<Vec<(u64, u64, f32)>> {
+        crate::index::minhash::near_duplicates(self, column, threshold).await
+    }
+
+    /// Split this dataset into two new datasets, at `selected_uri` for the
+    /// rows picked by `method` and at `remainder_uri` for the other rows.
+    ///
+    /// Both datasets copy the data files of this one and hide the rows of the
+    /// other side with deletion files, so fragment ids and stable row ids are
+    /// preserved. Their schema metadata records the source uri and version
+    /// and the split method. Indices are not carried over.
+    pub async fn split(
+        &self,
+        method: SplitMethod,
+        selected_uri: &str,
+        remainder_uri: &str,
+    ) -> Result<(Self, Self)> {
+        split::split(self, method, selected_uri, remainder_uri).await
+    }
+
+    pub(crate) fn object_store(&self) -> &ObjectStore {
+        &self.object_store
+    }
+
+    pub(crate) async fn manifest_file(&self, version: u64) -> Result<Path> {
+        self.commit_handler
+            .resolve_version(&self.base, version, &self.object_store.inner)
+            .await
+    }
+
+    pub(crate) fn data_dir(&self) -> Path {
+        self.base.child(DATA_DIR)
+    }
+
+    pub(crate) fn indices_dir(&self) -> Path {
+        self.base.child(INDICES_DIR)
+    }
+
+    pub fn session(&self) -> Arc<Session> {
+        self.session.clone()
+    }
+
+    pub fn version(&self) -> Version {
+        Version::from(self.manifest.as_ref())
+    }
+
+    /// Get the number of entries currently in the index cache.
+    pub fn index_cache_entry_count(&self) -> usize {
+        self.session.index_cache.get_size()
+    }
+
+    /// Get cache hit ratio.
+    pub fn index_cache_hit_rate(&self) -> f32 {
+        self.session.index_cache.hit_rate()
+    }
+
+    pub fn cache_size_bytes(&self) -> u64 {
+        self.session.deep_size_of() as u64
+    }
+
+    /// Get all versions.
+    pub async fn versions(&self) -> Result<Vec<Version>> {
+        let mut versions: Vec<Version> = self
+            .commit_handler
+            .list_manifests(&self.base, &self.object_store.inner)
+            .await?
+            .try_filter_map(|path| async move {
+                match read_manifest(&self.object_store, &path).await {
+                    Ok(manifest) => Ok(Some(Version::from(&manifest))),
+                    Err(e) => Err(e),
+                }
+            })
+            .try_collect()
+            .await?;
+
+        // TODO: this API should support pagination
+        versions.sort_by_key(|v| v.version);
+
+        Ok(versions)
+    }
+
+    /// Get the latest version of the dataset
+    /// This is meant to be a fast path for checking if a dataset has changed. This is why
+    /// we don't return the full version struct.
     pub async fn latest_version_id(&self) -> Result<u64> {
         self.commit_handler
             .resolve_latest_version_id(&self.base, &self.object_store)
@@ -1127,6 +1546,21 @@ impl Dataset {
             .collect()
     }
 
+    /// The partitioning of the dataset, if it was written with one.
+    pub fn partitioning(&self) -> Option<PartitionSpec> {
+        let partitioning = self.manifest.partitioning.as_ref()?;
+        PartitionSpec::from_partitioning(partitioning, self.schema()).ok()
+    }
+
+    /// Get fragments, grouped by partition.
+    ///
+    /// Fragments holding rows of several partitions, such as the ones written
+    /// by updates, are under `None`. Datasets partitioned the same way can be
+    /// joined partition by partition.
+    pub fn partitions(&self) -> BTreeMap<Option<u32>, Vec<FileFragment>> {
+        partitioning::partitions(self)
+    }
+
     pub fn get_fragment(&self, fragment_id: usize) -> Option<FileFragment> {
         let dataset = Arc::new(self.clone());
         let fragment = self
@@ -1200,6 +1634,19 @@ impl Dataset {
         schema_evolution::add_columns(self, transforms, read_columns).await
     }
 
+    /// Rewrite the values of existing columns in the dataset.
+    ///
+    /// The transform returns the new values of each column, named by its path
+    /// (e.g. `metadata.tags`). Only these columns are rewritten, so a nested
+    /// field can be updated without rewriting the other fields of its struct.
+    pub async fn update_columns(
+        &mut self,
+        transforms: NewColumnTransform,
+        read_columns: Option<Vec<String>>,
+    ) -> Result<()> {
+        schema_evolution::update_columns(self, transforms, read_columns).await
+    }
+
     /// Modify columns in the dataset, changing their name, type, or nullability.
     ///
     /// If a column has an index, it's index will be preserved.
@@ -1220,9 +1667,10 @@ impl Dataset {
 
 #[derive(Debug)]
 pub(crate) struct ManifestWriteConfig {
-    auto_set_feature_flags: bool,  // default true
-    timestamp: Option<SystemTime>, // default None
-    use_move_stable_row_ids: bool, // default false
+    auto_set_feature_flags: bool,       // default true
+    timestamp: Option<SystemTime>,      // default None
+    use_move_stable_row_ids: bool,      // default false
+    partitioning: Option<Partitioning>, // default None, only used by overwrites
 }
 
 impl Default for ManifestWriteConfig {
@@ -1231,11 +1679,13 @@ impl Default for ManifestWriteConfig {
             auto_set_feature_flags: true,
             timestamp: None,
             use_move_stable_row_ids: false,
+            partitioning: None,
         }
     }
 }
 
 /// Commit a manifest file and create a copy at the latest manifest path.
+#[instrument(level = "debug", skip_all, fields(version = manifest.version))]
 pub(crate) async fn write_manifest_file(
     object_store: &ObjectStore,
     commit_handler: &dyn CommitHandler,
@@ -1245,7 +1695,7 @@ pub(crate) async fn write_manifest_file(
     config: &ManifestWriteConfig,
 ) -> std::result::Result<(), CommitError> {
     if config.auto_set_feature_flags {
-        apply_feature_flags(manifest)?;
+        apply_feature_flags(manifest, indices.as_deref().unwrap_or_default())?;
     }
     manifest.set_timestamp(timestamp_to_nanos(config.timestamp));
 
@@ -1287,23 +1737,34 @@ mod tests {
 
     use super::*;
     use crate::arrow::FixedSizeListArrayExt;
+    use crate::dataset::config::CONSISTENCY_CONFORM_VECTORS;
     use crate::dataset::optimize::{compact_files, CompactionOptions};
+    use crate::dataset::scanner::ColumnOrdering;
     use crate::dataset::WriteMode::Overwrite;
     use crate::index::scalar::ScalarIndexParams;
     use crate::index::vector::VectorIndexParams;
+    use crate::index::DatasetIndexInternalExt;
     use crate::utils::test::TestDatasetGenerator;
 
     use arrow::array::as_struct_array;
     use arrow::compute::concat_batches;
+    use arrow_array::types::{
+        Float32Type, Float64Type, IntervalMonthDayNanoType, Time64MicrosecondType,
+    };
     use arrow_array::{
         builder::StringDictionaryBuilder, cast::as_string_array, types::Int32Type, ArrayRef,
-        DictionaryArray, Float32Array, Int32Array, Int64Array, Int8Array, Int8DictionaryArray,
-        RecordBatchIterator, StringArray, UInt16Array, UInt32Array,
+        BinaryArray, BinaryViewArray, Decimal128Array, Decimal256Array, DictionaryArray,
+        DurationMillisecondArray, Float32Array, Int32Array, Int64Array, Int8Array,
+        Int8DictionaryArray, IntervalMonthDayNanoArray, IntervalYearMonthArray, LargeBinaryArray,
+        LargeStringArray, RecordBatchIterator, StringArray, StringViewArray,
+        Time64MicrosecondArray, UInt16Array, UInt32Array,
     };
-    use arrow_array::{FixedSizeListArray, StructArray};
+    use arrow_array::{cast::AsArray, Array, FixedSizeListArray, ListArray, StructArray};
+    use arrow_buffer::i256;
     use arrow_ord::sort::sort_to_indices;
     use arrow_schema::{
-        DataType, Field as ArrowField, Fields as ArrowFields, Schema as ArrowSchema,
+        DataType, Field as ArrowField, Fields as ArrowFields, IntervalUnit, Schema as ArrowSchema,
+        TimeUnit,
     };
     use lance_arrow::bfloat16::{self, ARROW_EXT_META_KEY, ARROW_EXT_NAME_KEY, BFLOAT16_EXT_NAME};
     use lance_datagen::{array, gen, BatchCount, RowCount};
@@ -1582,6 +2043,166 @@ mod tests {
         assert_eq!(get_iops(), 2);
     }
 
+    #[tokio::test]
+    async fn test_load_indices_iops() {
+        use crate::utils::test::IoTrackingStore;
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("j", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..10_i32)),
+                Arc::new(Int32Array::from_iter_values(0..10_i32)),
+            ],
+        )
+        .unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let mut dataset = Dataset::write(batches, "memory://test", None)
+            .await
+            .unwrap();
+        for column in ["i", "j"] {
+            dataset
+                .create_index(
+                    &[column],
+                    IndexType::Scalar,
+                    None,
+                    &ScalarIndexParams::default(),
+                    false,
+                )
+                .await
+                .unwrap();
+        }
+
+        let memory_store = dataset.object_store.inner.clone();
+        let (io_stats_wrapper, io_stats) = IoTrackingStore::new_wrapper();
+        let dataset = DatasetBuilder::from_uri("memory://test")
+            .with_read_params(ReadParams {
+                store_options: Some(ObjectStoreParams {
+                    object_store_wrapper: Some(io_stats_wrapper),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .with_object_store(
+                memory_store,
+                Url::parse("memory://test").unwrap(),
+                Arc::new(RenameCommitHandler),
+            )
+            .load()
+            .await
+            .unwrap();
+        let get_iops = || io_stats.lock().unwrap().read_iops;
+        let iops = get_iops();
+
+        // The index metadata is decoded from the manifest read by the load,
+        // only for the fields asked for
+        let field = dataset.schema().field_id("j").unwrap();
+        let indices = dataset.load_indices_on_fields(&[field]).await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].fields, vec![field]);
+        let indices = dataset.load_indices().await.unwrap();
+        assert_eq!(indices.len(), 2);
+        assert_eq!(get_iops(), iops);
+    }
+
+    #[tokio::test]
+    async fn test_fault_injecting_store() {
+        use lance_io::object_store::fault::{FaultConfig, FaultInjector, Operation};
+
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10_i32))],
+        )
+        .unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(batches, "memory://test", None)
+            .await
+            .unwrap();
+
+        let memory_store = dataset.object_store.inner.clone();
+        let injector = FaultInjector::new(FaultConfig::default(), 0);
+        let open = || {
+            DatasetBuilder::from_uri("memory://test")
+                .with_read_params(ReadParams {
+                    store_options: Some(ObjectStoreParams {
+                        object_store_wrapper: Some(Arc::new(injector.clone())),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .with_object_store(
+                    memory_store.clone(),
+                    Url::parse("memory://test").unwrap(),
+                    Arc::new(RenameCommitHandler),
+                )
+                .load()
+        };
+
+        // Reads retry transient failures three times, so reading the manifest
+        // fails once all four attempts fail
+        injector.fail_next(Operation::Get, 4);
+        assert!(open().await.is_err());
+        assert_eq!(injector.num_failures(), 4);
+        let dataset = open().await.unwrap();
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_object_store_registry() {
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10_i32))],
+        )
+        .unwrap();
+
+        // Each in-memory store is empty, so the datasets only see each other
+        // through the registered store.
+        let session = Arc::new(Session::default());
+        session
+            .object_store_registry()
+            .register("memory://test", ObjectStore::memory());
+        let batches = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let write_params = WriteParams {
+            session: Some(session.clone()),
+            ..Default::default()
+        };
+        Dataset::write(batches, "memory://test", Some(write_params))
+            .await
+            .unwrap();
+
+        let dataset = DatasetBuilder::from_uri("memory://test")
+            .with_session(session.clone())
+            .load()
+            .await
+            .unwrap();
+        assert_eq!(dataset.scan().try_into_batch().await.unwrap(), batch);
+        assert!(Dataset::open("memory://test").await.is_err());
+
+        let batches = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let write_params = WriteParams {
+            mode: WriteMode::Append,
+            session: Some(session.clone()),
+            ..Default::default()
+        };
+        let dataset = Dataset::write(batches, "memory://test", Some(write_params))
+            .await
+            .unwrap();
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 20);
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_write_params(#[values(false, true)] use_legacy_format: bool) {
@@ -1698,8 +2319,8 @@ mod tests {
         );
 
         // Write with custom manifest
-        manifest.writer_feature_flags = 5; // Set another flag
-        manifest.reader_feature_flags = 5;
+        manifest.writer_feature_flags = 65; // Set an unknown flag
+        manifest.reader_feature_flags = 65;
         manifest.version += 1;
         write_manifest_file(
             dataset.object_store(),
@@ -1711,6 +2332,7 @@ mod tests {
                 auto_set_feature_flags: false,
                 timestamp: None,
                 use_move_stable_row_ids: false,
+                partitioning: None,
             },
         )
         .await
@@ -2109,6 +2731,226 @@ mod tests {
         assert_eq!(&ArrowSchema::from(first_ver.schema()), schema.as_ref());
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_append_missing_columns(#[values(false, true)] use_legacy_format: bool) {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("s", DataType::Utf8, true),
+            ArrowField::new("x", DataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..10)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..10).map(|v| v.to_string()),
+                )),
+                Arc::new(Int64Array::from_iter_values(0..10)),
+            ],
+        )
+        .unwrap();
+        let params = WriteParams {
+            use_legacy_format,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            test_uri,
+            Some(params.clone()),
+        )
+        .await
+        .unwrap();
+
+        // New rows without `x`
+        let partial_schema = Arc::new(schema.project(&[0, 1]).unwrap());
+        let partial = RecordBatch::try_new(
+            partial_schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(10..15)),
+                Arc::new(StringArray::from_iter_values(
+                    (10..15).map(|v| v.to_string()),
+                )),
+            ],
+        )
+        .unwrap();
+        let partial_rows =
+            || RecordBatchIterator::new(vec![Ok(partial.clone())], partial_schema.clone());
+        assert!(dataset
+            .append(partial_rows(), Some(params.clone()))
+            .await
+            .is_err());
+        let params = WriteParams {
+            allow_missing_columns: true,
+            ..params
+        };
+        dataset
+            .append(partial_rows(), Some(params.clone()))
+            .await
+            .unwrap();
+        dataset.validate().await.unwrap();
+        assert_eq!(
+            dataset.schema(),
+            &Schema::try_from(schema.as_ref()).unwrap()
+        );
+
+        // A non-nullable column can't be missing
+        let strings_schema = Arc::new(schema.project(&[1]).unwrap());
+        let strings = RecordBatch::try_new(
+            strings_schema.clone(),
+            vec![Arc::new(StringArray::from(vec!["a"]))],
+        )
+        .unwrap();
+        assert!(dataset
+            .append(
+                RecordBatchIterator::new(vec![Ok(strings)], strings_schema),
+                Some(params)
+            )
+            .await
+            .is_err());
+
+        let batch = dataset.scan().try_into_batch().await.unwrap();
+        assert_eq!(batch.schema(), schema);
+        assert_eq!(batch.num_rows(), 15);
+        assert_eq!(batch.column_by_name("x").unwrap().null_count(), 5);
+
+        // Only the missing column, with row ids
+        let mut scanner = dataset.scan();
+        scanner.project(&["x"]).unwrap().with_row_id();
+        scanner.filter("x IS NULL").unwrap();
+        let batch = scanner.try_into_batch().await.unwrap();
+        assert_eq!(batch.num_rows(), 5);
+        assert_eq!(batch.column_by_name("x").unwrap().null_count(), 5);
+
+        // Backfill the missing values
+        let dataset = UpdateBuilder::new(Arc::new(dataset))
+            .update_where("x IS NULL")
+            .unwrap()
+            .set("x", "i * 2")
+            .unwrap()
+            .build()
+            .unwrap()
+            .execute()
+            .await
+            .unwrap();
+        let mut scanner = dataset.scan();
+        scanner.filter("i >= 10").unwrap();
+        let batch = scanner.try_into_batch().await.unwrap();
+        assert_eq!(batch.num_rows(), 5);
+        let mut x = batch
+            .column_by_name("x")
+            .unwrap()
+            .as_primitive::<arrow_array::types::Int64Type>()
+            .values()
+            .to_vec();
+        x.sort();
+        assert_eq!(x, vec![20, 22, 24, 26, 28]);
+    }
+
+    #[tokio::test]
+    async fn test_append_conform_vectors() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let vector_type = DataType::FixedSizeList(
+            Arc::new(ArrowField::new("item", DataType::Float32, true)),
+            4,
+        );
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "vec",
+            vector_type,
+            true,
+        )]));
+        let vectors = FixedSizeListArray::try_new_from_values(
+            Float32Array::from_iter_values((0..8).map(|v| v as f32)),
+            4,
+        )
+        .unwrap();
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            test_uri,
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Vectors from another source, as lists of doubles of any dimension.
+        let lists = |dims: &[usize]| {
+            let lists = ListArray::from_iter_primitive::<Float64Type, _, _>(
+                dims.iter()
+                    .map(|dim| Some((0..*dim).map(|v| Some(v as f64 + 1.0)))),
+            );
+            let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+                "vec",
+                lists.data_type().clone(),
+                true,
+            )]));
+            let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(lists)]).unwrap();
+            RecordBatchIterator::new(vec![Ok(batch.clone()), Ok(batch)], schema)
+        };
+
+        let params = WriteParams {
+            conform_vectors: Some(VectorDimensionPolicy::Error),
+            ..Default::default()
+        };
+        let err = dataset
+            .append(lists(&[4, 3]), Some(params))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Batch 0: vector 1 of column vec has dimension 3, expected 4"),
+            "{}",
+            err
+        );
+
+        let params = WriteParams {
+            conform_vectors: Some(VectorDimensionPolicy::PadOrTruncate),
+            ..Default::default()
+        };
+        dataset
+            .append(lists(&[4, 3, 5]), Some(params))
+            .await
+            .unwrap();
+        assert_eq!(
+            dataset.schema(),
+            &Schema::try_from(schema.as_ref()).unwrap()
+        );
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 8);
+
+        let batch = dataset.scan().try_into_batch().await.unwrap();
+        let vectors = batch["vec"].as_fixed_size_list();
+        let values = |row: usize| {
+            vectors
+                .value(row)
+                .as_primitive::<Float32Type>()
+                .values()
+                .to_vec()
+        };
+        assert_eq!(values(2), vec![1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(values(3), vec![1.0, 2.0, 3.0, 0.0]);
+        assert_eq!(values(4), vec![1.0, 2.0, 3.0, 4.0]);
+
+        // Appends without a policy use the one configured for the dataset
+        dataset
+            .update_config(
+                [CONSISTENCY_CONFORM_VECTORS.entry(VectorDimensionPolicy::PadOrTruncate)],
+            )
+            .await
+            .unwrap();
+        dataset.append(lists(&[3]), None).await.unwrap();
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 10);
+        let params = WriteParams {
+            conform_vectors: Some(VectorDimensionPolicy::Error),
+            ..Default::default()
+        };
+        assert!(dataset.append(lists(&[3]), Some(params)).await.is_err());
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_fast_count_rows(#[values(false, true)] use_legacy_format: bool) {
@@ -2155,6 +2997,61 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_count_rows_strategy() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let data = gen()
+            .col("i", array::step::<Int32Type>())
+            .col("j", array::step::<Int32Type>())
+            .into_reader_rows(RowCount::from(100), BatchCount::from(4));
+        let write_params = WriteParams {
+            max_rows_per_file: 100,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(data, test_uri, Some(write_params))
+            .await
+            .unwrap();
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        dataset.delete("i % 10 = 0").await.unwrap();
+
+        assert_eq!(
+            dataset.count_rows_with_strategy(None).await.unwrap(),
+            (360, CountStrategy::Metadata)
+        );
+        assert_eq!(
+            dataset
+                .count_rows_with_strategy(Some("i < 100".to_string()))
+                .await
+                .unwrap(),
+            (90, CountStrategy::Index)
+        );
+        assert_eq!(
+            dataset
+                .count_rows_with_strategy(Some("j < 100".to_string()))
+                .await
+                .unwrap(),
+            (90, CountStrategy::Scan)
+        );
+        // Parts of the filter the indices can't answer are scanned
+        assert_eq!(
+            dataset
+                .count_rows_with_strategy(Some("i < 100 AND j >= 50".to_string()))
+                .await
+                .unwrap(),
+            (45, CountStrategy::Scan)
+        );
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_create_index(#[values(false, true)] use_legacy_format: bool) {
@@ -2310,6 +3207,299 @@ mod tests {
         dataset.index_statistics(&index_name).await.unwrap();
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_decimal_filters(#[values(false, true)] use_legacy_format: bool) {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        // Prices from -20.00 to 29.95, and big values from 0 to 1233.2655
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("price", DataType::Decimal128(10, 2), false),
+            ArrowField::new("big", DataType::Decimal256(50, 4), false),
+        ]));
+        let prices = Decimal128Array::from_iter_values((0..1000).map(|v| v * 5 - 2000))
+            .with_precision_and_scale(10, 2)
+            .unwrap();
+        let bigs = Decimal256Array::from_iter_values((0..1000).map(|v| i256::from_i128(v * 12345)))
+            .with_precision_and_scale(50, 4)
+            .unwrap();
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(prices), Arc::new(bigs)]).unwrap();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_group: 100,
+                use_legacy_format,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+
+        let cases = [
+            ("price >= 10.5", 390),
+            ("price < -3", 340),
+            ("price = 1.25", 1),
+            ("price IN (1.25, 2.5)", 2),
+            // More digits than the scale of the column
+            ("price > 2.499", 550),
+            ("price = 1.251", 0),
+            ("big >= 617.25", 500),
+            ("big = 12.345", 1),
+            ("big > decimal(9,2) '1.23'", 999),
+        ];
+        for (filter, expected) in cases {
+            let count = dataset.count_rows(Some(filter.to_string())).await.unwrap();
+            assert_eq!(count, expected, "{}", filter);
+        }
+
+        for column in ["price", "big"] {
+            dataset
+                .create_index(
+                    &[column],
+                    IndexType::Scalar,
+                    None,
+                    &ScalarIndexParams::default(),
+                    false,
+                )
+                .await
+                .unwrap();
+        }
+        for (filter, expected) in cases {
+            let count = dataset.count_rows(Some(filter.to_string())).await.unwrap();
+            assert_eq!(count, expected, "{} with index", filter);
+        }
+        for filter in ["price >= 10.5", "price IN (1.25, 2.5)", "big = 12.345"] {
+            let plan = dataset
+                .scan()
+                .filter(filter)
+                .unwrap()
+                .explain_plan(false)
+                .await
+                .unwrap();
+            assert!(plan.contains("MaterializeIndex"), "{}: {}", filter, plan);
+        }
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_temporal_filters(#[values(false, true)] use_legacy_format: bool) {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        // Row i is at i seconds past midnight, lasts 999 - i seconds, and is
+        // i months or i days long.
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("time", DataType::Time64(TimeUnit::Microsecond), false),
+            ArrowField::new("duration", DataType::Duration(TimeUnit::Millisecond), false),
+            ArrowField::new("months", DataType::Interval(IntervalUnit::YearMonth), false),
+            ArrowField::new(
+                "days",
+                DataType::Interval(IntervalUnit::MonthDayNano),
+                false,
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Time64MicrosecondArray::from_iter_values(
+                    (0..1000).map(|i| i * 1_000_000),
+                )),
+                Arc::new(DurationMillisecondArray::from_iter_values(
+                    (0..1000).map(|i| (999 - i) * 1000),
+                )),
+                Arc::new(IntervalYearMonthArray::from_iter_values(0..1000)),
+                Arc::new(IntervalMonthDayNanoArray::from_iter_values(
+                    (0..1000).map(|i| IntervalMonthDayNanoType::make_value(0, i, 0)),
+                )),
+            ],
+        )
+        .unwrap();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone()),
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_group: 100,
+                use_legacy_format,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+
+        let scanned = dataset.scan().try_into_batch().await.unwrap();
+        assert_eq!(scanned, batch);
+
+        let sorted = dataset
+            .scan()
+            .order_by(Some(vec![ColumnOrdering::asc_nulls_first(
+                "duration".to_string(),
+            )]))
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+        let times = sorted["time"].as_primitive::<Time64MicrosecondType>();
+        assert_eq!(times.value(0), 999_000_000);
+        assert_eq!(times.value(999), 0);
+
+        let cases = [
+            ("time >= TIME '00:10:00'", 400),
+            ("time < TIME '00:00:10.5'", 11),
+            ("duration > INTERVAL '990 seconds'", 9),
+            ("duration = INTERVAL '1' MINUTE", 1),
+            ("months >= INTERVAL '5 years'", 940),
+            ("days < INTERVAL '10 days'", 10),
+        ];
+        for (filter, expected) in cases {
+            let count = dataset.count_rows(Some(filter.to_string())).await.unwrap();
+            assert_eq!(count, expected, "{}", filter);
+        }
+
+        for column in ["time", "duration", "months"] {
+            dataset
+                .create_index(
+                    &[column],
+                    IndexType::Scalar,
+                    None,
+                    &ScalarIndexParams::default(),
+                    false,
+                )
+                .await
+                .unwrap();
+        }
+        for (filter, expected) in cases {
+            let count = dataset.count_rows(Some(filter.to_string())).await.unwrap();
+            assert_eq!(count, expected, "{} with index", filter);
+        }
+        for filter in [
+            "time >= TIME '00:10:00'",
+            "duration = INTERVAL '1' MINUTE",
+            "months >= INTERVAL '5 years'",
+        ] {
+            let plan = dataset
+                .scan()
+                .filter(filter)
+                .unwrap()
+                .explain_plan(false)
+                .await
+                .unwrap();
+            assert!(plan.contains("MaterializeIndex"), "{}: {}", filter, plan);
+        }
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_view_types(#[values(false, true)] use_legacy_format: bool) {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let values = vec![Some("a"), None, Some("a string longer than twelve bytes")];
+        let view_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("s", DataType::Utf8View, true),
+            ArrowField::new("b", DataType::BinaryView, true),
+        ]));
+        let view_batch = RecordBatch::try_new(
+            view_schema.clone(),
+            vec![
+                Arc::new(StringViewArray::from_iter(values.clone())),
+                Arc::new(BinaryViewArray::from_iter(
+                    values.iter().map(|v| v.map(str::as_bytes)),
+                )),
+            ],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            use_legacy_format,
+            ..Default::default()
+        };
+
+        // Views are written as large strings and binaries.
+        let dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(view_batch.clone())], view_schema.clone()),
+            test_uri,
+            Some(write_params.clone()),
+        )
+        .await
+        .unwrap();
+        let large_batch = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                ArrowField::new("s", DataType::LargeUtf8, true),
+                ArrowField::new("b", DataType::LargeBinary, true),
+            ])),
+            vec![
+                Arc::new(LargeStringArray::from(values.clone())),
+                Arc::new(LargeBinaryArray::from_iter(
+                    values.iter().map(|v| v.map(str::as_bytes)),
+                )),
+            ],
+        )
+        .unwrap();
+        assert_eq!(dataset.scan().try_into_batch().await.unwrap(), large_batch);
+
+        let mut scanner = dataset.scan();
+        scanner.use_view_types(true);
+        assert_eq!(scanner.schema().await.unwrap(), view_schema);
+        assert_eq!(scanner.try_into_batch().await.unwrap(), view_batch);
+
+        // Views appended to a dataset take the types of its columns.
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let string_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("s", DataType::Utf8, true),
+            ArrowField::new("b", DataType::Binary, true),
+        ]));
+        let string_batch = RecordBatch::try_new(
+            string_schema.clone(),
+            vec![
+                Arc::new(StringArray::from(values.clone())),
+                Arc::new(BinaryArray::from_iter(
+                    values.iter().map(|v| v.map(str::as_bytes)),
+                )),
+            ],
+        )
+        .unwrap();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(string_batch.clone())], string_schema.clone()),
+            test_uri,
+            Some(write_params.clone()),
+        )
+        .await
+        .unwrap();
+        dataset
+            .append(
+                RecordBatchIterator::new(vec![Ok(view_batch.clone())], view_schema.clone()),
+                Some(write_params.clone()),
+            )
+            .await
+            .unwrap();
+        Dataset::write(
+            RecordBatchIterator::new(vec![Ok(view_batch.clone())], view_schema.clone()),
+            test_uri,
+            Some(WriteParams {
+                mode: WriteMode::Append,
+                ..write_params
+            }),
+        )
+        .await
+        .unwrap();
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(
+            dataset.schema(),
+            &Schema::try_from(string_schema.as_ref()).unwrap()
+        );
+        assert_eq!(
+            dataset.scan().try_into_batch().await.unwrap(),
+            concat_batches(
+                &string_schema,
+                &[string_batch.clone(), string_batch.clone(), string_batch]
+            )
+            .unwrap()
+        );
+    }
+
     async fn create_bad_file(use_legacy_format: bool) -> Result<Dataset> {
         let test_dir = tempdir().unwrap();
 
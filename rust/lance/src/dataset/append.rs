@@ -0,0 +1,513 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Append the rows of another dataset
+//!
+//! When both schemas match, the data files and deletion files of the other
+//! dataset are copied, and only the field ids in the fragment metadata are
+//! translated. This is much cheaper than scanning and writing
+//! the rows again, which is only done when the column types differ and have
+//! to be cast, or when a file can't be read with the translated field ids.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::compute::CastOptions;
+use arrow_array::RecordBatch;
+use arrow_schema::Schema as ArrowSchema;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::{stream, StreamExt, TryStreamExt};
+use lance_arrow::cast::{can_cast_types, cast_with_options};
+use lance_core::datatypes::SchemaCompareOptions;
+use lance_core::{Error, Result};
+use lance_table::format::Fragment;
+use lance_table::io::deletion::deletion_file_path;
+use snafu::{location, Location};
+use uuid::Uuid;
+
+use super::download::copy_file;
+use super::fragment::FileFragment;
+use super::optimize::reserve_fragment_ids;
+use super::transaction::{Operation, Transaction};
+use super::{commit_transaction, write_fragments_internal, Dataset, WriteMode, WriteParams};
+
+/// Parameters of [`Dataset::append_dataset`].
+#[derive(Debug, Clone, Default)]
+pub struct AppendDatasetParams {
+    /// Cast the columns of the other dataset to the types of this one when
+    /// they differ. The rows are then read and written again. By default,
+    /// the schemas must match.
+    pub allow_cast: bool,
+    /// Parameters used to write the rows that are not copied.
+    pub write_params: Option<WriteParams>,
+}
+
+/// Whether the files of `other` can be appended as they are.
+fn schemas_match(dataset: &Dataset, other: &Dataset) -> bool {
+    let options = SchemaCompareOptions {
+        // Legacy files store dictionary values in the schema.
+        compare_dictionary: true,
+        ..Default::default()
+    };
+    other
+        .schema()
+        .compare_with_options(dataset.schema(), &options)
+}
+
+/// Check that every column of `dataset` exists in `other` with a type that
+/// can be cast.
+fn check_castable(dataset: &Dataset, other: &Dataset) -> Result<()> {
+    if dataset.schema().fields.len() != other.schema().fields.len() {
+        return Err(Error::SchemaMismatch {
+            difference: format!(
+                "expected {} columns but the other dataset has {}",
+                dataset.schema().fields.len(),
+                other.schema().fields.len()
+            ),
+            location: location!(),
+        });
+    }
+    for field in &dataset.schema().fields {
+        let other_field =
+            other
+                .schema()
+                .field(&field.name)
+                .ok_or_else(|| Error::SchemaMismatch {
+                    difference: format!("`{}` is missing from the other dataset", field.name),
+                    location: location!(),
+                })?;
+        if !can_cast_types(&other_field.data_type(), &field.data_type()) {
+            return Err(Error::SchemaMismatch {
+                difference: format!(
+                    "`{}` can't be cast from {} to {}",
+                    field.name,
+                    other_field.data_type(),
+                    field.data_type()
+                ),
+                location: location!(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// How a fragment of the other dataset ends up in this one.
+enum AppendedFragment {
+    /// Its data files were copied. The deletion file is copied once the
+    /// fragment has an id in this dataset, since its name depends on it.
+    Copied(Fragment),
+    /// Its rows were written into new fragments.
+    Rewritten(Vec<Fragment>),
+}
+
+/// Translate the field ids of the data files of `fragment` to this dataset.
+///
+/// Returns `None` when a file can't be read with the new ids: legacy files
+/// locate columns by their offset from the first field id, so the ids must
+/// keep their spacing, and no file may hold fields that were dropped from the
+/// other dataset.
+fn translate_field_ids(mut fragment: Fragment, field_ids: &HashMap<i32, i32>) -> Option<Fragment> {
+    for file in fragment.files.iter_mut() {
+        let translated = file
+            .fields
+            .iter()
+            .map(|id| field_ids.get(id).copied())
+            .collect::<Option<Vec<_>>>()?;
+        if file.is_legacy_file() {
+            let (first, translated_first) = (*file.fields.first()?, *translated.first()?);
+            if file
+                .fields
+                .iter()
+                .zip(&translated)
+                .any(|(id, translated)| id - first != translated - translated_first)
+            {
+                return None;
+            }
+        }
+        file.fields = translated;
+    }
+    // Row ids are assigned again when this dataset uses stable row ids.
+    fragment.row_id_meta = None;
+    Some(fragment)
+}
+
+/// Read `fragment`, cast it to the schema of `dataset` and write it there.
+async fn rewrite_fragment(
+    dataset: &Dataset,
+    fragment: FileFragment,
+    params: &WriteParams,
+) -> Result<Vec<Fragment>> {
+    let columns = dataset
+        .schema()
+        .fields
+        .iter()
+        .map(|f| f.name.as_str())
+        .collect::<Vec<_>>();
+    let mut scanner = fragment.scan();
+    scanner.project(&columns)?.scan_in_order(true);
+    let data = SendableRecordBatchStream::from(scanner.try_into_stream().await?);
+
+    let target_schema = Arc::new(ArrowSchema::from(dataset.schema()));
+    let cast_schema = target_schema.clone();
+    let cast = data.map(move |batch| {
+        let batch = batch?;
+        let columns = batch
+            .columns()
+            .iter()
+            .zip(cast_schema.fields())
+            .map(|(column, field)| {
+                cast_with_options(column, field.data_type(), &CastOptions::default())
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(RecordBatch::try_new(cast_schema.clone(), columns)?)
+    });
+    let data = Box::pin(RecordBatchStreamAdapter::new(target_schema, cast));
+
+    write_fragments_internal(
+        Some(dataset),
+        dataset.object_store.clone(),
+        &dataset.base,
+        dataset.schema(),
+        data,
+        WriteParams {
+            mode: WriteMode::Append,
+            ..params.clone()
+        },
+    )
+    .await
+}
+
+async fn append_fragment(
+    dataset: &Dataset,
+    fragment: FileFragment,
+    field_ids: Option<&HashMap<i32, i32>>,
+    params: &WriteParams,
+) -> Result<AppendedFragment> {
+    let translated =
+        field_ids.and_then(|field_ids| translate_field_ids(fragment.metadata().clone(), field_ids));
+    let Some(translated) = translated else {
+        let fragments = rewrite_fragment(dataset, fragment, params).await?;
+        return Ok(AppendedFragment::Rewritten(fragments));
+    };
+    let other = fragment.dataset();
+    let mut translated = translated;
+    for file in translated.files.iter_mut() {
+        // A new name, so appending the same dataset twice doesn't share files
+        let path = format!("{}.lance", Uuid::new_v4());
+        copy_file(
+            &other.object_store,
+            &other.data_dir().child(file.path.as_str()),
+            &dataset.object_store,
+            &dataset.data_dir().child(path.as_str()),
+        )
+        .await?;
+        file.path = path;
+    }
+    Ok(AppendedFragment::Copied(translated))
+}
+
+pub(super) async fn append_dataset(
+    dataset: &mut Dataset,
+    other: &Dataset,
+    params: Option<AppendDatasetParams>,
+) -> Result<()> {
+    let params = params.unwrap_or_default();
+    // Without a cast, compatible schemas have the same shape, so fields
+    // match by position.
+    let field_ids = if schemas_match(dataset, other) {
+        Some(
+            other
+                .schema()
+                .fields_pre_order()
+                .zip(dataset.schema().fields_pre_order())
+                .map(|(from, to)| (from.id, to.id))
+                .collect::<HashMap<_, _>>(),
+        )
+    } else if params.allow_cast {
+        check_castable(dataset, other)?;
+        None
+    } else {
+        other.schema().check_compatible(
+            dataset.schema(),
+            &SchemaCompareOptions {
+                compare_dictionary: true,
+                ..Default::default()
+            },
+        )?;
+        None
+    };
+
+    let write_params = params.write_params.unwrap_or_default();
+    let other_ref = Arc::new(other.clone());
+    let this = &*dataset;
+    let appended = stream::iter(other.manifest.fragments.iter().cloned())
+        .map(|fragment| {
+            append_fragment(
+                this,
+                FileFragment::new(other_ref.clone(), fragment),
+                field_ids.as_ref(),
+                &write_params,
+            )
+        })
+        .buffered(num_cpus::get())
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let mut fragments = Vec::new();
+    // The id in `other` of each copied fragment that has a deletion file
+    let mut other_ids = Vec::new();
+    for appended in appended {
+        match appended {
+            AppendedFragment::Copied(fragment) => {
+                other_ids.push(fragment.deletion_file.as_ref().map(|_| fragment.id));
+                fragments.push(fragment);
+            }
+            AppendedFragment::Rewritten(rewritten) => {
+                other_ids.extend(rewritten.iter().map(|_| None));
+                fragments.extend(rewritten);
+            }
+        }
+    }
+
+    if other_ids.iter().any(Option::is_some) {
+        // Deletion files are named after the id of their fragment, so the ids
+        // are reserved before the files are copied.  Every fragment reserves
+        // one to keep them in order.
+        reserve_fragment_ids(dataset, &mut fragments).await?;
+        for (fragment, other_id) in fragments.iter().zip(other_ids) {
+            if let (Some(deletion_file), Some(other_id)) = (&fragment.deletion_file, other_id) {
+                copy_file(
+                    &other.object_store,
+                    &deletion_file_path(&other.base, other_id, deletion_file),
+                    &dataset.object_store,
+                    &deletion_file_path(&dataset.base, fragment.id, deletion_file),
+                )
+                .await?;
+            }
+        }
+    } else {
+        // The commit assigns ids to the fragments with id 0, so they stay
+        // unique if it is retried against a newer version
+        for fragment in fragments.iter_mut() {
+            fragment.id = 0;
+        }
+    }
+
+    let transaction = Transaction::new(
+        dataset.manifest.version,
+        Operation::Append { fragments },
+        None,
+    );
+    let manifest = commit_transaction(
+        dataset,
+        &dataset.object_store,
+        dataset.commit_handler.as_ref(),
+        &transaction,
+        &Default::default(),
+        &Default::default(),
+    )
+    .await?;
+    dataset.manifest = Arc::new(manifest);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int32Array, Int64Array, RecordBatchIterator, StringArray};
+    use arrow_schema::{DataType, Field as ArrowField};
+    use tempfile::tempdir;
+
+    fn batch(start: i32, count: i32) -> RecordBatch {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("s", DataType::Utf8, true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from_iter_values(start..start + count)),
+                Arc::new(StringArray::from_iter_values(
+                    (start..start + count).map(|i| format!("s-{}", i)),
+                )),
+            ],
+        )
+        .unwrap()
+    }
+
+    async fn write(batch: RecordBatch, uri: &str) -> Dataset {
+        let schema = batch.schema();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let params = WriteParams {
+            max_rows_per_file: 50,
+            ..Default::default()
+        };
+        Dataset::write(reader, uri, Some(params)).await.unwrap()
+    }
+
+    async fn values(dataset: &Dataset) -> Vec<i32> {
+        let batches = dataset
+            .scan()
+            .project(&["i"])
+            .unwrap()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        batches
+            .iter()
+            .flat_map(|b| {
+                b.column(0)
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_append_dataset_copies_files() {
+        let dir = tempdir().unwrap();
+        let mut dataset = write(batch(0, 100), dir.path().join("a").to_str().unwrap()).await;
+        let mut other = write(batch(100, 100), dir.path().join("b").to_str().unwrap()).await;
+        other.delete("i >= 190").await.unwrap();
+
+        dataset.append_dataset(&other, None).await.unwrap();
+        // The fragment ids were reserved in their own version, since one of
+        // the fragments has a deletion file.
+        assert_eq!(dataset.version().version, 3);
+        assert_eq!(dataset.count_fragments(), 4);
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 190);
+        assert_eq!(values(&dataset).await, (0..190).collect::<Vec<_>>());
+        // The data files were copied under new names, not written again.
+        let fragments = dataset.get_fragments();
+        let other_fragments = other.get_fragments();
+        let path = &fragments[2].metadata().files[0].path;
+        let other_path = &other_fragments[0].metadata().files[0].path;
+        assert_ne!(path, other_path);
+        assert_eq!(
+            dataset
+                .object_store
+                .size(&dataset.data_dir().child(path.as_str()))
+                .await
+                .unwrap(),
+            other
+                .object_store
+                .size(&other.data_dir().child(other_path.as_str()))
+                .await
+                .unwrap()
+        );
+        assert!(fragments[3].metadata().deletion_file.is_some());
+        dataset.validate().await.unwrap();
+
+        // Appending again gets new ids and files.
+        dataset.append_dataset(&other, None).await.unwrap();
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 280);
+        let fragments = dataset.get_fragments();
+        assert_ne!(
+            fragments[4].metadata().files[0].path,
+            fragments[2].metadata().files[0].path
+        );
+        dataset.validate().await.unwrap();
+
+        // The other dataset is left untouched.
+        assert_eq!(other.count_rows(None).await.unwrap(), 90);
+    }
+
+    #[tokio::test]
+    async fn test_append_dataset_different_field_ids() {
+        let dir = tempdir().unwrap();
+        let mut dataset = write(batch(0, 100), dir.path().join("a").to_str().unwrap()).await;
+        let mut other = write(batch(100, 100), dir.path().join("b").to_str().unwrap()).await;
+        // `s` gets a new field id and the old one stays in the data files.
+        other.drop_columns(&["s"]).await.unwrap();
+        other
+            .add_columns(
+                crate::dataset::NewColumnTransform::SqlExpressions(vec![(
+                    "s".into(),
+                    "concat('s-', cast(i as string))".into(),
+                )]),
+                None,
+            )
+            .await
+            .unwrap();
+
+        dataset.append_dataset(&other, None).await.unwrap();
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 200);
+        assert_eq!(values(&dataset).await, (0..200).collect::<Vec<_>>());
+        assert_eq!(
+            dataset
+                .count_rows(Some("s = 's-150'".to_string()))
+                .await
+                .unwrap(),
+            1
+        );
+        dataset.validate().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_append_dataset_cast() {
+        let dir = tempdir().unwrap();
+        let mut dataset = write(batch(0, 10), dir.path().join("a").to_str().unwrap()).await;
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int64, false),
+            ArrowField::new("s", DataType::Utf8, true),
+        ]));
+        let other_batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from_iter_values(10..20)),
+                Arc::new(StringArray::from_iter_values(
+                    (10..20).map(|i| i.to_string()),
+                )),
+            ],
+        )
+        .unwrap();
+        let other = write(other_batch, dir.path().join("b").to_str().unwrap()).await;
+
+        let err = dataset.append_dataset(&other, None).await.unwrap_err();
+        assert!(matches!(err, Error::SchemaMismatch { .. }));
+
+        let params = AppendDatasetParams {
+            allow_cast: true,
+            ..Default::default()
+        };
+        dataset.append_dataset(&other, Some(params)).await.unwrap();
+        assert_eq!(
+            dataset.schema().field("i").unwrap().data_type(),
+            DataType::Int32
+        );
+        assert_eq!(values(&dataset).await, (0..20).collect::<Vec<_>>());
+        dataset.validate().await.unwrap();
+
+        // Columns that don't exist can't be cast.
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("t", DataType::Utf8, false),
+        ]));
+        let other_batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..1)),
+                Arc::new(StringArray::from_iter_values(["x"])),
+            ],
+        )
+        .unwrap();
+        let other = write(other_batch, dir.path().join("c").to_str().unwrap()).await;
+        let params = AppendDatasetParams {
+            allow_cast: true,
+            ..Default::default()
+        };
+        let err = dataset
+            .append_dataset(&other, Some(params))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::SchemaMismatch { .. }));
+    }
+}
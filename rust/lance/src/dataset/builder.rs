@@ -176,6 +176,9 @@ impl DatasetBuilder {
     }
 
     /// Build a lance object store for the given config
+    ///
+    /// If the session has a store registered for the URI, that store is used
+    /// and the storage options are ignored.
     pub async fn build_object_store(self) -> Result<(ObjectStore, Path, Arc<dyn CommitHandler>)> {
         let commit_handler = match self.commit_handler {
             Some(commit_handler) => Ok(commit_handler),
@@ -194,16 +197,24 @@ impl DatasetBuilder {
                 commit_handler,
             )),
             None => {
-                let (store, path) =
-                    ObjectStore::from_uri_and_params(&self.table_uri, &self.options).await?;
+                let registered = self
+                    .session
+                    .as_ref()
+                    .and_then(|session| session.object_store_registry().resolve(&self.table_uri));
+                let (store, path) = match registered {
+                    Some(registered) => registered,
+                    None => {
+                        ObjectStore::from_uri_and_params(&self.table_uri, &self.options).await?
+                    }
+                };
                 Ok((store, path, commit_handler))
             }
         }
     }
 
     #[instrument(skip_all)]
-    pub async fn load(mut self) -> Result<Dataset> {
-        let session = match self.session.take() {
+    pub async fn load(self) -> Result<Dataset> {
+        let session = match self.session.clone() {
             Some(session) => session,
             None => Arc::new(Session::new(
                 self.index_cache_size,
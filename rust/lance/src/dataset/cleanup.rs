@@ -16,6 +16,8 @@
 //!   any fragment in a valid manifest file then it will be deleted.
 //! * Unreferenced index files - If an index file is not referenced by
 //!   any valid manifest file then it will be deleted.
+//! * Unreferenced sketch files - If the data file a sketch file belongs
+//!   to is not referenced then the sketch file will be deleted.
 //!
 //! It is also difficult to distinguish between a data/tx/idx file which was
 //! leftover from an abandoned transaction and a data file which is part
@@ -42,6 +44,7 @@ use lance_table::{
     io::{
         deletion::deletion_file_path,
         manifest::{read_manifest, read_manifest_indexes},
+        sketch::SKETCH_DIR,
     },
 };
 use object_store::path::Path;
@@ -51,7 +54,7 @@ use std::{
     sync::{Mutex, MutexGuard},
 };
 
-use crate::{utils::temporal::utc_now, Dataset};
+use crate::{dataset::DATA_DIR, utils::temporal::utc_now, Dataset};
 
 #[derive(Clone, Debug, Default)]
 struct ReferencedFiles {
@@ -345,6 +348,24 @@ impl<'a> CleanupTask<'a> {
                     } else {
                         Ok(None)
                     }
+                } else if relative_path.as_ref().starts_with(SKETCH_DIR) {
+                    // Sketch files are named after their data file
+                    let Some(data_path) = relative_path
+                        .filename()
+                        .and_then(|name| name.strip_suffix(".arrow"))
+                        .map(|stem| Path::from(DATA_DIR).child(format!("{stem}.lance")))
+                    else {
+                        return Ok(None);
+                    };
+                    if inspection.referenced_files.data_paths.contains(&data_path) {
+                        Ok(None)
+                    } else if !maybe_in_progress
+                        || inspection.verified_files.data_paths.contains(&data_path)
+                    {
+                        Ok(Some(path))
+                    } else {
+                        Ok(None)
+                    }
                 } else {
                     Ok(None)
                 }
@@ -0,0 +1,458 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Dataset configuration
+//!
+//! The configuration is a set of string key-value pairs stored in the
+//! manifest, so it is shared by every reader and writer of the dataset and is
+//! versioned along with the data. It is changed with an `UpdateConfig`
+//! transaction, which only conflicts with concurrent changes to the same keys.
+//!
+//! Any key can be stored. The [`ConfigKey`] constants are the settings Lance
+//! itself understands, [`DatasetConfig`] reads them with their types.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use futures::TryStreamExt;
+use lance_core::datatypes::Field;
+use lance_core::{Error, Result};
+use lance_index::vector::NullVectorHandling;
+use lance_table::io::manifest::read_manifest;
+use snafu::{location, Location};
+
+use super::optimize::CompactionOptions;
+use super::transaction::{Operation, Transaction};
+use super::write::VectorDimensionPolicy;
+use super::{commit_transaction, Dataset};
+
+/// A setting of the dataset configuration, with its type and default value
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigKey<T: 'static> {
+    /// The key the setting is stored under
+    pub name: &'static str,
+    /// The value used when the key is not set
+    pub default: T,
+}
+
+impl<T> ConfigKey<T>
+where
+    T: FromStr + ToString + Clone,
+    T::Err: Display,
+{
+    /// Parse a stored value of this setting
+    pub fn parse(&self, value: &str) -> Result<T> {
+        value.parse().map_err(|err| {
+            Error::invalid_input(
+                format!(
+                    "Invalid value '{}' for dataset config {}: {}",
+                    value, self.name, err
+                ),
+                location!(),
+            )
+        })
+    }
+
+    /// The key and stored value for setting this to `value`, to pass to
+    /// [`Dataset::update_config`]
+    pub fn entry(&self, value: T) -> (String, String) {
+        (self.name.to_string(), value.to_string())
+    }
+}
+
+/// Target number of rows of the fragments written by compaction
+pub const COMPACTION_TARGET_ROWS_PER_FRAGMENT: ConfigKey<usize> = ConfigKey {
+    name: "lance.compaction.target_rows_per_fragment",
+    default: 1024 * 1024,
+};
+
+/// Maximum number of rows per group of the fragments written by compaction
+pub const COMPACTION_MAX_ROWS_PER_GROUP: ConfigKey<usize> = ConfigKey {
+    name: "lance.compaction.max_rows_per_group",
+    default: 1024,
+};
+
+/// Fraction of deleted rows above which compaction rewrites a fragment
+pub const COMPACTION_MATERIALIZE_DELETIONS_THRESHOLD: ConfigKey<f32> = ConfigKey {
+    name: "lance.compaction.materialize_deletions_threshold",
+    default: 0.1,
+};
+
+/// How long, in seconds, old versions are kept before they can be cleaned up
+pub const CLEANUP_RETENTION_SECONDS: ConfigKey<u64> = ConfigKey {
+    name: "lance.cleanup.retention_seconds",
+    default: 14 * 24 * 60 * 60,
+};
+
+/// How many times a commit is attempted at most on top of concurrent commits,
+/// at least 1
+///
+/// This is used by the commits that aren't given a [`CommitConfig`], in place
+/// of the default of [`CommitConfig::num_retries`].
+///
+/// [`CommitConfig`]: lance_table::io::commit::CommitConfig
+/// [`CommitConfig::num_retries`]: lance_table::io::commit::CommitConfig::num_retries
+pub const COMMIT_NUM_RETRIES: ConfigKey<u32> = ConfigKey {
+    name: "lance.commit.num_retries",
+    default: 5,
+};
+
+/// How appends conform the vectors of their batches to the vector columns of
+/// the dataset, when their write parameters don't say
+pub const CONSISTENCY_CONFORM_VECTORS: ConfigKey<VectorDimensionPolicy> = ConfigKey {
+    name: "lance.consistency.conform_vectors",
+    default: VectorDimensionPolicy::Error,
+};
+
+/// How vector indices and searches treat null vectors, in the columns whose
+/// metadata doesn't say
+pub const INDEX_NULL_VECTORS: ConfigKey<NullVectorHandling> = ConfigKey {
+    name: "lance.index.null_vectors",
+    default: NullVectorHandling::Skip,
+};
+
+/// Size in bytes of the memory pool of queries that can spill to disk, such
+/// as merge inserts and scalar index training
+///
+/// When not set, the `LANCE_MEM_POOL_SIZE` environment variable is used.
+pub const EXECUTION_MEM_POOL_SIZE: ConfigKey<u64> = ConfigKey {
+    name: "lance.execution.mem_pool_size",
+    default: 100 * 1024 * 1024,
+};
+
+/// The retention of `seconds` seconds, if it is not too long to represent
+fn retention_from_seconds(seconds: u64) -> Result<Duration> {
+    i64::try_from(seconds)
+        .ok()
+        .and_then(Duration::try_seconds)
+        .ok_or_else(|| {
+            Error::invalid_input(
+                format!(
+                    "Dataset config {} is too large: {}",
+                    CLEANUP_RETENTION_SECONDS.name, seconds
+                ),
+                location!(),
+            )
+        })
+}
+
+/// Check that the known settings in `values` can be parsed.
+fn validate(values: &HashMap<String, String>) -> Result<()> {
+    fn check<T>(key: &ConfigKey<T>, values: &HashMap<String, String>) -> Result<()>
+    where
+        T: FromStr + ToString + Clone,
+        T::Err: Display,
+    {
+        match values.get(key.name) {
+            Some(value) => key.parse(value).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+    check(&COMPACTION_TARGET_ROWS_PER_FRAGMENT, values)?;
+    check(&COMPACTION_MAX_ROWS_PER_GROUP, values)?;
+    check(&COMPACTION_MATERIALIZE_DELETIONS_THRESHOLD, values)?;
+    check(&CLEANUP_RETENTION_SECONDS, values)?;
+    if let Some(value) = values.get(CLEANUP_RETENTION_SECONDS.name) {
+        retention_from_seconds(CLEANUP_RETENTION_SECONDS.parse(value)?)?;
+    }
+    check(&COMMIT_NUM_RETRIES, values)?;
+    if let Some(value) = values.get(COMMIT_NUM_RETRIES.name) {
+        // No commit could succeed, not even the one that undoes this
+        if COMMIT_NUM_RETRIES.parse(value)? == 0 {
+            return Err(Error::invalid_input(
+                format!(
+                    "Dataset config {} must be at least 1",
+                    COMMIT_NUM_RETRIES.name
+                ),
+                location!(),
+            ));
+        }
+    }
+    check(&CONSISTENCY_CONFORM_VECTORS, values)?;
+    check(&INDEX_NULL_VECTORS, values)?;
+    check(&EXECUTION_MEM_POOL_SIZE, values)
+}
+
+/// Typed view of the configuration of a dataset version, see [`Dataset::config`].
+#[derive(Debug, Clone, Copy)]
+pub struct DatasetConfig<'a> {
+    values: &'a HashMap<String, String>,
+}
+
+impl<'a> DatasetConfig<'a> {
+    pub(super) fn new(values: &'a HashMap<String, String>) -> Self {
+        Self { values }
+    }
+
+    /// All the stored keys and values
+    pub fn values(&self) -> &'a HashMap<String, String> {
+        self.values
+    }
+
+    /// The value of `key`, if it is set
+    pub fn get_opt<T>(&self, key: &ConfigKey<T>) -> Result<Option<T>>
+    where
+        T: FromStr + ToString + Clone,
+        T::Err: Display,
+    {
+        self.values
+            .get(key.name)
+            .map(|value| key.parse(value))
+            .transpose()
+    }
+
+    /// The value of `key`, or its default if it is not set
+    pub fn get<T>(&self, key: &ConfigKey<T>) -> Result<T>
+    where
+        T: FromStr + ToString + Clone,
+        T::Err: Display,
+    {
+        Ok(self.get_opt(key)?.unwrap_or_else(|| key.default.clone()))
+    }
+
+    /// The compaction options configured for the dataset
+    pub fn compaction_options(&self) -> Result<CompactionOptions> {
+        Ok(CompactionOptions {
+            target_rows_per_fragment: self.get(&COMPACTION_TARGET_ROWS_PER_FRAGMENT)?,
+            max_rows_per_group: self.get(&COMPACTION_MAX_ROWS_PER_GROUP)?,
+            materialize_deletions_threshold: self
+                .get(&COMPACTION_MATERIALIZE_DELETIONS_THRESHOLD)?,
+            ..Default::default()
+        })
+    }
+
+    /// How long old versions are kept, to pass to
+    /// [`Dataset::cleanup_old_versions`]
+    pub fn retention(&self) -> Result<Duration> {
+        retention_from_seconds(self.get(&CLEANUP_RETENTION_SECONDS)?)
+    }
+
+    /// The vector dimension policy of appends whose write parameters don't
+    /// have one, if it is configured
+    pub fn conform_vectors(&self) -> Result<Option<VectorDimensionPolicy>> {
+        self.get_opt(&CONSISTENCY_CONFORM_VECTORS)
+    }
+
+    /// How vector indices and searches treat the null vectors of `field`
+    pub fn null_vectors(&self, field: &Field) -> Result<NullVectorHandling> {
+        NullVectorHandling::from_field(field, self.get(&INDEX_NULL_VECTORS)?)
+    }
+
+    /// The memory pool size of spilling queries, if it is configured
+    pub fn mem_pool_size(&self) -> Result<Option<u64>> {
+        self.get_opt(&EXECUTION_MEM_POOL_SIZE)
+    }
+}
+
+pub(super) async fn update_config(
+    dataset: &mut Dataset,
+    upsert_values: HashMap<String, String>,
+    delete_keys: Vec<String>,
+) -> Result<()> {
+    validate(&upsert_values)?;
+    let transaction = Transaction::new(
+        dataset.manifest.version,
+        Operation::UpdateConfig {
+            upsert_values,
+            delete_keys,
+        },
+        None,
+    );
+    let manifest = commit_transaction(
+        dataset,
+        &dataset.object_store,
+        dataset.commit_handler.as_ref(),
+        &transaction,
+        &Default::default(),
+        &Default::default(),
+    )
+    .await?;
+    dataset.manifest = Arc::new(manifest);
+    Ok(())
+}
+
+pub(super) async fn config_history(dataset: &Dataset) -> Result<Vec<ConfigChange>> {
+    let mut manifests = dataset
+        .commit_handler
+        .list_manifests(&dataset.base, &dataset.object_store.inner)
+        .await?
+        .try_filter_map(|path| async move {
+            let manifest = read_manifest(&dataset.object_store, &path).await?;
+            Ok((manifest.version <= dataset.manifest.version).then_some(manifest))
+        })
+        .try_collect::<Vec<_>>()
+        .await?;
+    manifests.sort_by_key(|manifest| manifest.version);
+
+    let empty = HashMap::<String, String>::new();
+    let mut previous = &empty;
+    let mut changes = Vec::new();
+    for manifest in &manifests {
+        let upserted = manifest
+            .config
+            .iter()
+            .filter(|(key, value)| previous.get(*key) != Some(value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<HashMap<_, _>>();
+        let mut deleted = previous
+            .keys()
+            .filter(|key| !manifest.config.contains_key(*key))
+            .cloned()
+            .collect::<Vec<_>>();
+        deleted.sort();
+        if !upserted.is_empty() || !deleted.is_empty() {
+            changes.push(ConfigChange {
+                version: manifest.version,
+                timestamp: manifest.timestamp(),
+                upserted,
+                deleted,
+            });
+        }
+        previous = &manifest.config;
+    }
+    Ok(changes)
+}
+
+/// A change to the dataset configuration, see [`Dataset::config_history`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    /// The version that made the change
+    pub version: u64,
+    pub timestamp: DateTime<Utc>,
+    /// Keys that were set, with their new values
+    pub upserted: HashMap<String, String>,
+    /// Keys that were removed
+    pub deleted: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_dataset_config() {
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let mut dataset = Dataset::write(reader, uri, None).await.unwrap();
+
+        let config = dataset.config();
+        assert!(config.values().is_empty());
+        assert_eq!(
+            config.get(&COMPACTION_TARGET_ROWS_PER_FRAGMENT).unwrap(),
+            1024 * 1024
+        );
+        assert_eq!(config.mem_pool_size().unwrap(), None);
+
+        dataset
+            .update_config([
+                COMPACTION_TARGET_ROWS_PER_FRAGMENT.entry(1000),
+                CLEANUP_RETENTION_SECONDS.entry(60),
+                ("custom".to_string(), "value".to_string()),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(dataset.version().version, 2);
+        let options = dataset.config().compaction_options().unwrap();
+        assert_eq!(options.target_rows_per_fragment, 1000);
+        assert_eq!(options.max_rows_per_group, 1024);
+        assert_eq!(dataset.config().retention().unwrap(), Duration::seconds(60));
+
+        // Invalid values of known settings are rejected
+        let err = dataset
+            .update_config([(COMMIT_NUM_RETRIES.name.to_string(), "many".to_string())])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains(COMMIT_NUM_RETRIES.name));
+        assert_eq!(dataset.version().version, 2);
+
+        // So are retentions too long to represent
+        for seconds in [i64::MAX as u64 / 1000 + 1, u64::MAX] {
+            let err = dataset
+                .update_config([CLEANUP_RETENTION_SECONDS.entry(seconds)])
+                .await
+                .unwrap_err();
+            assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        }
+        assert_eq!(dataset.version().version, 2);
+        let values = HashMap::from([CLEANUP_RETENTION_SECONDS.entry(u64::MAX)]);
+        let err = DatasetConfig::new(&values).retention().unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+
+        // The config is carried over to later versions
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        dataset.append(reader, None).await.unwrap();
+        dataset.delete_config_keys(&["custom"]).await.unwrap();
+        let dataset = Dataset::open(uri).await.unwrap();
+        assert_eq!(dataset.version().version, 4);
+        assert_eq!(
+            dataset
+                .config()
+                .get(&COMPACTION_TARGET_ROWS_PER_FRAGMENT)
+                .unwrap(),
+            1000
+        );
+        assert!(!dataset.config().values().contains_key("custom"));
+
+        let history = dataset.config_history().await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].version, 2);
+        assert_eq!(history[0].upserted.len(), 3);
+        assert!(history[0].deleted.is_empty());
+        assert_eq!(history[1].version, 4);
+        assert!(history[1].upserted.is_empty());
+        assert_eq!(history[1].deleted, vec!["custom".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_commit_num_retries() {
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let mut dataset = Dataset::write(reader, uri, None).await.unwrap();
+
+        let err = dataset
+            .update_config([COMMIT_NUM_RETRIES.entry(0)])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+        assert_eq!(dataset.version().version, 1);
+
+        // A single attempt is enough without concurrent commits
+        dataset
+            .update_config([COMMIT_NUM_RETRIES.entry(1)])
+            .await
+            .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        dataset.append(reader, None).await.unwrap();
+        assert_eq!(dataset.version().version, 3);
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 20);
+    }
+}
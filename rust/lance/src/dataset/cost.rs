@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Cost model of decoding columns, used to choose between plans
+//!
+//! Bytes on disk underestimate the cost of compressed columns: their values
+//! have to be decompressed and then materialized at their full size. The cost
+//! of a column is estimated from the page encodings of a sample data file, in
+//! bytes of decoded values per row.
+//!
+//! Only the choice between early and late materialization of the columns of
+//! a filtered scan is costed. Plans answered from an index alone are not
+//! considered.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use lance_core::datatypes::Schema;
+use lance_core::Result;
+use lance_table::format::Fragment;
+
+use super::fragment::FileFragment;
+use super::statistics::{data_file_stats, DataFileStats};
+use super::Dataset;
+
+/// Cost of decompressing a byte, relative to materializing it.
+const DECOMPRESSION_COST: f64 = 2.0;
+
+/// Cost of taking a row by its row id after a filter, in the same units as
+/// [`decode_cost_per_row`]: the row id has to be read and carried along, and
+/// the row is read from its page by a separate request.
+const TAKE_COST_PER_ROW: f64 = 64.0;
+
+/// Fraction of the rows assumed to pass a filter. Filters are not estimated.
+const DEFAULT_SELECTIVITY: f64 = 0.1;
+
+/// Cost of decoding a field, from its size on disk and uncompressed size.
+///
+/// Every uncompressed byte is materialized, and the bytes the pages don't
+/// store have to be produced by decompression.
+fn field_decode_cost(bytes_on_disk: u64, uncompressed_bytes: u64) -> f64 {
+    let decompressed = uncompressed_bytes.saturating_sub(bytes_on_disk) as f64;
+    uncompressed_bytes as f64 + decompressed * DECOMPRESSION_COST
+}
+
+/// Whether columns that cost `decode_cost` per row are cheaper to read with
+/// the filter columns, for every row, than to take for the rows that pass the
+/// filter.
+pub(super) fn prefer_early_materialization(decode_cost: f64) -> bool {
+    decode_cost <= DEFAULT_SELECTIVITY * (decode_cost + TAKE_COST_PER_ROW)
+}
+
+/// Estimated cost of decoding a row of the fields of `projection`.
+///
+/// The first of `fragments` is sampled. Fields it has no data for, which are
+/// read as nulls, cost nothing. The statistics of its data files are kept in
+/// the file metadata cache of the session, so that planning only reads them
+/// once.
+pub(super) async fn decode_cost_per_row(
+    dataset: &Arc<Dataset>,
+    fragments: &[Fragment],
+    projection: &Schema,
+) -> Result<f64> {
+    let Some(fragment) = fragments.first() else {
+        return Ok(0.0);
+    };
+    if fragment.files.is_empty() {
+        return Ok(0.0);
+    }
+    let field_ids = projection.field_ids().into_iter().collect::<HashSet<_>>();
+    let fragment = FileFragment::new(dataset.clone(), fragment.clone());
+    let num_rows = fragment.physical_rows().await?;
+    if num_rows == 0 {
+        return Ok(0.0);
+    }
+
+    let mut cost = 0.0;
+    for data_file in &fragment.metadata().files {
+        if !data_file.fields.iter().any(|id| field_ids.contains(id)) {
+            continue;
+        }
+        let path = dataset.data_dir().child(data_file.path.as_str());
+        let cache = &dataset.session.file_metadata_cache;
+        let stats = match cache.get::<DataFileStats>(&path) {
+            Some(stats) => stats,
+            None => {
+                let stats = Arc::new(data_file_stats(&fragment, data_file).await?);
+                cache.insert(path, stats.clone());
+                stats
+            }
+        };
+        cost += stats
+            .fields
+            .iter()
+            .filter(|(field_id, _)| field_ids.contains(field_id))
+            .map(|(_, (on_disk, uncompressed))| field_decode_cost(*on_disk, *uncompressed))
+            .sum::<f64>();
+    }
+    Ok(cost / num_rows as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_decode_cost() {
+        assert_eq!(field_decode_cost(100, 100), 100.0);
+        // A compressed field costs more than its uncompressed size
+        assert_eq!(field_decode_cost(25, 100), 250.0);
+        // The cost grows with the compression ratio, without any step
+        assert!(field_decode_cost(99, 100) - field_decode_cost(100, 100) < 3.0);
+        assert!(field_decode_cost(25, 100) > field_decode_cost(50, 100));
+    }
+
+    #[test]
+    fn test_prefer_early_materialization() {
+        // Narrow columns are read for every row, wide ones taken
+        assert!(prefer_early_materialization(0.0));
+        assert!(prefer_early_materialization(4.0));
+        assert!(!prefer_early_materialization(128.0));
+    }
+}
@@ -0,0 +1,405 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Delete rows by id or with scalar indices
+//!
+//! The ids of the rows to delete are grouped by fragment into bitmaps that
+//! are merged into the deletion vectors of the fragments. Fragments without
+//! any of the rows are not read at all.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use arrow_array::cast::as_primitive_array;
+use arrow_array::UInt64Array;
+use futures::{stream, StreamExt, TryStreamExt};
+use lance_core::utils::address::RowAddress;
+use lance_core::{Error, Result, ROW_ID};
+use lance_table::format::Fragment;
+use roaring::{RoaringBitmap, RoaringTreemap};
+use snafu::{location, Location};
+
+use super::transaction::{Operation, Transaction};
+use super::{commit_transaction, Dataset, FileFragment};
+use crate::index::DatasetIndexInternalExt;
+use crate::io::exec::Planner;
+
+/// Whether scalar indices can locate the rows matching the predicate.
+pub(super) async fn uses_scalar_index(dataset: &Dataset, predicate: &str) -> Result<bool> {
+    let planner = Planner::new(Arc::new(dataset.schema().into()));
+    let filter = planner.parse_filter(predicate)?;
+    let columns = Planner::column_names_in_expr(&filter);
+    let index_info = dataset.scalar_index_info_for_columns(&columns).await?;
+    let filter_plan = planner.create_filter_plan(filter, &index_info, true)?;
+    Ok(filter_plan.index_query.is_some())
+}
+
+/// Merge the row addresses into the deletion vectors of their fragments.
+///
+/// Fragments without any of the addresses are left untouched, without even
+/// reading their deletion files. Returns the updated fragments and the ids of
+/// the fragments where all rows are now deleted.
+async fn apply_deletions(
+    dataset: &Dataset,
+    addresses: &RoaringTreemap,
+) -> Result<(Vec<Fragment>, Vec<u64>)> {
+    let mut bitmaps = addresses
+        .bitmaps()
+        .map(|(fragment_id, bitmap)| (fragment_id, bitmap.clone()))
+        .collect::<BTreeMap<_, _>>();
+    let fragments = dataset
+        .get_fragments()
+        .into_iter()
+        .filter_map(|f| bitmaps.remove(&(f.id() as u32)).map(|bitmap| (f, bitmap)))
+        .collect::<Vec<_>>();
+
+    let mut updated_fragments = Vec::new();
+    let mut deleted_fragment_ids = Vec::new();
+    let mut changes = stream::iter(fragments)
+        .map(|(fragment, bitmap)| async move {
+            let fragment_id = fragment.id() as u64;
+            let physical_rows = fragment.physical_rows().await?;
+            if let Some(offset) = bitmap.max().filter(|o| *o as usize >= physical_rows) {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Row id {} is out of range: fragment {} has {} rows",
+                        RowAddress::new_from_parts(fragment_id as u32, offset),
+                        fragment_id,
+                        physical_rows
+                    ),
+                    location!(),
+                ));
+            }
+            let old_fragment = fragment.metadata.clone();
+            let new_fragment = fragment.extend_deletions(bitmap).await?;
+            Ok((fragment_id, old_fragment, new_fragment.map(|f| f.metadata)))
+        })
+        .buffer_unordered(num_cpus::get() * 4);
+    while let Some((fragment_id, old_fragment, new_fragment)) = changes.try_next().await? {
+        match new_fragment {
+            Some(new_fragment) if new_fragment != old_fragment => {
+                updated_fragments.push(new_fragment)
+            }
+            Some(_) => {}
+            None => deleted_fragment_ids.push(fragment_id),
+        }
+    }
+    Ok((updated_fragments, deleted_fragment_ids))
+}
+
+/// The rows newly deleted in each of the updated fragments, by fragment id.
+///
+/// This is the difference between the deletion vectors of the updated
+/// fragments and those of the same fragments in the dataset.
+pub(super) async fn deleted_rows(
+    dataset: &Dataset,
+    updated_fragments: &[Fragment],
+) -> Result<HashMap<u64, RoaringBitmap>> {
+    let dataset = Arc::new(dataset.clone());
+    stream::iter(updated_fragments.iter().cloned())
+        .map(|new_fragment| {
+            let dataset = dataset.clone();
+            async move {
+                let fragment_id = new_fragment.id;
+                let new_deletions = FileFragment::new(dataset.clone(), new_fragment)
+                    .get_deletion_vector()
+                    .await?;
+                let mut deleted = new_deletions
+                    .map(|dv| RoaringBitmap::from(dv.as_ref()))
+                    .unwrap_or_default();
+                if let Some(old_fragment) = dataset.get_fragment(fragment_id as usize) {
+                    if let Some(old_deletions) = old_fragment.get_deletion_vector().await? {
+                        deleted -= RoaringBitmap::from(old_deletions.as_ref());
+                    }
+                }
+                Ok((fragment_id, deleted))
+            }
+        })
+        .buffer_unordered(num_cpus::get() * 4)
+        .try_filter(|(_, deleted)| futures::future::ready(!deleted.is_empty()))
+        .try_collect()
+        .await
+}
+
+pub(super) async fn commit_delete(
+    dataset: &mut Dataset,
+    updated_fragments: Vec<Fragment>,
+    deleted_fragment_ids: Vec<u64>,
+    predicate: String,
+) -> Result<()> {
+    let modified_rows = deleted_rows(dataset, &updated_fragments).await?;
+    let transaction = Transaction::new(
+        dataset.manifest.version,
+        Operation::Delete {
+            updated_fragments,
+            deleted_fragment_ids,
+            predicate,
+        },
+        None,
+    )
+    .with_modified_rows(modified_rows);
+    let manifest = commit_transaction(
+        dataset,
+        &dataset.object_store,
+        dataset.commit_handler.as_ref(),
+        &transaction,
+        &Default::default(),
+        &Default::default(),
+    )
+    .await?;
+    dataset.manifest = Arc::new(manifest);
+
+    Ok(())
+}
+
+/// Delete the rows matching the predicate, located with scalar indices.
+///
+/// Only the fragments that are not covered by the indices are scanned.
+pub(super) async fn delete_indexed(dataset: &mut Dataset, predicate: &str) -> Result<()> {
+    let mut scanner = dataset.scan();
+    scanner
+        .with_row_id()
+        .filter(predicate)?
+        .project::<&str>(&[])?;
+    let mut addresses = RoaringTreemap::new();
+    let mut stream = scanner.try_into_stream().await?;
+    while let Some(batch) = stream.try_next().await? {
+        let row_ids: &UInt64Array = as_primitive_array(batch[ROW_ID].as_ref());
+        addresses.extend(row_ids.values().iter().copied());
+    }
+
+    let (updated_fragments, deleted_fragment_ids) = apply_deletions(dataset, &addresses).await?;
+    commit_delete(
+        dataset,
+        updated_fragments,
+        deleted_fragment_ids,
+        predicate.to_string(),
+    )
+    .await
+}
+
+pub(super) async fn delete_rows(
+    dataset: &mut Dataset,
+    row_ids: impl IntoIterator<Item = u64>,
+) -> Result<()> {
+    // Like the `_rowid` column of scans, the ids are row addresses
+    let addresses = row_ids.into_iter().collect::<RoaringTreemap>();
+    let (updated_fragments, deleted_fragment_ids) = apply_deletions(dataset, &addresses).await?;
+    if updated_fragments.is_empty() && deleted_fragment_ids.is_empty() {
+        return Ok(());
+    }
+    commit_delete(
+        dataset,
+        updated_fragments,
+        deleted_fragment_ids,
+        format!("_rowid IN ({} row ids)", addresses.len()),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use lance_index::{DatasetIndexExt, IndexType};
+
+    use crate::dataset::{UpdateBuilder, WriteMode, WriteParams};
+    use crate::index::scalar::ScalarIndexParams;
+
+    fn reader(values: std::ops::Range<i32>) -> impl arrow_array::RecordBatchReader {
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(values))],
+        )
+        .unwrap();
+        RecordBatchIterator::new(vec![Ok(batch)], schema)
+    }
+
+    async fn create_dataset(uri: &str, stable_row_ids: bool) -> Dataset {
+        let params = WriteParams {
+            max_rows_per_file: 10,
+            enable_move_stable_row_ids: stable_row_ids,
+            ..Default::default()
+        };
+        Dataset::write(reader(0..30), uri, Some(params))
+            .await
+            .unwrap()
+    }
+
+    async fn values(dataset: &Dataset) -> Vec<i32> {
+        let batch = dataset.scan().try_into_batch().await.unwrap();
+        let mut values = batch["i"]
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap()
+            .values()
+            .to_vec();
+        values.sort();
+        values
+    }
+
+    #[tokio::test]
+    async fn test_delete_rows() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let mut dataset = create_dataset(test_dir.path().to_str().unwrap(), false).await;
+
+        // Rows 1 and 2 of the first fragment, all of the second one, and a
+        // fragment that doesn't exist.
+        let address = |fragment, row| u64::from(RowAddress::new_from_parts(fragment, row));
+        let row_ids = [address(0, 1), address(0, 2), address(7, 0)]
+            .into_iter()
+            .chain((0..10).map(|row| address(1, row)));
+        dataset.delete_rows(row_ids).await.unwrap();
+        assert_eq!(dataset.version().version, 2);
+        assert_eq!(dataset.get_fragments().len(), 2);
+        let expected = [0]
+            .into_iter()
+            .chain(3..10)
+            .chain(20..30)
+            .collect::<Vec<_>>();
+        assert_eq!(values(&dataset).await, expected);
+
+        let transaction = dataset.read_transaction().await.unwrap().unwrap();
+        assert!(matches!(
+            transaction.operation,
+            Operation::Delete { ref deleted_fragment_ids, .. } if deleted_fragment_ids == &[1]
+        ));
+        let expected = HashMap::from([(0, RoaringBitmap::from_iter([1, 2]))]);
+        assert_eq!(transaction.modified_rows, expected);
+
+        // Deleting deleted rows again doesn't commit a new version.
+        dataset.delete_rows([address(0, 1)]).await.unwrap();
+        assert_eq!(dataset.version().version, 2);
+
+        let err = dataset.delete_rows([address(0, 10)]).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_delete_stable_row_ids() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let mut dataset = create_dataset(test_dir.path().to_str().unwrap(), true).await;
+
+        // The ids are taken from the `_rowid` column of a scan
+        let batch = dataset
+            .scan()
+            .with_row_id()
+            .filter("i % 10 = 5")
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+        let row_ids: &UInt64Array = as_primitive_array(batch[ROW_ID].as_ref());
+        assert_eq!(row_ids.len(), 3);
+        dataset
+            .delete_rows(row_ids.values().iter().copied())
+            .await
+            .unwrap();
+        let expected = (0..30).filter(|v| v % 10 != 5).collect::<Vec<_>>();
+        assert_eq!(values(&dataset).await, expected);
+    }
+
+    #[tokio::test]
+    async fn test_delete_with_scalar_index() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = create_dataset(test_uri, false).await;
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                true,
+            )
+            .await
+            .unwrap();
+        // The new fragment is not covered by the index.
+        let params = WriteParams {
+            mode: WriteMode::Append,
+            ..Default::default()
+        };
+        dataset.append(reader(30..40), Some(params)).await.unwrap();
+
+        assert!(uses_scalar_index(&dataset, "i IN (5, 35)").await.unwrap());
+        assert!(!uses_scalar_index(&dataset, "i % 10 = 5").await.unwrap());
+
+        dataset.delete("i IN (5, 35)").await.unwrap();
+        let has_deletions = dataset
+            .get_fragments()
+            .iter()
+            .map(|f| f.metadata.deletion_file.is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(has_deletions, vec![true, false, false, true]);
+
+        dataset.delete("i < 10").await.unwrap();
+        let transaction = dataset.read_transaction().await.unwrap().unwrap();
+        assert!(matches!(
+            transaction.operation,
+            Operation::Delete { ref updated_fragments, ref deleted_fragment_ids, .. }
+                if updated_fragments.is_empty() && deleted_fragment_ids == &[0]
+        ));
+
+        // A predicate without matches still commits, like a scan-based delete.
+        let version = dataset.version().version;
+        dataset.delete("i = 100").await.unwrap();
+        assert_eq!(dataset.version().version, version + 1);
+
+        let expected = (10..40).filter(|v| *v != 35).collect::<Vec<_>>();
+        assert_eq!(values(&dataset).await, expected);
+    }
+
+    #[tokio::test]
+    async fn test_modified_rows() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let mut dataset = create_dataset(test_dir.path().to_str().unwrap(), false).await;
+        assert!(dataset.modified_rows(1).await.unwrap().is_empty());
+
+        // Rows already deleted are not modified again.
+        dataset.delete("i IN (1, 2)").await.unwrap();
+        dataset.delete("i IN (2, 3, 12)").await.unwrap();
+        let transaction = dataset.read_transaction().await.unwrap().unwrap();
+        let expected = HashMap::from([
+            (0, RoaringBitmap::from_iter([3])),
+            (1, RoaringBitmap::from_iter([2])),
+        ]);
+        assert_eq!(transaction.modified_rows, expected);
+
+        // Updated rows are moved to a new fragment.
+        let dataset = UpdateBuilder::new(Arc::new(dataset))
+            .update_where("i >= 28")
+            .unwrap()
+            .set("i", "i + 100")
+            .unwrap()
+            .build()
+            .unwrap()
+            .execute()
+            .await
+            .unwrap();
+        let transaction = dataset.read_transaction().await.unwrap().unwrap();
+        let expected = HashMap::from([(2, RoaringBitmap::from_iter([8, 9]))]);
+        assert_eq!(transaction.modified_rows, expected);
+
+        let expected = HashMap::from([
+            (0, RoaringBitmap::from_iter([1, 2, 3])),
+            (1, RoaringBitmap::from_iter([2])),
+            (2, RoaringBitmap::from_iter([8, 9])),
+            (3, RoaringBitmap::from_iter([0, 1])),
+        ]);
+        assert_eq!(dataset.modified_rows(1).await.unwrap(), expected);
+        let expected = HashMap::from([
+            (2, RoaringBitmap::from_iter([8, 9])),
+            (3, RoaringBitmap::from_iter([0, 1])),
+        ]);
+        assert_eq!(dataset.modified_rows(3).await.unwrap(), expected);
+        assert!(dataset.modified_rows(4).await.unwrap().is_empty());
+        assert!(dataset.modified_rows(5).await.is_err());
+    }
+}
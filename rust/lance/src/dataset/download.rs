@@ -0,0 +1,362 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Export a snapshot of a dataset to another location
+//!
+//! Only the files needed to read one version are copied, optionally narrowed
+//! to some columns and fragments, and a manifest describing the copy is
+//! written next to them. The copy is a standalone dataset, which is useful to
+//! run inference on edge devices or offline.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use futures::{stream, StreamExt, TryStreamExt};
+use lance_core::{Error, Result};
+use lance_index::DatasetIndexExt;
+use lance_io::object_store::{ObjectStore, ObjectStoreExt};
+use lance_table::format::RowIdMeta;
+use lance_table::io::commit::commit_handler_from_url;
+use lance_table::io::deletion::deletion_file_path;
+use object_store::path::Path;
+use roaring::RoaringBitmap;
+use snafu::{location, Location};
+use tokio::io::AsyncWriteExt;
+
+use super::{write_manifest_file, Dataset, ManifestWriteConfig, DATA_DIR, INDICES_DIR};
+use crate::utils::temporal::SystemTime;
+
+/// Parameters of [`Dataset::download`].
+#[derive(Debug, Clone, Default)]
+pub struct DownloadParams {
+    /// Columns to copy. Nested fields can be selected with `.`. All columns
+    /// by default.
+    pub columns: Option<Vec<String>>,
+    /// Ids of the fragments to copy. All fragments by default.
+    pub fragment_ids: Option<Vec<u64>>,
+}
+
+pub async fn copy_file(
+    source: &ObjectStore,
+    from: &Path,
+    target: &ObjectStore,
+    to: &Path,
+) -> Result<()> {
+    let mut chunks = source.inner.get(from).await?.into_stream();
+    let mut writer = target.create(to).await?;
+    while let Some(chunk) = chunks.try_next().await? {
+        writer.write_all(&chunk).await?;
+    }
+    writer.shutdown().await?;
+    Ok(())
+}
+
+pub(super) async fn download(
+    dataset: &Dataset,
+    version: u64,
+    uri: &str,
+    params: &DownloadParams,
+) -> Result<Dataset> {
+    if Dataset::open(uri).await.is_ok() {
+        return Err(Error::DatasetAlreadyExists {
+            uri: uri.to_string(),
+            location: location!(),
+        });
+    }
+    let source = dataset.checkout_version(version).await?;
+    let (target_store, target_base) = ObjectStore::from_uri(uri).await?;
+
+    let schema = match &params.columns {
+        Some(columns) => source.schema().project(columns)?,
+        None => source.schema().clone(),
+    };
+    let field_ids = schema.field_ids().into_iter().collect::<HashSet<_>>();
+
+    let mut fragments = source.manifest.fragments.as_ref().clone();
+    if let Some(fragment_ids) = &params.fragment_ids {
+        let known = fragments.iter().map(|f| f.id).collect::<HashSet<_>>();
+        if let Some(missing) = fragment_ids.iter().find(|id| !known.contains(id)) {
+            return Err(Error::invalid_input(
+                format!("Fragment {} does not exist in version {}", missing, version),
+                location!(),
+            ));
+        }
+        fragments.retain(|f| fragment_ids.contains(&f.id));
+    }
+
+    // Pairs of (source, target) paths. A file may be shared by fragments.
+    let mut copies = Vec::new();
+    let mut seen = HashSet::new();
+    let mut add_copy = |from: Path, to: Path| {
+        if seen.insert(from.clone()) {
+            copies.push((from, to));
+        }
+    };
+    for fragment in fragments.iter_mut() {
+        // Data files without any selected field are left out. Files that mix
+        // selected and other fields are copied whole, and readers ignore the
+        // fields that are not in the schema.
+        fragment
+            .files
+            .retain(|file| file.fields.iter().any(|id| field_ids.contains(id)));
+        for file in &fragment.files {
+            add_copy(
+                source.data_dir().child(file.path.as_str()),
+                target_base.child(DATA_DIR).child(file.path.as_str()),
+            );
+        }
+        if let Some(deletion_file) = &fragment.deletion_file {
+            add_copy(
+                deletion_file_path(&source.base, fragment.id, deletion_file),
+                deletion_file_path(&target_base, fragment.id, deletion_file),
+            );
+        }
+        if let Some(RowIdMeta::External(file)) = &fragment.row_id_meta {
+            add_copy(
+                source.base.child(file.path.as_str()),
+                target_base.child(file.path.as_str()),
+            );
+        }
+    }
+
+    // Indices are kept if they only cover copied fields and fragments.
+    let fragment_bitmap = fragments
+        .iter()
+        .map(|f| f.id as u32)
+        .collect::<RoaringBitmap>();
+    let indices = source
+        .load_indices()
+        .await?
+        .iter()
+        .filter(|index| {
+            index.fields.iter().all(|id| field_ids.contains(id))
+                && (params.fragment_ids.is_none()
+                    || index
+                        .fragment_bitmap
+                        .as_ref()
+                        .is_some_and(|bitmap| bitmap.is_subset(&fragment_bitmap)))
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    for index in &indices {
+        let index_dir = source.indices_dir().child(index.uuid.to_string());
+        let files = source
+            .object_store
+            .inner
+            .read_dir_all(&index_dir, None)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        for file in files {
+            let Some(relative) = file.location.prefix_match(&index_dir) else {
+                continue;
+            };
+            let to = relative.fold(
+                target_base.child(INDICES_DIR).child(index.uuid.to_string()),
+                |path, part| path.child(part),
+            );
+            add_copy(file.location, to);
+        }
+    }
+
+    stream::iter(copies)
+        .map(|(from, to)| {
+            let source_store = source.object_store.clone();
+            let target_store = &target_store;
+            async move { copy_file(&source_store, &from, target_store, &to).await }
+        })
+        .buffer_unordered(num_cpus::get() * 4)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let mut manifest = source.manifest.as_ref().clone();
+    manifest.schema = schema;
+    manifest.fragments = fragments.into();
+    manifest.transaction_file = None;
+    manifest.index_section = None;
+    let commit_handler = commit_handler_from_url(uri, &None).await?;
+    let config = ManifestWriteConfig {
+        auto_set_feature_flags: false,
+        timestamp: Some(
+            SystemTime::UNIX_EPOCH + Duration::from_nanos(manifest.timestamp_nanos as u64),
+        ),
+        ..Default::default()
+    };
+    write_manifest_file(
+        &target_store,
+        commit_handler.as_ref(),
+        &target_base,
+        &mut manifest,
+        Some(indices),
+        &config,
+    )
+    .await?;
+
+    Dataset::open(uri).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator, StringArray};
+    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use lance_index::IndexType;
+    use tempfile::tempdir;
+
+    use crate::dataset::WriteParams;
+    use crate::index::scalar::ScalarIndexParams;
+
+    fn batch(start: i32, count: i32) -> RecordBatch {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("s", DataType::Utf8, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from_iter_values(start..start + count)),
+                Arc::new(StringArray::from_iter_values(
+                    (start..start + count).map(|i| format!("s-{}", i)),
+                )),
+            ],
+        )
+        .unwrap()
+    }
+
+    async fn create_dataset(uri: &str) -> Dataset {
+        let data = batch(0, 100);
+        let schema = data.schema();
+        let params = WriteParams {
+            max_rows_per_file: 50,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(data)], schema);
+        let mut dataset = Dataset::write(reader, uri, Some(params)).await.unwrap();
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        dataset.delete("i < 10").await.unwrap();
+        dataset
+    }
+
+    #[tokio::test]
+    async fn test_download() {
+        let source_dir = tempdir().unwrap();
+        let source_uri = source_dir
+            .path()
+            .join("source")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let dataset = create_dataset(&source_uri).await;
+        let target_dir = tempdir().unwrap();
+        let target_uri = target_dir.path().join("copy").to_str().unwrap().to_string();
+
+        let copy = dataset
+            .download(dataset.version().version, &target_uri, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(copy.version().version, dataset.version().version);
+        assert_eq!(copy.schema(), dataset.schema());
+        assert_eq!(copy.count_rows(None).await.unwrap(), 90);
+        assert_eq!(copy.load_indices().await.unwrap().len(), 1);
+        assert_eq!(
+            copy.count_rows(Some("i = 42".to_string())).await.unwrap(),
+            1
+        );
+        copy.validate().await.unwrap();
+
+        // The copy can't be overwritten.
+        let err = dataset
+            .download(dataset.version().version, &target_uri, &Default::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::DatasetAlreadyExists { .. }));
+
+        // Deleting the source does not affect the copy.
+        drop(dataset);
+        drop(source_dir);
+        let batches = copy
+            .scan()
+            .filter("i >= 98")
+            .unwrap()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_download_subset() {
+        let source_dir = tempdir().unwrap();
+        let source_uri = source_dir
+            .path()
+            .join("source")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let dataset = create_dataset(&source_uri).await;
+        let target_dir = tempdir().unwrap();
+
+        // An older version, before the deletion.
+        let target_uri = target_dir.path().join("v1").to_str().unwrap().to_string();
+        let copy = dataset
+            .download(1, &target_uri, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(copy.version().version, 1);
+        assert_eq!(copy.count_rows(None).await.unwrap(), 100);
+        assert!(copy.load_indices().await.unwrap().is_empty());
+
+        // One column of one fragment. The index covers both fragments, so it
+        // is left out.
+        let target_uri = target_dir
+            .path()
+            .join("subset")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let params = DownloadParams {
+            columns: Some(vec!["s".to_string()]),
+            fragment_ids: Some(vec![1]),
+        };
+        let copy = dataset
+            .download(dataset.version().version, &target_uri, &params)
+            .await
+            .unwrap();
+        assert_eq!(copy.schema().fields.len(), 1);
+        assert_eq!(copy.schema().fields[0].name, "s");
+        assert_eq!(copy.count_fragments(), 1);
+        assert_eq!(copy.count_rows(None).await.unwrap(), 50);
+        assert!(copy.load_indices().await.unwrap().is_empty());
+
+        let params = DownloadParams {
+            fragment_ids: Some(vec![7]),
+            ..Default::default()
+        };
+        let target_uri = target_dir
+            .path()
+            .join("missing")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let err = dataset
+            .download(dataset.version().version, &target_uri, &params)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+    }
+}
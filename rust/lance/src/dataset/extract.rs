@@ -0,0 +1,359 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Extract a subset of the columns of a dataset into a new dataset
+//!
+//! This splits very wide datasets vertically. Data files that hold only
+//! selected columns, in the requested file format, are copied byte for byte.
+//! Fragments where the selected columns share files with other columns are
+//! read and written again, so the new dataset never holds the other columns.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::{stream, StreamExt, TryStreamExt};
+use lance_core::{Error, Result};
+use lance_io::object_store::ObjectStore;
+use lance_table::format::Fragment;
+use lance_table::io::deletion::deletion_file_path;
+use object_store::path::Path;
+use snafu::{location, Location};
+
+use super::download::copy_file;
+use super::fragment::FileFragment;
+use super::transaction::Operation;
+use super::{write_fragments_internal, Dataset, WriteMode, WriteParams, DATA_DIR};
+use crate::datatypes::Schema;
+
+/// How a fragment of the source dataset ends up in the new dataset.
+enum ExtractedFragment {
+    /// Its data files and deletion file were copied.
+    Copied(Fragment),
+    /// The selected columns were written into new fragments.
+    Rewritten(Vec<Fragment>),
+}
+
+/// Whether the files of `fragment` can be copied: every data file holds
+/// either only selected fields or none, in the requested format.
+fn can_copy(fragment: &Fragment, field_ids: &HashSet<i32>, params: &WriteParams) -> bool {
+    fragment.files.iter().all(|file| {
+        let selected = file
+            .fields
+            .iter()
+            .filter(|id| field_ids.contains(id))
+            .count();
+        selected == 0
+            || (selected == file.fields.len() && file.is_legacy_file() == params.use_legacy_format)
+    })
+}
+
+async fn extract_fragment(
+    fragment: FileFragment,
+    columns: &[&str],
+    schema: &Schema,
+    field_ids: &HashSet<i32>,
+    target_store: &Arc<ObjectStore>,
+    target_base: &Path,
+    params: &WriteParams,
+) -> Result<ExtractedFragment> {
+    let dataset = fragment.dataset();
+    let metadata = fragment.metadata();
+    if can_copy(metadata, field_ids, params) {
+        let mut copied = metadata.clone();
+        copied
+            .files
+            .retain(|file| file.fields.iter().any(|id| field_ids.contains(id)));
+        copied.row_id_meta = None;
+        for file in &copied.files {
+            copy_file(
+                &dataset.object_store,
+                &dataset.data_dir().child(file.path.as_str()),
+                target_store,
+                &target_base.child(DATA_DIR).child(file.path.as_str()),
+            )
+            .await?;
+        }
+        if let Some(deletion_file) = &copied.deletion_file {
+            copy_file(
+                &dataset.object_store,
+                &deletion_file_path(&dataset.base, copied.id, deletion_file),
+                target_store,
+                &deletion_file_path(target_base, copied.id, deletion_file),
+            )
+            .await?;
+        }
+        return Ok(ExtractedFragment::Copied(copied));
+    }
+
+    let mut scanner = fragment.scan();
+    scanner.project(columns)?.scan_in_order(true);
+    let data = SendableRecordBatchStream::from(scanner.try_into_stream().await?);
+    let fragments = write_fragments_internal(
+        None,
+        target_store.clone(),
+        target_base,
+        schema,
+        data,
+        WriteParams {
+            mode: WriteMode::Create,
+            ..params.clone()
+        },
+    )
+    .await?;
+    Ok(ExtractedFragment::Rewritten(fragments))
+}
+
+pub(super) async fn extract_columns(
+    dataset: &Dataset,
+    columns: &[&str],
+    uri: &str,
+    params: Option<WriteParams>,
+) -> Result<Dataset> {
+    let params = params.unwrap_or_default();
+    if Dataset::open(uri).await.is_ok() {
+        return Err(Error::DatasetAlreadyExists {
+            uri: uri.to_string(),
+            location: location!(),
+        });
+    }
+    if columns.is_empty() {
+        return Err(Error::invalid_input(
+            "At least one column must be extracted",
+            location!(),
+        ));
+    }
+    let schema = dataset.schema().project(columns)?;
+    let field_ids = schema.field_ids().into_iter().collect::<HashSet<_>>();
+    let store_params = params.store_params.clone().unwrap_or_default();
+    let (target_store, target_base) = ObjectStore::from_uri_and_params(uri, &store_params).await?;
+    let target_store = Arc::new(target_store);
+
+    let dataset_ref = Arc::new(dataset.clone());
+    let extracted = stream::iter(dataset.manifest.fragments.iter().cloned())
+        .map(|fragment| {
+            extract_fragment(
+                FileFragment::new(dataset_ref.clone(), fragment),
+                columns,
+                &schema,
+                &field_ids,
+                &target_store,
+                &target_base,
+                &params,
+            )
+        })
+        .buffered(num_cpus::get())
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    // Copied fragments keep their ids, since their deletion file paths depend
+    // on them. Rewritten fragments get ids above every source fragment.
+    let mut next_id = dataset.manifest.max_fragment_id().map_or(0, |id| id + 1);
+    let mut fragments = Vec::new();
+    for extracted in extracted {
+        match extracted {
+            ExtractedFragment::Copied(fragment) => fragments.push(fragment),
+            ExtractedFragment::Rewritten(rewritten) => {
+                for mut fragment in rewritten {
+                    fragment.id = next_id;
+                    next_id += 1;
+                    fragments.push(fragment);
+                }
+            }
+        }
+    }
+
+    Dataset::commit(
+        uri,
+        Operation::Overwrite { fragments, schema },
+        None,
+        params.store_params,
+        params.commit_handler,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use lance_datagen::{array, gen, BatchCount, RowCount};
+    use tempfile::tempdir;
+
+    use crate::dataset::NewColumnTransform;
+
+    async fn create_dataset(uri: &str) -> Dataset {
+        let reader = gen()
+            .col("a", array::step::<arrow_array::types::Int32Type>())
+            .col(
+                "b",
+                array::rand_utf8(lance_datagen::ByteCount::from(8), false),
+            )
+            .into_reader_rows(RowCount::from(50), BatchCount::from(4));
+        let params = WriteParams {
+            max_rows_per_file: 100,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(reader, uri, Some(params)).await.unwrap();
+        // `c` lives in its own data files.
+        dataset
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![("c".into(), "a * 2".into())]),
+                None,
+            )
+            .await
+            .unwrap();
+        dataset.delete("a < 10").await.unwrap();
+        dataset
+    }
+
+    async fn column_values(dataset: &Dataset, column: &str) -> Vec<i32> {
+        let batches = dataset
+            .scan()
+            .project(&[column])
+            .unwrap()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        batches
+            .iter()
+            .flat_map(|b| {
+                b.column(0)
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_extract_copies_files() {
+        let dir = tempdir().unwrap();
+        let source_uri = dir.path().join("source").to_str().unwrap().to_string();
+        let dataset = create_dataset(&source_uri).await;
+        let uri = dir.path().join("c").to_str().unwrap().to_string();
+
+        let extracted = dataset.extract_columns(&["c"], &uri, None).await.unwrap();
+        assert_eq!(extracted.schema().fields.len(), 1);
+        assert_eq!(
+            extracted.schema().fields[0].id,
+            dataset.schema().field("c").unwrap().id
+        );
+        assert_eq!(extracted.count_rows(None).await.unwrap(), 190);
+        assert_eq!(
+            column_values(&extracted, "c").await,
+            column_values(&dataset, "c").await
+        );
+
+        // Every fragment was copied with its deletion file.
+        for (source, copied) in dataset
+            .get_fragments()
+            .iter()
+            .zip(extracted.get_fragments())
+        {
+            assert_eq!(copied.id(), source.id());
+            assert_eq!(copied.metadata().files.len(), 1);
+            assert_eq!(
+                copied.metadata().files[0].path,
+                source.metadata().files[1].path
+            );
+            assert_eq!(
+                copied.metadata().deletion_file,
+                source.metadata().deletion_file
+            );
+        }
+
+        let err = dataset
+            .extract_columns(&["c"], &uri, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::DatasetAlreadyExists { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_extract_rewrites_shared_files() {
+        let dir = tempdir().unwrap();
+        let source_uri = dir.path().join("source").to_str().unwrap().to_string();
+        let dataset = create_dataset(&source_uri).await;
+        let uri = dir.path().join("a_c").to_str().unwrap().to_string();
+
+        // `a` shares its files with `b`, so those fragments are rewritten.
+        let extracted = dataset
+            .extract_columns(&["a", "c"], &uri, None)
+            .await
+            .unwrap();
+        let names = extracted
+            .schema()
+            .fields
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "c"]);
+        assert_eq!(extracted.count_rows(None).await.unwrap(), 190);
+        assert_eq!(
+            column_values(&extracted, "a").await,
+            column_values(&dataset, "a").await
+        );
+        assert_eq!(
+            column_values(&extracted, "c").await,
+            column_values(&dataset, "c").await
+        );
+        let max_source_id = dataset.manifest.max_fragment_id().unwrap();
+        for fragment in extracted.get_fragments() {
+            assert!(fragment.id() as u64 > max_source_id);
+            assert!(fragment.metadata().deletion_file.is_none());
+            let fields = &fragment.metadata().files[0].fields;
+            assert!(!fields.contains(&dataset.schema().field("b").unwrap().id));
+        }
+        extracted.validate().await.unwrap();
+
+        let err = dataset
+            .extract_columns(&[], dir.path().join("none").to_str().unwrap(), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_extract_converts_format() {
+        let dir = tempdir().unwrap();
+        let uri = dir.path().join("source").to_str().unwrap().to_string();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "x",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let dataset = Dataset::write(reader, &uri, None).await.unwrap();
+
+        // A different file format can't be copied, so the data is rewritten.
+        let params = WriteParams {
+            use_legacy_format: !WriteParams::default().use_legacy_format,
+            ..Default::default()
+        };
+        let target = dir.path().join("x").to_str().unwrap().to_string();
+        let extracted = dataset
+            .extract_columns(&["x"], &target, Some(params.clone()))
+            .await
+            .unwrap();
+        let fragments = extracted.get_fragments();
+        let file = &fragments[0].metadata().files[0];
+        assert_eq!(file.is_legacy_file(), params.use_legacy_format);
+        assert_eq!(
+            column_values(&extracted, "x").await,
+            (0..10).collect::<Vec<_>>()
+        );
+    }
+}
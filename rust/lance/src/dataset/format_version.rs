@@ -0,0 +1,279 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Format versions used by a dataset
+//!
+//! A dataset is described by its manifest, whose feature flags name the table
+//! features readers and writers must support, and its data files, each of
+//! which is written in some version of the Lance file format.  Datasets that
+//! were written over time can mix file format versions, [`migrate_format`]
+//! rewrites the data files so they all use the same one.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use futures::{stream, StreamExt, TryStreamExt};
+use lance_core::{datatypes::Schema, Error, Result};
+use lance_file::format::{MAJOR_VERSION, MINOR_VERSION_NEXT};
+use lance_table::feature_flags::{can_read_dataset, can_write_dataset, feature_flag_names};
+use lance_table::format::{DataFile, Fragment, WriterVersion};
+use snafu::{location, Location};
+
+use super::fragment::FileFragment;
+use super::transaction::{Operation, Transaction};
+use super::{commit_transaction, Dataset};
+
+/// The version of the Lance file format a data file is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FileFormatVersion {
+    /// The original file format, written with `use_legacy_format`
+    Legacy,
+    /// The v2 file format
+    V2,
+    /// A file format version this library doesn't know about
+    Unknown { major: u32, minor: u32 },
+}
+
+impl FileFormatVersion {
+    /// The version `data_file` is written in
+    pub fn of(data_file: &DataFile) -> Self {
+        if data_file.is_legacy_file() {
+            Self::Legacy
+        } else if data_file.file_major_version == MAJOR_VERSION as u32
+            && data_file.file_minor_version == MINOR_VERSION_NEXT as u32
+        {
+            Self::V2
+        } else {
+            Self::Unknown {
+                major: data_file.file_major_version,
+                minor: data_file.file_minor_version,
+            }
+        }
+    }
+
+    /// Whether this library can read files in this version
+    pub fn can_read(&self) -> bool {
+        !matches!(self, Self::Unknown { .. })
+    }
+
+    /// Whether this library can write files in this version
+    pub fn can_write(&self) -> bool {
+        !matches!(self, Self::Unknown { .. })
+    }
+}
+
+impl fmt::Display for FileFormatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Legacy => write!(f, "legacy"),
+            Self::V2 => write!(f, "{}.{}", MAJOR_VERSION, MINOR_VERSION_NEXT),
+            Self::Unknown { major, minor } => write!(f, "{}.{}", major, minor),
+        }
+    }
+}
+
+/// The format versions of a dataset, see [`Dataset::format_report`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatReport {
+    /// The library and version that wrote the checked out version, if recorded
+    pub writer_version: Option<WriterVersion>,
+    /// Number of data files written in each file format version
+    pub file_versions: BTreeMap<FileFormatVersion, usize>,
+    /// Features a reader must support to read the dataset
+    pub reader_features: Vec<String>,
+    /// Features a writer must support to write to the dataset
+    pub writer_features: Vec<String>,
+    /// Whether this library can read the dataset
+    pub can_read: bool,
+    /// Whether this library can write to the dataset
+    pub can_write: bool,
+}
+
+pub(super) fn format_report(dataset: &Dataset) -> FormatReport {
+    let manifest = dataset.manifest.as_ref();
+    let mut file_versions = BTreeMap::new();
+    for data_file in manifest.fragments.iter().flat_map(|frag| frag.files.iter()) {
+        *file_versions
+            .entry(FileFormatVersion::of(data_file))
+            .or_insert(0) += 1;
+    }
+    let can_read = can_read_dataset(manifest.reader_feature_flags)
+        && file_versions.keys().all(|version| version.can_read());
+    // Writes add new files next to the existing ones, which are still read.
+    let can_write = can_write_dataset(manifest.writer_feature_flags)
+        && file_versions
+            .keys()
+            .all(|version| version.can_read() && version.can_write());
+    FormatReport {
+        writer_version: manifest.writer_version.clone(),
+        file_versions,
+        reader_features: feature_flag_names(manifest.reader_feature_flags),
+        writer_features: feature_flag_names(manifest.writer_feature_flags),
+        can_read,
+        can_write,
+    }
+}
+
+/// Rewrite the data files that aren't in the `target` version.
+///
+/// Each fragment is rewritten into a single data file, keeping the positions
+/// of its rows, so row ids, deletion files and indices stay valid.
+pub(super) async fn migrate_format(dataset: &mut Dataset, target: FileFormatVersion) -> Result<()> {
+    if !target.can_write() {
+        return Err(Error::invalid_input(
+            format!("Cannot migrate to file format version {}", target),
+            location!(),
+        ));
+    }
+    let use_legacy_format = target == FileFormatVersion::Legacy;
+
+    let fragments = dataset
+        .get_fragments()
+        .into_iter()
+        .filter(|fragment| {
+            fragment
+                .metadata()
+                .files
+                .iter()
+                .any(|data_file| FileFormatVersion::of(data_file) != target)
+        })
+        .collect::<Vec<_>>();
+    if fragments.is_empty() {
+        return Ok(());
+    }
+
+    let schema = Arc::new(dataset.schema().clone());
+    let updated_fragments = stream::iter(fragments)
+        .map(|fragment| migrate_fragment(fragment, schema.clone(), use_legacy_format))
+        .buffered(num_cpus::get())
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let transaction = Transaction::new(
+        dataset.manifest.version,
+        Operation::Update {
+            removed_fragment_ids: vec![],
+            updated_fragments,
+            new_fragments: vec![],
+        },
+        None,
+    );
+    let manifest = commit_transaction(
+        dataset,
+        &dataset.object_store,
+        dataset.commit_handler.as_ref(),
+        &transaction,
+        &Default::default(),
+        &Default::default(),
+    )
+    .await?;
+    dataset.manifest = Arc::new(manifest);
+
+    Ok(())
+}
+
+async fn migrate_fragment(
+    fragment: FileFragment,
+    schema: Arc<Schema>,
+    use_legacy_format: bool,
+) -> Result<Fragment> {
+    let num_files = fragment.metadata().files.len();
+    let mut updater = fragment
+        .updater::<&str>(
+            None,
+            Some((schema.as_ref().clone(), schema.as_ref().clone())),
+        )
+        .await?
+        .with_legacy_format(use_legacy_format);
+    while let Some(batch) = updater.next().await? {
+        let batch = batch.clone();
+        updater.update(batch).await?;
+    }
+    let mut metadata = updater.finish().await?;
+    // The new file holds every column, so it replaces all the old ones
+    if metadata.files.len() > num_files {
+        metadata.files.drain(..num_files);
+    }
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int64Array, RecordBatch, RecordBatchIterator, StringArray};
+    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use lance_table::feature_flags::FLAG_DELETION_FILES;
+    use tempfile::tempdir;
+
+    use crate::dataset::WriteParams;
+
+    #[tokio::test]
+    async fn test_migrate_format() {
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int64, false),
+            ArrowField::new("s", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(0..1000)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..1000).map(|i| format!("string-{i}")),
+                )),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema);
+        let params = WriteParams {
+            max_rows_per_file: 500,
+            use_legacy_format: true,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(reader, uri, Some(params)).await.unwrap();
+        dataset.delete("i % 10 = 0").await.unwrap();
+        let expected = dataset.scan().try_into_batch().await.unwrap();
+
+        let report = dataset.format_report();
+        assert_eq!(
+            report.file_versions,
+            BTreeMap::from([(FileFormatVersion::Legacy, 2)])
+        );
+        assert_eq!(
+            report.reader_features,
+            feature_flag_names(FLAG_DELETION_FILES)
+        );
+        assert!(report.can_read);
+        assert!(report.can_write);
+
+        assert!(dataset
+            .migrate_format(FileFormatVersion::Unknown { major: 9, minor: 9 })
+            .await
+            .is_err());
+
+        dataset.migrate_format(FileFormatVersion::V2).await.unwrap();
+        assert_eq!(dataset.version().version, 3);
+        let report = dataset.format_report();
+        assert_eq!(
+            report.file_versions,
+            BTreeMap::from([(FileFormatVersion::V2, 2)])
+        );
+        assert_eq!(dataset.count_deleted_rows().await.unwrap(), 100);
+        let actual = dataset.scan().try_into_batch().await.unwrap();
+        assert_eq!(actual, expected);
+        dataset.validate().await.unwrap();
+
+        // Nothing to do once all the files are migrated
+        dataset.migrate_format(FileFormatVersion::V2).await.unwrap();
+        assert_eq!(dataset.version().version, 3);
+
+        let dataset = Dataset::open(uri).await.unwrap();
+        assert_eq!(
+            dataset.format_report().file_versions,
+            BTreeMap::from([(FileFormatVersion::V2, 2)])
+        );
+    }
+}
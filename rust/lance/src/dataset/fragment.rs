@@ -12,14 +12,18 @@ use std::sync::Arc;
 
 use arrow::compute::concat_batches;
 use arrow_array::cast::as_primitive_array;
-use arrow_array::{RecordBatch, RecordBatchReader, UInt32Array, UInt64Array};
-use arrow_schema::Schema as ArrowSchema;
+use arrow_array::{
+    new_null_array, ArrayRef, RecordBatch, RecordBatchOptions, RecordBatchReader, UInt32Array,
+    UInt64Array,
+};
+use arrow_schema::{DataType, Schema as ArrowSchema};
 use datafusion::logical_expr::Expr;
 use datafusion::scalar::ScalarValue;
 use futures::future::try_join_all;
 use futures::{join, stream, FutureExt, StreamExt, TryFutureExt, TryStreamExt};
 use lance_core::utils::address::RowAddress;
 use lance_core::utils::deletion::DeletionVector;
+use lance_core::utils::tokio::DecodeLimit;
 use lance_core::ROW_ID_FIELD;
 use lance_core::{datatypes::Schema, Error, Result, ROW_ID};
 use lance_file::reader::{read_batch, FileReader};
@@ -86,6 +90,11 @@ pub trait GenericFileReader: std::fmt::Debug + Send + Sync {
     /// Return the number of rows in the file
     fn len(&self) -> u32;
 
+    /// Bound how many batches of this reader are decoded at the same time.
+    ///
+    /// Readers that decode while they read bound their reads as well.
+    fn set_decode_limit(&mut self, _decode_limit: DecodeLimit) {}
+
     // Helper functions to fallback to the legacy implementation while we
     // slowly migrate functionality over to the generic reader
 
@@ -121,6 +130,9 @@ fn ranges_to_tasks(
             let reader = reader.clone();
             let projection = projection.clone();
             let task = tokio::task::spawn(async move {
+                // The legacy format decodes while it reads, so the whole read
+                // counts against the limit.
+                let _permit = reader.decode_limit().reserve().await;
                 read_batch(
                     &reader,
                     &ReadBatchParams::Range(range.clone()),
@@ -218,6 +230,10 @@ impl GenericFileReader for FileReader {
         self.len() as u32
     }
 
+    fn set_decode_limit(&mut self, decode_limit: DecodeLimit) {
+        self.with_decode_limit(decode_limit);
+    }
+
     fn clone_box(&self) -> Box<dyn GenericFileReader> {
         Box::new(self.clone())
     }
@@ -236,6 +252,10 @@ impl GenericFileReader for FileReader {
 }
 
 mod v2_adapter {
+    use futures::future::BoxFuture;
+    use lance_arrow::RecordBatchExt;
+    use lance_core::datatypes::Field;
+
     use super::*;
 
     #[derive(Debug, Clone)]
@@ -255,8 +275,31 @@ mod v2_adapter {
             }
         }
 
-        pub fn projection_from_lance(&self, schema: &Schema) -> ReaderProjection {
-            let arrow_schema = Arc::new(ArrowSchema::from(schema));
+        /// The projection to read `schema` with, and the schema to project the
+        /// batches read to, if it is not the same.
+        ///
+        /// The columns of a struct are read together, so structs are read
+        /// with all the fields stored in the file. The fields that are not
+        /// part of `schema` are projected away after reading.
+        pub fn projection_from_lance(
+            &self,
+            schema: &Schema,
+        ) -> (ReaderProjection, Option<Arc<ArrowSchema>>) {
+            let file_schema = self.reader.metadata().file_schema.as_ref();
+            let read_schema = Schema {
+                fields: schema
+                    .fields
+                    .iter()
+                    .map(|field| match file_schema.field_by_id(field.id) {
+                        Some(stored_field) => read_field(field, stored_field),
+                        None => field.clone(),
+                    })
+                    .collect(),
+                metadata: schema.metadata.clone(),
+            };
+            let output_schema =
+                (read_schema != *schema).then(|| Arc::new(ArrowSchema::from(schema)));
+            let arrow_schema = Arc::new(ArrowSchema::from(&read_schema));
             let column_indices = schema
                 .fields
                 .iter()
@@ -269,13 +312,61 @@ mod v2_adapter {
                     })
                 })
                 .collect::<Vec<_>>();
-            ReaderProjection {
-                schema: arrow_schema,
-                column_indices,
-            }
+            (
+                ReaderProjection {
+                    schema: arrow_schema,
+                    column_indices,
+                },
+                output_schema,
+            )
         }
     }
 
+    /// The field to read for `field`, which is stored as `stored_field`.
+    fn read_field(field: &Field, stored_field: &Field) -> Field {
+        if !matches!(field.data_type(), DataType::Struct(_)) {
+            return field.clone();
+        }
+        let children = stored_field
+            .children
+            .iter()
+            .map(
+                |stored_child| match field.children.iter().find(|c| c.id == stored_child.id) {
+                    Some(child) => read_field(child, stored_child),
+                    None => Field {
+                        name: format!("__unprojected_{}", stored_child.id),
+                        ..stored_child.clone()
+                    },
+                },
+            )
+            .collect();
+        Field {
+            children,
+            ..field.clone()
+        }
+    }
+
+    fn to_read_batch_task(
+        task: BoxFuture<'static, Result<RecordBatch>>,
+        num_rows: u32,
+        output_schema: Option<Arc<ArrowSchema>>,
+    ) -> ReadBatchTask {
+        let task = task.map_err(Error::from);
+        let task = match output_schema {
+            Some(output_schema) => task
+                .and_then(move |batch| {
+                    std::future::ready(
+                        batch
+                            .project_by_schema(output_schema.as_ref())
+                            .map_err(Error::from),
+                    )
+                })
+                .boxed(),
+            None => task.boxed(),
+        };
+        ReadBatchTask { task, num_rows }
+    }
+
     #[async_trait::async_trait]
     impl GenericFileReader for Reader {
         /// Reads the requested range of rows from the file, returning as a stream
@@ -285,7 +376,7 @@ mod v2_adapter {
             batch_size: u32,
             projection: Arc<Schema>,
         ) -> Result<ReadBatchTaskStream> {
-            let projection = self.projection_from_lance(projection.as_ref());
+            let (projection, output_schema) = self.projection_from_lance(projection.as_ref());
             Ok(self
                 .reader
                 .read_tasks(
@@ -293,9 +384,8 @@ mod v2_adapter {
                     batch_size,
                     &projection,
                 )?
-                .map(|v2_task| ReadBatchTask {
-                    task: v2_task.task.map_err(Error::from).boxed(),
-                    num_rows: v2_task.num_rows,
+                .map(move |v2_task| {
+                    to_read_batch_task(v2_task.task, v2_task.num_rows, output_schema.clone())
                 })
                 .boxed())
         }
@@ -305,13 +395,12 @@ mod v2_adapter {
             batch_size: u32,
             projection: Arc<Schema>,
         ) -> Result<ReadBatchTaskStream> {
-            let projection = self.projection_from_lance(projection.as_ref());
+            let (projection, output_schema) = self.projection_from_lance(projection.as_ref());
             Ok(self
                 .reader
                 .read_tasks(ReadBatchParams::RangeFull, batch_size, &projection)?
-                .map(|v2_task| ReadBatchTask {
-                    task: v2_task.task.map_err(Error::from).boxed(),
-                    num_rows: v2_task.num_rows,
+                .map(move |v2_task| {
+                    to_read_batch_task(v2_task.task, v2_task.num_rows, output_schema.clone())
                 })
                 .boxed())
         }
@@ -323,13 +412,12 @@ mod v2_adapter {
             projection: Arc<Schema>,
         ) -> Result<ReadBatchTaskStream> {
             let indices = UInt32Array::from(indices.to_vec());
-            let projection = self.projection_from_lance(projection.as_ref());
+            let (projection, output_schema) = self.projection_from_lance(projection.as_ref());
             Ok(self
                 .reader
                 .read_tasks(ReadBatchParams::Indices(indices), batch_size, &projection)?
-                .map(|v2_task| ReadBatchTask {
-                    task: v2_task.task.map_err(Error::from).boxed(),
-                    num_rows: v2_task.num_rows,
+                .map(move |v2_task| {
+                    to_read_batch_task(v2_task.task, v2_task.num_rows, output_schema.clone())
                 })
                 .boxed())
         }
@@ -339,6 +427,10 @@ mod v2_adapter {
             self.reader.metadata().num_rows as u32
         }
 
+        fn set_decode_limit(&mut self, decode_limit: DecodeLimit) {
+            self.reader = Arc::new(self.reader.as_ref().clone().with_decode_limit(decode_limit));
+        }
+
         fn clone_box(&self) -> Box<dyn GenericFileReader> {
             Box::new(self.clone())
         }
@@ -430,9 +522,20 @@ impl FileFragment {
         let deletion_vec_load =
             self.load_deletion_vector(&self.dataset.object_store, &self.metadata);
         let (opened_files, deletion_vec) = join!(open_files, deletion_vec_load);
-        let opened_files = opened_files?;
+        let mut opened_files = opened_files?;
         let deletion_vec = deletion_vec?;
 
+        // None of the projected columns were written to this fragment, so they
+        // are all null.  Keep a reader of the first data file, without any of
+        // its columns, for the number of rows.
+        if opened_files.is_empty() && !projection.fields.is_empty() {
+            if let Some(data_file) = self.metadata.files.first() {
+                if let Some((reader, _)) = self.open_reader(data_file, None, false).await? {
+                    opened_files.push((reader, Arc::new(Schema::default())));
+                }
+            }
+        }
+
         if opened_files.is_empty() {
             return Err(Error::io(
                 format!(
@@ -502,7 +605,10 @@ impl FileFragment {
             Ok(None)
         } else {
             let path = self.dataset.data_dir().child(data_file.path.as_str());
-            let store_scheduler = ScanScheduler::new(self.dataset.object_store.clone(), 16);
+            let store_scheduler = ScanScheduler::new(
+                self.dataset.object_store.clone(),
+                self.dataset.session.config().io_parallelism(),
+            );
             let file_scheduler = store_scheduler.open_file(&path).await?;
             let reader = Arc::new(v2::reader::FileReader::try_open(file_scheduler, None).await?);
             let field_id_to_column_idx = Arc::new(BTreeMap::from_iter(
@@ -644,16 +750,23 @@ impl FileFragment {
     /// Validate the fragment
     ///
     /// Verifies:
-    /// * All field ids in the fragment are distinct
+    /// * All field ids in the fragment are distinct, except those of structs,
+    ///   whose fields can be in different data files
     /// * Within each data file, field ids are in increasing order
-    /// * All fields in the schema have a corresponding field in one of the data
-    ///  files
+    /// * All non-nullable fields in the schema have a corresponding field in one
+    ///   of the data files
     /// * All data files exist and have the same length
     /// * Field ids are distinct between data files.
     /// * Deletion file exists and has rowids in the correct range
     /// * `Fragment.physical_rows` matches length of file
     /// * `DeletionFile.num_deleted_rows` matches length of deletion vector
     pub async fn validate(&self) -> Result<()> {
+        let struct_ids = self
+            .schema()
+            .fields_pre_order()
+            .filter(|f| matches!(f.data_type(), DataType::Struct(_)))
+            .map(|f| f.id)
+            .collect::<HashSet<_>>();
         let mut seen_fields = HashSet::new();
         for data_file in &self.metadata.files {
             let last = -1;
@@ -671,7 +784,7 @@ impl FileFragment {
                     ));
                 }
 
-                if !seen_fields.insert(field_id) {
+                if !seen_fields.insert(field_id) && !struct_ids.contains(field_id) {
                     return Err(Error::corrupt_file(
                         self.dataset
                             .data_dir()
@@ -699,7 +812,9 @@ impl FileFragment {
         }
 
         for field in self.schema().fields_pre_order() {
-            if !seen_fields.contains(&field.id) {
+            // Nullable columns can be missing from fragments appended before
+            // they were written
+            if !seen_fields.contains(&field.id) && !field.nullable {
                 return Err(Error::corrupt_file(
                     self.dataset
                         .data_dir()
@@ -1083,8 +1198,14 @@ impl FileFragment {
         .await?
         .unwrap_or_default();
 
+        let starting_length = deletion_vector.len();
         deletion_vector.extend(new_deletions);
 
+        // If none of the rows are newly deleted, the fragment is unchanged.
+        if deletion_vector.len() == starting_length {
+            return Ok(Some(self));
+        }
+
         self.write_deletions(deletion_vector).await
     }
 
@@ -1188,6 +1309,39 @@ impl std::fmt::Display for FragmentReader {
     }
 }
 
+/// Project `batch` to `schema`, with null columns for the top-level fields of
+/// `schema` that are not in `batch`.
+///
+/// Fragments appended before a column was written have no data for it, see
+/// [`WriteParams::allow_missing_columns`].
+fn project_with_nulls(batch: &RecordBatch, schema: &ArrowSchema) -> Result<RecordBatch> {
+    let batch_schema = batch.schema();
+    let (present, missing): (Vec<_>, Vec<_>) = schema
+        .fields()
+        .iter()
+        .partition(|field| batch_schema.field_with_name(field.name()).is_ok());
+    if missing.is_empty() {
+        return Ok(batch.project_by_schema(schema)?);
+    }
+    let projected = batch.project_by_schema(&ArrowSchema::new(
+        present.into_iter().cloned().collect::<Vec<_>>(),
+    ))?;
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| match projected.column_by_name(field.name()) {
+            Some(column) => column.clone(),
+            None => new_null_array(field.data_type(), batch.num_rows()),
+        })
+        .collect();
+    let options = RecordBatchOptions::new().with_row_count(Some(batch.num_rows()));
+    Ok(RecordBatch::try_new_with_options(
+        Arc::new(schema.clone()),
+        columns,
+        &options,
+    )?)
+}
+
 fn merge_batches(batches: &[RecordBatch]) -> Result<RecordBatch> {
     if batches.is_empty() {
         return Err(Error::io(
@@ -1270,6 +1424,15 @@ impl FragmentReader {
         self
     }
 
+    /// Bound how many batches are decoded at the same time, across all the
+    /// data files of the fragment.
+    pub(crate) fn with_decode_limit(&mut self, decode_limit: DecodeLimit) -> &mut Self {
+        for (reader, _) in self.readers.iter_mut() {
+            reader.set_decode_limit(decode_limit.clone());
+        }
+        self
+    }
+
     /// TODO: This method is relied upon by the v1 pushdown mechanism and will need to stay
     /// in place until v1 is removed.  v2 uses a different mechanism for pushdown and so there
     /// is little benefit in updating the v1 pushdown node.
@@ -1346,7 +1509,7 @@ impl FragmentReader {
             .map(|(reader, schema)| read_fn(reader.as_ref(), schema))
             .collect::<Vec<_>>();
         let batches = try_join_all(futures).await?;
-        Ok(merge_batches(&batches)?.project_by_schema(&self.output_schema)?)
+        project_with_nulls(&merge_batches(&batches)?, &self.output_schema)
     }
 
     #[cfg(test)]
@@ -1426,7 +1589,7 @@ impl FragmentReader {
             output_schema
         };
 
-        let result = merge_batches(&batches)?.project_by_schema(&output_schema)?;
+        let result = project_with_nulls(&merge_batches(&batches)?, &output_schema)?;
 
         Ok(result)
     }
@@ -1450,8 +1613,9 @@ impl FragmentReader {
                 location!(),
             ));
         }
-        // If just the row id there is no need to actually read any data
-        // and we don't need to involve the readers at all.
+        // If just the row id, or only columns that were not written to this
+        // fragment, there is no need to actually read any data and we don't
+        // need to involve the readers at all.
         //
         // TODO: This is somewhat redundant at the moment.  The `wrap_with_row_id_and_delete`
         // function can handle empty (zero column) batches.  However, the v1 reader will
@@ -1459,7 +1623,11 @@ impl FragmentReader {
         //
         // We could potentially delete the support for no-columns in the wrap function or
         // we can delete this path once we migrate away from any support of v1.
-        if self.with_row_id && self.output_schema.fields.len() == 1 {
+        if self
+            .readers
+            .iter()
+            .all(|(_, schema)| schema.fields.is_empty())
+        {
             let mut offsets = params
                 .slice(0, total_num_rows as usize)
                 .unwrap()
@@ -1484,13 +1652,26 @@ impl FragmentReader {
                 .collect();
             let num_intact_rows = row_ids.len() as u32;
             let row_ids_array = UInt64Array::from(row_ids);
-            let row_id_schema = Arc::new(self.output_schema.clone());
+            let output_schema = Arc::new(self.output_schema.clone());
             let tasks = (0..num_intact_rows)
                 .step_by(batch_size as usize)
                 .map(move |offset| {
                     let length = batch_size.min(num_intact_rows - offset);
-                    let array = Arc::new(row_ids_array.slice(offset as usize, length as usize));
-                    let batch = RecordBatch::try_new(row_id_schema.clone(), vec![array]);
+                    let columns = output_schema
+                        .fields()
+                        .iter()
+                        .map(|field| {
+                            if field.name() == ROW_ID {
+                                Arc::new(row_ids_array.slice(offset as usize, length as usize))
+                                    as ArrayRef
+                            } else {
+                                new_null_array(field.data_type(), length as usize)
+                            }
+                        })
+                        .collect();
+                    let options = RecordBatchOptions::new().with_row_count(Some(length as usize));
+                    let batch =
+                        RecordBatch::try_new_with_options(output_schema.clone(), columns, &options);
                     std::future::ready(batch.map_err(Error::from)).boxed()
                 });
             return Ok(stream::iter(tasks).boxed());
@@ -1532,11 +1713,7 @@ impl FragmentReader {
                 .map(move |batch_fut| {
                     let output_schema = output_schema.clone();
                     batch_fut
-                        .map(move |batch| {
-                            batch?
-                                .project_by_schema(&output_schema)
-                                .map_err(Error::from)
-                        })
+                        .map(move |batch| project_with_nulls(&batch?, &output_schema))
                         .boxed()
                 })
                 .boxed(),
@@ -1792,6 +1969,33 @@ mod tests {
         }
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_decode_limit(#[values(false, true)] use_legacy_format: bool) {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = create_dataset(test_uri, use_legacy_format).await;
+        let fragment = &dataset.get_fragments()[0];
+        let mut reader = fragment.open(fragment.schema(), false).await.unwrap();
+        let decode_limit = DecodeLimit::new(1);
+        reader.with_decode_limit(decode_limit.clone());
+
+        // Nothing can be read while the only thread of the limit is taken
+        let permit = decode_limit.reserve().await;
+        let mut read = reader
+            .read_all(10)
+            .unwrap()
+            .buffered(4)
+            .try_collect::<Vec<_>>()
+            .boxed();
+        let timeout = std::time::Duration::from_millis(200);
+        assert!(tokio::time::timeout(timeout, &mut read).await.is_err());
+
+        drop(permit);
+        let batches = read.await.unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 40);
+    }
+
     #[tokio::test]
     async fn test_fragment_scan_deletions() {
         let test_dir = tempdir().unwrap();
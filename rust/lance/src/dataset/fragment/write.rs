@@ -2,12 +2,14 @@
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
 use std::borrow::Cow;
+use std::sync::Arc;
 
-use arrow_array::RecordBatchReader;
+use arrow_array::{RecordBatch, RecordBatchReader};
 use arrow_schema::Schema as ArrowSchema;
 use datafusion::execution::SendableRecordBatchStream;
 use futures::StreamExt;
 use lance_core::datatypes::Schema;
+use lance_core::sink::AsyncBatchSink;
 use lance_core::Error;
 use lance_datafusion::chunker::chunk_stream;
 use lance_datafusion::utils::{peek_reader_schema, reader_to_stream};
@@ -19,6 +21,8 @@ use snafu::{location, Location};
 use uuid::Uuid;
 
 use crate::dataset::builder::DatasetBuilder;
+use crate::dataset::progress::WriteFragmentProgress;
+use crate::dataset::write::{open_writer, GenericWriter};
 use crate::dataset::{WriteMode, WriteParams, DATA_DIR};
 use crate::Result;
 
@@ -67,6 +71,50 @@ impl<'a> FragmentCreateBuilder<'a> {
         self.write_impl(stream, schema, id).await
     }
 
+    /// Open a writer to write a fragment one batch at a time.
+    ///
+    /// The schema must be set with [`Self::schema`], unless the write mode is
+    /// append and the dataset exists, in which case the dataset's schema is used.
+    pub async fn open_writer(&self, id: Option<u64>) -> Result<FragmentWriter> {
+        let schema = match self.schema {
+            Some(schema) => schema.clone(),
+            None if matches!(self.write_params.map(|p| p.mode), Some(WriteMode::Append)) => {
+                self.existing_dataset_schema().await?.ok_or_else(|| {
+                    Error::invalid_input(
+                        "The schema must be set to open a fragment writer",
+                        location!(),
+                    )
+                })?
+            }
+            None => {
+                return Err(Error::invalid_input(
+                    "The schema must be set to open a fragment writer",
+                    location!(),
+                ))
+            }
+        };
+        let params = self.write_params.cloned().unwrap_or_default();
+
+        let (object_store, base_path) = ObjectStore::from_uri(self.dataset_uri).await?;
+        let writer =
+            open_writer(&object_store, &schema, &base_path, params.use_legacy_format).await?;
+        let fragment = Fragment::new(id.unwrap_or_default());
+        params
+            .progress
+            .begin(&fragment, writer.multipart_id())
+            .await?;
+
+        Ok(FragmentWriter {
+            writer,
+            schema,
+            fragment,
+            progress: params.progress,
+            max_rows_per_group: params.max_rows_per_group,
+            buffered: Vec::new(),
+            num_rows: 0,
+        })
+    }
+
     async fn write_impl(
         &self,
         stream: SendableRecordBatchStream,
@@ -161,6 +209,67 @@ impl<'a> FragmentCreateBuilder<'a> {
     }
 }
 
+/// Writes a new fragment one batch at a time.
+///
+/// Created with [`FragmentCreateBuilder::open_writer`].  Batches are buffered
+/// until there is a full row group (`max_rows_per_group` rows) to write.
+/// Finishing the writer returns the fragment, which still has to be committed
+/// to the dataset.
+pub struct FragmentWriter {
+    writer: Box<dyn GenericWriter>,
+    schema: Schema,
+    fragment: Fragment,
+    progress: Arc<dyn WriteFragmentProgress>,
+    max_rows_per_group: usize,
+    buffered: Vec<RecordBatch>,
+    num_rows: usize,
+}
+
+impl FragmentWriter {
+    fn buffered_rows(&self) -> usize {
+        self.buffered.iter().map(|batch| batch.num_rows()).sum()
+    }
+}
+
+#[async_trait::async_trait]
+impl AsyncBatchSink for FragmentWriter {
+    /// The written fragment
+    type Output = Fragment;
+
+    async fn write_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        FragmentCreateBuilder::validate_schema(&self.schema, batch.schema().as_ref())?;
+        self.buffered.push(batch.clone());
+        if self.buffered_rows() >= self.max_rows_per_group {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if !self.buffered.is_empty() {
+            self.num_rows += self.buffered_rows();
+            self.writer.write(&self.buffered).await?;
+            self.buffered.clear();
+        }
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<Fragment> {
+        self.flush().await?;
+        if self.num_rows == 0 {
+            return Err(Error::invalid_input("Input data was empty.", location!()));
+        }
+
+        let (num_rows, data_file) = self.writer.finish().await?;
+        self.fragment.files.push(data_file);
+        self.fragment.physical_rows = Some(num_rows as usize);
+
+        self.progress.complete(&self.fragment).await?;
+
+        Ok(self.fragment.clone())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
@@ -275,4 +384,25 @@ mod tests {
         assert_eq!(fragment.files.len(), 1);
         assert_eq!(fragment.files[0].fields, vec![1, 3]);
     }
+
+    #[tokio::test]
+    async fn test_fragment_writer() {
+        let data = test_data();
+        let schema = Schema::try_from(data.schema().as_ref()).unwrap();
+        let batch = data.collect::<Vec<_>>().pop().unwrap().unwrap();
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let builder = FragmentCreateBuilder::new(tmp_dir.path().to_str().unwrap());
+        assert!(builder.open_writer(None).await.is_err());
+
+        let mut writer = builder.schema(&schema).open_writer(Some(7)).await.unwrap();
+        writer.write_batch(&batch).await.unwrap();
+        writer.write_batch(&batch.slice(0, 1)).await.unwrap();
+        let fragment = writer.finish().await.unwrap();
+
+        assert_eq!(fragment.id, 7);
+        assert_eq!(fragment.physical_rows, Some(4));
+        assert_eq!(fragment.files.len(), 1);
+        assert_eq!(fragment.files[0].fields, vec![0, 1]);
+    }
 }
@@ -80,10 +80,17 @@
 //! the successful tasks can be committed. You can also commit in batches if
 //! you wish. As long as the tasks don't rewrite any of the same fragments,
 //! they can be committed in any order.
+//!
+//! ## Maintenance advisor
+//!
+//! To decide which maintenance operations are worth running, use
+//! [advisor::advise()]. It produces a [advisor::MaintenancePlan] with the
+//! recommended operations and their estimated benefits.
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::ops::{AddAssign, Range};
 use std::sync::{Arc, RwLock};
+use std::time::Instant;
 
 use async_trait::async_trait;
 use datafusion::physical_plan::SendableRecordBatchStream;
@@ -105,6 +112,8 @@ use super::transaction::{Operation, RewriteGroup, RewrittenIndex, Transaction};
 use super::utils::make_rowid_capture_stream;
 use super::{write_fragments_internal, WriteMode, WriteParams};
 
+pub mod advisor;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct RemappedIndex {
     original: Uuid,
@@ -139,6 +148,10 @@ pub trait IndexRemapperOptions: Send + Sync {
 }
 
 /// Options to be passed to [compact_files].
+///
+/// The options configured for a dataset are given by
+/// [`DatasetConfig::compaction_options`](crate::dataset::config::DatasetConfig::compaction_options),
+/// which explicit options can be set on top of.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CompactionOptions {
     /// Target number of rows per file. Defaults to 1 million.
@@ -245,23 +258,150 @@ pub async fn compact_files(
     remap_options: Option<Arc<dyn IndexRemapperOptions>>,
 ) -> Result<CompactionMetrics> {
     options.validate();
+    let started = Instant::now();
 
     let compaction_plan: CompactionPlan = plan_compaction(dataset, &options).await?;
 
+    rewrite_and_commit(
+        dataset,
+        compaction_plan.tasks,
+        &options,
+        remap_options,
+        started,
+    )
+    .await
+}
+
+/// Merges the delta fragments of the dataset, written by small appends with
+/// [`WriteParams::delta_log`], into larger fragments.
+///
+/// Runs of adjacent delta fragments are merged into fragments of up to about
+/// `target_rows_per_fragment` rows. A delta fragment without delta neighbors
+/// is merged into a neighboring fragment below that size instead. Appends
+/// merge them once there are enough, but they can be merged at any time, such
+/// as periodically.
+///
+/// If there is no run of delta fragments to merge, this method will not make
+/// a new version of the table.
+pub async fn merge_delta_fragments(
+    dataset: &mut Dataset,
+    mut options: CompactionOptions,
+    remap_options: Option<Arc<dyn IndexRemapperOptions>>,
+) -> Result<CompactionMetrics> {
+    options.validate();
+    let started = Instant::now();
+
+    let index_fragmaps = load_index_fragmaps(dataset).await?;
+    let indices_containing_frag = |frag_id: u64| {
+        index_fragmaps
+            .iter()
+            .enumerate()
+            .filter(|(_, bitmap)| bitmap.contains(frag_id as u32))
+            .map(|(pos, _)| pos)
+            .collect::<Vec<_>>()
+    };
+
+    let tasks = plan_delta_runs(
+        &dataset.manifest.fragments,
+        options.target_rows_per_fragment,
+        |left, right| indices_containing_frag(left.id) == indices_containing_frag(right.id),
+    )
+    .into_iter()
+    .map(|fragments| TaskData { fragments })
+    .collect();
+    rewrite_and_commit(dataset, tasks, &options, remap_options, started).await
+}
+
+/// The runs of adjacent fragments that merging the delta fragments rewrites.
+///
+/// Like in compaction, runs don't mix fragments of different partitions or of
+/// fragments that `same_indices` tells apart, and are split when they reach
+/// `target_rows`. A lone delta fragment is paired with a neighboring fragment
+/// below `target_rows`, so that it isn't left behind by the fragments merged
+/// around it.
+pub(super) fn plan_delta_runs(
+    fragments: &[Fragment],
+    target_rows: usize,
+    same_indices: impl Fn(&Fragment, &Fragment) -> bool,
+) -> Vec<Vec<Fragment>> {
+    let compatible = |a: &Fragment, b: &Fragment| a.partition == b.partition && same_indices(a, b);
+    let num_rows = |fragment: &Fragment| fragment.num_rows().unwrap_or_default();
+
+    // Positions of the runs of delta fragments
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    let mut current_rows = 0;
+    for (pos, fragment) in fragments.iter().enumerate() {
+        if !fragment.delta {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run)
+                if run.end == pos
+                    && compatible(&fragments[run.start], fragment)
+                    && current_rows < target_rows =>
+            {
+                run.end += 1;
+            }
+            _ => {
+                runs.push(pos..pos + 1);
+                current_rows = 0;
+            }
+        }
+        current_rows += num_rows(fragment);
+    }
+
+    let mut paired = vec![false; fragments.len()];
+    for run in runs.iter_mut().filter(|run| run.len() == 1) {
+        let delta = &fragments[run.start];
+        let can_pair = |pos: usize| {
+            let neighbor = &fragments[pos];
+            !neighbor.delta
+                && !paired[pos]
+                && num_rows(neighbor) < target_rows
+                && compatible(neighbor, delta)
+        };
+        if run.start > 0 && can_pair(run.start - 1) {
+            run.start -= 1;
+            paired[run.start] = true;
+        } else if run.end < fragments.len() && can_pair(run.end) {
+            paired[run.end] = true;
+            run.end += 1;
+        }
+    }
+
+    runs.into_iter()
+        .filter(|run| run.len() > 1)
+        .map(|run| fragments[run].to_vec())
+        .collect()
+}
+
+/// Rewrite the fragments of each task, and commit them.
+async fn rewrite_and_commit(
+    dataset: &mut Dataset,
+    tasks: Vec<TaskData>,
+    options: &CompactionOptions,
+    remap_options: Option<Arc<dyn IndexRemapperOptions>>,
+    started: Instant,
+) -> Result<CompactionMetrics> {
     // If nothing to compact, don't make a commit.
-    if compaction_plan.tasks().is_empty() {
+    if tasks.is_empty() {
         return Ok(CompactionMetrics::default());
     }
 
     let dataset_ref = &dataset.clone();
 
-    let result_stream = futures::stream::iter(compaction_plan.tasks.into_iter())
-        .map(|task| rewrite_files(Cow::Borrowed(dataset_ref), task, &options))
+    let result_stream = futures::stream::iter(tasks)
+        .map(|task| rewrite_files(Cow::Borrowed(dataset_ref), task, options))
         .buffer_unordered(options.num_threads);
 
     let completed_tasks: Vec<RewriteResult> = result_stream.try_collect().await?;
     let remap_options = remap_options.unwrap_or(Arc::new(DatasetIndexRemapperOptions::default()));
     let metrics = commit_compaction(dataset, completed_tasks, remap_options).await?;
+    crate::metrics::record_compaction(
+        started.elapsed(),
+        metrics.fragments_removed,
+        metrics.fragments_added,
+    );
 
     Ok(metrics)
 }
@@ -390,6 +530,7 @@ impl CompactionPlan {
         self.tasks.extend(tasks);
     }
 
+    #[cfg(test)]
     fn tasks(&self) -> &[TaskData] {
         &self.tasks
     }
@@ -552,8 +693,9 @@ pub async fn plan_compaction(
             }
             (Some(candidacy), Some(bin)) => {
                 // We cannot mix "indexed" and "non-indexed" fragments and so we only consider
-                // the existing bin if it contains the same indices
-                if bin.indices == indices {
+                // the existing bin if it contains the same indices. Fragments of different
+                // partitions are not mixed either, so the new fragments keep their partition.
+                if bin.indices == indices && bin.fragments[0].partition == fragment.partition {
                     // Add to current bin
                     bin.fragments.push(fragment);
                     bin.pos_range.end += 1;
@@ -721,7 +863,10 @@ fn transpose_row_ids(
     mapping
 }
 
-async fn reserve_fragment_ids(dataset: &Dataset, fragments: &mut [Fragment]) -> Result<()> {
+pub(super) async fn reserve_fragment_ids(
+    dataset: &Dataset,
+    fragments: &mut [Fragment],
+) -> Result<()> {
     let transaction = Transaction::new(
         dataset.manifest.version,
         Operation::ReserveFragments {
@@ -813,6 +958,13 @@ async fn rewrite_files(
         .into_inner()
         .expect("Row ids mutex still locked");
 
+    let partition = task.fragments[0].partition;
+    if task.fragments.iter().all(|f| f.partition == partition) {
+        for fragment in new_fragments.iter_mut() {
+            fragment.partition = partition;
+        }
+    }
+
     reserve_fragment_ids(&dataset, &mut new_fragments).await?;
 
     let row_id_map: HashMap<u64, Option<u64>> =
@@ -919,6 +1071,7 @@ mod tests {
     use tempfile::tempdir;
 
     use super::*;
+    use crate::dataset::DeltaLogParams;
 
     #[test]
     fn test_missing_indices() {
@@ -931,6 +1084,8 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: Some(5),
+                partition: None,
+                delta: false,
             },
             Fragment {
                 id: 3,
@@ -938,6 +1093,8 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: Some(3),
+                partition: None,
+                delta: false,
             },
         ];
         let rows = [(0, 1), (0, 3), (0, 4), (3, 0), (3, 2)]
@@ -1064,6 +1221,8 @@ mod tests {
             deletion_file: None,
             row_id_meta: None,
             physical_rows: Some(0),
+            partition: None,
+            delta: false,
         };
         let single_bin = CandidateBin {
             fragments: vec![fragment.clone()],
@@ -1558,6 +1717,86 @@ mod tests {
         assert_eq!(dataset.manifest.version, 6);
     }
 
+    #[tokio::test]
+    async fn test_merge_delta_fragments() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let data = sample_data();
+        let reader = RecordBatchIterator::new(vec![Ok(data.slice(0, 100))], data.schema());
+        let mut dataset = Dataset::write(reader, test_uri, None).await.unwrap();
+
+        let params = WriteParams {
+            delta_log: Some(DeltaLogParams {
+                max_delta_rows: 10,
+                merge_threshold: 3,
+            }),
+            ..Default::default()
+        };
+        let rows = |range: Range<usize>| {
+            RecordBatchIterator::new(
+                vec![Ok(data.slice(range.start, range.len()))],
+                data.schema(),
+            )
+        };
+        let is_delta = |dataset: &Dataset| {
+            dataset
+                .get_fragments()
+                .iter()
+                .map(|f| f.metadata().delta)
+                .collect::<Vec<_>>()
+        };
+
+        // Small appends are delta fragments, others are not
+        dataset
+            .append(rows(100..105), Some(params.clone()))
+            .await
+            .unwrap();
+        dataset
+            .append(rows(105..110), Some(params.clone()))
+            .await
+            .unwrap();
+        dataset
+            .append(rows(110..130), Some(params.clone()))
+            .await
+            .unwrap();
+        assert_eq!(is_delta(&dataset), vec![false, true, true, false]);
+
+        // The append reaching the threshold merges the runs of delta fragments,
+        // and the lone delta fragment into the small fragment before it.
+        // Like other compactions, the merged fragments take new ids.
+        dataset
+            .append(rows(130..135), Some(params.clone()))
+            .await
+            .unwrap();
+        assert_eq!(is_delta(&dataset), vec![false, false, false]);
+        let mut physical_rows = dataset
+            .get_fragments()
+            .iter()
+            .map(|f| f.metadata.physical_rows.unwrap())
+            .collect::<Vec<_>>();
+        physical_rows.sort();
+        assert_eq!(physical_rows, vec![10, 25, 100]);
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 135);
+
+        // A lone delta fragment between fragments of the target size isn't merged
+        dataset
+            .append(rows(135..140), Some(params.clone()))
+            .await
+            .unwrap();
+        let version = dataset.manifest.version;
+        let options = CompactionOptions {
+            target_rows_per_fragment: 10,
+            ..Default::default()
+        };
+        let metrics = merge_delta_fragments(&mut dataset, options, None)
+            .await
+            .unwrap();
+        assert_eq!(metrics, CompactionMetrics::default());
+        assert_eq!(dataset.manifest.version, version);
+        assert_eq!(is_delta(&dataset), vec![false, false, false, true]);
+    }
+
     #[test]
     fn test_missing_ids() {
         // test with missing first row
@@ -1571,6 +1810,8 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: Some(5),
+                partition: None,
+                delta: false,
             },
             Fragment {
                 id: 3,
@@ -1578,6 +1819,8 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: Some(3),
+                partition: None,
+                delta: false,
             },
             Fragment {
                 id: 1,
@@ -1585,6 +1828,8 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: Some(3),
+                partition: None,
+                delta: false,
             },
         ];
 
@@ -0,0 +1,485 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Maintenance advisor
+//!
+//! Deciding when to run table maintenance is not obvious. The advisor looks at
+//! the distribution of fragment sizes, the ratio of deleted rows, the coverage
+//! of the indices and, if provided, how many rows are read from each fragment.
+//! From those it recommends maintenance operations, each with an estimate of
+//! what it would gain.
+//!
+//! ```text
+//! advise() ─► MaintenancePlan ─► MaintenancePlan.execute()
+//! ```
+//!
+//! The plan is compiled against a specific version of the dataset. Compaction
+//! and reindexing can be executed directly from the plan. Clustering is only
+//! advisory: Lance cannot rewrite a table sorted by a column yet, so it has to
+//! be done by rewriting the data.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use futures::{StreamExt, TryStreamExt};
+use lance_index::optimize::OptimizeOptions;
+use lance_index::DatasetIndexExt;
+
+use super::{
+    collect_metrics, commit_compaction, plan_compaction, CompactionMetrics, CompactionOptions,
+    CompactionPlan, FragmentMetrics, IndexRemapperOptions,
+};
+use crate::dataset::index::DatasetIndexRemapperOptions;
+use crate::index::DatasetIndexInternalExt;
+use crate::{Dataset, Result};
+
+/// Options to be passed to [advise].
+#[derive(Debug, Clone)]
+pub struct AdvisorOptions {
+    /// Options used to plan compaction. `target_rows_per_fragment` is also the
+    /// size fragments are compared against.
+    pub compaction: CompactionOptions,
+    /// Fragments with fewer rows than this fraction of the target fragment size
+    /// are considered small. Defaults to 0.5.
+    pub small_fragment_fraction: f32,
+    /// The fraction of rows not covered by an index before reindexing is
+    /// recommended. Defaults to 10% (0.1).
+    pub unindexed_rows_threshold: f32,
+    /// The number of rows read from each fragment, keyed by fragment id, over
+    /// some observation window. Leave empty if the query pattern is unknown.
+    pub rows_read_per_fragment: HashMap<u64, u64>,
+    /// Clustering is recommended when queries read, on average, less than this
+    /// fraction of the rows of the fragments they touch. Defaults to 10% (0.1).
+    pub cluster_read_density_threshold: f32,
+}
+
+impl Default for AdvisorOptions {
+    fn default() -> Self {
+        Self {
+            compaction: CompactionOptions::default(),
+            small_fragment_fraction: 0.5,
+            unindexed_rows_threshold: 0.1,
+            rows_read_per_fragment: HashMap::new(),
+            cluster_read_density_threshold: 0.1,
+        }
+    }
+}
+
+/// Summary of the fragment sizes of a dataset.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FragmentSizeDistribution {
+    pub num_fragments: usize,
+    /// Number of rows, excluding deleted rows
+    pub num_rows: usize,
+    pub num_deleted_rows: usize,
+    pub min_rows: usize,
+    pub median_rows: usize,
+    pub max_rows: usize,
+    /// Number of fragments smaller than the small fragment threshold
+    pub num_small_fragments: usize,
+    /// Number of fragments whose deletion ratio exceeds the compaction threshold
+    pub num_fragments_over_deletion_threshold: usize,
+}
+
+/// A maintenance operation recommended by [advise].
+#[derive(Debug, Clone)]
+pub enum MaintenanceAction {
+    /// Merge small fragments and materialize deletions.
+    Compact(CompactionPlan),
+    /// Rewrite the data sorted by the columns that queries filter on, so that
+    /// queries touch fewer fragments. This cannot be executed from the plan.
+    Cluster {
+        /// Fragments that are read sparsely
+        fragment_ids: Vec<u64>,
+    },
+    /// Index the fragments that are not covered by the index yet.
+    Reindex {
+        index_name: String,
+        unindexed_fragment_ids: Vec<u64>,
+    },
+}
+
+/// Estimated gains of a [MaintenanceAction].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EstimatedBenefit {
+    /// Reduction in the number of fragments
+    pub fragments_removed: usize,
+    /// Deleted rows that would be removed from the data files
+    pub deleted_rows_reclaimed: usize,
+    /// Rows that would be added to an index
+    pub rows_indexed: usize,
+    /// Rows that queries would no longer need to scan, over the same
+    /// observation window as [AdvisorOptions::rows_read_per_fragment].
+    pub rows_scanned_saved: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Recommendation {
+    pub action: MaintenanceAction,
+    /// Human readable explanation of why the action is recommended
+    pub reason: String,
+    pub benefit: EstimatedBenefit,
+}
+
+/// Recommendations produced by [advise].
+#[derive(Debug, Clone)]
+pub struct MaintenancePlan {
+    /// The version of the dataset that was analyzed.
+    pub read_version: u64,
+    pub distribution: FragmentSizeDistribution,
+    pub recommendations: Vec<Recommendation>,
+}
+
+/// Metrics returned by [MaintenancePlan::execute].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaintenanceMetrics {
+    pub compaction: CompactionMetrics,
+    /// Whether the indices were optimized
+    pub indices_optimized: bool,
+}
+
+impl MaintenancePlan {
+    /// Whether there is nothing to do.
+    pub fn is_empty(&self) -> bool {
+        self.recommendations.is_empty()
+    }
+
+    /// Execute the compaction and reindex recommendations.
+    ///
+    /// Compaction runs first, since it changes which fragments are indexed.
+    /// Cluster recommendations are skipped.
+    pub async fn execute(&self, dataset: &mut Dataset) -> Result<MaintenanceMetrics> {
+        let mut metrics = MaintenanceMetrics::default();
+        for recommendation in &self.recommendations {
+            if let MaintenanceAction::Compact(plan) = &recommendation.action {
+                let dataset_ref = &dataset.clone();
+                let completed_tasks = futures::stream::iter(plan.compaction_tasks())
+                    .map(|task| async move { task.execute(dataset_ref).await })
+                    .buffer_unordered(plan.options().num_threads)
+                    .try_collect::<Vec<_>>()
+                    .await?;
+                let remap_options: Arc<dyn IndexRemapperOptions> =
+                    Arc::new(DatasetIndexRemapperOptions::default());
+                metrics.compaction +=
+                    commit_compaction(dataset, completed_tasks, remap_options).await?;
+            }
+        }
+
+        let needs_reindex = self
+            .recommendations
+            .iter()
+            .any(|r| matches!(r.action, MaintenanceAction::Reindex { .. }));
+        if needs_reindex {
+            dataset
+                .optimize_indices(&OptimizeOptions::default())
+                .await?;
+            metrics.indices_optimized = true;
+        }
+        Ok(metrics)
+    }
+}
+
+fn distribution(
+    metrics: &HashMap<u64, FragmentMetrics>,
+    options: &AdvisorOptions,
+) -> FragmentSizeDistribution {
+    if metrics.is_empty() {
+        return FragmentSizeDistribution::default();
+    }
+    let small_threshold = (options.compaction.target_rows_per_fragment as f32
+        * options.small_fragment_fraction) as usize;
+
+    let mut rows = metrics.values().map(|m| m.num_rows()).collect::<Vec<_>>();
+    rows.sort_unstable();
+    FragmentSizeDistribution {
+        num_fragments: metrics.len(),
+        num_rows: rows.iter().sum(),
+        num_deleted_rows: metrics.values().map(|m| m.num_deletions).sum(),
+        min_rows: rows[0],
+        median_rows: rows[rows.len() / 2],
+        max_rows: rows[rows.len() - 1],
+        num_small_fragments: rows.iter().filter(|r| **r < small_threshold).count(),
+        num_fragments_over_deletion_threshold: metrics
+            .values()
+            .filter(|m| {
+                m.deletion_percentage() > options.compaction.materialize_deletions_threshold
+            })
+            .count(),
+    }
+}
+
+fn compaction_recommendation(
+    plan: CompactionPlan,
+    metrics: &HashMap<u64, FragmentMetrics>,
+) -> Option<Recommendation> {
+    if plan.num_tasks() == 0 {
+        return None;
+    }
+    let target_rows = plan.options().target_rows_per_fragment.max(1);
+    let mut benefit = EstimatedBenefit::default();
+    for task in plan.compaction_tasks() {
+        let task_metrics = task
+            .task
+            .fragments
+            .iter()
+            .filter_map(|f| metrics.get(&f.id))
+            .collect::<Vec<_>>();
+        let num_rows = task_metrics.iter().map(|m| m.num_rows()).sum::<usize>();
+        let new_fragments = num_rows.div_ceil(target_rows);
+        benefit.fragments_removed += task.task.fragments.len().saturating_sub(new_fragments);
+        benefit.deleted_rows_reclaimed +=
+            task_metrics.iter().map(|m| m.num_deletions).sum::<usize>();
+    }
+    let reason = format!(
+        "{} fragment(s) would be merged into fewer, larger fragments and {} deleted row(s) reclaimed",
+        benefit.fragments_removed, benefit.deleted_rows_reclaimed
+    );
+    Some(Recommendation {
+        action: MaintenanceAction::Compact(plan),
+        reason,
+        benefit,
+    })
+}
+
+fn cluster_recommendation(
+    metrics: &HashMap<u64, FragmentMetrics>,
+    options: &AdvisorOptions,
+) -> Option<Recommendation> {
+    let mut fragment_ids = Vec::new();
+    let mut rows_read = 0;
+    let mut rows_in_read_fragments = 0;
+    for (fragment_id, read) in &options.rows_read_per_fragment {
+        let Some(fragment_metrics) = metrics.get(fragment_id) else {
+            continue;
+        };
+        if *read == 0 {
+            continue;
+        }
+        fragment_ids.push(*fragment_id);
+        rows_read += *read;
+        rows_in_read_fragments += fragment_metrics.num_rows() as u64;
+    }
+    // Clustering can't help if a single fragment is read.
+    if fragment_ids.len() < 2 || rows_in_read_fragments == 0 {
+        return None;
+    }
+    let density = rows_read as f64 / rows_in_read_fragments as f64;
+    if density >= options.cluster_read_density_threshold as f64 {
+        return None;
+    }
+    fragment_ids.sort_unstable();
+
+    // If the rows that are read were stored together, queries would only
+    // need to scan roughly as many rows as they read.
+    let benefit = EstimatedBenefit {
+        rows_scanned_saved: rows_in_read_fragments - rows_read,
+        ..Default::default()
+    };
+    let reason = format!(
+        "queries read {:.1}% of the rows of the {} fragment(s) they touch",
+        density * 100.0,
+        fragment_ids.len()
+    );
+    Some(Recommendation {
+        action: MaintenanceAction::Cluster { fragment_ids },
+        reason,
+        benefit,
+    })
+}
+
+async fn reindex_recommendations(
+    dataset: &Dataset,
+    metrics: &HashMap<u64, FragmentMetrics>,
+    options: &AdvisorOptions,
+) -> Result<Vec<Recommendation>> {
+    let total_rows = metrics.values().map(|m| m.num_rows()).sum::<usize>();
+    if total_rows == 0 {
+        return Ok(Vec::new());
+    }
+
+    let indices = dataset.load_indices().await?;
+    let index_names = indices
+        .iter()
+        .map(|index| index.name.as_str())
+        .collect::<HashSet<_>>();
+    let mut index_names = index_names.into_iter().collect::<Vec<_>>();
+    index_names.sort_unstable();
+
+    let mut recommendations = Vec::new();
+    for index_name in index_names {
+        let unindexed = dataset.unindexed_fragments(index_name).await?;
+        let unindexed_rows = unindexed
+            .iter()
+            .filter_map(|f| metrics.get(&f.id))
+            .map(|m| m.num_rows())
+            .sum::<usize>();
+        if (unindexed_rows as f32 / total_rows as f32) <= options.unindexed_rows_threshold {
+            continue;
+        }
+        recommendations.push(Recommendation {
+            action: MaintenanceAction::Reindex {
+                index_name: index_name.to_string(),
+                unindexed_fragment_ids: unindexed.iter().map(|f| f.id).collect(),
+            },
+            reason: format!(
+                "{} of {} row(s) are not covered by index {}",
+                unindexed_rows, total_rows, index_name
+            ),
+            benefit: EstimatedBenefit {
+                rows_indexed: unindexed_rows,
+                ..Default::default()
+            },
+        });
+    }
+    Ok(recommendations)
+}
+
+/// Analyze the dataset and recommend maintenance operations.
+///
+/// Recommendations are ordered as they should be executed: compaction first,
+/// then clustering and finally reindexing.
+pub async fn advise(dataset: &Dataset, options: &AdvisorOptions) -> Result<MaintenancePlan> {
+    let mut compaction_options = options.compaction.clone();
+    compaction_options.validate();
+
+    let metrics = futures::stream::iter(dataset.get_fragments())
+        .map(|fragment| async move {
+            let metrics = collect_metrics(&fragment).await?;
+            Result::Ok((fragment.id() as u64, metrics))
+        })
+        .buffered(num_cpus::get() * 2)
+        .try_collect::<HashMap<_, _>>()
+        .await?;
+
+    let mut recommendations = Vec::new();
+    let plan = plan_compaction(dataset, &compaction_options).await?;
+    recommendations.extend(compaction_recommendation(plan, &metrics));
+    recommendations.extend(cluster_recommendation(&metrics, options));
+    recommendations.extend(reindex_recommendations(dataset, &metrics, options).await?);
+
+    Ok(MaintenancePlan {
+        read_version: dataset.manifest.version,
+        distribution: distribution(&metrics, options),
+        recommendations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int64Array, RecordBatch, RecordBatchIterator, RecordBatchReader};
+    use arrow_schema::{DataType, Field, Schema};
+    use lance_index::IndexType;
+    use tempfile::tempdir;
+
+    use crate::dataset::WriteParams;
+    use crate::index::scalar::ScalarIndexParams;
+
+    fn make_data(range: std::ops::Range<i64>) -> impl RecordBatchReader + Send + 'static {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int64Array::from_iter_values(range))],
+        )
+        .unwrap();
+        RecordBatchIterator::new(vec![Ok(batch)], schema)
+    }
+
+    #[tokio::test]
+    async fn test_advise_empty_plan() {
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+        let dataset = Dataset::write(make_data(0..1000), uri, None).await.unwrap();
+
+        let options = AdvisorOptions {
+            compaction: CompactionOptions {
+                target_rows_per_fragment: 1000,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let plan = advise(&dataset, &options).await.unwrap();
+        assert!(plan.is_empty());
+        assert_eq!(plan.distribution.num_fragments, 1);
+        assert_eq!(plan.distribution.num_rows, 1000);
+        assert_eq!(plan.distribution.num_small_fragments, 0);
+    }
+
+    #[tokio::test]
+    async fn test_advise_and_execute() {
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 100,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(make_data(0..1000), uri, Some(write_params.clone()))
+            .await
+            .unwrap();
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                Some("i_idx".to_string()),
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        dataset
+            .append(make_data(1000..1500), Some(write_params))
+            .await
+            .unwrap();
+        dataset.delete("i < 50").await.unwrap();
+
+        let options = AdvisorOptions {
+            compaction: CompactionOptions {
+                target_rows_per_fragment: 1000,
+                ..Default::default()
+            },
+            // Each query reads a few rows from every fragment
+            rows_read_per_fragment: (0..15).map(|id| (id, 5)).collect(),
+            ..Default::default()
+        };
+        let plan = advise(&dataset, &options).await.unwrap();
+        assert_eq!(plan.distribution.num_fragments, 15);
+        assert_eq!(plan.distribution.num_rows, 1450);
+        assert_eq!(plan.distribution.num_deleted_rows, 50);
+        assert_eq!(plan.distribution.num_small_fragments, 15);
+        assert_eq!(plan.distribution.num_fragments_over_deletion_threshold, 1);
+
+        assert_eq!(plan.recommendations.len(), 3);
+        let compact = &plan.recommendations[0];
+        assert!(matches!(compact.action, MaintenanceAction::Compact(_)));
+        assert!(compact.benefit.fragments_removed > 0);
+        assert_eq!(compact.benefit.deleted_rows_reclaimed, 50);
+
+        let cluster = &plan.recommendations[1];
+        assert!(matches!(cluster.action, MaintenanceAction::Cluster { .. }));
+        assert_eq!(cluster.benefit.rows_scanned_saved, 1450 - 15 * 5);
+
+        let reindex = &plan.recommendations[2];
+        match &reindex.action {
+            MaintenanceAction::Reindex {
+                index_name,
+                unindexed_fragment_ids,
+            } => {
+                assert_eq!(index_name, "i_idx");
+                assert_eq!(unindexed_fragment_ids, &(10..15).collect::<Vec<_>>());
+            }
+            _ => panic!("expected a reindex recommendation"),
+        }
+        assert_eq!(reindex.benefit.rows_indexed, 500);
+
+        let metrics = plan.execute(&mut dataset).await.unwrap();
+        assert!(metrics.compaction.fragments_removed > 0);
+        assert!(metrics.indices_optimized);
+        assert!(dataset
+            .unindexed_fragments("i_idx")
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 1450);
+    }
+}
@@ -0,0 +1,623 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Partitioned datasets
+//!
+//! A dataset written with a [`PartitionSpec`] buckets its rows by the hash or
+//! the range of a column, and each fragment holds the rows of one bucket.  The
+//! partition of each fragment is recorded in the manifest, so scans filtering
+//! on the partitioning column skip the fragments of other partitions, and two
+//! datasets partitioned the same way can be joined partition by partition.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use arrow::compute::cast;
+use arrow_array::cast::AsArray;
+use arrow_array::types::Int64Type;
+use arrow_array::{ArrayRef, RecordBatch, UInt32Array};
+use arrow_schema::DataType;
+use datafusion::logical_expr::expr::InList;
+use datafusion::logical_expr::{Between, BinaryExpr, Expr, Operator};
+use datafusion::scalar::ScalarValue;
+use lance_arrow::RecordBatchExt;
+use lance_core::datatypes::Schema;
+use lance_core::{Error, Result};
+use lance_table::format::{Fragment, PartitionScheme, Partitioning};
+use snafu::{location, Location};
+
+use super::fragment::FileFragment;
+use super::Dataset;
+
+/// How to partition the rows written to a dataset, see
+/// [`WriteParams::partitioning`](super::WriteParams::partitioning).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionSpec {
+    /// The top-level column to partition by
+    pub column: String,
+    pub scheme: PartitionScheme,
+}
+
+impl PartitionSpec {
+    /// Bucket rows by the hash of an integer or string `column`
+    pub fn hash(column: impl Into<String>, num_buckets: u32) -> Self {
+        Self {
+            column: column.into(),
+            scheme: PartitionScheme::Hash { num_buckets },
+        }
+    }
+
+    /// Bucket rows by ranges of an integer `column`, split at `bounds`
+    pub fn range(column: impl Into<String>, bounds: Vec<i64>) -> Self {
+        Self {
+            column: column.into(),
+            scheme: PartitionScheme::Range { bounds },
+        }
+    }
+
+    /// The spec of an existing partitioning of `schema`
+    pub(crate) fn from_partitioning(partitioning: &Partitioning, schema: &Schema) -> Result<Self> {
+        let field = schema.field_by_id(partitioning.field_id).ok_or_else(|| {
+            Error::invalid_input(
+                format!(
+                    "The partitioning column {} is not in the schema",
+                    partitioning.field_id
+                ),
+                location!(),
+            )
+        })?;
+        Ok(Self {
+            column: field.name.clone(),
+            scheme: partitioning.scheme.clone(),
+        })
+    }
+
+    /// Check the spec against `schema` and resolve its column
+    pub(crate) fn to_partitioning(&self, schema: &Schema) -> Result<Partitioning> {
+        self.scheme.validate()?;
+        let field = schema
+            .fields
+            .iter()
+            .find(|field| field.name == self.column)
+            .ok_or_else(|| {
+                Error::invalid_input(
+                    format!(
+                        "The partitioning column {} is not a top-level column",
+                        self.column
+                    ),
+                    location!(),
+                )
+            })?;
+        let supported = match field.data_type() {
+            DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32 => true,
+            DataType::Utf8 | DataType::LargeUtf8 => {
+                matches!(self.scheme, PartitionScheme::Hash { .. })
+            }
+            _ => false,
+        };
+        if !supported {
+            return Err(Error::invalid_input(
+                format!(
+                    "Cannot partition by {:?} of column {} with type {}",
+                    self.scheme,
+                    self.column,
+                    field.data_type()
+                ),
+                location!(),
+            ));
+        }
+        Ok(Partitioning {
+            field_id: field.id,
+            scheme: self.scheme.clone(),
+        })
+    }
+}
+
+/// The partitioning of a write to `dataset`.
+///
+/// Appends keep the partitioning of the dataset, others use `spec`.
+pub(super) fn write_partitioning(
+    dataset: Option<&Dataset>,
+    appending: bool,
+    spec: Option<&PartitionSpec>,
+) -> Result<Option<PartitionSpec>> {
+    let dataset = match dataset {
+        Some(dataset) if appending => dataset,
+        _ => return Ok(spec.cloned()),
+    };
+    let existing = dataset
+        .manifest
+        .partitioning
+        .as_ref()
+        .map(|partitioning| PartitionSpec::from_partitioning(partitioning, dataset.schema()))
+        .transpose()?;
+    match (existing, spec) {
+        (existing, None) => Ok(existing),
+        (Some(existing), Some(spec)) if &existing == spec => Ok(Some(existing)),
+        (existing, Some(spec)) => Err(Error::invalid_input(
+            format!(
+                "Cannot append with partitioning {:?} to a dataset partitioned by {:?}",
+                spec, existing
+            ),
+            location!(),
+        )),
+    }
+}
+
+/// The partition of each value of `array`. Nulls are in partition 0.
+fn partition_ids(scheme: &PartitionScheme, array: &ArrayRef) -> Result<Vec<u32>> {
+    let partition_of_str = |value: Option<&str>| {
+        value
+            .and_then(|value| scheme.partition_of_str(value))
+            .unwrap_or(0)
+    };
+    Ok(match array.data_type() {
+        DataType::Utf8 => array
+            .as_string::<i32>()
+            .iter()
+            .map(partition_of_str)
+            .collect(),
+        DataType::LargeUtf8 => array
+            .as_string::<i64>()
+            .iter()
+            .map(partition_of_str)
+            .collect(),
+        _ => cast(array, &DataType::Int64)?
+            .as_primitive::<Int64Type>()
+            .iter()
+            .map(|value| value.map(|v| scheme.partition_of_int(v)).unwrap_or(0))
+            .collect(),
+    })
+}
+
+/// Split `batch` into the rows of each partition, keeping their order.
+pub(super) fn split_by_partition(
+    partitioning: &Partitioning,
+    schema: &Schema,
+    batch: &RecordBatch,
+) -> Result<Vec<(u32, RecordBatch)>> {
+    let column = schema
+        .field_by_id(partitioning.field_id)
+        .and_then(|field| batch.column_by_name(&field.name))
+        .ok_or_else(|| Error::Internal {
+            message: "The partitioning column is missing from the written data".to_string(),
+            location: location!(),
+        })?;
+    let ids = partition_ids(&partitioning.scheme, column)?;
+    let mut rows = BTreeMap::<u32, Vec<u32>>::new();
+    for (row, id) in ids.into_iter().enumerate() {
+        rows.entry(id).or_default().push(row as u32);
+    }
+    if rows.len() == 1 {
+        let id = *rows.keys().next().unwrap();
+        return Ok(vec![(id, batch.clone())]);
+    }
+    rows.into_iter()
+        .map(|(id, rows)| Ok((id, batch.take(&UInt32Array::from(rows))?)))
+        .collect()
+}
+
+/// The partitions that may hold rows matching `filter`, if it only matches
+/// some of them. `data_type` is the type of `column`.
+fn matching_partitions(
+    filter: &Expr,
+    column: &str,
+    data_type: &DataType,
+    scheme: &PartitionScheme,
+) -> Option<BTreeSet<u32>> {
+    // Casts that change values would compare, and hash, other values than the
+    // ones the rows were partitioned by
+    let is_column = |expr: &Expr| match expr {
+        Expr::Column(col) => col.name == column,
+        Expr::Cast(cast) => {
+            matches!(cast.expr.as_ref(), Expr::Column(col) if col.name == column)
+                && is_lossless_cast(data_type, &cast.data_type)
+        }
+        _ => false,
+    };
+    let literal = |expr: &Expr| match expr {
+        Expr::Literal(value) => Some(value.clone()),
+        _ => None,
+    };
+    let partition_of = |value: &ScalarValue| match value {
+        ScalarValue::Utf8(Some(value)) | ScalarValue::LargeUtf8(Some(value)) => {
+            scheme.partition_of_str(value)
+        }
+        value => scalar_to_int(value).map(|value| scheme.partition_of_int(value)),
+    };
+    let in_range = |lower: Option<i64>, upper: Option<i64>| {
+        scheme
+            .partitions_in_range(lower, upper)
+            .map(BTreeSet::from_iter)
+    };
+
+    match filter {
+        Expr::BinaryExpr(BinaryExpr { left, op, right }) => match op {
+            Operator::And => {
+                match (
+                    matching_partitions(left, column, data_type, scheme),
+                    matching_partitions(right, column, data_type, scheme),
+                ) {
+                    (Some(left), Some(right)) => Some(&left & &right),
+                    (Some(partitions), None) | (None, Some(partitions)) => Some(partitions),
+                    (None, None) => None,
+                }
+            }
+            Operator::Or => {
+                let left = matching_partitions(left, column, data_type, scheme)?;
+                let right = matching_partitions(right, column, data_type, scheme)?;
+                Some(&left | &right)
+            }
+            _ => {
+                // Normalize to `column op value`
+                let (op, value) = if is_column(left) {
+                    (*op, literal(right)?)
+                } else if is_column(right) {
+                    (op.swap()?, literal(left)?)
+                } else {
+                    return None;
+                };
+                match op {
+                    Operator::Eq => Some(BTreeSet::from([partition_of(&value)?])),
+                    Operator::Lt => in_range(None, Some(scalar_to_int(&value)?.checked_sub(1)?)),
+                    Operator::LtEq => in_range(None, Some(scalar_to_int(&value)?)),
+                    Operator::Gt => in_range(Some(scalar_to_int(&value)?.checked_add(1)?), None),
+                    Operator::GtEq => in_range(Some(scalar_to_int(&value)?), None),
+                    _ => None,
+                }
+            }
+        },
+        Expr::InList(InList {
+            expr,
+            list,
+            negated: false,
+        }) if is_column(expr) => list
+            .iter()
+            .map(|value| literal(value).and_then(|value| partition_of(&value)))
+            .collect(),
+        Expr::Between(Between {
+            expr,
+            negated: false,
+            low,
+            high,
+        }) if is_column(expr) => in_range(
+            Some(scalar_to_int(&literal(low)?)?),
+            Some(scalar_to_int(&literal(high)?)?),
+        ),
+        _ => None,
+    }
+}
+
+/// Whether casting from `from` to `to` keeps every value, so that it keeps
+/// the partition of every value too.
+fn is_lossless_cast(from: &DataType, to: &DataType) -> bool {
+    fn int_range(data_type: &DataType) -> Option<(i128, i128)> {
+        Some(match data_type {
+            DataType::Int8 => (i8::MIN as i128, i8::MAX as i128),
+            DataType::Int16 => (i16::MIN as i128, i16::MAX as i128),
+            DataType::Int32 => (i32::MIN as i128, i32::MAX as i128),
+            DataType::Int64 => (i64::MIN as i128, i64::MAX as i128),
+            DataType::UInt8 => (0, u8::MAX as i128),
+            DataType::UInt16 => (0, u16::MAX as i128),
+            DataType::UInt32 => (0, u32::MAX as i128),
+            DataType::UInt64 => (0, u64::MAX as i128),
+            _ => return None,
+        })
+    }
+    match (from, to) {
+        _ if from == to => true,
+        (DataType::Utf8 | DataType::LargeUtf8, DataType::Utf8 | DataType::LargeUtf8) => true,
+        _ => match (int_range(from), int_range(to)) {
+            (Some((from_min, from_max)), Some((to_min, to_max))) => {
+                to_min <= from_min && from_max <= to_max
+            }
+            _ => false,
+        },
+    }
+}
+
+fn scalar_to_int(value: &ScalarValue) -> Option<i64> {
+    match value {
+        ScalarValue::Int8(Some(v)) => Some(*v as i64),
+        ScalarValue::Int16(Some(v)) => Some(*v as i64),
+        ScalarValue::Int32(Some(v)) => Some(*v as i64),
+        ScalarValue::Int64(Some(v)) => Some(*v),
+        ScalarValue::UInt8(Some(v)) => Some(*v as i64),
+        ScalarValue::UInt16(Some(v)) => Some(*v as i64),
+        ScalarValue::UInt32(Some(v)) => Some(*v as i64),
+        ScalarValue::UInt64(Some(v)) => i64::try_from(*v).ok(),
+        _ => None,
+    }
+}
+
+/// Leave out the fragments whose partition can't hold rows matching `filter`.
+///
+/// Fragments without a partition are always kept.
+pub(super) fn prune_fragments(
+    dataset: &Dataset,
+    filter: &Expr,
+    fragments: &[Fragment],
+) -> Option<Vec<Fragment>> {
+    let partitioning = dataset.manifest.partitioning.as_ref()?;
+    let field = dataset.schema().field_by_id(partitioning.field_id)?;
+    let partitions = matching_partitions(
+        filter,
+        &field.name,
+        &field.data_type(),
+        &partitioning.scheme,
+    )?;
+    Some(
+        fragments
+            .iter()
+            .filter(|fragment| match fragment.partition {
+                Some(partition) => partitions.contains(&partition),
+                None => true,
+            })
+            .cloned()
+            .collect(),
+    )
+}
+
+pub(super) fn partitions(dataset: &Dataset) -> BTreeMap<Option<u32>, Vec<FileFragment>> {
+    let mut partitions = BTreeMap::<_, Vec<_>>::new();
+    for fragment in dataset.get_fragments() {
+        partitions
+            .entry(fragment.metadata().partition)
+            .or_default()
+            .push(fragment);
+    }
+    partitions
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, RecordBatchIterator, StringArray};
+    use arrow_schema::{Field as ArrowField, Schema as ArrowSchema};
+    use datafusion::prelude::{cast, col, lit};
+
+    use super::*;
+    use crate::dataset::optimize::{compact_files, CompactionOptions};
+    use crate::dataset::{WriteMode, WriteParams};
+
+    #[test]
+    fn test_matching_partitions() {
+        let range = PartitionScheme::Range {
+            bounds: vec![0, 10, 20],
+        };
+        let matching = |expr: Expr| {
+            matching_partitions(&expr, "i", &DataType::Int32, &range)
+                .map(|p| p.into_iter().collect::<Vec<_>>())
+        };
+        assert_eq!(matching(col("i").eq(lit(15))), Some(vec![2]));
+        assert_eq!(matching(lit(15).eq(col("i"))), Some(vec![2]));
+        assert_eq!(matching(col("i").lt(lit(10))), Some(vec![0, 1]));
+        assert_eq!(matching(col("i").gt_eq(lit(10))), Some(vec![2, 3]));
+        assert_eq!(
+            matching(col("i").between(lit(5), lit(12))),
+            Some(vec![1, 2])
+        );
+        assert_eq!(
+            matching(col("i").gt(lit(0)).and(col("i").lt(lit(10)))),
+            Some(vec![1])
+        );
+        assert_eq!(
+            matching(
+                col("i")
+                    .eq(lit(-1))
+                    .or(col("i").in_list(vec![lit(25)], false))
+            ),
+            Some(vec![0, 3])
+        );
+        assert_eq!(
+            matching(col("i").eq(lit(5)).and(col("s").eq(lit("a")))),
+            Some(vec![1])
+        );
+        assert_eq!(
+            matching(col("i").eq(lit(5)).or(col("s").eq(lit("a")))),
+            None
+        );
+        assert_eq!(matching(col("i").not_eq(lit(5))), None);
+        assert_eq!(matching(col("s").eq(lit(5))), None);
+
+        // Only casts that keep the values of the column can be pruned on
+        assert_eq!(
+            matching(cast(col("i"), DataType::Int64).eq(lit(15_i64))),
+            Some(vec![2])
+        );
+        assert_eq!(
+            matching(cast(col("i"), DataType::Int8).eq(lit(15_i8))),
+            None
+        );
+        assert_eq!(
+            matching(cast(col("i"), DataType::UInt32).lt(lit(10_u32))),
+            None
+        );
+        assert_eq!(matching(cast(col("i"), DataType::Utf8).eq(lit("15"))), None);
+
+        let hash = PartitionScheme::Hash { num_buckets: 4 };
+        let partition = hash.partition_of_str("a").unwrap();
+        assert_eq!(
+            matching_partitions(&col("s").eq(lit("a")), "s", &DataType::Utf8, &hash),
+            Some(BTreeSet::from([partition]))
+        );
+        assert_eq!(
+            matching_partitions(
+                &cast(col("s"), DataType::LargeUtf8).eq(lit("a")),
+                "s",
+                &DataType::Utf8,
+                &hash
+            ),
+            Some(BTreeSet::from([partition]))
+        );
+        assert_eq!(
+            matching_partitions(&col("s").lt(lit("a")), "s", &DataType::Utf8, &hash),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cast_filter_on_hash_partitions() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+        let params = WriteParams {
+            partitioning: Some(PartitionSpec::hash("i", 8)),
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let dataset = Dataset::write(reader, test_uri, Some(params))
+            .await
+            .unwrap();
+
+        for filter in [
+            "CAST(i AS BIGINT) = 15",
+            "CAST(i AS STRING) = '15'",
+            "CAST(i AS TINYINT) = 15",
+        ] {
+            let mut scan = dataset.scan();
+            scan.filter(filter).unwrap();
+            let result = scan.try_into_batch().await.unwrap();
+            assert_eq!(result.num_rows(), 1, "{}", filter);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partitioned_write() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("s", DataType::Utf8, false),
+        ]));
+        let batch = |range: std::ops::Range<i32>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(range.clone())),
+                    Arc::new(StringArray::from_iter_values(
+                        range.map(|i| format!("s-{}", i)),
+                    )),
+                ],
+            )
+            .unwrap()
+        };
+
+        let params = WriteParams {
+            partitioning: Some(PartitionSpec::range("s", vec![10])),
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(batch(0..10))], schema.clone());
+        assert!(Dataset::write(reader, test_uri, Some(params))
+            .await
+            .is_err());
+
+        let params = WriteParams {
+            partitioning: Some(PartitionSpec::range("i", vec![10, 20])),
+            max_rows_per_file: 5,
+            max_rows_per_group: 5,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(batch(0..25))], schema.clone());
+        let mut dataset = Dataset::write(reader, test_uri, Some(params))
+            .await
+            .unwrap();
+        assert_eq!(
+            dataset.manifest.partitioning,
+            Some(Partitioning {
+                field_id: 0,
+                scheme: PartitionScheme::Range {
+                    bounds: vec![10, 20]
+                },
+            })
+        );
+        let num_fragments = |dataset: &Dataset| {
+            partitions(dataset)
+                .into_iter()
+                .map(|(partition, fragments)| (partition, fragments.len()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            num_fragments(&dataset),
+            vec![(Some(0), 2), (Some(1), 2), (Some(2), 1)]
+        );
+
+        // Appends keep the partitioning, whichever order the rows come in
+        let reader = RecordBatchIterator::new(vec![Ok(batch(15..30))], schema.clone());
+        dataset.append(reader, None).await.unwrap();
+        assert_eq!(
+            num_fragments(&dataset),
+            vec![(Some(0), 2), (Some(1), 3), (Some(2), 2)]
+        );
+        let reader = RecordBatchIterator::new(vec![Ok(batch(0..1))], schema.clone());
+        let params = WriteParams {
+            mode: WriteMode::Append,
+            partitioning: Some(PartitionSpec::hash("i", 2)),
+            ..Default::default()
+        };
+        assert!(dataset.append(reader, Some(params)).await.is_err());
+
+        // Filters on the partitioning column only read the matching fragments
+        let mut scan = dataset.scan();
+        scan.filter("i >= 12 AND i < 18").unwrap();
+        let result = scan.try_into_batch().await.unwrap();
+        assert_eq!(result.num_rows(), 9);
+        let expected = dataset
+            .get_fragments()
+            .iter()
+            .filter(|fragment| fragment.metadata().partition == Some(1))
+            .map(|fragment| fragment.id())
+            .collect::<Vec<_>>();
+        let fragments = dataset.get_fragments();
+        let fragments = fragments
+            .iter()
+            .map(|fragment| fragment.metadata().clone())
+            .collect::<Vec<_>>();
+        let pruned = prune_fragments(
+            &dataset,
+            &col("i").gt_eq(lit(12)).and(col("i").lt(lit(18))),
+            &fragments,
+        )
+        .unwrap();
+        assert_eq!(
+            pruned.iter().map(|f| f.id as usize).collect::<Vec<_>>(),
+            expected
+        );
+
+        // Compaction only merges fragments of the same partition
+        compact_files(&mut dataset, CompactionOptions::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            num_fragments(&dataset),
+            vec![(Some(0), 1), (Some(1), 2), (Some(2), 2)]
+        );
+
+        // Overwriting without a partitioning removes it
+        let reader = RecordBatchIterator::new(vec![Ok(batch(0..10))], schema);
+        let params = WriteParams {
+            mode: WriteMode::Overwrite,
+            ..Default::default()
+        };
+        let dataset = Dataset::write(reader, test_uri, Some(params))
+            .await
+            .unwrap();
+        assert_eq!(dataset.manifest.partitioning, None);
+        assert_eq!(num_fragments(&dataset), vec![(None, 1)]);
+    }
+}
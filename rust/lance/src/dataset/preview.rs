@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Quick previews of a dataset, with bounded latency
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::compute::concat_batches;
+use arrow_array::RecordBatch;
+use arrow_schema::Schema as ArrowSchema;
+use futures::{stream, StreamExt, TryStreamExt};
+use lance_core::Result;
+
+use super::Dataset;
+
+/// Number of fragments the preview rows are read from
+const PREVIEW_FRAGMENTS: usize = 4;
+
+pub(super) async fn preview(
+    dataset: &Dataset,
+    n: usize,
+    max_bytes: usize,
+    timeout: Duration,
+) -> Result<RecordBatch> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let schema = Arc::new(ArrowSchema::from(dataset.schema()));
+    let fragments = dataset.get_fragments();
+    let num_sampled = fragments.len().min(PREVIEW_FRAGMENTS);
+    if n == 0 || num_sampled == 0 {
+        return Ok(RecordBatch::new_empty(schema));
+    }
+
+    // Read the first rows of fragments spread over the dataset concurrently
+    let per_fragment = n.div_ceil(num_sampled);
+    let mut batches = stream::select_all(
+        (0..num_sampled)
+            .map(|i| {
+                let fragment = fragments[i * fragments.len() / num_sampled].metadata();
+                let mut scanner = dataset.scan();
+                scanner
+                    .with_fragments(vec![fragment.clone()])
+                    .batch_size(per_fragment)
+                    .limit(Some(per_fragment as i64), None)?;
+                Ok(stream::once(async move { scanner.try_into_stream().await })
+                    .try_flatten()
+                    .map_ok(move |batch| (i, batch))
+                    .boxed())
+            })
+            .collect::<Result<Vec<_>>>()?,
+    );
+
+    let mut collected = vec![Vec::new(); num_sampled];
+    let mut num_rows = 0;
+    let mut num_bytes = 0;
+    while num_rows < n && num_bytes < max_bytes {
+        let Ok(next) = tokio::time::timeout_at(deadline, batches.next()).await else {
+            break;
+        };
+        let Some((i, batch)) = next.transpose()? else {
+            break;
+        };
+        if batch.num_rows() == 0 {
+            continue;
+        }
+        // Slices of a batch report the size of the whole batch, so the bytes
+        // are estimated from the average row size
+        let row_bytes = batch.get_array_memory_size().div_ceil(batch.num_rows());
+        let fitting_rows = (max_bytes - num_bytes) / row_bytes.max(1);
+        let kept = batch.num_rows().min(n - num_rows).min(fitting_rows);
+        if kept == 0 {
+            break;
+        }
+        num_rows += kept;
+        num_bytes += kept * row_bytes;
+        collected[i].push(batch.slice(0, kept));
+    }
+    Ok(concat_batches(&schema, collected.iter().flatten())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{cast::AsArray, types::Int32Type, Int32Array, RecordBatchIterator};
+    use arrow_schema::{DataType, Field};
+    use tempfile::tempdir;
+
+    use crate::dataset::WriteParams;
+
+    #[tokio::test]
+    async fn test_preview() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..800))],
+        )
+        .unwrap();
+        let params = WriteParams {
+            max_rows_per_file: 100,
+            ..Default::default()
+        };
+        let dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            test_uri,
+            Some(params),
+        )
+        .await
+        .unwrap();
+
+        let timeout = Duration::from_secs(60);
+        let preview = dataset.preview(20, usize::MAX, timeout).await.unwrap();
+        assert_eq!(preview.schema().fields(), schema.fields());
+        // The first rows of every other fragment
+        let values = preview.column(0).as_primitive::<Int32Type>();
+        let expected = [0, 200, 400, 600]
+            .into_iter()
+            .flat_map(|start| start..start + 5)
+            .collect::<Vec<_>>();
+        assert_eq!(values.values().to_vec(), expected);
+
+        // The byte budget limits the rows
+        let preview = dataset.preview(20, 40, timeout).await.unwrap();
+        assert!(preview.num_rows() < 20);
+
+        assert_eq!(
+            dataset
+                .preview(0, usize::MAX, timeout)
+                .await
+                .unwrap()
+                .num_rows(),
+            0
+        );
+        // An expired timeout returns what was read so far
+        let preview = dataset
+            .preview(20, usize::MAX, Duration::ZERO)
+            .await
+            .unwrap();
+        assert!(preview.num_rows() <= 20);
+        assert_eq!(preview.schema().fields(), schema.fields());
+    }
+}
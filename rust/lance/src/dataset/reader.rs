@@ -0,0 +1,340 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! A read-ahead reader for training loops
+//!
+//! [`DatasetReader`] reads the batches of a dataset, or of a shard of it, one
+//! epoch at a time. Batches are read and transformed in a background task up
+//! to [`DatasetReaderParams::prefetch`] batches ahead of the consumer, so a
+//! trainer rarely waits for I/O. The optional transform, such as decoding or
+//! augmenting the rows, runs on the CPU thread pool for several batches at
+//! once while their order is kept.
+//!
+//! Fragments are assigned to shards round-robin, so every shard reads a
+//! disjoint set of fragments whatever the epoch. With a shuffle seed, each
+//! epoch reads the fragments of its shard in a different order, which only
+//! depends on the seed and the epoch.
+
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use futures::stream::{self, BoxStream};
+use futures::{StreamExt, TryStreamExt};
+use lance_core::utils::tokio::spawn_cpu;
+use lance_core::{Error, Result};
+use lance_table::format::Fragment;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use snafu::{location, Location};
+use tokio::sync::mpsc;
+
+use super::Dataset;
+
+/// A transform applied to each batch read by a [`DatasetReader`].
+pub type BatchTransform = Arc<dyn Fn(RecordBatch) -> Result<RecordBatch> + Send + Sync>;
+
+/// Parameters of a [`DatasetReader`].
+#[derive(Debug, Clone)]
+pub struct DatasetReaderParams {
+    /// Columns to read. If `None`, all columns are read.
+    pub columns: Option<Vec<String>>,
+    /// Rows per batch. Default: 1024.
+    pub batch_size: usize,
+    /// Batches read and transformed ahead of the consumer. Default: 16.
+    pub prefetch: usize,
+    /// Batches transformed at the same time. Default: the number of CPUs.
+    pub num_workers: usize,
+    /// Number of shards the fragments are split into. Default: 1.
+    pub num_shards: usize,
+    /// The shard to read, less than `num_shards`. Default: 0.
+    pub shard: usize,
+    /// If set, the order of the fragments of each epoch is shuffled with
+    /// this seed.
+    pub shuffle_seed: Option<u64>,
+}
+
+impl Default for DatasetReaderParams {
+    fn default() -> Self {
+        Self {
+            columns: None,
+            batch_size: 1024,
+            prefetch: 16,
+            num_workers: num_cpus::get(),
+            num_shards: 1,
+            shard: 0,
+            shuffle_seed: None,
+        }
+    }
+}
+
+/// Reads epochs of a dataset ahead of a training loop, see the
+/// [module docs](self).
+pub struct DatasetReader {
+    dataset: Arc<Dataset>,
+    params: DatasetReaderParams,
+    transform: Option<BatchTransform>,
+}
+
+impl std::fmt::Debug for DatasetReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DatasetReader")
+            .field("dataset", &self.dataset.uri())
+            .field("params", &self.params)
+            .field("transform", &self.transform.is_some())
+            .finish()
+    }
+}
+
+impl DatasetReader {
+    pub fn try_new(dataset: Arc<Dataset>, params: DatasetReaderParams) -> Result<Self> {
+        for (name, value) in [
+            ("batch_size", params.batch_size),
+            ("prefetch", params.prefetch),
+            ("num_workers", params.num_workers),
+            ("num_shards", params.num_shards),
+        ] {
+            if value == 0 {
+                return Err(Error::invalid_input(
+                    format!("{} must be greater than 0", name),
+                    location!(),
+                ));
+            }
+        }
+        if params.shard >= params.num_shards {
+            return Err(Error::invalid_input(
+                format!(
+                    "Shard {} does not exist, there are {} shards",
+                    params.shard, params.num_shards
+                ),
+                location!(),
+            ));
+        }
+        if let Some(columns) = &params.columns {
+            dataset.schema().project(columns)?;
+        }
+        Ok(Self {
+            dataset,
+            params,
+            transform: None,
+        })
+    }
+
+    /// Apply `transform` to each batch before it is returned.
+    pub fn with_transform(
+        mut self,
+        transform: impl Fn(RecordBatch) -> Result<RecordBatch> + Send + Sync + 'static,
+    ) -> Self {
+        self.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// The schema of the batches, before they are transformed.
+    pub fn schema(&self) -> Result<SchemaRef> {
+        let schema = match &self.params.columns {
+            Some(columns) => self.dataset.schema().project(columns)?,
+            None => self.dataset.schema().clone(),
+        };
+        Ok(Arc::new((&schema).into()))
+    }
+
+    /// The fragments of the shard, in the order they are read in `epoch`.
+    pub fn fragments(&self, epoch: u64) -> Vec<Fragment> {
+        let mut fragments = self
+            .dataset
+            .get_fragments()
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i % self.params.num_shards == self.params.shard)
+            .map(|(_, fragment)| fragment.metadata().clone())
+            .collect::<Vec<_>>();
+        if let Some(seed) = self.params.shuffle_seed {
+            let mut rng = StdRng::seed_from_u64(seed.wrapping_add(epoch));
+            fragments.shuffle(&mut rng);
+        }
+        fragments
+    }
+
+    /// Read the batches of `epoch`.
+    ///
+    /// Reading starts right away, in a background task that stops when the
+    /// returned stream is dropped.
+    pub async fn epoch(&self, epoch: u64) -> Result<BoxStream<'static, Result<RecordBatch>>> {
+        let fragments = self.fragments(epoch);
+        if fragments.is_empty() {
+            return Ok(stream::empty().boxed());
+        }
+        let mut scanner = self.dataset.scan();
+        scanner
+            .with_fragments(fragments)
+            .batch_size(self.params.batch_size)
+            .scan_in_order(true);
+        if let Some(columns) = &self.params.columns {
+            scanner.project(columns)?;
+        }
+        let batches = scanner.try_into_stream().await?;
+
+        let transform = self.transform.clone();
+        let mut batches = batches
+            .map(move |batch| {
+                let transform = transform.clone();
+                async move {
+                    match transform {
+                        Some(transform) => spawn_cpu(move || transform(batch?)).await,
+                        None => batch,
+                    }
+                }
+            })
+            .buffered(self.params.num_workers);
+
+        let (tx, rx) = mpsc::channel(self.params.prefetch);
+        tokio::spawn(async move {
+            while let Some(batch) = batches.next().await {
+                let failed = batch.is_err();
+                // The receiver is gone if sending fails, so nothing is read anymore
+                if tx.send(batch).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|batch| (batch, rx))
+        })
+        .boxed())
+    }
+
+    /// Read the batches of `num_epochs` epochs, one after the other.
+    pub fn epochs(&self, num_epochs: u64) -> BoxStream<'_, Result<RecordBatch>> {
+        stream::iter(0..num_epochs)
+            .then(move |epoch| self.epoch(epoch))
+            .try_flatten()
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{cast::AsArray, types::Int32Type, Int32Array, RecordBatchIterator};
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use tempfile::tempdir;
+
+    use crate::dataset::WriteParams;
+
+    async fn ids(stream: BoxStream<'_, Result<RecordBatch>>) -> Vec<i32> {
+        stream
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap()
+            .iter()
+            .flat_map(|batch| batch["i"].as_primitive::<Int32Type>().values().to_vec())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_dataset_reader() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..400))],
+        )
+        .unwrap();
+        let params = WriteParams {
+            max_rows_per_file: 100,
+            ..Default::default()
+        };
+        let dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            test_uri,
+            Some(params),
+        )
+        .await
+        .unwrap();
+        let dataset = Arc::new(dataset);
+
+        let params = DatasetReaderParams {
+            batch_size: 30,
+            prefetch: 2,
+            num_workers: 3,
+            ..Default::default()
+        };
+        let reader = DatasetReader::try_new(dataset.clone(), params.clone())
+            .unwrap()
+            .with_transform(|batch| {
+                let values = batch["i"].as_primitive::<Int32Type>();
+                let doubled = Int32Array::from_iter_values(values.values().iter().map(|v| v * 2));
+                Ok(RecordBatch::try_new(
+                    batch.schema(),
+                    vec![Arc::new(doubled)],
+                )?)
+            });
+        assert_eq!(reader.schema().unwrap().fields(), schema.fields());
+        let values = ids(reader.epoch(0).await.unwrap()).await;
+        assert_eq!(values, (0..400).map(|v| v * 2).collect::<Vec<_>>());
+        assert_eq!(ids(reader.epochs(2)).await.len(), 800);
+
+        // Shards read disjoint fragments, in an order shuffled per epoch
+        let mut all_values = vec![];
+        for shard in 0..2 {
+            let reader = DatasetReader::try_new(
+                dataset.clone(),
+                DatasetReaderParams {
+                    num_shards: 2,
+                    shard,
+                    shuffle_seed: Some(42),
+                    ..params.clone()
+                },
+            )
+            .unwrap();
+            let fragment_ids = |epoch| {
+                let mut ids = reader
+                    .fragments(epoch)
+                    .iter()
+                    .map(|fragment| fragment.id)
+                    .collect::<Vec<_>>();
+                ids.sort();
+                ids
+            };
+            assert_eq!(fragment_ids(0), vec![shard as u64, shard as u64 + 2]);
+            assert_eq!(fragment_ids(1), fragment_ids(0));
+            assert_eq!(reader.fragments(3), reader.fragments(3));
+            let values = ids(reader.epoch(0).await.unwrap()).await;
+            assert_eq!(values.len(), 200);
+            all_values.extend(values);
+        }
+        all_values.sort();
+        assert_eq!(all_values, (0..400).collect::<Vec<_>>());
+
+        // Errors of the transform end the epoch
+        let reader = DatasetReader::try_new(dataset.clone(), params.clone())
+            .unwrap()
+            .with_transform(|_| Err(Error::invalid_input("bad batch", location!())));
+        let err = reader
+            .epoch(0)
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("bad batch"), "{err}");
+
+        let err = DatasetReader::try_new(
+            dataset.clone(),
+            DatasetReaderParams {
+                num_shards: 2,
+                shard: 2,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Shard 2"), "{err}");
+    }
+}
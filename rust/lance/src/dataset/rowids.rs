@@ -8,8 +8,8 @@ use snafu::{location, Location};
 use std::sync::Arc;
 
 use lance_table::{
-    format::RowIdMeta,
-    rowids::{read_row_ids, RowIdIndex},
+    format::{Fragment, RowIdMeta},
+    rowids::{read_row_ids, RowIdIndex, RowIdSequence},
 };
 
 // TODO: remove allow unused once we start using this in query and take paths.
@@ -47,7 +47,7 @@ async fn load_row_id_index(dataset: &Dataset) -> Result<lance_table::rowids::Row
     for fragment in dataset.manifest.fragments.iter() {
         match &fragment.row_id_meta {
             Some(RowIdMeta::External(file_slice)) => {
-                external_files.push((fragment.id as u32, file_slice))
+                external_files.push((fragment.id as u32, file_slice.clone()))
             }
             Some(RowIdMeta::Inline(row_ids)) => inline_files.push((fragment.id as u32, row_ids)),
             _ => {}
@@ -86,6 +86,29 @@ async fn load_row_id_index(dataset: &Dataset) -> Result<lance_table::rowids::Row
     Ok(index)
 }
 
+/// Load the row ids of `fragment`, if the dataset uses stable row ids.
+pub async fn load_row_id_sequence(
+    dataset: &Dataset,
+    fragment: &Fragment,
+) -> Result<Option<RowIdSequence>> {
+    match &fragment.row_id_meta {
+        None => Ok(None),
+        Some(RowIdMeta::Inline(row_ids)) => Ok(Some(read_row_ids(row_ids)?)),
+        Some(RowIdMeta::External(file_slice)) => {
+            let path = dataset.base.child(file_slice.path.as_str());
+            let range =
+                file_slice.offset as usize..(file_slice.offset as usize + file_slice.size as usize);
+            let data = dataset
+                .object_store
+                .open(&path)
+                .await?
+                .get_range(range)
+                .await?;
+            Ok(Some(read_row_ids(&data)?))
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::dataset::{UpdateBuilder, WriteMode, WriteParams};
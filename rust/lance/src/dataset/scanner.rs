@@ -2,11 +2,12 @@
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
 use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 
-use arrow_array::{Array, Float32Array, Int64Array, RecordBatch};
+use arrow_array::{Array, FixedSizeListArray, Float32Array, Int64Array, RecordBatch};
 use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema, SchemaRef, SortOptions};
 use arrow_select::concat::concat_batches;
 use async_recursion::async_recursion;
@@ -16,6 +17,7 @@ use datafusion::physical_expr::PhysicalSortExpr;
 use datafusion::physical_plan::expressions;
 use datafusion::physical_plan::projection::ProjectionExec as DFProjectionExec;
 use datafusion::physical_plan::sorts::sort::SortExec;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use datafusion::physical_plan::{
     aggregates::{AggregateExec, AggregateMode, PhysicalGroupBy},
     display::DisplayableExecutionPlan,
@@ -30,26 +32,44 @@ use datafusion::scalar::ScalarValue;
 use datafusion_physical_expr::PhysicalExpr;
 use futures::stream::{Stream, StreamExt};
 use futures::TryStreamExt;
+use lance_arrow::cast::cast_with_options;
 use lance_arrow::floats::{coerce_float_vector, FloatType};
+use lance_arrow::FixedSizeListArrayExt;
+use lance_core::utils::tokio::DecodeLimit;
 use lance_core::{ROW_ID, ROW_ID_FIELD};
-use lance_datafusion::exec::{execute_plan, LanceExecutionOptions};
-use lance_index::vector::{Query, DIST_COL};
-use lance_index::{scalar::expression::ScalarIndexExpr, DatasetIndexExt};
+use lance_datafusion::exec::{execute_plan, ExecutionMemoryStats, LanceExecutionOptions};
+use lance_datafusion::trace::trace_plan;
+use lance_datafusion::utils::batch_size_for_bytes;
+use lance_index::vector::transform::finite_vector_indices;
+use lance_index::vector::{NullVectorHandling, Query, DIST_COL};
+use lance_index::{
+    scalar::expression::{IndexInformationProvider, ScalarIndexExpr},
+    DatasetIndexExt,
+};
 use lance_io::stream::RecordBatchStream;
 use lance_linalg::distance::MetricType;
 use lance_table::format::{Fragment, Index};
-use log::debug;
+use log::{debug, warn};
 use roaring::RoaringBitmap;
 use tracing::{info_span, instrument, Span};
 
+use super::cost::{decode_cost_per_row, prefer_early_materialization};
+use super::partitioning::prune_fragments;
+use super::shard::{shard_fragments, split_scan_ranges};
+pub use super::shard::{ScanRange, ShardGranularity};
 use super::Dataset;
 use crate::datatypes::Schema;
-use crate::index::DatasetIndexInternalExt;
+use crate::index::vector::tune::{tuned_ef, tuned_for_recall, EfTuning};
+use crate::index::{DatasetIndexInternalExt, ScalarIndexInfo};
 use crate::io::exec::scalar_index::{MaterializeIndexExec, ScalarIndexExec};
 use crate::io::exec::{
-    knn::new_knn_exec, FilterPlan, KNNFlatExec, LancePushdownScanExec, LanceScanExec, Planner,
-    PreFilterSource, ProjectionExec, ScanConfig, TakeExec,
+    explain_plan, knn::new_knn_exec, ExplainFormat, FilterPlan, KNNFlatExec, LancePushdownScanExec,
+    LanceScanExec, PlanMetrics, Planner, PreFilterSource, ProjectionExec, ScanConfig,
+    SearchExplain, TakeExec,
 };
+use crate::metrics::ScanTimer;
+use crate::session::query_log::QueryRecorder;
+use crate::session::watchdog::ScanWatch;
 use crate::{Error, Result};
 use snafu::{location, Location};
 
@@ -68,6 +88,7 @@ pub const DEFAULT_FRAGMENT_READAHEAD: usize = 4;
 ///
 /// Floats are sorted using the IEEE 754 total ordering
 /// Strings are sorted using UTF-8 lexicographic order (i.e. we sort the binary)
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ColumnOrdering {
     pub ascending: bool,
     pub nulls_first: bool,
@@ -108,6 +129,27 @@ impl ColumnOrdering {
     }
 }
 
+/// The order in which a scan returns its rows
+///
+/// Vector searches always return their results by distance, so the scan order
+/// only applies to other scans.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanOrder {
+    /// Batches are returned as soon as they are read, for maximum parallelism.
+    Unordered,
+    /// Rows are returned by increasing row address, fragment by fragment.
+    ///
+    /// Fragments are still read concurrently, but a batch that is ready is held
+    /// until the batches before it are returned. This is the default.
+    FragmentOrder,
+    /// Rows are sorted by the given columns.
+    ///
+    /// The data is read without ordering and then sorted, so all of it must be
+    /// read before the first batch is returned. When a limit is set, only the
+    /// top rows are kept while sorting.
+    Sorted(Vec<ColumnOrdering>),
+}
+
 /// Dataset Scanner
 ///
 /// ```rust,ignore
@@ -140,12 +182,19 @@ pub struct Scanner {
     /// The batch size controls the maximum size of rows to return for each read.
     batch_size: Option<usize>,
 
+    /// If set, the batch size is computed from the estimated width of the
+    /// rows read, to make batches of about this many bytes.
+    batch_size_bytes: Option<usize>,
+
     /// Number of batches to prefetch
     batch_readahead: usize,
 
     /// Number of fragments to read concurrently
     fragment_readahead: usize,
 
+    /// Maximum number of threads decoding data for this scan
+    decode_threads: Option<usize>,
+
     limit: Option<i64>,
     offset: Option<i64>,
 
@@ -161,6 +210,12 @@ pub struct Scanner {
 
     nearest: Option<Query>,
 
+    /// If set, `ef` of graph indices is tuned when the query does not set it.
+    ef_tuning: Option<EfTuning>,
+
+    /// If set, the parameters of vector indices are tuned to reach this recall.
+    recall_tuning: Option<EfTuning>,
+
     /// Scan the dataset with a meta column: "_rowid"
     with_row_id: bool,
 
@@ -174,8 +229,63 @@ pub struct Scanner {
     /// This field is ignored if `ordering` is defined
     ordered: bool,
 
+    /// Whether to return string and binary columns as Utf8View and BinaryView (default: false)
+    use_view_types: bool,
+
     /// If set, this scanner serves only these fragments.
     fragments: Option<Vec<Fragment>>,
+
+    /// If set, only these rows of some fragments are scanned, by fragment id.
+    row_ranges: Option<Arc<HashMap<u64, Vec<Range<u32>>>>>,
+
+    /// Read fragments of more rows than this as several ranges
+    split_rows: Option<u32>,
+
+    /// Whether to fall back to a scan when an index fails to load
+    allow_degraded: bool,
+
+    /// The indices the last plan fell back from
+    degraded_indices: Mutex<Vec<String>>,
+}
+
+/// The schema with its top-level string and binary columns as view types.
+fn view_schema(schema: &ArrowSchema) -> SchemaRef {
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|field| match field.data_type() {
+            DataType::Utf8 | DataType::LargeUtf8 => {
+                Arc::new(field.as_ref().clone().with_data_type(DataType::Utf8View))
+            }
+            DataType::Binary | DataType::LargeBinary => {
+                Arc::new(field.as_ref().clone().with_data_type(DataType::BinaryView))
+            }
+            _ => field.clone(),
+        })
+        .collect::<Vec<_>>();
+    Arc::new(ArrowSchema::new_with_metadata(
+        fields,
+        schema.metadata().clone(),
+    ))
+}
+
+/// Cast the top-level string and binary columns of the stream to view types.
+fn to_view_stream(stream: SendableRecordBatchStream) -> SendableRecordBatchStream {
+    let schema = view_schema(stream.schema().as_ref());
+    let batch_schema = schema.clone();
+    let stream = stream.map(move |batch| {
+        let batch = batch?;
+        let columns = batch
+            .columns()
+            .iter()
+            .zip(batch_schema.fields())
+            .map(|(column, field)| {
+                cast_with_options(column, field.data_type(), &Default::default())
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(RecordBatch::try_new(batch_schema.clone(), columns)?)
+    });
+    Box::pin(RecordBatchStreamAdapter::new(schema, stream))
 }
 
 fn escape_column_name(name: &str) -> String {
@@ -196,16 +306,25 @@ impl Scanner {
             prefilter: false,
             filter: None,
             batch_size: None,
+            batch_size_bytes: None,
             batch_readahead: DEFAULT_BATCH_READAHEAD,
             fragment_readahead: DEFAULT_FRAGMENT_READAHEAD,
+            decode_threads: None,
             limit: None,
             offset: None,
             ordering: None,
             nearest: None,
+            ef_tuning: None,
+            recall_tuning: None,
             use_stats: true,
             with_row_id: false,
             ordered: true,
+            use_view_types: false,
             fragments: None,
+            row_ranges: None,
+            split_rows: None,
+            allow_degraded: false,
+            degraded_indices: Mutex::new(Vec::new()),
         }
     }
 
@@ -224,18 +343,129 @@ impl Scanner {
         self
     }
 
-    fn get_batch_size(&self) -> usize {
+    /// Only scan the share of the dataset of worker `rank` of `world_size`
+    /// distributed workers.
+    ///
+    /// The fragments to scan, or all fragments if they are not set, are split
+    /// so that every worker reads about the same number of bytes, see
+    /// [`ShardGranularity`]. The split is deterministic, so each worker can
+    /// compute its share on its own, and the shares of all workers are
+    /// disjoint and cover every row.
+    ///
+    /// With [`ShardGranularity::Rows`], the scan can't use indices.
+    pub async fn shard(
+        &mut self,
+        world_size: usize,
+        rank: usize,
+        granularity: ShardGranularity,
+    ) -> Result<&mut Self> {
+        let fragments = match &self.fragments {
+            Some(fragments) => fragments.clone(),
+            None => self.dataset.fragments().as_ref().clone(),
+        };
+        let (fragments, row_ranges) =
+            shard_fragments(&self.dataset, &fragments, world_size, rank, granularity).await?;
+        self.fragments = Some(fragments);
+        self.row_ranges = (!row_ranges.is_empty()).then(|| Arc::new(row_ranges));
+        Ok(self)
+    }
+
+    /// Read fragments of more than `max_rows` rows as ranges of at most
+    /// `max_rows` rows, which are read concurrently like fragments, see
+    /// [`Self::fragment_readahead`].
+    ///
+    /// This speeds up scans of datasets with few large fragments.
+    pub fn split_fragments(&mut self, max_rows: u32) -> Result<&mut Self> {
+        if max_rows == 0 {
+            return Err(Error::invalid_input(
+                "Fragments can't be split into ranges of 0 rows",
+                location!(),
+            ));
+        }
+        self.split_rows = Some(max_rows);
+        Ok(self)
+    }
+
+    /// Split the scan into ranges of at most `max_rows` rows, to be scanned
+    /// by distributed workers with [`Self::with_scan_ranges`].
+    pub async fn scan_ranges(&self, max_rows: u32) -> Result<Vec<ScanRange>> {
+        let fragments = match &self.fragments {
+            Some(fragments) => fragments.clone(),
+            None => self.dataset.fragments().as_ref().clone(),
+        };
+        split_scan_ranges(
+            &self.dataset,
+            &fragments,
+            self.row_ranges.as_deref(),
+            max_rows,
+        )
+        .await
+    }
+
+    /// Only scan `ranges`, see [`Self::scan_ranges`].
+    ///
+    /// As with [`Self::shard`], the scan then can't use indices.
+    ///
+    /// Returns an error if a range is reversed or goes beyond the rows of its
+    /// fragment.
+    pub fn with_scan_ranges(&mut self, ranges: &[ScanRange]) -> Result<&mut Self> {
+        let mut fragments = Vec::new();
+        let mut row_ranges = HashMap::<u64, Vec<Range<u32>>>::new();
+        for range in ranges {
+            let fragment = self
+                .dataset
+                .get_fragment(range.fragment_id as usize)
+                .ok_or_else(|| {
+                    Error::invalid_input(
+                        format!("Fragment {} does not exist", range.fragment_id),
+                        location!(),
+                    )
+                })?;
+            // Fragments written by older versions don't record their rows,
+            // their ranges are checked when they are read
+            let num_rows = fragment.metadata().physical_rows;
+            if range.rows.start > range.rows.end
+                || num_rows.is_some_and(|num_rows| range.rows.end as usize > num_rows)
+            {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Rows {:?} are out of the bounds of fragment {}",
+                        range.rows, range.fragment_id
+                    ),
+                    location!(),
+                ));
+            }
+            if !row_ranges.contains_key(&range.fragment_id) {
+                fragments.push(fragment.metadata().clone());
+            }
+            row_ranges
+                .entry(range.fragment_id)
+                .or_default()
+                .push(range.rows.clone());
+        }
+        self.fragments = Some(fragments);
+        self.row_ranges = Some(Arc::new(row_ranges));
+        Ok(self)
+    }
+
+    fn get_batch_size(&self, projection: &Schema) -> usize {
         // Default batch size to be large enough so that a i32 column can be
         // read in a single range request. For the object store default of
         // 64KB, this is 16K rows. For local file systems, the default block size
         // is just 4K, which would mean only 1K rows, which might be a little small.
         // So we use a default minimum of 8K rows.
-        self.batch_size.unwrap_or_else(|| {
+        let batch_size = self.batch_size.unwrap_or_else(|| {
             std::cmp::max(
                 self.dataset.object_store().block_size() / 4,
                 DEFAULT_BATCH_SIZE,
             )
-        })
+        });
+        match self.batch_size_bytes {
+            Some(batch_size_bytes) => {
+                batch_size_for_bytes(&ArrowSchema::from(projection), batch_size_bytes, batch_size)
+            }
+            None => batch_size,
+        }
     }
 
     fn ensure_not_fragment_scan(&self) -> Result<()> {
@@ -363,6 +593,17 @@ impl Scanner {
         self
     }
 
+    /// Size batches to about `batch_size_bytes` bytes, from the estimated
+    /// width of the rows read.
+    ///
+    /// Wide rows, such as rows of large embeddings, then make batches of
+    /// fewer rows than narrow rows.  The batch size, or its default, bounds
+    /// the number of rows of a batch.
+    pub fn batch_size_bytes(&mut self, batch_size_bytes: usize) -> &mut Self {
+        self.batch_size_bytes = Some(batch_size_bytes);
+        self
+    }
+
     /// Set the prefetch size.
     pub fn batch_readahead(&mut self, nbatches: usize) -> &mut Self {
         self.batch_readahead = nbatches;
@@ -377,6 +618,17 @@ impl Scanner {
         self
     }
 
+    /// Set the maximum number of threads used to decode data for this scan.
+    ///
+    /// Decoding is also bounded by the process-wide limit, which defaults to
+    /// the number of CPUs and can be set with the `LANCE_DECODE_THREADS`
+    /// environment variable.  This does not change how many I/O requests are
+    /// in flight.
+    pub fn decode_threads(&mut self, nthreads: usize) -> &mut Self {
+        self.decode_threads = Some(nthreads);
+        self
+    }
+
     /// Set whether to read data in order (default: true)
     ///
     /// A scan will always read from the disk concurrently.  If this property
@@ -395,6 +647,43 @@ impl Scanner {
         self
     }
 
+    /// Set the order the rows are returned in (default: [ScanOrder::FragmentOrder])
+    ///
+    /// This replaces both [Self::scan_in_order] and [Self::order_by].
+    pub fn scan_order(&mut self, order: ScanOrder) -> Result<&mut Self> {
+        match order {
+            ScanOrder::Unordered => {
+                self.ordering = None;
+                self.ordered = false;
+            }
+            ScanOrder::FragmentOrder => {
+                self.ordering = None;
+                self.ordered = true;
+            }
+            ScanOrder::Sorted(ordering) => {
+                if ordering.is_empty() {
+                    return Err(Error::invalid_input(
+                        "A sorted scan needs at least one column to sort by",
+                        location!(),
+                    ));
+                }
+                self.order_by(Some(ordering))?;
+                // The rows are sorted after they are read, so read them in any order
+                self.ordered = false;
+            }
+        }
+        Ok(self)
+    }
+
+    /// The order the rows are returned in
+    pub fn get_scan_order(&self) -> ScanOrder {
+        match &self.ordering {
+            Some(ordering) => ScanOrder::Sorted(ordering.clone()),
+            None if self.ordered => ScanOrder::FragmentOrder,
+            None => ScanOrder::Unordered,
+        }
+    }
+
     /// Set limit and offset.
     ///
     /// If offset is set, the first offset rows will be skipped. If limit is set,
@@ -465,12 +754,26 @@ impl Scanner {
                 ));
             }
         };
+        let key: Arc<dyn Array> = key.into();
+        if self.dataset.config().null_vectors(field)? == NullVectorHandling::Error {
+            let vector = FixedSizeListArray::try_new_from_values(key.clone(), key.len() as i32)?;
+            if finite_vector_indices(&vector).is_empty() {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Query vector must be finite to search column {}, which rejects null vectors",
+                        column
+                    ),
+                    location!(),
+                ));
+            }
+        }
 
         self.nearest = Some(Query {
             column: column.to_string(),
-            key: key.into(),
+            key,
             k,
             nprobes: 1,
+            probe_fraction: None,
             ef: None,
             refine_factor: None,
             metric_type: MetricType::L2,
@@ -493,6 +796,48 @@ impl Scanner {
         self
     }
 
+    /// Pick `ef` for graph indices, like IVF_HNSW_SQ, so that the search
+    /// reaches the recall target of `tuning`.
+    ///
+    /// The first search with a given `k` and `nprobes` compares searches with
+    /// a growing `ef` to exact searches of vectors sampled from the dataset,
+    /// which is slow. The smallest `ef` that reaches the target is cached in
+    /// the session for the next searches. An `ef` set with [`Self::ef`] takes
+    /// precedence.
+    pub fn auto_tune_ef(&mut self, tuning: EfTuning) -> Result<&mut Self> {
+        tuning.validate()?;
+        self.ef_tuning = Some(tuning);
+        Ok(self)
+    }
+
+    /// Probe the closest IVF partitions, past `nprobes`, until they hold
+    /// `fraction` of the indexed rows.
+    pub fn probe_fraction(&mut self, fraction: f32) -> &mut Self {
+        if let Some(q) = self.nearest.as_mut() {
+            q.probe_fraction = Some(fraction);
+        }
+        self
+    }
+
+    /// Search vector indices with the parameters that reach `recall`, between
+    /// 0 and 1, instead of picking `nprobes` and the refine factor by hand.
+    ///
+    /// The first search with a given `k` and `nprobes` tunes the refine factor,
+    /// `ef` for graph indices, and the fraction of rows to probe, see
+    /// [`Self::probe_fraction`], against exact searches of vectors sampled from
+    /// the dataset, which is slow. The tuned parameters are cached in the
+    /// session for the next searches. Parameters set on the scanner take
+    /// precedence.
+    pub fn target_recall(&mut self, recall: f32) -> Result<&mut Self> {
+        let tuning = EfTuning {
+            target_recall: recall,
+            ..Default::default()
+        };
+        tuning.validate()?;
+        self.recall_tuning = Some(tuning);
+        Ok(self)
+    }
+
     /// Apply a refine step to the vector search.
     ///
     /// A refine improves query accuracy but also makes search slower, by reading extra elements
@@ -557,6 +902,49 @@ impl Scanner {
         self
     }
 
+    /// Set whether to fall back to a scan when an index fails to load (default: false)
+    ///
+    /// By default, a query that uses a missing or corrupt index fails. When
+    /// degraded queries are allowed, a warning is logged instead and the
+    /// query is answered without the index: by a brute-force search for
+    /// vector indices, and by filtering the scanned rows for scalar indices.
+    /// The indices that were skipped are reported by
+    /// [`DatasetRecordBatchStream::degraded_indices`].
+    pub fn allow_degraded(&mut self, allow_degraded: bool) -> &mut Self {
+        self.allow_degraded = allow_degraded;
+        self
+    }
+
+    /// The indices the last plan created by this scanner could not load, see
+    /// [`Self::allow_degraded`]
+    pub fn degraded_indices(&self) -> Vec<String> {
+        self.degraded_indices.lock().unwrap().clone()
+    }
+
+    /// Whether `index` can be used: `load` loads it, and if that fails the
+    /// index is skipped when degraded queries are allowed.
+    async fn index_loads<T>(
+        &self,
+        index: &Index,
+        load: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<bool> {
+        match load.await {
+            Ok(_) => Ok(true),
+            Err(err) if self.allow_degraded => {
+                warn!(
+                    "Index {} failed to load, answering the query without it: {}",
+                    index.name, err
+                );
+                let mut degraded_indices = self.degraded_indices.lock().unwrap();
+                if !degraded_indices.contains(&index.name) {
+                    degraded_indices.push(index.name.clone());
+                }
+                Ok(false)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
     /// Set whether to use statistics to optimize the scan (default: true)
     ///
     /// This is used for debugging or benchmarking purposes.
@@ -565,10 +953,20 @@ impl Scanner {
         self
     }
 
+    /// Set whether to return string and binary columns as Utf8View and BinaryView (default: false)
+    pub fn use_view_types(&mut self, use_view_types: bool) -> &mut Self {
+        self.use_view_types = use_view_types;
+        self
+    }
+
     /// The Arrow schema of the output, including projections and vector / _distance
     pub async fn schema(&self) -> Result<SchemaRef> {
         let plan = self.create_plan().await?;
-        Ok(plan.schema())
+        if self.use_view_types {
+            Ok(view_schema(plan.schema().as_ref()))
+        } else {
+            Ok(plan.schema())
+        }
     }
 
     /// The schema of the Scanner from lance physical takes
@@ -672,10 +1070,37 @@ impl Scanner {
     #[instrument(skip_all)]
     pub async fn try_into_stream(&self) -> Result<DatasetRecordBatchStream> {
         let plan = self.create_plan().await?;
-        Ok(DatasetRecordBatchStream::new(execute_plan(
-            plan,
-            LanceExecutionOptions::default(),
-        )?))
+        let filter = self.filter.as_ref().map(|f| f.to_string());
+        let recorder = self.dataset.session.query_log.as_ref().map(|log| {
+            QueryRecorder::new(
+                log.clone(),
+                &self.dataset,
+                plan.as_ref(),
+                self.filter.as_ref(),
+            )
+        });
+        let (watch, mut stream) = match &self.dataset.session.slow_query_watchdog {
+            Some(watchdog) => {
+                // Trace the plan here so the watchdog sees the metrics of the
+                // executed nodes
+                let plan = trace_plan(plan)?;
+                let watch = watchdog.watch(&self.dataset, plan.clone(), filter);
+                let options = LanceExecutionOptions {
+                    bypass_tracing: true,
+                    ..Default::default()
+                };
+                (Some(watch), execute_plan(plan, options)?)
+            }
+            None => (None, execute_plan(plan, LanceExecutionOptions::default())?),
+        };
+        if self.use_view_types {
+            stream = to_view_stream(stream);
+        }
+        let mut stream = DatasetRecordBatchStream::new(stream);
+        stream.recorder = recorder;
+        stream.watch = watch;
+        stream.degraded_indices = self.degraded_indices();
+        Ok(stream)
     }
 
     pub(crate) async fn try_into_dfstream(
@@ -697,6 +1122,11 @@ impl Scanner {
     #[instrument(skip_all)]
     pub async fn count_rows(&self) -> Result<u64> {
         let plan = self.create_plan().await?;
+        Self::count_plan_rows(plan).await
+    }
+
+    /// The number of rows `plan` returns
+    pub(crate) async fn count_plan_rows(plan: Arc<dyn ExecutionPlan>) -> Result<u64> {
         // Datafusion interprets COUNT(*) as COUNT(1)
         let one = Arc::new(Literal::new(ScalarValue::UInt8(Some(1))));
         let count_expr = create_aggregate_expr(
@@ -797,6 +1227,7 @@ impl Scanner {
     /// 4. Limit / Offset
     /// 5. Take remaining columns / Projection
     pub async fn create_plan(&self) -> Result<Arc<dyn ExecutionPlan>> {
+        self.degraded_indices.lock().unwrap().clear();
         if self.phyical_columns.fields.is_empty() && !self.with_row_id {
             return Err(Error::InvalidInput {
                 source:
@@ -805,14 +1236,53 @@ impl Scanner {
                 location: location!(),
             });
         }
+        if self.row_ranges.is_some() && self.nearest.is_some() {
+            return Err(Error::invalid_input(
+                "Vector search is not supported for scans sharded by rows",
+                location!(),
+            ));
+        }
         // Scalar indices are only used when prefiltering
         // TODO: Should we use them when postfiltering if there is no vector search?
-        let use_scalar_index = self.prefilter || self.nearest.is_none();
+        // Scans sharded by rows can only read the rows of their ranges.
+        let use_scalar_index =
+            (self.prefilter || self.nearest.is_none()) && self.row_ranges.is_none();
 
         let planner = Planner::new(Arc::new(self.dataset.schema().into()));
 
         let mut filter_plan = if let Some(filter) = self.filter.as_ref() {
-            let index_info = self.dataset.scalar_index_info().await?;
+            // The index metadata is only needed if the filter may use indices,
+            // and only for the columns it filters
+            let mut index_info = if use_scalar_index {
+                let columns = Planner::column_names_in_expr(filter);
+                self.dataset.scalar_index_info_for_columns(&columns).await?
+            } else {
+                ScalarIndexInfo::default()
+            };
+            if self.allow_degraded && use_scalar_index {
+                // Composite indices are loaded by their key
+                let keys = Planner::column_names_in_expr(filter)
+                    .into_iter()
+                    .filter(|column| index_info.get_index(column).is_some())
+                    .chain(
+                        index_info
+                            .composite_indices()
+                            .iter()
+                            .map(|index| index.key.clone()),
+                    )
+                    .collect::<Vec<_>>();
+                for column in keys {
+                    let Some(index) = self.dataset.load_scalar_index_for_column(&column).await?
+                    else {
+                        continue;
+                    };
+                    let uuid = index.uuid.to_string();
+                    let load = self.dataset.open_scalar_index(&column, &uuid);
+                    if !self.index_loads(&index, load).await? {
+                        index_info.remove(&column);
+                    }
+                }
+            }
             let filter_plan =
                 planner.create_filter_plan(filter.clone(), &index_info, use_scalar_index)?;
 
@@ -863,11 +1333,13 @@ impl Scanner {
             } else {
                 self.dataset.fragments()
             };
-            let use_stats = if fragments.iter().any(|f| !f.has_legacy_files()) {
-                false
-            } else {
-                self.use_stats
-            };
+            let use_stats =
+                if fragments.iter().any(|f| !f.has_legacy_files()) || self.row_ranges.is_some() {
+                    false
+                } else {
+                    self.use_stats
+                };
+            let has_refine = filter_plan.has_refine();
             match (&filter_plan.index_query, &mut filter_plan.refine_expr) {
                 (Some(index_query), None) => {
                     self.scalar_indexed_scan(&self.phyical_columns, index_query)
@@ -882,21 +1354,40 @@ impl Scanner {
                     self.scalar_indexed_scan(&filter_schema, index_query)
                         .await?
                 }
-                (None, Some(_)) if use_stats && self.batch_size.is_none() => {
+                (None, Some(_))
+                    if use_stats
+                        && self.batch_size.is_none()
+                        && self.batch_size_bytes.is_none() =>
+                {
                     self.pushdown_scan(false, filter_plan.refine_expr.take().unwrap())?
                 }
-                (None, _) => {
+                (None, _) if !has_refine => {
                     // The source is a full scan of the table
-                    let with_row_id = filter_plan.has_refine() || self.with_row_id;
-                    let schema = if filter_plan.has_refine() {
-                        // If there is a filter then only load the filter columns in the
-                        // initial scan.  We will `take` the remaining columns later
-                        let columns = filter_plan.refine_columns();
-                        Arc::new(self.dataset.schema().project(&columns)?)
+                    let schema = Arc::new(self.phyical_columns.clone());
+                    self.scan(self.with_row_id, false, schema)
+                }
+                (None, _) => {
+                    // The source is a full scan of the table.  If there is a filter
+                    // then only load the filter columns in the initial scan and `take`
+                    // the remaining columns later, unless they are cheaper to decode
+                    // for every row than to take.
+                    let columns = filter_plan.refine_columns();
+                    let filter_schema = self.dataset.schema().project(&columns)?;
+                    let remaining_schema = self.phyical_columns.exclude(&filter_schema)?;
+                    let early_materialization = !remaining_schema.fields.is_empty()
+                        && self.ordering.is_none()
+                        && prefer_early_materialization(
+                            decode_cost_per_row(&self.dataset, fragments, &remaining_schema)
+                                .await?,
+                        );
+                    if early_materialization {
+                        let mut field_ids = filter_schema.field_ids();
+                        field_ids.extend(remaining_schema.field_ids());
+                        let schema = Arc::new(self.dataset.schema().project_by_ids(&field_ids));
+                        self.scan(self.with_row_id, false, schema)
                     } else {
-                        Arc::new(self.phyical_columns.clone())
-                    };
-                    self.scan(with_row_id, false, schema)
+                        self.scan(true, false, Arc::new(filter_schema))
+                    }
                 }
             }
         };
@@ -961,7 +1452,12 @@ impl Scanner {
                     })
                 })
                 .collect::<Result<Vec<_>>>()?;
-            plan = Arc::new(SortExec::new(col_exprs, plan));
+            // With a limit, only the top rows need to be kept
+            let fetch = self
+                .limit
+                .filter(|limit| *limit > 0)
+                .map(|limit| (limit + self.offset.unwrap_or(0)) as usize);
+            plan = Arc::new(SortExec::new(col_exprs, plan).with_fetch(fetch));
         }
 
         // Stage 4: limit / offset
@@ -1031,7 +1527,18 @@ impl Scanner {
         } else {
             Arc::new(vec![])
         };
-        if let Some(index) = indices.iter().find(|i| i.fields.contains(&column_id)) {
+        let mut index = indices.iter().find(|i| i.fields.contains(&column_id));
+        if let (Some(idx), true) = (index, self.allow_degraded) {
+            for delta in self.dataset.load_indices_by_name(&idx.name).await? {
+                let uuid = delta.uuid.to_string();
+                let load = self.dataset.open_vector_index(q.column.as_str(), &uuid);
+                if !self.index_loads(idx, load).await? {
+                    index = None;
+                    break;
+                }
+            }
+        }
+        if let Some(index) = index {
             // There is an index built for the column.
             // We will use the index.
             if matches!(q.refine_factor, Some(0)) {
@@ -1041,6 +1548,18 @@ impl Scanner {
                 ));
             }
 
+            let mut q = q.clone();
+            if let Some(tuning) = &self.recall_tuning {
+                let params = tuned_for_recall(&self.dataset, index, &q, tuning).await?;
+                q.probe_fraction = q.probe_fraction.or(params.probe_fraction);
+                q.refine_factor = q.refine_factor.or(params.refine_factor);
+                q.ef = q.ef.or(params.ef);
+            }
+            if let (None, Some(tuning)) = (q.ef, &self.ef_tuning) {
+                q.ef = tuned_ef(&self.dataset, index, &q, tuning).await?;
+            }
+            let q = &q;
+
             // Find all deltas with the same index name.
             let deltas = self.dataset.load_indices_by_name(&index.name).await?;
             let ann_node = self.ann(q, &deltas, filter_plan).await?; // _distance, _rowid
@@ -1062,7 +1581,7 @@ impl Scanner {
 
             knn_node = self.knn_combined(&q, index, knn_node, filter_plan).await?;
 
-            Ok(knn_node)
+            self.with_null_partition(q, knn_node, filter_plan)
         } else {
             // No index found. use flat search.
             let mut columns = vec![q.column.clone()];
@@ -1082,10 +1601,76 @@ impl Scanner {
 
                 plan = Arc::new(FilterExec::try_new(physical_refine_expr, plan)?);
             }
-            Ok(self.flat_knn(plan, q)?)
+            let knn_node = self.flat_knn(plan, q)?;
+            self.with_null_partition(q, knn_node, filter_plan)
         }
     }
 
+    /// Add the rows with a null vector after the results of a vector search,
+    /// with a null distance, if the column maps them to a null partition.
+    fn with_null_partition(
+        &self,
+        q: &Query,
+        knn_node: Arc<dyn ExecutionPlan>,
+        filter_plan: &FilterPlan,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let field = self.dataset.schema().field(&q.column).unwrap();
+        if self.dataset.config().null_vectors(field)? != NullVectorHandling::NullPartition {
+            return Ok(knn_node);
+        }
+
+        let knn_schema = knn_node.schema();
+        let mut columns = knn_schema
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .filter(|name| name != DIST_COL && name != ROW_ID)
+            .collect::<Vec<_>>();
+        let mut predicate = datafusion::prelude::col(q.column.as_str()).is_null();
+        if let Some(expr) = filter_plan.full_expr.as_ref() {
+            columns.extend(Planner::column_names_in_expr(expr));
+            predicate = predicate.and(expr.clone());
+        }
+        let projection = Arc::new(self.dataset.schema().project(&columns)?);
+        let scan = self.scan(true, false, projection);
+        let planner = Planner::new(scan.schema());
+        let nulls = Arc::new(FilterExec::try_new(
+            planner.create_physical_expr(&predicate)?,
+            scan,
+        )?);
+
+        // Match the schema of the search results.
+        let exprs = knn_schema
+            .fields()
+            .iter()
+            .map(|f| {
+                let expr: Arc<dyn PhysicalExpr> = if f.name() == DIST_COL {
+                    Arc::new(Literal::new(ScalarValue::try_from(f.data_type())?))
+                } else {
+                    expressions::col(f.name(), nulls.schema().as_ref())?
+                };
+                Ok((expr, f.name().clone()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let nulls = Arc::new(DFProjectionExec::try_new(exprs, nulls)?);
+
+        let unioned = UnionExec::new(vec![knn_node, nulls]);
+        let unioned = RepartitionExec::try_new(
+            Arc::new(unioned),
+            datafusion::physical_plan::Partitioning::RoundRobinBatch(1),
+        )?;
+        let sort_expr = PhysicalSortExpr {
+            expr: expressions::col(DIST_COL, unioned.schema().as_ref())?,
+            options: SortOptions {
+                descending: false,
+                nulls_first: false,
+            },
+        };
+        Ok(Arc::new(
+            SortExec::new(vec![sort_expr], Arc::new(unioned)).with_fetch(Some(q.k)),
+        ))
+    }
+
     /// Combine ANN results with KNN results for data appended after index creation
     async fn knn_combined(
         &self,
@@ -1279,11 +1864,7 @@ impl Scanner {
         with_make_deletions_null: bool,
         projection: Arc<Schema>,
     ) -> Arc<dyn ExecutionPlan> {
-        let fragments = if let Some(fragment) = self.fragments.as_ref() {
-            Arc::new(fragment.clone())
-        } else {
-            self.dataset.fragments().clone()
-        };
+        let fragments = self.filtered_fragments();
         let ordered = if self.ordering.is_some() || self.nearest.is_some() {
             // If we are sorting the results there is no need to scan in order
             false
@@ -1299,6 +1880,22 @@ impl Scanner {
         )
     }
 
+    /// The fragments to scan, leaving out the partitions the filter can't match.
+    ///
+    /// This is only correct for scans whose rows are then filtered.
+    fn filtered_fragments(&self) -> Arc<Vec<Fragment>> {
+        let fragments = if let Some(fragment) = self.fragments.as_ref() {
+            Arc::new(fragment.clone())
+        } else {
+            self.dataset.fragments().clone()
+        };
+        self.filter
+            .as_ref()
+            .and_then(|filter| prune_fragments(&self.dataset, filter, &fragments))
+            .map(Arc::new)
+            .unwrap_or(fragments)
+    }
+
     fn scan_fragments(
         &self,
         with_row_id: bool,
@@ -1307,17 +1904,33 @@ impl Scanner {
         fragments: Arc<Vec<Fragment>>,
         ordered: bool,
     ) -> Arc<dyn ExecutionPlan> {
-        Arc::new(LanceScanExec::new(
+        let batch_size = self.get_batch_size(&projection);
+        let scan = LanceScanExec::new(
             self.dataset.clone(),
             fragments,
             projection,
-            self.get_batch_size(),
+            batch_size,
             self.batch_readahead,
             self.fragment_readahead,
             with_row_id,
             with_make_deletions_null,
             ordered,
-        ))
+        );
+        let scan = match &self.row_ranges {
+            Some(row_ranges) => scan.with_row_ranges(row_ranges.clone()),
+            None => scan,
+        };
+        let scan = match self.split_rows {
+            Some(split_rows) => scan.with_split_rows(split_rows),
+            None => scan,
+        };
+        let decode_threads = self
+            .decode_threads
+            .or(self.dataset.session.config().decode_threads);
+        match decode_threads {
+            Some(nthreads) => Arc::new(scan.with_decode_limit(DecodeLimit::new(nthreads))),
+            None => Arc::new(scan),
+        }
     }
 
     fn pushdown_scan(
@@ -1333,11 +1946,7 @@ impl Scanner {
             ordered_output: self.ordered,
         };
 
-        let fragments = if let Some(fragment) = self.fragments.as_ref() {
-            Arc::new(fragment.clone())
-        } else {
-            self.dataset.fragments().clone()
-        };
+        let fragments = self.filtered_fragments();
 
         Ok(Arc::new(LancePushdownScanExec::try_new(
             self.dataset.clone(),
@@ -1451,6 +2060,56 @@ impl Scanner {
 
         Ok(format!("{}", display.indent(verbose)))
     }
+
+    /// Render the plan of the scan in `format`, see [`explain_plan`].
+    pub async fn explain_plan_with_format(&self, format: ExplainFormat) -> Result<String> {
+        let plan = self.create_plan().await?;
+        explain_plan(plan.as_ref(), format)
+    }
+
+    /// Run the scan and return the metrics of each node of its plan.
+    ///
+    /// The peak memory of the whole execution is reported on the root node.
+    /// The results themselves are discarded.
+    #[instrument(skip_all)]
+    pub async fn analyze_plan(&self) -> Result<PlanMetrics> {
+        // Trace the plan here so its metrics are those of the executed nodes
+        let plan = trace_plan(self.create_plan().await?)?;
+        let memory_stats = Arc::new(ExecutionMemoryStats::default());
+        let options = LanceExecutionOptions {
+            bypass_tracing: true,
+            memory_stats: Some(memory_stats.clone()),
+            ..Default::default()
+        };
+        let stream = execute_plan(plan.clone(), options)?;
+        stream.try_for_each(|_| futures::future::ok(())).await?;
+        let mut metrics = PlanMetrics::from_plan(plan.as_ref());
+        metrics.peak_memory_bytes = Some(memory_stats.peak_bytes());
+        metrics.reservation_failures = Some(memory_stats.reservation_failures());
+        Ok(metrics)
+    }
+
+    /// Run the vector search and report what it did: the IVF partitions
+    /// probed in each delta index, the candidates examined, the results
+    /// re-ranked and the partitions served from the index cache.
+    ///
+    /// The results themselves are discarded.
+    pub async fn explain_search(&self) -> Result<SearchExplain> {
+        if self.nearest.is_none() {
+            return Err(Error::invalid_input(
+                "Only vector searches can be explained, call nearest() first",
+                location!(),
+            ));
+        }
+        let plan = trace_plan(self.create_plan().await?)?;
+        let options = LanceExecutionOptions {
+            bypass_tracing: true,
+            ..Default::default()
+        };
+        let stream = execute_plan(plan.clone(), options)?;
+        stream.try_for_each(|_| futures::future::ok(())).await?;
+        Ok(SearchExplain::from_plan(plan.as_ref()))
+    }
 }
 
 /// [`DatasetRecordBatchStream`] wraps the dataset into a [`RecordBatchStream`] for
@@ -1461,12 +2120,36 @@ pub struct DatasetRecordBatchStream {
     #[pin]
     exec_node: SendableRecordBatchStream,
     span: Span,
+    /// Records the scan into the session's query log, if enabled.
+    recorder: Option<QueryRecorder>,
+    /// Reports the scan if it runs too long, if enabled.
+    watch: Option<ScanWatch>,
+    timer: ScanTimer,
+    degraded_indices: Vec<String>,
 }
 
 impl DatasetRecordBatchStream {
     pub fn new(exec_node: SendableRecordBatchStream) -> Self {
         let span = info_span!("DatasetRecordBatchStream");
-        Self { exec_node, span }
+        Self {
+            exec_node,
+            span,
+            recorder: None,
+            watch: None,
+            timer: ScanTimer::new(),
+            degraded_indices: Vec::new(),
+        }
+    }
+
+    /// Whether the query was answered without some of the indices it should
+    /// have used, because they failed to load, see [`Scanner::allow_degraded`]
+    pub fn is_degraded(&self) -> bool {
+        !self.degraded_indices.is_empty()
+    }
+
+    /// The names of the indices the query was answered without
+    pub fn degraded_indices(&self) -> &[String] {
+        &self.degraded_indices
     }
 }
 
@@ -1484,6 +2167,22 @@ impl Stream for DatasetRecordBatchStream {
         let _guard = this.span.enter();
         match this.exec_node.poll_next_unpin(cx) {
             Poll::Ready(result) => {
+                match &result {
+                    Some(Ok(batch)) => this.timer.observe(batch.num_rows()),
+                    _ => this.timer.finish(),
+                }
+                if let Some(recorder) = this.recorder.as_mut() {
+                    match &result {
+                        Some(Ok(batch)) => recorder.observe(batch),
+                        _ => recorder.finish(),
+                    }
+                }
+                if let Some(watch) = this.watch.as_ref() {
+                    match &result {
+                        Some(Ok(batch)) => watch.observe(batch),
+                        _ => watch.finish(),
+                    }
+                }
                 Poll::Ready(result.map(|r| r.map_err(|e| Error::io(e.to_string(), location!()))))
             }
             Poll::Pending => Poll::Pending,
@@ -1493,7 +2192,27 @@ impl Stream for DatasetRecordBatchStream {
 
 impl From<DatasetRecordBatchStream> for SendableRecordBatchStream {
     fn from(stream: DatasetRecordBatchStream) -> Self {
-        stream.exec_node
+        let DatasetRecordBatchStream {
+            exec_node,
+            mut recorder,
+            watch,
+            mut timer,
+            ..
+        } = stream;
+        // Keep observing the scan, which is recorded once the stream is dropped
+        let schema = exec_node.schema();
+        let observed = exec_node.inspect(move |result| {
+            if let Ok(batch) = result {
+                timer.observe(batch.num_rows());
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.observe(batch);
+                }
+                if let Some(watch) = watch.as_ref() {
+                    watch.observe(batch);
+                }
+            }
+        });
+        Box::pin(RecordBatchStreamAdapter::new(schema, observed))
     }
 }
 
@@ -1647,7 +2366,7 @@ mod test {
     use arrow::array::as_primitive_array;
     use arrow::datatypes::Int32Type;
     use arrow_array::cast::AsArray;
-    use arrow_array::types::{Float32Type, UInt64Type};
+    use arrow_array::types::{Float32Type, Int64Type, UInt64Type};
     use arrow_array::{
         ArrayRef, FixedSizeListArray, Float16Array, Int32Array, LargeStringArray, PrimitiveArray,
         RecordBatchIterator, StringArray, StructArray,
@@ -1656,6 +2375,7 @@ mod test {
     use arrow_select::take;
     use datafusion::logical_expr::{col, lit};
     use half::f16;
+    use lance_datafusion::utils::estimated_row_width;
     use lance_datagen::{array, gen, BatchCount, Dimension, RowCount};
     use lance_index::IndexType;
     use lance_io::object_store::ObjectStoreParams;
@@ -1725,6 +2445,61 @@ mod test {
         }
     }
 
+    #[tokio::test]
+    async fn test_batch_size_bytes() {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, true),
+            ArrowField::new("s", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..40)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..40).map(|v| format!("s-{}", v)),
+                )),
+            ],
+        )
+        .unwrap();
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let write_params = WriteParams {
+            max_rows_per_group: 20,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        // Rows of both columns are estimated at 72 bytes, rows of `i` at 4
+        let row_width = estimated_row_width(&schema);
+        let mut builder = dataset.scan();
+        builder.batch_size_bytes(row_width * 8);
+        let batches = builder
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let lens = batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>();
+        assert_eq!(lens, vec![8, 8, 4, 8, 8, 4]);
+
+        let mut builder = dataset.scan();
+        builder.project(&["i"]).unwrap();
+        builder.batch_size_bytes(row_width * 8);
+        let batches = builder
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let lens = batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>();
+        assert_eq!(lens, vec![20, 20]);
+    }
+
     #[cfg(not(windows))]
     #[tokio::test]
     async fn test_local_object_store() {
@@ -1807,22 +2582,181 @@ mod test {
         Ok(())
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_decode_threads(#[values(false, true)] use_legacy_format: bool) -> Result<()> {
+        let test_ds = TestVectorDataset::new(use_legacy_format).await?;
+        let dataset = &test_ds.dataset;
+
+        let full_data = dataset.scan().try_into_batch().await?;
+
+        let actual = dataset
+            .scan()
+            .batch_size(8)
+            .decode_threads(1)
+            .try_into_batch()
+            .await?;
+
+        assert_eq!(actual, full_data);
+        Ok(())
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_limit(#[values(false, true)] use_legacy_format: bool) -> Result<()> {
         let test_ds = TestVectorDataset::new(use_legacy_format).await?;
         let dataset = &test_ds.dataset;
 
-        let full_data = dataset.scan().try_into_batch().await?.slice(19, 2);
+        let full_data = dataset.scan().try_into_batch().await?.slice(19, 2);
+
+        let actual = dataset
+            .scan()
+            .limit(Some(2), Some(19))?
+            .try_into_batch()
+            .await?;
+
+        assert_eq!(actual, full_data);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shard() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..1000))],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 200,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema),
+            test_uri,
+            Some(write_params),
+        )
+        .await
+        .unwrap();
+        dataset.delete("i % 10 = 0").await.unwrap();
+
+        for granularity in [ShardGranularity::Fragment, ShardGranularity::Rows] {
+            let mut all_values = vec![];
+            for rank in 0..3 {
+                let mut scanner = dataset.scan();
+                scanner.shard(3, rank, granularity).await.unwrap();
+                let batch = scanner.try_into_batch().await.unwrap();
+                assert_eq!(scanner.count_rows().await.unwrap(), batch.num_rows() as u64);
+                let values = batch["i"].as_primitive::<Int32Type>().values().to_vec();
+                if granularity == ShardGranularity::Rows {
+                    // Fragments have the same bytes per row
+                    assert!((values.len() as i64 - 300).abs() <= 5, "{}", values.len());
+                } else {
+                    assert!(values.len() % 180 == 0, "{}", values.len());
+                }
+                all_values.extend(values);
+            }
+            all_values.sort();
+            let expected = (0..1000).filter(|i| i % 10 != 0).collect::<Vec<_>>();
+            assert_eq!(all_values, expected, "{:?}", granularity);
+        }
+
+        let mut scanner = dataset.scan();
+        let err = scanner
+            .shard(3, 3, ShardGranularity::Fragment)
+            .await
+            .map(|_| ())
+            .unwrap_err();
+        assert!(err.to_string().contains("rank 3"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_scan_ranges() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..1000))],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 600,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema),
+            test_uri,
+            Some(write_params),
+        )
+        .await
+        .unwrap();
+        dataset.delete("i % 10 = 0").await.unwrap();
+        let expected = (0..1000).filter(|i| i % 10 != 0).collect::<Vec<_>>();
+
+        // Ranges of one fragment are read concurrently, but in order if the
+        // scan is ordered
+        let mut scanner = dataset.scan();
+        scanner.split_fragments(100).unwrap().batch_size(30);
+        let batch = scanner.try_into_batch().await.unwrap();
+        let values = batch["i"].as_primitive::<Int32Type>().values().to_vec();
+        assert_eq!(values, expected);
+        assert!(dataset.scan().split_fragments(0).is_err());
+
+        let ranges = dataset.scan().scan_ranges(250).await.unwrap();
+        let rows = ranges
+            .iter()
+            .map(|range| (range.fragment_id, range.rows.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                (0, 0..250),
+                (0, 250..500),
+                (0, 500..600),
+                (1, 0..250),
+                (1, 250..400)
+            ]
+        );
 
-        let actual = dataset
-            .scan()
-            .limit(Some(2), Some(19))?
-            .try_into_batch()
-            .await?;
+        // Workers scan disjoint ranges covering every row
+        let mut all_values = Vec::<i32>::new();
+        for worker_ranges in [&ranges[..2], &ranges[2..4], &ranges[4..]] {
+            let mut scanner = dataset.scan();
+            scanner.with_scan_ranges(worker_ranges).unwrap();
+            let batch = scanner.try_into_batch().await.unwrap();
+            all_values.extend(batch["i"].as_primitive::<Int32Type>().values().iter());
+        }
+        all_values.sort();
+        assert_eq!(all_values, expected);
 
-        assert_eq!(actual, full_data);
-        Ok(())
+        // Ranges must be within their fragment, and not reversed
+        let reversed = Range {
+            start: 300,
+            end: 200,
+        };
+        for rows in [0..601, reversed] {
+            let range = ScanRange {
+                fragment_id: 0,
+                rows,
+            };
+            let err = dataset
+                .scan()
+                .with_scan_ranges(&[range])
+                .map(|_| ())
+                .unwrap_err();
+            assert!(matches!(err, Error::InvalidInput { .. }), "{err}");
+        }
     }
 
     #[rstest]
@@ -1880,6 +2814,124 @@ mod test {
         }
     }
 
+    #[tokio::test]
+    async fn test_explain_plan_formats() {
+        let mut test_ds = TestVectorDataset::new(false).await.unwrap();
+        test_ds.make_vector_index().await.unwrap();
+        let key: Float32Array = (32..64).map(|v| v as f32).collect();
+        let mut scan = test_ds.dataset.scan();
+        scan.nearest("vec", &key, 5).unwrap().nprobs(2);
+
+        let text = scan
+            .explain_plan_with_format(ExplainFormat::Text { verbose: false })
+            .await
+            .unwrap();
+        assert_eq!(text, scan.explain_plan(false).await.unwrap());
+
+        let json = scan
+            .explain_plan_with_format(ExplainFormat::Json)
+            .await
+            .unwrap();
+        let root: crate::io::exec::ExplainNode = serde_json::from_str(&json).unwrap();
+        let mut nodes = vec![&root];
+        let mut details = HashMap::new();
+        while let Some(node) = nodes.pop() {
+            for (name, value) in &node.details {
+                details.insert((node.name.clone(), name.clone()), value.clone());
+            }
+            nodes.extend(node.children.iter());
+        }
+        let detail =
+            |node: &str, name: &str| details[&(node.to_string(), name.to_string())].clone();
+        assert_eq!(detail("ANNIvfPartition", "nprobes"), "2");
+        assert_eq!(detail("ANNSubIndex", "index"), "idx");
+        assert_eq!(detail("ANNSubIndex", "column"), "vec");
+
+        let mut scan = test_ds.dataset.scan();
+        scan.filter("i > 10").unwrap();
+        let json = scan
+            .explain_plan_with_format(ExplainFormat::Json)
+            .await
+            .unwrap();
+        assert!(json.contains("\"fragment_ids\""), "{}", json);
+
+        let dot = scan
+            .explain_plan_with_format(ExplainFormat::Graphviz)
+            .await
+            .unwrap();
+        assert!(dot.starts_with("digraph LancePlan {"), "{}", dot);
+        assert!(dot.contains("node1 -> node0;"), "{}", dot);
+        assert!(dot.contains("fragments=1"), "{}", dot);
+    }
+
+    #[tokio::test]
+    async fn test_explain_search() {
+        let mut test_ds = TestVectorDataset::new(false).await.unwrap();
+        test_ds.make_vector_index().await.unwrap();
+        let dataset = &test_ds.dataset;
+        let key: Float32Array = (32..64).map(|v| v as f32).collect();
+
+        let mut scan = dataset.scan();
+        assert!(scan.explain_search().await.is_err());
+        scan.nearest("vec", &key, 5).unwrap().nprobs(2).refine(2);
+        let explain = scan.explain_search().await.unwrap();
+        assert_eq!(explain.partitions.len(), 1);
+        let mut partitions = explain.partitions[0].1.clone();
+        partitions.sort();
+        assert_eq!(partitions, vec![0, 1]);
+        assert_eq!(explain.candidates, 400);
+        assert_eq!(explain.reranked, 10);
+        assert_eq!(explain.cache_hits + explain.cache_misses, 2);
+
+        // The partitions are cached by the first search.
+        let explain = scan.explain_search().await.unwrap();
+        assert_eq!(explain.cache_hits, 2);
+        assert_eq!(explain.cache_misses, 0);
+        assert!(explain.to_string().contains("partitions="));
+
+        let mut scan = dataset.scan();
+        scan.nearest("vec", &key, 5).unwrap();
+        let explain = scan.explain_search().await.unwrap();
+        assert_eq!(explain.partitions[0].1.len(), 1);
+        assert_eq!(explain.reranked, 0);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_plan() {
+        let test_ds = TestVectorDataset::new(false).await.unwrap();
+        let dataset = &test_ds.dataset;
+
+        let mut scan = dataset.scan();
+        scan.project(&["i"]).unwrap();
+        let metrics = scan.analyze_plan().await.unwrap();
+
+        fn find<'a>(metrics: &'a PlanMetrics, name: &str) -> Option<&'a PlanMetrics> {
+            if metrics.name == name {
+                return Some(metrics);
+            }
+            metrics.children.iter().find_map(|child| find(child, name))
+        }
+        let scan_metrics = find(&metrics, "LanceScan").unwrap();
+        assert!(scan_metrics.description.contains("projection=[i]"));
+        assert_eq!(
+            scan_metrics.output_rows,
+            Some(dataset.count_rows(None).await.unwrap())
+        );
+        assert!(scan_metrics.decoded_bytes.unwrap() > 0);
+        assert_eq!(scan_metrics.spill_count, None);
+        // Memory is only recorded for the whole execution, at the root, which
+        // may be the scan itself
+        assert!(metrics.peak_memory_bytes.is_some());
+        assert_eq!(metrics.reservation_failures, Some(0));
+        assert!(metrics
+            .children
+            .iter()
+            .all(|child| child.peak_memory_bytes.is_none()));
+
+        let json = serde_json::to_string(&metrics).unwrap();
+        assert_eq!(serde_json::from_str::<PlanMetrics>(&json).unwrap(), metrics);
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_knn_with_new_data(#[values(false, true)] use_legacy_format: bool) {
@@ -2444,6 +3496,64 @@ mod test {
         assert_eq!(batches_by_int_then_float[0], sorted_by_int_then_float);
     }
 
+    #[tokio::test]
+    async fn test_scan_order_modes() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values((0..100).rev()))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema);
+        let params = WriteParams {
+            max_rows_per_file: 10,
+            ..Default::default()
+        };
+        let dataset = Dataset::write(reader, test_uri, Some(params))
+            .await
+            .unwrap();
+
+        let mut scan = dataset.scan();
+        assert_eq!(scan.get_scan_order(), ScanOrder::FragmentOrder);
+        assert_eq!(scan.try_into_batch().await.unwrap(), batch);
+
+        scan.scan_order(ScanOrder::Unordered).unwrap();
+        assert_eq!(scan.get_scan_order(), ScanOrder::Unordered);
+        let mut values = scan.try_into_batch().await.unwrap()["i"]
+            .as_primitive::<Int32Type>()
+            .values()
+            .to_vec();
+        values.sort();
+        assert_eq!(values, (0..100).collect::<Vec<_>>());
+
+        let ordering = vec![ColumnOrdering::asc_nulls_first("i".to_string())];
+        scan.scan_order(ScanOrder::Sorted(ordering.clone()))
+            .unwrap()
+            .limit(Some(5), Some(2))
+            .unwrap();
+        assert_eq!(scan.get_scan_order(), ScanOrder::Sorted(ordering));
+        assert!(!scan.ordered);
+        assert!(scan
+            .explain_plan(false)
+            .await
+            .unwrap()
+            .contains("SortExec: TopK(fetch=7)"));
+        let values = scan.try_into_batch().await.unwrap();
+        assert_eq!(
+            values["i"].as_primitive::<Int32Type>().values(),
+            &[2, 3, 4, 5, 6]
+        );
+
+        assert!(scan.scan_order(ScanOrder::Sorted(vec![])).is_err());
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_ann_prefilter(#[values(false, true)] use_legacy_format: bool) {
@@ -3656,6 +4766,331 @@ mod test {
         assert!(second_index_scan_bytes < filtered_scan_bytes);
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_decode_cost_materialization(#[values(false, true)] use_legacy_format: bool) {
+        let data = gen()
+            .col("a", array::step::<Int32Type>())
+            .col("b", array::step::<Int32Type>())
+            .col("vec", array::rand_vec::<Float32Type>(Dimension::from(32)))
+            .into_reader_rows(RowCount::from(100), BatchCount::from(2));
+        let dataset = Dataset::write(
+            data,
+            "memory://test",
+            Some(WriteParams {
+                use_legacy_format,
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+
+        // A narrow column is cheaper to decode for every row than to take
+        assert_plan_equals(
+            &dataset,
+            |scan| scan.use_stats(false).project(&["b"])?.filter("a > 10"),
+            "Projection: fields=[b]
+  FilterExec: a@0 > 10
+    LanceScan: uri=..., projection=[a, b], row_id=false, ordered=true",
+        )
+        .await
+        .unwrap();
+
+        // A vector column is only decoded for the rows that pass the filter
+        assert_plan_equals(
+            &dataset,
+            |scan| scan.use_stats(false).project(&["vec"])?.filter("a > 10"),
+            "Projection: fields=[vec]
+  Take: columns=\"a, _rowid, vec\"
+    FilterExec: a@0 > 10
+      LanceScan: uri=..., projection=[a], row_id=true, ordered=true",
+        )
+        .await
+        .unwrap();
+
+        let batch = dataset
+            .scan()
+            .use_stats(false)
+            .project(&["b"])
+            .unwrap()
+            .filter("a > 10")
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+        assert_eq!(batch.num_rows(), 189);
+    }
+
+    #[tokio::test]
+    async fn test_degraded_index() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let data = gen()
+            .col("i", array::step::<Int32Type>())
+            .col("vec", array::rand_vec::<Float32Type>(Dimension::from(16)))
+            .into_reader_rows(RowCount::from(256), BatchCount::from(2));
+        let mut dataset = Dataset::write(data, test_uri, None).await.unwrap();
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        dataset
+            .create_index(
+                &["vec"],
+                IndexType::Vector,
+                None,
+                &VectorIndexParams::ivf_pq(2, 8, 2, MetricType::L2, 2),
+                false,
+            )
+            .await
+            .unwrap();
+        // Lose the files of both indices
+        for index in dataset.load_indices().await.unwrap().iter() {
+            let index_dir = test_dir
+                .path()
+                .join("_indices")
+                .join(index.uuid.to_string());
+            std::fs::remove_dir_all(index_dir).unwrap();
+        }
+        let dataset = Arc::new(Dataset::open(test_uri).await.unwrap());
+
+        let mut scan = dataset.scan();
+        scan.filter("i < 10").unwrap();
+        assert!(scan.try_into_batch().await.is_err());
+
+        scan.allow_degraded(true);
+        let stream = scan.try_into_stream().await.unwrap();
+        assert!(stream.is_degraded());
+        assert_eq!(stream.degraded_indices(), &["i_idx".to_string()]);
+        let batches = stream.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10);
+
+        let query = Float32Array::from(vec![0.0; 16]);
+        let mut scan = dataset.scan();
+        scan.allow_degraded(true).nearest("vec", &query, 5).unwrap();
+        let stream = scan.try_into_stream().await.unwrap();
+        assert_eq!(stream.degraded_indices(), &["vec_idx".to_string()]);
+        let batches = stream.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 5);
+
+        // Once dropped, the indices are not used any more
+        let mut dataset = Dataset::open(test_uri).await.unwrap();
+        dataset.drop_index("i_idx").await.unwrap();
+        dataset.drop_index("vec_idx").await.unwrap();
+        assert!(dataset.drop_index("vec_idx").await.is_err());
+        assert!(dataset.load_indices().await.unwrap().is_empty());
+        let stream = dataset
+            .scan()
+            .filter("i < 10")
+            .unwrap()
+            .try_into_stream()
+            .await
+            .unwrap();
+        assert!(!stream.is_degraded());
+        let batches = stream.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_lowercase_index() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "name",
+            DataType::Utf8,
+            true,
+        )]));
+        let batch = |names: Vec<Option<&str>>| {
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(StringArray::from(names))]).unwrap()
+        };
+        let reader = RecordBatchIterator::new(
+            vec![Ok(batch(vec![
+                Some("Bob"),
+                Some("alice"),
+                Some("BOBBY"),
+                None,
+                Some("Carol"),
+            ]))],
+            schema.clone(),
+        );
+        let mut dataset = Dataset::write(reader, test_uri, None).await.unwrap();
+        dataset
+            .create_index(
+                &["name"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams { lowercase: true },
+                false,
+            )
+            .await
+            .unwrap();
+        let indices = dataset.load_indices().await.unwrap();
+        assert_eq!(
+            indices[0].kind.as_deref(),
+            Some(lance_index::scalar::LOWERCASE_INDEX_KIND)
+        );
+
+        let check = |dataset: Dataset, filter: &'static str, expected: Vec<&'static str>| async move {
+            let mut scan = dataset.scan();
+            scan.filter(filter).unwrap();
+            let plan = scan.explain_plan(true).await.unwrap();
+            // Index searches that need no refining are materialized directly
+            assert!(plan.contains("query=lower(name)"), "{}", plan);
+            let batch = scan.try_into_batch().await.unwrap();
+            let mut names = batch
+                .column_by_name("name")
+                .unwrap()
+                .as_string::<i32>()
+                .iter()
+                .map(|name| name.unwrap().to_string())
+                .collect::<Vec<_>>();
+            names.sort();
+            assert_eq!(names, expected, "{}", filter);
+        };
+        check(dataset.clone(), "name ILIKE 'bo%'", vec!["BOBBY", "Bob"]).await;
+        check(dataset.clone(), "name ILIKE 'BOB'", vec!["Bob"]).await;
+        check(dataset.clone(), "lower(name) = 'carol'", vec!["Carol"]).await;
+
+        // Case-sensitive filters don't use the lowercase index
+        let mut scan = dataset.scan();
+        scan.filter("name = 'bob'").unwrap();
+        assert!(!scan.explain_plan(true).await.unwrap().contains("query="));
+        assert_eq!(scan.try_into_batch().await.unwrap().num_rows(), 0);
+
+        // Appended rows are found before and after they are indexed
+        let reader =
+            RecordBatchIterator::new(vec![Ok(batch(vec![Some("bOb"), Some("Dave")]))], schema);
+        dataset.append(reader, None).await.unwrap();
+        check(dataset.clone(), "name ILIKE 'bob'", vec!["Bob", "bOb"]).await;
+        dataset.optimize_indices(&Default::default()).await.unwrap();
+        check(dataset.clone(), "name ILIKE 'bob'", vec!["Bob", "bOb"]).await;
+        check(dataset.clone(), "name ILIKE 'd%'", vec!["Dave"]).await;
+
+        // Only string columns can have a lowercase index
+        let data = gen()
+            .col("i", array::step::<Int32Type>())
+            .into_reader_rows(RowCount::from(10), BatchCount::from(1));
+        let mut dataset = Dataset::write(data, "memory://ints", None).await.unwrap();
+        assert!(dataset
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams { lowercase: true },
+                false,
+            )
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_composite_index() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("tenant_id", DataType::Int32, false),
+            ArrowField::new("created_at", DataType::Int64, false),
+        ]));
+        // Row i belongs to tenant i % 4, and was created at i
+        let batch = |rows: std::ops::Range<i64>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(
+                        rows.clone().map(|i| (i % 4) as i32),
+                    )),
+                    Arc::new(Int64Array::from_iter_values(rows)),
+                ],
+            )
+            .unwrap()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(batch(0..100))], schema.clone());
+        let mut dataset = Dataset::write(reader, test_uri, None).await.unwrap();
+        dataset
+            .create_index(
+                &["tenant_id", "created_at"],
+                IndexType::Scalar,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        let indices = dataset.load_indices().await.unwrap();
+        assert_eq!(indices[0].name, "tenant_id_created_at_idx");
+        assert_eq!(indices[0].fields.len(), 2);
+
+        let check = |dataset: Dataset, filter: &'static str, expected: Vec<i64>| async move {
+            let mut scan = dataset.scan();
+            scan.filter(filter).unwrap();
+            let plan = scan.explain_plan(true).await.unwrap();
+            // Index searches that need no refining are materialized directly
+            assert!(plan.contains("query=tenant_id = "), "{}", plan);
+            let batch = scan.try_into_batch().await.unwrap();
+            let mut created_at = batch
+                .column_by_name("created_at")
+                .unwrap()
+                .as_primitive::<Int64Type>()
+                .values()
+                .to_vec();
+            created_at.sort();
+            assert_eq!(created_at, expected, "{}", filter);
+        };
+        let tenant = |tenant: i64, rows: std::ops::Range<i64>| {
+            rows.filter(|i| i % 4 == tenant).collect::<Vec<_>>()
+        };
+        check(
+            dataset.clone(),
+            "tenant_id = 1 AND created_at >= 50",
+            tenant(1, 50..100),
+        )
+        .await;
+        check(
+            dataset.clone(),
+            "created_at >= 10 AND created_at <= 20 AND tenant_id = 3",
+            tenant(3, 10..21),
+        )
+        .await;
+        check(dataset.clone(), "tenant_id = 2 AND created_at = 6", vec![6]).await;
+        // The leading column has no index of its own
+        check(dataset.clone(), "tenant_id = 0", tenant(0, 0..100)).await;
+
+        // Appended rows are found before and after they are indexed
+        let reader = RecordBatchIterator::new(vec![Ok(batch(100..120))], schema);
+        dataset.append(reader, None).await.unwrap();
+        check(
+            dataset.clone(),
+            "tenant_id = 1 AND created_at > 90",
+            tenant(1, 91..120),
+        )
+        .await;
+        dataset.optimize_indices(&Default::default()).await.unwrap();
+        check(
+            dataset.clone(),
+            "tenant_id = 1 AND created_at > 90",
+            tenant(1, 91..120),
+        )
+        .await;
+
+        // Only scalar indices can have several columns
+        assert!(dataset
+            .create_index(
+                &["tenant_id", "created_at"],
+                IndexType::Vector,
+                None,
+                &VectorIndexParams::ivf_pq(2, 8, 2, MetricType::L2, 2),
+                false,
+            )
+            .await
+            .is_err());
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_project_nested(#[values(false, true)] use_legacy_format: bool) -> Result<()> {
@@ -1,12 +1,15 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use crate::io::commit::commit_transaction;
 use crate::{io::exec::Planner, Error, Result};
 use arrow::compute::CastOptions;
-use arrow_array::RecordBatch;
+use arrow_array::{ArrayRef, RecordBatch, StructArray};
 use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
 use futures::stream::{StreamExt, TryStreamExt};
 use lance_arrow::SchemaExt;
@@ -65,6 +68,9 @@ pub struct ColumnAlteration {
     pub nullable: Option<bool>,
     /// The new data type of the column. If None, the data type will not be changed.
     pub data_type: Option<DataType>,
+    /// Metadata entries to set on the column, replacing the existing values
+    /// of the same keys.
+    pub metadata: HashMap<String, String>,
 }
 
 impl ColumnAlteration {
@@ -74,6 +80,7 @@ impl ColumnAlteration {
             rename: None,
             nullable: None,
             data_type: None,
+            metadata: HashMap::new(),
         }
     }
 
@@ -91,6 +98,11 @@ impl ColumnAlteration {
         self.data_type = Some(data_type);
         self
     }
+
+    pub fn set_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
 }
 
 /// Limit casts to same type. This is mostly to filter out weird casts like
@@ -117,13 +129,84 @@ fn is_upcast_downcast(from_type: &DataType, to_type: &DataType) -> bool {
     }
 }
 
+/// We just transform the SQL expressions into a UDF backed by DataFusion
+/// physical expressions. Returns the UDF and the columns it reads.
+fn sql_expressions_udf(
+    dataset: &Dataset,
+    expressions: Vec<(String, String)>,
+) -> Result<(BatchUDF, Option<Vec<String>>)> {
+    let arrow_schema = Arc::new(ArrowSchema::from(dataset.schema()));
+    let planner = Planner::new(arrow_schema);
+    let exprs = expressions
+        .into_iter()
+        .map(|(name, expr)| {
+            let expr = planner.parse_expr(&expr)?;
+            let expr = planner.optimize_expr(expr)?;
+            Ok((name, expr))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let needed_columns = exprs
+        .iter()
+        .flat_map(|(_, expr)| Planner::column_names_in_expr(expr))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let read_schema = dataset.schema().project(&needed_columns)?;
+    let read_schema = Arc::new(ArrowSchema::from(&read_schema));
+    // Need to re-create the planner with the read schema because physical
+    // expressions use positional column references.
+    let planner = Planner::new(read_schema.clone());
+    let exprs = exprs
+        .into_iter()
+        .map(|(name, expr)| {
+            let expr = planner.create_physical_expr(&expr)?;
+            Ok((name, expr))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let output_schema = Arc::new(ArrowSchema::new(
+        exprs
+            .iter()
+            .map(|(name, expr)| {
+                Ok(ArrowField::new(
+                    name,
+                    expr.data_type(read_schema.as_ref())?,
+                    expr.nullable(read_schema.as_ref())?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?,
+    ));
+
+    let schema_ref = output_schema.clone();
+    let mapper = move |batch: &RecordBatch| {
+        let num_rows = batch.num_rows();
+        let columns = exprs
+            .iter()
+            .map(|(_, expr)| Ok(expr.evaluate(batch)?.into_array(num_rows)?))
+            .collect::<Result<Vec<_>>>()?;
+
+        let batch = RecordBatch::try_new(schema_ref.clone(), columns)?;
+        Ok(batch)
+    };
+    let mapper = Box::new(mapper);
+
+    let read_columns = Some(read_schema.field_names().into_iter().cloned().collect());
+    Ok((
+        BatchUDF {
+            mapper,
+            output_schema,
+            result_checkpoint: None,
+        },
+        read_columns,
+    ))
+}
+
 pub(super) async fn add_columns(
     dataset: &mut Dataset,
     transforms: NewColumnTransform,
     read_columns: Option<Vec<String>>,
 ) -> Result<()> {
-    // We just transform the SQL expression into a UDF backed by DataFusion
-    // physical expressions.
     let (
         BatchUDF {
             mapper,
@@ -134,71 +217,7 @@ pub(super) async fn add_columns(
     ) = match transforms {
         NewColumnTransform::BatchUDF(udf) => (udf, read_columns),
         NewColumnTransform::SqlExpressions(expressions) => {
-            let arrow_schema = Arc::new(ArrowSchema::from(dataset.schema()));
-            let planner = Planner::new(arrow_schema);
-            let exprs = expressions
-                .into_iter()
-                .map(|(name, expr)| {
-                    let expr = planner.parse_expr(&expr)?;
-                    let expr = planner.optimize_expr(expr)?;
-                    Ok((name, expr))
-                })
-                .collect::<Result<Vec<_>>>()?;
-
-            let needed_columns = exprs
-                .iter()
-                .flat_map(|(_, expr)| Planner::column_names_in_expr(expr))
-                .collect::<HashSet<_>>()
-                .into_iter()
-                .collect::<Vec<_>>();
-            let read_schema = dataset.schema().project(&needed_columns)?;
-            let read_schema = Arc::new(ArrowSchema::from(&read_schema));
-            // Need to re-create the planner with the read schema because physical
-            // expressions use positional column references.
-            let planner = Planner::new(read_schema.clone());
-            let exprs = exprs
-                .into_iter()
-                .map(|(name, expr)| {
-                    let expr = planner.create_physical_expr(&expr)?;
-                    Ok((name, expr))
-                })
-                .collect::<Result<Vec<_>>>()?;
-
-            let output_schema = Arc::new(ArrowSchema::new(
-                exprs
-                    .iter()
-                    .map(|(name, expr)| {
-                        Ok(ArrowField::new(
-                            name,
-                            expr.data_type(read_schema.as_ref())?,
-                            expr.nullable(read_schema.as_ref())?,
-                        ))
-                    })
-                    .collect::<Result<Vec<_>>>()?,
-            ));
-
-            let schema_ref = output_schema.clone();
-            let mapper = move |batch: &RecordBatch| {
-                let num_rows = batch.num_rows();
-                let columns = exprs
-                    .iter()
-                    .map(|(_, expr)| Ok(expr.evaluate(batch)?.into_array(num_rows)?))
-                    .collect::<Result<Vec<_>>>()?;
-
-                let batch = RecordBatch::try_new(schema_ref.clone(), columns)?;
-                Ok(batch)
-            };
-            let mapper = Box::new(mapper);
-
-            let read_columns = Some(read_schema.field_names().into_iter().cloned().collect());
-            (
-                BatchUDF {
-                    mapper,
-                    output_schema,
-                    result_checkpoint: None,
-                },
-                read_columns,
-            )
+            sql_expressions_udf(dataset, expressions)?
         }
     };
 
@@ -302,6 +321,164 @@ async fn add_columns_impl(
     Ok(fragments)
 }
 
+/// Rewrite the values of existing columns of the dataset.
+///
+/// The transform returns a column for each column to rewrite, named by its
+/// path, e.g. `metadata.tags`. Each rewritten column gets new field ids and
+/// only its data is written, so a leaf of a struct can be rewritten without
+/// rewriting its siblings.
+pub(super) async fn update_columns(
+    dataset: &mut Dataset,
+    transforms: NewColumnTransform,
+    read_columns: Option<Vec<String>>,
+) -> Result<()> {
+    let (
+        BatchUDF {
+            mapper,
+            output_schema,
+            result_checkpoint,
+        },
+        read_columns,
+    ) = match transforms {
+        NewColumnTransform::BatchUDF(udf) => (udf, read_columns),
+        NewColumnTransform::SqlExpressions(expressions) => {
+            sql_expressions_udf(dataset, expressions)?
+        }
+    };
+
+    let mut new_schema = dataset.schema().clone();
+    let mut next_field_id = dataset.manifest.max_field_id() + 1;
+    let mut new_ids = Vec::with_capacity(output_schema.fields().len());
+    for output_field in output_schema.fields() {
+        let path = output_field.name();
+        let field_src = dataset.schema().field(path).ok_or_else(|| {
+            Error::invalid_input(
+                format!("Column \"{}\" does not exist in the dataset", path),
+                location!(),
+            )
+        })?;
+        if matches!(field_src.data_type(), DataType::Struct(_)) {
+            return Err(Error::invalid_input(
+                format!("Column \"{}\" is a struct, update its fields instead", path),
+                location!(),
+            ));
+        }
+        if !lance_arrow::cast::can_cast_types(output_field.data_type(), &field_src.data_type()) {
+            return Err(Error::invalid_input(
+                format!(
+                    "Cannot write {:?} values to column \"{}\" of type {:?}",
+                    output_field.data_type(),
+                    path,
+                    field_src.data_type()
+                ),
+                location!(),
+            ));
+        }
+
+        let field_dest = new_schema.mut_field_by_id(field_src.id).unwrap();
+        *field_dest = Field::try_from(&ArrowField::from(&*field_dest))?;
+        field_dest.set_id(field_src.parent_id, &mut next_field_id);
+        new_ids.push(field_dest.id);
+    }
+
+    new_schema.validate()?;
+
+    // This schema contains the rewritten fields, with their ancestors, and
+    // the exact field ids we want to write them with.
+    let write_schema = new_schema.project_by_ids(&new_ids);
+    let arrow_write_schema = Arc::new(ArrowSchema::from(&write_schema));
+    let mapper = move |batch: &RecordBatch| {
+        let values = mapper(batch)?;
+        let columns = arrow_write_schema
+            .fields()
+            .iter()
+            .map(|field| updated_column(&values, field, field.name()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new(arrow_write_schema.clone(), columns)?)
+    };
+    let mapper = Box::new(mapper);
+
+    let fragments = add_columns_impl(
+        dataset,
+        read_columns,
+        mapper,
+        result_checkpoint,
+        Some((write_schema, new_schema.clone())),
+    )
+    .await?;
+    let fragments = remove_unused_files(fragments, &new_schema);
+
+    let transaction = Transaction::new(
+        dataset.manifest.version,
+        Operation::Merge {
+            schema: new_schema,
+            fragments,
+        },
+        None,
+    );
+    let manifest = commit_transaction(
+        dataset,
+        &dataset.object_store,
+        dataset.commit_handler.as_ref(),
+        &transaction,
+        &Default::default(),
+        &Default::default(),
+    )
+    .await?;
+
+    dataset.manifest = Arc::new(manifest);
+
+    Ok(())
+}
+
+/// The column of `field`, at `path`, built from the columns of `values`,
+/// which are named by their paths.
+fn updated_column(values: &RecordBatch, field: &ArrowField, path: &str) -> Result<ArrayRef> {
+    match (field.data_type(), values.column_by_name(path)) {
+        (_, Some(column)) => Ok(lance_arrow::cast::cast_with_options(
+            column,
+            field.data_type(),
+            // Safe: false means it will error if the cast is lossy.
+            &CastOptions {
+                safe: false,
+                ..Default::default()
+            },
+        )?),
+        (DataType::Struct(children), None) => {
+            let columns = children
+                .iter()
+                .map(|child| updated_column(values, child, &format!("{}.{}", path, child.name())))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Arc::new(StructArray::try_new(
+                children.clone(),
+                columns,
+                None,
+            )?))
+        }
+        (_, None) => Err(Error::invalid_input(
+            format!("The transform did not return column \"{}\"", path),
+            location!(),
+        )),
+    }
+}
+
+/// Some data files may no longer contain any columns in the dataset (e.g. if every
+/// remaining column has been altered into a different data file) and so we remove them
+fn remove_unused_files(fragments: Vec<Fragment>, schema: &Schema) -> Vec<Fragment> {
+    let schema_field_ids = schema.field_ids().into_iter().collect::<Vec<_>>();
+    fragments
+        .into_iter()
+        .map(|mut frag| {
+            frag.files.retain(|f| {
+                f.fields
+                    .iter()
+                    .any(|field| schema_field_ids.contains(field))
+            });
+            frag
+        })
+        .collect::<Vec<_>>()
+}
+
 /// Modify columns in the dataset, changing their name, type, or nullability.
 ///
 /// If a column has an index, it's index will be preserved.
@@ -343,6 +520,7 @@ pub(super) async fn alter_columns(
         }
 
         let field_dest = new_schema.mut_field_by_id(field_src.id).unwrap();
+        let mut field_dest_id = field_dest.id;
         if let Some(rename) = &alteration.rename {
             field_dest.name.clone_from(rename);
         }
@@ -372,9 +550,15 @@ pub(super) async fn alter_columns(
             );
             *field_dest = Field::try_from(&arrow_field)?;
             field_dest.set_id(field_src.parent_id, &mut next_field_id);
+            field_dest_id = field_dest.id;
 
             cast_fields.push((field_src.clone(), field_dest.clone()));
         }
+
+        if !alteration.metadata.is_empty() {
+            let field_dest = new_schema.mut_field_by_id(field_dest_id).unwrap();
+            field_dest.metadata.extend(alteration.metadata.clone());
+        }
     }
 
     new_schema.validate()?;
@@ -435,20 +619,7 @@ pub(super) async fn alter_columns(
         )
         .await?;
 
-        // Some data files may no longer contain any columns in the dataset (e.g. if every
-        // remaining column has been altered into a different data file) and so we remove them
-        let schema_field_ids = new_schema.field_ids().into_iter().collect::<Vec<_>>();
-        let fragments = fragments
-            .into_iter()
-            .map(|mut frag| {
-                frag.files.retain(|f| {
-                    f.fields
-                        .iter()
-                        .any(|field| schema_field_ids.contains(field))
-                });
-                frag
-            })
-            .collect::<Vec<_>>();
+        let fragments = remove_unused_files(fragments, &new_schema);
 
         Transaction::new(
             dataset.manifest.version,
@@ -797,6 +968,8 @@ mod test {
                         deletion_file: None,
                         row_id_meta: None,
                         physical_rows: Some(50),
+                        partition: None,
+                        delta: false,
                     }))
                 } else {
                     Ok(None)
@@ -1210,6 +1383,144 @@ mod test {
         Ok(())
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_update_nested_column(
+        #[values(false, true)] use_legacy_format: bool,
+    ) -> Result<()> {
+        use arrow_array::{cast::AsArray, types::Int32Type, StringArray};
+
+        let metadata_fields = ArrowFields::from(vec![
+            ArrowField::new("tags", DataType::Utf8, true),
+            ArrowField::new("count", DataType::Int32, true),
+        ]);
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("metadata", DataType::Struct(metadata_fields.clone()), true),
+        ]));
+        let nrows = 100;
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..nrows)),
+                Arc::new(StructArray::new(
+                    metadata_fields,
+                    vec![
+                        Arc::new(StringArray::from_iter_values(
+                            (0..nrows).map(|i| format!("tag{}", i)),
+                        )),
+                        Arc::new(Int32Array::from_iter_values(0..nrows)),
+                    ],
+                    None,
+                )),
+            ],
+        )?;
+
+        let test_dir = tempfile::tempdir()?;
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone()),
+            test_uri,
+            Some(WriteParams {
+                max_rows_per_file: 50,
+                use_legacy_format,
+                ..Default::default()
+            }),
+        )
+        .await?;
+        let original_schema = dataset.schema().clone();
+        let tags_id = original_schema.field("metadata.tags").unwrap().id;
+
+        // Columns must exist and can't be structs
+        let res = dataset
+            .update_columns(
+                NewColumnTransform::SqlExpressions(vec![("metadata.size".into(), "1".into())]),
+                None,
+            )
+            .await;
+        assert!(matches!(res, Err(Error::InvalidInput { .. })));
+        let res = dataset
+            .update_columns(
+                NewColumnTransform::SqlExpressions(vec![("metadata".into(), "id".into())]),
+                None,
+            )
+            .await;
+        assert!(matches!(res, Err(Error::InvalidInput { .. })));
+
+        // Rewrite a leaf from a SQL expression
+        dataset
+            .update_columns(
+                NewColumnTransform::SqlExpressions(vec![(
+                    "metadata.count".into(),
+                    "metadata.count * 2".into(),
+                )]),
+                None,
+            )
+            .await?;
+        dataset.validate().await?;
+
+        // Rewrite a leaf with a UDF
+        let output_schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "metadata.tags",
+            DataType::Utf8,
+            true,
+        )]));
+        let udf_schema = output_schema.clone();
+        dataset
+            .update_columns(
+                NewColumnTransform::BatchUDF(BatchUDF {
+                    mapper: Box::new(move |batch| {
+                        let ids = batch["id"].as_primitive::<Int32Type>();
+                        let tags = StringArray::from_iter_values(
+                            ids.values().iter().map(|id| format!("new_tag{}", id)),
+                        );
+                        Ok(RecordBatch::try_new(
+                            udf_schema.clone(),
+                            vec![Arc::new(tags)],
+                        )?)
+                    }),
+                    output_schema,
+                    result_checkpoint: None,
+                }),
+                Some(vec!["id".into()]),
+            )
+            .await?;
+        dataset.validate().await?;
+
+        // The schema keeps its shape, and only the rewritten fields have new ids
+        assert_eq!(
+            ArrowSchema::from(dataset.schema()),
+            ArrowSchema::from(&original_schema)
+        );
+        assert_eq!(dataset.schema().field("id").unwrap().id, 0);
+        assert_eq!(
+            dataset.schema().field("metadata").unwrap().id,
+            original_schema.field("metadata").unwrap().id
+        );
+        let new_tags_id = dataset.schema().field("metadata.tags").unwrap().id;
+        assert_ne!(new_tags_id, tags_id);
+
+        // Each rewrite only writes the struct and the rewritten leaf
+        for fragment in dataset.fragments().iter() {
+            assert_eq!(fragment.files.len(), 3);
+            let metadata_id = dataset.schema().field("metadata").unwrap().id;
+            assert_eq!(fragment.files[2].fields, vec![metadata_id, new_tags_id]);
+        }
+
+        let data = dataset.scan().try_into_batch().await?;
+        let metadata = data["metadata"].as_struct();
+        assert_eq!(
+            metadata["tags"].as_string::<i32>(),
+            &StringArray::from_iter_values((0..nrows).map(|i| format!("new_tag{}", i)))
+        );
+        assert_eq!(
+            metadata["count"].as_primitive::<Int32Type>(),
+            &Int32Array::from_iter_values((0..nrows).map(|i| i * 2))
+        );
+
+        Ok(())
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_drop_columns(#[values(false, true)] use_legacy_format: bool) -> Result<()> {
@@ -0,0 +1,189 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Split the fragments of a scan between distributed workers
+//!
+//! Fragments are weighted by the bytes of their data files, so every worker
+//! reads about the same number of bytes. The split only depends on the
+//! fragments and their files, so every worker computes the same split on its
+//! own and reads rows no other worker reads.
+//!
+//! Planners that assign work to workers themselves can instead split a scan
+//! into [`ScanRange`]s.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+use futures::{stream, StreamExt, TryStreamExt};
+use lance_core::{Error, Result};
+use lance_table::format::Fragment;
+use serde::{Deserialize, Serialize};
+use snafu::{location, Location};
+
+use super::fragment::FileFragment;
+use super::Dataset;
+
+/// How [`Scanner::shard`](super::scanner::Scanner::shard) splits the rows
+/// between workers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardGranularity {
+    /// Each worker reads whole fragments.
+    ///
+    /// This reads files as efficiently as an unsharded scan, but the bytes
+    /// are only balanced if there are many more fragments than workers.
+    Fragment,
+    /// Each worker reads a contiguous range of rows, which may start or end
+    /// in the middle of a fragment.
+    ///
+    /// The bytes are balanced whatever the number of fragments.
+    Rows,
+}
+
+/// The fragments `rank` of `world_size` workers reads, in the order of
+/// `fragments`, and the rows it reads of the fragments it doesn't read whole,
+/// by fragment id.
+pub(super) async fn shard_fragments(
+    dataset: &Arc<Dataset>,
+    fragments: &[Fragment],
+    world_size: usize,
+    rank: usize,
+    granularity: ShardGranularity,
+) -> Result<(Vec<Fragment>, HashMap<u64, Vec<Range<u32>>>)> {
+    if world_size == 0 || rank >= world_size {
+        return Err(Error::invalid_input(
+            format!(
+                "Invalid shard: rank {} of a world of size {}",
+                rank, world_size
+            ),
+            location!(),
+        ));
+    }
+
+    let sizes = stream::iter(fragments)
+        .map(|fragment| fragment_size(dataset, fragment))
+        .buffered(num_cpus::get() * 4)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    match granularity {
+        ShardGranularity::Fragment => {
+            // Largest fragments first, each to the worker with the fewest bytes
+            let mut order = (0..fragments.len()).collect::<Vec<_>>();
+            order.sort_by_key(|&i| (std::cmp::Reverse(sizes[i].0), fragments[i].id));
+            let mut loads = vec![0; world_size];
+            let mut selected = vec![false; fragments.len()];
+            for i in order {
+                let (worker, _) = loads
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(worker, load)| (**load, *worker))
+                    .unwrap();
+                loads[worker] += sizes[i].0;
+                selected[i] = worker == rank;
+            }
+            let fragments = fragments
+                .iter()
+                .zip(selected)
+                .filter(|(_, selected)| *selected)
+                .map(|(fragment, _)| fragment.clone())
+                .collect();
+            Ok((fragments, HashMap::new()))
+        }
+        ShardGranularity::Rows => {
+            // Workers read equal slices of the bytes of all fragments, and a
+            // fragment's bytes are assumed to be spread evenly over its rows.
+            let total = sizes.iter().map(|(bytes, _)| *bytes as u128).sum::<u128>();
+            let start = total * rank as u128 / world_size as u128;
+            let end = total * (rank as u128 + 1) / world_size as u128;
+            let mut selected = Vec::new();
+            let mut ranges = HashMap::new();
+            let mut offset = 0;
+            for (fragment, (bytes, num_rows)) in fragments.iter().zip(sizes) {
+                let (bytes, num_rows) = (bytes as u128, num_rows as u128);
+                let row_at = |position: u128| {
+                    (position.clamp(offset, offset + bytes) - offset) * num_rows / bytes
+                };
+                let rows = row_at(start)..row_at(end);
+                offset += bytes;
+                if rows.is_empty() {
+                    continue;
+                }
+                selected.push(fragment.clone());
+                if rows != (0..num_rows) {
+                    ranges.insert(
+                        fragment.id,
+                        std::iter::once(rows.start as u32..rows.end as u32).collect(),
+                    );
+                }
+            }
+            Ok((selected, ranges))
+        }
+    }
+}
+
+/// A range of rows of a fragment, a unit of work of a distributed scan, see
+/// [`Scanner::scan_ranges`](super::scanner::Scanner::scan_ranges).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanRange {
+    pub fragment_id: u64,
+    /// The offsets of the rows in the fragment, counting deleted rows.
+    pub rows: Range<u32>,
+}
+
+/// The rows of `fragments`, or the ranges of `row_ranges` of the fragments
+/// they are given for, as ranges of at most `max_rows` rows, in order.
+pub(super) async fn split_scan_ranges(
+    dataset: &Arc<Dataset>,
+    fragments: &[Fragment],
+    row_ranges: Option<&HashMap<u64, Vec<Range<u32>>>>,
+    max_rows: u32,
+) -> Result<Vec<ScanRange>> {
+    if max_rows == 0 {
+        return Err(Error::invalid_input(
+            "Scan ranges must have at least one row",
+            location!(),
+        ));
+    }
+    let num_rows = stream::iter(fragments)
+        .map(|fragment| {
+            let fragment = FileFragment::new(dataset.clone(), fragment.clone());
+            async move { fragment.physical_rows().await }
+        })
+        .buffered(num_cpus::get() * 4)
+        .try_collect::<Vec<_>>()
+        .await?;
+    let mut scan_ranges = Vec::new();
+    for (fragment, num_rows) in fragments.iter().zip(num_rows) {
+        let ranges = match row_ranges.and_then(|ranges| ranges.get(&fragment.id)) {
+            Some(ranges) => ranges.clone(),
+            None => std::iter::once(0..num_rows as u32).collect(),
+        };
+        for range in ranges {
+            scan_ranges.extend(
+                range
+                    .clone()
+                    .step_by(max_rows as usize)
+                    .map(|start| ScanRange {
+                        fragment_id: fragment.id,
+                        rows: start..range.end.min(start.saturating_add(max_rows)),
+                    }),
+            );
+        }
+    }
+    Ok(scan_ranges)
+}
+
+/// The bytes of the data files of a fragment, at least 1, and its number of
+/// rows, including deleted rows.
+async fn fragment_size(dataset: &Arc<Dataset>, fragment: &Fragment) -> Result<(u64, usize)> {
+    let num_rows = FileFragment::new(dataset.clone(), fragment.clone())
+        .physical_rows()
+        .await?;
+    let mut bytes = 0;
+    for data_file in &fragment.files {
+        let path = dataset.data_dir().child(data_file.path.as_str());
+        bytes += dataset.object_store.size(&path).await? as u64;
+    }
+    Ok((bytes.max(1), num_rows))
+}
@@ -0,0 +1,277 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Count-distinct and heavy-hitters sketches of columns
+//!
+//! When [`WriteParams::sketch_columns`](super::WriteParams::sketch_columns) is
+//! set, each written data file gets a sketch file with a [`ColumnSketch`] of
+//! those columns. [`Dataset::column_sketch`] merges the sketches of all
+//! fragments, so the number of distinct values and the most frequent values
+//! of a column are estimated without scanning it. Fragments without a sketch,
+//! or with deleted rows, are scanned instead.
+
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use futures::{stream, StreamExt, TryStreamExt};
+use lance_core::datatypes::{Field, Schema};
+use lance_core::{Error, Result};
+use lance_io::object_store::ObjectStore;
+use lance_table::format::{ColumnSketch, DataFile, DataFileSketches};
+use lance_table::io::sketch::{read_sketch_file, sketch_file_path, write_sketch_file};
+use object_store::path::Path;
+use snafu::{location, Location};
+
+use super::write::GenericWriter;
+use super::Dataset;
+
+/// The top-level fields of `schema` named in `columns`, checking they can be
+/// sketched
+pub(super) fn sketch_fields(schema: &Schema, columns: &[String]) -> Result<Vec<Field>> {
+    columns
+        .iter()
+        .map(|column| {
+            let field = schema
+                .fields
+                .iter()
+                .find(|field| &field.name == column)
+                .ok_or_else(|| {
+                    Error::invalid_input(
+                        format!("Cannot sketch {column}: no top-level column with this name"),
+                        location!(),
+                    )
+                })?;
+            if !ColumnSketch::supports(&field.data_type()) {
+                return Err(Error::invalid_input(
+                    format!(
+                        "Cannot sketch {column}: columns of type {} are not supported",
+                        field.data_type()
+                    ),
+                    location!(),
+                ));
+            }
+            Ok(field.clone())
+        })
+        .collect()
+}
+
+/// A writer that sketches the written values of some columns, and writes
+/// them next to the data file when it is finished
+pub(super) struct SketchingWriter {
+    inner: Box<dyn GenericWriter>,
+    object_store: Arc<ObjectStore>,
+    base_dir: Path,
+    /// Sketched fields and their sketches
+    sketches: Vec<(Field, ColumnSketch)>,
+}
+
+impl SketchingWriter {
+    pub fn try_new(
+        inner: Box<dyn GenericWriter>,
+        object_store: Arc<ObjectStore>,
+        base_dir: &Path,
+        fields: &[Field],
+    ) -> Result<Self> {
+        let sketches = fields
+            .iter()
+            .map(|field| Ok((field.clone(), ColumnSketch::try_new(&field.data_type())?)))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            inner,
+            object_store,
+            base_dir: base_dir.clone(),
+            sketches,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl GenericWriter for SketchingWriter {
+    fn multipart_id(&self) -> &str {
+        self.inner.multipart_id()
+    }
+    async fn write(&mut self, batches: &[RecordBatch]) -> Result<()> {
+        for batch in batches {
+            for (field, sketch) in &mut self.sketches {
+                if let Some(values) = batch.column_by_name(&field.name) {
+                    sketch.update(values.as_ref())?;
+                }
+            }
+        }
+        self.inner.write(batches).await
+    }
+    async fn tell(&mut self) -> Result<u64> {
+        self.inner.tell().await
+    }
+    async fn finish(&mut self) -> Result<(u32, DataFile)> {
+        let (num_rows, data_file) = self.inner.finish().await?;
+        let sketches = self
+            .sketches
+            .iter()
+            .map(|(field, sketch)| (field.id, sketch.clone()))
+            .collect::<DataFileSketches>();
+        write_sketch_file(
+            &self.object_store,
+            &sketch_file_path(&self.base_dir, &data_file),
+            &sketches,
+        )
+        .await?;
+        Ok((num_rows, data_file))
+    }
+}
+
+pub(super) async fn column_sketch(dataset: &Dataset, column: &str) -> Result<ColumnSketch> {
+    let field = dataset.schema().field(column).ok_or_else(|| {
+        Error::invalid_input(format!("No column with name {}", column), location!())
+    })?;
+    let field_id = field.id;
+    let mut sketch = ColumnSketch::try_new(&field.data_type())?;
+
+    // The stored sketch of each fragment, if it has one and no deleted rows
+    let stored = stream::iter(dataset.get_fragments())
+        .map(|fragment| async move {
+            let metadata = fragment.metadata();
+            let data_file = metadata
+                .files
+                .iter()
+                .find(|data_file| data_file.fields.contains(&field_id));
+            let stored = match data_file {
+                Some(data_file) if metadata.deletion_file.is_none() => {
+                    let path = sketch_file_path(&dataset.base, data_file);
+                    read_sketch_file(&dataset.object_store, &path)
+                        .await?
+                        .and_then(|mut sketches| sketches.remove(&field_id))
+                }
+                _ => None,
+            };
+            Ok::<_, Error>((metadata.clone(), stored))
+        })
+        .buffered(num_cpus::get() * 4)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let mut unsketched = Vec::new();
+    for (fragment, stored) in stored {
+        match stored {
+            Some(stored) => sketch.merge(&stored)?,
+            None => unsketched.push(fragment),
+        }
+    }
+    if !unsketched.is_empty() {
+        let mut scanner = dataset.scan();
+        scanner.with_fragments(unsketched).project(&[column])?;
+        let mut batches = scanner.try_into_stream().await?;
+        while let Some(batch) = batches.try_next().await? {
+            sketch.update(batch.column(0).as_ref())?;
+        }
+    }
+    Ok(sketch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{cast::AsArray, Array, Int32Array, RecordBatchIterator, StringArray};
+    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use lance_table::io::sketch::SKETCH_DIR;
+    use tempfile::tempdir;
+
+    use crate::dataset::{WriteMode, WriteParams};
+
+    fn batch(ids: std::ops::Range<i32>) -> RecordBatch {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("category", DataType::Utf8, true),
+        ]));
+        let categories = StringArray::from_iter_values(ids.clone().map(|id| {
+            if id % 4 == 0 {
+                "common"
+            } else {
+                "rare"
+            }
+        }));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from_iter_values(ids)),
+                Arc::new(categories),
+            ],
+        )
+        .unwrap()
+    }
+
+    async fn num_sketch_files(dataset: &Dataset) -> usize {
+        dataset
+            .object_store
+            .read_dir(dataset.base.child(SKETCH_DIR))
+            .await
+            .map(|files| files.len())
+            .unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn test_column_sketch() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let params = WriteParams {
+            max_rows_per_file: 500,
+            sketch_columns: vec!["id".to_string(), "category".to_string()],
+            ..Default::default()
+        };
+        let data = batch(0..1000);
+        let schema = data.schema();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(data)], schema.clone()),
+            test_uri,
+            Some(params.clone()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(num_sketch_files(&dataset).await, 2);
+
+        // Appended without sketches, so this fragment is scanned
+        dataset
+            .append(
+                RecordBatchIterator::new(vec![Ok(batch(1000..1200))], schema.clone()),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(num_sketch_files(&dataset).await, 2);
+
+        let sketch = dataset.column_sketch("id").await.unwrap();
+        assert_eq!(sketch.num_values, 1200);
+        let distinct = sketch.count_distinct() as f64;
+        assert!((distinct - 1200.0).abs() < 60.0, "{distinct}");
+
+        let sketch = dataset.column_sketch("category").await.unwrap();
+        assert_eq!(sketch.count_distinct(), 2);
+        let (values, counts) = sketch.top_k(5).unwrap();
+        let values = values.as_string::<i32>();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values.value(0), "rare");
+        assert_eq!(values.value(1), "common");
+        assert_eq!(counts, vec![900, 300]);
+
+        // Deleted rows are not counted
+        dataset.delete("id >= 100 and id < 200").await.unwrap();
+        let sketch = dataset.column_sketch("category").await.unwrap();
+        assert_eq!(sketch.num_values, 1100);
+        let (_, counts) = sketch.top_k(2).unwrap();
+        assert_eq!(counts, vec![825, 275]);
+
+        let err = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch(0..10))], schema.clone()),
+            test_uri,
+            Some(WriteParams {
+                mode: WriteMode::Overwrite,
+                sketch_columns: vec!["missing".to_string()],
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("missing"), "{err}");
+    }
+}
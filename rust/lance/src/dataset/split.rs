@@ -0,0 +1,454 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Split a dataset into two datasets
+//!
+//! Both datasets get a copy of the data files of the source and hide the rows
+//! of the other dataset with deletion files. Fragments keep their ids and
+//! rows keep their stable row ids, so a row can be traced back to the source
+//! and the split can be reproduced. Which source the datasets were split
+//! from is recorded in their schema metadata.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use futures::{stream, StreamExt, TryStreamExt};
+use lance_core::utils::address::RowAddress;
+use lance_core::utils::deletion::DeletionVector;
+use lance_core::{Error, Result, ROW_ID};
+use lance_io::object_store::ObjectStore;
+use lance_table::format::{Fragment, RowIdMeta};
+use lance_table::io::commit::commit_handler_from_url;
+use lance_table::io::deletion::{read_deletion_file, write_deletion_file};
+use object_store::path::Path;
+use roaring::RoaringBitmap;
+use snafu::{location, Location};
+
+use super::download::copy_file;
+use super::rowids::load_row_id_sequence;
+use super::{write_manifest_file, Dataset, ManifestWriteConfig, DATA_DIR};
+use crate::utils::temporal::SystemTime;
+
+/// Schema metadata key of the uri of the dataset a split came from.
+pub const SPLIT_SOURCE_KEY: &str = "lance:split:source";
+/// Schema metadata key of the version of the dataset a split came from.
+pub const SPLIT_SOURCE_VERSION_KEY: &str = "lance:split:source_version";
+/// Schema metadata key of the method used to split, see [`SplitMethod`].
+pub const SPLIT_METHOD_KEY: &str = "lance:split:method";
+/// Schema metadata key of which side of the split a dataset holds, either
+/// `selected` or `remainder`.
+pub const SPLIT_PART_KEY: &str = "lance:split:part";
+
+/// How [`Dataset::split`] picks the rows of the first dataset.
+#[derive(Debug, Clone)]
+pub enum SplitMethod {
+    /// Rows that match a SQL filter.
+    Filter(String),
+    /// A random sample of about `fraction` of the rows.
+    ///
+    /// Whether a row is picked only depends on its row id and `seed`. With
+    /// stable row ids, the same rows are picked after compaction or when more
+    /// rows are appended to the source.
+    Sample { fraction: f64, seed: u64 },
+}
+
+impl std::fmt::Display for SplitMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Filter(filter) => write!(f, "filter({})", filter),
+            Self::Sample { fraction, seed } => write!(f, "sample({}, seed={})", fraction, seed),
+        }
+    }
+}
+
+/// Whether the row with `row_id` is in a sample of `fraction` of the rows.
+fn sampled(row_id: u64, fraction: f64, seed: u64) -> bool {
+    // SplitMix64, so the sample doesn't change across platforms or releases.
+    let mut z = row_id ^ seed;
+    z = z.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64 <= fraction
+}
+
+/// Offsets of the rows picked by `method`, per fragment id.
+async fn select_rows(
+    dataset: &Dataset,
+    method: &SplitMethod,
+) -> Result<HashMap<u64, RoaringBitmap>> {
+    let mut selected = HashMap::<u64, RoaringBitmap>::new();
+    match method {
+        SplitMethod::Filter(filter) => {
+            let mut scanner = dataset.scan();
+            scanner.filter(filter)?.project::<&str>(&[])?.with_row_id();
+            let mut batches = scanner.try_into_stream().await?;
+            while let Some(batch) = batches.try_next().await? {
+                let row_ids = batch[ROW_ID]
+                    .as_any()
+                    .downcast_ref::<arrow_array::UInt64Array>()
+                    .ok_or_else(|| Error::Internal {
+                        message: format!("{} is not a u64 array", ROW_ID),
+                        location: location!(),
+                    })?;
+                for address in row_ids
+                    .values()
+                    .iter()
+                    .copied()
+                    .map(RowAddress::new_from_id)
+                {
+                    selected
+                        .entry(address.fragment_id() as u64)
+                        .or_default()
+                        .insert(address.row_id());
+                }
+            }
+        }
+        SplitMethod::Sample { fraction, seed } => {
+            for fragment in dataset.manifest.fragments.iter() {
+                let offsets = match load_row_id_sequence(dataset, fragment).await? {
+                    Some(row_ids) => row_ids
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, row_id)| sampled(*row_id, *fraction, *seed))
+                        .map(|(offset, _)| offset as u32)
+                        .collect(),
+                    None => (0..physical_rows(fragment)?)
+                        .filter(|offset| {
+                            let address = RowAddress::new_from_parts(fragment.id as u32, *offset);
+                            sampled(u64::from(address), *fraction, *seed)
+                        })
+                        .collect(),
+                };
+                selected.insert(fragment.id, offsets);
+            }
+        }
+    }
+    Ok(selected)
+}
+
+fn physical_rows(fragment: &Fragment) -> Result<u32> {
+    fragment
+        .physical_rows
+        .map(|rows| rows as u32)
+        .ok_or_else(|| Error::Internal {
+            message: format!("Fragment {} does not have physical rows", fragment.id),
+            location: location!(),
+        })
+}
+
+/// Write the side of the split that holds `part` at `uri`.
+async fn write_part(
+    dataset: &Dataset,
+    method: &SplitMethod,
+    selected: &HashMap<u64, RoaringBitmap>,
+    deleted: &HashMap<u64, RoaringBitmap>,
+    part: &str,
+    uri: &str,
+) -> Result<Dataset> {
+    let (target_store, target_base) = ObjectStore::from_uri(uri).await?;
+    let empty = RoaringBitmap::new();
+    let mut fragments = Vec::new();
+    let mut copies = Vec::<(Path, Path)>::new();
+    for fragment in dataset.manifest.fragments.iter() {
+        let all = RoaringBitmap::from_iter(0..physical_rows(fragment)?);
+        let picked = selected.get(&fragment.id).unwrap_or(&empty);
+        let mut hidden = if part == "selected" {
+            &all - picked
+        } else {
+            picked.clone()
+        };
+        hidden |= deleted.get(&fragment.id).unwrap_or(&empty);
+        if hidden == all {
+            continue;
+        }
+
+        let hidden = if hidden.is_empty() {
+            DeletionVector::NoDeletions
+        } else {
+            DeletionVector::Bitmap(hidden)
+        };
+        let mut fragment = fragment.clone();
+        for file in &fragment.files {
+            copies.push((
+                dataset.data_dir().child(file.path.as_str()),
+                target_base.child(DATA_DIR).child(file.path.as_str()),
+            ));
+        }
+        if let Some(RowIdMeta::External(file)) = &fragment.row_id_meta {
+            copies.push((
+                dataset.base.child(file.path.as_str()),
+                target_base.child(file.path.as_str()),
+            ));
+        }
+        fragment.deletion_file =
+            write_deletion_file(&target_base, fragment.id, 1, &hidden, &target_store).await?;
+        fragments.push(fragment);
+    }
+
+    stream::iter(copies)
+        .map(|(from, to)| {
+            let target_store = &target_store;
+            async move { copy_file(&dataset.object_store, &from, target_store, &to).await }
+        })
+        .buffer_unordered(num_cpus::get() * 4)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let mut manifest = dataset.manifest.as_ref().clone();
+    manifest.version = 1;
+    manifest.fragments = fragments.into();
+    manifest.transaction_file = None;
+    manifest.index_section = None;
+    manifest.tag = None;
+    let metadata = &mut manifest.schema.metadata;
+    metadata.insert(SPLIT_SOURCE_KEY.to_string(), dataset.uri().to_string());
+    metadata.insert(
+        SPLIT_SOURCE_VERSION_KEY.to_string(),
+        dataset.version().version.to_string(),
+    );
+    metadata.insert(SPLIT_METHOD_KEY.to_string(), method.to_string());
+    metadata.insert(SPLIT_PART_KEY.to_string(), part.to_string());
+    let commit_handler = commit_handler_from_url(uri, &None).await?;
+    let config = ManifestWriteConfig {
+        // Keep the feature flags of the source, like stable row ids.
+        auto_set_feature_flags: false,
+        timestamp: Some(
+            SystemTime::UNIX_EPOCH + Duration::from_nanos(manifest.timestamp_nanos as u64),
+        ),
+        ..Default::default()
+    };
+    write_manifest_file(
+        &target_store,
+        commit_handler.as_ref(),
+        &target_base,
+        &mut manifest,
+        None,
+        &config,
+    )
+    .await?;
+
+    Dataset::open(uri).await
+}
+
+pub(super) async fn split(
+    dataset: &Dataset,
+    method: SplitMethod,
+    selected_uri: &str,
+    remainder_uri: &str,
+) -> Result<(Dataset, Dataset)> {
+    if let SplitMethod::Sample { fraction, .. } = method {
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(Error::invalid_input(
+                format!("Split fraction must be between 0 and 1, got {}", fraction),
+                location!(),
+            ));
+        }
+    }
+    for uri in [selected_uri, remainder_uri] {
+        if Dataset::open(uri).await.is_ok() {
+            return Err(Error::DatasetAlreadyExists {
+                uri: uri.to_string(),
+                location: location!(),
+            });
+        }
+    }
+
+    let selected = select_rows(dataset, &method).await?;
+    let mut deleted = HashMap::new();
+    for fragment in dataset.manifest.fragments.iter() {
+        if let Some(deletion_vector) =
+            read_deletion_file(&dataset.base, fragment, &dataset.object_store).await?
+        {
+            deleted.insert(fragment.id, deletion_vector.into_iter().collect());
+        }
+    }
+
+    let first = write_part(
+        dataset,
+        &method,
+        &selected,
+        &deleted,
+        "selected",
+        selected_uri,
+    )
+    .await?;
+    let second = write_part(
+        dataset,
+        &method,
+        &selected,
+        &deleted,
+        "remainder",
+        remainder_uri,
+    )
+    .await?;
+    Ok((first, second))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use tempfile::tempdir;
+
+    use lance_table::feature_flags::FLAG_MOVE_STABLE_ROW_IDS;
+
+    use crate::dataset::WriteParams;
+
+    async fn create_dataset(uri: &str, stable_row_ids: bool) -> Dataset {
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..1000))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let params = WriteParams {
+            max_rows_per_file: 300,
+            enable_move_stable_row_ids: stable_row_ids,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(reader, uri, Some(params)).await.unwrap();
+        dataset.delete("i < 100").await.unwrap();
+        dataset
+    }
+
+    async fn values(dataset: &Dataset) -> HashSet<i32> {
+        let batches = dataset
+            .scan()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        batches
+            .iter()
+            .flat_map(|b| {
+                b["i"]
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_split_filter() {
+        let dir = tempdir().unwrap();
+        let dataset = create_dataset(dir.path().join("source").to_str().unwrap(), false).await;
+        let train_uri = dir.path().join("train").to_str().unwrap().to_string();
+        let test_uri = dir.path().join("test").to_str().unwrap().to_string();
+
+        let (train, test) = dataset
+            .split(
+                SplitMethod::Filter("i % 5 != 0".into()),
+                &train_uri,
+                &test_uri,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            values(&train).await,
+            (100..1000).filter(|i| i % 5 != 0).collect()
+        );
+        assert_eq!(
+            values(&test).await,
+            (100..1000).filter(|i| i % 5 == 0).collect()
+        );
+        train.validate().await.unwrap();
+        test.validate().await.unwrap();
+
+        // Fragments keep their ids.
+        let ids = train
+            .get_fragments()
+            .iter()
+            .map(|f| f.id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![0, 1, 2, 3]);
+
+        let metadata = &test.schema().metadata;
+        assert_eq!(metadata[SPLIT_SOURCE_KEY], dataset.uri());
+        assert_eq!(
+            metadata[SPLIT_SOURCE_VERSION_KEY],
+            dataset.version().version.to_string()
+        );
+        assert_eq!(metadata[SPLIT_METHOD_KEY], "filter(i % 5 != 0)");
+        assert_eq!(metadata[SPLIT_PART_KEY], "remainder");
+
+        let err = dataset
+            .split(SplitMethod::Filter("i > 0".into()), &train_uri, &test_uri)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::DatasetAlreadyExists { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_split_sample_is_reproducible() {
+        let dir = tempdir().unwrap();
+        let dataset = create_dataset(dir.path().join("source").to_str().unwrap(), true).await;
+        let method = SplitMethod::Sample {
+            fraction: 0.8,
+            seed: 42,
+        };
+
+        let (train, test) = dataset
+            .split(
+                method.clone(),
+                dir.path().join("train").to_str().unwrap(),
+                dir.path().join("test").to_str().unwrap(),
+            )
+            .await
+            .unwrap();
+        let train_values = values(&train).await;
+        let test_values = values(&test).await;
+        assert!(train_values.is_disjoint(&test_values));
+        assert_eq!(train_values.len() + test_values.len(), 900);
+        assert!((650..800).contains(&train_values.len()));
+
+        // Stable row ids are kept, and the same seed picks the same rows.
+        assert_ne!(
+            train.manifest.reader_feature_flags & FLAG_MOVE_STABLE_ROW_IDS,
+            0
+        );
+        for (fragment, source) in train.get_fragments().iter().zip(dataset.get_fragments()) {
+            assert_eq!(
+                fragment.metadata().row_id_meta,
+                source.metadata().row_id_meta
+            );
+        }
+        let (again, _) = dataset
+            .split(
+                method,
+                dir.path().join("train2").to_str().unwrap(),
+                dir.path().join("test2").to_str().unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(values(&again).await, train_values);
+
+        let err = dataset
+            .split(
+                SplitMethod::Sample {
+                    fraction: 1.5,
+                    seed: 0,
+                },
+                dir.path().join("a").to_str().unwrap(),
+                dir.path().join("b").to_str().unwrap(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+    }
+}
@@ -0,0 +1,428 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Storage footprint of a dataset
+//!
+//! Attributes the bytes of the current version to fields, indices and deletion
+//! files, so that the cost of each column can be tracked. It also reports how
+//! much storage is retained for older versions.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{UInt32Type, UInt64Type};
+use arrow_array::Array;
+use deepsize::DeepSizeOf;
+use futures::{stream, StreamExt, TryStreamExt};
+use lance_core::Result;
+use lance_file::reader::FileReader;
+use lance_file::v2;
+use lance_index::DatasetIndexExt;
+use lance_io::scheduler::ScanScheduler;
+use lance_table::format::DataFile;
+use lance_table::io::deletion::deletion_file_path;
+use uuid::Uuid;
+
+use super::fragment::FileFragment;
+use super::Dataset;
+
+/// Storage used by a single field of the dataset schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldStorageStats {
+    /// Field id
+    pub id: i32,
+    /// Full path of the field, with nested fields separated by `.`
+    pub name: String,
+    /// Bytes the field occupies in the data files.
+    pub bytes_on_disk: u64,
+    /// Bytes the field would occupy without any compression.
+    pub uncompressed_bytes: u64,
+}
+
+impl FieldStorageStats {
+    /// Ratio between the uncompressed and the on-disk size.
+    pub fn compression_ratio(&self) -> f64 {
+        if self.bytes_on_disk == 0 {
+            1.0
+        } else {
+            self.uncompressed_bytes as f64 / self.bytes_on_disk as f64
+        }
+    }
+}
+
+/// Storage used by an index of the current version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexStorageStats {
+    pub name: String,
+    pub uuid: Uuid,
+    /// Ids of the indexed fields
+    pub fields: Vec<i32>,
+    /// Total size of the files in the index directory.
+    pub bytes_on_disk: u64,
+}
+
+/// Storage overhead caused by deleted rows in the current version.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeletionStorageStats {
+    pub num_deletion_files: usize,
+    /// Total size of the deletion files.
+    pub deletion_file_bytes: u64,
+    pub num_deleted_rows: usize,
+    /// Estimated bytes of the data files still occupied by deleted rows. These
+    /// are reclaimed by compaction.
+    pub dead_bytes: u64,
+}
+
+/// Storage footprint of a dataset, see [`Dataset::storage_stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageStats {
+    /// Per-field usage, in schema order. Only fields that own data are listed.
+    pub fields: Vec<FieldStorageStats>,
+    pub indices: Vec<IndexStorageStats>,
+    pub deletions: DeletionStorageStats,
+    /// Total size of the data files referenced by the current version.
+    pub data_file_bytes: u64,
+    /// Bytes of the data files that are not attributed to any field, such as
+    /// file metadata and columns that have been dropped from the schema.
+    pub data_file_overhead_bytes: u64,
+    /// Total size of all files under the dataset root, across all versions.
+    pub total_bytes: u64,
+}
+
+impl StorageStats {
+    /// Bytes used by the data, deletion and index files of the current version.
+    pub fn current_version_bytes(&self) -> u64 {
+        self.data_file_bytes
+            + self.deletions.deletion_file_bytes
+            + self.indices.iter().map(|i| i.bytes_on_disk).sum::<u64>()
+    }
+
+    /// Bytes retained for older versions, manifests and transaction files.
+    /// Most of these can be reclaimed with [`super::cleanup::cleanup_old_versions`].
+    pub fn other_version_bytes(&self) -> u64 {
+        self.total_bytes
+            .saturating_sub(self.current_version_bytes())
+    }
+}
+
+/// Usage of a single data file, per field.
+#[derive(Debug, Default, DeepSizeOf)]
+pub(super) struct DataFileStats {
+    file_bytes: u64,
+    /// Field id -> (bytes on disk, uncompressed bytes)
+    pub(super) fields: BTreeMap<i32, (u64, u64)>,
+}
+
+impl DataFileStats {
+    fn field_bytes(&self) -> u64 {
+        self.fields.values().map(|(on_disk, _)| on_disk).sum()
+    }
+}
+
+pub(super) async fn data_file_stats(
+    fragment: &FileFragment,
+    data_file: &DataFile,
+) -> Result<DataFileStats> {
+    let dataset = fragment.dataset();
+    let path = dataset.data_dir().child(data_file.path.as_str());
+    let file_bytes = dataset.object_store.size(&path).await? as u64;
+
+    let mut fields = BTreeMap::new();
+    if data_file.is_legacy_file() {
+        let Some(max_field_id) = data_file.fields.iter().max() else {
+            return Ok(DataFileStats { file_bytes, fields });
+        };
+        let reader = FileReader::try_new_with_fragment_id(
+            &dataset.object_store,
+            &path,
+            dataset.schema().clone(),
+            fragment.id() as u32,
+            data_file.fields.first().copied().unwrap_or(0),
+            *max_field_id,
+            Some(&dataset.session.file_metadata_cache),
+        )
+        .await?;
+        // Legacy files are never compressed
+        for (field_id, bytes) in reader.field_sizes_on_disk() {
+            if data_file.fields.contains(&field_id) {
+                fields.insert(field_id, (bytes, bytes));
+            }
+        }
+    } else {
+        let scheduler = ScanScheduler::new(
+            dataset.object_store.clone(),
+            dataset.session.config().io_parallelism(),
+        );
+        let file_scheduler = scheduler.open_file(&path).await?;
+        let reader = v2::reader::FileReader::try_open(file_scheduler, None).await?;
+        let layout = reader.page_layout()?;
+
+        // Columns that are not the first column of a field (e.g. the second column
+        // of a binary field) belong to the closest preceding field.
+        let mut column_to_field = data_file
+            .column_indices
+            .iter()
+            .zip(data_file.fields.iter())
+            .filter(|(column_index, _)| **column_index >= 0)
+            .map(|(column_index, field_id)| (*column_index as u32, *field_id))
+            .collect::<Vec<_>>();
+        column_to_field.sort_unstable();
+
+        let column_index = layout["column_index"].as_primitive::<UInt32Type>();
+        let num_bytes = layout["num_bytes"].as_primitive::<UInt64Type>();
+        let uncompressed_bytes = layout["uncompressed_bytes"].as_primitive::<UInt64Type>();
+        for row in 0..layout.num_rows() {
+            let column = column_index.value(row);
+            let pos = column_to_field.partition_point(|(c, _)| *c <= column);
+            if pos == 0 {
+                continue;
+            }
+            let field_id = column_to_field[pos - 1].1;
+            let on_disk = num_bytes.value(row);
+            let uncompressed = if uncompressed_bytes.is_null(row) {
+                on_disk
+            } else {
+                uncompressed_bytes.value(row)
+            };
+            let entry: &mut (u64, u64) = fields.entry(field_id).or_default();
+            entry.0 += on_disk;
+            entry.1 += uncompressed;
+        }
+    }
+    Ok(DataFileStats { file_bytes, fields })
+}
+
+/// Usage of a single fragment
+#[derive(Debug, Default)]
+struct FragmentStats {
+    data_files: Vec<DataFileStats>,
+    deletion_file_bytes: Option<u64>,
+    num_deleted_rows: usize,
+    dead_bytes: u64,
+}
+
+async fn fragment_stats(fragment: FileFragment) -> Result<FragmentStats> {
+    let data_files = stream::iter(fragment.metadata().files.iter())
+        .then(|data_file| data_file_stats(&fragment, data_file))
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let mut stats = FragmentStats {
+        data_files,
+        ..Default::default()
+    };
+    if let Some(deletion_file) = &fragment.metadata().deletion_file {
+        let dataset = fragment.dataset();
+        let path = deletion_file_path(&dataset.base, fragment.metadata().id, deletion_file);
+        stats.deletion_file_bytes = Some(dataset.object_store.size(&path).await? as u64);
+        stats.num_deleted_rows = fragment.count_deletions().await?;
+
+        let physical_rows = fragment.physical_rows().await?;
+        if physical_rows > 0 {
+            let field_bytes = stats
+                .data_files
+                .iter()
+                .map(DataFileStats::field_bytes)
+                .sum::<u64>();
+            stats.dead_bytes =
+                (field_bytes as f64 * stats.num_deleted_rows as f64 / physical_rows as f64) as u64;
+        }
+    }
+    Ok(stats)
+}
+
+pub(super) async fn storage_stats(dataset: &Dataset) -> Result<StorageStats> {
+    let dataset_ref = Arc::new(dataset.clone());
+    let fragments = stream::iter(
+        dataset
+            .manifest
+            .fragments
+            .iter()
+            .map(|f| FileFragment::new(dataset_ref.clone(), f.clone())),
+    )
+    .map(fragment_stats)
+    .buffered(num_cpus::get() * 4)
+    .try_collect::<Vec<_>>()
+    .await?;
+
+    let mut field_bytes: BTreeMap<i32, (u64, u64)> = BTreeMap::new();
+    let mut data_file_bytes = 0;
+    let mut data_file_overhead_bytes = 0;
+    let mut deletions = DeletionStorageStats::default();
+    for fragment in fragments {
+        for data_file in fragment.data_files {
+            data_file_bytes += data_file.file_bytes;
+            let mut attributed = 0;
+            for (field_id, (on_disk, uncompressed)) in data_file.fields {
+                if dataset.schema().field_by_id(field_id).is_none() {
+                    continue;
+                }
+                let entry = field_bytes.entry(field_id).or_default();
+                entry.0 += on_disk;
+                entry.1 += uncompressed;
+                attributed += on_disk;
+            }
+            data_file_overhead_bytes += data_file.file_bytes.saturating_sub(attributed);
+        }
+        if let Some(bytes) = fragment.deletion_file_bytes {
+            deletions.num_deletion_files += 1;
+            deletions.deletion_file_bytes += bytes;
+            deletions.num_deleted_rows += fragment.num_deleted_rows;
+            deletions.dead_bytes += fragment.dead_bytes;
+        }
+    }
+
+    let fields = dataset
+        .schema()
+        .fields_pre_order()
+        .filter_map(|field| {
+            let (bytes_on_disk, uncompressed_bytes) = field_bytes.get(&field.id)?;
+            let name = dataset
+                .schema()
+                .field_ancestry_by_id(field.id)?
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<_>>()
+                .join(".");
+            Some(FieldStorageStats {
+                id: field.id,
+                name,
+                bytes_on_disk: *bytes_on_disk,
+                uncompressed_bytes: *uncompressed_bytes,
+            })
+        })
+        .collect();
+
+    let indices = dataset.load_indices().await?;
+    let indices = stream::iter(indices.iter())
+        .then(|index| async move {
+            let index_dir = dataset.indices_dir().child(index.uuid.to_string());
+            let bytes_on_disk = dataset
+                .object_store
+                .read_dir_all(&index_dir, None)
+                .await?
+                .try_fold(0, |acc, meta| async move { Ok(acc + meta.size as u64) })
+                .await?;
+            Result::Ok(IndexStorageStats {
+                name: index.name.clone(),
+                uuid: index.uuid,
+                fields: index.fields.clone(),
+                bytes_on_disk,
+            })
+        })
+        .try_collect()
+        .await?;
+
+    let total_bytes = dataset
+        .object_store
+        .read_dir_all(&dataset.base, None)
+        .await?
+        .try_fold(0, |acc, meta| async move { Ok(acc + meta.size as u64) })
+        .await?;
+
+    Ok(StorageStats {
+        fields,
+        indices,
+        deletions,
+        data_file_bytes,
+        data_file_overhead_bytes,
+        total_bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int64Array, RecordBatch, RecordBatchIterator, StringArray};
+    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use lance_index::IndexType;
+    use tempfile::tempdir;
+
+    use crate::dataset::WriteParams;
+    use crate::index::scalar::ScalarIndexParams;
+
+    async fn create_dataset(uri: &str, use_legacy_format: bool) -> Dataset {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int64, false),
+            ArrowField::new("s", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(0..1000)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..1000).map(|i| format!("string-{i:06}")),
+                )),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let params = WriteParams {
+            max_rows_per_file: 500,
+            use_legacy_format,
+            ..Default::default()
+        };
+        Dataset::write(reader, uri, Some(params)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_storage_stats() {
+        for use_legacy_format in [true, false] {
+            let test_dir = tempdir().unwrap();
+            let uri = test_dir.path().to_str().unwrap();
+            let mut dataset = create_dataset(uri, use_legacy_format).await;
+
+            let stats = dataset.storage_stats().await.unwrap();
+            assert_eq!(stats.fields.len(), 2);
+            assert_eq!(stats.fields[0].name, "i");
+            assert_eq!(stats.fields[1].name, "s");
+            if use_legacy_format {
+                assert_eq!(stats.fields[0].bytes_on_disk, 1000 * 8);
+                // 13 bytes per string and 501 offsets per file
+                assert_eq!(stats.fields[1].bytes_on_disk, 1000 * 13 + 2 * 501 * 8);
+            }
+            assert_eq!(stats.fields[0].uncompressed_bytes, 1000 * 8);
+            assert!(stats.fields[1].bytes_on_disk > stats.fields[0].bytes_on_disk);
+            assert_eq!(
+                stats.data_file_bytes,
+                stats.fields.iter().map(|f| f.bytes_on_disk).sum::<u64>()
+                    + stats.data_file_overhead_bytes
+            );
+            assert_eq!(stats.deletions, DeletionStorageStats::default());
+            assert!(stats.indices.is_empty());
+            assert!(stats.total_bytes > stats.current_version_bytes());
+
+            dataset.delete("i < 250").await.unwrap();
+            let stats = dataset.storage_stats().await.unwrap();
+            assert_eq!(stats.deletions.num_deletion_files, 1);
+            assert_eq!(stats.deletions.num_deleted_rows, 250);
+            assert!(stats.deletions.deletion_file_bytes > 0);
+            assert!(stats.deletions.dead_bytes > 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_storage_stats_with_index() {
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+        let mut dataset = create_dataset(uri, true).await;
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                Some("i_idx".to_string()),
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+
+        let stats = dataset.storage_stats().await.unwrap();
+        assert_eq!(stats.indices.len(), 1);
+        assert_eq!(stats.indices[0].name, "i_idx");
+        assert_eq!(stats.indices[0].fields, vec![0]);
+        assert!(stats.indices[0].bytes_on_disk > 0);
+    }
+}
@@ -1,12 +1,17 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
-use std::{collections::BTreeMap, ops::Range, pin::Pin, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+    pin::Pin,
+    sync::Arc,
+};
 
 use crate::{Error, Result};
 use arrow::{array::as_struct_array, compute::concat_batches, datatypes::UInt64Type};
 use arrow_array::cast::AsArray;
-use arrow_array::{Array, RecordBatch, StructArray, UInt64Array};
+use arrow_array::{Array, RecordBatch, RecordBatchOptions, StructArray, UInt64Array};
 use arrow_schema::{Field as ArrowField, Schema as ArrowSchema};
 use arrow_select::interleave::interleave;
 use datafusion::error::DataFusionError;
@@ -16,6 +21,7 @@ use lance_core::{datatypes::Schema, ROW_ID};
 use snafu::{location, Location};
 
 use super::{fragment::FileFragment, scanner::DatasetRecordBatchStream, Dataset};
+use crate::session::row_cache::{RowChunk, CHUNK_ROWS};
 
 pub async fn take(
     dataset: &Dataset,
@@ -157,6 +163,93 @@ pub async fn take_rows(
         }
     }
 
+    let row_cache = dataset
+        .session
+        .row_cache()
+        .filter(|_| !projection.fields.is_empty())
+        .and_then(|row_cache| {
+            let prefix = row_cache.prefix(dataset.uri(), &dataset.manifest, projection.as_ref())?;
+            Some((row_cache, prefix))
+        });
+    if let Some((row_cache, prefix)) = row_cache {
+        // The number of rows taken from each chunk
+        let mut unique_row_ids = Vec::from(row_ids);
+        unique_row_ids.sort();
+        unique_row_ids.dedup();
+        let mut rows_per_chunk: BTreeMap<(u64, u32), u64> = BTreeMap::new();
+        for row_id in unique_row_ids {
+            *rows_per_chunk
+                .entry((row_id >> 32, row_id as u32 / CHUNK_ROWS))
+                .or_default() += 1;
+        }
+
+        // Only read the chunks that aren't cached, and cache them
+        let mut chunks = HashMap::with_capacity(rows_per_chunk.len());
+        let mut reads = Vec::new();
+        for ((fragment_id, chunk), num_rows) in rows_per_chunk {
+            if let Some(rows) = row_cache.get(&prefix, fragment_id, chunk, num_rows) {
+                chunks.insert((fragment_id, chunk), rows);
+                continue;
+            }
+            let fragment = dataset.get_fragment(fragment_id as usize).ok_or_else(|| {
+                Error::invalid_input(
+                    format!("row_id belongs to non-existant fragment: {}", fragment_id),
+                    location!(),
+                )
+            })?;
+            let projection = projection.clone();
+            reads.push(async move {
+                let start = chunk * CHUNK_ROWS;
+                let end = (start + CHUNK_ROWS).min(fragment.physical_rows().await? as u32);
+                let offsets = (start..end).collect::<Vec<_>>();
+                let batch = do_take(fragment, offsets, projection, true).await?;
+                Result::Ok(((fragment_id, chunk), batch))
+            });
+        }
+        let batches: Vec<_> = futures::stream::iter(reads)
+            .buffered(4 * num_cpus::get())
+            .try_collect()
+            .await?;
+        for ((fragment_id, chunk), batch) in batches {
+            // The row ids are the last column
+            let chunk_row_ids = batch[ROW_ID].as_primitive::<UInt64Type>().values().to_vec();
+            let batch = batch.project(&(0..batch.num_columns() - 1).collect::<Vec<_>>())?;
+            let rows = RowChunk::new(chunk_row_ids.into(), batch);
+            row_cache.insert(&prefix, fragment_id, chunk, rows.clone());
+            chunks.insert((fragment_id, chunk), rows);
+        }
+
+        // Deleted rows are left out, as they are when the rows aren't cached
+        let chunks = chunks.into_iter().collect::<Vec<_>>();
+        let chunk_index = chunks
+            .iter()
+            .enumerate()
+            .map(|(i, (key, _))| (*key, i))
+            .collect::<HashMap<_, _>>();
+        let indices = row_ids
+            .iter()
+            .filter_map(|row_id| {
+                let i = chunk_index[&(row_id >> 32, *row_id as u32 / CHUNK_ROWS)];
+                Some((i, chunks[i].1.position(*row_id)?))
+            })
+            .collect::<Vec<_>>();
+        let schema = Arc::new(ArrowSchema::from(projection.as_ref()));
+        let columns = (0..schema.fields().len())
+            .map(|column| {
+                let arrays = chunks
+                    .iter()
+                    .map(|(_, rows)| rows.rows().column(column).as_ref())
+                    .collect::<Vec<_>>();
+                interleave(&arrays, &indices)
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        return Ok(RecordBatch::try_new_with_options(
+            schema,
+            columns,
+            &RecordBatchOptions::new().with_row_count(Some(indices.len())),
+        )?);
+    }
+
     if row_id_meta.contiguous {
         // Fastest path: Can use `read_range` directly
         let start = row_ids.first().expect("empty range passed to take_rows");
@@ -625,4 +718,107 @@ mod test {
             &[1],
         );
     }
+
+    #[tokio::test]
+    async fn test_take_rows_cached() {
+        use crate::dataset::builder::DatasetBuilder;
+        use crate::session::{row_cache::RowCache, Session};
+
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("s", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..100)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..100).map(|i| format!("str-{i}")),
+                )),
+            ],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 50,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        let row_cache = Arc::new(RowCache::new(1024 * 1024));
+        let mut session = Session::default();
+        session.set_row_cache(row_cache.clone());
+        let mut dataset = DatasetBuilder::from_uri(test_uri)
+            .with_session(Arc::new(session))
+            .load()
+            .await
+            .unwrap();
+
+        let projection = Schema::try_from(schema.as_ref()).unwrap();
+        let expected = |values: &[i32]| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(values.iter().copied())),
+                    Arc::new(StringArray::from_iter_values(
+                        values.iter().map(|v| format!("str-{v}")),
+                    )),
+                ],
+            )
+            .unwrap()
+        };
+
+        let row_ids = [(1_u64 << 32) + 2, 7, 7, 3];
+        let values = dataset.take_rows(&row_ids, &projection).await.unwrap();
+        assert_eq!(values, expected(&[52, 7, 7, 3]));
+        assert_eq!((row_cache.hits(), row_cache.misses()), (0, 3));
+        // The rows are cached in a chunk per fragment
+        assert_eq!(row_cache.len(), 2);
+
+        // Rows of cached chunks are not read again
+        let row_ids = [3, 4, (1_u64 << 32) + 2];
+        let values = dataset.take_rows(&row_ids, &projection).await.unwrap();
+        assert_eq!(values, expected(&[3, 4, 52]));
+        assert_eq!((row_cache.hits(), row_cache.misses()), (3, 3));
+        assert_eq!(row_cache.len(), 2);
+
+        // Other projections are cached apart
+        let projection_i = dataset.schema().project(&["i"]).unwrap();
+        let values = dataset.take_rows(&[3], &projection_i).await.unwrap();
+        assert_eq!(values.num_columns(), 1);
+        assert_eq!((row_cache.hits(), row_cache.misses()), (3, 4));
+
+        // The rows of older versions aren't used
+        dataset.delete("i = 3").await.unwrap();
+        let values = dataset.take_rows(&[3, 4, 6], &projection).await.unwrap();
+        assert_eq!(values, expected(&[4, 6]));
+        assert_eq!((row_cache.hits(), row_cache.misses()), (3, 7));
+
+        // Nor are those of a dataset that was recreated at the same location
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(100..200)),
+                Arc::new(StringArray::from_iter_values(
+                    (100..200).map(|i| format!("str-{i}")),
+                )),
+            ],
+        )
+        .unwrap();
+        std::fs::remove_dir_all(test_uri).unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        Dataset::write(batches, test_uri, None).await.unwrap();
+        let dataset = DatasetBuilder::from_uri(test_uri)
+            .with_session(dataset.session.clone())
+            .load()
+            .await
+            .unwrap();
+        assert_eq!(dataset.version().version, 1);
+        let values = dataset.take_rows(&[4, 6], &projection).await.unwrap();
+        assert_eq!(values, expected(&[104, 106]));
+    }
 }
@@ -0,0 +1,470 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! A service that takes rows for streams of requests
+//!
+//! Clients send batches of row ids, or of values of a key column, and get
+//! the matching rows back in the same order, one response batch per request
+//! batch. This is the shape of an Arrow Flight `DoExchange` call, and with the
+//! `flight` feature, [`TakeFlightService`] serves the service over one.
+//!
+//! Requests that queue up while rows are being read are served together, and
+//! recently taken rows are cached, which keeps the latency of feature
+//! retrieval low.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Deref;
+use std::sync::Arc;
+
+use arrow::compute::{cast, concat_batches};
+use arrow_array::cast::AsArray;
+use arrow_array::types::UInt64Type;
+use arrow_array::{Array, ArrayRef, RecordBatch};
+use arrow_schema::{DataType, SchemaRef};
+use arrow_select::interleave::interleave;
+use datafusion::common::Column;
+use datafusion::logical_expr::Expr;
+use datafusion::scalar::ScalarValue;
+use futures::stream::{self, BoxStream};
+use futures::{Stream, StreamExt, TryStreamExt};
+use lance_arrow::RecordBatchExt;
+use lance_core::datatypes::Schema;
+use lance_core::{Error, Result, ROW_ID};
+use moka::sync::{Cache, ConcurrentCacheExt};
+use snafu::{location, Location};
+
+use super::Dataset;
+
+#[cfg(feature = "flight")]
+mod flight;
+#[cfg(feature = "flight")]
+pub use flight::TakeFlightService;
+
+/// Number of queued requests served together at most.
+const MAX_QUEUED_REQUESTS: usize = 64;
+
+/// Parameters of a [`TakeService`].
+#[derive(Debug, Clone)]
+pub struct TakeServiceParams {
+    /// Column whose values identify the requested rows. If `None`, rows are
+    /// identified by row id, in a `_rowid` column of the requests.
+    pub key_column: Option<String>,
+    /// Rows read from the dataset at once at most. Default: 8192.
+    pub max_batch_rows: usize,
+    /// Number of rows kept in the cache, 0 to disable it. Default: 65536.
+    pub cache_rows: usize,
+}
+
+impl Default for TakeServiceParams {
+    fn default() -> Self {
+        Self {
+            key_column: None,
+            max_batch_rows: 8192,
+            cache_rows: 65536,
+        }
+    }
+}
+
+/// Takes rows of a dataset for streams of requests, see the
+/// [module docs](self).
+pub struct TakeService {
+    dataset: Arc<Dataset>,
+    projection: Schema,
+    schema: SchemaRef,
+    params: TakeServiceParams,
+    /// Single-row batches, by row id or key.
+    cache: Option<Cache<ScalarValue, RecordBatch>>,
+}
+
+impl std::fmt::Debug for TakeService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TakeService")
+            .field("dataset", &self.dataset.uri())
+            .field("schema", &self.schema)
+            .field("params", &self.params)
+            .finish()
+    }
+}
+
+impl TakeService {
+    /// Create a service that returns `columns` of `dataset`.
+    pub fn try_new(
+        dataset: Arc<Dataset>,
+        columns: &[&str],
+        params: TakeServiceParams,
+    ) -> Result<Self> {
+        if params.max_batch_rows == 0 {
+            return Err(Error::invalid_input(
+                "max_batch_rows must be greater than 0",
+                location!(),
+            ));
+        }
+        if let Some(key) = &params.key_column {
+            dataset.schema().field(key).ok_or_else(|| {
+                Error::invalid_input(format!("Key column {} does not exist", key), location!())
+            })?;
+        }
+        let projection = dataset.schema().project(columns)?;
+        let schema = Arc::new((&projection).into());
+        let cache = (params.cache_rows > 0).then(|| Cache::new(params.cache_rows as u64));
+        Ok(Self {
+            dataset,
+            projection,
+            schema,
+            params,
+            cache,
+        })
+    }
+
+    /// The schema of the responses.
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Number of rows in the cache.
+    pub fn cached_rows(&self) -> u64 {
+        self.cache.as_ref().map_or(0, |cache| {
+            cache.sync();
+            cache.entry_count()
+        })
+    }
+
+    /// Serve a stream of requests, in order.
+    ///
+    /// Each request batch holds the row ids, in a `_rowid` column, or the
+    /// keys of the rows to take, and is answered by a batch with the same
+    /// number of rows. A row id or key that matches no row, such as the id of
+    /// a deleted row, is an error. If several
+    /// rows share a key, one of them is returned.
+    pub fn exchange<'a>(
+        &'a self,
+        requests: impl Stream<Item = Result<RecordBatch>> + Send + 'a,
+    ) -> BoxStream<'a, Result<RecordBatch>> {
+        Self::exchange_with(self, requests)
+    }
+
+    /// [`Self::exchange`], for any handle on the service, such as an `Arc`
+    /// when the responses must outlive the caller.
+    fn exchange_with<'a, S>(
+        service: S,
+        requests: impl Stream<Item = Result<RecordBatch>> + Send + 'a,
+    ) -> BoxStream<'a, Result<RecordBatch>>
+    where
+        S: Deref<Target = Self> + Clone + Send + Sync + 'a,
+    {
+        requests
+            .ready_chunks(MAX_QUEUED_REQUESTS)
+            .then(move |requests| {
+                let service = service.clone();
+                async move {
+                    let requests = requests.into_iter().collect::<Result<Vec<_>>>()?;
+                    service.serve(&requests).await
+                }
+            })
+            .map_ok(|responses| stream::iter(responses.into_iter().map(Ok)))
+            .try_flatten()
+            .boxed()
+    }
+
+    /// Take the rows of a single request.
+    pub async fn take(&self, request: &RecordBatch) -> Result<RecordBatch> {
+        let mut responses = self.serve(std::slice::from_ref(request)).await?;
+        Ok(responses.remove(0))
+    }
+
+    async fn serve(&self, requests: &[RecordBatch]) -> Result<Vec<RecordBatch>> {
+        let column = self.params.key_column.as_deref().unwrap_or(ROW_ID);
+        let key_type = match &self.params.key_column {
+            Some(key) => self.dataset.schema().field(key).unwrap().data_type(),
+            None => DataType::UInt64,
+        };
+        let mut keys = Vec::with_capacity(requests.len());
+        for request in requests {
+            let array = request.column_by_name(column).ok_or_else(|| {
+                Error::invalid_input(
+                    format!("Take request does not have a {} column", column),
+                    location!(),
+                )
+            })?;
+            let array = cast(array, &key_type)?;
+            let request_keys = (0..array.len())
+                .map(|i| ScalarValue::try_from_array(&array, i))
+                .collect::<datafusion::error::Result<Vec<_>>>()?;
+            keys.push(request_keys);
+        }
+
+        // The rows of the responses are picked from these single-row or
+        // freshly read batches.
+        let mut sources = Vec::new();
+        let mut positions = HashMap::new();
+        let mut missing = Vec::new();
+        for key in keys.iter().flatten() {
+            if positions.contains_key(key) {
+                continue;
+            }
+            match self.cache.as_ref().and_then(|cache| cache.get(key)) {
+                Some(row) => {
+                    positions.insert(key.clone(), (sources.len(), 0));
+                    sources.push(row);
+                }
+                None => {
+                    positions.insert(key.clone(), (usize::MAX, 0));
+                    missing.push(key.clone());
+                }
+            }
+        }
+        for chunk in missing.chunks(self.params.max_batch_rows) {
+            let (batch, found) = self.read(chunk).await?;
+            for (row, key) in found.into_iter().enumerate() {
+                if let Some(cache) = &self.cache {
+                    cache.insert(key.clone(), batch.slice(row, 1));
+                }
+                positions.insert(key, (sources.len(), row));
+            }
+            sources.push(batch);
+        }
+
+        let columns = (0..self.schema.fields().len())
+            .map(|i| {
+                sources
+                    .iter()
+                    .map(|batch| batch.column(i).as_ref())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        keys.iter()
+            .map(|request_keys| {
+                let indices = request_keys
+                    .iter()
+                    .map(|key| match positions[key] {
+                        (usize::MAX, _) => Err(Error::invalid_input(
+                            format!("No row matches {} = {}", column, key),
+                            location!(),
+                        )),
+                        position => Ok(position),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                if indices.is_empty() {
+                    return Ok(RecordBatch::new_empty(self.schema.clone()));
+                }
+                let arrays = columns
+                    .iter()
+                    .map(|values| interleave(values, &indices))
+                    .collect::<std::result::Result<Vec<ArrayRef>, _>>()?;
+                Ok(RecordBatch::try_new(self.schema.clone(), arrays)?)
+            })
+            .collect()
+    }
+
+    /// Read the rows of `keys` from the dataset, with the keys found in the
+    /// order of the rows.
+    async fn read(&self, keys: &[ScalarValue]) -> Result<(RecordBatch, Vec<ScalarValue>)> {
+        let Some(key_column) = &self.params.key_column else {
+            let row_ids = keys
+                .iter()
+                .map(|key| match key {
+                    ScalarValue::UInt64(Some(row_id)) => Ok(*row_id),
+                    _ => Err(Error::invalid_input(
+                        format!("Row ids must be non-null u64, got {}", key),
+                        location!(),
+                    )),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            return self.read_rows(&row_ids).await;
+        };
+
+        let mut columns = self
+            .schema
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect::<Vec<_>>();
+        if !columns.contains(&key_column.as_str()) {
+            columns.push(key_column);
+        }
+        let filter = Expr::Column(Column::from_name(key_column))
+            .in_list(keys.iter().cloned().map(Expr::Literal).collect(), false);
+        let mut scanner = self.dataset.scan();
+        scanner.project(&columns)?.filter_expr(filter);
+        let batch = scanner.try_into_batch().await?;
+        let found = batch[key_column.as_str()].clone();
+        let found = (0..found.len())
+            .map(|i| ScalarValue::try_from_array(&found, i))
+            .collect::<datafusion::error::Result<Vec<_>>>()?;
+        Ok((batch.project_by_schema(&self.schema)?, found))
+    }
+
+    /// Read the rows of `row_ids`, with the row ids found in the order of the
+    /// rows. Deleted rows and rows of missing fragments are left out.
+    async fn read_rows(&self, row_ids: &[u64]) -> Result<(RecordBatch, Vec<ScalarValue>)> {
+        let mut row_ids_per_fragment: BTreeMap<u64, Vec<u32>> = BTreeMap::new();
+        for row_id in row_ids {
+            row_ids_per_fragment
+                .entry(row_id >> 32)
+                .or_default()
+                .push(*row_id as u32);
+        }
+        let mut batches = Vec::with_capacity(row_ids_per_fragment.len());
+        for (fragment_id, mut offsets) in row_ids_per_fragment {
+            let Some(fragment) = self.dataset.get_fragment(fragment_id as usize) else {
+                continue;
+            };
+            offsets.sort_unstable();
+            offsets.dedup();
+            batches.push(fragment.take_rows(&offsets, &self.projection, true).await?);
+        }
+
+        let mut found = Vec::new();
+        let mut rows = Vec::with_capacity(batches.len());
+        for batch in batches {
+            found.extend(
+                batch[ROW_ID]
+                    .as_primitive::<UInt64Type>()
+                    .values()
+                    .iter()
+                    .map(|row_id| ScalarValue::UInt64(Some(*row_id))),
+            );
+            rows.push(batch.project_by_schema(&self.schema)?);
+        }
+        Ok((concat_batches(&self.schema, &rows)?, found))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Int32Type;
+    use arrow_array::{Int32Array, RecordBatchIterator, StringArray, UInt64Array};
+    use arrow_schema::{Field, Schema as ArrowSchema};
+    use tempfile::tempdir;
+
+    async fn create_dataset(uri: &str) -> Arc<Dataset> {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new("s", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..100)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..100).map(|i| format!("k-{}", i)),
+                )),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        Arc::new(Dataset::write(reader, uri, None).await.unwrap())
+    }
+
+    fn request(column: &str, values: ArrayRef) -> RecordBatch {
+        RecordBatch::try_from_iter(vec![(column, values)]).unwrap()
+    }
+
+    fn values(batch: &RecordBatch) -> Vec<i32> {
+        batch["i"].as_primitive::<Int32Type>().values().to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_take_by_row_id() {
+        let dir = tempdir().unwrap();
+        let dataset = create_dataset(dir.path().to_str().unwrap()).await;
+        let service = TakeService::try_new(dataset, &["i"], TakeServiceParams::default()).unwrap();
+
+        let requests = vec![
+            request(ROW_ID, Arc::new(UInt64Array::from(vec![5, 1, 5]))),
+            request(ROW_ID, Arc::new(UInt64Array::from(Vec::<u64>::new()))),
+            request(ROW_ID, Arc::new(UInt64Array::from(vec![7]))),
+        ];
+        let responses = service
+            .exchange(stream::iter(requests.into_iter().map(Ok)))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(values(&responses[0]), vec![5, 1, 5]);
+        assert_eq!(responses[1].num_rows(), 0);
+        assert_eq!(values(&responses[2]), vec![7]);
+        assert_eq!(responses[0].schema(), service.schema());
+        assert_eq!(service.cached_rows(), 3);
+
+        // Served from the cache and the dataset.
+        let response = service
+            .take(&request(ROW_ID, Arc::new(UInt64Array::from(vec![7, 2]))))
+            .await
+            .unwrap();
+        assert_eq!(values(&response), vec![7, 2]);
+        assert_eq!(service.cached_rows(), 4);
+
+        let err = service
+            .take(&request("i", Arc::new(Int32Array::from(vec![1]))))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_take_deleted_rows() {
+        let dir = tempdir().unwrap();
+        let mut dataset = create_dataset(dir.path().to_str().unwrap())
+            .await
+            .as_ref()
+            .clone();
+        dataset.delete("i = 2 OR i = 4").await.unwrap();
+        let service =
+            TakeService::try_new(Arc::new(dataset), &["i"], TakeServiceParams::default()).unwrap();
+
+        // The rows after the deleted ones keep their ids.
+        let response = service
+            .take(&request(ROW_ID, Arc::new(UInt64Array::from(vec![5, 1, 3]))))
+            .await
+            .unwrap();
+        assert_eq!(values(&response), vec![5, 1, 3]);
+
+        for row_ids in [vec![4], vec![3, 2, 6], vec![(1 << 32) + 1]] {
+            let err = service
+                .take(&request(ROW_ID, Arc::new(UInt64Array::from(row_ids))))
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("No row matches"), "{}", err);
+        }
+        let response = service
+            .take(&request(ROW_ID, Arc::new(UInt64Array::from(vec![6, 3]))))
+            .await
+            .unwrap();
+        assert_eq!(values(&response), vec![6, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_take_by_key() {
+        let dir = tempdir().unwrap();
+        let dataset = create_dataset(dir.path().to_str().unwrap()).await;
+        let params = TakeServiceParams {
+            key_column: Some("s".to_string()),
+            max_batch_rows: 2,
+            cache_rows: 0,
+        };
+        let service = TakeService::try_new(dataset, &["i"], params).unwrap();
+
+        let keys = StringArray::from(vec!["k-3", "k-40", "k-3", "k-9"]);
+        let response = service.take(&request("s", Arc::new(keys))).await.unwrap();
+        assert_eq!(values(&response), vec![3, 40, 3, 9]);
+        assert_eq!(service.cached_rows(), 0);
+
+        let keys = StringArray::from(vec!["k-3", "missing"]);
+        let err = service
+            .take(&request("s", Arc::new(keys)))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("missing"), "{}", err);
+
+        let params = TakeServiceParams {
+            key_column: Some("x".to_string()),
+            ..Default::default()
+        };
+        let dataset = service.dataset.clone();
+        assert!(TakeService::try_new(dataset, &["i"], params).is_err());
+    }
+}
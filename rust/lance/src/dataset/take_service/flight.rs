@@ -0,0 +1,251 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Arrow Flight server for the take service
+
+use std::sync::Arc;
+
+use arrow::ipc::writer::IpcWriteOptions;
+use arrow_flight::decode::FlightRecordBatchStream;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaAsIpc, SchemaResult, Ticket,
+};
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use lance_core::Error;
+use snafu::{location, Location};
+use tonic::{Request, Response, Status, Streaming};
+
+use super::TakeService;
+
+/// Serves a [`TakeService`] over Arrow Flight.
+///
+/// `DoExchange` calls stream take requests and get the rows back, as in
+/// [`TakeService::exchange`], and `GetSchema` returns the schema of the
+/// responses. Other calls are not supported.
+#[derive(Debug, Clone)]
+pub struct TakeFlightService {
+    service: Arc<TakeService>,
+}
+
+impl TakeFlightService {
+    pub fn new(service: Arc<TakeService>) -> Self {
+        Self { service }
+    }
+
+    /// A gRPC service to add to a `tonic` server.
+    pub fn into_server(self) -> FlightServiceServer<Self> {
+        FlightServiceServer::new(self)
+    }
+}
+
+fn to_status(err: Error) -> Status {
+    match err {
+        Error::InvalidInput { .. } => Status::invalid_argument(err.to_string()),
+        _ => Status::internal(err.to_string()),
+    }
+}
+
+#[tonic::async_trait]
+impl FlightService for TakeFlightService {
+    type HandshakeStream = BoxStream<'static, Result<HandshakeResponse, Status>>;
+    type ListFlightsStream = BoxStream<'static, Result<FlightInfo, Status>>;
+    type DoGetStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoPutStream = BoxStream<'static, Result<PutResult, Status>>;
+    type DoActionStream = BoxStream<'static, Result<arrow_flight::Result, Status>>;
+    type ListActionsStream = BoxStream<'static, Result<ActionType, Status>>;
+    type DoExchangeStream = BoxStream<'static, Result<FlightData, Status>>;
+
+    async fn do_exchange(
+        &self,
+        request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        let requests =
+            FlightRecordBatchStream::new_from_flight_data(request.into_inner().map_err(Into::into))
+                .map_err(|err| {
+                    Error::io(
+                        format!("Failed to decode take request: {}", err),
+                        location!(),
+                    )
+                });
+        let responses = TakeService::exchange_with(self.service.clone(), requests)
+            .map_err(|err| FlightError::Tonic(to_status(err)));
+        let responses = FlightDataEncoderBuilder::new()
+            .with_schema(self.service.schema())
+            .build(responses)
+            .map_err(Status::from);
+        Ok(Response::new(responses.boxed()))
+    }
+
+    async fn get_schema(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        let schema = self.service.schema();
+        let result = SchemaAsIpc::new(&schema, &IpcWriteOptions::default())
+            .try_into()
+            .map_err(|err: arrow_schema::ArrowError| Status::internal(err.to_string()))?;
+        Ok(Response::new(result))
+    }
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("Handshake is not supported"))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        Err(Status::unimplemented("ListFlights is not supported"))
+    }
+
+    async fn get_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        Err(Status::unimplemented("GetFlightInfo is not supported"))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("PollFlightInfo is not supported"))
+    }
+
+    async fn do_get(
+        &self,
+        _request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        Err(Status::unimplemented("DoGet is not supported"))
+    }
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("DoPut is not supported"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("DoAction is not supported"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Err(Status::unimplemented("ListActions is not supported"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Int32Type;
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator, UInt64Array};
+    use arrow_flight::flight_service_client::FlightServiceClient;
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use futures::stream;
+    use lance_core::ROW_ID;
+    use tempfile::tempdir;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+
+    use crate::dataset::take_service::TakeServiceParams;
+    use crate::Dataset;
+
+    #[tokio::test]
+    async fn test_flight_exchange() {
+        let dir = tempdir().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let dataset = Dataset::write(reader, dir.path().to_str().unwrap(), None)
+            .await
+            .unwrap();
+        let service =
+            TakeService::try_new(Arc::new(dataset), &["i"], TakeServiceParams::default()).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(TakeFlightService::new(Arc::new(service)).into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let mut client = FlightServiceClient::connect(format!("http://{}", addr))
+            .await
+            .unwrap();
+
+        let requests = [vec![5, 1, 5], vec![42]].into_iter().map(|row_ids| {
+            RecordBatch::try_from_iter(vec![(ROW_ID, Arc::new(UInt64Array::from(row_ids)) as _)])
+                .unwrap()
+        });
+        let requests = FlightDataEncoderBuilder::new()
+            .build(stream::iter(requests).map(Ok))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let responses = client
+            .do_exchange(stream::iter(requests))
+            .await
+            .unwrap()
+            .into_inner();
+        let responses =
+            FlightRecordBatchStream::new_from_flight_data(responses.map_err(FlightError::from))
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+        let values = responses
+            .iter()
+            .map(|batch| batch["i"].as_primitive::<Int32Type>().values().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![vec![5, 1, 5], vec![42]]);
+
+        // Bad requests are reported as such
+        let request =
+            RecordBatch::try_from_iter(vec![("i", Arc::new(Int32Array::from(vec![1])) as _)])
+                .unwrap();
+        let requests = FlightDataEncoderBuilder::new()
+            .build(stream::iter([Ok(request)]))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let responses = client
+            .do_exchange(stream::iter(requests))
+            .await
+            .unwrap()
+            .into_inner();
+        let err =
+            FlightRecordBatchStream::new_from_flight_data(responses.map_err(FlightError::from))
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap_err();
+        let FlightError::Tonic(status) = err else {
+            panic!("Expected a gRPC error, got {}", err);
+        };
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+}
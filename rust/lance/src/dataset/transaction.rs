@@ -35,7 +35,10 @@
 //! (1) Delete and rewrite are compatible with each other and themselves only if
 //! they affect distinct fragments. Otherwise, they conflict.
 
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use lance_core::{datatypes::Schema, Error, Result};
 use lance_file::datatypes::Fields;
@@ -72,6 +75,10 @@ pub struct Transaction {
     pub uuid: String,
     pub operation: Operation,
     pub tag: Option<String>,
+    /// Rows of existing fragments that were deleted or rewritten by this
+    /// transaction, by fragment id. Rows of fragments added by the
+    /// transaction are not listed.
+    pub modified_rows: HashMap<u64, RoaringBitmap>,
 }
 
 /// An operation on a dataset.
@@ -139,6 +146,14 @@ pub enum Operation {
 
     /// Project to a new schema. This only changes the schema, not the data.
     Project { schema: Schema },
+
+    /// Change the dataset configuration. This doesn't change the data.
+    UpdateConfig {
+        /// Keys to set, with their new values
+        upsert_values: HashMap<String, String>,
+        /// Keys to remove
+        delete_keys: Vec<String>,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -153,6 +168,63 @@ pub struct RewriteGroup {
     pub new_fragments: Vec<Fragment>,
 }
 
+/// Details of a concurrent commit that a transaction conflicts with.
+///
+/// This is the source of the [`Error::CommitConflict`] returned when a commit
+/// can't be retried on top of a concurrent one. Use [`Self::from_error`] to
+/// get it from the error.
+#[derive(Debug, Clone)]
+pub struct TransactionConflict {
+    /// The version committed by the conflicting transaction.
+    pub version: u64,
+    /// The name of the operation of the transaction being committed.
+    pub operation: String,
+    /// The transaction that was committed concurrently.
+    pub other_transaction: Transaction,
+    /// The ids of the existing fragments modified by both transactions.
+    pub fragment_ids: Vec<u64>,
+    /// The ids of the fields written by both transactions.
+    pub field_ids: Vec<i32>,
+}
+
+impl TransactionConflict {
+    /// Get the conflict details from a commit conflict error, if it has them.
+    pub fn from_error(err: &Error) -> Option<&Self> {
+        match err {
+            Error::CommitConflict { source, .. } => source.downcast_ref::<Self>(),
+            _ => None,
+        }
+    }
+
+    /// The name of the operation of the conflicting transaction.
+    pub fn other_operation(&self) -> &str {
+        self.other_transaction.operation.name()
+    }
+}
+
+impl std::fmt::Display for TransactionConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "There was a concurrent commit that conflicts with this one and it \
+            cannot be automatically resolved. Please rerun the operation off the latest version \
+            of the table.\n {} conflicts with {} committed at version {}",
+            self.operation,
+            self.other_operation(),
+            self.version
+        )?;
+        if !self.fragment_ids.is_empty() {
+            write!(f, ", overlapping fragments: {:?}", self.fragment_ids)?;
+        }
+        if !self.field_ids.is_empty() {
+            write!(f, ", overlapping fields: {:?}", self.field_ids)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TransactionConflict {}
+
 impl Operation {
     /// Returns the IDs of fragments that have been modified by this operation.
     ///
@@ -165,6 +237,7 @@ impl Operation {
             | Self::CreateIndex { .. }
             | Self::ReserveFragments { .. }
             | Self::Project { .. }
+            | Self::UpdateConfig { .. }
             | Self::Restore { .. } => Box::new(std::iter::empty()),
             Self::Delete {
                 updated_fragments,
@@ -202,6 +275,71 @@ impl Operation {
         other_ids.any(|id| self_ids.contains(&id))
     }
 
+    /// Returns the IDs of the fields written by this operation.
+    ///
+    /// These are the fields of the data files of the new or updated fragments,
+    /// of the new schema for a project, and of the new indices.
+    fn modified_field_ids(&self) -> Box<dyn Iterator<Item = i32> + '_> {
+        fn data_fields(fragments: &[Fragment]) -> impl Iterator<Item = i32> + '_ {
+            fragments
+                .iter()
+                .flat_map(|f| f.files.iter().flat_map(|file| file.fields.iter().copied()))
+        }
+        match self {
+            Self::Delete { .. }
+            | Self::Rewrite { .. }
+            | Self::ReserveFragments { .. }
+            | Self::UpdateConfig { .. }
+            | Self::Restore { .. } => Box::new(std::iter::empty()),
+            Self::Append { fragments }
+            | Self::Overwrite { fragments, .. }
+            | Self::Merge { fragments, .. } => Box::new(data_fields(fragments)),
+            Self::Update {
+                updated_fragments,
+                new_fragments,
+                ..
+            } => Box::new(data_fields(updated_fragments).chain(data_fields(new_fragments))),
+            Self::Project { schema } => Box::new(schema.field_ids().into_iter()),
+            Self::CreateIndex { new_indices, .. } => Box::new(
+                new_indices
+                    .iter()
+                    .flat_map(|index| index.fields.iter().copied()),
+            ),
+        }
+    }
+
+    /// Check whether another operation changes the same configuration keys as this one.
+    fn modifies_same_config_keys(&self, other: &Self) -> bool {
+        fn config_keys(operation: &Operation) -> HashSet<&str> {
+            match operation {
+                Operation::UpdateConfig {
+                    upsert_values,
+                    delete_keys,
+                } => upsert_values
+                    .keys()
+                    .chain(delete_keys.iter())
+                    .map(String::as_str)
+                    .collect(),
+                _ => HashSet::new(),
+            }
+        }
+        !config_keys(self).is_disjoint(&config_keys(other))
+    }
+
+    fn overlap<T: std::hash::Hash + Eq + Ord>(
+        ours: impl Iterator<Item = T>,
+        theirs: impl Iterator<Item = T>,
+    ) -> Vec<T> {
+        let ours = ours.collect::<HashSet<_>>();
+        let mut overlap = theirs
+            .filter(|id| ours.contains(id))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        overlap.sort();
+        overlap
+    }
+
     pub fn name(&self) -> &str {
         match self {
             Self::Append { .. } => "Append",
@@ -214,6 +352,7 @@ impl Operation {
             Self::Restore { .. } => "Restore",
             Self::Update { .. } => "Update",
             Self::Project { .. } => "Project",
+            Self::UpdateConfig { .. } => "UpdateConfig",
         }
     }
 }
@@ -226,9 +365,16 @@ impl Transaction {
             uuid,
             operation,
             tag,
+            modified_rows: HashMap::new(),
         }
     }
 
+    /// Record the rows of existing fragments that this transaction modifies.
+    pub fn with_modified_rows(mut self, modified_rows: HashMap<u64, RoaringBitmap>) -> Self {
+        self.modified_rows = modified_rows;
+        self
+    }
+
     /// Returns true if the transaction cannot be committed if the other
     /// transaction is committed first.
     pub fn conflicts_with(&self, other: &Self) -> bool {
@@ -245,6 +391,7 @@ impl Transaction {
                 Operation::Delete { .. } | Operation::Update { .. } => false,
                 Operation::ReserveFragments { .. } => false,
                 Operation::Project { .. } => false,
+                Operation::UpdateConfig { .. } => false,
                 _ => true,
             },
             Operation::Rewrite { .. } => match &other.operation {
@@ -259,6 +406,7 @@ impl Transaction {
                     self.operation.modifies_same_ids(&other.operation)
                 }
                 Operation::Project { .. } => false,
+                Operation::UpdateConfig { .. } => false,
                 _ => true,
             },
             // Overwrite and Restore always succeed
@@ -270,7 +418,11 @@ impl Transaction {
                 &other.operation,
                 Operation::Overwrite { .. } | Operation::Restore { .. }
             ),
-            Operation::CreateIndex { .. } => match &other.operation {
+            Operation::CreateIndex {
+                removed_indices, ..
+            } => match &other.operation {
+                // The new index covers the fragments it was built on, appended
+                // fragments are searched as unindexed data.
                 Operation::Append { .. } => false,
                 // Indices are identified by UUIDs, so they shouldn't conflict.
                 Operation::CreateIndex { .. } => false,
@@ -280,11 +432,19 @@ impl Transaction {
                 // Merge & reserve don't change row ids, so this should be fine.
                 Operation::Merge { .. } => false,
                 Operation::ReserveFragments { .. } => false,
-                // Rewrite likely changed many of the row ids, so our index is
-                // likely useless. It should be rebuilt.
-                // TODO: we could be smarter here and only invalidate the index
-                // if the rewrite changed more than X% of row ids.
-                Operation::Rewrite { .. } => true,
+                // The old fragments of a rewrite are gone, so their rows are
+                // filtered out of the results of the new index, and the new
+                // fragments are not covered by it, so they are searched as
+                // unindexed data. This only fails if the rewrite remapped an
+                // index that we replace.
+                Operation::Rewrite {
+                    rewritten_indices, ..
+                } => rewritten_indices.iter().any(|rewritten| {
+                    removed_indices
+                        .iter()
+                        .any(|removed| removed.uuid == rewritten.old_id)
+                }),
+                Operation::UpdateConfig { .. } => false,
                 _ => true,
             },
             Operation::Delete { .. } | Operation::Update { .. } => match &other.operation {
@@ -295,21 +455,55 @@ impl Transaction {
                     self.operation.modifies_same_ids(&other.operation)
                 }
                 Operation::Project { .. } => false,
+                Operation::UpdateConfig { .. } => false,
                 _ => true,
             },
             // Merge changes the schema, but preserves row ids, so the only operations
-            // it's compatible with is CreateIndex and ReserveFragments.
+            // it's compatible with are CreateIndex, ReserveFragments and UpdateConfig.
             Operation::Merge { .. } => !matches!(
                 &other.operation,
-                Operation::CreateIndex { .. } | Operation::ReserveFragments { .. }
+                Operation::CreateIndex { .. }
+                    | Operation::ReserveFragments { .. }
+                    | Operation::UpdateConfig { .. }
             ),
             Operation::Project { .. } => match &other.operation {
                 // Project is compatible with anything that doesn't change the schema
                 Operation::CreateIndex { .. } => false,
                 Operation::Overwrite { .. } => false,
+                Operation::UpdateConfig { .. } => false,
                 _ => true,
             },
+            // Config changes don't touch the data, they only conflict with changes
+            // to the same keys and with a restore, which replaces the config.
+            Operation::UpdateConfig { .. } => match &other.operation {
+                Operation::UpdateConfig { .. } => {
+                    self.operation.modifies_same_config_keys(&other.operation)
+                }
+                Operation::Restore { .. } => true,
+                _ => false,
+            },
+        }
+    }
+
+    /// Returns the details of the conflict if the transaction cannot be
+    /// committed after `other`, which was committed at `version`.
+    pub fn conflict_details(&self, version: u64, other: &Self) -> Option<TransactionConflict> {
+        if !self.conflicts_with(other) {
+            return None;
         }
+        Some(TransactionConflict {
+            version,
+            operation: self.operation.name().to_string(),
+            other_transaction: other.clone(),
+            fragment_ids: Operation::overlap(
+                self.operation.modified_fragment_ids(),
+                other.operation.modified_fragment_ids(),
+            ),
+            field_ids: Operation::overlap(
+                self.operation.modified_field_ids(),
+                other.operation.modified_field_ids(),
+            ),
+        })
     }
 
     fn fragments_with_ids<'a, T>(
@@ -503,7 +697,7 @@ impl Transaction {
                 });
                 final_indices.extend(new_indices.clone());
             }
-            Operation::ReserveFragments { .. } => {
+            Operation::ReserveFragments { .. } | Operation::UpdateConfig { .. } => {
                 final_fragments.extend(maybe_existing_fragments?.clone());
             }
             Operation::Merge { ref fragments, .. } => {
@@ -550,8 +744,29 @@ impl Transaction {
 
         manifest.tag.clone_from(&self.tag);
 
+        if let Operation::Overwrite { .. } = self.operation {
+            manifest.partitioning.clone_from(&config.partitioning);
+        }
+        // The partitioning column may have been dropped
+        if let Some(partitioning) = &manifest.partitioning {
+            if manifest.schema.field_by_id(partitioning.field_id).is_none() {
+                manifest.partitioning = None;
+            }
+        }
+
+        if let Operation::UpdateConfig {
+            upsert_values,
+            delete_keys,
+        } = &self.operation
+        {
+            for key in delete_keys {
+                manifest.config.remove(key);
+            }
+            manifest.config.extend(upsert_values.clone());
+        }
+
         if config.auto_set_feature_flags {
-            apply_feature_flags(&mut manifest)?;
+            apply_feature_flags(&mut manifest, &final_indices)?;
         }
         manifest.set_timestamp(timestamp_to_nanos(config.timestamp));
 
@@ -834,6 +1049,13 @@ impl TryFrom<pb::Transaction> for Transaction {
                     schema: Schema::from(&Fields(schema.clone())),
                 }
             }
+            Some(pb::transaction::Operation::UpdateConfig(pb::transaction::UpdateConfig {
+                upsert_values,
+                delete_keys,
+            })) => Operation::UpdateConfig {
+                upsert_values,
+                delete_keys,
+            },
             None => {
                 return Err(Error::Internal {
                     message: "Transaction message did not contain an operation".to_string(),
@@ -850,6 +1072,14 @@ impl TryFrom<pb::Transaction> for Transaction {
             } else {
                 Some(message.tag.clone())
             },
+            modified_rows: message
+                .modified_rows
+                .into_iter()
+                .map(|(fragment_id, bitmap)| {
+                    let bitmap = RoaringBitmap::deserialize_from(bitmap.as_slice())?;
+                    Ok((fragment_id, bitmap))
+                })
+                .collect::<Result<_>>()?,
         })
     }
 }
@@ -982,6 +1212,13 @@ impl From<&Transaction> for pb::Transaction {
                     schema: Fields::from(schema).0,
                 })
             }
+            Operation::UpdateConfig {
+                upsert_values,
+                delete_keys,
+            } => pb::transaction::Operation::UpdateConfig(pb::transaction::UpdateConfig {
+                upsert_values: upsert_values.clone(),
+                delete_keys: delete_keys.clone(),
+            }),
         };
 
         Self {
@@ -989,6 +1226,16 @@ impl From<&Transaction> for pb::Transaction {
             uuid: value.uuid.clone(),
             operation: Some(operation),
             tag: value.tag.clone().unwrap_or("".to_string()),
+            modified_rows: value
+                .modified_rows
+                .iter()
+                .map(|(fragment_id, bitmap)| {
+                    let mut bytes = Vec::with_capacity(bitmap.serialized_size());
+                    // Writing to a Vec cannot fail.
+                    bitmap.serialize_into(&mut bytes).unwrap();
+                    (*fragment_id, bytes)
+                })
+                .collect(),
         }
     }
 }
@@ -1093,6 +1340,7 @@ fn schema_fragments_valid(schema: &Schema, fragments: &[Fragment]) -> Result<()>
 #[cfg(test)]
 mod tests {
     use super::*;
+    use lance_table::format::DataFile;
 
     #[test]
     fn test_conflicts() {
@@ -1102,6 +1350,7 @@ mod tests {
             fields: vec![0],
             dataset_version: 1,
             fragment_bitmap: None,
+            kind: None,
         };
         let fragment0 = Fragment::new(0);
         let fragment1 = Fragment::new(1);
@@ -1141,6 +1390,10 @@ mod tests {
                 updated_fragments: vec![fragment0.clone()],
                 new_fragments: vec![fragment2.clone()],
             },
+            Operation::UpdateConfig {
+                upsert_values: HashMap::from([("a".to_string(), "1".to_string())]),
+                delete_keys: vec![],
+            },
         ];
         let other_transactions = other_operations
             .iter()
@@ -1154,7 +1407,7 @@ mod tests {
                 Operation::Append {
                     fragments: vec![fragment0.clone()],
                 },
-                [false, false, false, true, true, false, false, false],
+                [false, false, false, true, true, false, false, false, false],
             ),
             (
                 Operation::Delete {
@@ -1163,7 +1416,7 @@ mod tests {
                     deleted_fragment_ids: vec![],
                     predicate: "x > 2".to_string(),
                 },
-                [true, false, false, true, true, false, false, true],
+                [true, false, false, true, true, false, false, true, false],
             ),
             (
                 Operation::Delete {
@@ -1172,7 +1425,7 @@ mod tests {
                     deleted_fragment_ids: vec![],
                     predicate: "x > 2".to_string(),
                 },
-                [true, false, true, true, true, true, false, true],
+                [true, false, true, true, true, true, false, true, false],
             ),
             (
                 Operation::Overwrite {
@@ -1181,15 +1434,17 @@ mod tests {
                 },
                 // No conflicts: overwrite can always happen since it doesn't
                 // depend on previous state of the table.
-                [false, false, false, false, false, false, false, false],
+                [
+                    false, false, false, false, false, false, false, false, false,
+                ],
             ),
             (
                 Operation::CreateIndex {
                     new_indices: vec![index0.clone()],
                     removed_indices: vec![index0.clone()],
                 },
-                // Will only conflict with operations that modify row ids.
-                [false, false, false, false, true, true, false, false],
+                // Only conflicts with operations that replace the data.
+                [false, false, false, false, true, false, false, false, false],
             ),
             (
                 // Rewrite that affects different fragments
@@ -1200,7 +1455,7 @@ mod tests {
                     }],
                     rewritten_indices: Vec::new(),
                 },
-                [false, true, false, true, true, false, false, true],
+                [false, true, false, true, true, false, false, true, false],
             ),
             (
                 // Rewrite that affects the same fragments
@@ -1211,20 +1466,21 @@ mod tests {
                     }],
                     rewritten_indices: Vec::new(),
                 },
-                [false, true, true, true, true, true, false, true],
+                [false, true, true, true, true, true, false, true, false],
             ),
             (
                 Operation::Merge {
                     fragments: vec![fragment0.clone(), fragment2.clone()],
                     schema: Schema::default(),
                 },
-                // Merge conflicts with everything except CreateIndex and ReserveFragments.
-                [true, false, true, true, true, true, false, true],
+                // Merge conflicts with everything except CreateIndex, ReserveFragments
+                // and UpdateConfig.
+                [true, false, true, true, true, true, false, true, false],
             ),
             (
                 Operation::ReserveFragments { num_fragments: 2 },
                 // ReserveFragments only conflicts with Overwrite and Restore.
-                [false, false, false, false, true, false, false, false],
+                [false, false, false, false, true, false, false, false, false],
             ),
             (
                 Operation::Update {
@@ -1233,7 +1489,25 @@ mod tests {
                     removed_fragment_ids: vec![],
                     new_fragments: vec![fragment2.clone()],
                 },
-                [true, false, true, true, true, true, false, true],
+                [true, false, true, true, true, true, false, true, false],
+            ),
+            (
+                // Config change to the same key
+                Operation::UpdateConfig {
+                    upsert_values: HashMap::new(),
+                    delete_keys: vec!["a".to_string()],
+                },
+                [false, false, false, false, false, false, false, false, true],
+            ),
+            (
+                // Config change to a different key
+                Operation::UpdateConfig {
+                    upsert_values: HashMap::from([("b".to_string(), "2".to_string())]),
+                    delete_keys: vec![],
+                },
+                [
+                    false, false, false, false, false, false, false, false, false,
+                ],
             ),
         ];
 
@@ -1256,6 +1530,101 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_create_index_conflicts_with_remapped_index() {
+        let index0 = Index {
+            uuid: uuid::Uuid::new_v4(),
+            name: "test".to_string(),
+            fields: vec![0],
+            dataset_version: 1,
+            fragment_bitmap: None,
+            kind: None,
+        };
+        let rewrite = |old_id| {
+            Transaction::new(
+                0,
+                Operation::Rewrite {
+                    groups: vec![RewriteGroup {
+                        old_fragments: vec![Fragment::new(0)],
+                        new_fragments: vec![Fragment::new(1)],
+                    }],
+                    rewritten_indices: vec![RewrittenIndex {
+                        old_id,
+                        new_id: uuid::Uuid::new_v4(),
+                    }],
+                },
+                None,
+            )
+        };
+        let create_index = Transaction::new(
+            0,
+            Operation::CreateIndex {
+                new_indices: vec![],
+                removed_indices: vec![index0.clone()],
+            },
+            None,
+        );
+        assert!(create_index.conflicts_with(&rewrite(index0.uuid)));
+        assert!(!create_index.conflicts_with(&rewrite(uuid::Uuid::new_v4())));
+    }
+
+    #[test]
+    fn test_conflict_details() {
+        let fragment = |id, fields: Vec<i32>| Fragment {
+            files: vec![DataFile::new_legacy_from_fields("data.lance", fields)],
+            ..Fragment::new(id)
+        };
+        let merge = Transaction::new(
+            0,
+            Operation::Merge {
+                fragments: vec![fragment(0, vec![0, 1, 2])],
+                schema: Schema::default(),
+            },
+            None,
+        );
+        let update = Transaction::new(
+            0,
+            Operation::Update {
+                removed_fragment_ids: vec![1],
+                updated_fragments: vec![],
+                new_fragments: vec![fragment(2, vec![2, 0])],
+            },
+            None,
+        );
+        let conflict = merge.conflict_details(3, &update).unwrap();
+        assert_eq!(conflict.version, 3);
+        assert_eq!(conflict.operation, "Merge");
+        assert_eq!(conflict.other_operation(), "Update");
+        assert_eq!(conflict.other_transaction.uuid, update.uuid);
+        assert!(conflict.fragment_ids.is_empty());
+        assert_eq!(conflict.field_ids, vec![0, 2]);
+
+        let delete = |ids: Vec<u64>| {
+            Transaction::new(
+                0,
+                Operation::Delete {
+                    updated_fragments: vec![],
+                    deleted_fragment_ids: ids,
+                    predicate: "x > 2".to_string(),
+                },
+                None,
+            )
+        };
+        let conflict = update.conflict_details(1, &delete(vec![0, 1])).unwrap();
+        assert_eq!(conflict.fragment_ids, vec![1]);
+        assert!(update.conflict_details(1, &delete(vec![0])).is_none());
+
+        let err = Error::CommitConflict {
+            version: 1,
+            source: Box::new(conflict),
+            location: location!(),
+        };
+        let conflict = TransactionConflict::from_error(&err).unwrap();
+        assert_eq!(conflict.other_operation(), "Delete");
+        assert!(err.to_string().contains("overlapping fragments: [1]"));
+        assert!(TransactionConflict::from_error(&Error::io("", location!())).is_none());
+    }
+
     #[test]
     fn test_rewrite_fragments() {
         let existing_fragments: Vec<Fragment> = (0..10).map(Fragment::new).collect();
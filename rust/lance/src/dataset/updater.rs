@@ -43,6 +43,10 @@ pub struct Updater {
 
     finished: bool,
 
+    /// Whether to write the new file in the legacy format. If not set, the
+    /// format of the fragment's existing files is used.
+    use_legacy_format: Option<bool>,
+
     deletion_restorer: DeletionRestorer,
 }
 
@@ -78,10 +82,18 @@ impl Updater {
             write_schema,
             final_schema,
             finished: false,
+            use_legacy_format: None,
             deletion_restorer: DeletionRestorer::new(deletion_vector, batch_size),
         })
     }
 
+    /// Write the new file in the legacy or the v2 format, regardless of the
+    /// format of the fragment's existing files.
+    pub(crate) fn with_legacy_format(mut self, use_legacy_format: bool) -> Self {
+        self.use_legacy_format = Some(use_legacy_format);
+        self
+    }
+
     pub fn fragment(&self) -> &FileFragment {
         &self.fragment
     }
@@ -126,7 +138,9 @@ impl Updater {
     /// Internal use only.
     async fn new_writer(&mut self, schema: Schema) -> Result<Box<dyn GenericWriter>> {
         // Look at some file in the fragment to determine if it is a v2 file or not
-        let is_legacy = self.fragment.metadata.files[0].is_legacy_file();
+        let is_legacy = self
+            .use_legacy_format
+            .unwrap_or_else(|| self.fragment.metadata.files[0].is_legacy_file());
 
         open_writer(
             &self.fragment.dataset().object_store,
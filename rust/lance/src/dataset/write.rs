@@ -1,20 +1,24 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use arrow_array::{RecordBatch, RecordBatchReader};
 use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::stream::BoxStream;
 use futures::StreamExt;
-use lance_core::{datatypes::Schema, Error, Result};
-use lance_datafusion::chunker::chunk_stream;
+use lance_core::datatypes::{Field, Schema};
+use lance_core::{Error, Result};
+use lance_datafusion::chunker::{chunk_stream, chunk_stream_with_max_bytes};
 use lance_datafusion::utils::{peek_reader_schema, reader_to_stream};
 use lance_file::format::{MAJOR_VERSION, MINOR_VERSION_NEXT};
 use lance_file::v2;
 use lance_file::v2::writer::FileWriterOptions;
 use lance_file::writer::{FileWriter, ManifestProvider};
 use lance_io::object_store::{ObjectStore, ObjectStoreParams};
-use lance_table::format::{DataFile, Fragment};
+use lance_table::format::{DataFile, Fragment, Partitioning};
 use lance_table::io::commit::CommitHandler;
 use lance_table::io::manifest::ManifestDescribing;
 use object_store::path::Path;
@@ -22,15 +26,29 @@ use snafu::{location, Location};
 use tracing::instrument;
 use uuid::Uuid;
 
+use crate::session::Session;
 use crate::Dataset;
 
 use super::builder::DatasetBuilder;
+use super::optimize::{merge_delta_fragments, plan_delta_runs, CompactionOptions};
+use super::partitioning::{split_by_partition, PartitionSpec};
 use super::progress::{NoopFragmentWriteProgress, WriteFragmentProgress};
+use super::sketch::{sketch_fields, SketchingWriter};
 use super::DATA_DIR;
 
+mod conform;
+mod dataset_writer;
 pub mod merge_insert;
+mod partitioned;
 pub mod update;
 
+pub use conform::conform_vectors;
+pub use conform::conform_views;
+pub use conform::VectorDimensionPolicy;
+pub use dataset_writer::DatasetWriter;
+pub(super) use dataset_writer::PendingWrite;
+pub(super) use partitioned::write_fragments_by_partition;
+
 /// The mode to write dataset.
 #[derive(Debug, Clone, Copy)]
 pub enum WriteMode {
@@ -58,6 +76,11 @@ impl TryFrom<&str> for WriteMode {
     }
 }
 
+/// The most bytes of input buffered before writing them, when writing in the
+/// v2 format. Larger chunks don't make better files, since the v2 writer
+/// buffers pages on its own.
+const MAX_CHUNK_BYTES: usize = 64 * 1024 * 1024;
+
 /// Dataset Write Parameters
 #[derive(Debug, Clone)]
 pub struct WriteParams {
@@ -107,6 +130,49 @@ pub struct WriteParams {
     /// This makes compaction more efficient, since with stable row ids no
     /// secondary indices need to be updated to point to new row ids.
     pub enable_move_stable_row_ids: bool,
+
+    /// If set, the vector (fixed size list) columns of each batch are checked
+    /// against the schema of the write before they are encoded.
+    ///
+    /// Lists and fixed size lists of another dimension or value type are cast
+    /// to the vector type of the column, and vectors of another dimension are
+    /// padded or truncated according to the policy. The errors name the batch,
+    /// the column and the row of the mismatched vector.
+    pub conform_vectors: Option<VectorDimensionPolicy>,
+
+    /// The session the written dataset is opened with. If not set, the session
+    /// of the existing dataset is reused, or a new one is created.
+    pub session: Option<Arc<Session>>,
+
+    /// If set, the rows are bucketed by a column into fragments of a single
+    /// partition, and scans filtering on that column only read the fragments of
+    /// the matching partitions.
+    ///
+    /// Creating or overwriting a dataset records the partitioning in the
+    /// dataset, and appends keep it.
+    pub partitioning: Option<PartitionSpec>,
+
+    /// Top-level columns to keep count-distinct and heavy-hitters sketches of.
+    ///
+    /// Each written data file gets a sketch file with the sketches of these
+    /// columns, which [`Dataset::column_sketch`] merges to estimate the number
+    /// of distinct values and the most frequent values without a scan.
+    pub sketch_columns: Vec<String>,
+
+    /// If true, appended data can leave out nullable top-level columns of the
+    /// dataset.
+    ///
+    /// The appended fragments have no data for these columns, and scans read
+    /// them as nulls until they are backfilled, for example by an update that
+    /// sets them.
+    pub allow_missing_columns: bool,
+
+    /// If set, small appends are written as delta fragments, which are merged
+    /// into larger fragments once there are enough of them.
+    ///
+    /// This lets a dataset take frequent small appends without piling up tiny
+    /// fragments.  The rows of delta fragments are read like any others.
+    pub delta_log: Option<DeltaLogParams>,
 }
 
 impl Default for WriteParams {
@@ -123,6 +189,85 @@ impl Default for WriteParams {
             commit_handler: None,
             use_legacy_format: true,
             enable_move_stable_row_ids: false,
+            conform_vectors: None,
+            session: None,
+            partitioning: None,
+            sketch_columns: Vec::new(),
+            allow_missing_columns: false,
+            delta_log: None,
+        }
+    }
+}
+
+/// How small appends are absorbed, see [`WriteParams::delta_log`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeltaLogParams {
+    /// Appends of fewer rows are written as delta fragments. Defaults to
+    /// 10,000.
+    pub max_delta_rows: usize,
+    /// The number of delta fragments at which an append merges them. Defaults
+    /// to 16.
+    pub merge_threshold: usize,
+}
+
+impl Default for DeltaLogParams {
+    fn default() -> Self {
+        Self {
+            max_delta_rows: 10_000,
+            merge_threshold: 16,
+        }
+    }
+}
+
+/// Mark the fragments of an append as delta fragments, if it is small enough.
+pub(super) fn mark_delta_fragments(params: &WriteParams, fragments: &mut [Fragment]) {
+    let Some(delta_log) = &params.delta_log else {
+        return;
+    };
+    let num_rows = fragments
+        .iter()
+        .map(|fragment| fragment.physical_rows.unwrap_or_default())
+        .sum::<usize>();
+    if num_rows < delta_log.max_delta_rows {
+        for fragment in fragments {
+            fragment.delta = true;
+        }
+    }
+}
+
+/// Merge the delta fragments of `dataset` once there are enough of them that
+/// can be merged.
+///
+/// This is best effort: the append is already committed, so a failed merge,
+/// such as one conflicting with a concurrent merge, is left to a later append
+/// or to [`merge_delta_fragments`].
+pub(super) async fn maybe_merge_delta_fragments(dataset: &mut Dataset, params: &WriteParams) {
+    let Some(delta_log) = &params.delta_log else {
+        return;
+    };
+    // Fragments fresh enough to be delta fragments are seldom indexed, so
+    // this doesn't pay for loading the index fragment bitmaps
+    let num_delta_fragments = plan_delta_runs(
+        &dataset.manifest.fragments,
+        params.max_rows_per_file,
+        |_, _| true,
+    )
+    .iter()
+    .flatten()
+    .filter(|fragment| fragment.delta)
+    .count();
+    if num_delta_fragments >= delta_log.merge_threshold.max(2) {
+        let options = CompactionOptions {
+            target_rows_per_fragment: params.max_rows_per_file,
+            max_rows_per_group: params.max_rows_per_group,
+            ..Default::default()
+        };
+        if let Err(err) = merge_delta_fragments(dataset, options, None).await {
+            log::warn!(
+                "Failed to merge the delta fragments of {}: {}",
+                dataset.uri(),
+                err
+            );
         }
     }
 }
@@ -167,8 +312,18 @@ pub async fn write_fragments(
         (None, object_store, base)
     };
 
-    let (data, schema) = peek_reader_schema(Box::new(data)).await?;
-    let stream = reader_to_stream(data);
+    let data = conform_views(Box::new(data), dataset.as_ref().map(|d| d.schema()))?;
+    let (data, schema) = peek_reader_schema(data).await?;
+    let mut stream = reader_to_stream(data);
+    let mut schema = schema;
+    let policy = match (params.conform_vectors, dataset.as_ref()) {
+        (None, Some(dataset)) => dataset.config().conform_vectors()?,
+        (policy, _) => policy,
+    };
+    if let Some(policy) = policy {
+        let target = dataset.as_ref().map(|d| d.schema());
+        (stream, schema) = conform_vectors(stream, &schema, target, policy)?;
+    }
     write_fragments_internal(
         dataset.as_ref(),
         Arc::new(object_store),
@@ -189,6 +344,39 @@ pub async fn write_fragments(
 /// This is a private variant that takes a `SendableRecordBatchStream` instead
 /// of a reader. We don't expose the stream at our interface because it is a
 /// DataFusion type.
+/// The fields of the dataset schema `expected` that data of `schema` appends.
+///
+/// These are all the fields of `expected`, unless `allow_missing_columns` is
+/// set, in which case nullable top-level fields that are not in `schema` are
+/// left out.
+pub(super) fn append_schema(
+    schema: &Schema,
+    expected: &Schema,
+    allow_missing_columns: bool,
+) -> Result<Schema> {
+    if !allow_missing_columns {
+        return Ok(expected.clone());
+    }
+    let mut fields = Vec::with_capacity(schema.fields.len());
+    for field in &expected.fields {
+        if schema.field(&field.name).is_some() {
+            fields.push(field.clone());
+        } else if !field.nullable {
+            return Err(Error::SchemaMismatch {
+                difference: format!(
+                    "non-nullable column {} is missing from the appended data",
+                    field.name
+                ),
+                location: location!(),
+            });
+        }
+    }
+    Ok(Schema {
+        fields,
+        metadata: expected.metadata.clone(),
+    })
+}
+
 #[instrument(level = "debug", skip_all)]
 pub async fn write_fragments_internal(
     dataset: Option<&Dataset>,
@@ -201,13 +389,15 @@ pub async fn write_fragments_internal(
     // Make sure the max rows per group is not larger than the max rows per file
     params.max_rows_per_group = std::cmp::min(params.max_rows_per_group, params.max_rows_per_file);
 
+    let written_schema;
     let schema = if let Some(dataset) = dataset {
         if matches!(params.mode, WriteMode::Append) {
             // Append mode, so we need to check compatibility
-            schema.check_compatible(dataset.schema(), &Default::default())?;
+            written_schema = append_schema(schema, dataset.schema(), params.allow_missing_columns)?;
+            schema.check_compatible(&written_schema, &Default::default())?;
             // Use the schema from the dataset, because it has the correct
             // field ids.
-            dataset.schema()
+            &written_schema
         } else {
             schema
         }
@@ -215,47 +405,68 @@ pub async fn write_fragments_internal(
         schema
     };
 
+    if let Some(spec) = &params.partitioning {
+        let partitioning = spec.to_partitioning(schema)?;
+        // Each chunk is split between partitions, so only limit it by group size
+        let buffered_reader = chunk_stream(data, params.max_rows_per_group);
+        let writer_generator = WriterGenerator::try_new(object_store, base_dir, schema, &params)?;
+        return write_partitioned_fragments(
+            buffered_reader,
+            writer_generator,
+            &partitioning,
+            schema,
+            &params,
+        )
+        .await;
+    }
+
     let mut buffered_reader = if params.use_legacy_format {
         chunk_stream(data, params.max_rows_per_group)
     } else {
         // In v2 we don't care about group size but we do want to chunk
-        // by max_rows_per_file
-        chunk_stream(data, params.max_rows_per_file)
+        // by max_rows_per_file, without buffering too much of the input
+        chunk_stream_with_max_bytes(data, params.max_rows_per_file, MAX_CHUNK_BYTES)
     };
 
-    let writer_generator =
-        WriterGenerator::new(object_store, base_dir, schema, params.use_legacy_format);
+    let writer_generator = WriterGenerator::try_new(object_store, base_dir, schema, &params)?;
     let mut writer: Option<Box<dyn GenericWriter>> = None;
     let mut num_rows_in_current_file = 0;
     let mut fragments = Vec::new();
     while let Some(batch_chunk) = buffered_reader.next().await {
-        let batch_chunk = batch_chunk?;
-
-        if writer.is_none() {
-            let (new_writer, new_fragment) = writer_generator.new_writer().await?;
-            // rustc has a hard time analyzing the lifetime of the &str returned
-            // by multipart_id(), so we convert it to an owned value here.
-            let multipart_id = new_writer.multipart_id().to_string();
-            params.progress.begin(&new_fragment, &multipart_id).await?;
-            writer = Some(new_writer);
-            fragments.push(new_fragment);
-        }
+        let mut batch_chunk = batch_chunk?;
+
+        // A chunk can fill up the current file, so it is split at the rows
+        // left in the file and the rest goes to the next one
+        while !batch_chunk.is_empty() {
+            if writer.is_none() {
+                let (new_writer, new_fragment) = writer_generator.new_writer().await?;
+                // rustc has a hard time analyzing the lifetime of the &str returned
+                // by multipart_id(), so we convert it to an owned value here.
+                let multipart_id = new_writer.multipart_id().to_string();
+                params.progress.begin(&new_fragment, &multipart_id).await?;
+                writer = Some(new_writer);
+                fragments.push(new_fragment);
+            }
 
-        writer.as_mut().unwrap().write(&batch_chunk).await?;
-        for batch in batch_chunk {
-            num_rows_in_current_file += batch.num_rows() as u32;
-        }
+            let rows_left = (params.max_rows_per_file - num_rows_in_current_file as usize).max(1);
+            let (head, rest) = split_chunk(batch_chunk, rows_left);
+            batch_chunk = rest;
+            writer.as_mut().unwrap().write(&head).await?;
+            for batch in head {
+                num_rows_in_current_file += batch.num_rows() as u32;
+            }
 
-        if num_rows_in_current_file >= params.max_rows_per_file as u32
-            || writer.as_mut().unwrap().tell().await? >= params.max_bytes_per_file as u64
-        {
-            let (num_rows, data_file) = writer.take().unwrap().finish().await?;
-            debug_assert_eq!(num_rows, num_rows_in_current_file);
-            params.progress.complete(fragments.last().unwrap()).await?;
-            let last_fragment = fragments.last_mut().unwrap();
-            last_fragment.physical_rows = Some(num_rows as usize);
-            last_fragment.files.push(data_file);
-            num_rows_in_current_file = 0;
+            if num_rows_in_current_file >= params.max_rows_per_file as u32
+                || writer.as_mut().unwrap().tell().await? >= params.max_bytes_per_file as u64
+            {
+                let (num_rows, data_file) = writer.take().unwrap().finish().await?;
+                debug_assert_eq!(num_rows, num_rows_in_current_file);
+                params.progress.complete(fragments.last().unwrap()).await?;
+                let last_fragment = fragments.last_mut().unwrap();
+                last_fragment.physical_rows = Some(num_rows as usize);
+                last_fragment.files.push(data_file);
+                num_rows_in_current_file = 0;
+            }
         }
     }
 
@@ -270,6 +481,79 @@ pub async fn write_fragments_internal(
     Ok(fragments)
 }
 
+/// Split `chunk` into its first `num_rows` rows and the rest, slicing the
+/// batch that straddles them.
+fn split_chunk(chunk: Vec<RecordBatch>, num_rows: usize) -> (Vec<RecordBatch>, Vec<RecordBatch>) {
+    let mut head = Vec::with_capacity(chunk.len());
+    let mut rest = Vec::new();
+    let mut rows_left = num_rows;
+    for batch in chunk {
+        if rows_left >= batch.num_rows() {
+            rows_left -= batch.num_rows();
+            head.push(batch);
+        } else if rows_left > 0 {
+            head.push(batch.slice(0, rows_left));
+            rest.push(batch.slice(rows_left, batch.num_rows() - rows_left));
+            rows_left = 0;
+        } else {
+            rest.push(batch);
+        }
+    }
+    (head, rest)
+}
+
+/// Write the rows of each partition to their own fragments.
+async fn write_partitioned_fragments(
+    mut buffered_reader: BoxStream<'static, Result<Vec<RecordBatch>>>,
+    writer_generator: WriterGenerator,
+    partitioning: &Partitioning,
+    schema: &Schema,
+    params: &WriteParams,
+) -> Result<Vec<Fragment>> {
+    // The open writer, fragment and number of rows written of each partition
+    let mut writers = BTreeMap::<u32, (Box<dyn GenericWriter>, Fragment, u32)>::new();
+    let mut fragments = Vec::new();
+    while let Some(batch_chunk) = buffered_reader.next().await {
+        for batch in batch_chunk? {
+            for (partition, batch) in split_by_partition(partitioning, schema, &batch)? {
+                let (writer, _, num_rows) = match writers.entry(partition) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let (writer, mut fragment) = writer_generator.new_writer().await?;
+                        let multipart_id = writer.multipart_id().to_string();
+                        params.progress.begin(&fragment, &multipart_id).await?;
+                        fragment.partition = Some(partition);
+                        entry.insert((writer, fragment, 0))
+                    }
+                };
+                writer.write(std::slice::from_ref(&batch)).await?;
+                *num_rows += batch.num_rows() as u32;
+
+                if *num_rows >= params.max_rows_per_file as u32
+                    || writer.tell().await? >= params.max_bytes_per_file as u64
+                {
+                    let (mut writer, mut fragment, _) = writers.remove(&partition).unwrap();
+                    let (num_rows, data_file) = writer.finish().await?;
+                    params.progress.complete(&fragment).await?;
+                    fragment.physical_rows = Some(num_rows as usize);
+                    fragment.files.push(data_file);
+                    fragments.push(fragment);
+                }
+            }
+        }
+    }
+
+    // Complete the final writers
+    for (_, (mut writer, mut fragment, _)) in writers {
+        let (num_rows, data_file) = writer.finish().await?;
+        fragment.physical_rows = Some(num_rows as usize);
+        fragment.files.push(data_file);
+        fragments.push(fragment);
+    }
+
+    Ok(fragments)
+}
+
 #[async_trait::async_trait]
 pub trait GenericWriter: Send {
     /// Get a unique id associated with the fragment being written
@@ -382,21 +666,24 @@ struct WriterGenerator {
     base_dir: Path,
     schema: Schema,
     use_legacy_format: bool,
+    /// Fields to sketch in each written file
+    sketch_fields: Vec<Field>,
 }
 
 impl WriterGenerator {
-    pub fn new(
+    pub fn try_new(
         object_store: Arc<ObjectStore>,
         base_dir: &Path,
         schema: &Schema,
-        use_legacy_format: bool,
-    ) -> Self {
-        Self {
+        params: &WriteParams,
+    ) -> Result<Self> {
+        Ok(Self {
             object_store,
             base_dir: base_dir.clone(),
             schema: schema.clone(),
-            use_legacy_format,
-        }
+            use_legacy_format: params.use_legacy_format,
+            sketch_fields: sketch_fields(schema, &params.sketch_columns)?,
+        })
     }
 
     pub async fn new_writer(&self) -> Result<(Box<dyn GenericWriter>, Fragment)> {
@@ -410,8 +697,17 @@ impl WriterGenerator {
             self.use_legacy_format,
         )
         .await?;
+        if self.sketch_fields.is_empty() {
+            return Ok((writer, fragment));
+        }
+        let writer = SketchingWriter::try_new(
+            writer,
+            self.object_store.clone(),
+            &self.base_dir,
+            &self.sketch_fields,
+        )?;
 
-        Ok((writer, fragment))
+        Ok((Box::new(writer), fragment))
     }
 }
 
@@ -419,7 +715,7 @@ impl WriterGenerator {
 mod tests {
     use super::*;
 
-    use arrow_array::{Int32Array, StructArray};
+    use arrow_array::{Int32Array, RecordBatchIterator, StructArray};
     use arrow_schema::{DataType, Field as ArrowField, Fields, Schema as ArrowSchema};
     use datafusion::{error::DataFusionError, physical_plan::stream::RecordBatchStreamAdapter};
     use futures::TryStreamExt;
@@ -470,6 +766,45 @@ mod tests {
         assert_eq!(chunks[3][1].num_rows(), 2);
     }
 
+    #[tokio::test]
+    async fn test_max_rows_per_file_splits_chunks() {
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "a",
+            DataType::Int32,
+            false,
+        )]));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from_iter(0..12))])
+                .unwrap();
+
+        // Chunks of 3 rows don't line up with files of 5 rows
+        for use_legacy_format in [true, false] {
+            let test_dir = tempfile::tempdir().unwrap();
+            let test_uri = test_dir.path().to_str().unwrap();
+            let params = WriteParams {
+                max_rows_per_file: 5,
+                max_rows_per_group: 3,
+                use_legacy_format,
+                ..Default::default()
+            };
+            let reader = RecordBatchIterator::new(
+                vec![Ok(batch.slice(0, 3)), Ok(batch.slice(3, 9))],
+                schema.clone(),
+            );
+            let dataset = Dataset::write(reader, test_uri, Some(params))
+                .await
+                .unwrap();
+            let rows = dataset
+                .get_fragments()
+                .iter()
+                .map(|fragment| fragment.metadata().physical_rows.unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(rows, vec![5, 5, 2]);
+            let result = dataset.scan().try_into_batch().await.unwrap();
+            assert_eq!(result["a"].as_ref(), batch["a"].as_ref());
+        }
+    }
+
     #[tokio::test]
     async fn test_chunking_small_batches() {
         // Create a stream of 10 batches of 3 rows
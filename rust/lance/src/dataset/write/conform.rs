@@ -0,0 +1,487 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Conform the vector columns of written batches to the schema
+//!
+//! Data ingested from several sources may not agree on the dimension or the
+//! value type of the vectors. Each batch is checked before it reaches the
+//! encoder, so mismatches are reported with the batch, the column and the row
+//! where they occur, and vectors can be padded or truncated to fit.
+//!
+//! String and binary view columns are cast to the byte array types, which is
+//! how they are stored.
+
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arrow::compute::{cast, concat};
+use arrow_array::{
+    cast::AsArray, new_empty_array, Array, ArrayRef, FixedSizeListArray, Int8Array, RecordBatch,
+    RecordBatchIterator, RecordBatchReader, UInt64Array,
+};
+use arrow_schema::{ArrowError, DataType, Field as ArrowField, Schema as ArrowSchema, SchemaRef};
+use arrow_select::take::take;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::StreamExt;
+use lance_arrow::cast::cast_with_options;
+use lance_core::datatypes::Schema;
+use lance_core::{Error, Result};
+use snafu::{location, Location};
+
+/// How to write vectors whose dimension is not the one of their column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VectorDimensionPolicy {
+    /// Fail the write.
+    #[default]
+    Error,
+    /// Pad shorter vectors with zeros, and fail on longer ones.
+    Pad,
+    /// Truncate longer vectors, and fail on shorter ones.
+    Truncate,
+    /// Pad shorter vectors with zeros, and truncate longer ones.
+    PadOrTruncate,
+}
+
+impl VectorDimensionPolicy {
+    fn allows(&self, len: usize, dim: usize) -> bool {
+        match self {
+            Self::Error => len == dim,
+            Self::Pad => len <= dim,
+            Self::Truncate => len >= dim,
+            Self::PadOrTruncate => true,
+        }
+    }
+}
+
+impl TryFrom<&str> for VectorDimensionPolicy {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "error" => Ok(Self::Error),
+            "pad" => Ok(Self::Pad),
+            "truncate" => Ok(Self::Truncate),
+            "pad_or_truncate" => Ok(Self::PadOrTruncate),
+            _ => Err(Error::invalid_input(
+                format!(
+                    "Invalid vector dimension policy: {}, expected error, pad, truncate or pad_or_truncate",
+                    value
+                ),
+                location!(),
+            )),
+        }
+    }
+}
+
+impl FromStr for VectorDimensionPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::try_from(s)
+    }
+}
+
+impl Display for VectorDimensionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Error => "error",
+            Self::Pad => "pad",
+            Self::Truncate => "truncate",
+            Self::PadOrTruncate => "pad_or_truncate",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The values and the range of values of each row of a list array.
+fn list_parts(array: &dyn Array) -> Option<(ArrayRef, Vec<(usize, usize)>)> {
+    match array.data_type() {
+        DataType::FixedSizeList(_, dim) => {
+            let list = array.as_fixed_size_list();
+            let dim = *dim as usize;
+            let offset = list.offset() * dim;
+            let ranges = (0..list.len())
+                .map(|row| (offset + row * dim, offset + (row + 1) * dim))
+                .collect();
+            Some((list.values().clone(), ranges))
+        }
+        DataType::List(_) => {
+            let list = array.as_list::<i32>();
+            let ranges = list
+                .value_offsets()
+                .windows(2)
+                .map(|w| (w[0] as usize, w[1] as usize))
+                .collect();
+            Some((list.values().clone(), ranges))
+        }
+        DataType::LargeList(_) => {
+            let list = array.as_list::<i64>();
+            let ranges = list
+                .value_offsets()
+                .windows(2)
+                .map(|w| (w[0] as usize, w[1] as usize))
+                .collect();
+            Some((list.values().clone(), ranges))
+        }
+        _ => None,
+    }
+}
+
+/// Conform a column to the vector type of `field`.
+fn conform_column(
+    array: &ArrayRef,
+    field: &ArrowField,
+    policy: VectorDimensionPolicy,
+    batch_index: usize,
+) -> Result<ArrayRef> {
+    if array.data_type() == field.data_type() {
+        return Ok(array.clone());
+    }
+    let DataType::FixedSizeList(item_field, dim) = field.data_type() else {
+        unreachable!("only vector columns are conformed");
+    };
+    let dim = *dim as usize;
+    let (values, ranges) = list_parts(array.as_ref()).ok_or_else(|| {
+        Error::invalid_input(
+            format!(
+                "Batch {}: column {} is {}, which can't be written as vectors of {}",
+                batch_index,
+                field.name(),
+                array.data_type(),
+                field.data_type()
+            ),
+            location!(),
+        )
+    })?;
+    let values = cast(&values, item_field.data_type()).map_err(|err| {
+        Error::invalid_input(
+            format!(
+                "Batch {}: can't cast the values of column {} to {}: {}",
+                batch_index,
+                field.name(),
+                item_field.data_type(),
+                err
+            ),
+            location!(),
+        )
+    })?;
+
+    // The padding refers to a zero after the values.
+    let zero = cast(&Int8Array::from(vec![0]), item_field.data_type())?;
+    let pad_index = values.len() as u64;
+    let values = concat(&[values.as_ref(), zero.as_ref()])?;
+
+    let mut indices = Vec::with_capacity(ranges.len() * dim);
+    for (row, (start, end)) in ranges.into_iter().enumerate() {
+        let len = end - start;
+        if array.is_valid(row) && !policy.allows(len, dim) {
+            return Err(Error::invalid_input(
+                format!(
+                    "Batch {}: vector {} of column {} has dimension {}, expected {}",
+                    batch_index,
+                    row,
+                    field.name(),
+                    len,
+                    dim
+                ),
+                location!(),
+            ));
+        }
+        let kept = if array.is_valid(row) { len.min(dim) } else { 0 };
+        indices.extend((start..start + kept).map(|i| i as u64));
+        indices.extend(std::iter::repeat(pad_index).take(dim - kept));
+    }
+    let values = if indices.is_empty() {
+        new_empty_array(item_field.data_type())
+    } else {
+        take(&values, &UInt64Array::from(indices), None)?
+    };
+    Ok(Arc::new(FixedSizeListArray::try_new(
+        item_field.clone(),
+        dim as i32,
+        values,
+        array.nulls().cloned(),
+    )?))
+}
+
+/// The schema of the conformed batches: the vector columns of `target`
+/// replace the columns of the same name.
+fn conformed_schema(schema: &ArrowSchema, target: &ArrowSchema) -> ArrowSchema {
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|field| match target.field_with_name(field.name()) {
+            Ok(target_field) if matches!(target_field.data_type(), DataType::FixedSizeList(..)) => {
+                Arc::new(target_field.clone())
+            }
+            _ => field.clone(),
+        })
+        .collect::<Vec<_>>();
+    ArrowSchema::new_with_metadata(fields, schema.metadata().clone())
+}
+
+fn conform_batch(
+    batch: RecordBatch,
+    schema: &SchemaRef,
+    policy: VectorDimensionPolicy,
+    batch_index: usize,
+) -> Result<RecordBatch> {
+    let batch_schema = batch.schema();
+    let batch_fields = batch_schema.fields();
+    let matches_schema = batch_fields.len() == schema.fields().len()
+        && batch_fields
+            .iter()
+            .zip(schema.fields())
+            .all(|(field, expected)| field.name() == expected.name());
+    if !matches_schema {
+        return Err(Error::invalid_input(
+            format!(
+                "Batch {}: columns {:?} don't match the columns of the write {:?}",
+                batch_index,
+                batch_fields.iter().map(|f| f.name()).collect::<Vec<_>>(),
+                schema.fields().iter().map(|f| f.name()).collect::<Vec<_>>()
+            ),
+            location!(),
+        ));
+    }
+    let columns = batch
+        .columns()
+        .iter()
+        .zip(schema.fields())
+        .map(|(column, field)| match field.data_type() {
+            DataType::FixedSizeList(..) => conform_column(column, field, policy, batch_index),
+            _ => Ok(column.clone()),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    RecordBatch::try_new(schema.clone(), columns)
+        .map_err(|err| Error::invalid_input(format!("Batch {}: {}", batch_index, err), location!()))
+}
+
+/// Conform the vectors of each batch of the stream to the vector columns of
+/// `target`, or of `schema`, the schema of the stream, if there is none.
+///
+/// Returns the conformed stream and its schema.
+pub fn conform_vectors(
+    stream: SendableRecordBatchStream,
+    schema: &Schema,
+    target: Option<&Schema>,
+    policy: VectorDimensionPolicy,
+) -> Result<(SendableRecordBatchStream, Schema)> {
+    let (arrow_schema, schema) = if let Some(target) = target {
+        let arrow_schema = conformed_schema(stream.schema().as_ref(), &target.into());
+        let schema = Schema::try_from(&arrow_schema)?;
+        (Arc::new(arrow_schema), schema)
+    } else {
+        (stream.schema(), schema.clone())
+    };
+    let stream_schema = arrow_schema.clone();
+    let stream = stream.enumerate().map(move |(batch_index, batch)| {
+        let batch = batch?;
+        conform_batch(batch, &arrow_schema, policy, batch_index).map_err(Into::into)
+    });
+    Ok((
+        Box::pin(RecordBatchStreamAdapter::new(stream_schema, stream)),
+        schema,
+    ))
+}
+
+/// The type a view column is written as: the type of the column of the same
+/// name in `target` or, since views are not limited to 2GB of data, the large
+/// variant of the byte array type.
+fn view_storage_type(field: &ArrowField, target: Option<&ArrowSchema>) -> Option<DataType> {
+    let target_type = target
+        .and_then(|target| target.field_with_name(field.name()).ok())
+        .map(|target_field| target_field.data_type());
+    match (field.data_type(), target_type) {
+        (DataType::Utf8View, Some(ty @ (DataType::Utf8 | DataType::LargeUtf8)))
+        | (DataType::BinaryView, Some(ty @ (DataType::Binary | DataType::LargeBinary))) => {
+            Some(ty.clone())
+        }
+        (DataType::Utf8View, _) => Some(DataType::LargeUtf8),
+        (DataType::BinaryView, _) => Some(DataType::LargeBinary),
+        _ => None,
+    }
+}
+
+/// The schema of `schema` with its view columns replaced by their storage
+/// types, if it has any.
+fn view_storage_schema(schema: &ArrowSchema, target: Option<&Schema>) -> Option<SchemaRef> {
+    let target = target.map(ArrowSchema::from);
+    let storage_types = schema
+        .fields()
+        .iter()
+        .map(|field| view_storage_type(field, target.as_ref()))
+        .collect::<Vec<_>>();
+    if storage_types.iter().all(Option::is_none) {
+        return None;
+    }
+    let fields = schema
+        .fields()
+        .iter()
+        .zip(&storage_types)
+        .map(|(field, storage_type)| match storage_type {
+            Some(data_type) => Arc::new(field.as_ref().clone().with_data_type(data_type.clone())),
+            None => field.clone(),
+        })
+        .collect::<Vec<_>>();
+    Some(Arc::new(ArrowSchema::new_with_metadata(
+        fields,
+        schema.metadata().clone(),
+    )))
+}
+
+/// Cast the columns of `batch` to the types of `schema`.
+fn cast_views(
+    batch: RecordBatch,
+    schema: &SchemaRef,
+) -> std::result::Result<RecordBatch, ArrowError> {
+    let columns = batch
+        .columns()
+        .iter()
+        .zip(schema.fields())
+        .map(|(column, field)| {
+            if column.data_type() == field.data_type() {
+                Ok(column.clone())
+            } else {
+                cast_with_options(column, field.data_type(), &Default::default())
+            }
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    RecordBatch::try_new(schema.clone(), columns)
+}
+
+/// Cast the string and binary view columns of each batch to the byte array
+/// types of the columns of `target`, or to the large byte array types if there
+/// is no such column.
+///
+/// Returns `reader` itself if it has no view columns.
+pub fn conform_views(
+    reader: Box<dyn RecordBatchReader + Send>,
+    target: Option<&Schema>,
+) -> Result<Box<dyn RecordBatchReader + Send>> {
+    let Some(conformed_schema) = view_storage_schema(&reader.schema(), target) else {
+        return Ok(reader);
+    };
+    let batch_schema = conformed_schema.clone();
+    let batches = reader.map(move |batch| cast_views(batch?, &batch_schema));
+    Ok(Box::new(RecordBatchIterator::new(
+        batches,
+        conformed_schema,
+    )))
+}
+
+/// Like [`conform_views`], for a stream of batches.
+pub(super) fn conform_views_stream(
+    stream: SendableRecordBatchStream,
+    target: Option<&Schema>,
+) -> SendableRecordBatchStream {
+    let Some(conformed_schema) = view_storage_schema(&stream.schema(), target) else {
+        return stream;
+    };
+    let batch_schema = conformed_schema.clone();
+    let stream = stream.map(move |batch| cast_views(batch?, &batch_schema).map_err(Into::into));
+    Box::pin(RecordBatchStreamAdapter::new(conformed_schema, stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::types::Float32Type;
+    use arrow_array::{Float32Array, Float64Array, ListArray};
+
+    fn vector_field(dim: i32) -> ArrowField {
+        ArrowField::new(
+            "v",
+            DataType::FixedSizeList(
+                Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                dim,
+            ),
+            true,
+        )
+    }
+
+    fn to_vecs(array: &ArrayRef) -> Vec<Option<Vec<f32>>> {
+        array
+            .as_fixed_size_list()
+            .iter()
+            .map(|v| v.map(|v| v.as_primitive::<Float32Type>().values().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_conform_column() {
+        let lists: ArrayRef = Arc::new(ListArray::from_iter_primitive::<
+            arrow_array::types::Float64Type,
+            _,
+            _,
+        >(vec![
+            Some(vec![Some(1.0), Some(2.0)]),
+            None,
+            Some(vec![Some(1.0), Some(2.0), Some(3.0), Some(4.0)]),
+            Some(vec![Some(1.0), Some(2.0), Some(3.0)]),
+        ]));
+
+        let conformed = conform_column(
+            &lists,
+            &vector_field(3),
+            VectorDimensionPolicy::PadOrTruncate,
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            to_vecs(&conformed),
+            vec![
+                Some(vec![1.0, 2.0, 0.0]),
+                None,
+                Some(vec![1.0, 2.0, 3.0]),
+                Some(vec![1.0, 2.0, 3.0])
+            ]
+        );
+
+        let err = conform_column(&lists, &vector_field(3), VectorDimensionPolicy::Pad, 7)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Batch 7: vector 2 of column v has dimension 4, expected 3"),
+            "{}",
+            err
+        );
+        let err = conform_column(&lists, &vector_field(3), VectorDimensionPolicy::Truncate, 0)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("vector 0 of column v has dimension 2"),
+            "{}",
+            err
+        );
+
+        // Vectors of the right dimension are cast to the value type.
+        let vectors: ArrayRef = Arc::new(
+            FixedSizeListArray::try_new(
+                Arc::new(ArrowField::new("item", DataType::Float64, true)),
+                2,
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0, 4.0])),
+                None,
+            )
+            .unwrap(),
+        );
+        let conformed =
+            conform_column(&vectors, &vector_field(2), VectorDimensionPolicy::Error, 0).unwrap();
+        assert_eq!(
+            to_vecs(&conformed),
+            vec![Some(vec![1.0, 2.0]), Some(vec![3.0, 4.0])]
+        );
+
+        let strings: ArrayRef = Arc::new(arrow_array::StringArray::from(vec!["a"]));
+        assert!(
+            conform_column(&strings, &vector_field(2), VectorDimensionPolicy::Error, 0).is_err()
+        );
+        let floats: ArrayRef = Arc::new(Float32Array::from(vec![1.0]));
+        assert!(
+            conform_column(&floats, &vector_field(2), VectorDimensionPolicy::Error, 0).is_err()
+        );
+    }
+}
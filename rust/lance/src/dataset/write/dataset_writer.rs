@@ -0,0 +1,440 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_schema::Schema as ArrowSchema;
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::StreamExt;
+use lance_core::datatypes::{Schema, SchemaCompareOptions};
+use lance_core::sink::AsyncBatchSink;
+use lance_core::{Error, Result};
+use lance_io::object_store::ObjectStore;
+use lance_table::feature_flags::can_write_dataset;
+use lance_table::format::Fragment;
+use lance_table::io::commit::CommitHandler;
+use log::warn;
+use object_store::path::Path;
+use snafu::{location, Location};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use super::conform::conform_views_stream;
+use super::{
+    append_schema, conform_vectors, mark_delta_fragments, maybe_merge_delta_fragments,
+    write_fragments_internal, WriteMode, WriteParams,
+};
+use crate::dataset::builder::DatasetBuilder;
+use crate::dataset::partitioning::write_partitioning;
+use crate::dataset::transaction::{Operation, Transaction};
+use crate::dataset::{ManifestWriteConfig, ReadParams};
+use crate::io::commit::{commit_new_dataset, commit_transaction};
+use crate::session::Session;
+use crate::Dataset;
+
+/// A write to a dataset that has been checked, but not committed yet.
+pub struct PendingWrite {
+    uri: String,
+    pub params: WriteParams,
+    pub object_store: Arc<ObjectStore>,
+    pub base: Path,
+    commit_handler: Arc<dyn CommitHandler>,
+    /// The dataset written to, unless it is being created.
+    pub dataset: Option<Dataset>,
+}
+
+impl PendingWrite {
+    pub async fn try_new(uri: &str, params: Option<WriteParams>) -> Result<Self> {
+        let mut params = params.unwrap_or_default();
+        let (object_store, base, commit_handler) = Dataset::params_from_uri(
+            uri,
+            &params.commit_handler,
+            &params.store_params,
+            params.session.as_deref(),
+        )
+        .await?;
+
+        // Read expected manifest path for the dataset
+        let dataset_exists = match commit_handler
+            .resolve_latest_version(&base, &object_store)
+            .await
+        {
+            Ok(_) => true,
+            Err(Error::NotFound { .. }) => false,
+            Err(e) => return Err(e),
+        };
+
+        // Running checks for the different write modes
+        // create + dataset already exists = error
+        if dataset_exists && matches!(params.mode, WriteMode::Create) {
+            return Err(Error::DatasetAlreadyExists {
+                uri: uri.to_owned(),
+                location: location!(),
+            });
+        }
+
+        // append + dataset doesn't already exists = warn + switch to create mode
+        if !dataset_exists
+            && (matches!(params.mode, WriteMode::Append)
+                || matches!(params.mode, WriteMode::Overwrite))
+        {
+            warn!("No existing dataset at {uri}, it will be created");
+            params = WriteParams {
+                mode: WriteMode::Create,
+                ..params
+            };
+        }
+        let dataset = if matches!(params.mode, WriteMode::Create) {
+            None
+        } else {
+            // pull the store params from write params because there might be creds in there
+            Some(
+                DatasetBuilder::from_uri(uri)
+                    .with_read_params(ReadParams {
+                        store_options: params.store_params.clone(),
+                        commit_handler: params.commit_handler.clone(),
+                        session: params.session.clone(),
+                        ..Default::default()
+                    })
+                    .load()
+                    .await?,
+            )
+        };
+
+        if let Some(d) = dataset.as_ref() {
+            if !can_write_dataset(d.manifest.writer_feature_flags) {
+                let message = format!(
+                    "This dataset cannot be written by this version of Lance. \
+                Please upgrade Lance to write to this dataset.\n Flags: {}",
+                    d.manifest.writer_feature_flags
+                );
+                return Err(Error::NotSupported {
+                    source: message.into(),
+                    location: location!(),
+                });
+            }
+        }
+
+        params.partitioning = write_partitioning(
+            dataset.as_ref(),
+            matches!(params.mode, WriteMode::Append),
+            params.partitioning.as_ref(),
+        )?;
+        if let (Some(d), WriteMode::Append, None) =
+            (dataset.as_ref(), params.mode, params.conform_vectors)
+        {
+            params.conform_vectors = d.config().conform_vectors()?;
+        }
+
+        Ok(Self {
+            uri: uri.to_string(),
+            params,
+            object_store: Arc::new(object_store),
+            base,
+            commit_handler,
+            dataset,
+        })
+    }
+
+    /// Check that data with `schema` can be written.
+    pub fn check_schema(&self, schema: &Schema) -> Result<()> {
+        // append + input schema different from existing schema = error
+        if matches!(self.params.mode, WriteMode::Append) {
+            if let Some(d) = self.dataset.as_ref() {
+                let m = d.manifest.as_ref();
+                schema.check_compatible(
+                    &append_schema(schema, &m.schema, self.params.allow_missing_columns)?,
+                    &SchemaCompareOptions {
+                        compare_dictionary: true,
+                        ..Default::default()
+                    },
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Commit the written fragments as a new version of the dataset.
+    pub async fn commit(self, schema: Schema, mut fragments: Vec<Fragment>) -> Result<Dataset> {
+        let manifest_config = ManifestWriteConfig {
+            use_move_stable_row_ids: self.params.enable_move_stable_row_ids,
+            partitioning: self
+                .params
+                .partitioning
+                .as_ref()
+                .map(|spec| spec.to_partitioning(&schema))
+                .transpose()?,
+            ..Default::default()
+        };
+        let operation = match self.params.mode {
+            WriteMode::Create | WriteMode::Overwrite => Operation::Overwrite { schema, fragments },
+            WriteMode::Append => {
+                mark_delta_fragments(&self.params, &mut fragments);
+                Operation::Append { fragments }
+            }
+        };
+
+        let transaction = Transaction::new(
+            self.dataset
+                .as_ref()
+                .map(|ds| ds.manifest.version)
+                .unwrap_or(0),
+            operation,
+            None,
+        );
+
+        let manifest = if let Some(dataset) = &self.dataset {
+            commit_transaction(
+                dataset,
+                &self.object_store,
+                self.commit_handler.as_ref(),
+                &transaction,
+                &manifest_config,
+                &Default::default(),
+            )
+            .await?
+        } else {
+            commit_new_dataset(
+                &self.object_store,
+                self.commit_handler.as_ref(),
+                &self.base,
+                &transaction,
+                &manifest_config,
+            )
+            .await?
+        };
+
+        let session = match (self.params.session.clone(), self.dataset) {
+            (Some(session), _) => session,
+            (None, Some(dataset)) => dataset.session,
+            (None, None) => Arc::new(Session::default()),
+        };
+        let mut dataset = Dataset {
+            object_store: self.object_store,
+            base: self.base,
+            uri: self.uri,
+            manifest: Arc::new(manifest),
+            session,
+            commit_handler: self.commit_handler,
+            manifest_tail: None,
+        };
+        if matches!(self.params.mode, WriteMode::Append) {
+            maybe_merge_delta_fragments(&mut dataset, &self.params).await;
+        }
+        Ok(dataset)
+    }
+}
+
+/// Writes batches to a dataset as they arrive.
+///
+/// This is the incremental counterpart of [`Dataset::write`].  The batches are
+/// written to new fragments in the background, following the same
+/// [`WriteParams`].  Nothing is visible to readers until the writer is
+/// finished, which commits all the batches as a single new version and returns
+/// the dataset at that version.
+pub struct DatasetWriter {
+    pending: Option<PendingWrite>,
+    schema: Schema,
+    sender: Option<mpsc::Sender<std::result::Result<RecordBatch, DataFusionError>>>,
+    task: Option<JoinHandle<Result<Vec<Fragment>>>>,
+}
+
+impl DatasetWriter {
+    /// Start writing batches with `schema` to the dataset at `uri`.
+    pub async fn try_new(
+        uri: &str,
+        schema: &ArrowSchema,
+        params: Option<WriteParams>,
+    ) -> Result<Self> {
+        let pending = PendingWrite::try_new(uri, params).await?;
+
+        let (sender, receiver) = mpsc::channel(1);
+        // The writer may poll for more batches after the last one
+        let batches = futures::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|batch| (batch, receiver))
+        })
+        .fuse();
+        let stream: SendableRecordBatchStream = Box::pin(RecordBatchStreamAdapter::new(
+            Arc::new(schema.clone()),
+            batches,
+        ));
+        let target = pending
+            .dataset
+            .as_ref()
+            .filter(|_| matches!(pending.params.mode, WriteMode::Append))
+            .map(|d| d.schema());
+        let stream = conform_views_stream(stream, target);
+        let schema = Schema::try_from(stream.schema().as_ref())?;
+        let (stream, schema) = if let Some(policy) = pending.params.conform_vectors {
+            conform_vectors(stream, &schema, target, policy)?
+        } else {
+            (stream, schema)
+        };
+        pending.check_schema(&schema)?;
+
+        let dataset = pending.dataset.clone();
+        let object_store = pending.object_store.clone();
+        let base = pending.base.clone();
+        let write_schema = schema.clone();
+        let params = pending.params.clone();
+        let task = tokio::spawn(async move {
+            write_fragments_internal(
+                dataset.as_ref(),
+                object_store,
+                &base,
+                &write_schema,
+                stream,
+                params,
+            )
+            .await
+        });
+
+        Ok(Self {
+            pending: Some(pending),
+            schema,
+            sender: Some(sender),
+            task: Some(task),
+        })
+    }
+
+    fn finished_error() -> Error {
+        Error::invalid_input("The dataset writer has already been finished", location!())
+    }
+
+    /// Wait for all the batches sent so far to be written.
+    async fn write_fragments(&mut self) -> Result<Vec<Fragment>> {
+        self.sender.take();
+        let task = self.task.take().ok_or_else(Self::finished_error)?;
+        task.await?
+    }
+}
+
+#[async_trait::async_trait]
+impl AsyncBatchSink for DatasetWriter {
+    /// The dataset at the committed version
+    type Output = Dataset;
+
+    async fn write_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        let sender = self.sender.as_ref().ok_or_else(Self::finished_error)?;
+        if sender.send(Ok(batch.clone())).await.is_err() {
+            // The writing task only stops receiving batches when it fails.
+            self.write_fragments().await?;
+            return Err(Error::Internal {
+                message: "The dataset writer stopped before the end of the data".to_string(),
+                location: location!(),
+            });
+        }
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<Dataset> {
+        let fragments = self.write_fragments().await?;
+        let pending = self.pending.take().ok_or_else(Self::finished_error)?;
+        pending.commit(self.schema.clone(), fragments).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{cast::AsArray, types::Int32Type, Int32Array, StringViewArray};
+    use arrow_schema::{DataType, Field as ArrowField};
+    use lance_core::sink::TeeSink;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dataset_writer() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batches = (0..5)
+            .map(|i| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int32Array::from_iter_values(i * 10..(i + 1) * 10))],
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let params = WriteParams {
+            max_rows_per_file: 20,
+            max_rows_per_group: 10,
+            ..Default::default()
+        };
+
+        let mut writer = DatasetWriter::try_new(test_uri, &schema, Some(params.clone()))
+            .await
+            .unwrap();
+        for batch in &batches[..3] {
+            writer.write_batch(batch).await.unwrap();
+        }
+        // Nothing is committed before the writer is finished.
+        assert!(Dataset::open(test_uri).await.is_err());
+        let dataset = writer.finish().await.unwrap();
+        assert_eq!(dataset.version().version, 1);
+        assert_eq!(dataset.get_fragments().len(), 2);
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 30);
+        assert!(writer.write_batch(&batches[3]).await.is_err());
+
+        // Tee the rest of the data to a new dataset and appended to the first.
+        let other_dir = tempfile::tempdir().unwrap();
+        let append_params = WriteParams {
+            mode: WriteMode::Append,
+            ..params.clone()
+        };
+        let mut sink = TeeSink::new(
+            DatasetWriter::try_new(test_uri, &schema, Some(append_params))
+                .await
+                .unwrap(),
+            DatasetWriter::try_new(other_dir.path().to_str().unwrap(), &schema, Some(params))
+                .await
+                .unwrap(),
+        );
+        let data = futures::stream::iter(batches[3..].to_vec()).map(Ok);
+        let (appended, other) = sink.write_stream(data).await.unwrap();
+        assert_eq!(appended.version().version, 2);
+        assert_eq!(appended.count_rows(None).await.unwrap(), 50);
+        assert_eq!(other.count_rows(None).await.unwrap(), 20);
+
+        let values = appended.scan().try_into_batch().await.unwrap();
+        let values = values["i"].as_primitive::<Int32Type>().values().to_vec();
+        assert_eq!(values, (0..50).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_dataset_writer_views() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "s",
+            DataType::Utf8View,
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringViewArray::from_iter_values(["a", "b", "c"]))],
+        )
+        .unwrap();
+
+        let mut writer = DatasetWriter::try_new(test_uri, &schema, None)
+            .await
+            .unwrap();
+        writer.write_batch(&batch).await.unwrap();
+        let dataset = writer.finish().await.unwrap();
+        // Views are stored as the large variant of their type
+        assert_eq!(
+            dataset.schema().field("s").unwrap().data_type(),
+            DataType::LargeUtf8
+        );
+        let values = dataset.scan().try_into_batch().await.unwrap();
+        let values = values["s"].as_string::<i64>().iter().collect::<Vec<_>>();
+        assert_eq!(values, vec![Some("a"), Some("b"), Some("c")]);
+    }
+}
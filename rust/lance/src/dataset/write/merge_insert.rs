@@ -50,7 +50,7 @@ use lance_core::{
     Error, Result,
 };
 use lance_datafusion::{
-    exec::{execute_plan, LanceExecutionOptions, OneShotExec},
+    exec::{execute_plan, OneShotExec},
     utils::reader_to_stream,
 };
 use lance_index::DatasetIndexExt;
@@ -61,7 +61,10 @@ use snafu::{location, Location, ResultExt};
 
 use crate::{
     datafusion::dataframe::SessionContextExt,
-    dataset::transaction::{Operation, Transaction},
+    dataset::{
+        delete::deleted_rows,
+        transaction::{Operation, Transaction},
+    },
     index::DatasetIndexInternalExt,
     io::{
         commit::commit_transaction,
@@ -73,33 +76,41 @@ use crate::{
 use super::write_fragments_internal;
 
 // "update if" expressions typically compare fields from the source table to the target table.
-// These tables have the same schema and so filter expressions need to differentiate.  To do that
+// These tables have the same columns and so filter expressions need to differentiate.  To do that
 // we wrap the left side and the right side in a struct and make a single "combined schema"
-fn combined_schema(schema: &Schema) -> Schema {
-    let target = Field::new("target", DataType::Struct(schema.fields.clone()), false);
-    let source = Field::new("source", DataType::Struct(schema.fields.clone()), false);
+fn combined_schema(source_schema: &Schema, target_schema: &Schema) -> Schema {
+    let target = Field::new(
+        "target",
+        DataType::Struct(target_schema.fields.clone()),
+        false,
+    );
+    let source = Field::new(
+        "source",
+        DataType::Struct(source_schema.fields.clone()),
+        false,
+    );
     Schema::new(vec![source, target])
 }
 
 // This takes a double-wide table (e.g. the result of the outer join below) and takes the left
-// half, puts it into a struct, then takes the right half, and puts that into a struct.  This
+// side, puts it into a struct, then takes the right side, and puts that into a struct.  This
 // makes the table match the "combined schema" so we can apply an "update if" expression
-fn unzip_batch(batch: &RecordBatch, schema: &Schema) -> RecordBatch {
+fn unzip_batch(batch: &RecordBatch, source_schema: &Schema, target_schema: &Schema) -> RecordBatch {
     // The schema of the combined batches will be:
-    // target_data_keys, target_data_non_keys, target_data_row_id, source_data_keys, source_data_non_keys
-    // The keys and non_keys on both sides will be equal
+    // source_data_columns, target_data_columns, target_data_row_id
+    // The source may only have some of the columns of the target
     let num_fields = batch.num_columns();
-    debug_assert_eq!(num_fields % 2, 1);
-    let half_num_fields = num_fields / 2;
+    let right_offset = source_schema.fields.len();
     let row_id_col = num_fields - 1;
+    debug_assert_eq!(row_id_col - right_offset, target_schema.fields.len());
 
-    let source_arrays = batch.columns()[0..half_num_fields].to_vec();
-    let source = StructArray::new(schema.fields.clone(), source_arrays, None);
+    let source_arrays = batch.columns()[0..right_offset].to_vec();
+    let source = StructArray::new(source_schema.fields.clone(), source_arrays, None);
 
-    let target_arrays = batch.columns()[half_num_fields..row_id_col].to_vec();
-    let target = StructArray::new(schema.fields.clone(), target_arrays, None);
+    let target_arrays = batch.columns()[right_offset..row_id_col].to_vec();
+    let target = StructArray::new(target_schema.fields.clone(), target_arrays, None);
 
-    let combined_schema = combined_schema(schema);
+    let combined_schema = combined_schema(source_schema, target_schema);
     RecordBatch::try_new(
         Arc::new(combined_schema),
         vec![Arc::new(source), Arc::new(target)],
@@ -158,22 +169,71 @@ pub enum WhenMatched {
     /// The row is updated (similar to UpdateAll) only for rows where the expression evaluates to
     /// true
     UpdateIf(Expr),
+    /// Only the given columns of the row are updated, and the others keep their old values, for
+    /// rows where the condition, if any, evaluates to true
+    ///
+    /// The source table only needs the key columns and the updated columns, unless new rows are
+    /// inserted.  This can be used to update a few columns of wide rows.
+    UpdateColumns {
+        columns: Vec<String>,
+        condition: Option<Expr>,
+    },
 }
 
 impl WhenMatched {
     pub fn update_if(dataset: &Dataset, expr: &str) -> Result<Self> {
+        Ok(Self::UpdateIf(Self::parse_condition(dataset, expr)?))
+    }
+
+    /// Create an instance of WhenMatched::UpdateColumns, with an optional SQL
+    /// condition that can compare the source and the target, such as
+    /// `source.updated_at > target.updated_at`
+    pub fn update_columns(
+        dataset: &Dataset,
+        columns: &[impl AsRef<str>],
+        condition: Option<&str>,
+    ) -> Result<Self> {
+        if columns.is_empty() {
+            return Err(Error::invalid_input(
+                "A merge insert operation must update at least one column",
+                location!(),
+            ));
+        }
+        let columns = columns
+            .iter()
+            .map(|column| {
+                let column = column.as_ref();
+                if dataset.schema().fields.iter().any(|f| f.name == column) {
+                    Ok(column.to_string())
+                } else {
+                    Err(Error::invalid_input(
+                        format!(
+                            "Cannot update the column {} which is not a top-level column of the dataset",
+                            column
+                        ),
+                        location!(),
+                    ))
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let condition = condition
+            .map(|expr| Self::parse_condition(dataset, expr))
+            .transpose()?;
+        Ok(Self::UpdateColumns { columns, condition })
+    }
+
+    fn parse_condition(dataset: &Dataset, expr: &str) -> Result<Expr> {
         let dataset_schema: Schema = dataset.schema().into();
-        let combined_schema = combined_schema(&dataset_schema);
+        let combined_schema = combined_schema(&dataset_schema, &dataset_schema);
         let planner = Planner::new(Arc::new(combined_schema));
         let expr = planner
             .parse_filter(expr)
             .map_err(box_error)
             .context(InvalidInputSnafu)?;
-        let expr = planner
+        planner
             .optimize_expr(expr)
             .map_err(box_error)
-            .context(InvalidInputSnafu)?;
-        Ok(Self::UpdateIf(expr))
+            .context(InvalidInputSnafu)
     }
 }
 
@@ -284,6 +344,20 @@ impl MergeInsertBuilder {
         self
     }
 
+    /// Update only `columns` of the target rows that match a source row, where
+    /// `condition`, if any, evaluates to true
+    ///
+    /// The condition can reference both tables, as in
+    /// `source.updated_at > target.updated_at`.  See [WhenMatched::UpdateColumns].
+    pub fn when_matched_update(
+        &mut self,
+        columns: &[impl AsRef<str>],
+        condition: Option<&str>,
+    ) -> Result<&mut Self> {
+        self.params.when_matched = WhenMatched::update_columns(&self.dataset, columns, condition)?;
+        Ok(self)
+    }
+
     /// Specify what should happen when a source row has no match in the target
     ///
     /// These are typically "new rows"
@@ -314,6 +388,17 @@ impl MergeInsertBuilder {
                 location!(),
             ));
         }
+        if let WhenMatched::UpdateColumns { columns, .. } = &self.params.when_matched {
+            if let Some(key) = columns.iter().find(|c| self.params.on.contains(c)) {
+                return Err(Error::invalid_input(
+                    format!(
+                        "A merge insert operation cannot update its key column {}",
+                        key
+                    ),
+                    location!(),
+                ));
+            }
+        }
         Ok(MergeInsertJob {
             dataset: self.dataset.clone(),
             params: self.params.clone(),
@@ -330,10 +415,16 @@ impl MergeInsertJob {
         self.execute(stream).await
     }
 
+    // The source can leave out columns, if they aren't updated or inserted, which the merger checks
     fn check_compatible_schema(&self, schema: &Schema) -> Result<()> {
         let lance_schema: lance_core::datatypes::Schema = schema.try_into()?;
+        let columns = schema
+            .fields
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect::<Vec<_>>();
         lance_schema.check_compatible(
-            self.dataset.schema(),
+            &self.dataset.schema().project(&columns)?,
             &SchemaCompareOptions {
                 compare_dictionary: true,
                 ..Default::default()
@@ -439,13 +530,7 @@ impl MergeInsertJob {
             )
             .unwrap(),
         );
-        execute_plan(
-            joined,
-            LanceExecutionOptions {
-                use_spilling: true,
-                ..Default::default()
-            },
-        )
+        execute_plan(joined, self.dataset.spilling_execution_options()?)
     }
 
     // If the join keys are not indexed then we need to do a full scan of the table
@@ -500,15 +585,17 @@ impl MergeInsertJob {
         source: SendableRecordBatchStream,
     ) -> Result<(Arc<Dataset>, MergeStats)> {
         let schema = source.schema();
+        let dataset_schema = Arc::new(Schema::from(self.dataset.schema()));
+        let merger = Merger::try_new(self.params.clone(), schema, dataset_schema)?;
 
         let joined = self.create_joined_stream(source).await?;
-        let merger = Merger::try_new(self.params, schema.clone())?;
+        let output_schema = merger.output_schema.clone();
         let merge_statistics = merger.merge_stats.clone();
         let deleted_rows = merger.deleted_rows.clone();
         let stream = joined
             .and_then(move |batch| merger.clone().execute_batch(batch))
             .try_flatten();
-        let stream = RecordBatchStreamAdapter::new(schema, stream);
+        let stream = RecordBatchStreamAdapter::new(output_schema, stream);
 
         let new_fragments = write_fragments_internal(
             None,
@@ -597,12 +684,14 @@ impl MergeInsertJob {
         updated_fragments: Vec<Fragment>,
         new_fragments: Vec<Fragment>,
     ) -> Result<Arc<Dataset>> {
+        let modified_rows = deleted_rows(&dataset, &updated_fragments).await?;
         let operation = Operation::Update {
             removed_fragment_ids,
             updated_fragments,
             new_fragments,
         };
-        let transaction = Transaction::new(dataset.manifest.version, operation, None);
+        let transaction = Transaction::new(dataset.manifest.version, operation, None)
+            .with_modified_rows(modified_rows);
 
         let manifest = commit_transaction(
             dataset.as_ref(),
@@ -647,23 +736,42 @@ struct Merger {
     // User statistics for merging
     merge_stats: Arc<Mutex<MergeStats>>,
     // Physical "when matched update if" expression, only set if params.when_matched is UpdateIf
+    // or UpdateColumns with a condition
     match_filter_expr: Option<Arc<dyn PhysicalExpr>>,
     // The parameters controlling the merge
     params: MergeInsertParams,
-    // The schema of the dataset, used to recover nullability information
-    schema: Arc<Schema>,
+    // The schema of the source data
+    source_schema: Arc<Schema>,
+    // The schema of the dataset
+    target_schema: Arc<Schema>,
+    // The schema of the written rows, the columns of the dataset, used to recover nullability
+    // information
+    output_schema: Arc<Schema>,
+    // The positions of the key columns of the source and the target in the joined batches
+    source_keys: Vec<usize>,
+    target_keys: Vec<usize>,
+    // The positions in the joined batches of the columns of the updated rows, empty if matched
+    // rows are not updated
+    matched_columns: Vec<usize>,
+    // The positions in the joined batches of the columns of the inserted rows, empty if rows
+    // are not inserted
+    inserted_columns: Vec<usize>,
 }
 
 impl Merger {
     // Creates a new merger with an empty set of deleted rows, compiles expressions, if present
-    fn try_new(params: MergeInsertParams, schema: Arc<Schema>) -> Result<Self> {
+    fn try_new(
+        params: MergeInsertParams,
+        source_schema: Arc<Schema>,
+        target_schema: Arc<Schema>,
+    ) -> Result<Self> {
         let delete_expr = if let WhenNotMatchedBySource::DeleteIf(expr) =
             &params.delete_not_matched_by_source
         {
-            let planner = Planner::new(schema.clone());
+            let planner = Planner::new(target_schema.clone());
             let expr = planner.optimize_expr(expr.clone())?;
             let physical_expr = planner.create_physical_expr(&expr)?;
-            let data_type = physical_expr.data_type(&schema)?;
+            let data_type = physical_expr.data_type(&target_schema)?;
             if data_type != DataType::Boolean {
                 return Err(Error::invalid_input(format!("Merge insert conditions must be expressions that return a boolean value, received expression ({}) which has data type {}", expr, data_type), location!()));
             }
@@ -671,8 +779,13 @@ impl Merger {
         } else {
             None
         };
-        let match_filter_expr = if let WhenMatched::UpdateIf(expr) = &params.when_matched {
-            let combined_schema = Arc::new(combined_schema(&schema));
+        let match_filter = match &params.when_matched {
+            WhenMatched::UpdateIf(expr) => Some(expr),
+            WhenMatched::UpdateColumns { condition, .. } => condition.as_ref(),
+            _ => None,
+        };
+        let match_filter_expr = if let Some(expr) = match_filter {
+            let combined_schema = Arc::new(combined_schema(&source_schema, &target_schema));
             let planner = Planner::new(combined_schema.clone());
             let expr = planner.optimize_expr(expr.clone())?;
             let match_expr = planner.create_physical_expr(&expr)?;
@@ -684,29 +797,97 @@ impl Merger {
         } else {
             None
         };
+
+        // The joined batches hold the source columns, then the target columns and the row id
+        let right_offset = source_schema.fields.len();
+        let source_column = |name: &str, purpose: &str| {
+            source_schema.index_of(name).map_err(|_| {
+                Error::invalid_input(
+                    format!(
+                        "The source data of the merge insert is missing the column {}, which it {}",
+                        name, purpose
+                    ),
+                    location!(),
+                )
+            })
+        };
+        let source_keys = params
+            .on
+            .iter()
+            .map(|key| source_column(key, "joins on"))
+            .collect::<Result<Vec<_>>>()?;
+        let target_keys = params
+            .on
+            .iter()
+            .map(|key| -> Result<usize> { Ok(right_offset + target_schema.index_of(key)?) })
+            .collect::<Result<Vec<_>>>()?;
+        let matched_columns = match &params.when_matched {
+            WhenMatched::DoNothing => Vec::new(),
+            WhenMatched::UpdateColumns { columns, .. } => target_schema
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    if columns.contains(field.name()) {
+                        source_column(field.name(), "updates")
+                    } else {
+                        Ok(right_offset + i)
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?,
+            WhenMatched::UpdateAll | WhenMatched::UpdateIf(_) => target_schema
+                .fields
+                .iter()
+                .map(|field| source_column(field.name(), "updates"))
+                .collect::<Result<Vec<_>>>()?,
+        };
+        let inserted_columns = if params.insert_not_matched {
+            target_schema
+                .fields
+                .iter()
+                .map(|field| source_column(field.name(), "inserts"))
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            Vec::new()
+        };
+        // Columns the source has keep its fields, like when the source has every column
+        let output_fields = target_schema
+            .fields
+            .iter()
+            .map(|field| match source_schema.field_with_name(field.name()) {
+                Ok(source_field) => source_field.clone(),
+                Err(_) => field.as_ref().clone(),
+            })
+            .collect::<Vec<_>>();
+        let output_schema = Arc::new(Schema::new_with_metadata(
+            output_fields,
+            source_schema.metadata().clone(),
+        ));
+
         Ok(Self {
             deleted_rows: Arc::new(Mutex::new(RoaringTreemap::new())),
             delete_expr,
             merge_stats: Arc::new(Mutex::new(MergeStats::default())),
             match_filter_expr,
             params,
-            schema,
+            source_schema,
+            target_schema,
+            output_schema,
+            source_keys,
+            target_keys,
+            matched_columns,
+            inserted_columns,
         })
     }
 
-    // Retrieves a bitmap of rows where at least one of the columns in the range
-    // col_offset..coll_offset+num_cols is not null.
+    // Retrieves a bitmap of rows where at least one of the columns is not null.
     //
-    fn not_all_null(
-        batch: &RecordBatch,
-        col_offset: usize,
-        num_cols: usize,
-    ) -> Result<BooleanArray> {
+    fn not_all_null(batch: &RecordBatch, columns: &[usize]) -> Result<BooleanArray> {
         // For our purposes we know there is always at least 1 on key
-        debug_assert_ne!(num_cols, 0);
-        let mut at_least_one_valid = arrow::compute::is_not_null(batch.column(col_offset))?;
-        for idx in col_offset + 1..col_offset + num_cols {
-            let is_valid = arrow::compute::is_not_null(batch.column(idx))?;
+        debug_assert_ne!(columns.len(), 0);
+        let mut at_least_one_valid = arrow::compute::is_not_null(batch.column(columns[0]))?;
+        for idx in &columns[1..] {
+            let is_valid = arrow::compute::is_not_null(batch.column(*idx))?;
             at_least_one_valid = arrow::compute::or(&at_least_one_valid, &is_valid)?;
         }
         Ok(at_least_one_valid)
@@ -730,11 +911,9 @@ impl Merger {
     fn extract_selections(
         &self,
         combined_batch: &RecordBatch,
-        right_offset: usize,
-        num_keys: usize,
     ) -> Result<(BooleanArray, BooleanArray, BooleanArray)> {
-        let in_left = Self::not_all_null(combined_batch, 0, num_keys)?;
-        let in_right = Self::not_all_null(combined_batch, right_offset, num_keys)?;
+        let in_left = Self::not_all_null(combined_batch, &self.source_keys)?;
+        let in_right = Self::not_all_null(combined_batch, &self.target_keys)?;
         let in_both = arrow::compute::and(&in_left, &in_right)?;
         let left_only = arrow::compute::and(&in_left, &arrow::compute::not(&in_right)?)?;
         let right_only = arrow::compute::and(&arrow::compute::not(&in_left)?, &in_right)?;
@@ -755,19 +934,15 @@ impl Merger {
         let mut merge_statistics = self.merge_stats.lock().unwrap();
         let num_fields = batch.schema().fields.len();
         // The schema of the combined batches will be:
-        // source_keys, source_payload, target_keys, target_payload, row_id
-        // The keys and non_keys on both sides will be equal
-        debug_assert_eq!(num_fields % 2, 1);
+        // source_columns, target_columns, row_id
+        // The source may only have some of the columns of the target
         let row_id_col = num_fields - 1;
-        let right_offset = num_fields / 2;
-        let num_keys = self.params.on.len();
+        let right_offset = self.source_schema.fields.len();
 
-        let left_cols = Vec::from_iter(0..right_offset);
         let right_cols_with_id = Vec::from_iter(right_offset..num_fields);
 
         let mut batches = Vec::with_capacity(2);
-        let (left_only, in_both, right_only) =
-            self.extract_selections(&batch, right_offset, num_keys)?;
+        let (left_only, in_both, right_only) = self.extract_selections(&batch)?;
 
         // There is no contention on this mutex.  We're only using it to bypass the rust
         // borrow checker (the stream needs to be `sync` since it crosses an await point)
@@ -777,7 +952,7 @@ impl Merger {
             let mut matched = arrow::compute::filter_record_batch(&batch, &in_both)?;
 
             if let Some(match_filter) = self.match_filter_expr {
-                let unzipped = unzip_batch(&matched, &self.schema);
+                let unzipped = unzip_batch(&matched, &self.source_schema, &self.target_schema);
                 let filtered = match_filter.evaluate(&unzipped)?;
                 match filtered {
                     ColumnarValue::Array(mask) => {
@@ -795,21 +970,22 @@ impl Merger {
                 }
             }
 
-            merge_statistics.num_updated_rows = matched.num_rows() as u64;
+            merge_statistics.num_updated_rows += matched.num_rows() as u64;
 
             // If the filter eliminated all rows then its important we don't try and write
             // the batch at all.  Writing an empty batch currently panics
             if matched.num_rows() > 0 {
                 let row_ids = matched.column(row_id_col).as_primitive::<UInt64Type>();
                 deleted_row_ids.extend(row_ids.values());
-                let matched = matched.project(&left_cols)?;
+                // The updated columns come from the source, and the others from the target
+                let matched = matched.project(&self.matched_columns)?;
                 // The payload columns of an outer join are always nullable.  We need to restore
                 // non-nullable to columns that were originally non-nullable.  This should be safe
-                // since the not_matched rows should all be valid on the right_cols
+                // since the matched rows should all be valid on both sides
                 //
                 // Sadly we can't use with_schema because it doesn't let you toggle nullability
                 let matched = RecordBatch::try_new(
-                    self.schema.clone(),
+                    self.output_schema.clone(),
                     Vec::from_iter(matched.columns().iter().cloned()),
                 )?;
                 batches.push(Ok(matched));
@@ -817,20 +993,20 @@ impl Merger {
         }
         if self.params.insert_not_matched {
             let not_matched = arrow::compute::filter_record_batch(&batch, &left_only)?;
-            let not_matched = not_matched.project(&left_cols)?;
+            let not_matched = not_matched.project(&self.inserted_columns)?;
             // See comment above explaining this schema replacement
             let not_matched = RecordBatch::try_new(
-                self.schema.clone(),
+                self.output_schema.clone(),
                 Vec::from_iter(not_matched.columns().iter().cloned()),
             )?;
 
-            merge_statistics.num_inserted_rows = not_matched.num_rows() as u64;
+            merge_statistics.num_inserted_rows += not_matched.num_rows() as u64;
             batches.push(Ok(not_matched));
         }
         match self.params.delete_not_matched_by_source {
             WhenNotMatchedBySource::Delete => {
                 let unmatched = arrow::compute::filter(batch.column(row_id_col), &right_only)?;
-                merge_statistics.num_deleted_rows = unmatched.len() as u64;
+                merge_statistics.num_deleted_rows += unmatched.len() as u64;
                 let row_ids = unmatched.as_primitive::<UInt64Type>();
                 deleted_row_ids.extend(row_ids.values());
             }
@@ -847,13 +1023,13 @@ impl Merger {
                             mask.as_boolean(),
                         )?;
                         let row_ids = row_ids.as_primitive::<UInt64Type>();
-                        merge_statistics.num_deleted_rows = row_ids.len() as u64;
+                        merge_statistics.num_deleted_rows += row_ids.len() as u64;
                         deleted_row_ids.extend(row_ids.values());
                     }
                     ColumnarValue::Scalar(scalar) => {
                         if let ScalarValue::Boolean(Some(true)) = scalar {
                             let row_ids = unmatched.column(row_id_col).as_primitive::<UInt64Type>();
-                            merge_statistics.num_deleted_rows = row_ids.len() as u64;
+                            merge_statistics.num_deleted_rows += row_ids.len() as u64;
                             deleted_row_ids.extend(row_ids.values());
                         }
                     }
@@ -869,7 +1045,10 @@ impl Merger {
 #[cfg(test)]
 mod tests {
 
-    use arrow_array::{types::UInt32Type, RecordBatchIterator, StringArray, UInt32Array};
+    use arrow_array::{
+        types::{Int64Type, UInt32Type},
+        Int64Array, RecordBatchIterator, StringArray, UInt32Array,
+    };
     use arrow_select::concat::concat_batches;
     use datafusion::common::Column;
     use lance_datagen::{array, BatchCount, RowCount, Seed};
@@ -1156,6 +1335,123 @@ mod tests {
         check(new_batch.clone(), job, &[1, 4, 5, 6], &[], &[0, 0, 2]).await;
     }
 
+    #[tokio::test]
+    async fn test_merge_insert_update_columns() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("key", DataType::UInt32, false),
+            Field::new("value", DataType::UInt32, false),
+            Field::new("updated_at", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(UInt32Array::from(vec![1, 2, 3, 4])),
+                Arc::new(UInt32Array::from(vec![1, 1, 1, 1])),
+                Arc::new(Int64Array::from(vec![10, 10, 10, 10])),
+                Arc::new(StringArray::from(vec!["a", "b", "c", "d"])),
+            ],
+        )
+        .unwrap();
+
+        // The source only has the key and the updated columns
+        let source_schema = Arc::new(schema.project(&[0, 1, 2]).unwrap());
+        let new_batch = RecordBatch::try_new(
+            source_schema.clone(),
+            vec![
+                Arc::new(UInt32Array::from(vec![2, 3, 5])),
+                Arc::new(UInt32Array::from(vec![2, 2, 2])),
+                Arc::new(Int64Array::from(vec![5, 20, 20])),
+            ],
+        )
+        .unwrap();
+        let new_stream = || {
+            reader_to_stream(Box::new(RecordBatchIterator::new(
+                [Ok(new_batch.clone())],
+                source_schema.clone(),
+            )))
+        };
+
+        for indexed in [false, true] {
+            let test_dir = tempdir().unwrap();
+            let test_uri = test_dir.path().to_str().unwrap();
+            let batches = RecordBatchIterator::new([Ok(batch.clone())], schema.clone());
+            let mut ds = Dataset::write(batches, test_uri, None).await.unwrap();
+            if indexed {
+                ds.create_index(
+                    &["key"],
+                    IndexType::Scalar,
+                    None,
+                    &ScalarIndexParams::default(),
+                    false,
+                )
+                .await
+                .unwrap();
+            }
+            let ds = Arc::new(ds);
+
+            let keys = vec!["key".to_string()];
+            let job = MergeInsertBuilder::try_new(ds.clone(), keys.clone())
+                .unwrap()
+                .when_matched_update(
+                    &["value", "updated_at"],
+                    Some("source.updated_at > target.updated_at"),
+                )
+                .unwrap()
+                .when_not_matched(WhenNotMatched::DoNothing)
+                .try_build()
+                .unwrap();
+            let (merged, stats) = job.execute(new_stream()).await.unwrap();
+            assert_eq!(stats.num_updated_rows, 1);
+            assert_eq!(stats.num_inserted_rows, 0);
+
+            // Only the newer row is updated, and it keeps the columns that aren't updated
+            let merged = merged.scan().try_into_batch().await.unwrap();
+            let mut rows = (0..merged.num_rows())
+                .map(|i| {
+                    (
+                        merged.column(0).as_primitive::<UInt32Type>().value(i),
+                        merged.column(1).as_primitive::<UInt32Type>().value(i),
+                        merged.column(2).as_primitive::<Int64Type>().value(i),
+                        merged.column(3).as_string::<i32>().value(i).to_string(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            rows.sort();
+            assert_eq!(
+                rows,
+                vec![
+                    (1, 1, 10, "a".to_string()),
+                    (2, 1, 10, "b".to_string()),
+                    (3, 2, 20, "c".to_string()),
+                    (4, 1, 10, "d".to_string()),
+                ]
+            );
+
+            // Inserting needs every column of the dataset
+            let job = MergeInsertBuilder::try_new(ds.clone(), keys.clone())
+                .unwrap()
+                .when_matched_update(&["value"], None)
+                .unwrap()
+                .try_build()
+                .unwrap();
+            let err = job.execute(new_stream()).await.unwrap_err();
+            assert!(err.to_string().contains("name"), "{}", err);
+
+            // The updated columns must be dataset columns other than the keys
+            assert!(MergeInsertBuilder::try_new(ds.clone(), keys.clone())
+                .unwrap()
+                .when_matched_update(&["missing"], None)
+                .is_err());
+            assert!(MergeInsertBuilder::try_new(ds.clone(), keys.clone())
+                .unwrap()
+                .when_matched_update(&["key", "value"], None)
+                .unwrap()
+                .try_build()
+                .is_err());
+        }
+    }
+
     #[tokio::test]
     async fn test_indexed_merge_insert() {
         let test_dir = tempdir().unwrap();
@@ -0,0 +1,238 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Writing the rows of many partitions concurrently
+
+use std::collections::HashMap;
+
+use arrow_array::{RecordBatch, UInt32Array};
+use arrow_row::{OwnedRow, RowConverter, SortField};
+use arrow_schema::SchemaRef;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use lance_arrow::RecordBatchExt;
+use lance_core::datatypes::Schema;
+use lance_core::{Error, Result};
+use lance_table::format::Fragment;
+use snafu::{location, Location};
+use tokio::task::JoinHandle;
+
+use crate::io::exec::Planner;
+use crate::Dataset;
+
+use super::{write_fragments_internal, WriteParams};
+
+/// The batches buffered for the writer of each partition
+const PARTITION_BUFFER: usize = 4;
+
+/// The writer of a partition, fed its rows through a channel
+struct PartitionWriter {
+    sender: mpsc::Sender<RecordBatch>,
+    task: JoinHandle<Result<Vec<Fragment>>>,
+}
+
+impl PartitionWriter {
+    fn spawn(
+        dataset: &Dataset,
+        schema: &Schema,
+        arrow_schema: &SchemaRef,
+        params: &WriteParams,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(PARTITION_BUFFER);
+        let stream: SendableRecordBatchStream = Box::pin(RecordBatchStreamAdapter::new(
+            arrow_schema.clone(),
+            receiver.map(Ok),
+        ));
+        let dataset = dataset.clone();
+        let schema = schema.clone();
+        let params = params.clone();
+        let task = tokio::spawn(async move {
+            write_fragments_internal(
+                Some(&dataset),
+                dataset.object_store.clone(),
+                &dataset.base,
+                &schema,
+                stream,
+                params,
+            )
+            .await
+        });
+        Self { sender, task }
+    }
+}
+
+/// Write the rows of each value of the SQL expression `partition_by` to their
+/// own fragments, a writer per value, and return the fragments of all of them
+///
+/// The fragments are returned by partition, in the order the partitions
+/// first appear in `data`.  Like [`write_fragments_internal`], this doesn't
+/// commit them.
+pub async fn write_fragments_by_partition(
+    dataset: &Dataset,
+    schema: &Schema,
+    mut data: SendableRecordBatchStream,
+    partition_by: &str,
+    params: WriteParams,
+) -> Result<Vec<Fragment>> {
+    let arrow_schema = data.schema();
+    let planner = Planner::new(arrow_schema.clone());
+    let expr = planner.optimize_expr(planner.parse_expr(partition_by)?)?;
+    let expr = planner.create_physical_expr(&expr)?;
+    let converter = RowConverter::new(vec![SortField::new(expr.data_type(&arrow_schema)?)])?;
+
+    let mut partitions = HashMap::<OwnedRow, usize>::new();
+    let mut writers = Vec::<PartitionWriter>::new();
+    let result = async {
+        while let Some(batch) = data.next().await {
+            let batch = batch?;
+            let values = expr.evaluate(&batch)?.into_array(batch.num_rows())?;
+            let rows = converter.convert_columns(&[values])?;
+
+            // The rows of each partition of the batch, in order
+            let mut batch_rows = Vec::<(usize, Vec<u32>)>::new();
+            let mut batch_partitions = HashMap::<usize, usize>::new();
+            for (i, row) in rows.iter().enumerate() {
+                let partition = *partitions.entry(row.owned()).or_insert_with(|| {
+                    writers.push(PartitionWriter::spawn(
+                        dataset,
+                        schema,
+                        &arrow_schema,
+                        &params,
+                    ));
+                    writers.len() - 1
+                });
+                let slot = *batch_partitions.entry(partition).or_insert_with(|| {
+                    batch_rows.push((partition, Vec::new()));
+                    batch_rows.len() - 1
+                });
+                batch_rows[slot].1.push(i as u32);
+            }
+
+            for (partition, rows) in batch_rows {
+                let rows = if rows.len() == batch.num_rows() {
+                    batch.clone()
+                } else {
+                    batch.take(&UInt32Array::from(rows))?
+                };
+                // The writer only hangs up if it failed, which joining it reports
+                if writers[partition].sender.send(rows).await.is_err() {
+                    return Ok(Some(partition));
+                }
+            }
+        }
+        Ok::<_, Error>(None)
+    }
+    .await;
+
+    match result {
+        Ok(failed) => {
+            let mut fragments = Vec::new();
+            for (partition, writer) in writers.into_iter().enumerate() {
+                drop(writer.sender);
+                let written = writer.task.await?;
+                if failed == Some(partition) && written.is_ok() {
+                    return Err(Error::Internal {
+                        message: "The writer of a partition stopped before its input".to_string(),
+                        location: location!(),
+                    });
+                }
+                fragments.extend(written?);
+            }
+            Ok(fragments)
+        }
+        Err(err) => {
+            for writer in writers {
+                writer.task.abort();
+            }
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    use arrow_array::{cast::AsArray, types::Int32Type, Int32Array, RecordBatchIterator};
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use futures::TryStreamExt;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_append_partitioned() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batches = |range: std::ops::Range<i32>| {
+            let batches = range
+                .step_by(10)
+                .map(|start| {
+                    RecordBatch::try_new(
+                        schema.clone(),
+                        vec![Arc::new(Int32Array::from_iter_values(start..start + 10))],
+                    )
+                })
+                .collect::<Vec<_>>();
+            RecordBatchIterator::new(batches, schema.clone())
+        };
+        let mut dataset = Dataset::write(batches(0..10), test_uri, None)
+            .await
+            .unwrap();
+        let version = dataset.version().version;
+
+        let params = WriteParams {
+            max_rows_per_file: 10,
+            ..Default::default()
+        };
+        dataset
+            .append_partitioned(batches(10..100), "i % 3", Some(params))
+            .await
+            .unwrap();
+
+        // A single commit appends the fragments of all the partitions
+        assert_eq!(dataset.version().version, version + 1);
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 100);
+        let fragments = dataset.get_fragments();
+        // 30 rows of each partition, in fragments of up to 10 rows
+        assert_eq!(fragments.len(), 1 + 9);
+        for fragment in &fragments[1..] {
+            let batches = fragment
+                .scan()
+                .try_into_stream()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            let partitions = batches
+                .iter()
+                .flat_map(|batch| {
+                    batch
+                        .column(0)
+                        .as_primitive::<Int32Type>()
+                        .values()
+                        .to_vec()
+                })
+                .map(|i| i % 3)
+                .collect::<HashSet<_>>();
+            assert_eq!(partitions.len(), 1);
+        }
+
+        // The expression is checked against the written columns
+        let err = dataset
+            .append_partitioned(batches(100..110), "missing % 3", None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("missing"), "{}", err);
+        assert_eq!(dataset.version().version, version + 1);
+    }
+}
@@ -23,6 +23,7 @@ use lance_table::format::Fragment;
 use roaring::RoaringTreemap;
 use snafu::{location, Location, ResultExt};
 
+use crate::dataset::delete::deleted_rows;
 use crate::dataset::transaction::{Operation, Transaction};
 use crate::io::commit::commit_transaction;
 use crate::{io::exec::Planner, Dataset};
@@ -320,12 +321,14 @@ impl UpdateJob {
         updated_fragments: Vec<Fragment>,
         new_fragments: Vec<Fragment>,
     ) -> Result<Arc<Dataset>> {
+        let modified_rows = deleted_rows(&self.dataset, &updated_fragments).await?;
         let operation = Operation::Update {
             removed_fragment_ids,
             updated_fragments,
             new_fragments,
         };
-        let transaction = Transaction::new(self.dataset.manifest.version, operation, None);
+        let transaction = Transaction::new(self.dataset.manifest.version, operation, None)
+            .with_modified_rows(modified_rows);
 
         let manifest = commit_transaction(
             self.dataset.as_ref(),
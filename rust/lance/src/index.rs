@@ -12,11 +12,17 @@ use async_trait::async_trait;
 use futures::{stream, StreamExt, TryStreamExt};
 use itertools::Itertools;
 use lance_file::reader::FileReader;
+use lance_index::minhash::MinHashIndex;
 use lance_index::optimize::OptimizeOptions;
 use lance_index::pb::index::Implementation;
+use lance_index::scalar::composite::{
+    composite_index_key, parse_composite_index_key, CompositeIndexColumns, COMPOSITE_INDEX_KIND,
+};
 use lance_index::scalar::expression::IndexInformationProvider;
 use lance_index::scalar::lance_format::LanceIndexStore;
-use lance_index::scalar::ScalarIndex;
+use lance_index::scalar::{
+    lowercase_index_key, parse_lowercase_index_key, ScalarIndex, LOWERCASE_INDEX_KIND,
+};
 pub use lance_index::IndexParams;
 use lance_index::{pb, DatasetIndexExt, Index, IndexType, INDEX_FILE_NAME};
 use lance_io::traits::Reader;
@@ -33,7 +39,9 @@ use tracing::instrument;
 use uuid::Uuid;
 
 pub(crate) mod append;
+pub(crate) mod artifact;
 pub(crate) mod cache;
+pub mod minhash;
 pub(crate) mod prefilter;
 pub mod scalar;
 pub mod vector;
@@ -42,12 +50,16 @@ use crate::dataset::index::LanceIndexStoreExt;
 pub use crate::index::prefilter::{FilterLoader, PreFilter};
 
 use crate::dataset::transaction::{Operation, Transaction};
+use crate::datatypes::Schema;
 use crate::index::vector::remap_vector_index;
 use crate::io::commit::commit_transaction;
 use crate::{dataset::Dataset, Error, Result};
 
 use self::append::merge_indices;
-use self::scalar::{build_scalar_index, LANCE_SCALAR_INDEX};
+use self::minhash::{build_minhash_index, MinHashParams, LANCE_MINHASH_INDEX, MINHASH_INDEX_KIND};
+use self::scalar::{
+    build_composite_index, build_scalar_index, ScalarIndexParams, LANCE_SCALAR_INDEX,
+};
 use self::vector::{build_vector_index, VectorIndex, VectorIndexParams, LANCE_VECTOR_INDEX};
 
 /// Builds index.
@@ -73,7 +85,8 @@ pub(crate) async fn remap_index(
             location: location!(),
         })?;
 
-    if matched.fields.len() > 1 {
+    // Composite indices are opened by their first column, like other scalar indices
+    if matched.fields.len() > 1 && matched.kind.as_deref() != Some(COMPOSITE_INDEX_KIND) {
         return Err(Error::Index {
             message: "Remapping indices with multiple fields is not supported".to_string(),
             location: location!(),
@@ -101,6 +114,12 @@ pub(crate) async fn remap_index(
                 .await?;
             scalar_index.remap(row_id_map, &new_store).await?;
         }
+        IndexType::MinHash => {
+            let new_store = LanceIndexStore::from_dataset(dataset, &new_id.to_string());
+
+            let minhash_index = dataset.open_minhash_index(&index_id.to_string()).await?;
+            minhash_index.remap(row_id_map, &new_store).await?;
+        }
         IndexType::Vector => {
             remap_vector_index(
                 Arc::new(dataset.clone()),
@@ -117,15 +136,78 @@ pub(crate) async fn remap_index(
     Ok(new_id)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ScalarIndexInfo {
     indexed_columns: HashMap<String, DataType>,
+    composite_indices: Vec<CompositeIndexColumns>,
+}
+
+impl ScalarIndexInfo {
+    /// Information about the scalar indices among `indices`
+    fn from_indices(schema: &Schema, indices: &[IndexMetadata]) -> Result<Self> {
+        let indexed_fields = indices
+        .iter()
+        .filter(|idx| {
+            idx.fields.len() == 1
+                && (idx.kind.is_none() || idx.kind.as_deref() == Some(LOWERCASE_INDEX_KIND))
+        })
+        .map(|idx| {
+            let field = idx.fields[0];
+            let field = schema.field_by_id(field).ok_or_else(|| Error::Internal { message: format!("Index referenced a field with id {field} which did not exist in the schema"), location: location!() });
+            field.map(|field| {
+                // Lowercase indices are keyed by the expression they index
+                let key = if idx.kind.is_some() {
+                    lowercase_index_key(&field.name)
+                } else {
+                    field.name.clone()
+                };
+                (key, field.data_type())
+            })
+        }).collect::<Result<Vec<_>>>()?;
+        let index_info_map = HashMap::from_iter(indexed_fields);
+        let composite_indices = indices
+            .iter()
+            .filter(|idx| idx.kind.as_deref() == Some(COMPOSITE_INDEX_KIND))
+            .map(|idx| {
+                let columns = idx
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let field = schema.field_by_id(*field).ok_or_else(|| Error::Internal {
+                            message: format!("Index referenced a field with id {field} which did not exist in the schema"),
+                            location: location!(),
+                        })?;
+                        Ok((field.name.clone(), field.data_type()))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let names = columns.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+                Ok(CompositeIndexColumns {
+                    key: composite_index_key(&names),
+                    columns,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            indexed_columns: index_info_map,
+            composite_indices,
+        })
+    }
+
+    /// Stop using the index of `col`, or the composite index of key `col`, if any
+    pub(crate) fn remove(&mut self, col: &str) {
+        self.indexed_columns.remove(col);
+        self.composite_indices.retain(|index| index.key != col);
+    }
 }
 
 impl IndexInformationProvider for ScalarIndexInfo {
     fn get_index(&self, col: &str) -> Option<&DataType> {
         self.indexed_columns.get(col)
     }
+
+    fn composite_indices(&self) -> &[CompositeIndexColumns] {
+        &self.composite_indices
+    }
 }
 
 async fn open_index_proto(reader: &dyn Reader) -> Result<pb::Index> {
@@ -153,25 +235,30 @@ impl DatasetIndexExt for Dataset {
         params: &dyn IndexParams,
         replace: bool,
     ) -> Result<()> {
-        if columns.len() != 1 {
+        // Scalar indices over several columns are composite indices
+        if columns.is_empty() || (columns.len() > 1 && index_type != IndexType::Scalar) {
             return Err(Error::Index {
                 message: "Only support building index on 1 column at the moment".to_string(),
                 location: location!(),
             });
         }
         let column = columns[0];
-        let Some(field) = self.schema().field(column) else {
-            return Err(Error::Index {
-                message: format!("CreateIndex: column '{column}' does not exist"),
-                location: location!(),
-            });
-        };
+        let mut field_ids = Vec::with_capacity(columns.len());
+        for column in columns {
+            let Some(field) = self.schema().field(column) else {
+                return Err(Error::Index {
+                    message: format!("CreateIndex: column '{column}' does not exist"),
+                    location: location!(),
+                });
+            };
+            field_ids.push(field.id);
+        }
 
         // Load indices from the disk.
         let indices = self.load_indices().await?;
-        let index_name = name.unwrap_or(format!("{column}_idx"));
+        let index_name = name.unwrap_or(format!("{}_idx", columns.join("_")));
         if let Some(idx) = indices.iter().find(|i| i.name == index_name) {
-            if idx.fields == [field.id] && !replace {
+            if idx.fields == field_ids && !replace {
                 return Err(Error::Index {
                     message: format!(
                         "Index name '{index_name} already exists, \
@@ -180,7 +267,7 @@ impl DatasetIndexExt for Dataset {
                     location: location!(),
                 });
             };
-            if idx.fields != [field.id] {
+            if idx.fields != field_ids {
                 return Err(Error::Index {
                     message: format!(
                         "Index name '{index_name} already exists with different fields, \
@@ -192,9 +279,38 @@ impl DatasetIndexExt for Dataset {
         }
 
         let index_id = Uuid::new_v4();
+        let mut kind = None;
         match (index_type, params.index_name()) {
             (IndexType::Scalar, LANCE_SCALAR_INDEX) => {
-                build_scalar_index(self, column, &index_id.to_string()).await?;
+                let default_params = ScalarIndexParams::default();
+                let scalar_params = params
+                    .as_any()
+                    .downcast_ref::<ScalarIndexParams>()
+                    .unwrap_or(&default_params);
+
+                if columns.len() > 1 {
+                    build_composite_index(self, columns, &index_id.to_string(), scalar_params)
+                        .await?;
+                    kind = Some(COMPOSITE_INDEX_KIND.to_string());
+                } else {
+                    build_scalar_index(self, column, &index_id.to_string(), scalar_params).await?;
+                    if scalar_params.lowercase {
+                        kind = Some(LOWERCASE_INDEX_KIND.to_string());
+                    }
+                }
+            }
+            (IndexType::MinHash, LANCE_MINHASH_INDEX) => {
+                let minhash_params =
+                    params
+                        .as_any()
+                        .downcast_ref::<MinHashParams>()
+                        .ok_or_else(|| Error::Index {
+                            message: "MinHash index type must take a MinHashParams".to_string(),
+                            location: location!(),
+                        })?;
+
+                build_minhash_index(self, column, &index_id.to_string(), minhash_params).await?;
+                kind = Some(MINHASH_INDEX_KIND.to_string());
             }
             (IndexType::Vector, LANCE_VECTOR_INDEX) => {
                 // Vector index params.
@@ -247,9 +363,10 @@ impl DatasetIndexExt for Dataset {
         let new_idx = IndexMetadata {
             uuid: index_id,
             name: index_name,
-            fields: vec![field.id],
+            fields: field_ids,
             dataset_version: self.manifest.version,
             fragment_bitmap: Some(self.get_fragments().iter().map(|f| f.id() as u32).collect()),
+            kind,
         };
         let transaction = Transaction::new(
             self.manifest.version,
@@ -275,6 +392,38 @@ impl DatasetIndexExt for Dataset {
         Ok(())
     }
 
+    async fn drop_index(&mut self, name: &str) -> Result<()> {
+        let indices = self.load_indices_by_name(name).await?;
+        if indices.is_empty() {
+            return Err(Error::IndexNotFound {
+                identity: format!("name={}", name),
+                location: location!(),
+            });
+        }
+
+        let transaction = Transaction::new(
+            self.manifest.version,
+            Operation::CreateIndex {
+                new_indices: vec![],
+                removed_indices: indices,
+            },
+            None,
+        );
+        let new_manifest = commit_transaction(
+            self,
+            self.object_store(),
+            self.commit_handler.as_ref(),
+            &transaction,
+            &Default::default(),
+            &Default::default(),
+        )
+        .await?;
+
+        self.manifest = Arc::new(new_manifest);
+
+        Ok(())
+    }
+
     async fn load_indices(&self) -> Result<Arc<Vec<IndexMetadata>>> {
         let dataset_dir = self.base.to_string();
         if let Some(indices) = self
@@ -285,11 +434,22 @@ impl DatasetIndexExt for Dataset {
             return Ok(indices);
         }
 
-        let manifest_file = self.manifest_file(self.version().version).await?;
-        let loaded_indices: Arc<Vec<IndexMetadata>> =
-            read_manifest_indexes(&self.object_store, &manifest_file, &self.manifest)
-                .await?
-                .into();
+        // The index section is usually in the block of the manifest file read
+        // when the dataset was loaded
+        let cached = self
+            .manifest_tail
+            .as_ref()
+            .and_then(|tail| tail.read_indices(&self.manifest, None))
+            .transpose()?;
+        let loaded_indices: Arc<Vec<IndexMetadata>> = match cached {
+            Some(indices) => indices.into(),
+            None => {
+                let manifest_file = self.manifest_file(self.version().version).await?;
+                read_manifest_indexes(&self.object_store, &manifest_file, &self.manifest)
+                    .await?
+                    .into()
+            }
+        };
 
         self.session.index_cache.insert_metadata(
             &dataset_dir,
@@ -300,11 +460,41 @@ impl DatasetIndexExt for Dataset {
     }
 
     async fn load_scalar_index_for_column(&self, col: &str) -> Result<Option<IndexMetadata>> {
+        // Composite indices are referred to by their key, which lists their columns
+        if let Some(columns) = parse_composite_index_key(col) {
+            let fields = columns
+                .iter()
+                .filter_map(|column| self.schema().field(column))
+                .map(|field| field.id)
+                .collect::<Vec<_>>();
+            return Ok(self
+                .load_indices_on_fields(&fields)
+                .await?
+                .iter()
+                .filter(|idx| idx.kind.as_deref() == Some(COMPOSITE_INDEX_KIND))
+                .find(|idx| {
+                    idx.fields.len() == columns.len()
+                        && idx.fields.iter().zip(&columns).all(|(field, column)| {
+                            self.schema()
+                                .field_by_id(*field)
+                                .map_or(false, |field| field.name == *column)
+                        })
+                })
+                .cloned());
+        }
+        // The lowercase index of a column is referred to by its key
+        let (col, kind) = match parse_lowercase_index_key(col) {
+            Some(col) => (col, Some(LOWERCASE_INDEX_KIND)),
+            None => (col, None),
+        };
+        let Some(field) = self.schema().field(col) else {
+            return Ok(None);
+        };
         Ok(self
-            .load_indices()
+            .load_indices_on_fields(&[field.id])
             .await?
             .iter()
-            .filter(|idx| idx.fields.len() == 1)
+            .filter(|idx| idx.fields.len() == 1 && idx.kind.as_deref() == kind)
             .find(|idx| {
                 let field = self.schema().field_by_id(idx.fields[0]);
                 if let Some(field) = field {
@@ -345,6 +535,7 @@ impl DatasetIndexExt for Dataset {
                 fields: last_idx.fields.clone(),
                 dataset_version: self.manifest.version,
                 fragment_bitmap: Some(new_frag_ids),
+                kind: last_idx.kind.clone(),
             };
             removed_indices.extend(removed.iter().map(|&idx| idx.clone()));
             if deltas.len() > removed.len() {
@@ -455,8 +646,17 @@ pub trait DatasetIndexInternalExt: DatasetIndexExt {
     async fn open_scalar_index(&self, column: &str, uuid: &str) -> Result<Arc<dyn ScalarIndex>>;
     /// Opens the requested vector index
     async fn open_vector_index(&self, column: &str, uuid: &str) -> Result<Arc<dyn VectorIndex>>;
+    /// Opens the requested MinHash index
+    async fn open_minhash_index(&self, uuid: &str) -> Result<Arc<MinHashIndex>>;
     /// Loads information about all the available scalar indices on the dataset
     async fn scalar_index_info(&self) -> Result<ScalarIndexInfo>;
+    /// Loads information about the available scalar indices on any of the columns
+    async fn scalar_index_info_for_columns(&self, columns: &[String]) -> Result<ScalarIndexInfo>;
+    /// Loads the metadata of the indices on any of the fields
+    ///
+    /// Unless the metadata of all the indices is already cached, the metadata
+    /// of the other indices is not converted, and nothing is cached.
+    async fn load_indices_on_fields(&self, fields: &[i32]) -> Result<Vec<IndexMetadata>>;
 
     /// Return the fragments that are not covered by any of the deltas of the index.
     async fn unindexed_fragments(&self, idx_name: &str) -> Result<Vec<Fragment>>;
@@ -472,6 +672,18 @@ impl DatasetIndexInternalExt for Dataset {
         if let Some(index) = self.session.index_cache.get_vector(uuid) {
             return Ok(index.as_index());
         }
+        if let Some(index) = self.session.index_cache.get_minhash(uuid) {
+            return Ok(index.as_index());
+        }
+
+        // Indices with a kind are recognized from their metadata
+        let is_minhash = self.load_indices().await?.iter().any(|idx| {
+            idx.uuid.to_string() == uuid && idx.kind.as_deref() == Some(MINHASH_INDEX_KIND)
+        });
+        if is_minhash {
+            let index = self.open_minhash_index(uuid).await?;
+            return Ok(index.as_index());
+        }
 
         // Sometimes we want to open an index and we don't care if it is a scalar or vector index.
         // For example, we might want to get statistics for an index, regardless of type.
@@ -502,6 +714,16 @@ impl DatasetIndexInternalExt for Dataset {
         Ok(index)
     }
 
+    async fn open_minhash_index(&self, uuid: &str) -> Result<Arc<MinHashIndex>> {
+        if let Some(index) = self.session.index_cache.get_minhash(uuid) {
+            return Ok(index);
+        }
+
+        let index = crate::index::minhash::open_minhash_index(self, uuid).await?;
+        self.session.index_cache.insert_minhash(uuid, index.clone());
+        Ok(index)
+    }
+
     async fn open_vector_index(&self, column: &str, uuid: &str) -> Result<Arc<dyn VectorIndex>> {
         if let Some(index) = self.session.index_cache.get_vector(uuid) {
             log::debug!("Found vector index in cache uuid: {}", uuid);
@@ -564,19 +786,43 @@ impl DatasetIndexInternalExt for Dataset {
 
     async fn scalar_index_info(&self) -> Result<ScalarIndexInfo> {
         let indices = self.load_indices().await?;
-        let schema = self.schema();
-        let indexed_fields = indices
-        .iter()
-        .filter(|idx| idx.fields.len() == 1)
-        .map(|idx| {
-            let field = idx.fields[0];
-            let field = schema.field_by_id(field).ok_or_else(|| Error::Internal { message: format!("Index referenced a field with id {field} which did not exist in the schema"), location: location!() });
-            field.map(|field| (field.name.clone(), field.data_type()))
-        }).collect::<Result<Vec<_>>>()?;
-        let index_info_map = HashMap::from_iter(indexed_fields);
-        Ok(ScalarIndexInfo {
-            indexed_columns: index_info_map,
-        })
+        ScalarIndexInfo::from_indices(self.schema(), &indices)
+    }
+
+    async fn scalar_index_info_for_columns(&self, columns: &[String]) -> Result<ScalarIndexInfo> {
+        let fields = columns
+            .iter()
+            .filter_map(|column| self.schema().field(column))
+            .map(|field| field.id)
+            .collect::<Vec<_>>();
+        let indices = self.load_indices_on_fields(&fields).await?;
+        ScalarIndexInfo::from_indices(self.schema(), &indices)
+    }
+
+    async fn load_indices_on_fields(&self, fields: &[i32]) -> Result<Vec<IndexMetadata>> {
+        let on_fields =
+            |idx: &&IndexMetadata| idx.fields.iter().any(|field| fields.contains(field));
+        if let Some(indices) = self
+            .session
+            .index_cache
+            .get_metadata(&self.base.to_string(), self.version().version)
+        {
+            return Ok(indices.iter().filter(on_fields).cloned().collect());
+        }
+        if let Some(indices) = self
+            .manifest_tail
+            .as_ref()
+            .and_then(|tail| tail.read_indices(&self.manifest, Some(fields)))
+        {
+            return indices;
+        }
+        Ok(self
+            .load_indices()
+            .await?
+            .iter()
+            .filter(on_fields)
+            .cloned()
+            .collect())
     }
 
     async fn unindexed_fragments(&self, name: &str) -> Result<Vec<Fragment>> {
@@ -927,4 +1173,72 @@ mod tests {
         assert_eq!(stats["num_unindexed_fragments"], 0);
         assert_eq!(stats["num_indices"], 1);
     }
+
+    #[tokio::test]
+    async fn test_create_index_with_concurrent_writes() {
+        use crate::dataset::optimize::{compact_files, CompactionOptions};
+        use crate::dataset::WriteParams;
+        use crate::index::scalar::ScalarIndexParams;
+        use arrow_array::Int32Array;
+
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let batch = |range: std::ops::Range<i32>| {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from_iter_values(range))],
+            )
+            .unwrap();
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone())
+        };
+        let params = WriteParams {
+            max_rows_per_file: 100,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(batch(0..400), test_uri, Some(params.clone()))
+            .await
+            .unwrap();
+
+        // The index is built on version 1, while rows are appended and the
+        // data is compacted.
+        let mut building = dataset.clone();
+        dataset.append(batch(400..600), Some(params)).await.unwrap();
+        compact_files(&mut dataset, CompactionOptions::default(), None)
+            .await
+            .unwrap();
+        building
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                Some("i_idx".into()),
+                &ScalarIndexParams::default(),
+                true,
+            )
+            .await
+            .unwrap();
+
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        let indices = dataset.load_indices().await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].dataset_version, 1);
+        assert_eq!(
+            indices[0].fragment_bitmap.as_ref().unwrap(),
+            &RoaringBitmap::from_iter(0..4)
+        );
+        // The compacted fragments are not covered by the index.
+        let unindexed = dataset.unindexed_fragments("i_idx").await.unwrap();
+        assert_eq!(unindexed, dataset.manifest.fragments.as_ref().clone());
+
+        for (filter, expected) in [("i = 5", 1), ("i >= 300", 300)] {
+            let count = dataset
+                .scan()
+                .filter(filter)
+                .unwrap()
+                .count_rows()
+                .await
+                .unwrap();
+            assert_eq!(count, expected, "{}", filter);
+        }
+    }
 }
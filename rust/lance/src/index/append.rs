@@ -6,16 +6,17 @@ use std::sync::Arc;
 use lance_core::{Error, Result};
 use lance_index::optimize::OptimizeOptions;
 use lance_index::scalar::lance_format::LanceIndexStore;
+use lance_index::scalar::{composite::COMPOSITE_INDEX_KIND, LOWERCASE_INDEX_KIND};
 use lance_index::IndexType;
 use lance_table::format::Index as IndexMetadata;
 use roaring::RoaringBitmap;
 use snafu::{location, Location};
 use uuid::Uuid;
 
+use super::scalar::{scan_ordered_composite_keys, scan_ordered_values};
 use super::vector::ivf::optimize_vector_indices;
 use super::DatasetIndexInternalExt;
 use crate::dataset::index::LanceIndexStoreExt;
-use crate::dataset::scanner::ColumnOrdering;
 use crate::dataset::Dataset;
 
 /// Merge in-inflight unindexed data, with a specific number of previous indices
@@ -84,13 +85,50 @@ pub async fn merge_indices<'a>(
                 .open_scalar_index(&column.name, &old_indices[0].uuid.to_string())
                 .await?;
 
+            let new_data_stream = match old_indices[0].kind.as_deref() {
+                Some(COMPOSITE_INDEX_KIND) => {
+                    let columns = old_indices[0]
+                        .fields
+                        .iter()
+                        .map(|field| {
+                            dataset
+                                .schema()
+                                .field_by_id(*field)
+                                .map(|field| field.name.clone())
+                                .ok_or(Error::Index {
+                                    message: format!(
+                                        "Append index: column {} does not exist",
+                                        field
+                                    ),
+                                    location: location!(),
+                                })
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    scan_ordered_composite_keys(&dataset, &columns, Some(unindexed)).await?
+                }
+                kind => {
+                    let lowercased = kind == Some(LOWERCASE_INDEX_KIND);
+                    scan_ordered_values(&dataset, &column.name, Some(unindexed), lowercased).await?
+                }
+            };
+
+            let new_uuid = Uuid::new_v4();
+
+            let new_store = LanceIndexStore::from_dataset(&dataset, &new_uuid.to_string());
+
+            index.update(new_data_stream, &new_store).await?;
+
+            Ok((new_uuid, 1))
+        }
+        IndexType::MinHash => {
+            let index = dataset
+                .open_minhash_index(&old_indices[0].uuid.to_string())
+                .await?;
+
             let mut scanner = dataset.scan();
             scanner
                 .with_fragments(unindexed)
                 .with_row_id()
-                .order_by(Some(vec![ColumnOrdering::asc_nulls_first(
-                    column.name.clone(),
-                )]))?
                 .project(&[&column.name])?;
             let new_data_stream = scanner.try_into_stream().await?;
 
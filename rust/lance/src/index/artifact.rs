@@ -0,0 +1,474 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Export indices to standalone artifacts and import them into replicas
+//!
+//! An artifact is a directory with the files of every index with the given
+//! name, plus an `index.json` describing them. Vector and scalar indices
+//! refer to rows by their address, so they can only be imported into a
+//! dataset whose indexed fragments are identical to the ones of the source.
+//! This is checked with a checksum of each fragment, computed from its row
+//! count and the names and sizes of the data files of the indexed columns.
+//! Data files are named after random ids, so fragments only match when the
+//! replica was copied from the same files.
+
+use std::collections::BTreeMap;
+
+use futures::TryStreamExt;
+use lance_index::DatasetIndexExt;
+use lance_io::object_store::ObjectStore;
+use lance_table::format::{Fragment, Index as IndexMetadata};
+use object_store::path::Path;
+use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
+use snafu::{location, Location};
+use uuid::Uuid;
+
+use crate::dataset::download::copy_file;
+use crate::dataset::transaction::{Operation, Transaction};
+use crate::io::commit::commit_transaction;
+use crate::{Dataset, Error, Result};
+
+const ARTIFACT_FILE_NAME: &str = "index.json";
+
+/// Description of an exported index, stored as `index.json`.
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexArtifact {
+    name: String,
+    /// Full paths of the indexed columns.
+    columns: Vec<String>,
+    /// One entry per index with the name. Delta indices share a name.
+    segments: Vec<IndexSegment>,
+    /// Checksum of every covered fragment, as hex, by fragment id.
+    fragment_checksums: BTreeMap<u64, String>,
+    /// The kind of index, see [`IndexMetadata::kind`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexSegment {
+    fragment_ids: Vec<u32>,
+    /// Paths of the index files, relative to the directory of the segment.
+    files: Vec<String>,
+}
+
+/// FNV-1a, because the checksums are persisted and must not change across
+/// platforms or releases.
+struct Checksum(u64);
+
+impl Checksum {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+async fn fragment_checksum(
+    dataset: &Dataset,
+    fragment: &Fragment,
+    field_ids: &[i32],
+) -> Result<String> {
+    let mut checksum = Checksum::new();
+    checksum.update(&fragment.id.to_le_bytes());
+    checksum.update(&fragment.physical_rows.unwrap_or_default().to_le_bytes());
+    for file in &fragment.files {
+        if !file.fields.iter().any(|id| field_ids.contains(id)) {
+            continue;
+        }
+        let size = dataset
+            .object_store
+            .size(&dataset.data_dir().child(file.path.as_str()))
+            .await?;
+        checksum.update(file.path.as_bytes());
+        checksum.update(&size.to_le_bytes());
+    }
+    Ok(format!("{:016x}", checksum.0))
+}
+
+fn column_path(dataset: &Dataset, field_id: i32) -> Result<String> {
+    let ancestry = dataset
+        .schema()
+        .field_ancestry_by_id(field_id)
+        .ok_or_else(|| Error::Index {
+            message: format!("Indexed field {} does not exist", field_id),
+            location: location!(),
+        })?;
+    Ok(ancestry
+        .iter()
+        .map(|field| field.name.as_str())
+        .collect::<Vec<_>>()
+        .join("."))
+}
+
+async fn list_files(store: &ObjectStore, dir: &Path) -> Result<Vec<String>> {
+    let files = store
+        .read_dir_all(dir, None)
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    Ok(files
+        .into_iter()
+        .filter_map(|file| {
+            file.location
+                .prefix_match(dir)
+                .map(|parts| parts.map(|part| part.as_ref().to_string()))
+                .map(|parts| parts.collect::<Vec<_>>().join("/"))
+        })
+        .collect())
+}
+
+pub async fn export_index(dataset: &Dataset, name: &str, uri: &str) -> Result<()> {
+    let indices = dataset.load_indices_by_name(name).await?;
+    let Some(first) = indices.first() else {
+        return Err(Error::Index {
+            message: format!("Index with name {} does not exist", name),
+            location: location!(),
+        });
+    };
+    let field_ids = first.fields.clone();
+    let columns = field_ids
+        .iter()
+        .map(|id| column_path(dataset, *id))
+        .collect::<Result<Vec<_>>>()?;
+
+    let (target_store, target_base) = ObjectStore::from_uri(uri).await?;
+    if target_store
+        .exists(&target_base.child(ARTIFACT_FILE_NAME))
+        .await?
+    {
+        return Err(Error::invalid_input(
+            format!("An index artifact already exists at {}", uri),
+            location!(),
+        ));
+    }
+
+    let mut segments = Vec::with_capacity(indices.len());
+    let mut covered = RoaringBitmap::new();
+    for (i, index) in indices.iter().enumerate() {
+        let fragment_bitmap = index.fragment_bitmap.as_ref().ok_or_else(|| Error::Index {
+            message: format!(
+                "Index {} does not record the fragments it covers, so it can't be exported",
+                index.uuid
+            ),
+            location: location!(),
+        })?;
+        covered |= fragment_bitmap;
+        let index_dir = dataset.indices_dir().child(index.uuid.to_string());
+        let files = list_files(&dataset.object_store, &index_dir).await?;
+        for file in &files {
+            copy_file(
+                &dataset.object_store,
+                &index_dir.child(file.as_str()),
+                &target_store,
+                &target_base.child(i.to_string()).child(file.as_str()),
+            )
+            .await?;
+        }
+        segments.push(IndexSegment {
+            fragment_ids: fragment_bitmap.iter().collect(),
+            files,
+        });
+    }
+
+    // Fragments that were deleted since the index was built are not covered.
+    let mut fragment_checksums = BTreeMap::new();
+    for fragment in dataset.manifest.fragments.iter() {
+        if covered.contains(fragment.id as u32) {
+            fragment_checksums.insert(
+                fragment.id,
+                fragment_checksum(dataset, fragment, &field_ids).await?,
+            );
+        }
+    }
+
+    let artifact = IndexArtifact {
+        name: name.to_string(),
+        columns,
+        segments,
+        fragment_checksums,
+        kind: first.kind.clone(),
+    };
+    target_store
+        .put(
+            &target_base.child(ARTIFACT_FILE_NAME),
+            &serde_json::to_vec_pretty(&artifact)?,
+        )
+        .await
+}
+
+pub async fn import_index(dataset: &mut Dataset, uri: &str) -> Result<()> {
+    let (source_store, source_base) = ObjectStore::from_uri(uri).await?;
+    let data = source_store
+        .inner
+        .get(&source_base.child(ARTIFACT_FILE_NAME))
+        .await?
+        .bytes()
+        .await?;
+    let artifact: IndexArtifact = serde_json::from_slice(&data)?;
+
+    if dataset
+        .load_indices()
+        .await?
+        .iter()
+        .any(|index| index.name == artifact.name)
+    {
+        return Err(Error::Index {
+            message: format!("Index name '{}' already exists", artifact.name),
+            location: location!(),
+        });
+    }
+    let field_ids = artifact
+        .columns
+        .iter()
+        .map(|column| dataset.schema().field_id(column))
+        .collect::<Result<Vec<_>>>()?;
+
+    for (fragment_id, expected) in &artifact.fragment_checksums {
+        let fragment = dataset
+            .manifest
+            .fragments
+            .iter()
+            .find(|f| f.id == *fragment_id)
+            .ok_or_else(|| {
+                Error::invalid_input(
+                    format!(
+                        "Index {} covers fragment {} which does not exist in this dataset",
+                        artifact.name, fragment_id
+                    ),
+                    location!(),
+                )
+            })?;
+        let actual = fragment_checksum(dataset, fragment, &field_ids).await?;
+        if &actual != expected {
+            return Err(Error::invalid_input(
+                format!(
+                    "Fragment {} does not match the data index {} was built on",
+                    fragment_id, artifact.name
+                ),
+                location!(),
+            ));
+        }
+    }
+
+    let mut new_indices = Vec::with_capacity(artifact.segments.len());
+    for (i, segment) in artifact.segments.iter().enumerate() {
+        let uuid = Uuid::new_v4();
+        let index_dir = dataset.indices_dir().child(uuid.to_string());
+        for file in &segment.files {
+            copy_file(
+                &source_store,
+                &source_base.child(i.to_string()).child(file.as_str()),
+                &dataset.object_store,
+                &index_dir.child(file.as_str()),
+            )
+            .await?;
+        }
+        // Fragments deleted before the export are left out of the bitmap.
+        let fragment_bitmap = segment
+            .fragment_ids
+            .iter()
+            .copied()
+            .filter(|id| artifact.fragment_checksums.contains_key(&(*id as u64)))
+            .collect::<RoaringBitmap>();
+        new_indices.push(IndexMetadata {
+            uuid,
+            name: artifact.name.clone(),
+            fields: field_ids.clone(),
+            dataset_version: dataset.manifest.version,
+            fragment_bitmap: Some(fragment_bitmap),
+            kind: artifact.kind.clone(),
+        });
+    }
+
+    let transaction = Transaction::new(
+        dataset.manifest.version,
+        Operation::CreateIndex {
+            new_indices,
+            removed_indices: vec![],
+        },
+        None,
+    );
+    let new_manifest = commit_transaction(
+        dataset,
+        dataset.object_store(),
+        dataset.commit_handler.as_ref(),
+        &transaction,
+        &Default::default(),
+        &Default::default(),
+    )
+    .await?;
+    dataset.manifest = std::sync::Arc::new(new_manifest);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow_array::{
+        FixedSizeListArray, Float32Array, Int32Array, RecordBatch, RecordBatchIterator,
+    };
+    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use lance_arrow::FixedSizeListArrayExt;
+    use lance_index::IndexType;
+    use lance_linalg::distance::MetricType;
+    use tempfile::tempdir;
+
+    use crate::dataset::WriteParams;
+    use crate::index::scalar::ScalarIndexParams;
+    use crate::index::vector::VectorIndexParams;
+
+    async fn create_dataset(uri: &str) -> Dataset {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new(
+                "vec",
+                DataType::FixedSizeList(
+                    Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                    4,
+                ),
+                false,
+            ),
+        ]));
+        let values = Float32Array::from_iter_values((0..1024).map(|v| v as f32));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..256)),
+                Arc::new(FixedSizeListArray::try_new_from_values(values, 4).unwrap()),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let params = WriteParams {
+            max_rows_per_file: 128,
+            ..Default::default()
+        };
+        Dataset::write(reader, uri, Some(params)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_export_import_index() {
+        let dir = tempdir().unwrap();
+        let source_uri = dir.path().join("source").to_str().unwrap().to_string();
+        let mut dataset = create_dataset(&source_uri).await;
+        dataset
+            .create_index(
+                &["vec"],
+                IndexType::Vector,
+                Some("vec_idx".into()),
+                &VectorIndexParams::ivf_pq(2, 8, 2, MetricType::L2, 2),
+                false,
+            )
+            .await
+            .unwrap();
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                Some("i_idx".into()),
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+
+        // A replica copied from the source, before the indices were built.
+        let replica_uri = dir.path().join("replica").to_str().unwrap().to_string();
+        let mut replica = dataset
+            .download(1, &replica_uri, &Default::default())
+            .await
+            .unwrap();
+        assert!(replica.load_indices().await.unwrap().is_empty());
+
+        for name in ["vec_idx", "i_idx"] {
+            let artifact_uri = dir.path().join(name).to_str().unwrap().to_string();
+            dataset.export_index(name, &artifact_uri).await.unwrap();
+            replica.import_index(&artifact_uri).await.unwrap();
+
+            let err = replica.import_index(&artifact_uri).await.unwrap_err();
+            assert!(matches!(err, Error::Index { .. }));
+        }
+
+        let indices = replica.load_indices().await.unwrap();
+        assert_eq!(indices.len(), 2);
+        let source_indices = dataset.load_indices().await.unwrap();
+        for index in indices.iter() {
+            let source = source_indices
+                .iter()
+                .find(|i| i.name == index.name)
+                .unwrap();
+            assert_ne!(index.uuid, source.uuid);
+            assert_eq!(index.fields, source.fields);
+            assert_eq!(index.fragment_bitmap, source.fragment_bitmap);
+        }
+
+        assert_eq!(
+            replica
+                .count_rows(Some("i = 42".to_string()))
+                .await
+                .unwrap(),
+            1
+        );
+        let query = Float32Array::from_iter_values([0.0, 1.0, 2.0, 3.0]);
+        let results = replica
+            .scan()
+            .nearest("vec", &query, 1)
+            .unwrap()
+            .try_into_batch()
+            .await
+            .unwrap();
+        assert_eq!(
+            results["i"]
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap()
+                .value(0),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_index_checks_fragments() {
+        let dir = tempdir().unwrap();
+        let mut dataset = create_dataset(dir.path().join("source").to_str().unwrap()).await;
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::Scalar,
+                Some("i_idx".into()),
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        let artifact_uri = dir.path().join("artifact").to_str().unwrap().to_string();
+        dataset.export_index("i_idx", &artifact_uri).await.unwrap();
+
+        let err = dataset
+            .export_index("i_idx", &artifact_uri)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+        let err = dataset
+            .export_index("missing", dir.path().join("missing").to_str().unwrap())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Index { .. }));
+
+        // Same rows, written again, so the data files differ.
+        let mut other = create_dataset(dir.path().join("other").to_str().unwrap()).await;
+        let err = other.import_index(&artifact_uri).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+        assert!(other.load_indices().await.unwrap().is_empty());
+    }
+}
@@ -4,6 +4,7 @@
 use std::sync::Arc;
 
 use deepsize::DeepSizeOf;
+use lance_index::minhash::MinHashIndex;
 use lance_index::scalar::ScalarIndex;
 use lance_table::format::Index;
 use moka::sync::{Cache, ConcurrentCacheExt};
@@ -21,10 +22,12 @@ struct CacheStats {
 impl CacheStats {
     fn record_hit(&self) {
         self.hits.fetch_add(1, Ordering::Relaxed);
+        crate::metrics::record_index_cache_lookup(true);
     }
 
     fn record_miss(&self) {
         self.misses.fetch_add(1, Ordering::Relaxed);
+        crate::metrics::record_index_cache_lookup(false);
     }
 }
 
@@ -33,6 +36,7 @@ pub struct IndexCache {
     // TODO: Can we merge these two caches into one for uniform memory management?
     scalar_cache: Arc<Cache<String, Arc<dyn ScalarIndex>>>,
     vector_cache: Arc<Cache<String, Arc<dyn VectorIndex>>>,
+    minhash_cache: Arc<Cache<String, Arc<MinHashIndex>>>,
 
     /// Index metadata cache.
     ///
@@ -54,6 +58,11 @@ impl DeepSizeOf for IndexCache {
                 .iter()
                 .map(|(_, v)| v.deep_size_of_children(context))
                 .sum::<usize>()
+            + self
+                .minhash_cache
+                .iter()
+                .map(|(_, v)| v.deep_size_of_children(context))
+                .sum::<usize>()
             + self
                 .metadata_cache
                 .iter()
@@ -68,6 +77,7 @@ impl IndexCache {
         Self {
             scalar_cache: Arc::new(Cache::new(capacity as u64)),
             vector_cache: Arc::new(Cache::new(capacity as u64)),
+            minhash_cache: Arc::new(Cache::new(capacity as u64)),
             metadata_cache: Arc::new(Cache::new(capacity as u64)),
             cache_stats: Arc::new(CacheStats::default()),
         }
@@ -82,9 +92,11 @@ impl IndexCache {
     pub(crate) fn get_size(&self) -> usize {
         self.scalar_cache.sync();
         self.vector_cache.sync();
+        self.minhash_cache.sync();
         self.metadata_cache.sync();
         (self.scalar_cache.entry_count()
             + self.vector_cache.entry_count()
+            + self.minhash_cache.entry_count()
             + self.metadata_cache.entry_count()) as usize
     }
 
@@ -109,6 +121,21 @@ impl IndexCache {
         }
     }
 
+    pub(crate) fn get_minhash(&self, key: &str) -> Option<Arc<MinHashIndex>> {
+        if let Some(index) = self.minhash_cache.get(key) {
+            self.cache_stats.record_hit();
+            Some(index)
+        } else {
+            self.cache_stats.record_miss();
+            None
+        }
+    }
+
+    /// Whether a vector index is cached, without counting a hit or a miss.
+    pub(crate) fn contains_vector(&self, key: &str) -> bool {
+        self.vector_cache.contains_key(key)
+    }
+
     /// Insert a new entry into the cache.
     pub(crate) fn insert_scalar(&self, key: &str, index: Arc<dyn ScalarIndex>) {
         self.scalar_cache.insert(key.to_string(), index);
@@ -118,6 +145,10 @@ impl IndexCache {
         self.vector_cache.insert(key.to_string(), index);
     }
 
+    pub(crate) fn insert_minhash(&self, key: &str, index: Arc<MinHashIndex>) {
+        self.minhash_cache.insert(key.to_string(), index);
+    }
+
     /// Construct a key for index metadata arrays.
     fn metadata_key(dataset_uuid: &str, version: u64) -> String {
         format!("{}:{}", dataset_uuid, version)
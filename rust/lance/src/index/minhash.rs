@@ -0,0 +1,358 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! MinHash LSH indices over text and token-set columns
+//!
+//! A MinHash index estimates the Jaccard similarity between rows, to find
+//! near duplicates that neither exact matching nor dense vectors catch.
+
+use std::sync::Arc;
+
+use arrow_schema::DataType;
+use lance_core::{Error, Result};
+use lance_index::minhash::{estimate_jaccard, MinHashIndex};
+use lance_index::scalar::lance_format::LanceIndexStore;
+use lance_index::DatasetIndexExt;
+use lance_table::format::Index as IndexMetadata;
+use roaring::RoaringBitmap;
+use snafu::{location, Location};
+use tracing::instrument;
+
+pub use lance_index::minhash::{MinHashParams, LANCE_MINHASH_INDEX};
+
+use super::prefilter::PreFilter;
+use super::DatasetIndexInternalExt;
+use crate::dataset::index::LanceIndexStoreExt;
+use crate::Dataset;
+
+/// The [`IndexMetadata::kind`] of MinHash indices
+pub const MINHASH_INDEX_KIND: &str = "minhash";
+
+/// A set of tokens to compare with the rows of a MinHash index
+#[derive(Debug, Clone, Copy)]
+pub enum MinHashQuery<'a> {
+    /// Text, split into shingles like the values of a text column
+    Text(&'a str),
+    /// Tokens, like the values of a list column
+    Tokens(&'a [&'a str]),
+}
+
+/// Build a MinHash index
+#[instrument(level = "debug", skip(dataset))]
+pub async fn build_minhash_index(
+    dataset: &Dataset,
+    column: &str,
+    uuid: &str,
+    params: &MinHashParams,
+) -> Result<()> {
+    params.validate()?;
+    let field = dataset.schema().field(column).ok_or_else(|| {
+        Error::invalid_input(format!("No column with name {}", column), location!())
+    })?;
+    if !MinHashParams::supports(&field.data_type()) {
+        return Err(Error::invalid_input(
+            format!(
+                "A MinHash index can only be created on a string or a list of strings column, \
+                but {} is {}",
+                column,
+                field.data_type()
+            ),
+            location!(),
+        ));
+    }
+
+    let mut scanner = dataset.scan();
+    scanner.with_row_id().project(&[column])?;
+    let data = scanner.try_into_stream().await?;
+    let index = MinHashIndex::try_from_stream(params.clone(), data.into()).await?;
+    index
+        .write(&LanceIndexStore::from_dataset(dataset, uuid))
+        .await
+}
+
+pub async fn open_minhash_index(dataset: &Dataset, uuid: &str) -> Result<Arc<MinHashIndex>> {
+    let index_store = Arc::new(LanceIndexStore::from_dataset(dataset, uuid));
+    MinHashIndex::load(index_store).await
+}
+
+/// The deltas of the MinHash index on `column`
+async fn minhash_indices(dataset: &Dataset, column: &str) -> Result<Vec<IndexMetadata>> {
+    let field = dataset.schema().field(column).ok_or_else(|| {
+        Error::invalid_input(format!("No column with name {}", column), location!())
+    })?;
+    let indices = dataset.load_indices().await?;
+    let Some(name) = indices
+        .iter()
+        .find(|idx| idx.fields == [field.id] && idx.kind.as_deref() == Some(MINHASH_INDEX_KIND))
+        .map(|idx| idx.name.clone())
+    else {
+        return Err(Error::IndexNotFound {
+            identity: format!("minhash index on column {}", column),
+            location: location!(),
+        });
+    };
+    dataset.load_indices_by_name(&name).await
+}
+
+/// The rows of `column` not covered by the deltas, with their signatures
+async fn unindexed_signatures(
+    dataset: &Dataset,
+    column: &str,
+    deltas: &[IndexMetadata],
+    params: &MinHashParams,
+) -> Result<Option<MinHashIndex>> {
+    let unindexed = dataset.unindexed_fragments(&deltas[0].name).await?;
+    if unindexed.is_empty() {
+        return Ok(None);
+    }
+    let mut scanner = dataset.scan();
+    scanner
+        .with_fragments(unindexed)
+        .with_row_id()
+        .project(&[column])?;
+    let data = scanner.try_into_stream().await?;
+    Ok(Some(
+        MinHashIndex::try_from_stream(params.clone(), data.into()).await?,
+    ))
+}
+
+/// Whether the indexed rows with the given ids were deleted
+async fn deleted_rows(dataset: &Dataset, deltas: &[IndexMetadata]) -> Result<impl Fn(u64) -> bool> {
+    let mut fragments = RoaringBitmap::new();
+    for delta in deltas {
+        fragments |= delta.fragment_bitmap.as_ref().ok_or_else(|| Error::Index {
+            message: "Please upgrade lance to 0.8+ to use this function".to_string(),
+            location: location!(),
+        })?;
+    }
+    let deleted = match PreFilter::create_deletion_mask(Arc::new(dataset.clone()), fragments) {
+        Some(deletion_mask) => Some(deletion_mask.await?),
+        None => None,
+    };
+    Ok(move |row_id| {
+        deleted
+            .as_ref()
+            .map(|deleted| deleted.contains(row_id))
+            .unwrap_or(false)
+    })
+}
+
+pub(crate) async fn jaccard_search(
+    dataset: &Dataset,
+    column: &str,
+    query: MinHashQuery<'_>,
+    threshold: f32,
+) -> Result<Vec<(u64, f32)>> {
+    let deltas = minhash_indices(dataset, column).await?;
+    let mut indices = Vec::with_capacity(deltas.len());
+    for delta in &deltas {
+        indices.push(dataset.open_minhash_index(&delta.uuid.to_string()).await?);
+    }
+    let params = indices[0].params().clone();
+
+    let is_text = dataset
+        .schema()
+        .field(column)
+        .map(|field| matches!(field.data_type(), DataType::Utf8 | DataType::LargeUtf8))
+        .unwrap_or_default();
+    let hashes = match query {
+        MinHashQuery::Text(text) if is_text => params.hash_text(text),
+        MinHashQuery::Tokens(tokens) if !is_text => params.hash_tokens(tokens.iter().copied()),
+        _ => {
+            return Err(Error::invalid_input(
+                format!(
+                    "Text queries only apply to string columns and token queries to list \
+                    columns, but {} is a {} column",
+                    column,
+                    if is_text { "string" } else { "list" }
+                ),
+                location!(),
+            ))
+        }
+    };
+    let Some(signature) = params.signature(&hashes) else {
+        return Ok(Vec::new());
+    };
+
+    let is_deleted = deleted_rows(dataset, &deltas).await?;
+    let mut results = indices
+        .iter()
+        .flat_map(|index| index.search(&signature, threshold))
+        .filter(|(row_id, _)| !is_deleted(*row_id))
+        .collect::<Vec<_>>();
+    // Unindexed rows are compared one by one
+    if let Some(unindexed) = unindexed_signatures(dataset, column, &deltas, &params).await? {
+        results.extend(
+            unindexed
+                .row_ids()
+                .iter()
+                .enumerate()
+                .map(|(position, row_id)| {
+                    let similarity = estimate_jaccard(&signature, unindexed.signature(position));
+                    (*row_id, similarity)
+                })
+                .filter(|(_, similarity)| *similarity >= threshold),
+        );
+    }
+    results.sort_by(|(left_id, left), (right_id, right)| {
+        right.total_cmp(left).then(left_id.cmp(right_id))
+    });
+    Ok(results)
+}
+
+pub(crate) async fn near_duplicates(
+    dataset: &Dataset,
+    column: &str,
+    threshold: f32,
+) -> Result<Vec<(u64, u64, f32)>> {
+    let deltas = minhash_indices(dataset, column).await?;
+    let is_deleted = deleted_rows(dataset, &deltas).await?;
+
+    // The deltas and the unindexed rows are merged into one index, so that
+    // duplicates across them are found too.
+    let mut parts = Vec::with_capacity(deltas.len() + 1);
+    for delta in &deltas {
+        parts.push(dataset.open_minhash_index(&delta.uuid.to_string()).await?);
+    }
+    let params = parts[0].params().clone();
+    if let Some(unindexed) = unindexed_signatures(dataset, column, &deltas, &params).await? {
+        parts.push(Arc::new(unindexed));
+    }
+    let mut row_ids = Vec::new();
+    let mut signatures = Vec::new();
+    for part in &parts {
+        for (position, row_id) in part.row_ids().iter().enumerate() {
+            if !is_deleted(*row_id) {
+                row_ids.push(*row_id);
+                signatures.extend_from_slice(part.signature(position));
+            }
+        }
+    }
+    Ok(MinHashIndex::new(params, row_ids, signatures).similar_pairs(threshold))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{RecordBatch, RecordBatchIterator, StringArray};
+    use arrow_schema::{Field, Schema};
+    use lance_index::IndexType;
+    use lance_table::feature_flags::FLAG_INDEX_KINDS;
+    use tempfile::tempdir;
+
+    use crate::dataset::WriteParams;
+
+    fn batch(texts: Vec<&str>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new("text", DataType::Utf8, true)]));
+        RecordBatch::try_new(schema, vec![Arc::new(StringArray::from(texts))]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_minhash_index() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let data = batch(vec![
+            "the quick brown fox jumps over the lazy dog near the river bank",
+            "lorem ipsum dolor sit amet consectetur adipiscing elit sed do",
+            "the quick brown fox jumps over the lazy dog near the river bank",
+            "pack my box with five dozen liquor jugs said the sphinx",
+        ]);
+        let schema = data.schema();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(data)], schema.clone()),
+            test_uri,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(dataset
+            .jaccard_search("text", MinHashQuery::Text("lorem ipsum"), 0.5)
+            .await
+            .is_err());
+        dataset
+            .create_index(
+                &["text"],
+                IndexType::MinHash,
+                None,
+                &MinHashParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        let indices = dataset.load_indices().await.unwrap();
+        assert_eq!(indices[0].kind.as_deref(), Some(MINHASH_INDEX_KIND));
+        // Older readers would mistake the index for a btree index
+        assert_ne!(dataset.manifest.reader_feature_flags & FLAG_INDEX_KINDS, 0);
+        // MinHash indices don't answer filters
+        assert!(dataset
+            .load_scalar_index_for_column("text")
+            .await
+            .unwrap()
+            .is_none());
+
+        let results = dataset
+            .jaccard_search(
+                "text",
+                MinHashQuery::Text(
+                    "Lorem ipsum dolor sit amet, consectetur adipiscing elit sed do",
+                ),
+                0.8,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results, vec![(1, 1.0)]);
+        assert!(dataset
+            .jaccard_search("text", MinHashQuery::Tokens(&["lorem"]), 0.8)
+            .await
+            .is_err());
+        assert_eq!(
+            dataset.near_duplicates("text", 0.99).await.unwrap(),
+            vec![(0, 2, 1.0)]
+        );
+
+        // Appended rows are found before the index is optimized
+        let appended = batch(vec![
+            "Pack my box with five dozen liquor jugs, said the sphinx.",
+        ]);
+        dataset
+            .append(
+                RecordBatchIterator::new(vec![Ok(appended)], schema.clone()),
+                Some(WriteParams::default()),
+            )
+            .await
+            .unwrap();
+        let appended_id = 1 << 32;
+        assert_eq!(
+            dataset.near_duplicates("text", 0.99).await.unwrap(),
+            vec![(0, 2, 1.0), (3, appended_id, 1.0)]
+        );
+
+        // Deleted rows are not
+        dataset.delete_rows([0, 2]).await.unwrap();
+        assert_eq!(
+            dataset.near_duplicates("text", 0.99).await.unwrap(),
+            vec![(3, appended_id, 1.0)]
+        );
+
+        dataset.optimize_indices(&Default::default()).await.unwrap();
+        let indices = dataset.load_indices().await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].kind.as_deref(), Some(MINHASH_INDEX_KIND));
+        assert!(dataset
+            .unindexed_fragments(&indices[0].name)
+            .await
+            .unwrap()
+            .is_empty());
+        let results = dataset
+            .jaccard_search(
+                "text",
+                MinHashQuery::Text("pack my box with five dozen liquor jugs said the sphinx"),
+                0.8,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results, vec![(3, 1.0), (appended_id, 1.0)]);
+    }
+}
@@ -6,12 +6,21 @@
 
 use std::sync::Arc;
 
+use arrow_array::RecordBatch;
+use arrow_schema::{DataType, Field, Schema, SortOptions};
 use async_trait::async_trait;
-use datafusion::physical_plan::SendableRecordBatchStream;
-use lance_datafusion::{chunker::chunk_concat_stream, exec::LanceExecutionOptions};
+use datafusion::physical_plan::{
+    expressions, sorts::sort::SortExec, stream::RecordBatchStreamAdapter, SendableRecordBatchStream,
+};
+use datafusion_physical_expr::PhysicalSortExpr;
+use futures::StreamExt;
+use lance_arrow::string::lowercase;
+use lance_datafusion::chunker::chunk_concat_stream;
+use lance_datafusion::exec::{execute_plan, LanceExecutionOptions, OneShotExec};
 use lance_index::{
     scalar::{
         btree::{train_btree_index, BTreeIndex, BtreeTrainingSource},
+        composite::{self, encode_composite_keys},
         flat::FlatIndexMetadata,
         lance_format::LanceIndexStore,
         ScalarIndex,
@@ -21,7 +30,8 @@ use lance_index::{
 use snafu::{location, Location};
 use tracing::instrument;
 
-use lance_core::{Error, Result};
+use lance_core::{Error, Result, ROW_ID};
+use lance_table::format::Fragment;
 
 use crate::{
     dataset::{index::LanceIndexStoreExt, scanner::ColumnOrdering},
@@ -33,7 +43,21 @@ use super::IndexParams;
 pub const LANCE_SCALAR_INDEX: &str = "__lance_scalar_index";
 
 #[derive(Default)]
-pub struct ScalarIndexParams {}
+pub struct ScalarIndexParams {
+    /// Index the lowercase of the values of the column, to answer
+    /// case-insensitive queries, such as `name ILIKE 'bob%'`, instead of
+    /// queries of the values themselves.  Queries answered by the index
+    /// compare values lowercased, as by SQL `lower`, which differs from the
+    /// case folding of `ILIKE` for a few characters, such as 'ς', 'ſ' or 'K'
+    /// (Kelvin sign).  Unicode normalization is not supported: an 'é' written
+    /// as one character never matches one written as 'e' and an accent.
+    ///
+    /// Only string columns can have such an index.  It has the kind
+    /// [`LOWERCASE_INDEX_KIND`](lance_index::scalar::LOWERCASE_INDEX_KIND),
+    /// and can be created, under another name, next to an index of the values
+    /// themselves.
+    pub lowercase: bool,
+}
 
 impl IndexParams for ScalarIndexParams {
     fn as_any(&self) -> &dyn std::any::Any {
@@ -49,9 +73,122 @@ impl IndexParams for ScalarIndexParams {
     }
 }
 
+/// Scan the values of `column` in `fragments`, or in the whole dataset, with
+/// their row ids, ordered by value
+///
+/// The values of a lowercase index are lowercased before they are ordered.
+pub(crate) async fn scan_ordered_values(
+    dataset: &Dataset,
+    column: &str,
+    fragments: Option<Vec<Fragment>>,
+    lowercased: bool,
+) -> Result<SendableRecordBatchStream> {
+    let mut scan = dataset.scan();
+    if let Some(fragments) = fragments {
+        scan.with_fragments(fragments);
+    }
+    scan.with_row_id().project(&[column])?;
+    if !lowercased {
+        scan.order_by(Some(vec![ColumnOrdering::asc_nulls_first(
+            column.to_string(),
+        )]))?;
+        return scan
+            .try_into_dfstream(dataset.spilling_execution_options()?)
+            .await;
+    }
+
+    let values = scan
+        .try_into_dfstream(LanceExecutionOptions::default())
+        .await?;
+    let schema = values.schema();
+    let lowercased_values = values.map(|batch| -> datafusion::error::Result<RecordBatch> {
+        let batch = batch?;
+        let mut columns = batch.columns().to_vec();
+        columns[0] = lowercase(columns[0].as_ref())?;
+        Ok(RecordBatch::try_new(batch.schema(), columns)?)
+    });
+    sort_by_values(
+        dataset,
+        Box::pin(RecordBatchStreamAdapter::new(schema, lowercased_values)),
+    )
+}
+
+/// Scan the composite keys of `columns` in `fragments`, or in the whole
+/// dataset, with their row ids, ordered by key
+///
+/// See [`encode_composite_keys`] for the keys.
+pub(crate) async fn scan_ordered_composite_keys(
+    dataset: &Dataset,
+    columns: &[String],
+    fragments: Option<Vec<Fragment>>,
+) -> Result<SendableRecordBatchStream> {
+    let mut scan = dataset.scan();
+    if let Some(fragments) = fragments {
+        scan.with_fragments(fragments);
+    }
+    scan.with_row_id().project(columns)?;
+    let rows = scan
+        .try_into_dfstream(LanceExecutionOptions::default())
+        .await?;
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("values", DataType::Binary, false),
+        Field::new(ROW_ID, DataType::UInt64, false),
+    ]));
+    let num_columns = columns.len();
+    let keys_schema = schema.clone();
+    let keys = rows.map(move |batch| -> datafusion::error::Result<RecordBatch> {
+        let batch = batch?;
+        let keys = encode_composite_keys(&batch.columns()[..num_columns])?;
+        Ok(RecordBatch::try_new(
+            keys_schema.clone(),
+            vec![Arc::new(keys), batch.column(num_columns).clone()],
+        )?)
+    });
+    sort_by_values(
+        dataset,
+        Box::pin(RecordBatchStreamAdapter::new(schema, keys)),
+    )
+}
+
+/// Sort the batches of `input`, whose first column holds the values, by value
+fn sort_by_values(
+    dataset: &Dataset,
+    input: SendableRecordBatchStream,
+) -> Result<SendableRecordBatchStream> {
+    let schema = input.schema();
+    let sort_expr = PhysicalSortExpr {
+        expr: expressions::col(schema.field(0).name(), &schema)?,
+        options: SortOptions {
+            descending: false,
+            nulls_first: true,
+        },
+    };
+    let input = Arc::new(OneShotExec::new(input));
+    let sorted = Arc::new(SortExec::new(vec![sort_expr], input));
+    execute_plan(sorted, dataset.spilling_execution_options()?)
+}
+
 struct TrainingRequest {
     dataset: Arc<Dataset>,
     column: String,
+    lowercase: bool,
+}
+
+struct CompositeTrainingRequest {
+    dataset: Arc<Dataset>,
+    columns: Vec<String>,
+}
+
+#[async_trait]
+impl BtreeTrainingSource for CompositeTrainingRequest {
+    async fn scan_ordered_chunks(
+        self: Box<Self>,
+        chunk_size: u32,
+    ) -> Result<SendableRecordBatchStream> {
+        let ordered_batches =
+            scan_ordered_composite_keys(&self.dataset, &self.columns, None).await?;
+        Ok(chunk_concat_stream(ordered_batches, chunk_size as usize))
+    }
 }
 
 #[async_trait]
@@ -60,30 +197,24 @@ impl BtreeTrainingSource for TrainingRequest {
         self: Box<Self>,
         chunk_size: u32,
     ) -> Result<SendableRecordBatchStream> {
-        let mut scan = self.dataset.scan();
-        let scan = scan
-            .with_row_id()
-            .order_by(Some(vec![ColumnOrdering::asc_nulls_first(
-                self.column.clone(),
-            )]))?
-            .project(&[&self.column])?;
-
-        let ordered_batches = scan
-            .try_into_dfstream(LanceExecutionOptions {
-                use_spilling: true,
-                ..Default::default()
-            })
-            .await?;
+        let ordered_batches =
+            scan_ordered_values(&self.dataset, &self.column, None, self.lowercase).await?;
         Ok(chunk_concat_stream(ordered_batches, chunk_size as usize))
     }
 }
 
 /// Build a Vector Index
-#[instrument(level = "debug", skip(dataset))]
-pub async fn build_scalar_index(dataset: &Dataset, column: &str, uuid: &str) -> Result<()> {
+#[instrument(level = "debug", skip(dataset, params))]
+pub async fn build_scalar_index(
+    dataset: &Dataset,
+    column: &str,
+    uuid: &str,
+    params: &ScalarIndexParams,
+) -> Result<()> {
     let training_request = Box::new(TrainingRequest {
         dataset: Arc::new(dataset.clone()),
         column: column.to_string(),
+        lowercase: params.lowercase,
     });
     let field = dataset.schema().field(column).ok_or(Error::InvalidInput {
         source: format!("No column with name {}", column).into(),
@@ -97,11 +228,62 @@ pub async fn build_scalar_index(dataset: &Dataset, column: &str, uuid: &str) ->
             location: location!(),
         });
     }
+    if params.lowercase && !matches!(field.data_type(), DataType::Utf8 | DataType::LargeUtf8) {
+        return Err(Error::invalid_input(
+            format!(
+                "A lowercase index can only be created on a string column, not {}",
+                field.data_type()
+            ),
+            location!(),
+        ));
+    }
     let flat_index_trainer = FlatIndexMetadata::new(field.data_type());
     let index_store = LanceIndexStore::from_dataset(dataset, uuid);
     train_btree_index(training_request, &flat_index_trainer, &index_store).await
 }
 
+/// Build a composite index, over several columns
+///
+/// The index is a btree index of the keys of [`encode_composite_keys`], so
+/// it answers filters fixing the leading columns, and bounding the next one.
+#[instrument(level = "debug", skip(dataset, params))]
+pub async fn build_composite_index(
+    dataset: &Dataset,
+    columns: &[&str],
+    uuid: &str,
+    params: &ScalarIndexParams,
+) -> Result<()> {
+    if params.lowercase {
+        return Err(Error::invalid_input(
+            "A lowercase index can only be created on a single column",
+            location!(),
+        ));
+    }
+    for column in columns {
+        let field = dataset.schema().field(column).ok_or(Error::InvalidInput {
+            source: format!("No column with name {}", column).into(),
+            location: location!(),
+        })?;
+        if !composite::supports(&field.data_type()) {
+            return Err(Error::invalid_input(
+                format!(
+                    "A composite index can't include the column {} of type {}",
+                    column,
+                    field.data_type()
+                ),
+                location!(),
+            ));
+        }
+    }
+    let training_request = Box::new(CompositeTrainingRequest {
+        dataset: Arc::new(dataset.clone()),
+        columns: columns.iter().map(|column| column.to_string()).collect(),
+    });
+    let flat_index_trainer = FlatIndexMetadata::new(DataType::Binary);
+    let index_store = LanceIndexStore::from_dataset(dataset, uuid);
+    train_btree_index(training_request, &flat_index_trainer, &index_store).await
+}
+
 pub async fn open_scalar_index(dataset: &Dataset, uuid: &str) -> Result<Arc<dyn ScalarIndex>> {
     let index_store = Arc::new(LanceIndexStore::from_dataset(dataset, uuid));
     // Currently we assume all scalar indices are btree indices.  In the future, if this is not the
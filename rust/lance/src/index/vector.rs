@@ -13,6 +13,7 @@ pub mod ivf;
 pub mod pq;
 pub mod sq;
 mod traits;
+pub mod tune;
 mod utils;
 
 #[cfg(test)]
@@ -24,7 +25,9 @@ use lance_index::vector::ivf::storage::IvfData;
 use lance_index::vector::pq::ProductQuantizerImpl;
 use lance_index::vector::sq::builder::SQBuildParams;
 use lance_index::vector::sq::ScalarQuantizer;
-use lance_index::vector::{hnsw::builder::HnswBuildParams, ivf::IvfBuildParams, pq::PQBuildParams};
+use lance_index::vector::{
+    hnsw::builder::HnswBuildParams, ivf::IvfBuildParams, pq::PQBuildParams, NullVectorHandling,
+};
 use lance_index::{IndexType, INDEX_AUXILIARY_FILE_NAME, INDEX_METADATA_SCHEMA_KEY};
 use lance_io::traits::Reader;
 use lance_linalg::distance::*;
@@ -198,6 +201,25 @@ pub(crate) async fn build_vector_index(
 ) -> Result<()> {
     let stages = &params.stages;
 
+    let field = dataset.schema().field(column).ok_or_else(|| {
+        Error::invalid_input(
+            format!("Build Vector Index: column {} does not exist", column),
+            location!(),
+        )
+    })?;
+    if dataset.config().null_vectors(field)? == NullVectorHandling::Error {
+        let num_invalid = utils::count_invalid_vectors(dataset, column).await?;
+        if num_invalid > 0 {
+            return Err(Error::invalid_input(
+                format!(
+                    "Build Vector Index: column {} has {} null or non-finite vectors",
+                    column, num_invalid
+                ),
+                location!(),
+            ));
+        }
+    }
+
     if stages.is_empty() {
         return Err(Error::Index {
             message: "Build Vector Index: must have at least 1 stage".to_string(),
@@ -513,3 +535,157 @@ pub(crate) async fn open_vector_index_v2(
 
     Ok(index)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{cast::AsArray, Array, FixedSizeListArray, RecordBatch, RecordBatchIterator};
+    use arrow_array::{Float32Array, Int32Array};
+    use arrow_schema::{DataType, Field, Schema};
+    use lance_arrow::FixedSizeListArrayExt;
+    use lance_index::vector::NULL_VECTORS_KEY;
+    use lance_index::DatasetIndexExt;
+    use lance_testing::datagen::generate_random_array;
+
+    use crate::dataset::config::INDEX_NULL_VECTORS;
+    use crate::dataset::{ColumnAlteration, WriteParams};
+
+    const DIM: i32 = 8;
+    const NUM_ROWS: usize = 512;
+
+    /// The first 10 vectors are null, and the next one has a NaN value.
+    async fn create_dataset(uri: &str) -> Dataset {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new(
+                "v",
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), DIM),
+                true,
+            ),
+        ]));
+        let mut values = generate_random_array(NUM_ROWS * DIM as usize)
+            .values()
+            .to_vec();
+        values[10 * DIM as usize] = f32::NAN;
+        let vectors =
+            FixedSizeListArray::try_new_from_values(Float32Array::from(values), DIM).unwrap();
+        let nulls = (0..NUM_ROWS).map(|i| i >= 10).collect::<Vec<_>>();
+        let vectors = FixedSizeListArray::new(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            DIM,
+            vectors.values().clone(),
+            Some(nulls.into()),
+        );
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..NUM_ROWS as i32)),
+                Arc::new(vectors),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        // The legacy format doesn't keep the nulls of vectors.
+        let params = WriteParams {
+            use_legacy_format: false,
+            ..Default::default()
+        };
+        Dataset::write(reader, uri, Some(params)).await.unwrap()
+    }
+
+    async fn set_null_vectors(dataset: &mut Dataset, handling: NullVectorHandling) {
+        dataset
+            .alter_columns(&[ColumnAlteration::new("v".to_string())
+                .set_metadata(NULL_VECTORS_KEY, handling.as_str())])
+            .await
+            .unwrap();
+    }
+
+    async fn create_index(dataset: &mut Dataset) -> Result<()> {
+        let params = VectorIndexParams::ivf_pq(2, 8, 2, MetricType::L2, 10);
+        dataset
+            .create_index(&["v"], IndexType::Vector, None, &params, true)
+            .await
+    }
+
+    /// Search all the rows, and return the ids of the results and whether
+    /// their distance is null.
+    async fn search(dataset: &Dataset, k: usize, filter: Option<&str>) -> Vec<(i32, bool)> {
+        let mut scanner = dataset.scan();
+        scanner
+            .nearest("v", &Float32Array::from(vec![0.5; DIM as usize]), k)
+            .unwrap()
+            .nprobs(2)
+            .prefilter(true);
+        if let Some(filter) = filter {
+            scanner.filter(filter).unwrap();
+        }
+        let batch = scanner.try_into_batch().await.unwrap();
+        let ids = batch["i"].as_primitive::<arrow_array::types::Int32Type>();
+        let distances = batch[lance_index::vector::DIST_COL].clone();
+        ids.values()
+            .iter()
+            .enumerate()
+            .map(|(row, id)| (*id, distances.is_null(row)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_null_vector_handling() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let mut dataset = create_dataset(test_dir.path().to_str().unwrap()).await;
+
+        // Rows without a finite vector are neither indexed nor returned.
+        create_index(&mut dataset).await.unwrap();
+        let results = search(&dataset, NUM_ROWS, None).await;
+        assert_eq!(results.len(), NUM_ROWS - 11);
+        assert!(results.iter().all(|(id, is_null)| *id > 10 && !is_null));
+
+        // The default of the dataset applies to the columns that don't set one
+        dataset
+            .update_config([INDEX_NULL_VECTORS.entry(NullVectorHandling::Error)])
+            .await
+            .unwrap();
+        assert!(create_index(&mut dataset).await.is_err());
+        set_null_vectors(&mut dataset, NullVectorHandling::Skip).await;
+        create_index(&mut dataset).await.unwrap();
+
+        set_null_vectors(&mut dataset, NullVectorHandling::Error).await;
+        let err = create_index(&mut dataset).await.unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("has 11 null or non-finite vectors"),
+            "{}",
+            err
+        );
+        let mut scanner = dataset.scan();
+        let mut query = vec![0.5; DIM as usize];
+        query[0] = f32::INFINITY;
+        assert!(matches!(
+            scanner.nearest("v", &Float32Array::from(query), 10),
+            Err(Error::InvalidInput { .. })
+        ));
+
+        // Null vectors come last, with a null distance.
+        set_null_vectors(&mut dataset, NullVectorHandling::NullPartition).await;
+        let results = search(&dataset, NUM_ROWS, None).await;
+        assert_eq!(results.len(), NUM_ROWS - 1);
+        assert!(results[..NUM_ROWS - 11].iter().all(|(_, is_null)| !is_null));
+        let mut nulls = results[NUM_ROWS - 11..]
+            .iter()
+            .map(|(id, is_null)| {
+                assert!(is_null);
+                *id
+            })
+            .collect::<Vec<_>>();
+        nulls.sort();
+        assert_eq!(nulls, (0..10).collect::<Vec<_>>());
+
+        let results = search(&dataset, 10, None).await;
+        assert!(results.iter().all(|(_, is_null)| !is_null));
+        let results = search(&dataset, NUM_ROWS, Some("i < 20")).await;
+        assert_eq!(results.len(), 19);
+        assert_eq!(results.iter().filter(|(_, is_null)| *is_null).count(), 10);
+    }
+}
@@ -6,6 +6,7 @@ use std::sync::Arc;
 use arrow_array::{FixedSizeListArray, RecordBatch};
 use futures::prelude::stream::{StreamExt, TryStreamExt};
 use itertools::Itertools;
+use lance_core::utils::temp::temp_files;
 use lance_core::{Error, Result};
 use lance_file::v2::{reader::FileReader, writer::FileWriter};
 use lance_index::{
@@ -50,6 +51,8 @@ pub struct IvfIndexBuilder<S: IvfSubIndex, Q: Quantization + Clone> {
     sub_index: S,
     quantizer: Q,
     temp_dir: Path,
+    // Deletes `temp_dir` once the builder is dropped
+    _temp_dir_guard: TempDir,
 }
 
 impl<S: IvfSubIndex, Q: Quantization + Clone> IvfIndexBuilder<S, Q> {
@@ -65,8 +68,8 @@ impl<S: IvfSubIndex, Q: Quantization + Clone> IvfIndexBuilder<S, Q> {
         sub_index: S,
         quantizer: Q,
     ) -> Result<Self> {
-        let temp_dir = TempDir::new()?;
-        let temp_dir = Path::from(temp_dir.path().to_str().unwrap());
+        let temp_dir_guard = temp_files().new_dir()?;
+        let temp_dir = Path::from(temp_dir_guard.path().to_str().unwrap());
         Ok(Self {
             dataset,
             column,
@@ -78,6 +81,7 @@ impl<S: IvfSubIndex, Q: Quantization + Clone> IvfIndexBuilder<S, Q> {
             sub_index,
             quantizer,
             temp_dir,
+            _temp_dir_guard: temp_dir_guard,
         })
     }
 
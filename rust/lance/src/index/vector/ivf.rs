@@ -97,6 +97,9 @@ use crate::{
 
 mod builder;
 mod io;
+mod precomputed;
+
+pub(crate) use precomputed::create_index_from_assignments;
 
 /// IVF Index.
 pub struct IVFIndex {
@@ -166,7 +169,7 @@ impl IVFIndex {
         partition_id: usize,
         write_cache: bool,
     ) -> Result<Arc<dyn VectorIndex>> {
-        let cache_key = format!("{}-ivf-{}", self.uuid, partition_id);
+        let cache_key = self.partition_cache_key(partition_id);
         let session = self.session.upgrade().ok_or(Error::Internal {
             message: "attempt to use index after dataset was destroyed".into(),
             location: location!(),
@@ -200,6 +203,24 @@ impl IVFIndex {
         Ok(part_index)
     }
 
+    fn partition_cache_key(&self, partition_id: usize) -> String {
+        format!("{}-ivf-{}", self.uuid, partition_id)
+    }
+
+    /// Whether the partition is in the index cache of the session.
+    pub(crate) fn is_partition_cached(&self, partition_id: usize) -> bool {
+        self.session.upgrade().is_some_and(|session| {
+            session
+                .index_cache
+                .contains_vector(&self.partition_cache_key(partition_id))
+        })
+    }
+
+    /// Number of rows in each IVF partition.
+    pub(crate) fn partition_lengths(&self) -> &[u32] {
+        &self.ivf.lengths
+    }
+
     /// preprocess the query vector given the partition id.
     ///
     /// Internal API with no stability guarantees.
@@ -240,7 +261,26 @@ impl IVFIndex {
             self.metric_type
         };
 
-        self.ivf.find_partitions(&query.key, query.nprobes, mt)
+        let Some(fraction) = query.probe_fraction else {
+            return self.ivf.find_partitions(&query.key, query.nprobes, mt);
+        };
+
+        // Probe the closest partitions until they hold enough rows.
+        let partitions = self
+            .ivf
+            .find_partitions(&query.key, self.ivf.num_partitions(), mt)?;
+        let total_rows = self.ivf.lengths.iter().map(|&len| len as u64).sum::<u64>();
+        let target_rows = (total_rows as f64 * fraction as f64).ceil() as u64;
+        let mut num_rows = 0;
+        let mut nprobes = 0;
+        for &part_id in partitions.values() {
+            if nprobes >= query.nprobes && num_rows >= target_rows {
+                break;
+            }
+            num_rows += self.ivf.lengths[part_id as usize] as u64;
+            nprobes += 1;
+        }
+        Ok(partitions.slice(0, nprobes))
     }
 }
 
@@ -363,6 +403,7 @@ async fn optimize_ivf_pq_indices(
                 10000,
                 2,
                 None,
+                None,
             )
             .await?,
         )
@@ -443,6 +484,7 @@ async fn optimize_ivf_hnsw_indices<Q: Quantization>(
                 10000,
                 2,
                 None,
+                None,
             )
             .await?,
         )
@@ -688,7 +730,7 @@ impl VectorIndex for IVFIndex {
         };
 
         let partition_ids = self.find_partitions(&query)?;
-        assert!(partition_ids.len() <= query.nprobes);
+        assert!(query.probe_fraction.is_some() || partition_ids.len() <= query.nprobes);
         let part_ids = partition_ids.values().to_vec();
         let batches = stream::iter(part_ids)
             .map(|part_id| self.search_in_partition(part_id as usize, &query, pre_filter.clone()))
@@ -1214,6 +1256,7 @@ pub async fn build_ivf_pq_index(
         ivf_params.shuffle_partition_batches,
         ivf_params.shuffle_partition_concurrency,
         ivf_params.precomputed_shuffle_buffers.clone(),
+        ivf_params.memory_budget,
     )
     .await
 }
@@ -1249,6 +1292,7 @@ pub async fn build_ivf_hnsw_pq_index(
         ivf_params.shuffle_partition_batches,
         ivf_params.shuffle_partition_concurrency,
         ivf_params.precomputed_shuffle_buffers.clone(),
+        ivf_params.memory_budget,
     )
     .await
 }
@@ -1284,6 +1328,7 @@ pub async fn build_ivf_hnsw_sq_index(
         ivf_params.shuffle_partition_batches,
         ivf_params.shuffle_partition_concurrency,
         ivf_params.precomputed_shuffle_buffers.clone(),
+        ivf_params.memory_budget,
     )
     .await
 }
@@ -1426,6 +1471,7 @@ async fn write_ivf_pq_file(
     shuffle_partition_batches: usize,
     shuffle_partition_concurrency: usize,
     precomputed_shuffle_buffers: Option<(Path, Vec<String>)>,
+    memory_budget: Option<usize>,
 ) -> Result<()> {
     let object_store = dataset.object_store();
     let path = dataset.indices_dir().child(uuid).child(INDEX_FILE_NAME);
@@ -1445,6 +1491,7 @@ async fn write_ivf_pq_file(
         shuffle_partition_batches,
         shuffle_partition_concurrency,
         precomputed_shuffle_buffers,
+        memory_budget,
     )
     .await?;
     info!("Built IVF partitions: {}s", start.elapsed().as_secs_f32());
@@ -1493,6 +1540,7 @@ async fn write_ivf_hnsw_file(
     shuffle_partition_batches: usize,
     shuffle_partition_concurrency: usize,
     precomputed_shuffle_buffers: Option<(Path, Vec<String>)>,
+    memory_budget: Option<usize>,
 ) -> Result<()> {
     let object_store = dataset.object_store();
     let path = dataset.indices_dir().child(uuid).child(INDEX_FILE_NAME);
@@ -1593,6 +1641,7 @@ async fn write_ivf_hnsw_file(
         shuffle_partition_batches,
         shuffle_partition_concurrency,
         precomputed_shuffle_buffers,
+        memory_budget,
     )
     .await?;
     info!("Built IVF partitions: {}s", start.elapsed().as_secs_f32());
@@ -1884,6 +1933,7 @@ mod tests {
                     key: Arc::new(row),
                     k: 5,
                     nprobes: 1,
+                    probe_fraction: None,
                     ef: None,
                     refine_factor: None,
                     metric_type: MetricType::L2,
@@ -2077,6 +2127,7 @@ mod tests {
             fields: Vec::new(),
             name: INDEX_NAME.to_string(),
             fragment_bitmap: None,
+            kind: None,
         };
 
         let prefilter = Arc::new(PreFilter::new(dataset.clone(), &[index_meta], None));
@@ -2820,4 +2871,69 @@ mod tests {
 
         assert!(correct_times >= 9, "correct: {}", correct_times);
     }
+
+    #[tokio::test]
+    async fn test_find_partitions_with_probe_fraction() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        const DIM: usize = 32;
+
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vector",
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                DIM as i32,
+            ),
+            true,
+        )]));
+        let arr = generate_random_array_with_range(1000 * DIM, 0.0..1.0);
+        let fsl = FixedSizeListArray::try_new_from_values(arr.clone(), DIM as i32).unwrap();
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(fsl)]).unwrap();
+        let batches = RecordBatchIterator::new(vec![batch].into_iter().map(Ok), schema.clone());
+        let mut dataset = Dataset::write(batches, test_uri, None).await.unwrap();
+
+        let params = VectorIndexParams::ivf_pq(8, 8, 4, MetricType::L2, 50);
+        dataset
+            .create_index(&["vector"], IndexType::Vector, None, &params, false)
+            .await
+            .unwrap();
+        let indices = dataset.load_indices().await.unwrap();
+        let idx = dataset
+            .open_generic_index("vector", indices[0].uuid.to_string().as_str())
+            .await
+            .unwrap();
+        let ivf_idx = idx.as_any().downcast_ref::<IVFIndex>().unwrap();
+
+        let mut query = Query {
+            column: "vector".to_string(),
+            key: Arc::new(arr.slice(0, DIM)),
+            k: 10,
+            nprobes: 1,
+            probe_fraction: Some(0.5),
+            ef: None,
+            refine_factor: None,
+            metric_type: MetricType::L2,
+            use_index: true,
+        };
+        let partitions = ivf_idx.find_partitions(&query).unwrap();
+        let num_rows = |partitions: &UInt32Array| {
+            partitions
+                .values()
+                .iter()
+                .map(|&p| ivf_idx.ivf.lengths[p as usize])
+                .sum::<u32>()
+        };
+        // The closest partitions are probed until they hold half of the rows.
+        assert!(num_rows(&partitions) >= 500);
+        let last = partitions.value(partitions.len() - 1) as usize;
+        assert!(num_rows(&partitions) - ivf_idx.ivf.lengths[last] < 500);
+        query.nprobes = partitions.len();
+        query.probe_fraction = None;
+        assert_eq!(ivf_idx.find_partitions(&query).unwrap(), partitions);
+
+        // `nprobes` is the least number of probes.
+        query.nprobes = 8;
+        query.probe_fraction = Some(0.0);
+        assert_eq!(ivf_idx.find_partitions(&query).unwrap().len(), 8);
+    }
 }
@@ -45,6 +45,7 @@ pub(super) async fn build_partitions(
     shuffle_partition_batches: usize,
     shuffle_partition_concurrency: usize,
     precomputed_shuffle_buffers: Option<(Path, Vec<String>)>,
+    memory_budget: Option<usize>,
 ) -> Result<()> {
     let schema = data.schema();
     if schema.column_with_name(column).is_none() {
@@ -77,6 +78,7 @@ pub(super) async fn build_partitions(
         shuffle_partition_batches,
         shuffle_partition_concurrency,
         precomputed_shuffle_buffers,
+        memory_budget,
     )
     .await?;
 
@@ -105,6 +107,7 @@ pub(super) async fn build_hnsw_partitions(
     shuffle_partition_batches: usize,
     shuffle_partition_concurrency: usize,
     precomputed_shuffle_buffers: Option<(Path, Vec<String>)>,
+    memory_budget: Option<usize>,
 ) -> Result<(Vec<HnswMetadata>, IvfData)> {
     let schema = data.schema();
     if schema.column_with_name(column).is_none() {
@@ -144,6 +147,7 @@ pub(super) async fn build_hnsw_partitions(
         shuffle_partition_batches,
         shuffle_partition_concurrency,
         precomputed_shuffle_buffers,
+        memory_budget,
     )
     .await?;
 
@@ -0,0 +1,470 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! IVF_PQ indices assembled from pre-computed partition assignments
+//!
+//! A distributed engine can assign every vector to an IVF partition and
+//! encode it with a PQ codebook itself, and hand the results to Lance, which
+//! validates them, writes the index file and commits the index. The engine
+//! does not need to know the index file format.
+
+use std::sync::Arc;
+
+use arrow::compute::cast;
+use arrow_array::{
+    cast::AsArray,
+    types::{UInt32Type, UInt64Type},
+    Array, ArrayRef, RecordBatch, RecordBatchReader,
+};
+use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+use futures::{stream, StreamExt, TryStreamExt};
+use lance_core::{Error, Result, ROW_ID};
+use lance_file::format::MAGIC;
+use lance_index::vector::{ivf::shuffler::IvfShuffler, PART_ID_COLUMN, PQ_CODE_COLUMN};
+use lance_index::DatasetIndexExt;
+use lance_io::traits::WriteExt;
+use lance_linalg::distance::MetricType;
+use lance_table::format::Index as IndexMetadata;
+use roaring::RoaringTreemap;
+use snafu::{location, Location};
+use uuid::Uuid;
+
+use super::{build_ivf_model, sanity_check, sanity_check_params, IvfPQIndexMetadata};
+use crate::dataset::transaction::{Operation, Transaction};
+use crate::index::vector::{
+    ivf::io::write_pq_partitions, pq::build_pq_model, StageParams, VectorIndexParams,
+};
+use crate::index::{pb, INDEX_FILE_NAME};
+use crate::io::commit::commit_transaction;
+use crate::Dataset;
+
+/// Check a batch of assignments, and keep the row id, partition id and PQ
+/// code columns in the layout the shuffler expects.
+///
+/// The row ids are removed from `unassigned`, a subset of `vector_rows`, so
+/// that unknown and duplicate row ids are caught.
+fn validate_assignments(
+    batch: &RecordBatch,
+    num_partitions: u32,
+    num_sub_vectors: usize,
+    vector_rows: &RoaringTreemap,
+    unassigned: &mut RoaringTreemap,
+) -> Result<RecordBatch> {
+    let column = |name: &str| {
+        batch
+            .column_by_name(name)
+            .filter(|column| column.null_count() == 0)
+            .ok_or_else(|| {
+                Error::invalid_input(
+                    format!("Assignments must have a {name} column without nulls"),
+                    location!(),
+                )
+            })
+    };
+    let row_ids = column(ROW_ID)?
+        .as_primitive_opt::<UInt64Type>()
+        .ok_or_else(|| {
+            Error::invalid_input(format!("{ROW_ID} must be a u64 column"), location!())
+        })?;
+    let part_ids = column(PART_ID_COLUMN)?
+        .as_primitive_opt::<UInt32Type>()
+        .ok_or_else(|| {
+            Error::invalid_input(
+                format!("{PART_ID_COLUMN} must be a u32 column"),
+                location!(),
+            )
+        })?;
+    let code_type = DataType::FixedSizeList(
+        Arc::new(Field::new("item", DataType::UInt8, true)),
+        num_sub_vectors as i32,
+    );
+    let codes = column(PQ_CODE_COLUMN)?;
+    if !matches!(
+        codes.data_type(),
+        DataType::FixedSizeList(field, len)
+            if field.data_type() == &DataType::UInt8 && *len == num_sub_vectors as i32
+    ) {
+        return Err(Error::invalid_input(
+            format!(
+                "{PQ_CODE_COLUMN} must be a {code_type} column, but is {}",
+                codes.data_type()
+            ),
+            location!(),
+        ));
+    }
+
+    if let Some(part_id) = part_ids.values().iter().find(|id| **id >= num_partitions) {
+        return Err(Error::invalid_input(
+            format!(
+                "Partition {part_id} is out of range, the index has {num_partitions} partitions"
+            ),
+            location!(),
+        ));
+    }
+    for row_id in row_ids.values() {
+        if !unassigned.remove(*row_id) {
+            let message = if vector_rows.contains(*row_id) {
+                format!("Row {row_id} is assigned more than once")
+            } else {
+                format!("Row {row_id} is not a row of the dataset with a vector")
+            };
+            return Err(Error::invalid_input(message, location!()));
+        }
+    }
+
+    let schema = Arc::new(ArrowSchema::new(vec![
+        Field::new(ROW_ID, DataType::UInt64, false),
+        Field::new(PART_ID_COLUMN, DataType::UInt32, false),
+        Field::new(PQ_CODE_COLUMN, code_type.clone(), false),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(row_ids.clone()),
+        Arc::new(part_ids.clone()),
+        cast(codes, &code_type)?,
+    ];
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+/// The row ids of the rows of `column` with a vector
+async fn vector_row_ids(dataset: &Dataset, column: &str) -> Result<RoaringTreemap> {
+    let mut scanner = dataset.scan();
+    scanner
+        .project::<String>(&[])?
+        .with_row_id()
+        .filter(&format!("{column} IS NOT NULL"))?;
+    let mut row_ids = RoaringTreemap::new();
+    let mut batches = scanner.try_into_stream().await?;
+    while let Some(batch) = batches.try_next().await? {
+        if let Some(ids) = batch.column_by_name(ROW_ID) {
+            row_ids.extend(ids.as_primitive::<UInt64Type>().values().iter().copied());
+        }
+    }
+    Ok(row_ids)
+}
+
+pub async fn create_index_from_assignments(
+    dataset: &mut Dataset,
+    column: &str,
+    name: Option<String>,
+    params: &VectorIndexParams,
+    assignments: impl RecordBatchReader + Send,
+) -> Result<()> {
+    let field = sanity_check(dataset, column)?.clone();
+    let [.., StageParams::Ivf(ivf_params), StageParams::PQ(pq_params)] = params.stages.as_slice()
+    else {
+        return Err(Error::invalid_input(
+            "Indices can only be created from assignments with IVF_PQ parameters".to_string(),
+            location!(),
+        ));
+    };
+    sanity_check_params(ivf_params, pq_params)?;
+    if !matches!(
+        params.metric_type,
+        MetricType::L2 | MetricType::Cosine | MetricType::Dot
+    ) {
+        return Err(Error::invalid_input(
+            format!(
+                "IVF_PQ indices don't support the {} metric",
+                params.metric_type
+            ),
+            location!(),
+        ));
+    }
+    if ivf_params.centroids.is_none() || pq_params.codebook.is_none() {
+        return Err(Error::invalid_input(
+            "The IVF centroids and PQ codebook of the assignments must be set".to_string(),
+            location!(),
+        ));
+    }
+    let DataType::FixedSizeList(_, dim) = field.data_type() else {
+        return Err(Error::invalid_input(
+            format!("{column} is not a vector column"),
+            location!(),
+        ));
+    };
+    let dim = dim as usize;
+    let codebook_len = pq_params
+        .codebook
+        .as_ref()
+        .map(|c| c.len())
+        .unwrap_or_default();
+    if pq_params.num_bits != 8
+        || pq_params.num_sub_vectors == 0
+        || dim % pq_params.num_sub_vectors != 0
+        || codebook_len != 256 * dim
+    {
+        return Err(Error::invalid_input(
+            format!(
+                "Invalid PQ parameters for {dim} dimensions: {} sub-vectors of {} bits, \
+                with a codebook of {codebook_len} values",
+                pq_params.num_sub_vectors, pq_params.num_bits
+            ),
+            location!(),
+        ));
+    }
+
+    let index_name = name.unwrap_or(format!("{column}_idx"));
+    if dataset
+        .load_indices()
+        .await?
+        .iter()
+        .any(|idx| idx.name == index_name)
+    {
+        return Err(Error::invalid_input(
+            format!("Index name '{index_name}' already exists"),
+            location!(),
+        ));
+    }
+
+    let mut ivf = build_ivf_model(dataset, column, dim, params.metric_type, ivf_params).await?;
+    let pq = build_pq_model(dataset, column, dim, params.metric_type, pq_params, None).await?;
+    let num_partitions = ivf.num_partitions() as u32;
+
+    // Validate the assignments while spilling them, then sort them by partition
+    let vector_rows = vector_row_ids(dataset, column).await?;
+    let mut unassigned = vector_rows.clone();
+    let mut shuffler = IvfShuffler::try_new(num_partitions, None)?;
+    let validated = stream::iter(assignments).map(|batch| {
+        validate_assignments(
+            &batch?,
+            num_partitions,
+            pq.num_sub_vectors(),
+            &vector_rows,
+            &mut unassigned,
+        )
+    });
+    shuffler.write_unsorted_stream(validated).await?;
+    if !unassigned.is_empty() {
+        return Err(Error::invalid_input(
+            format!(
+                "{} rows of {column} were not assigned to a partition",
+                unassigned.len()
+            ),
+            location!(),
+        ));
+    }
+    let batches_per_run = match ivf_params.memory_budget {
+        Some(memory_budget) => {
+            shuffler
+                .batches_per_run(memory_budget, ivf_params.shuffle_partition_concurrency)
+                .await?
+        }
+        None => ivf_params.shuffle_partition_batches,
+    };
+    let streams = shuffler
+        .merge_shards(batches_per_run, ivf_params.shuffle_partition_concurrency)
+        .await?;
+
+    let uuid = Uuid::new_v4();
+    let path = dataset
+        .indices_dir()
+        .child(uuid.to_string())
+        .child(INDEX_FILE_NAME);
+    let mut writer = dataset.object_store().create(&path).await?;
+    write_pq_partitions(&mut writer, &mut ivf, Some(streams), None).await?;
+    let metadata = IvfPQIndexMetadata {
+        name: index_name.clone(),
+        column: column.to_string(),
+        dimension: dim as u32,
+        dataset_version: dataset.version().version,
+        metric_type: params.metric_type,
+        ivf,
+        pq,
+        transforms: vec![],
+    };
+    let metadata = pb::Index::try_from(&metadata)?;
+    let pos = writer.write_protobuf(&metadata).await?;
+    writer.write_magics(pos, 0, 1, MAGIC).await?;
+    writer.shutdown().await?;
+
+    let new_idx = IndexMetadata {
+        uuid,
+        name: index_name,
+        fields: vec![field.id],
+        dataset_version: dataset.manifest.version,
+        fragment_bitmap: Some(
+            dataset
+                .get_fragments()
+                .iter()
+                .map(|f| f.id() as u32)
+                .collect(),
+        ),
+        kind: None,
+    };
+    let transaction = Transaction::new(
+        dataset.manifest.version,
+        Operation::CreateIndex {
+            new_indices: vec![new_idx],
+            removed_indices: vec![],
+        },
+        None,
+    );
+    let new_manifest = commit_transaction(
+        dataset,
+        dataset.object_store(),
+        dataset.commit_handler.as_ref(),
+        &transaction,
+        &Default::default(),
+        &Default::default(),
+    )
+    .await?;
+    dataset.manifest = Arc::new(new_manifest);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{
+        FixedSizeListArray, Float32Array, RecordBatchIterator, UInt32Array, UInt64Array, UInt8Array,
+    };
+    use lance_arrow::FixedSizeListArrayExt;
+    use lance_index::vector::{ivf::IvfBuildParams, pq::PQBuildParams};
+    use lance_linalg::distance::MetricType;
+    use tempfile::tempdir;
+
+    const DIM: usize = 16;
+    const NUM_ROWS: usize = 256;
+
+    fn vectors(values: impl Iterator<Item = f32>) -> FixedSizeListArray {
+        FixedSizeListArray::try_new_from_values(Float32Array::from_iter_values(values), DIM as i32)
+            .unwrap()
+    }
+
+    /// Assignments of the given rows: rows below 128 to partition 0, and the
+    /// others to partition `num_partitions / 2`
+    fn assignments(row_ids: Vec<u64>, num_partitions: u32) -> impl RecordBatchReader + Send {
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new(ROW_ID, DataType::UInt64, false),
+            Field::new(PART_ID_COLUMN, DataType::UInt32, false),
+            Field::new(
+                PQ_CODE_COLUMN,
+                DataType::FixedSizeList(Arc::new(Field::new("item", DataType::UInt8, true)), 2),
+                false,
+            ),
+        ]));
+        let part_ids = UInt32Array::from_iter_values(
+            row_ids
+                .iter()
+                .map(|id| (*id / 128).min(1) as u32 * num_partitions / 2),
+        );
+        // Row `i` has all its values equal to `i`, so its residual to the
+        // centroid of its partition has all its values equal to `i % 128`,
+        // which is its code for both sub-vectors
+        let codes = FixedSizeListArray::try_new_from_values(
+            UInt8Array::from_iter_values(row_ids.iter().flat_map(|id| [(*id % 128) as u8; 2])),
+            2,
+        )
+        .unwrap();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(UInt64Array::from(row_ids)),
+                Arc::new(part_ids),
+                Arc::new(codes),
+            ],
+        )
+        .unwrap();
+        RecordBatchIterator::new(vec![Ok(batch)], schema)
+    }
+
+    #[tokio::test]
+    async fn test_create_index_from_assignments() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let data = vectors((0..NUM_ROWS).flat_map(|i| [i as f32; DIM]));
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "vector",
+            data.data_type().clone(),
+            true,
+        )]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(data)]).unwrap();
+        let mut dataset = Dataset::write(
+            RecordBatchIterator::new(vec![Ok(batch)], schema),
+            test_uri,
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Centroids with all their values equal to 0 and 128, and a codebook
+        // whose centroid `c` has all its values equal to `c`, for each of the 2
+        // sub-vectors
+        let centroids = vectors([0.0, 128.0].into_iter().flat_map(|c| [c; DIM]));
+        let codebook = Float32Array::from_iter_values(
+            (0..2).flat_map(|_| (0..256).flat_map(|c| [c as f32; DIM / 2])),
+        );
+        let params = VectorIndexParams::with_ivf_pq_params(
+            MetricType::L2,
+            IvfBuildParams::try_with_centroids(2, Arc::new(centroids)).unwrap(),
+            PQBuildParams::with_codebook(2, 8, Arc::new(codebook)),
+        );
+        let all_rows = (0..NUM_ROWS as u64).collect::<Vec<_>>();
+
+        // Invalid assignments are rejected
+        let mut missing = all_rows.clone();
+        missing.pop();
+        let mut duplicated = all_rows.clone();
+        duplicated.push(3);
+        let mut unknown = all_rows.clone();
+        unknown.push(NUM_ROWS as u64);
+        for (rows, num_partitions, message) in [
+            (missing, 2, "were not assigned"),
+            (duplicated, 2, "more than once"),
+            (unknown, 2, "not a row of the dataset"),
+            (all_rows.clone(), 4, "out of range"),
+        ] {
+            let err = dataset
+                .create_index_from_assignments(
+                    "vector",
+                    None,
+                    &params,
+                    assignments(rows, num_partitions),
+                )
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains(message), "{err}");
+        }
+        let hamming = VectorIndexParams {
+            metric_type: MetricType::Hamming,
+            ..params.clone()
+        };
+        let err = dataset
+            .create_index_from_assignments(
+                "vector",
+                None,
+                &hamming,
+                assignments(all_rows.clone(), 2),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("metric"), "{err}");
+        assert!(dataset.load_indices().await.unwrap().is_empty());
+
+        dataset
+            .create_index_from_assignments("vector", None, &params, assignments(all_rows, 2))
+            .await
+            .unwrap();
+        let indices = dataset.load_indices().await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].name, "vector_idx");
+
+        // The codes are decoded relative to the centroids of their partitions
+        for row in [10, 200] {
+            let query = Float32Array::from(vec![row as f32; DIM]);
+            let results = dataset
+                .scan()
+                .nearest("vector", &query, 1)
+                .unwrap()
+                .nprobs(2)
+                .with_row_id()
+                .try_into_batch()
+                .await
+                .unwrap();
+            let row_ids = results[ROW_ID].as_primitive::<UInt64Type>();
+            assert_eq!(row_ids.values(), &[row]);
+        }
+    }
+}
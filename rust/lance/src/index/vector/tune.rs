@@ -0,0 +1,546 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Tune the search parameters of graph indices to reach a target recall
+//!
+//! The recall of an HNSW search grows with `ef`, the number of candidates it
+//! keeps, and so does its latency. Tuning samples vectors from the dataset as
+//! queries, computes their exact nearest neighbors as ground truth, and
+//! doubles `ef` until the average recall reaches the target. The same way,
+//! a target recall can replace `nprobes` and the refine factor altogether.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use arrow::compute::cast;
+use arrow_array::cast::AsArray;
+use arrow_array::{Array, Float32Array, UInt64Array};
+use arrow_schema::DataType;
+use deepsize::DeepSizeOf;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use lance_core::ROW_ID;
+use lance_index::vector::Query;
+use lance_table::format::Index;
+use snafu::{location, Location};
+use uuid::Uuid;
+
+use crate::index::DatasetIndexInternalExt;
+use crate::{Dataset, Error, Result};
+
+/// How to tune `ef`, see [`crate::dataset::scanner::Scanner::auto_tune_ef`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EfTuning {
+    /// The average recall to reach, between 0 and 1. Default: 0.95.
+    pub target_recall: f32,
+    /// Number of vectors sampled from the dataset as queries. Default: 100.
+    pub sample_size: usize,
+    /// The largest `ef` to try. It is used when the target can't be reached.
+    /// Default: 1024.
+    pub max_ef: usize,
+}
+
+impl Default for EfTuning {
+    fn default() -> Self {
+        Self {
+            target_recall: 0.95,
+            sample_size: 100,
+            max_ef: 1024,
+        }
+    }
+}
+
+impl EfTuning {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !(self.target_recall > 0.0 && self.target_recall <= 1.0) {
+            return Err(Error::invalid_input(
+                format!(
+                    "Target recall must be in (0, 1], got {}",
+                    self.target_recall
+                ),
+                location!(),
+            ));
+        }
+        if self.sample_size == 0 {
+            return Err(Error::invalid_input(
+                "Tuning needs at least one sample",
+                location!(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TuningKey {
+    index: Uuid,
+    k: usize,
+    nprobes: usize,
+    target_recall: u32,
+    sample_size: usize,
+    max_ef: usize,
+}
+
+/// Search parameters picked to reach a target recall, see
+/// [`crate::dataset::scanner::Scanner::target_recall`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RecallParams {
+    /// See [`Query::probe_fraction`].
+    pub probe_fraction: Option<f32>,
+    pub refine_factor: Option<u32>,
+    pub ef: Option<usize>,
+}
+
+/// The search parameters tuned in a session.
+#[derive(Debug, Default)]
+pub(crate) struct TuningCache {
+    /// `None` for indices without a graph.
+    ef: Mutex<HashMap<TuningKey, Option<usize>>>,
+    recall: Mutex<HashMap<TuningKey, RecallParams>>,
+}
+
+impl DeepSizeOf for TuningCache {
+    fn deep_size_of_children(&self, _context: &mut deepsize::Context) -> usize {
+        self.ef.lock().unwrap().capacity()
+            * (std::mem::size_of::<TuningKey>() + std::mem::size_of::<Option<usize>>())
+            + self.recall.lock().unwrap().capacity()
+                * (std::mem::size_of::<TuningKey>() + std::mem::size_of::<RecallParams>())
+    }
+}
+
+/// The largest refine factor tried to make up for quantization errors.
+const MAX_REFINE_FACTOR: u32 = 64;
+
+/// Row ids of the `k` nearest neighbors of `key`, with the index searched
+/// with `params` or by exact search when `params` is `None`.
+async fn search(
+    dataset: &Dataset,
+    column: &str,
+    key: &Float32Array,
+    k: usize,
+    nprobes: usize,
+    params: Option<&RecallParams>,
+) -> Result<HashSet<u64>> {
+    let mut scanner = dataset.scan();
+    scanner
+        .nearest(column, key, k)?
+        .nprobs(nprobes)
+        .project::<&str>(&[])?
+        .with_row_id();
+    match params {
+        Some(params) => {
+            if let Some(fraction) = params.probe_fraction {
+                scanner.probe_fraction(fraction);
+            }
+            if let Some(factor) = params.refine_factor {
+                scanner.refine(factor);
+            }
+            if let Some(ef) = params.ef {
+                scanner.ef(ef);
+            }
+        }
+        None => {
+            scanner.use_index(false);
+        }
+    };
+    let batch = scanner.try_into_batch().await?;
+    let row_ids = batch[ROW_ID]
+        .as_any()
+        .downcast_ref::<UInt64Array>()
+        .ok_or_else(|| Error::Internal {
+            message: format!("{} is not a u64 array", ROW_ID),
+            location: location!(),
+        })?;
+    Ok(row_ids.values().iter().copied().collect())
+}
+
+/// Vectors sampled from the dataset as queries, with their exact neighbors.
+struct Samples<'a> {
+    dataset: &'a Dataset,
+    column: &'a str,
+    k: usize,
+    nprobes: usize,
+    queries: Vec<Float32Array>,
+    ground_truth: Vec<HashSet<u64>>,
+}
+
+impl<'a> Samples<'a> {
+    async fn try_new(
+        dataset: &'a Dataset,
+        column: &'a str,
+        k: usize,
+        nprobes: usize,
+        tuning: &EfTuning,
+    ) -> Result<Self> {
+        tuning.validate()?;
+        let projection = dataset.schema().project(&[column])?;
+        let sample = dataset.sample(tuning.sample_size, &projection).await?;
+        let vectors = sample[column].as_fixed_size_list();
+        let mut queries = Vec::with_capacity(vectors.len());
+        for i in 0..vectors.len() {
+            let key = cast(&vectors.value(i), &DataType::Float32)?;
+            queries.push(key.as_primitive().clone());
+        }
+
+        let mut ground_truth = Vec::with_capacity(queries.len());
+        for key in &queries {
+            ground_truth.push(search(dataset, column, key, k, nprobes, None).await?);
+        }
+        Ok(Self {
+            dataset,
+            column,
+            k,
+            nprobes,
+            queries,
+            ground_truth,
+        })
+    }
+
+    /// The average recall of searches with `params`.
+    async fn recall(&self, params: &RecallParams) -> Result<f32> {
+        let expected = self.ground_truth.iter().map(|ids| ids.len()).sum::<usize>();
+        if expected == 0 {
+            return Ok(1.0);
+        }
+        let mut found = 0;
+        for (key, truth) in self.queries.iter().zip(&self.ground_truth) {
+            let ids = search(
+                self.dataset,
+                self.column,
+                key,
+                self.k,
+                self.nprobes,
+                Some(params),
+            )
+            .await?;
+            found += ids.intersection(truth).count();
+        }
+        Ok(found as f32 / expected as f32)
+    }
+
+    /// Double `ef` from `k` until the recall reaches the target.
+    async fn tune_ef(&self, params: &RecallParams, tuning: &EfTuning) -> Result<usize> {
+        let mut ef = self.k;
+        loop {
+            let params = RecallParams {
+                ef: Some(ef),
+                ..*params
+            };
+            if ef >= tuning.max_ef || self.recall(&params).await? >= tuning.target_recall {
+                return Ok(ef);
+            }
+            ef = (ef * 2).min(tuning.max_ef);
+        }
+    }
+}
+
+/// Find the smallest `ef`, doubling from `k`, that makes searches of the
+/// index on `column` reach the target recall of `tuning`.
+pub async fn tune_ef(
+    dataset: &Dataset,
+    column: &str,
+    k: usize,
+    nprobes: usize,
+    tuning: &EfTuning,
+) -> Result<usize> {
+    let samples = Samples::try_new(dataset, column, k, nprobes, tuning).await?;
+    samples.tune_ef(&RecallParams::default(), tuning).await
+}
+
+/// Pick the search parameters of the index on `column` that reach the
+/// target recall of `tuning` while probing as few rows as possible.
+///
+/// Searches first probe every partition, to find the `ef` of graph indices
+/// and the refine factor that make up for the errors of the index itself.
+/// The fraction of rows to probe then doubles from one partition's worth
+/// until the target is reached again. `num_partitions` and `graph` come from
+/// the statistics of the index.
+pub async fn tune_for_recall(
+    dataset: &Dataset,
+    column: &str,
+    k: usize,
+    nprobes: usize,
+    num_partitions: usize,
+    graph: bool,
+    tuning: &EfTuning,
+) -> Result<RecallParams> {
+    let samples = Samples::try_new(dataset, column, k, nprobes, tuning).await?;
+    tune_samples_for_recall(&samples, num_partitions, graph, tuning).await
+}
+
+async fn tune_samples_for_recall(
+    samples: &Samples<'_>,
+    num_partitions: usize,
+    graph: bool,
+    tuning: &EfTuning,
+) -> Result<RecallParams> {
+    let mut params = RecallParams {
+        probe_fraction: Some(1.0),
+        ..Default::default()
+    };
+    if graph {
+        params.ef = Some(samples.tune_ef(&params, tuning).await?);
+    }
+    let mut recall = samples.recall(&params).await?;
+    let mut factor = 1;
+    while recall < tuning.target_recall && factor < MAX_REFINE_FACTOR {
+        factor *= 2;
+        params.refine_factor = Some(factor);
+        recall = samples.recall(&params).await?;
+    }
+
+    let mut fraction = 1.0 / num_partitions.max(1) as f32;
+    while fraction < 1.0 {
+        let candidate = RecallParams {
+            probe_fraction: Some(fraction),
+            ..params
+        };
+        if samples.recall(&candidate).await? >= tuning.target_recall.min(recall) {
+            return Ok(candidate);
+        }
+        fraction *= 2.0;
+    }
+    Ok(params)
+}
+
+fn is_graph_index(statistics: &serde_json::Value) -> bool {
+    statistics["index_type"] == "HNSW" || statistics["sub_index"]["index_type"] == "HNSW"
+}
+
+fn tuning_key(index: &Index, k: usize, nprobes: usize, tuning: &EfTuning) -> TuningKey {
+    TuningKey {
+        index: index.uuid,
+        k,
+        nprobes,
+        target_recall: tuning.target_recall.to_bits(),
+        sample_size: tuning.sample_size,
+        max_ef: tuning.max_ef,
+    }
+}
+
+/// The tuned `ef` of `query` on `index`, cached in the session of `dataset`.
+///
+/// Returns `None` if the index does not search a graph. Tuning runs
+/// searches, which plan this function again, so the future is boxed.
+pub(crate) fn tuned_ef<'a>(
+    dataset: &'a Arc<Dataset>,
+    index: &'a Index,
+    query: &'a Query,
+    tuning: &'a EfTuning,
+) -> BoxFuture<'a, Result<Option<usize>>> {
+    async move {
+        let k = query.k * query.refine_factor.unwrap_or(1) as usize;
+        let key = tuning_key(index, k, query.nprobes, tuning);
+        let cache = &dataset.session.tuning;
+        if let Some(ef) = cache.ef.lock().unwrap().get(&key) {
+            return Ok(*ef);
+        }
+
+        let vector_index = dataset
+            .open_vector_index(&query.column, &index.uuid.to_string())
+            .await?;
+        let ef = if is_graph_index(&vector_index.statistics()?) {
+            Some(tune_ef(dataset, &query.column, k, query.nprobes, tuning).await?)
+        } else {
+            None
+        };
+        cache.ef.lock().unwrap().insert(key, ef);
+        Ok(ef)
+    }
+    .boxed()
+}
+
+/// The parameters of `query` on `index` tuned by [`tune_for_recall`],
+/// cached in the session of `dataset`.
+pub(crate) fn tuned_for_recall<'a>(
+    dataset: &'a Arc<Dataset>,
+    index: &'a Index,
+    query: &'a Query,
+    tuning: &'a EfTuning,
+) -> BoxFuture<'a, Result<RecallParams>> {
+    async move {
+        let key = tuning_key(index, query.k, query.nprobes, tuning);
+        let cache = &dataset.session.tuning;
+        if let Some(params) = cache.recall.lock().unwrap().get(&key) {
+            return Ok(*params);
+        }
+
+        let statistics = dataset
+            .open_vector_index(&query.column, &index.uuid.to_string())
+            .await?
+            .statistics()?;
+        let num_partitions = statistics["num_partitions"].as_u64().unwrap_or(1) as usize;
+        let params = tune_for_recall(
+            dataset,
+            &query.column,
+            query.k,
+            query.nprobes,
+            num_partitions,
+            is_graph_index(&statistics),
+            tuning,
+        )
+        .await?;
+        cache.recall.lock().unwrap().insert(key, params);
+        Ok(params)
+    }
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::types::Float32Type;
+    use arrow_array::{RecordBatch, RecordBatchIterator};
+    use arrow_schema::{Field as ArrowField, Schema as ArrowSchema};
+    use lance_arrow::FixedSizeListArrayExt;
+    use lance_index::vector::hnsw::builder::HnswBuildParams;
+    use lance_index::vector::ivf::IvfBuildParams;
+    use lance_index::vector::sq::builder::SQBuildParams;
+    use lance_index::{DatasetIndexExt, IndexType};
+    use lance_linalg::distance::MetricType;
+    use lance_testing::datagen::generate_random_array_with_seed;
+    use tempfile::tempdir;
+
+    use crate::index::vector::VectorIndexParams;
+
+    async fn create_dataset(uri: &str, hnsw: bool) -> Dataset {
+        const DIM: i32 = 16;
+        let values = generate_random_array_with_seed::<Float32Type>(1000 * DIM as usize, [7; 32]);
+        let vectors = arrow_array::FixedSizeListArray::try_new_from_values(values, DIM).unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "vec",
+            vectors.data_type().clone(),
+            false,
+        )]));
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let mut dataset = Dataset::write(reader, uri, None).await.unwrap();
+        let params = if hnsw {
+            VectorIndexParams::with_ivf_hnsw_sq_params(
+                MetricType::L2,
+                IvfBuildParams::new(1),
+                HnswBuildParams::default(),
+                SQBuildParams::default(),
+            )
+        } else {
+            VectorIndexParams::ivf_pq(4, 8, 2, MetricType::L2, 2)
+        };
+        dataset
+            .create_index(&["vec"], IndexType::Vector, None, &params, false)
+            .await
+            .unwrap();
+        dataset
+    }
+
+    #[tokio::test]
+    async fn test_tune_ef() {
+        let dir = tempdir().unwrap();
+        let dataset = create_dataset(dir.path().to_str().unwrap(), true).await;
+
+        let tuning = EfTuning {
+            target_recall: 0.9,
+            sample_size: 20,
+            ..Default::default()
+        };
+        let ef = tune_ef(&dataset, "vec", 10, 1, &tuning).await.unwrap();
+        assert!(ef >= 10);
+        assert!(ef <= tuning.max_ef);
+
+        // A target that can't be reached is capped.
+        let tuning = EfTuning {
+            target_recall: 1.0,
+            sample_size: 20,
+            max_ef: 10,
+        };
+        assert_eq!(tune_ef(&dataset, "vec", 10, 1, &tuning).await.unwrap(), 10);
+
+        let tuning = EfTuning {
+            target_recall: 1.5,
+            ..Default::default()
+        };
+        let err = tune_ef(&dataset, "vec", 10, 1, &tuning).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_auto_tune_ef_in_scanner() {
+        let dir = tempdir().unwrap();
+        let dataset = Arc::new(create_dataset(dir.path().to_str().unwrap(), true).await);
+        let key = Float32Array::from_iter_values((0..16).map(|v| v as f32 / 16.0));
+        let tuning = EfTuning {
+            target_recall: 0.9,
+            sample_size: 10,
+            ..Default::default()
+        };
+
+        let mut scanner = dataset.scan();
+        scanner
+            .nearest("vec", &key, 5)
+            .unwrap()
+            .auto_tune_ef(tuning)
+            .unwrap();
+        let batch = scanner.try_into_batch().await.unwrap();
+        assert_eq!(batch.num_rows(), 5);
+        let cached = dataset.session.tuning.ef.lock().unwrap().clone();
+        assert_eq!(cached.len(), 1);
+        assert!(cached.values().all(|ef| ef.is_some()));
+
+        // Indices without a graph are not tuned.
+        let dir = tempdir().unwrap();
+        let dataset = Arc::new(create_dataset(dir.path().to_str().unwrap(), false).await);
+        let mut scanner = dataset.scan();
+        scanner
+            .nearest("vec", &key, 5)
+            .unwrap()
+            .auto_tune_ef(tuning)
+            .unwrap();
+        assert_eq!(scanner.try_into_batch().await.unwrap().num_rows(), 5);
+        let cached = dataset.session.tuning.ef.lock().unwrap().clone();
+        assert!(cached.values().all(|ef| ef.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_tune_for_recall() {
+        let dir = tempdir().unwrap();
+        let dataset = create_dataset(dir.path().to_str().unwrap(), false).await;
+        let tuning = EfTuning {
+            target_recall: 0.9,
+            sample_size: 10,
+            ..Default::default()
+        };
+        let params = tune_for_recall(&dataset, "vec", 10, 1, 4, false, &tuning)
+            .await
+            .unwrap();
+        let fraction = params.probe_fraction.unwrap();
+        assert!(fraction > 0.0 && fraction <= 1.0);
+        assert_eq!(params.ef, None);
+
+        // Other samples may miss the target, so check the samples tuned on
+        let samples = Samples::try_new(&dataset, "vec", 10, 1, &tuning)
+            .await
+            .unwrap();
+        let params = tune_samples_for_recall(&samples, 4, false, &tuning)
+            .await
+            .unwrap();
+        assert!(samples.recall(&params).await.unwrap() >= 0.9);
+    }
+
+    #[tokio::test]
+    async fn test_target_recall_in_scanner() {
+        let dir = tempdir().unwrap();
+        let dataset = Arc::new(create_dataset(dir.path().to_str().unwrap(), false).await);
+        let key = Float32Array::from_iter_values((0..16).map(|v| v as f32 / 16.0));
+
+        let mut scanner = dataset.scan();
+        scanner.nearest("vec", &key, 5).unwrap();
+        assert!(scanner.target_recall(0.0).is_err());
+        scanner.target_recall(0.9).unwrap();
+        assert_eq!(scanner.try_into_batch().await.unwrap().num_rows(), 5);
+        let cached = dataset.session.tuning.recall.lock().unwrap().clone();
+        assert_eq!(cached.len(), 1);
+        assert!(cached
+            .values()
+            .all(|params| params.probe_fraction.is_some()));
+    }
+}
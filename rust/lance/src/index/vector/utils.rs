@@ -3,10 +3,11 @@
 
 use std::sync::Arc;
 
-use arrow_array::{cast::AsArray, FixedSizeListArray};
+use arrow_array::{cast::AsArray, Array, FixedSizeListArray, UInt32Array};
 use arrow_schema::Schema as ArrowSchema;
-use arrow_select::concat::concat_batches;
+use arrow_select::{concat::concat_batches, take::take};
 use futures::stream::TryStreamExt;
+use lance_index::vector::transform::finite_vector_indices;
 use snafu::{location, Location};
 
 use crate::dataset::Dataset;
@@ -63,5 +64,27 @@ pub async fn maybe_sample_training_data(
         ),
         location: location!(),
     })?;
-    Ok(array.as_fixed_size_list().clone())
+    let array = array.as_fixed_size_list();
+    // Null and non-finite vectors are never indexed, so they aren't trained on.
+    let valid = finite_vector_indices(array);
+    if valid.len() < array.len() {
+        let array = take(array, &UInt32Array::from(valid), None)?;
+        Ok(array.as_fixed_size_list().clone())
+    } else {
+        Ok(array.clone())
+    }
+}
+
+/// Count the rows of the column whose vector is null, or is not finite.
+pub async fn count_invalid_vectors(dataset: &Dataset, column: &str) -> Result<usize> {
+    let mut scanner = dataset.scan();
+    scanner.project(&[column])?;
+    scanner
+        .try_into_stream()
+        .await?
+        .try_fold(0, |count, batch| async move {
+            let vectors = batch[column].as_fixed_size_list();
+            Ok(count + vectors.len() - finite_vector_indices(vectors).len())
+        })
+        .await
 }
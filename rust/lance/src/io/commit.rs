@@ -24,7 +24,9 @@
 
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Instant;
 
+use arrow_schema::DataType;
 use lance_table::format::{pb, DeletionFile, Fragment, Index, Manifest, WriterVersion};
 use lance_table::io::commit::{CommitConfig, CommitError, CommitHandler};
 use lance_table::io::deletion::read_deletion_file;
@@ -36,12 +38,15 @@ use lance_core::{Error, Result};
 use lance_index::DatasetIndexExt;
 use object_store::path::Path;
 use prost::Message;
+use tracing::{debug, info, instrument, warn, Span};
 
 use super::ObjectStore;
+use crate::dataset::config::COMMIT_NUM_RETRIES;
 use crate::dataset::fragment::FileFragment;
 use crate::dataset::transaction::{Operation, Transaction};
 use crate::dataset::{write_manifest_file, ManifestWriteConfig};
 use crate::index::DatasetIndexInternalExt;
+use crate::metrics;
 use crate::Dataset;
 
 #[cfg(all(target_feature = "dynamodb", test))]
@@ -94,16 +99,21 @@ fn check_transaction(
         });
     }
 
-    if transaction.conflicts_with(other_transaction.as_ref().unwrap()) {
+    if let Some(conflict) =
+        transaction.conflict_details(other_version, other_transaction.as_ref().unwrap())
+    {
+        info!(
+            operation = transaction.operation.name(),
+            read_version = transaction.read_version,
+            other_version,
+            other_operation = conflict.other_operation(),
+            fragment_ids = ?conflict.fragment_ids,
+            field_ids = ?conflict.field_ids,
+            "transaction conflicts with concurrent commit"
+        );
         return Err(crate::Error::CommitConflict {
             version: other_version,
-            source: format!(
-                "There was a concurrent commit that conflicts with this one and it \
-                cannot be automatically resolved. Please rerun the operation off the latest version \
-                of the table.\n Transaction: {:?}\n Conflicting Transaction: {:?}",
-                transaction, other_transaction
-            )
-            .into(),
+            source: Box::new(conflict),
             location: location!(),
         });
     }
@@ -111,6 +121,7 @@ fn check_transaction(
     Ok(())
 }
 
+#[instrument(level = "debug", skip_all, fields(operation = transaction.operation.name()))]
 pub(crate) async fn commit_new_dataset(
     object_store: &ObjectStore,
     commit_handler: &dyn CommitHandler,
@@ -200,12 +211,20 @@ fn fix_schema(manifest: &mut Manifest) -> Result<()> {
     }
 
     // First, see which, if any fields have duplicate ids, within any fragment.
+    // Structs are exempt: their fields can be written to different data files,
+    // each of which holds the struct too.
+    let struct_ids = manifest
+        .schema
+        .fields_pre_order()
+        .filter(|f| matches!(f.data_type(), DataType::Struct(_)))
+        .map(|f| f.id)
+        .collect::<HashSet<_>>();
     let mut fields_with_duplicate_ids = HashSet::new();
     let mut seen_fields = HashSet::new();
     for fragment in manifest.fragments.iter() {
         for file in fragment.files.iter() {
             for field_id in file.fields.iter() {
-                if !seen_fields.insert(*field_id) {
+                if !struct_ids.contains(field_id) && !seen_fields.insert(*field_id) {
                     fields_with_duplicate_ids.insert(*field_id);
                 }
             }
@@ -362,13 +381,43 @@ async fn migrate_indices(dataset: &Dataset, indices: &mut [Index]) -> Result<()>
 }
 
 /// Attempt to commit a transaction, with retries and conflict resolution.
+#[instrument(level = "debug", skip_all, fields(
+    operation = transaction.operation.name(),
+    read_version = transaction.read_version,
+    version = tracing::field::Empty,
+))]
 pub(crate) async fn commit_transaction(
     dataset: &Dataset,
     object_store: &ObjectStore,
     commit_handler: &dyn CommitHandler,
     transaction: &Transaction,
     write_config: &ManifestWriteConfig,
-    commit_config: &CommitConfig,
+    commit_config: &Option<CommitConfig>,
+) -> Result<Manifest> {
+    let started = Instant::now();
+    let result = do_commit_transaction(
+        dataset,
+        object_store,
+        commit_handler,
+        transaction,
+        write_config,
+        commit_config,
+    )
+    .await;
+    metrics::record_commit(transaction.operation.name(), started.elapsed(), &result);
+    if let Ok(manifest) = &result {
+        Span::current().record("version", manifest.version);
+    }
+    result
+}
+
+async fn do_commit_transaction(
+    dataset: &Dataset,
+    object_store: &ObjectStore,
+    commit_handler: &dyn CommitHandler,
+    transaction: &Transaction,
+    write_config: &ManifestWriteConfig,
+    commit_config: &Option<CommitConfig>,
 ) -> Result<Manifest> {
     // Note: object_store has been configured with WriteParams, but dataset.object_store()
     // has not necessarily. So for anything involving writing, use `object_store`.
@@ -400,6 +449,11 @@ pub(crate) async fn commit_transaction(
     }
 
     let mut target_version = version;
+    debug!(
+        num_concurrent = other_transactions.len(),
+        latest_version = dataset.manifest.version,
+        "checking concurrent transactions for conflicts"
+    );
 
     // If any of them conflict with the transaction, return an error
     for (version_offset, other_transaction) in other_transactions.iter().enumerate() {
@@ -407,7 +461,14 @@ pub(crate) async fn commit_transaction(
         check_transaction(transaction, other_version, other_transaction)?;
     }
 
-    for _ in 0..commit_config.num_retries {
+    // Unless the commit says otherwise, the dataset may configure how many
+    // times its commits are attempted. Versions that stored 0 are still given
+    // an attempt, so that the setting can be changed.
+    let num_retries = match commit_config {
+        Some(commit_config) => commit_config.num_retries,
+        None => dataset.config().get(&COMMIT_NUM_RETRIES)?.max(1),
+    };
+    for attempt in 0..num_retries {
         // Build an up-to-date manifest from the transaction and current manifest
         let (mut manifest, mut indices) = match transaction.operation {
             Operation::Restore { version } => {
@@ -445,6 +506,7 @@ pub(crate) async fn commit_transaction(
         migrate_indices(&dataset, &mut indices).await?;
 
         // Try to commit the manifest
+        debug!(version = target_version, attempt, "writing manifest");
         let result = write_manifest_file(
             object_store,
             commit_handler,
@@ -461,10 +523,16 @@ pub(crate) async fn commit_transaction(
 
         match result {
             Ok(()) => {
+                debug!(version = target_version, attempt, "committed manifest");
                 return Ok(manifest);
             }
             Err(CommitError::CommitConflict) => {
                 // See if we can retry the commit
+                info!(
+                    version = target_version,
+                    attempt, "version was committed concurrently, retrying"
+                );
+                metrics::record_commit_retry(transaction.operation.name());
                 dataset = dataset.checkout_version(target_version).await?;
 
                 let other_transaction =
@@ -483,11 +551,15 @@ pub(crate) async fn commit_transaction(
         }
     }
 
+    warn!(
+        version = target_version,
+        num_retries, "giving up on commit after exhausting retries"
+    );
     Err(crate::Error::CommitConflict {
         version: target_version,
         source: format!(
             "Failed to commit the transaction after {} retries.",
-            commit_config.num_retries
+            num_retries
         )
         .into(),
         location: location!(),
@@ -513,6 +585,7 @@ mod tests {
 
     use super::*;
 
+    use crate::dataset::transaction::TransactionConflict;
     use crate::dataset::{WriteMode, WriteParams};
     use crate::index::vector::VectorIndexParams;
 
@@ -833,6 +906,184 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_conflict_details() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let mut dataset = Dataset::write(reader, test_uri, None).await.unwrap();
+
+        // Two deletes of the same fragment conflict.
+        let mut stale = dataset.clone();
+        dataset.delete("i = 1").await.unwrap();
+        let err = stale.delete("i = 2").await.unwrap_err();
+        let conflict = TransactionConflict::from_error(&err).unwrap();
+        assert_eq!(conflict.version, 2);
+        assert_eq!(conflict.operation, "Delete");
+        assert_eq!(conflict.other_operation(), "Delete");
+        assert_eq!(conflict.fragment_ids, vec![0]);
+        assert!(conflict.field_ids.is_empty());
+
+        // The conflicting transaction is the one committed at that version.
+        let other = dataset
+            .checkout_version(conflict.version)
+            .await
+            .unwrap()
+            .read_transaction()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(other.uuid, conflict.other_transaction.uuid);
+    }
+
+    /// Records the names and fields of spans and the messages of events.
+    #[derive(Clone, Default)]
+    struct TraceRecorder {
+        records: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct FieldVisitor<'a>(&'a mut String);
+
+    impl tracing::field::Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for TraceRecorder {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            _id: &tracing::span::Id,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut record = format!("span {}", attrs.metadata().name());
+            attrs.record(&mut FieldVisitor(&mut record));
+            self.records.lock().unwrap().push(record);
+        }
+
+        fn on_record(
+            &self,
+            _id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut record = "record".to_string();
+            values.record(&mut FieldVisitor(&mut record));
+            self.records.lock().unwrap().push(record);
+        }
+
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut record = "event".to_string();
+            event.record(&mut FieldVisitor(&mut record));
+            self.records.lock().unwrap().push(record);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_commit_tracing() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let recorder = TraceRecorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let dataset = Dataset::write(reader, test_uri, None).await.unwrap();
+
+        // Append from a stale copy of version 1 after another writer committed
+        // version 2, so the commit has to check version 2 for conflicts and
+        // lands at version 3.
+        let mut stale = dataset.clone();
+        let params = WriteParams {
+            mode: WriteMode::Append,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        Dataset::write(reader, test_uri, Some(params.clone()))
+            .await
+            .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        stale.append(reader, Some(params)).await.unwrap();
+        assert_eq!(stale.version().version, 3);
+
+        // A stale delete conflicts with the appends.
+        let mut stale = dataset.clone();
+        assert!(stale.delete("i = 1").await.is_err());
+
+        let records = recorder.records.lock().unwrap().clone();
+        let has = |parts: &[&str]| {
+            records
+                .iter()
+                .any(|r| parts.iter().all(|part| r.contains(part)))
+        };
+        assert!(
+            has(&["span commit_new_dataset", "operation=\"Overwrite\""]),
+            "{:?}",
+            records
+        );
+        assert!(
+            has(&[
+                "span commit_transaction",
+                "operation=\"Append\"",
+                "read_version=1"
+            ]),
+            "{:?}",
+            records
+        );
+        assert!(has(&["record version=3"]), "{:?}", records);
+        assert!(
+            has(&["span write_manifest_file", "version=3"]),
+            "{:?}",
+            records
+        );
+        assert!(
+            has(&[
+                "checking concurrent transactions for conflicts",
+                "num_concurrent=1"
+            ]),
+            "{:?}",
+            records
+        );
+        assert!(has(&["committed manifest", "version=3"]), "{:?}", records);
+        assert!(
+            has(&[
+                "transaction conflicts with concurrent commit",
+                "operation=\"Delete\"",
+                "other_version=2",
+                "other_operation=\"Append\""
+            ]),
+            "{:?}",
+            records
+        );
+    }
+
     #[test]
     fn test_fix_schema() {
         // Manifest has a fragment with no fields in use
@@ -858,6 +1109,8 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: None,
+                partition: None,
+                delta: false,
             },
             Fragment {
                 id: 1,
@@ -868,6 +1121,8 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: None,
+                partition: None,
+                delta: false,
             },
         ];
 
@@ -894,6 +1149,8 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: None,
+                partition: None,
+                delta: false,
             },
             Fragment {
                 id: 1,
@@ -904,6 +1161,8 @@ mod tests {
                 deletion_file: None,
                 row_id_meta: None,
                 physical_rows: None,
+                partition: None,
+                delta: false,
             },
         ];
         assert_eq!(manifest.fragments.as_ref(), &expected_fragments);
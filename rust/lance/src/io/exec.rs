@@ -5,7 +5,9 @@
 //!
 //! WARNING: Internal API with no stability guarantees.
 
+mod explain;
 pub(crate) mod knn;
+mod metrics;
 mod optimizer;
 mod planner;
 mod projection;
@@ -17,9 +19,13 @@ mod take;
 pub mod testing;
 pub mod utils;
 
-pub use knn::{ANNIvfPartitionExec, ANNIvfSubIndexExec, KNNFlatExec, PreFilterSource};
+pub use explain::{explain_plan, ExplainFormat, ExplainNode};
+pub use knn::{
+    ANNIvfPartitionExec, ANNIvfSubIndexExec, KNNFlatExec, PreFilterSource, SearchExplain,
+};
+pub use metrics::PlanMetrics;
 pub use planner::{FilterPlan, Planner};
 pub use projection::ProjectionExec;
 pub use pushdown_scan::{LancePushdownScanExec, ScanConfig};
 pub use scan::LanceScanExec;
-pub use take::TakeExec;
+pub use take::{DatasetRowReader, TakeExec};
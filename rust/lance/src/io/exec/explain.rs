@@ -0,0 +1,169 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use std::fmt::Write;
+
+use datafusion::physical_plan::{display::DisplayableExecutionPlan, ExecutionPlan};
+use serde::{Deserialize, Serialize};
+
+use super::scalar_index::{MapIndexExec, MaterializeIndexExec, ScalarIndexExec};
+use super::{
+    ANNIvfPartitionExec, ANNIvfSubIndexExec, KNNFlatExec, LancePushdownScanExec, LanceScanExec,
+};
+use crate::Result;
+
+/// How [`explain_plan`] renders a plan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplainFormat {
+    /// The indented text of `EXPLAIN`.
+    Text { verbose: bool },
+    /// A tree of [`ExplainNode`]s, as JSON.
+    Json,
+    /// A graph in the DOT language of graphviz, with an edge from each node
+    /// to the node it feeds.
+    Graphviz,
+}
+
+/// A node of a plan, and its children.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExplainNode {
+    /// The name of the node, such as `LanceScan` or `FilterExec`.
+    pub name: String,
+    /// The node as displayed by `explain_plan`.
+    pub description: String,
+    /// What Lance nodes do, such as the fragments they scan or the indices
+    /// they search, as pairs of a name and a value.
+    pub details: Vec<(String, String)>,
+    pub children: Vec<Self>,
+}
+
+impl ExplainNode {
+    /// Describe `plan` and its children.
+    pub fn from_plan(plan: &dyn ExecutionPlan) -> Self {
+        let description = DisplayableExecutionPlan::new(plan)
+            .one_line()
+            .to_string()
+            .trim_end()
+            .to_string();
+        let name = description
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        Self {
+            name,
+            description,
+            details: lance_details(plan),
+            children: plan
+                .children()
+                .iter()
+                .map(|child| Self::from_plan(child.as_ref()))
+                .collect(),
+        }
+    }
+
+    fn write_dot(&self, out: &mut String, next_id: &mut usize) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+        let mut label = escape_dot(&self.description);
+        for (name, value) in &self.details {
+            label.push_str(&format!("\\n{}={}", escape_dot(name), escape_dot(value)));
+        }
+        writeln!(out, "  node{} [label=\"{}\"];", id, label).unwrap();
+        for child in &self.children {
+            let child_id = child.write_dot(out, next_id);
+            writeln!(out, "  node{} -> node{};", child_id, id).unwrap();
+        }
+        id
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn fragment_ids(fragments: &[lance_table::format::Fragment]) -> String {
+    fragments
+        .iter()
+        .map(|fragment| fragment.id.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The details of the nodes Lance plans, empty for other nodes.
+fn lance_details(plan: &dyn ExecutionPlan) -> Vec<(String, String)> {
+    let any = plan.as_any();
+    let details = if let Some(scan) = any.downcast_ref::<LanceScanExec>() {
+        vec![
+            ("fragments", scan.fragments().len().to_string()),
+            ("fragment_ids", fragment_ids(scan.fragments())),
+        ]
+    } else if let Some(scan) = any.downcast_ref::<LancePushdownScanExec>() {
+        vec![
+            ("fragments", scan.fragments().len().to_string()),
+            ("fragment_ids", fragment_ids(scan.fragments())),
+        ]
+    } else if let Some(ann) = any.downcast_ref::<ANNIvfPartitionExec>() {
+        let query = ann.query();
+        vec![
+            ("column", query.column.clone()),
+            ("index_uuids", ann.index_uuids().join(",")),
+            ("nprobes", query.nprobes.to_string()),
+            ("metric", query.metric_type.to_string()),
+        ]
+    } else if let Some(ann) = any.downcast_ref::<ANNIvfSubIndexExec>() {
+        let query = ann.query();
+        let names = ann
+            .indices()
+            .iter()
+            .map(|index| index.name.clone())
+            .collect::<Vec<_>>();
+        let mut details = vec![
+            ("column", query.column.clone()),
+            ("index", names.join(",")),
+            ("k", query.k.to_string()),
+            ("nprobes", query.nprobes.to_string()),
+        ];
+        if let Some(refine_factor) = query.refine_factor {
+            details.push(("refine_factor", refine_factor.to_string()));
+        }
+        details
+    } else if let Some(knn) = any.downcast_ref::<KNNFlatExec>() {
+        vec![
+            ("column", knn.query.column.clone()),
+            ("k", knn.query.k.to_string()),
+            ("metric", knn.query.metric_type.to_string()),
+        ]
+    } else if let Some(index) = any.downcast_ref::<ScalarIndexExec>() {
+        vec![("index_query", index.expr().to_string())]
+    } else if let Some(index) = any.downcast_ref::<MaterializeIndexExec>() {
+        vec![
+            ("index_query", index.expr().to_string()),
+            ("fragments", index.fragments().len().to_string()),
+        ]
+    } else if let Some(index) = any.downcast_ref::<MapIndexExec>() {
+        vec![("index_column", index.column_name().to_string())]
+    } else {
+        vec![]
+    };
+    details
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+}
+
+/// Render `plan` in `format`, with the details of the nodes Lance plans.
+pub fn explain_plan(plan: &dyn ExecutionPlan, format: ExplainFormat) -> Result<String> {
+    match format {
+        ExplainFormat::Text { verbose } => Ok(DisplayableExecutionPlan::new(plan)
+            .indent(verbose)
+            .to_string()),
+        ExplainFormat::Json => Ok(serde_json::to_string_pretty(&ExplainNode::from_plan(plan))?),
+        ExplainFormat::Graphviz => {
+            let mut out = String::from("digraph LancePlan {\n  node [shape=box];\n");
+            ExplainNode::from_plan(plan).write_dot(&mut out, &mut 0);
+            out.push_str("}\n");
+            Ok(out)
+        }
+    }
+}
@@ -3,7 +3,8 @@
 
 use std::any::Any;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 
 use arrow::datatypes::UInt32Type;
@@ -33,7 +34,6 @@ use lance_io::stream::RecordBatchStream;
 use lance_linalg::distance::DistanceType;
 use lance_linalg::kernels::normalize_arrow;
 use lance_table::format::Index;
-use log::warn;
 use snafu::{location, Location};
 use tokio::sync::mpsc::Receiver;
 use tokio::task::JoinHandle;
@@ -238,9 +238,17 @@ impl ExecutionPlan for KNNFlatExec {
 
     fn with_new_children(
         self: Arc<Self>,
-        _children: Vec<Arc<dyn ExecutionPlan>>,
+        mut children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
-        Ok(self)
+        if children.len() != 1 {
+            return Err(DataFusionError::Internal(
+                "KNNFlatExec node must have exactly one child".to_string(),
+            ));
+        }
+        Ok(Arc::new(Self::try_new(
+            children.pop().expect("length checked"),
+            self.query.clone(),
+        )?))
     }
 
     fn execute(
@@ -360,6 +368,74 @@ pub fn new_knn_exec(
     Ok(Arc::new(sub_index))
 }
 
+/// What the vector search of a query did, see
+/// [`crate::dataset::scanner::Scanner::explain_search`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchExplain {
+    /// The IVF partitions probed in each delta index, by index uuid.
+    pub partitions: Vec<(String, Vec<u32>)>,
+    /// Number of indexed rows in the probed partitions.
+    pub candidates: u64,
+    /// Number of results re-ranked with the original vectors by the refine step.
+    pub reranked: u64,
+    /// Partitions searched from the index cache.
+    pub cache_hits: u64,
+    /// Partitions loaded from storage.
+    pub cache_misses: u64,
+}
+
+impl SearchExplain {
+    /// Collect what the vector search nodes of an executed `plan` did.
+    pub fn from_plan(plan: &dyn ExecutionPlan) -> Self {
+        let mut explain = Self::default();
+        explain.collect(plan);
+        explain
+    }
+
+    fn collect(&mut self, plan: &dyn ExecutionPlan) {
+        if let Some(node) = plan.as_any().downcast_ref::<ANNIvfPartitionExec>() {
+            let stats = node.stats();
+            self.partitions
+                .extend(stats.probed.lock().unwrap().iter().cloned());
+            self.candidates += stats.candidates.load(Ordering::Relaxed);
+        } else if let Some(node) = plan.as_any().downcast_ref::<ANNIvfSubIndexExec>() {
+            let stats = node.stats();
+            self.cache_hits += stats.cache_hits.load(Ordering::Relaxed);
+            self.cache_misses += stats.cache_misses.load(Ordering::Relaxed);
+            if let Some(factor) = node.query.refine_factor {
+                // Only the closest results of the partitions reach the refine step.
+                let limit = (node.query.k * factor as usize) as u64;
+                self.reranked += stats.rows_returned.load(Ordering::Relaxed).min(limit);
+            }
+        }
+        for child in plan.children() {
+            self.collect(child.as_ref());
+        }
+    }
+}
+
+impl std::fmt::Display for SearchExplain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (uuid, partitions) in &self.partitions {
+            writeln!(f, "index={}, partitions={:?}", uuid, partitions)?;
+        }
+        write!(
+            f,
+            "candidates={}, reranked={}, cache_hits={}, cache_misses={}",
+            self.candidates, self.reranked, self.cache_hits, self.cache_misses
+        )
+    }
+}
+
+/// What an [`ANNIvfPartitionExec`] found while executing.
+#[derive(Debug, Default)]
+pub struct IvfPartitionStats {
+    /// The partitions probed in each delta index, by index uuid.
+    pub probed: Mutex<Vec<(String, Vec<u32>)>>,
+    /// Number of rows in the probed partitions.
+    pub candidates: AtomicU64,
+}
+
 /// [ExecutionPlan] to execute the find the closest IVF partitions.
 ///
 /// It searches the partition IDs using the input query.
@@ -385,6 +461,8 @@ pub struct ANNIvfPartitionExec {
     index_uuids: Vec<String>,
 
     properties: PlanProperties,
+
+    stats: Arc<IvfPartitionStats>,
 }
 
 impl ANNIvfPartitionExec {
@@ -410,8 +488,24 @@ impl ANNIvfPartitionExec {
             query,
             index_uuids,
             properties,
+            stats: Arc::default(),
         })
     }
+
+    /// What the node found, once executed.
+    pub fn stats(&self) -> &IvfPartitionStats {
+        &self.stats
+    }
+
+    /// The vector query.
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// The UUIDs of the indices searched.
+    pub fn index_uuids(&self) -> &[String] {
+        &self.index_uuids
+    }
 }
 
 impl DisplayAs for ANNIvfPartitionExec {
@@ -456,9 +550,13 @@ impl ExecutionPlan for ANNIvfPartitionExec {
 
     fn with_new_children(
         self: Arc<Self>,
-        _children: Vec<Arc<dyn ExecutionPlan>>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
-        warn!("ANNIVFPartitionExec: with_new_children called, but no children to replace");
+        if !children.is_empty() {
+            return Err(DataFusionError::Internal(
+                "ANNIVFPartitionExec node does not accept children".to_string(),
+            ));
+        }
         Ok(self)
     }
 
@@ -469,11 +567,13 @@ impl ExecutionPlan for ANNIvfPartitionExec {
     ) -> DataFusionResult<SendableRecordBatchStream> {
         let query = self.query.clone();
         let ds = self.dataset.clone();
+        let stats = self.stats.clone();
 
         let stream = stream::iter(self.index_uuids.clone())
             .map(move |uuid| {
                 let query = query.clone();
                 let ds = ds.clone();
+                let stats = stats.clone();
 
                 async move {
                     let raw_index = ds.open_vector_index(&query.column, &uuid).await?;
@@ -493,6 +593,19 @@ impl ExecutionPlan for ANNIvfPartitionExec {
                         DataFusionError::Execution(format!("Failed to find partitions: {}", e))
                     })?;
 
+                    let lengths = index.partition_lengths();
+                    let candidates = partitions
+                        .values()
+                        .iter()
+                        .map(|&part_id| lengths[part_id as usize] as u64)
+                        .sum::<u64>();
+                    stats.candidates.fetch_add(candidates, Ordering::Relaxed);
+                    stats
+                        .probed
+                        .lock()
+                        .unwrap()
+                        .push((uuid.clone(), partitions.values().to_vec()));
+
                     let mut list_builder = ListBuilder::new(UInt32Builder::new())
                         .with_field(Field::new("item", DataType::UInt32, false));
                     list_builder.append_value(partitions.iter());
@@ -514,6 +627,17 @@ impl ExecutionPlan for ANNIvfPartitionExec {
     }
 }
 
+/// What an [`ANNIvfSubIndexExec`] did while executing.
+#[derive(Debug, Default)]
+pub struct IvfSubIndexStats {
+    /// Partitions searched from the index cache.
+    pub cache_hits: AtomicU64,
+    /// Partitions loaded from storage.
+    pub cache_misses: AtomicU64,
+    /// Number of results returned by the partition searches.
+    pub rows_returned: AtomicU64,
+}
+
 /// Datafusion [ExecutionPlan] to run search on IVF partitions.
 ///
 /// A IVF-{PQ/SQ/HNSW} query plan is:
@@ -539,6 +663,8 @@ pub struct ANNIvfSubIndexExec {
 
     /// Datafusion Plan Properties
     properties: PlanProperties,
+
+    stats: Arc<IvfSubIndexStats>,
 }
 
 impl ANNIvfSubIndexExec {
@@ -570,8 +696,24 @@ impl ANNIvfSubIndexExec {
             query,
             prefilter_source,
             properties,
+            stats: Arc::default(),
         })
     }
+
+    /// What the node did, once executed.
+    pub fn stats(&self) -> &IvfSubIndexStats {
+        &self.stats
+    }
+
+    /// The vector query.
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// The indices searched, one per delta.
+    pub fn indices(&self) -> &[Index] {
+        &self.indices
+    }
 }
 
 impl DisplayAs for ANNIvfSubIndexExec {
@@ -609,9 +751,36 @@ impl ExecutionPlan for ANNIvfSubIndexExec {
 
     fn with_new_children(
         self: Arc<Self>,
-        _children: Vec<Arc<dyn ExecutionPlan>>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
-        Ok(self)
+        let mut children = children.into_iter();
+        let (Some(input), prefilter, None) = (children.next(), children.next(), children.next())
+        else {
+            return Err(DataFusionError::Internal(
+                "ANNSubIndexExec node must have one or two children".to_string(),
+            ));
+        };
+        // The second child, if any, is the source of the prefilter
+        let prefilter_source = match (&self.prefilter_source, prefilter) {
+            (PreFilterSource::None, None) => PreFilterSource::None,
+            (PreFilterSource::FilteredRowIds(_), Some(src)) => PreFilterSource::FilteredRowIds(src),
+            (PreFilterSource::ScalarIndexQuery(_), Some(src)) => {
+                PreFilterSource::ScalarIndexQuery(src)
+            }
+            _ => {
+                return Err(DataFusionError::Internal(
+                    "ANNSubIndexExec node must have a child for its prefilter and no other"
+                        .to_string(),
+                ))
+            }
+        };
+        Ok(Arc::new(Self::try_new(
+            input,
+            self.dataset.clone(),
+            self.indices.clone(),
+            self.query.clone(),
+            prefilter_source,
+        )?))
     }
 
     fn execute(
@@ -627,6 +796,7 @@ impl ExecutionPlan for ANNIvfSubIndexExec {
         let column = self.query.column.clone();
         let indices = self.indices.clone();
         let prefilter_source = self.prefilter_source.clone();
+        let stats = self.stats.clone();
 
         // Per-delta-index stream:
         //   Stream<(parttitions, index uuid)>
@@ -704,6 +874,7 @@ impl ExecutionPlan for ANNIvfSubIndexExec {
                 .try_flatten()
                 .map(move |result| {
                     let query = query.clone();
+                    let stats = stats.clone();
                     async move {
                         let (part_id, (raw_index, pre_filter)) = result?;
 
@@ -719,7 +890,12 @@ impl ExecutionPlan for ANNIvfSubIndexExec {
                             query.key = key;
                         };
 
-                        index
+                        if index.is_partition_cached(part_id as usize) {
+                            stats.cache_hits.fetch_add(1, Ordering::Relaxed);
+                        } else {
+                            stats.cache_misses.fetch_add(1, Ordering::Relaxed);
+                        }
+                        let batch = index
                             .search_in_partition(part_id as usize, &query, pre_filter)
                             .map_err(|e| {
                                 DataFusionError::Execution(format!(
@@ -727,7 +903,11 @@ impl ExecutionPlan for ANNIvfSubIndexExec {
                                     e
                                 ))
                             })
-                            .await
+                            .await?;
+                        stats
+                            .rows_returned
+                            .fetch_add(batch.num_rows() as u64, Ordering::Relaxed);
+                        Ok(batch)
                     }
                 })
                 .buffered(num_cpus::get())
@@ -841,6 +1021,7 @@ mod tests {
                 key: q,
                 k: 10,
                 nprobes: 0,
+                probe_fraction: None,
                 ef: None,
                 refine_factor: None,
                 metric_type: MetricType::L2,
@@ -875,13 +1056,14 @@ mod tests {
             key: Arc::new(generate_random_array(dim)),
             k: 10,
             nprobes: 0,
+            probe_fraction: None,
             ef: None,
             refine_factor: None,
             metric_type: MetricType::L2,
             use_index: false,
         };
 
-        let input: Arc<dyn ExecutionPlan> = Arc::new(TestingExec::new(vec![batch]));
+        let input: Arc<dyn ExecutionPlan> = Arc::new(TestingExec::new(vec![batch.clone()]));
         let idx = KNNFlatExec::try_new(input, query).unwrap();
         println!("{:?}", idx);
         assert_eq!(
@@ -900,5 +1082,18 @@ mod tests {
                 ArrowField::new(DIST_COL, DataType::Float32, true),
             ])
         );
+
+        // The node is rebuilt on the children it is given
+        let idx: Arc<dyn ExecutionPlan> = Arc::new(idx);
+        let new_input: Arc<dyn ExecutionPlan> = Arc::new(TestingExec::new(vec![batch]));
+        let rebuilt = idx
+            .clone()
+            .with_new_children(vec![new_input.clone()])
+            .unwrap();
+        assert!(Arc::ptr_eq(&rebuilt.children()[0], &new_input));
+        assert_eq!(rebuilt.schema(), idx.schema());
+        assert!(idx
+            .with_new_children(vec![new_input.clone(), new_input])
+            .is_err());
     }
 }
@@ -0,0 +1,70 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+use datafusion::physical_plan::{display::DisplayableExecutionPlan, ExecutionPlan};
+use serde::{Deserialize, Serialize};
+
+/// The metrics of a node of an executed plan, and of its children.
+///
+/// Metrics a node doesn't record are `None`. Metrics of nodes with several
+/// partitions are summed over the partitions.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanMetrics {
+    /// The name of the node, such as `LanceScan` or `FilterExec`.
+    pub name: String,
+    /// The node as displayed by `explain_plan`.
+    pub description: String,
+    /// Number of rows the node produced.
+    pub output_rows: Option<usize>,
+    /// CPU time the node spent, in nanoseconds.
+    pub elapsed_compute_nanos: Option<usize>,
+    /// In-memory size of the batches the node decoded from storage. This
+    /// is not the number of bytes read from storage.
+    pub decoded_bytes: Option<usize>,
+    /// Number of times the node spilled to disk.
+    pub spill_count: Option<usize>,
+    /// Number of bytes the node spilled to disk.
+    pub spilled_bytes: Option<usize>,
+    /// The most memory the execution reserved at once. Only recorded for the
+    /// root node of plans run by `Scanner::analyze_plan`.
+    pub peak_memory_bytes: Option<usize>,
+    /// Number of memory reservations the execution was refused. Only
+    /// recorded with `peak_memory_bytes`.
+    pub reservation_failures: Option<usize>,
+    pub children: Vec<Self>,
+}
+
+impl PlanMetrics {
+    /// Collect the metrics of an executed `plan`.
+    pub fn from_plan(plan: &dyn ExecutionPlan) -> Self {
+        let description = DisplayableExecutionPlan::new(plan)
+            .one_line()
+            .to_string()
+            .trim_end()
+            .to_string();
+        let name = description
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let mut metrics = Self {
+            name,
+            description,
+            children: plan
+                .children()
+                .iter()
+                .map(|child| Self::from_plan(child.as_ref()))
+                .collect(),
+            ..Default::default()
+        };
+        if let Some(set) = plan.metrics() {
+            let set = set.aggregate_by_name();
+            metrics.output_rows = set.output_rows();
+            metrics.elapsed_compute_nanos = set.elapsed_compute();
+            metrics.decoded_bytes = set.sum_by_name("decoded_bytes").map(|v| v.as_usize());
+            metrics.spill_count = set.spill_count();
+            metrics.spilled_bytes = set.spilled_bytes();
+        }
+        metrics
+    }
+}
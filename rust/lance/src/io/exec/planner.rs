@@ -6,10 +6,14 @@
 use std::collections::{BTreeSet, VecDeque};
 use std::sync::Arc;
 
+use arrow::compute::kernels::cast_utils::parse_interval_month_day_nano;
 use arrow::compute::CastOptions;
 use arrow_array::ListArray;
 use arrow_buffer::OffsetBuffer;
-use arrow_schema::{DataType as ArrowDataType, Field, SchemaRef, TimeUnit};
+use arrow_schema::{
+    DataType as ArrowDataType, Field, IntervalUnit, SchemaRef, TimeUnit, DECIMAL128_MAX_PRECISION,
+    DECIMAL256_MAX_PRECISION,
+};
 use arrow_select::concat::concat;
 use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion, TreeNodeVisitor};
 use datafusion::common::DFSchema;
@@ -28,7 +32,7 @@ use datafusion::physical_optimizer::optimizer::PhysicalOptimizer;
 use datafusion::sql::planner::{ContextProvider, ParserOptions, PlannerContext, SqlToRel};
 use datafusion::sql::sqlparser::ast::{
     Array as SQLArray, BinaryOperator, DataType as SQLDataType, ExactNumberInfo, Expr as SQLExpr,
-    Function, FunctionArg, FunctionArgExpr, Ident, TimezoneInfo, UnaryOperator, Value,
+    Function, FunctionArg, FunctionArgExpr, Ident, Interval, TimezoneInfo, UnaryOperator, Value,
 };
 use datafusion::{
     common::Column,
@@ -40,6 +44,7 @@ use datafusion::{
 };
 use datafusion_functions::core::getfield::GetFieldFunc;
 use lance_arrow::cast::cast_with_options;
+use lance_arrow::string::{lowercase, within_edit_distance};
 use lance_datafusion::expr::safe_coerce_scalar;
 use lance_index::scalar::expression::{
     apply_scalar_indices, IndexInformationProvider, ScalarIndexExpr,
@@ -173,6 +178,142 @@ impl ScalarUDFImpl for CastListF16Udf {
     }
 }
 
+/// `lower`, on the vectorized kernel of lance_arrow
+///
+/// Filters on the lowercase of a column can use its lowercase scalar index.
+#[derive(Debug, Clone)]
+struct LowercaseUdf {
+    signature: Signature,
+}
+
+impl LowercaseUdf {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::uniform(
+                1,
+                vec![ArrowDataType::Utf8, ArrowDataType::LargeUtf8],
+                Volatility::Immutable,
+            ),
+        }
+    }
+}
+
+impl ScalarUDFImpl for LowercaseUdf {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "lower"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[ArrowDataType]) -> DFResult<ArrowDataType> {
+        Ok(arg_types[0].clone())
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> DFResult<ColumnarValue> {
+        match &args[0] {
+            ColumnarValue::Array(arr) => Ok(ColumnarValue::Array(lowercase(arr.as_ref())?)),
+            ColumnarValue::Scalar(ScalarValue::Utf8(value)) => Ok(ColumnarValue::Scalar(
+                ScalarValue::Utf8(value.as_deref().map(str::to_lowercase)),
+            )),
+            ColumnarValue::Scalar(ScalarValue::LargeUtf8(value)) => Ok(ColumnarValue::Scalar(
+                ScalarValue::LargeUtf8(value.as_deref().map(str::to_lowercase)),
+            )),
+            ColumnarValue::Scalar(value) => Err(datafusion::error::DataFusionError::Execution(
+                format!("lower does not support {} arguments", value.data_type()),
+            )),
+        }
+    }
+}
+
+/// `fuzzy_match(col, 'term', max_edits)`: whether the strings of `col` are
+/// within `max_edits` edits, by Levenshtein distance, of `term`
+///
+/// This verifies every row: no index narrows the candidates of the filter.
+#[derive(Debug, Clone)]
+struct FuzzyMatchUdf {
+    signature: Signature,
+}
+
+impl FuzzyMatchUdf {
+    pub fn new() -> Self {
+        Self {
+            signature: Signature::any(3, Volatility::Immutable),
+        }
+    }
+}
+
+impl ScalarUDFImpl for FuzzyMatchUdf {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "fuzzy_match"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[ArrowDataType]) -> DFResult<ArrowDataType> {
+        match arg_types {
+            [ArrowDataType::Utf8 | ArrowDataType::LargeUtf8, ArrowDataType::Utf8 | ArrowDataType::LargeUtf8, max_edits]
+                if max_edits.is_integer() =>
+            {
+                Ok(ArrowDataType::Boolean)
+            }
+            _ => Err(datafusion::error::DataFusionError::Plan(format!(
+                "fuzzy_match takes a string, a string and an integer, not {:?}",
+                arg_types
+            ))),
+        }
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> DFResult<ColumnarValue> {
+        let ColumnarValue::Scalar(
+            ScalarValue::Utf8(Some(term)) | ScalarValue::LargeUtf8(Some(term)),
+        ) = &args[1]
+        else {
+            return Err(datafusion::error::DataFusionError::Execution(
+                "the term of fuzzy_match must be a string literal".to_string(),
+            ));
+        };
+        let max_edits = match &args[2] {
+            ColumnarValue::Scalar(max_edits) if !max_edits.is_null() => {
+                match max_edits.cast_to(&ArrowDataType::UInt32)? {
+                    ScalarValue::UInt32(Some(max_edits)) => max_edits as usize,
+                    _ => unreachable!(),
+                }
+            }
+            _ => {
+                return Err(datafusion::error::DataFusionError::Execution(
+                    "the max edits of fuzzy_match must be an integer literal".to_string(),
+                ))
+            }
+        };
+        match &args[0] {
+            ColumnarValue::Array(arr) => Ok(ColumnarValue::Array(Arc::new(within_edit_distance(
+                arr.as_ref(),
+                term,
+                max_edits,
+            )?))),
+            ColumnarValue::Scalar(value) => {
+                let arr = value.to_array()?;
+                let within = within_edit_distance(arr.as_ref(), term, max_edits)?;
+                Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(
+                    &within, 0,
+                )?))
+            }
+        }
+    }
+}
+
 // Adapter that instructs datafusion how lance expects expressions to be interpreted
 struct LanceContextProvider {
     options: datafusion::config::ConfigOptions,
@@ -216,6 +357,8 @@ impl ContextProvider for LanceContextProvider {
             // TODO: cast should go thru CAST syntax instead of UDF
             // Going thru UDF makes it hard for the optimizer to find no-ops
             "_cast_list_f16" => Some(Arc::new(ScalarUDF::new_from_impl(CastListF16Udf::new()))),
+            "lower" => Some(Arc::new(ScalarUDF::new_from_impl(LowercaseUdf::new()))),
+            "fuzzy_match" => Some(Arc::new(ScalarUDF::new_from_impl(FuzzyMatchUdf::new()))),
             _ => self.state.scalar_functions().get(f).cloned(),
         }
     }
@@ -417,7 +560,7 @@ impl Planner {
     }
 
     fn parse_type(&self, data_type: &SQLDataType) -> Result<ArrowDataType> {
-        const SUPPORTED_TYPES: [&str; 13] = [
+        const SUPPORTED_TYPES: [&str; 15] = [
             "int [unsigned]",
             "tinyint [unsigned]",
             "smallint [unsigned]",
@@ -429,6 +572,8 @@ impl Planner {
             "date",
             "timestamp(precision)",
             "datetime(precision)",
+            "time(precision)",
+            "interval",
             "decimal(precision,scale)",
             "boolean",
         ];
@@ -491,18 +636,53 @@ impl Planner {
                 };
                 Ok(ArrowDataType::Timestamp(time_unit, None))
             }
-            SQLDataType::Decimal(number_info) => match number_info {
-                ExactNumberInfo::PrecisionAndScale(precision, scale) => {
-                    Ok(ArrowDataType::Decimal128(*precision as u8, *scale as i8))
+            SQLDataType::Time(resolution, tz) => {
+                if !matches!(tz, TimezoneInfo::None) {
+                    return Err(Error::io(
+                        "Timezone not supported in time".to_string(),
+                        location!(),
+                    ));
                 }
-                _ => Err(Error::io(
-                    format!(
-                        "Must provide precision and scale for decimal: {:?}",
-                        number_info
-                    ),
-                    location!(),
-                )),
-            },
+                match resolution {
+                    Some(0) => Ok(ArrowDataType::Time32(TimeUnit::Second)),
+                    Some(3) => Ok(ArrowDataType::Time32(TimeUnit::Millisecond)),
+                    // Default to microsecond to match PyArrow
+                    None | Some(6) => Ok(ArrowDataType::Time64(TimeUnit::Microsecond)),
+                    Some(9) => Ok(ArrowDataType::Time64(TimeUnit::Nanosecond)),
+                    _ => Err(Error::io(
+                        format!("Unsupported time resolution: {:?}", resolution),
+                        location!(),
+                    )),
+                }
+            }
+            SQLDataType::Interval => Ok(ArrowDataType::Interval(IntervalUnit::MonthDayNano)),
+            SQLDataType::Decimal(number_info) => {
+                let (precision, scale) = match number_info {
+                    ExactNumberInfo::PrecisionAndScale(precision, scale) => (*precision, *scale),
+                    ExactNumberInfo::Precision(precision) => (*precision, 0),
+                    ExactNumberInfo::None => {
+                        return Err(Error::io(
+                            format!("Must provide precision for decimal: {:?}", number_info),
+                            location!(),
+                        ))
+                    }
+                };
+                if precision == 0
+                    || precision > DECIMAL256_MAX_PRECISION as u64
+                    || scale > precision
+                {
+                    return Err(Error::io(
+                        format!("Invalid precision and scale for decimal: {:?}", number_info),
+                        location!(),
+                    ));
+                }
+                // Decimals that don't fit in 128 bits are 256 bits wide.
+                if precision > DECIMAL128_MAX_PRECISION as u64 {
+                    Ok(ArrowDataType::Decimal256(precision as u8, scale as i8))
+                } else {
+                    Ok(ArrowDataType::Decimal128(precision as u8, scale as i8))
+                }
+            }
             _ => Err(Error::io(
                 format!(
                     "Unsupported data type: {:?}. Supported types: {:?}",
@@ -513,6 +693,29 @@ impl Planner {
         }
     }
 
+    fn parse_interval(&self, interval: &Interval) -> Result<Expr> {
+        let value = match interval.value.as_ref() {
+            SQLExpr::Value(Value::SingleQuotedString(value)) if interval.last_field.is_none() => {
+                value
+            }
+            _ => {
+                return Err(Error::io(
+                    format!("Unsupported interval: {}", interval),
+                    location!(),
+                ))
+            }
+        };
+        // INTERVAL '1' DAY is read as '1 day'
+        let value = match &interval.leading_field {
+            Some(field) => format!("{} {}", value, field),
+            None => value.clone(),
+        };
+        let value = parse_interval_month_day_nano(&value)?;
+        Ok(Expr::Literal(ScalarValue::IntervalMonthDayNano(Some(
+            value,
+        ))))
+    }
+
     fn parse_sql_expr(&self, expr: &SQLExpr) -> Result<Expr> {
         match expr {
             SQLExpr::Identifier(id) => {
@@ -591,6 +794,8 @@ impl Planner {
                     data_type: self.parse_type(data_type)?,
                 }))
             }
+            // For example, INTERVAL '1 day'
+            SQLExpr::Interval(interval) => self.parse_interval(interval),
             SQLExpr::IsFalse(expr) => Ok(Expr::IsFalse(Box::new(self.parse_sql_expr(expr)?))),
             SQLExpr::IsNotFalse(_) => Ok(Expr::IsNotFalse(Box::new(self.parse_sql_expr(expr)?))),
             SQLExpr::IsTrue(expr) => Ok(Expr::IsTrue(Box::new(self.parse_sql_expr(expr)?))),
@@ -685,9 +890,17 @@ impl Planner {
             datafusion::optimizer::simplify_expressions::ExprSimplifier::new(simplify_context);
 
         let expr = simplifier.simplify(expr.clone())?;
-        let expr = simplifier.coerce(expr, df_schema.clone())?;
-
-        Ok(expr)
+        match simplifier.coerce(expr.clone(), df_schema.clone()) {
+            Ok(expr) => Ok(expr),
+            Err(err) => {
+                // Simplifying folds typed literals, such as `decimal(9,2) '1.25'`, into
+                // literals DataFusion can't always coerce to the column type, as between
+                // decimals of different widths. Resolve those against the schema again.
+                let schema = Schema::try_from(self.schema.as_ref())?;
+                let resolved = resolve_expr(&expr, &schema).map_err(|_| err)?;
+                Ok(simplifier.coerce(resolved, df_schema)?)
+            }
+        }
     }
 
     /// Create the [`PhysicalExpr`] from a logical [`Expr`]
@@ -804,10 +1017,13 @@ mod tests {
     use super::*;
 
     use arrow_array::{
-        ArrayRef, BooleanArray, Float32Array, Int32Array, Int64Array, RecordBatch, StringArray,
-        StructArray, TimestampMicrosecondArray, TimestampMillisecondArray,
-        TimestampNanosecondArray, TimestampSecondArray,
+        types::IntervalMonthDayNanoType, ArrayRef, BooleanArray, Decimal128Array, Decimal256Array,
+        DurationMillisecondArray, DurationSecondArray, Float32Array, Int32Array, Int64Array,
+        IntervalMonthDayNanoArray, IntervalYearMonthArray, RecordBatch, StringArray, StructArray,
+        Time32SecondArray, Time64NanosecondArray, TimestampMicrosecondArray,
+        TimestampMillisecondArray, TimestampNanosecondArray, TimestampSecondArray,
     };
+    use arrow_buffer::i256;
     use arrow_schema::{DataType, Fields, Schema};
     use datafusion::logical_expr::{lit, Cast, ScalarFunctionDefinition};
 
@@ -1057,6 +1273,107 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_sql_ilike() {
+        let schema = Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, true)]));
+
+        let planner = Planner::new(schema.clone());
+
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(StringArray::from(vec![
+                Some("Str-4"),
+                Some("STR-40"),
+                Some("str-5"),
+                None,
+            ]))],
+        )
+        .unwrap();
+        for (filter, expected) in [
+            (
+                "s ILIKE 'sTr-4%'",
+                vec![Some(true), Some(true), Some(false), None],
+            ),
+            (
+                "s NOT ILIKE 'str-4'",
+                vec![Some(false), Some(true), Some(true), None],
+            ),
+            (
+                "lower(s) = 'str-40'",
+                vec![Some(false), Some(true), Some(false), None],
+            ),
+        ] {
+            let expr = planner.parse_filter(filter).unwrap();
+            let physical_expr = planner.create_physical_expr(&expr).unwrap();
+            let predicates = physical_expr.evaluate(&batch).unwrap();
+            assert_eq!(
+                predicates.into_array(0).unwrap().as_ref(),
+                &BooleanArray::from(expected),
+                "{}",
+                filter
+            );
+        }
+
+        // ILIKE is left to Arrow, which folds cases rather than lowercasing:
+        // 'ς' lowercases to itself, but matches 'σ' all the same.  Only the
+        // lowercase scalar indices lowercase the pattern.
+        let expr = planner.parse_filter("s ILIKE 'σ'").unwrap();
+        let Expr::Like(like) = &expr else {
+            panic!("Expected a LIKE expression, got {:?}", expr);
+        };
+        assert!(like.case_insensitive);
+        assert_eq!(*like.pattern, lit("σ"));
+        let batch = RecordBatch::try_new(
+            batch.schema(),
+            vec![Arc::new(StringArray::from(vec!["ς", "Σ", "s"]))],
+        )
+        .unwrap();
+        let predicates = planner
+            .create_physical_expr(&expr)
+            .unwrap()
+            .evaluate(&batch)
+            .unwrap();
+        assert_eq!(
+            predicates.into_array(0).unwrap().as_ref(),
+            &BooleanArray::from(vec![true, true, false])
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        let schema = Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, true)]));
+
+        let planner = Planner::new(schema.clone());
+
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(StringArray::from(vec![
+                Some("headphones"),
+                Some("headfones"),
+                Some("earbuds"),
+                None,
+            ]))],
+        )
+        .unwrap();
+        let expr = planner
+            .parse_filter("fuzzy_match(s, 'headphones', 2)")
+            .unwrap();
+        let physical_expr = planner.create_physical_expr(&expr).unwrap();
+        let predicates = physical_expr.evaluate(&batch).unwrap();
+        assert_eq!(
+            predicates.into_array(0).unwrap().as_ref(),
+            &BooleanArray::from(vec![Some(true), Some(true), Some(false), None])
+        );
+
+        for filter in [
+            "fuzzy_match(s, 'headphones')",
+            "fuzzy_match(s, 'headphones', 'two')",
+        ] {
+            let expr = planner.parse_filter(filter).unwrap();
+            assert!(planner.create_physical_expr(&expr).is_err(), "{}", filter);
+        }
+    }
+
     #[test]
     fn test_sql_is_in() {
         let schema = Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, true)]));
@@ -1171,10 +1488,30 @@ mod tests {
                 ArrowDataType::Timestamp(TimeUnit::Nanosecond, None),
             ),
             ("x = cast('2021-01-01' as date)", ArrowDataType::Date32),
+            (
+                "x = cast('12:00:00' as time)",
+                ArrowDataType::Time64(TimeUnit::Microsecond),
+            ),
+            (
+                "x = cast('12:00:00' as time(0))",
+                ArrowDataType::Time32(TimeUnit::Second),
+            ),
+            (
+                "x = cast('1 day' as interval)",
+                ArrowDataType::Interval(IntervalUnit::MonthDayNano),
+            ),
             (
                 "x = cast('1.238' as decimal(9,3))",
                 ArrowDataType::Decimal128(9, 3),
             ),
+            (
+                "x = cast('1238' as decimal(9))",
+                ArrowDataType::Decimal128(9, 0),
+            ),
+            (
+                "x = cast('1.238' as decimal(50,3))",
+                ArrowDataType::Decimal256(50, 3),
+            ),
             ("x = cast(1 as float)", ArrowDataType::Float32),
             ("x = cast(1 as double)", ArrowDataType::Float64),
             ("x = cast(1 as tinyint)", ArrowDataType::Int8),
@@ -1248,7 +1585,19 @@ mod tests {
                 ArrowDataType::Timestamp(TimeUnit::Nanosecond, None),
             ),
             ("x = date '2021-01-01'", ArrowDataType::Date32),
+            (
+                "x = time '12:00:00.123'",
+                ArrowDataType::Time64(TimeUnit::Microsecond),
+            ),
+            (
+                "x = time(9) '12:00:00.123'",
+                ArrowDataType::Time64(TimeUnit::Nanosecond),
+            ),
             ("x = decimal(9,3) '1.238'", ArrowDataType::Decimal128(9, 3)),
+            (
+                "x = decimal(50,3) '1.238'",
+                ArrowDataType::Decimal256(50, 3),
+            ),
         ];
 
         for (sql, expected_data_type) in cases {
@@ -1338,6 +1687,48 @@ mod tests {
                 "timestamp_ns",
                 Arc::new(TimestampNanosecondArray::from_iter_values(4995..5005)),
             ),
+            (
+                "decimal128",
+                Arc::new(
+                    Decimal128Array::from_iter_values((0..10).map(|v| v * 50))
+                        .with_precision_and_scale(10, 2)
+                        .unwrap(),
+                ),
+            ),
+            (
+                "decimal256",
+                Arc::new(
+                    Decimal256Array::from_iter_values((0..10).map(|v| i256::from_i128(v * 250)))
+                        .with_precision_and_scale(50, 3)
+                        .unwrap(),
+                ),
+            ),
+            (
+                "time32_s",
+                Arc::new(Time32SecondArray::from_iter_values(0..10)),
+            ),
+            (
+                "time64_ns",
+                Arc::new(Time64NanosecondArray::from_iter_values(4995..5005)),
+            ),
+            (
+                "duration_s",
+                Arc::new(DurationSecondArray::from_iter_values(0..10)),
+            ),
+            (
+                "duration_ms",
+                Arc::new(DurationMillisecondArray::from_iter_values(0..10)),
+            ),
+            (
+                "interval_ym",
+                Arc::new(IntervalYearMonthArray::from_iter_values(0..10)),
+            ),
+            (
+                "interval_mdn",
+                Arc::new(IntervalMonthDayNanoArray::from_iter_values(
+                    (0..10).map(|days| IntervalMonthDayNanoType::make_value(0, days, 0)),
+                )),
+            ),
         ];
         let batch = RecordBatch::try_from_iter(batch).unwrap();
 
@@ -1349,6 +1740,16 @@ mod tests {
             "timestamp_ms >= TIMESTAMP '1970-01-01 00:00:00.005'",
             "timestamp_us >= TIMESTAMP '1970-01-01 00:00:00.000005'",
             "timestamp_ns >= TIMESTAMP '1970-01-01 00:00:00.000005'",
+            "decimal128 >= 2.5",
+            "decimal128 > 2.499",
+            "decimal256 > 1",
+            "decimal256 >= decimal(9,2) '1.25'",
+            "time32_s >= TIME '00:00:05'",
+            "time64_ns >= TIME '00:00:00.000005'",
+            "duration_s >= INTERVAL '5 seconds'",
+            "duration_ms > INTERVAL '4' MILLISECOND",
+            "interval_ym >= INTERVAL '5 months'",
+            "interval_mdn >= INTERVAL '5 days'",
         ];
 
         let expected: ArrayRef = Arc::new(BooleanArray::from_iter(
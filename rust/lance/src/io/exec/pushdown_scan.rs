@@ -140,6 +140,11 @@ impl LancePushdownScanExec {
             properties,
         })
     }
+
+    /// The fragments scanned.
+    pub fn fragments(&self) -> &Arc<Vec<Fragment>> {
+        &self.fragments
+    }
 }
 
 impl ExecutionPlan for LancePushdownScanExec {
@@ -157,9 +162,15 @@ impl ExecutionPlan for LancePushdownScanExec {
 
     fn with_new_children(
         self: Arc<Self>,
-        _children: Vec<Arc<dyn ExecutionPlan>>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
-        todo!()
+        if children.is_empty() {
+            Ok(self)
+        } else {
+            Err(DataFusionError::Internal(
+                "LancePushdownScanExec cannot be assigned children".to_string(),
+            ))
+        }
     }
 
     fn statistics(&self) -> datafusion::error::Result<datafusion::physical_plan::Statistics> {
@@ -93,6 +93,11 @@ impl ScalarIndexExec {
         }
     }
 
+    /// The query answered with scalar indices.
+    pub fn expr(&self) -> &ScalarIndexExpr {
+        &self.expr
+    }
+
     async fn do_execute(expr: ScalarIndexExpr, dataset: Arc<Dataset>) -> Result<RecordBatch> {
         let query_result = expr.evaluate(dataset.as_ref()).await?;
         let query_result_arr = query_result.into_arrow()?;
@@ -191,6 +196,11 @@ impl MapIndexExec {
         }
     }
 
+    /// The column whose index is looked up.
+    pub fn column_name(&self) -> &str {
+        &self.column_name
+    }
+
     async fn map_batch(
         column_name: String,
         dataset: Arc<Dataset>,
@@ -270,9 +280,18 @@ impl ExecutionPlan for MapIndexExec {
 
     fn with_new_children(
         self: Arc<Self>,
-        _: Vec<Arc<dyn ExecutionPlan>>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
-        unimplemented!()
+        if children.len() != 1 {
+            return Err(datafusion::error::DataFusionError::Internal(
+                "MapIndexExec wrong number of children".to_string(),
+            ));
+        }
+        Ok(Arc::new(Self::new(
+            self.dataset.clone(),
+            self.column_name.clone(),
+            children[0].clone(),
+        )))
     }
 
     fn execute(
@@ -383,6 +402,16 @@ impl MaterializeIndexExec {
         }
     }
 
+    /// The query answered with scalar indices.
+    pub fn expr(&self) -> &ScalarIndexExpr {
+        &self.expr
+    }
+
+    /// The fragments the result of the query is materialized for.
+    pub fn fragments(&self) -> &Arc<Vec<Fragment>> {
+        &self.fragments
+    }
+
     #[instrument(name = "materialize_scalar_index", skip_all, level = "debug")]
     async fn do_execute(
         expr: ScalarIndexExpr,
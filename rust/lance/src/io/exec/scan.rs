@@ -2,6 +2,8 @@
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
 use std::any::Any;
+use std::collections::HashMap;
+use std::ops::Range;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
@@ -10,14 +12,18 @@ use arrow_array::RecordBatch;
 use arrow_schema::{Field, Schema as ArrowSchema, SchemaRef};
 use datafusion::common::stats::Precision;
 use datafusion::error::{DataFusionError, Result};
+use datafusion::physical_plan::metrics::{
+    BaselineMetrics, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
 use datafusion::physical_plan::{
-    DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, PlanProperties, RecordBatchStream,
-    SendableRecordBatchStream, Statistics,
+    stream::RecordBatchStreamAdapter, DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning,
+    PlanProperties, RecordBatchStream, SendableRecordBatchStream, Statistics,
 };
 use datafusion_physical_expr::EquivalenceProperties;
 use futures::stream;
 use futures::stream::Stream;
 use futures::{StreamExt, TryStreamExt};
+use lance_core::utils::tokio::DecodeLimit;
 use lance_core::utils::tracing::StreamTracingExt;
 use lance_core::ROW_ID_FIELD;
 use lance_table::format::Fragment;
@@ -31,15 +37,39 @@ async fn open_file(
     projection: Arc<Schema>,
     with_row_id: bool,
     with_make_deletions_null: bool,
+    decode_limit: DecodeLimit,
 ) -> Result<FragmentReader> {
     let mut reader = file_fragment.open(projection.as_ref(), with_row_id).await?;
 
     if with_make_deletions_null {
         reader.with_make_deletions_null();
     };
+    reader.with_decode_limit(decode_limit);
     Ok(reader)
 }
 
+/// Split `range`, or the whole fragment of `physical_rows` rows if it is
+/// `None`, into ranges of at most `split_rows` rows.
+fn split_range(
+    range: Option<Range<u32>>,
+    physical_rows: Option<usize>,
+    split_rows: Option<u32>,
+) -> Vec<Option<Range<u32>>> {
+    let (Some(split_rows), Some(rows)) = (
+        split_rows,
+        range.clone().or(physical_rows.map(|n| 0..n as u32)),
+    ) else {
+        return vec![range];
+    };
+    if rows.len() <= split_rows as usize {
+        return vec![range];
+    }
+    rows.clone()
+        .step_by(split_rows as usize)
+        .map(|start| Some(start..rows.end.min(start.saturating_add(split_rows))))
+        .collect()
+}
+
 /// Dataset Scan Node.
 pub struct LanceStream {
     inner_stream: stream::BoxStream<'static, Result<RecordBatch>>,
@@ -64,6 +94,12 @@ impl LanceStream {
     ///    if scan_in_order = false).
     ///  - ***with_row_id***: load row ID from the datasets.
     ///  - ***scan_in_order***: whether to scan the fragments in the provided order.
+    ///  - ***decode_limit***: bounds how many batches are decoded at once.
+    ///  - ***row_ranges***: the rows to read of some fragments, by fragment id.
+    ///    The other fragments are read whole.
+    ///  - ***split_rows***: read fragments, or ranges of rows, of more rows
+    ///    than this as several ranges, which are read concurrently like
+    ///    fragments.
     #[allow(clippy::too_many_arguments)]
     pub fn try_new(
         dataset: Arc<Dataset>,
@@ -75,29 +111,48 @@ impl LanceStream {
         with_row_id: bool,
         with_make_deletions_null: bool,
         scan_in_order: bool,
+        decode_limit: DecodeLimit,
+        row_ranges: Option<Arc<HashMap<u64, Vec<Range<u32>>>>>,
+        split_rows: Option<u32>,
     ) -> Result<Self> {
         let project_schema = projection.clone();
 
-        let file_fragments = fragments
+        // The fragments and ranges of rows of fragments to read, in order
+        let units = fragments
             .iter()
-            .map(|fragment| FileFragment::new(dataset.clone(), fragment.clone()))
+            .flat_map(|fragment| {
+                let file_fragment = FileFragment::new(dataset.clone(), fragment.clone());
+                let ranges = match row_ranges.as_ref().and_then(|r| r.get(&fragment.id)) {
+                    Some(ranges) => ranges.iter().cloned().map(Some).collect(),
+                    None => vec![None],
+                };
+                ranges
+                    .into_iter()
+                    .flat_map(|range| split_range(range, fragment.physical_rows, split_rows))
+                    .map(move |range| (file_fragment.clone(), range))
+            })
             .collect::<Vec<_>>();
 
         let inner_stream = if scan_in_order {
-            let readers = stream::iter(file_fragments)
-                .map(move |file_fragment| {
-                    Ok(open_file(
+            let readers = stream::iter(units)
+                .map(move |(file_fragment, row_range)| {
+                    let reader = open_file(
                         file_fragment,
                         project_schema.clone(),
                         with_row_id,
                         with_make_deletions_null,
-                    ))
+                        decode_limit.clone(),
+                    );
+                    Ok(async move { Ok::<_, DataFusionError>((reader.await?, row_range)) })
                 })
                 .try_buffered(fragment_readahead);
-            let tasks = readers.and_then(move |reader| {
+            let tasks = readers.and_then(move |(reader, row_range)| {
+                let task_stream = match row_range {
+                    Some(row_range) => reader.read_range(row_range, read_size as u32),
+                    None => reader.read_all(read_size as u32),
+                };
                 std::future::ready(
-                    reader
-                        .read_all(read_size as u32)
+                    task_stream
                         .map(|task_stream| task_stream.map(Ok))
                         .map_err(DataFusionError::from),
                 )
@@ -110,20 +165,25 @@ impl LanceStream {
                 .stream_in_current_span()
                 .boxed()
         } else {
-            let readers = stream::iter(file_fragments)
-                .map(move |file_fragment| {
-                    Ok(open_file(
+            let readers = stream::iter(units)
+                .map(move |(file_fragment, row_range)| {
+                    let reader = open_file(
                         file_fragment,
                         project_schema.clone(),
                         with_row_id,
                         with_make_deletions_null,
-                    ))
+                        decode_limit.clone(),
+                    );
+                    Ok(async move { Ok::<_, DataFusionError>((reader.await?, row_range)) })
                 })
                 .try_buffered(fragment_readahead);
-            let tasks = readers.and_then(move |reader| {
+            let tasks = readers.and_then(move |(reader, row_range)| {
+                let task_stream = match row_range {
+                    Some(row_range) => reader.read_range(row_range, read_size as u32),
+                    None => reader.read_all(read_size as u32),
+                };
                 std::future::ready(
-                    reader
-                        .read_all(read_size as u32)
+                    task_stream
                         .map(|task_stream| task_stream.map(Ok))
                         .map_err(DataFusionError::from),
                 )
@@ -192,8 +252,12 @@ pub struct LanceScanExec {
     with_row_id: bool,
     with_make_deletions_null: bool,
     ordered_output: bool,
+    decode_limit: DecodeLimit,
+    row_ranges: Option<Arc<HashMap<u64, Vec<Range<u32>>>>>,
+    split_rows: Option<u32>,
     output_schema: Arc<ArrowSchema>,
     properties: PlanProperties,
+    metrics: ExecutionPlanMetricsSet,
 }
 
 impl DisplayAs for LanceScanExec {
@@ -256,10 +320,39 @@ impl LanceScanExec {
             with_row_id,
             with_make_deletions_null,
             ordered_output: ordered_ouput,
+            decode_limit: DecodeLimit::default(),
+            row_ranges: None,
+            split_rows: None,
             output_schema,
             properties,
+            metrics: ExecutionPlanMetricsSet::new(),
         }
     }
+
+    /// Bound how many batches of the scan are decoded at the same time.
+    pub fn with_decode_limit(mut self, decode_limit: DecodeLimit) -> Self {
+        self.decode_limit = decode_limit;
+        self
+    }
+
+    /// Only read `row_ranges` of the fragments they are given for, by
+    /// fragment id.
+    pub fn with_row_ranges(mut self, row_ranges: Arc<HashMap<u64, Vec<Range<u32>>>>) -> Self {
+        self.row_ranges = Some(row_ranges);
+        self
+    }
+
+    /// Read fragments of more than `split_rows` rows as several ranges of
+    /// rows, which are read concurrently like fragments.
+    pub fn with_split_rows(mut self, split_rows: u32) -> Self {
+        self.split_rows = Some(split_rows);
+        self
+    }
+
+    /// The fragments scanned.
+    pub fn fragments(&self) -> &Arc<Vec<Fragment>> {
+        &self.fragments
+    }
 }
 
 impl ExecutionPlan for LanceScanExec {
@@ -291,10 +384,15 @@ impl ExecutionPlan for LanceScanExec {
 
     fn execute(
         &self,
-        _partition: usize,
+        partition: usize,
         _context: Arc<datafusion::execution::context::TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
-        Ok(Box::pin(LanceStream::try_new(
+        let baseline_metrics = BaselineMetrics::new(&self.metrics, partition);
+        // In-memory size of the batches decoded. This is not the bytes read
+        // from storage, as files are read in ranges that may be shared by
+        // several batches.
+        let decoded_bytes = MetricBuilder::new(&self.metrics).counter("decoded_bytes", partition);
+        let stream = LanceStream::try_new(
             self.dataset.clone(),
             self.fragments.clone(),
             self.projection.clone(),
@@ -304,7 +402,24 @@ impl ExecutionPlan for LanceScanExec {
             self.with_row_id,
             self.with_make_deletions_null,
             self.ordered_output,
-        )?))
+            self.decode_limit.clone(),
+            self.row_ranges.clone(),
+            self.split_rows,
+        )?;
+        let stream = stream.inspect(move |batch| {
+            if let Ok(batch) = batch {
+                baseline_metrics.record_output(batch.num_rows());
+                decoded_bytes.add(batch.get_array_memory_size());
+            }
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.output_schema.clone(),
+            stream,
+        )))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
     }
 
     fn statistics(&self) -> datafusion::error::Result<Statistics> {
@@ -319,7 +434,8 @@ impl ExecutionPlan for LanceScanExec {
                         None => (row_count, false),
                     },
                 );
-        let num_rows = match is_exact {
+        // Only parts of some fragments are read with row ranges
+        let num_rows = match is_exact && self.row_ranges.is_none() {
             true => Precision::Exact(row_count),
             false => Precision::Absent,
         };
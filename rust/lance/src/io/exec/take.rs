@@ -1,12 +1,18 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-FileCopyrightText: Copyright The Lance Authors
 
+use std::collections::BTreeMap;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
-use arrow_array::{cast::as_primitive_array, RecordBatch, UInt64Array};
+use arrow_array::{
+    cast::{as_primitive_array, AsArray},
+    types::UInt64Type,
+    RecordBatch, UInt64Array,
+};
 use arrow_schema::{Schema as ArrowSchema, SchemaRef};
+use arrow_select::concat::concat_batches;
 use datafusion::common::Statistics;
 use datafusion::error::{DataFusionError, Result};
 use datafusion::physical_plan::{
@@ -16,6 +22,7 @@ use datafusion::physical_plan::{
 use datafusion_physical_expr::EquivalenceProperties;
 use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 use futures::{Future, FutureExt};
+use lance_datafusion::take::RowAddressReader;
 use tokio::sync::mpsc::{self, Receiver};
 use tokio::task::JoinHandle;
 use tracing::{instrument, Instrument};
@@ -313,6 +320,55 @@ impl ExecutionPlan for TakeExec {
     }
 }
 
+/// Reads the rows of a [`Dataset`] by their row ids, for the
+/// [`lance_datafusion::take::TakeRowsExec`] node. Deleted rows and the rows
+/// of removed fragments are skipped.
+#[derive(Debug)]
+pub struct DatasetRowReader {
+    dataset: Arc<Dataset>,
+    projection: Arc<Schema>,
+}
+
+impl DatasetRowReader {
+    /// Read the columns of `projection` from `dataset`.
+    pub fn new(dataset: Arc<Dataset>, projection: Arc<Schema>) -> Self {
+        Self {
+            dataset,
+            projection,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RowAddressReader for DatasetRowReader {
+    fn schema(&self) -> SchemaRef {
+        Arc::new(ArrowSchema::from(self.projection.as_ref()))
+    }
+
+    async fn take(&self, addresses: &[u64]) -> Result<(Vec<u64>, RecordBatch), Error> {
+        // Read each fragment with its row ids, to tell which rows are deleted
+        let mut offsets_per_fragment: BTreeMap<u64, Vec<u32>> = BTreeMap::new();
+        for address in addresses {
+            offsets_per_fragment
+                .entry(address >> 32)
+                .or_default()
+                .push(*address as u32);
+        }
+        let schema = self.schema();
+        let mut found = Vec::with_capacity(addresses.len());
+        let mut batches = Vec::with_capacity(offsets_per_fragment.len());
+        for (fragment_id, offsets) in offsets_per_fragment {
+            let Some(fragment) = self.dataset.get_fragment(fragment_id as usize) else {
+                continue;
+            };
+            let batch = fragment.take_rows(&offsets, &self.projection, true).await?;
+            found.extend_from_slice(batch[ROW_ID].as_primitive::<UInt64Type>().values());
+            batches.push(batch.project_by_schema(&schema)?);
+        }
+        Ok((found, concat_batches(&schema, &batches)?))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -324,6 +380,11 @@ mod tests {
     use crate::{dataset::WriteParams, io::exec::LanceScanExec};
 
     async fn create_dataset() -> Arc<Dataset> {
+        let test_dir = tempdir().unwrap();
+        create_dataset_at(test_dir.path().to_str().unwrap()).await
+    }
+
+    async fn create_dataset_at(test_uri: &str) -> Arc<Dataset> {
         let schema = Arc::new(ArrowSchema::new(vec![
             Field::new("i", DataType::Int32, false),
             Field::new("f", DataType::Float32, false),
@@ -347,8 +408,6 @@ mod tests {
             })
             .collect();
 
-        let test_dir = tempdir().unwrap();
-        let test_uri = test_dir.path().to_str().unwrap();
         let params = WriteParams {
             max_rows_per_group: 10,
             ..Default::default()
@@ -492,4 +551,44 @@ mod tests {
         assert_eq!(edited.schema().field_names(), vec!["i", ROW_ID, "s", "f"],);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_dataset_row_reader() {
+        use datafusion::execution::context::TaskContext;
+        use datafusion::physical_plan::memory::MemoryExec;
+        use lance_datafusion::take::{TakeOptions, TakeRowsExec};
+
+        // The rows are read after the dataset is opened, so keep its files
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        create_dataset_at(test_uri).await;
+        let mut dataset = Dataset::open(test_uri).await.unwrap();
+        dataset.delete("i = 19").await.unwrap();
+        let dataset = Arc::new(dataset);
+
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            ROW_ID,
+            DataType::UInt64,
+            false,
+        )]));
+        let row_ids = UInt64Array::from(vec![25, 3, 19, 3]);
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(row_ids)]).unwrap();
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None).unwrap());
+
+        let reader = Arc::new(DatasetRowReader::new(
+            dataset.clone(),
+            Arc::new(dataset.schema().project(&["i", "s"]).unwrap()),
+        ));
+        let take_exec = TakeRowsExec::try_new(input, reader, TakeOptions::default()).unwrap();
+        let batches = take_exec
+            .execute(0, Arc::new(TaskContext::default()))
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.len(), 1);
+        let values = as_primitive_array::<arrow_array::types::Int32Type>(batches[0].column(1));
+        // The deleted row is left out
+        assert_eq!(values.values(), &[25, 3, 3]);
+    }
 }
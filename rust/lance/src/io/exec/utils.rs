@@ -137,9 +137,14 @@ impl ExecutionPlan for ReplayExec {
 
     fn with_new_children(
         self: Arc<Self>,
-        _: Vec<Arc<dyn ExecutionPlan>>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
-        unimplemented!()
+        if children.len() != 1 {
+            return Err(DataFusionError::Internal(
+                "ReplayExec wrong number of children".to_string(),
+            ));
+        }
+        Ok(Arc::new(Self::new(self.capacity, children[0].clone())))
     }
 
     fn execute(
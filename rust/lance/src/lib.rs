@@ -77,10 +77,13 @@ pub use lance_core::{datatypes, error};
 pub use lance_core::{Error, Result};
 
 pub mod arrow;
+#[cfg(feature = "bench")]
+pub mod bench;
 pub mod datafusion;
 pub mod dataset;
 pub mod index;
 pub mod io;
+pub mod metrics;
 pub mod session;
 pub mod table;
 pub mod utils;
@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Operational metrics
+//!
+//! With the `metrics` feature enabled, Lance reports counters and histograms
+//! through the [`metrics`](https://docs.rs/metrics) facade. They are sent to
+//! whatever recorder the application installs, so services embedding Lance can
+//! export them to their monitoring system. The `prometheus` feature adds
+//! [`install_prometheus_recorder`], which installs a recorder rendering the
+//! Prometheus text exposition format.
+//!
+//! Without the `metrics` feature, recording is a no-op.
+
+use std::time::{Duration, Instant};
+
+use crate::Result;
+
+/// Histogram of the time from the start of a scan until its stream is exhausted.
+pub const SCAN_DURATION_SECONDS: &str = "lance_scan_duration_seconds";
+/// Counter of rows returned by scans.
+pub const SCAN_ROWS_TOTAL: &str = "lance_scan_rows_total";
+/// Counter of commit attempts, labelled by `operation` and `status`
+/// (`success`, `conflict` or `error`).
+pub const COMMITS_TOTAL: &str = "lance_commits_total";
+/// Histogram of commit durations, labelled by `operation`.
+pub const COMMIT_DURATION_SECONDS: &str = "lance_commit_duration_seconds";
+/// Counter of commits retried after a concurrent commit, labelled by `operation`.
+pub const COMMIT_RETRIES_TOTAL: &str = "lance_commit_retries_total";
+/// Counter of index cache lookups that found an entry.
+pub const INDEX_CACHE_HITS_TOTAL: &str = "lance_index_cache_hits_total";
+/// Counter of index cache lookups that did not find an entry.
+pub const INDEX_CACHE_MISSES_TOTAL: &str = "lance_index_cache_misses_total";
+/// Histogram of the duration of [`compact_files`](crate::dataset::optimize::compact_files).
+pub const COMPACTION_DURATION_SECONDS: &str = "lance_compaction_duration_seconds";
+/// Counter of fragments removed by compaction.
+pub const COMPACTION_FRAGMENTS_REMOVED_TOTAL: &str = "lance_compaction_fragments_removed_total";
+/// Counter of fragments added by compaction.
+pub const COMPACTION_FRAGMENTS_ADDED_TOTAL: &str = "lance_compaction_fragments_added_total";
+
+/// Register the descriptions and units of all Lance metrics with the
+/// installed recorder.
+#[cfg(feature = "metrics")]
+pub fn describe_metrics() {
+    use ::metrics::{describe_counter, describe_histogram, Unit};
+
+    describe_histogram!(
+        SCAN_DURATION_SECONDS,
+        Unit::Seconds,
+        "Time from the start of a scan until its stream is exhausted"
+    );
+    describe_counter!(SCAN_ROWS_TOTAL, Unit::Count, "Rows returned by scans");
+    describe_counter!(COMMITS_TOTAL, Unit::Count, "Commit attempts by outcome");
+    describe_histogram!(
+        COMMIT_DURATION_SECONDS,
+        Unit::Seconds,
+        "Duration of commits, including retries"
+    );
+    describe_counter!(
+        COMMIT_RETRIES_TOTAL,
+        Unit::Count,
+        "Commits retried because of a concurrent commit"
+    );
+    describe_counter!(
+        INDEX_CACHE_HITS_TOTAL,
+        Unit::Count,
+        "Index cache lookups that found an entry"
+    );
+    describe_counter!(
+        INDEX_CACHE_MISSES_TOTAL,
+        Unit::Count,
+        "Index cache lookups that did not find an entry"
+    );
+    describe_histogram!(
+        COMPACTION_DURATION_SECONDS,
+        Unit::Seconds,
+        "Duration of file compaction"
+    );
+    describe_counter!(
+        COMPACTION_FRAGMENTS_REMOVED_TOTAL,
+        Unit::Count,
+        "Fragments removed by compaction"
+    );
+    describe_counter!(
+        COMPACTION_FRAGMENTS_ADDED_TOTAL,
+        Unit::Count,
+        "Fragments added by compaction"
+    );
+}
+
+/// Install a global recorder that keeps metrics in the Prometheus format.
+///
+/// The returned handle renders the current metrics, for example to serve them
+/// from a `/metrics` endpoint. This fails if a global recorder is already
+/// installed.
+#[cfg(feature = "prometheus")]
+pub fn install_prometheus_recorder() -> Result<metrics_exporter_prometheus::PrometheusHandle> {
+    use snafu::{location, Location};
+
+    let handle = metrics_exporter_prometheus::PrometheusBuilder::new()
+        .install_recorder()
+        .map_err(|e| crate::Error::Internal {
+            message: format!("Failed to install Prometheus recorder: {}", e),
+            location: location!(),
+        })?;
+    describe_metrics();
+    Ok(handle)
+}
+
+/// Times a scan and counts the rows it returns.
+///
+/// The scan is recorded when it ends, or when it is dropped before that.
+#[derive(Debug)]
+pub(crate) struct ScanTimer {
+    started: Instant,
+    rows: u64,
+    finished: bool,
+}
+
+impl ScanTimer {
+    pub(crate) fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            rows: 0,
+            finished: false,
+        }
+    }
+
+    pub(crate) fn observe(&mut self, num_rows: usize) {
+        self.rows += num_rows as u64;
+    }
+
+    /// Record the scan. Only the first call has any effect.
+    pub(crate) fn finish(&mut self) {
+        if !self.finished {
+            self.finished = true;
+            record_scan(self.started.elapsed(), self.rows);
+        }
+    }
+}
+
+impl Drop for ScanTimer {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[allow(unused_variables)]
+fn record_scan(duration: Duration, rows: u64) {
+    #[cfg(feature = "metrics")]
+    {
+        ::metrics::histogram!(SCAN_DURATION_SECONDS).record(duration.as_secs_f64());
+        ::metrics::counter!(SCAN_ROWS_TOTAL).increment(rows);
+    }
+}
+
+#[allow(unused_variables)]
+pub(crate) fn record_commit<T>(operation: &str, duration: Duration, result: &Result<T>) {
+    #[cfg(feature = "metrics")]
+    {
+        let status = match result {
+            Ok(_) => "success",
+            Err(crate::Error::CommitConflict { .. }) => "conflict",
+            Err(_) => "error",
+        };
+        ::metrics::counter!(
+            COMMITS_TOTAL,
+            "operation" => operation.to_string(),
+            "status" => status
+        )
+        .increment(1);
+        ::metrics::histogram!(COMMIT_DURATION_SECONDS, "operation" => operation.to_string())
+            .record(duration.as_secs_f64());
+    }
+}
+
+#[allow(unused_variables)]
+pub(crate) fn record_commit_retry(operation: &str) {
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!(COMMIT_RETRIES_TOTAL, "operation" => operation.to_string()).increment(1);
+}
+
+#[allow(unused_variables)]
+pub(crate) fn record_index_cache_lookup(hit: bool) {
+    #[cfg(feature = "metrics")]
+    if hit {
+        ::metrics::counter!(INDEX_CACHE_HITS_TOTAL).increment(1);
+    } else {
+        ::metrics::counter!(INDEX_CACHE_MISSES_TOTAL).increment(1);
+    }
+}
+
+#[allow(unused_variables)]
+pub(crate) fn record_compaction(
+    duration: Duration,
+    fragments_removed: usize,
+    fragments_added: usize,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        ::metrics::histogram!(COMPACTION_DURATION_SECONDS).record(duration.as_secs_f64());
+        ::metrics::counter!(COMPACTION_FRAGMENTS_REMOVED_TOTAL).increment(fragments_removed as u64);
+        ::metrics::counter!(COMPACTION_FRAGMENTS_ADDED_TOTAL).increment(fragments_added as u64);
+    }
+}
+
+#[cfg(all(test, feature = "prometheus"))]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow_array::{Int64Array, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{DataType, Field, Schema};
+    use futures::StreamExt;
+    use tempfile::tempdir;
+
+    use crate::dataset::optimize::compact_files;
+    use crate::dataset::WriteParams;
+    use crate::Dataset;
+
+    #[tokio::test]
+    async fn test_prometheus_metrics() {
+        let handle = install_prometheus_recorder().unwrap();
+
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int64Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let params = WriteParams {
+            max_rows_per_file: 10,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(reader, uri, Some(params)).await.unwrap();
+        dataset.scan().try_into_batch().await.unwrap();
+        // A scan that is dropped before it ends is recorded too
+        let mut stream = dataset.scan().try_into_stream().await.unwrap();
+        stream.next().await.unwrap().unwrap();
+        drop(stream);
+        assert!(handle
+            .render()
+            .contains(&format!("{}_count 2", SCAN_DURATION_SECONDS)));
+        compact_files(&mut dataset, Default::default(), None)
+            .await
+            .unwrap();
+
+        let rendered = handle.render();
+        for name in [
+            SCAN_DURATION_SECONDS,
+            SCAN_ROWS_TOTAL,
+            COMMITS_TOTAL,
+            COMMIT_DURATION_SECONDS,
+            COMPACTION_DURATION_SECONDS,
+            COMPACTION_FRAGMENTS_REMOVED_TOTAL,
+        ] {
+            assert!(
+                rendered.contains(name),
+                "{} missing from {}",
+                name,
+                rendered
+            );
+        }
+        assert!(rendered.contains("operation=\"Rewrite\",status=\"success\""));
+    }
+}
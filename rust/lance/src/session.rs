@@ -8,14 +8,25 @@ use deepsize::DeepSizeOf;
 use lance_core::cache::FileMetadataCache;
 use lance_core::{Error, Result};
 use lance_index::IndexType;
+use lance_io::object_store::ObjectStoreRegistry;
 use snafu::{location, Location};
 
 use crate::dataset::{DEFAULT_INDEX_CACHE_SIZE, DEFAULT_METADATA_CACHE_SIZE};
 use crate::index::cache::IndexCache;
+use crate::index::vector::tune::TuningCache;
 
 use self::index_extension::IndexExtension;
+use self::query_log::QueryLog;
+use self::row_cache::RowCache;
+use self::watchdog::SlowQueryWatchdog;
 
+pub mod config;
 pub mod index_extension;
+pub mod query_log;
+pub mod row_cache;
+pub mod watchdog;
+
+pub use self::config::LanceConfig;
 
 /// A user session tracks the runtime state.
 #[derive(Clone, DeepSizeOf)]
@@ -27,6 +38,24 @@ pub struct Session {
     pub(crate) file_metadata_cache: FileMetadataCache,
 
     pub(crate) index_extensions: HashMap<(IndexType, String), Arc<dyn IndexExtension>>,
+
+    /// Where executed scans are recorded, if enabled.
+    pub(crate) query_log: Option<Arc<QueryLog>>,
+
+    /// Reports the scans that run too long, if enabled.
+    pub(crate) slow_query_watchdog: Option<Arc<SlowQueryWatchdog>>,
+
+    /// Cache for the rows taken by row id, if enabled.
+    pub(crate) row_cache: Option<Arc<RowCache>>,
+
+    /// Search parameters tuned for vector indices.
+    pub(crate) tuning: Arc<TuningCache>,
+
+    /// Settings that override the environment variables.
+    pub(crate) config: LanceConfig,
+
+    /// Object stores used by the datasets opened with this session.
+    pub(crate) object_stores: Arc<ObjectStoreRegistry>,
 }
 
 impl std::fmt::Debug for Session {
@@ -46,6 +75,12 @@ impl Session {
             index_cache: IndexCache::new(index_cache_size),
             file_metadata_cache: FileMetadataCache::new(metadata_cache_size),
             index_extensions: HashMap::new(),
+            query_log: None,
+            slow_query_watchdog: None,
+            row_cache: None,
+            tuning: Arc::default(),
+            config: LanceConfig::default(),
+            object_stores: Arc::default(),
         }
     }
 
@@ -69,6 +104,12 @@ impl Session {
                     location!(),
                 ));
             }
+            IndexType::MinHash => {
+                return Err(Error::invalid_input(
+                    "minhash index extension is not supported".to_string(),
+                    location!(),
+                ));
+            }
             IndexType::Vector => {
                 if self
                     .index_extensions
@@ -95,6 +136,61 @@ impl Session {
         Ok(())
     }
 
+    /// Record every scan executed through this session into `query_log`.
+    pub fn set_query_log(&mut self, query_log: Arc<QueryLog>) {
+        self.query_log = Some(query_log);
+    }
+
+    /// The query log of this session, if any.
+    pub fn query_log(&self) -> Option<&Arc<QueryLog>> {
+        self.query_log.as_ref()
+    }
+
+    /// Report the scans executed through this session that run longer than
+    /// the threshold of `watchdog`.
+    pub fn set_slow_query_watchdog(&mut self, watchdog: Arc<SlowQueryWatchdog>) {
+        self.slow_query_watchdog = Some(watchdog);
+    }
+
+    /// The slow-query watchdog of this session, if any.
+    pub fn slow_query_watchdog(&self) -> Option<&Arc<SlowQueryWatchdog>> {
+        self.slow_query_watchdog.as_ref()
+    }
+
+    /// Cache the rows taken by row id from the datasets using this session
+    /// in `row_cache`.
+    pub fn set_row_cache(&mut self, row_cache: Arc<RowCache>) {
+        self.row_cache = Some(row_cache);
+    }
+
+    /// The row cache of this session, if any.
+    pub fn row_cache(&self) -> Option<&Arc<RowCache>> {
+        self.row_cache.as_ref()
+    }
+
+    /// Override the environment variables for the datasets using this session.
+    pub fn set_config(&mut self, config: LanceConfig) {
+        self.config = config;
+    }
+
+    /// The settings of this session.
+    pub fn config(&self) -> &LanceConfig {
+        &self.config
+    }
+
+    /// Share `registry` with other sessions.
+    pub fn set_object_store_registry(&mut self, registry: Arc<ObjectStoreRegistry>) {
+        self.object_stores = registry;
+    }
+
+    /// The object stores registered for this session.
+    ///
+    /// Datasets opened with this session use the store registered for their
+    /// URI, if any, instead of building their own.
+    pub fn object_store_registry(&self) -> &Arc<ObjectStoreRegistry> {
+        &self.object_stores
+    }
+
     /// Return the current size of the session in bytes
     pub fn size_bytes(&self) -> u64 {
         // We re-expose deep_size_of here so that users don't
@@ -109,6 +205,12 @@ impl Default for Session {
             index_cache: IndexCache::new(DEFAULT_INDEX_CACHE_SIZE),
             file_metadata_cache: FileMetadataCache::new(DEFAULT_METADATA_CACHE_SIZE),
             index_extensions: HashMap::new(),
+            query_log: None,
+            slow_query_watchdog: None,
+            row_cache: None,
+            tuning: Arc::default(),
+            config: LanceConfig::default(),
+            object_stores: Arc::default(),
         }
     }
 }
@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Runtime configuration of a session
+//!
+//! Some behavior of Lance can be tuned with environment variables, which apply
+//! to the whole process. A [`LanceConfig`] set on a [`Session`](super::Session)
+//! overrides them for the datasets opened with that session, so that several
+//! tenants of one process can be configured differently.
+//!
+//! These environment variables are not covered, and still apply to the whole
+//! process:
+//!
+//! - `LANCE_DECODE_THREADS` sizes the pool of decode threads shared by every
+//!   session. [`LanceConfig::decode_threads`] only lowers what the scans of a
+//!   session can use of it.
+//! - `LANCE_UPLOAD_CONCURRENCY` and `LANCE_CONN_RESET_RETRIES` configure the
+//!   Google Cloud Storage client. They belong with the object store, which is
+//!   created before any dataset or session is opened on it.
+//! - `LANCE_PAGE_COMPRESSION` is an experimental switch of the v2 file
+//!   encoder, which doesn't know about sessions.
+
+use deepsize::DeepSizeOf;
+use lance_datafusion::exec::LanceExecutionOptions;
+
+/// Number of concurrent I/O requests used to read a data file by default.
+pub const DEFAULT_IO_PARALLELISM: u32 = 16;
+
+/// Settings of a session that override the environment variables
+///
+/// Settings left unset fall back to their environment variable, or to the
+/// default if there isn't one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, DeepSizeOf)]
+pub struct LanceConfig {
+    /// The size, in bytes, of the memory pool of queries that can spill to
+    /// disk, such as merge inserts and scalar index training. Overrides
+    /// `LANCE_MEM_POOL_SIZE`.
+    pub mem_pool_size: Option<u64>,
+    /// Whether queries that can spill to disk should keep everything in
+    /// memory instead. Overrides `LANCE_BYPASS_SPILLING`.
+    pub bypass_spilling: Option<bool>,
+    /// The number of concurrent I/O requests used to read a data file.
+    pub io_parallelism: Option<u32>,
+    /// The number of threads each scan decodes data with, unless set on the
+    /// scan. This is on top of the process-wide `LANCE_DECODE_THREADS` limit.
+    pub decode_threads: Option<usize>,
+}
+
+impl LanceConfig {
+    pub fn with_mem_pool_size(mut self, mem_pool_size: u64) -> Self {
+        self.mem_pool_size = Some(mem_pool_size);
+        self
+    }
+
+    pub fn with_bypass_spilling(mut self, bypass_spilling: bool) -> Self {
+        self.bypass_spilling = Some(bypass_spilling);
+        self
+    }
+
+    pub fn with_io_parallelism(mut self, io_parallelism: u32) -> Self {
+        self.io_parallelism = Some(io_parallelism);
+        self
+    }
+
+    pub fn with_decode_threads(mut self, decode_threads: usize) -> Self {
+        self.decode_threads = Some(decode_threads);
+        self
+    }
+
+    /// The number of concurrent I/O requests used to read a data file
+    pub fn io_parallelism(&self) -> u32 {
+        self.io_parallelism.unwrap_or(DEFAULT_IO_PARALLELISM)
+    }
+
+    /// The options to execute a query that can spill to disk with
+    pub fn spilling_execution_options(&self) -> LanceExecutionOptions {
+        LanceExecutionOptions {
+            use_spilling: true,
+            mem_pool_size: self.mem_pool_size,
+            bypass_spilling: self.bypass_spilling,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{DataType, Field, Schema};
+
+    use super::*;
+    use crate::dataset::{builder::DatasetBuilder, WriteParams};
+    use crate::session::Session;
+    use crate::Dataset;
+
+    #[test]
+    fn test_execution_options() {
+        // Nothing is overridden by default
+        let options = LanceConfig::default().spilling_execution_options();
+        assert!(options.use_spilling);
+        assert_eq!(options.mem_pool_size, None);
+        assert_eq!(options.bypass_spilling, None);
+
+        let options = LanceConfig::default()
+            .with_mem_pool_size(1024)
+            .with_bypass_spilling(true)
+            .spilling_execution_options();
+        assert_eq!(options.mem_pool_size(), 1024);
+        assert!(!options.use_spilling());
+    }
+
+    #[tokio::test]
+    async fn test_session_config() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+
+        let mut session = Session::default();
+        session.set_config(
+            LanceConfig::default()
+                .with_io_parallelism(1)
+                .with_decode_threads(1),
+        );
+        let session = Arc::new(session);
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch.clone())], schema);
+        let params = WriteParams {
+            use_legacy_format: false,
+            session: Some(session.clone()),
+            ..Default::default()
+        };
+        let dataset = Dataset::write(reader, test_uri, Some(params))
+            .await
+            .unwrap();
+        assert_eq!(dataset.session().config().io_parallelism(), 1);
+
+        // Datasets opened with the session share its config, others don't
+        let dataset = DatasetBuilder::from_uri(test_uri)
+            .with_session(session)
+            .load()
+            .await
+            .unwrap();
+        assert_eq!(dataset.session().config().decode_threads, Some(1));
+        assert_eq!(dataset.scan().try_into_batch().await.unwrap(), batch);
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(dataset.session().config(), &LanceConfig::default());
+    }
+}
@@ -0,0 +1,442 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Query log
+//!
+//! When a [`QueryLog`] is attached to a [`Session`](super::Session), every scan
+//! executed through that session is recorded: the plan fingerprint, the filter,
+//! the latency and the amount of data read. Entries are buffered in memory and
+//! appended to a Lance dataset, which can itself be opened and queried like
+//! any other dataset. Setting a minimum latency turns it into a slow-query log.
+
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use arrow_array::builder::{
+    Float64Builder, StringBuilder, TimestampMicrosecondBuilder, UInt64Builder,
+};
+use arrow_array::{RecordBatch, RecordBatchIterator};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::logical_expr::expr::Placeholder;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::Expr;
+use deepsize::DeepSizeOf;
+use lance_core::utils::hash::fnv1a;
+use lance_io::object_store::ObjectStore;
+use log::warn;
+
+use crate::dataset::{WriteMode, WriteParams};
+use crate::{Dataset, Result};
+
+/// Number of buffered entries that triggers a background flush by default.
+pub const DEFAULT_FLUSH_THRESHOLD: usize = 128;
+
+/// A single executed scan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryLogEntry {
+    /// When the scan started
+    pub timestamp: SystemTime,
+    pub dataset_uri: String,
+    pub dataset_version: u64,
+    /// Hash of the shape of the scan, see [`plan_fingerprint`].
+    pub plan_fingerprint: String,
+    pub filter: Option<String>,
+    /// Time from the start of the scan until its stream was exhausted or dropped
+    pub latency: Duration,
+    pub rows_returned: u64,
+    /// Bytes read from the dataset's object store while the scan ran.
+    ///
+    /// This includes reads by other operations on the same object store that
+    /// ran at the same time.
+    pub bytes_read: u64,
+}
+
+/// Buffers executed scans and appends them to a Lance dataset.
+pub struct QueryLog {
+    uri: String,
+    flush_threshold: usize,
+    min_latency: Duration,
+    buffer: Mutex<Vec<QueryLogEntry>>,
+    // Serializes flushes so they don't conflict with each other
+    flush_lock: tokio::sync::Mutex<()>,
+}
+
+impl std::fmt::Debug for QueryLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "QueryLog(uri={})", self.uri)
+    }
+}
+
+impl DeepSizeOf for QueryLog {
+    fn deep_size_of_children(&self, context: &mut deepsize::Context) -> usize {
+        self.uri.deep_size_of_children(context)
+            + self.buffer.lock().unwrap().capacity() * std::mem::size_of::<QueryLogEntry>()
+    }
+}
+
+/// The schema of the query log dataset.
+pub fn query_log_schema() -> SchemaRef {
+    static SCHEMA: OnceLock<SchemaRef> = OnceLock::new();
+    SCHEMA
+        .get_or_init(|| {
+            Arc::new(Schema::new(vec![
+                Field::new(
+                    "timestamp",
+                    DataType::Timestamp(TimeUnit::Microsecond, None),
+                    false,
+                ),
+                Field::new("dataset_uri", DataType::Utf8, false),
+                Field::new("dataset_version", DataType::UInt64, false),
+                Field::new("plan_fingerprint", DataType::Utf8, false),
+                Field::new("filter", DataType::Utf8, true),
+                Field::new("latency_ms", DataType::Float64, false),
+                Field::new("rows_returned", DataType::UInt64, false),
+                Field::new("bytes_read", DataType::UInt64, false),
+            ]))
+        })
+        .clone()
+}
+
+/// Fingerprint of a scan, as a hex string.
+///
+/// Scans of the same shape share a fingerprint: it hashes the nodes of the
+/// physical plan, the output columns and the filter with its literals left
+/// out, so scans that only differ in the values they filter on or in their
+/// limits are grouped together. The hash is stable across processes, so
+/// fingerprints of different log entries can be compared, but only between
+/// entries written by the same versions of Lance and DataFusion: it hashes
+/// the names of the plan nodes and the rendering of the filter and of the
+/// column types, which other versions may change.
+pub fn plan_fingerprint(plan: &dyn ExecutionPlan, filter: Option<&Expr>) -> String {
+    fn render_nodes(plan: &dyn ExecutionPlan, depth: usize, out: &mut String) {
+        writeln!(out, "{:indent$}{}", "", plan.name(), indent = depth * 2).unwrap();
+        for child in plan.children() {
+            render_nodes(child.as_ref(), depth + 1, out);
+        }
+    }
+
+    let mut rendered = String::new();
+    render_nodes(plan, 0, &mut rendered);
+    for field in plan.schema().fields() {
+        writeln!(rendered, "{}: {}", field.name(), field.data_type()).unwrap();
+    }
+    if let Some(filter) = filter {
+        let stripped = filter
+            .clone()
+            .transform(&|expr| match expr {
+                Expr::Literal(_) => Ok(Transformed::yes(Expr::Placeholder(Placeholder::new(
+                    "?".to_string(),
+                    None,
+                )))),
+                expr => Ok(Transformed::no(expr)),
+            })
+            .map(|stripped| stripped.data)
+            .unwrap_or_else(|_| filter.clone());
+        writeln!(rendered, "{}", stripped).unwrap();
+    }
+    format!("{:016x}", fnv1a(rendered.as_bytes()))
+}
+
+impl QueryLog {
+    /// Create a query log that writes to the dataset at `uri`.
+    ///
+    /// The dataset is created on the first flush if it does not exist.
+    pub fn new(uri: impl Into<String>) -> Self {
+        Self {
+            uri: uri.into(),
+            flush_threshold: DEFAULT_FLUSH_THRESHOLD,
+            min_latency: Duration::ZERO,
+            buffer: Mutex::new(Vec::new()),
+            flush_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Number of buffered entries that triggers a flush in the background.
+    pub fn with_flush_threshold(mut self, flush_threshold: usize) -> Self {
+        self.flush_threshold = flush_threshold.max(1);
+        self
+    }
+
+    /// Only record scans that take at least `min_latency`.
+    pub fn with_min_latency(mut self, min_latency: Duration) -> Self {
+        self.min_latency = min_latency;
+        self
+    }
+
+    /// The URI of the query log dataset.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Number of entries waiting to be flushed.
+    pub fn num_buffered(&self) -> usize {
+        self.buffer.lock().unwrap().len()
+    }
+
+    /// Record a scan.
+    ///
+    /// If the buffer reaches the flush threshold and a Tokio runtime is
+    /// available, the buffer is flushed in the background. Failures of
+    /// background flushes are logged and the entries are dropped.
+    pub fn record(self: &Arc<Self>, entry: QueryLogEntry) {
+        if entry.latency < self.min_latency {
+            return;
+        }
+        let should_flush = {
+            let mut buffer = self.buffer.lock().unwrap();
+            buffer.push(entry);
+            buffer.len() >= self.flush_threshold
+        };
+        if should_flush {
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                let log = self.clone();
+                handle.spawn(async move {
+                    if let Err(err) = log.flush().await {
+                        warn!("Failed to flush query log to {}: {}", log.uri, err);
+                    }
+                });
+            }
+        }
+    }
+
+    /// Append all buffered entries to the query log dataset.
+    pub async fn flush(&self) -> Result<()> {
+        let _guard = self.flush_lock.lock().await;
+        let entries = std::mem::take(&mut *self.buffer.lock().unwrap());
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let batch = entries_to_batch(&entries)?;
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], query_log_schema());
+        let params = WriteParams {
+            mode: WriteMode::Append,
+            ..Default::default()
+        };
+        Dataset::write(reader, &self.uri, Some(params)).await?;
+        Ok(())
+    }
+
+    /// Open the query log dataset, to query it.
+    ///
+    /// Entries still buffered in memory are not visible until flushed.
+    pub async fn open(&self) -> Result<Dataset> {
+        Dataset::open(&self.uri).await
+    }
+}
+
+fn entries_to_batch(entries: &[QueryLogEntry]) -> Result<RecordBatch> {
+    let mut timestamp = TimestampMicrosecondBuilder::with_capacity(entries.len());
+    let mut dataset_uri = StringBuilder::new();
+    let mut dataset_version = UInt64Builder::with_capacity(entries.len());
+    let mut plan_fingerprint = StringBuilder::new();
+    let mut filter = StringBuilder::new();
+    let mut latency_ms = Float64Builder::with_capacity(entries.len());
+    let mut rows_returned = UInt64Builder::with_capacity(entries.len());
+    let mut bytes_read = UInt64Builder::with_capacity(entries.len());
+
+    for entry in entries {
+        let micros = entry
+            .timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as i64;
+        timestamp.append_value(micros);
+        dataset_uri.append_value(&entry.dataset_uri);
+        dataset_version.append_value(entry.dataset_version);
+        plan_fingerprint.append_value(&entry.plan_fingerprint);
+        filter.append_option(entry.filter.as_ref());
+        latency_ms.append_value(entry.latency.as_secs_f64() * 1000.0);
+        rows_returned.append_value(entry.rows_returned);
+        bytes_read.append_value(entry.bytes_read);
+    }
+
+    Ok(RecordBatch::try_new(
+        query_log_schema(),
+        vec![
+            Arc::new(timestamp.finish()),
+            Arc::new(dataset_uri.finish()),
+            Arc::new(dataset_version.finish()),
+            Arc::new(plan_fingerprint.finish()),
+            Arc::new(filter.finish()),
+            Arc::new(latency_ms.finish()),
+            Arc::new(rows_returned.finish()),
+            Arc::new(bytes_read.finish()),
+        ],
+    )?)
+}
+
+/// Tracks a running scan and records it into the query log once done.
+pub(crate) struct QueryRecorder {
+    log: Arc<QueryLog>,
+    entry: Option<QueryLogEntry>,
+    started: std::time::Instant,
+    object_store: Arc<ObjectStore>,
+    // Bytes read by the object store when the scan started
+    bytes_read_at_start: u64,
+}
+
+impl QueryRecorder {
+    pub(crate) fn new(
+        log: Arc<QueryLog>,
+        dataset: &Dataset,
+        plan: &dyn ExecutionPlan,
+        filter: Option<&Expr>,
+    ) -> Self {
+        let entry = QueryLogEntry {
+            timestamp: SystemTime::now(),
+            dataset_uri: dataset.uri().to_string(),
+            dataset_version: dataset.version().version,
+            plan_fingerprint: plan_fingerprint(plan, filter),
+            filter: filter.map(|filter| filter.to_string()),
+            latency: Duration::ZERO,
+            rows_returned: 0,
+            bytes_read: 0,
+        };
+        Self {
+            log,
+            entry: Some(entry),
+            started: std::time::Instant::now(),
+            object_store: dataset.object_store.clone(),
+            bytes_read_at_start: dataset.object_store.bytes_read(),
+        }
+    }
+
+    pub(crate) fn observe(&mut self, batch: &RecordBatch) {
+        if let Some(entry) = self.entry.as_mut() {
+            entry.rows_returned += batch.num_rows() as u64;
+        }
+    }
+
+    /// Record the scan. Only the first call has any effect.
+    pub(crate) fn finish(&mut self) {
+        if let Some(mut entry) = self.entry.take() {
+            entry.latency = self.started.elapsed();
+            entry.bytes_read = self.object_store.bytes_read() - self.bytes_read_at_start;
+            self.log.record(entry);
+        }
+    }
+}
+
+impl Drop for QueryRecorder {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::UInt64Type;
+    use arrow_array::{Array, Int32Array};
+    use futures::TryStreamExt;
+    use tempfile::tempdir;
+
+    use crate::dataset::builder::DatasetBuilder;
+    use crate::session::Session;
+
+    #[tokio::test]
+    async fn test_query_log() {
+        let test_dir = tempdir().unwrap();
+        let data_uri = test_dir.path().join("data").to_str().unwrap().to_string();
+        let log_uri = test_dir.path().join("log").to_str().unwrap().to_string();
+
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        Dataset::write(reader, &data_uri, None).await.unwrap();
+
+        let log = Arc::new(QueryLog::new(&log_uri));
+        let mut session = Session::default();
+        session.set_query_log(log.clone());
+        let dataset = DatasetBuilder::from_uri(&data_uri)
+            .with_session(Arc::new(session))
+            .load()
+            .await
+            .unwrap();
+
+        let batches = dataset
+            .scan()
+            .filter("i < 10")
+            .unwrap()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10);
+        dataset.scan().try_into_batch().await.unwrap();
+        assert_eq!(log.num_buffered(), 2);
+
+        log.flush().await.unwrap();
+        assert_eq!(log.num_buffered(), 0);
+
+        let log_dataset = log.open().await.unwrap();
+        let entries = log_dataset.scan().try_into_batch().await.unwrap();
+        assert_eq!(entries.num_rows(), 2);
+        assert_eq!(entries.schema(), query_log_schema());
+        let rows = entries["rows_returned"].as_primitive::<UInt64Type>();
+        assert_eq!(rows.values(), &[10, 100]);
+        // Both scans read the whole column from disk, the first one also
+        // reads the file metadata
+        let bytes_read = entries["bytes_read"].as_primitive::<UInt64Type>();
+        assert!(bytes_read.value(0) > bytes_read.value(1));
+        assert!(bytes_read.value(1) >= 400);
+        let filters = entries["filter"].as_string::<i32>();
+        assert!(filters.value(0).starts_with("i < "));
+        assert!(filters.is_null(1));
+        let fingerprints = entries["plan_fingerprint"].as_string::<i32>();
+        assert_ne!(fingerprints.value(0), fingerprints.value(1));
+
+        // Slow query log: nothing is as slow as an hour
+        let log = QueryLog::new(&log_uri).with_min_latency(Duration::from_secs(3600));
+        let log = Arc::new(log);
+        let recorder = QueryRecorder::new(
+            log.clone(),
+            &dataset,
+            &*dataset.scan().create_plan().await.unwrap(),
+            None,
+        );
+        drop(recorder);
+        assert_eq!(log.num_buffered(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_plan_fingerprint() {
+        let test_dir = tempdir().unwrap();
+        let uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..100))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let dataset = Dataset::write(reader, uri, None).await.unwrap();
+
+        async fn fingerprint(dataset: &Dataset, filter: &str, limit: i64) -> String {
+            let mut scanner = dataset.scan();
+            scanner
+                .filter(filter)
+                .unwrap()
+                .limit(Some(limit), None)
+                .unwrap();
+            let plan = scanner.create_plan().await.unwrap();
+            plan_fingerprint(plan.as_ref(), scanner.filter.as_ref())
+        }
+
+        // Scans that only differ in their literals share a fingerprint
+        let fingerprint1 = fingerprint(&dataset, "i < 10", 5).await;
+        assert_eq!(fingerprint1, fingerprint(&dataset, "i < 50", 20).await);
+        assert_ne!(fingerprint1, fingerprint(&dataset, "i > 10", 5).await);
+        assert_eq!(fingerprint1.len(), 16);
+    }
+}
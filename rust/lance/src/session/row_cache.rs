@@ -0,0 +1,244 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Cache of the rows read by [`Dataset::take_rows`](crate::Dataset::take_rows)
+//!
+//! Serving workloads often take the same rows over and over. When a
+//! [`RowCache`] is attached to a [`Session`](super::Session), the rows taken
+//! by row id from the datasets of the session are kept in memory, so later
+//! takes of the same rows, or of rows near them, don't read them again.
+//!
+//! Rows are read and cached in chunks of [`CHUNK_ROWS`] consecutive rows of
+//! a fragment, keyed by the dataset, the identity of its manifest (its
+//! version and timestamp), the fragment, the position of the chunk in the
+//! fragment and the projection. A chunk is a single batch, so the overhead
+//! of caching is per chunk rather than per row.
+//!
+//! Rows of a dataset are only cached for its latest manifest seen by the
+//! cache. Once a newer manifest of a dataset is taken from, for example a
+//! newer version or a dataset recreated at the same location, the rows of
+//! the older manifests are evicted, and takes from older manifests bypass the
+//! cache. The chunks are evicted least recently used first once their size
+//! exceeds the budget of the cache.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use arrow_array::RecordBatch;
+use deepsize::DeepSizeOf;
+use lance_core::datatypes::Schema;
+use lance_table::format::Manifest;
+use moka::sync::{Cache, ConcurrentCacheExt};
+
+/// The number of consecutive rows of a fragment read and cached together
+pub const CHUNK_ROWS: u32 = 64;
+
+/// Identifies a manifest of a dataset, and orders the manifests by age
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct ManifestId {
+    timestamp_nanos: u128,
+    version: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ChunkKey {
+    dataset: String,
+    manifest: ManifestId,
+    fragment_id: u64,
+    chunk: u32,
+    projection: u64,
+}
+
+/// The rows of a chunk that exist, such as the rows that aren't deleted
+#[derive(Debug, Clone)]
+pub(crate) struct RowChunk {
+    /// The sorted row ids of the rows
+    row_ids: Arc<[u64]>,
+    rows: RecordBatch,
+}
+
+impl RowChunk {
+    pub(crate) fn new(row_ids: Arc<[u64]>, rows: RecordBatch) -> Self {
+        Self { row_ids, rows }
+    }
+
+    pub(crate) fn rows(&self) -> &RecordBatch {
+        &self.rows
+    }
+
+    /// The position of the row `row_id` in the rows, if it exists
+    pub(crate) fn position(&self, row_id: u64) -> Option<usize> {
+        self.row_ids.binary_search(&row_id).ok()
+    }
+}
+
+/// An in-memory cache of rows taken by row id, see the [module docs](self).
+pub struct RowCache {
+    chunks: Cache<ChunkKey, RowChunk>,
+    /// The latest manifest seen of each dataset
+    manifests: Mutex<HashMap<String, ManifestId>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl std::fmt::Debug for RowCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "RowCache(chunks={}, bytes={})",
+            self.chunks.entry_count(),
+            self.chunks.weighted_size()
+        )
+    }
+}
+
+impl DeepSizeOf for RowCache {
+    fn deep_size_of_children(&self, _context: &mut deepsize::Context) -> usize {
+        self.chunks.weighted_size() as usize
+    }
+}
+
+impl RowCache {
+    /// A cache holding up to `capacity_bytes` of rows.
+    pub fn new(capacity_bytes: u64) -> Self {
+        Self {
+            chunks: Cache::builder()
+                .max_capacity(capacity_bytes)
+                .weigher(|_, chunk: &RowChunk| {
+                    (chunk.rows.get_array_memory_size() + chunk.row_ids.len() * 8)
+                        .try_into()
+                        .unwrap_or(u32::MAX)
+                })
+                .support_invalidation_closures()
+                .build(),
+            manifests: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The number of chunks of rows cached.
+    pub fn len(&self) -> u64 {
+        self.chunks.sync();
+        self.chunks.entry_count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The size of the rows cached, in bytes.
+    pub fn size_bytes(&self) -> u64 {
+        self.chunks.sync();
+        self.chunks.weighted_size()
+    }
+
+    /// The number of rows taken from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of rows taken that weren't cached.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Evict all the rows.
+    pub fn clear(&self) {
+        self.chunks.invalidate_all();
+    }
+
+    /// The key of the rows of `projection` in `manifest` of `dataset`, or
+    /// `None` if the rows of that manifest must not be cached.
+    ///
+    /// Seeing a newer manifest of `dataset` evicts the rows of its older
+    /// manifests.
+    pub(crate) fn prefix(
+        &self,
+        dataset: &str,
+        manifest: &Manifest,
+        projection: &Schema,
+    ) -> Option<RowCachePrefix> {
+        let manifest = ManifestId {
+            timestamp_nanos: manifest.timestamp_nanos,
+            version: manifest.version,
+        };
+        let mut manifests = self.manifests.lock().unwrap();
+        match manifests.get(dataset) {
+            Some(latest) if *latest > manifest => return None,
+            Some(latest) if *latest == manifest => {}
+            _ => {
+                manifests.insert(dataset.to_string(), manifest);
+                let dataset = dataset.to_string();
+                // Only fails if invalidation closures aren't supported
+                self.chunks
+                    .invalidate_entries_if(move |key, _| {
+                        key.dataset == dataset && key.manifest != manifest
+                    })
+                    .unwrap();
+            }
+        }
+
+        let mut hasher = DefaultHasher::new();
+        projection.field_ids().hash(&mut hasher);
+        Some(RowCachePrefix {
+            dataset: dataset.to_string(),
+            manifest,
+            projection: hasher.finish(),
+        })
+    }
+
+    /// The chunk `chunk` of fragment `fragment_id` of the rows of `prefix`.
+    ///
+    /// `num_rows` is the number of rows taken from the chunk, which are
+    /// counted as hits or misses.
+    pub(crate) fn get(
+        &self,
+        prefix: &RowCachePrefix,
+        fragment_id: u64,
+        chunk: u32,
+        num_rows: u64,
+    ) -> Option<RowChunk> {
+        let rows = self.chunks.get(&prefix.key(fragment_id, chunk));
+        if rows.is_some() {
+            self.hits.fetch_add(num_rows, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(num_rows, Ordering::Relaxed);
+        }
+        rows
+    }
+
+    /// Cache `rows` as the chunk `chunk` of fragment `fragment_id` of the rows
+    /// of `prefix`.
+    pub(crate) fn insert(
+        &self,
+        prefix: &RowCachePrefix,
+        fragment_id: u64,
+        chunk: u32,
+        rows: RowChunk,
+    ) {
+        self.chunks.insert(prefix.key(fragment_id, chunk), rows);
+    }
+}
+
+/// Identifies the rows of a projection of a manifest of a dataset
+pub(crate) struct RowCachePrefix {
+    dataset: String,
+    manifest: ManifestId,
+    projection: u64,
+}
+
+impl RowCachePrefix {
+    fn key(&self, fragment_id: u64, chunk: u32) -> ChunkKey {
+        ChunkKey {
+            dataset: self.dataset.clone(),
+            manifest: self.manifest,
+            fragment_id,
+            chunk,
+            projection: self.projection,
+        }
+    }
+}
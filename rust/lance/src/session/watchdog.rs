@@ -0,0 +1,265 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Slow-query watchdog
+//!
+//! When a [`SlowQueryWatchdog`] is attached to a [`Session`](super::Session),
+//! every scan executed through that session that is still running after the
+//! watchdog's threshold is reported once: the plan with the metrics its nodes
+//! recorded so far, and the bytes the scan read and the rows it returned. The
+//! report is logged as a warning and passed to the watchdog's callback, if
+//! any. Reports are made while the scan is running, so they capture scans that
+//! stall, not only slow scans that eventually finish.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow_array::RecordBatch;
+use datafusion::physical_plan::{display::DisplayableExecutionPlan, ExecutionPlan};
+use deepsize::DeepSizeOf;
+use log::warn;
+use tokio::task::JoinHandle;
+
+use crate::io::exec::PlanMetrics;
+use crate::Dataset;
+
+/// Called with the report of each slow scan
+pub type SlowQueryCallback = Arc<dyn Fn(&SlowQueryReport) + Send + Sync>;
+
+/// A scan that ran longer than the threshold of a [`SlowQueryWatchdog`].
+#[derive(Debug, Clone)]
+pub struct SlowQueryReport {
+    pub dataset_uri: String,
+    pub dataset_version: u64,
+    pub filter: Option<String>,
+    /// Time since the scan started
+    pub elapsed: Duration,
+    /// The plan, with the metrics of its nodes so far
+    pub plan: String,
+    pub metrics: PlanMetrics,
+    /// In-memory size of the batches the nodes of the plan decoded so far
+    pub decoded_bytes: u64,
+    pub rows_returned: u64,
+    pub batches_returned: u64,
+}
+
+/// Reports the scans of a session that run longer than a threshold.
+pub struct SlowQueryWatchdog {
+    threshold: Duration,
+    callback: Option<SlowQueryCallback>,
+}
+
+impl std::fmt::Debug for SlowQueryWatchdog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SlowQueryWatchdog(threshold={:?})", self.threshold)
+    }
+}
+
+impl DeepSizeOf for SlowQueryWatchdog {
+    fn deep_size_of_children(&self, _context: &mut deepsize::Context) -> usize {
+        0
+    }
+}
+
+impl SlowQueryWatchdog {
+    /// Report scans still running `threshold` after they started.
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            callback: None,
+        }
+    }
+
+    /// Pass the reports to `callback`, in addition to logging them.
+    pub fn with_callback(
+        mut self,
+        callback: impl Fn(&SlowQueryReport) + Send + Sync + 'static,
+    ) -> Self {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    /// Watch the execution of `plan`, a scan of `dataset`.
+    pub(crate) fn watch(
+        self: &Arc<Self>,
+        dataset: &Dataset,
+        plan: Arc<dyn ExecutionPlan>,
+        filter: Option<String>,
+    ) -> ScanWatch {
+        let progress = Arc::new(ScanProgress::default());
+        let watchdog = self.clone();
+        let dataset_uri = dataset.uri().to_string();
+        let dataset_version = dataset.version().version;
+        let task_progress = progress.clone();
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(watchdog.threshold).await;
+            let metrics = PlanMetrics::from_plan(plan.as_ref());
+            let report = SlowQueryReport {
+                dataset_uri,
+                dataset_version,
+                filter,
+                elapsed: watchdog.threshold,
+                plan: DisplayableExecutionPlan::with_metrics(plan.as_ref())
+                    .indent(true)
+                    .to_string(),
+                decoded_bytes: total_decoded_bytes(&metrics),
+                metrics,
+                rows_returned: task_progress.rows.load(Ordering::Relaxed),
+                batches_returned: task_progress.batches.load(Ordering::Relaxed),
+            };
+            watchdog.report(&report);
+        });
+        ScanWatch { progress, task }
+    }
+
+    fn report(&self, report: &SlowQueryReport) {
+        warn!(
+            "Scan of {} (version {}) with filter {:?} still running after {:?}, \
+             {} rows returned and {} bytes decoded so far:\n{}",
+            report.dataset_uri,
+            report.dataset_version,
+            report.filter,
+            report.elapsed,
+            report.rows_returned,
+            report.decoded_bytes,
+            report.plan
+        );
+        if let Some(callback) = &self.callback {
+            callback(report);
+        }
+    }
+}
+
+fn total_decoded_bytes(metrics: &PlanMetrics) -> u64 {
+    metrics.decoded_bytes.unwrap_or(0) as u64
+        + metrics
+            .children
+            .iter()
+            .map(total_decoded_bytes)
+            .sum::<u64>()
+}
+
+#[derive(Debug, Default)]
+struct ScanProgress {
+    rows: AtomicU64,
+    batches: AtomicU64,
+}
+
+/// Tracks a running scan, until it is done or dropped.
+pub(crate) struct ScanWatch {
+    progress: Arc<ScanProgress>,
+    task: JoinHandle<()>,
+}
+
+impl ScanWatch {
+    pub(crate) fn observe(&self, batch: &RecordBatch) {
+        self.progress
+            .rows
+            .fetch_add(batch.num_rows() as u64, Ordering::Relaxed);
+        self.progress.batches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Stop watching the scan, which is done.
+    pub(crate) fn finish(&self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for ScanWatch {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use arrow_array::{Int32Array, RecordBatchIterator};
+    use arrow_schema::{DataType, Field, Schema};
+    use futures::StreamExt;
+    use tempfile::tempdir;
+
+    use crate::dataset::builder::DatasetBuilder;
+    use crate::session::Session;
+
+    #[tokio::test]
+    async fn test_slow_query_watchdog() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..1000))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        Dataset::write(reader, test_uri, None).await.unwrap();
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let captured = reports.clone();
+        let mut session = Session::default();
+        session.set_slow_query_watchdog(Arc::new(
+            SlowQueryWatchdog::new(Duration::from_millis(200)).with_callback(move |report| {
+                captured.lock().unwrap().push(report.clone());
+            }),
+        ));
+        let dataset = DatasetBuilder::from_uri(test_uri)
+            .with_session(Arc::new(session))
+            .load()
+            .await
+            .unwrap();
+
+        // A scan that finishes before the threshold isn't reported
+        let batches = dataset
+            .scan()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(batches.len(), 1);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(reports.lock().unwrap().is_empty());
+
+        // A scan that stalls is reported while it is running
+        let mut scanner = dataset.scan();
+        scanner.filter("i >= 500").unwrap().batch_size(100);
+        let mut stream = scanner.try_into_stream().await.unwrap();
+        let first = stream.next().await.unwrap().unwrap();
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        {
+            let reports = reports.lock().unwrap();
+            assert_eq!(reports.len(), 1);
+            let report = &reports[0];
+            assert_eq!(report.dataset_uri, dataset.uri());
+            assert!(report.filter.is_some());
+            assert_eq!(report.rows_returned, first.num_rows() as u64);
+            assert_eq!(report.batches_returned, 1);
+            assert!(report.plan.contains("Lance"));
+
+            // The metrics are those of the running nodes
+            fn find<'a>(metrics: &'a PlanMetrics, name: &str) -> Option<&'a PlanMetrics> {
+                if metrics.name == name {
+                    return Some(metrics);
+                }
+                metrics.children.iter().find_map(|child| find(child, name))
+            }
+            let filter = find(&report.metrics, "FilterExec").unwrap();
+            assert!(filter.output_rows.unwrap() >= first.num_rows());
+            let scan = find(&report.metrics, "LanceScan").unwrap();
+            assert!(scan.output_rows.unwrap() >= first.num_rows());
+            assert!(scan.decoded_bytes.unwrap() > 0);
+            assert_eq!(report.decoded_bytes, total_decoded_bytes(&report.metrics));
+            assert!(report.decoded_bytes > 0);
+        }
+        drop(stream);
+    }
+}
@@ -3,6 +3,8 @@
 
 //! Various utilities
 
+#[cfg(any(test, feature = "testing"))]
+pub mod fixture;
 pub(crate) mod future;
 pub mod sql;
 pub(crate) mod temporal;
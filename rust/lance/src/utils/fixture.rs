@@ -0,0 +1,438 @@
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-FileCopyrightText: Copyright The Lance Authors
+
+//! Datasets with precise layouts, for tests
+//!
+//! [`DatasetFixture`] builds a dataset by replaying a list of steps: appending
+//! fragments of exact sizes, deleting rows at exact offsets, and creating
+//! indices. Each step commits one version, so the dataset it builds has a
+//! known history, with the fragments, deletion files and index coverage the
+//! test needs.
+//!
+//! The data is generated from a seed, so a fixture always builds the same
+//! dataset. Rows have an `id` column numbering them from 0 in the order they
+//! are appended, a `text` column derived from the id and, optionally, a
+//! random `vec` column.
+//!
+//! ```ignore
+//! let dataset = DatasetFixture::new()
+//!     .with_vectors(16)
+//!     .append(&[300, 200]) // version 1: fragments 0 and 1
+//!     .scalar_index("id", "id_idx") // version 2: indexes fragments 0 and 1
+//!     .delete(1, &[0, 1, 2]) // version 3: a deletion file in fragment 1
+//!     .append(&[50]) // version 4: fragment 2, not indexed
+//!     .build("/tmp/fixture")
+//!     .await?;
+//! ```
+
+use std::sync::Arc;
+
+use arrow_array::{
+    FixedSizeListArray, Float32Array, Int64Array, RecordBatch, RecordBatchIterator, StringArray,
+};
+use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema, SchemaRef};
+use lance_arrow::FixedSizeListArrayExt;
+use lance_core::datatypes::Schema;
+use lance_index::{DatasetIndexExt, IndexType};
+use lance_linalg::distance::MetricType;
+use rand::{Rng, SeedableRng};
+use snafu::{location, Location};
+
+use crate::dataset::fragment::write::FragmentCreateBuilder;
+use crate::dataset::transaction::Operation;
+use crate::dataset::WriteParams;
+use crate::index::scalar::ScalarIndexParams;
+use crate::index::vector::VectorIndexParams;
+use crate::{Dataset, Error, Result};
+
+/// Name of the column numbering the rows
+pub const ID_COLUMN: &str = "id";
+/// Name of the string column, `"row-<id>"`
+pub const TEXT_COLUMN: &str = "text";
+/// Name of the vector column, see [`DatasetFixture::with_vectors`]
+pub const VECTOR_COLUMN: &str = "vec";
+
+#[derive(Debug, Clone)]
+enum FixtureStep {
+    Append(Vec<usize>),
+    Delete {
+        fragment_id: u64,
+        offsets: Vec<u32>,
+    },
+    ScalarIndex {
+        column: String,
+        name: String,
+    },
+    VectorIndex {
+        name: String,
+        num_partitions: usize,
+        num_sub_vectors: usize,
+    },
+}
+
+/// Builds a dataset with a precise layout and history.
+///
+/// Steps are replayed in the order they are added, and each commits exactly
+/// one version, so the dataset built from `n` steps has versions `1..=n`,
+/// where version `i` is the state after step `i`. Fragments get ids in the
+/// order they are appended, starting from 0.
+#[derive(Debug, Clone)]
+pub struct DatasetFixture {
+    seed: u64,
+    vector_dim: Option<usize>,
+    write_params: WriteParams,
+    steps: Vec<FixtureStep>,
+}
+
+impl Default for DatasetFixture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DatasetFixture {
+    pub fn new() -> Self {
+        Self {
+            seed: 42,
+            vector_dim: None,
+            write_params: WriteParams::default(),
+            steps: vec![],
+        }
+    }
+
+    /// Set the seed the vectors are generated from. Defaults to 42.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Add a `vec` column of random float32 vectors with `dim` dimensions.
+    pub fn with_vectors(mut self, dim: usize) -> Self {
+        self.vector_dim = Some(dim);
+        self
+    }
+
+    /// Set the parameters the fragments are written with.
+    ///
+    /// The file format and the object store parameters are used, but the
+    /// write mode and the file size limits are not: each fragment is written
+    /// as a single data file.
+    pub fn with_write_params(mut self, params: WriteParams) -> Self {
+        self.write_params = params;
+        self
+    }
+
+    /// Append one fragment for each of `fragment_rows`, with that many rows.
+    ///
+    /// The first step must be an append, which creates the dataset.
+    pub fn append(mut self, fragment_rows: &[usize]) -> Self {
+        self.steps.push(FixtureStep::Append(fragment_rows.to_vec()));
+        self
+    }
+
+    /// Delete the rows at `offsets` of the fragment with id `fragment_id`.
+    ///
+    /// At least one of the rows must not have been deleted already.
+    pub fn delete(mut self, fragment_id: u64, offsets: &[u32]) -> Self {
+        self.steps.push(FixtureStep::Delete {
+            fragment_id,
+            offsets: offsets.to_vec(),
+        });
+        self
+    }
+
+    /// Create a scalar index named `name` on `column`.
+    ///
+    /// The index covers the fragments appended before it, and replaces any
+    /// index with the same name.
+    pub fn scalar_index(mut self, column: &str, name: &str) -> Self {
+        self.steps.push(FixtureStep::ScalarIndex {
+            column: column.to_string(),
+            name: name.to_string(),
+        });
+        self
+    }
+
+    /// Create an IVF_PQ index named `name` on the `vec` column.
+    ///
+    /// The index covers the fragments appended before it, and replaces any
+    /// index with the same name. Training PQ needs at least 256 rows.
+    pub fn vector_index(
+        mut self,
+        name: &str,
+        num_partitions: usize,
+        num_sub_vectors: usize,
+    ) -> Self {
+        self.steps.push(FixtureStep::VectorIndex {
+            name: name.to_string(),
+            num_partitions,
+            num_sub_vectors,
+        });
+        self
+    }
+
+    /// The schema of the datasets this fixture builds
+    pub fn schema(&self) -> SchemaRef {
+        let mut fields = vec![
+            ArrowField::new(ID_COLUMN, DataType::Int64, false),
+            ArrowField::new(TEXT_COLUMN, DataType::Utf8, false),
+        ];
+        if let Some(dim) = self.vector_dim {
+            fields.push(ArrowField::new(
+                VECTOR_COLUMN,
+                DataType::FixedSizeList(
+                    Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                    dim as i32,
+                ),
+                false,
+            ));
+        }
+        Arc::new(ArrowSchema::new(fields))
+    }
+
+    /// Build the dataset at `uri`, which must not hold a dataset yet.
+    ///
+    /// Fragments are written before they are committed, so `uri` must not
+    /// be a `memory://` URI, which opens a new store each time.
+    pub async fn build(&self, uri: &str) -> Result<Dataset> {
+        let arrow_schema = self.schema();
+        let schema = Schema::try_from(arrow_schema.as_ref())?;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(self.seed);
+        let mut dataset: Option<Dataset> = None;
+        let mut next_id = 0;
+
+        for step in &self.steps {
+            let version = dataset.as_ref().map(|dataset| dataset.version().version);
+            dataset = Some(match (step, dataset) {
+                (FixtureStep::Append(fragment_rows), dataset) => {
+                    if fragment_rows.is_empty() || fragment_rows.contains(&0) {
+                        return Err(Error::invalid_input(
+                            "appended fragments must have at least one row",
+                            location!(),
+                        ));
+                    }
+                    let mut fragments = Vec::with_capacity(fragment_rows.len());
+                    for &num_rows in fragment_rows {
+                        let batch =
+                            self.make_batch(arrow_schema.clone(), next_id, num_rows, &mut rng)?;
+                        next_id += num_rows as i64;
+                        let reader =
+                            RecordBatchIterator::new(vec![Ok(batch)], arrow_schema.clone());
+                        let fragment = FragmentCreateBuilder::new(uri)
+                            .schema(&schema)
+                            .write_params(&self.write_params)
+                            .write(reader, None)
+                            .await?;
+                        fragments.push(fragment);
+                    }
+                    let operation = if dataset.is_some() {
+                        Operation::Append { fragments }
+                    } else {
+                        Operation::Overwrite {
+                            fragments,
+                            schema: schema.clone(),
+                        }
+                    };
+                    Dataset::commit(
+                        uri,
+                        operation,
+                        version,
+                        self.write_params.store_params.clone(),
+                        self.write_params.commit_handler.clone(),
+                    )
+                    .await?
+                }
+                (_, None) => {
+                    return Err(Error::invalid_input(
+                        "the first step of a fixture must append fragments",
+                        location!(),
+                    ));
+                }
+                (
+                    FixtureStep::Delete {
+                        fragment_id,
+                        offsets,
+                    },
+                    Some(mut dataset),
+                ) => {
+                    let row_ids = offsets
+                        .iter()
+                        .map(|&offset| (*fragment_id << 32) | offset as u64);
+                    dataset.delete_rows(row_ids).await?;
+                    if Some(dataset.version().version) == version {
+                        return Err(Error::invalid_input(
+                            format!(
+                                "deleting {:?} of fragment {} deleted no rows",
+                                offsets, fragment_id
+                            ),
+                            location!(),
+                        ));
+                    }
+                    dataset
+                }
+                (FixtureStep::ScalarIndex { column, name }, Some(mut dataset)) => {
+                    dataset
+                        .create_index(
+                            &[column.as_str()],
+                            IndexType::Scalar,
+                            Some(name.clone()),
+                            &ScalarIndexParams::default(),
+                            true,
+                        )
+                        .await?;
+                    dataset
+                }
+                (
+                    FixtureStep::VectorIndex {
+                        name,
+                        num_partitions,
+                        num_sub_vectors,
+                    },
+                    Some(mut dataset),
+                ) => {
+                    if self.vector_dim.is_none() {
+                        return Err(Error::invalid_input(
+                            "a vector index needs the vector column, see with_vectors",
+                            location!(),
+                        ));
+                    }
+                    let params = VectorIndexParams::ivf_pq(
+                        *num_partitions,
+                        8,
+                        *num_sub_vectors,
+                        MetricType::L2,
+                        50,
+                    );
+                    dataset
+                        .create_index(
+                            &[VECTOR_COLUMN],
+                            IndexType::Vector,
+                            Some(name.clone()),
+                            &params,
+                            true,
+                        )
+                        .await?;
+                    dataset
+                }
+            });
+        }
+
+        dataset
+            .ok_or_else(|| Error::invalid_input("a fixture needs at least one step", location!()))
+    }
+
+    fn make_batch(
+        &self,
+        schema: SchemaRef,
+        first_id: i64,
+        num_rows: usize,
+        rng: &mut impl Rng,
+    ) -> Result<RecordBatch> {
+        let ids = first_id..first_id + num_rows as i64;
+        let mut columns: Vec<Arc<dyn arrow_array::Array>> = vec![
+            Arc::new(Int64Array::from_iter_values(ids.clone())),
+            Arc::new(StringArray::from_iter_values(
+                ids.map(|id| format!("row-{}", id)),
+            )),
+        ];
+        if let Some(dim) = self.vector_dim {
+            let values =
+                Float32Array::from_iter_values((0..num_rows * dim).map(|_| rng.gen::<f32>()));
+            columns.push(Arc::new(FixedSizeListArray::try_new_from_values(
+                values, dim as i32,
+            )?));
+        }
+        Ok(RecordBatch::try_new(schema, columns)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::cast::AsArray;
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dataset_fixture() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let uri = |name: &str| tmp_dir.path().join(name).to_str().unwrap().to_string();
+        let fixture = DatasetFixture::new()
+            .with_vectors(16)
+            .append(&[300, 200])
+            .scalar_index(ID_COLUMN, "id_idx")
+            .delete(1, &[0, 1, 2])
+            .append(&[50])
+            .vector_index("vec_idx", 2, 4)
+            .append(&[10]);
+        let dataset = fixture.build(&uri("fixture")).await.unwrap();
+
+        assert_eq!(dataset.version().version, 6);
+        let fragments = dataset.get_fragments();
+        let layout = fragments
+            .iter()
+            .map(|fragment| (fragment.id(), fragment.metadata().physical_rows))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            layout,
+            vec![(0, Some(300)), (1, Some(200)), (2, Some(50)), (3, Some(10))]
+        );
+        assert!(fragments[0].metadata().deletion_file.is_none());
+        let deletion_file = fragments[1].metadata().deletion_file.as_ref().unwrap();
+        assert_eq!(deletion_file.num_deleted_rows, Some(3));
+        assert_eq!(dataset.count_rows(None).await.unwrap(), 557);
+
+        let indices = dataset.load_indices().await.unwrap();
+        let coverage = |name: &str| {
+            let index = indices.iter().find(|index| index.name == name).unwrap();
+            index
+                .fragment_bitmap
+                .as_ref()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(coverage("id_idx"), vec![0, 1]);
+        assert_eq!(coverage("vec_idx"), vec![0, 1, 2]);
+
+        // Each step is a version
+        let first = dataset.checkout_version(1).await.unwrap();
+        assert_eq!(first.count_rows(None).await.unwrap(), 500);
+        assert!(first.load_indices().await.unwrap().is_empty());
+
+        // The same fixture builds the same data
+        let other = fixture.build(&uri("other")).await.unwrap();
+        let scan = |dataset: Dataset| async move {
+            dataset
+                .scan()
+                .try_into_stream()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap()
+        };
+        let batches = scan(dataset).await;
+        assert_eq!(batches, scan(other).await);
+        let ids = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column_by_name(ID_COLUMN)
+                    .unwrap()
+                    .as_primitive::<arrow_array::types::Int64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(&ids[298..301], &[298, 299, 303]);
+
+        // Steps that cannot be replayed are rejected
+        let result = DatasetFixture::new()
+            .append(&[10])
+            .delete(0, &[20])
+            .build(&uri("invalid"))
+            .await;
+        assert!(result.is_err());
+    }
+}
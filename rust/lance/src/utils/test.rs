@@ -233,6 +233,8 @@ impl TestDatasetGenerator {
             deletion_file: None,
             row_id_meta: None,
             physical_rows: Some(batch.num_rows()),
+            partition: None,
+            delta: false,
         }
     }
 }